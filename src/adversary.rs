@@ -0,0 +1,146 @@
+use crate::network::*;
+use crate::node::NodeId;
+use log::info;
+use std::collections::HashMap;
+
+/// What a man-in-the-middle does with the messages crossing a link
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LinkAttack {
+    // Read the messages crossing the link
+    Eavesdrop,
+    // Discard the messages crossing the link
+    Drop,
+    // Replace the value carried by the messages crossing the link
+    Tamper(Value),
+    // Forward the messages and forge a copy carrying another value
+    Inject(Value),
+    // Forward the messages and forge a copy as sent by another node
+    Impersonate(NodeId),
+}
+use LinkAttack::*;
+
+/// Protections of the links between nodes
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LinkSecurity {
+    // Receivers detect and discard forged or modified messages
+    pub authenticated: bool,
+    // The adversary can't read the content of the messages
+    pub encrypted: bool,
+}
+
+/// What the man-in-the-middle achieved during a run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MitmStats {
+    pub eavesdropped: usize,
+    pub dropped: usize,
+    pub tampered: usize,
+    pub injected: usize,
+    pub impersonated: usize,
+    // Forged or modified messages discarded thanks to authentication
+    pub rejected: usize,
+}
+
+/// Adversary controlling some links of the network
+#[derive(Default)]
+pub struct ManInTheMiddle {
+    // Attack performed on each compromised link (from, to)
+    links: HashMap<(NodeId, NodeId), LinkAttack>,
+    // Content of the messages read on compromised links
+    transcript: Vec<String>,
+    stats: MitmStats,
+}
+
+impl ManInTheMiddle {
+    pub fn new() -> Self {
+        ManInTheMiddle::default()
+    }
+
+    /// Compromise the link from `from` to `to`
+    pub fn compromise(&mut self, from: NodeId, to: NodeId, attack: LinkAttack) {
+        self.links.insert((from, to), attack);
+    }
+
+    /// Compromised links and their attack, ordered by link
+    pub fn links(&self) -> Vec<((NodeId, NodeId), LinkAttack)> {
+        let mut links: Vec<_> = self
+            .links
+            .iter()
+            .map(|(link, attack)| (*link, attack.clone()))
+            .collect();
+        links.sort_by_key(|(link, _)| *link);
+        links
+    }
+
+    pub fn stats(&self) -> &MitmStats {
+        &self.stats
+    }
+
+    pub fn transcript(&self) -> &[String] {
+        &self.transcript
+    }
+
+    /// Apply the attack of the link to a message in transit
+    /// Returns the messages that actually reach the destination
+    pub(crate) fn intercept(
+        &mut self,
+        msg: NetworkMessage,
+        security: &LinkSecurity,
+    ) -> Vec<NetworkMessage> {
+        let attack = match self.links.get(&(msg.from, msg.to)) {
+            Some(attack) => attack.clone(),
+            None => return vec![msg],
+        };
+
+        match attack {
+            Eavesdrop => {
+                if !security.encrypted {
+                    info!("MITM read {:?}", msg);
+                    self.transcript.push(format!("{:?}", msg));
+                    self.stats.eavesdropped += 1;
+                }
+                vec![msg]
+            }
+
+            Drop => {
+                self.stats.dropped += 1;
+                vec![]
+            }
+
+            Tamper(v) => {
+                if security.authenticated {
+                    // Receiver notices the modification and discards the message
+                    self.stats.rejected += 1;
+                    return vec![];
+                }
+                self.stats.tampered += 1;
+                // Still the same message for the network
+                let msg = NetworkMessage {
+                    msg: msg.msg.with_value(v),
+                    ..msg
+                };
+                vec![msg]
+            }
+
+            Inject(v) => {
+                if security.authenticated {
+                    // Forged copy is discarded, genuine message goes through
+                    self.stats.rejected += 1;
+                    return vec![msg];
+                }
+                self.stats.injected += 1;
+                let forged = NetworkMessage::new(msg.from, msg.to, msg.msg.with_value(v));
+                vec![msg, forged]
+            }
+
+            Impersonate(node) => {
+                if security.authenticated {
+                    self.stats.rejected += 1;
+                    return vec![msg];
+                }
+                self.stats.impersonated += 1;
+                let forged = NetworkMessage::new(node, msg.to, msg.msg.clone());
+                vec![msg, forged]
+            }
+        }
+    }
+}
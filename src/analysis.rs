@@ -0,0 +1,164 @@
+use crate::network::{Message, Network, Value};
+use crate::node::NodeId;
+use crate::trace::{Trace, TraceEvent};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::time::Duration;
+
+/// Delivery of a recorded run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PivotalMessage {
+    // Position of the delivery in the trace
+    pub index: usize,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub description: String,
+}
+
+/// Deliveries of a recorded run that decided the outcome of each node
+#[derive(Clone, Debug, Default)]
+pub struct PivotalReport {
+    // Outcome of the nodes in the recorded run
+    pub outcome: HashMap<NodeId, Value>,
+    // Deliveries without which the outcome of the node changes
+    pub pivotal: BTreeMap<NodeId, Vec<PivotalMessage>>,
+}
+
+impl PivotalReport {
+    pub fn pivotal_for(&self, id: NodeId) -> &[PivotalMessage] {
+        self.pivotal.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+}
+
+/// Replay `trace` once per delivery with this delivery removed, and compare
+/// the outcome of every node with the one of the full replay
+/// `run` has to start the protocol of the recorded run with the same inputs
+pub fn pivotal_messages<F>(trace: &Trace, run: F) -> PivotalReport
+where
+    F: Fn(&mut Network) -> HashMap<NodeId, Value>,
+{
+    let mut network = Network::replay(trace);
+    let outcome = run(&mut network);
+    network.close();
+
+    let mut pivotal: BTreeMap<NodeId, Vec<PivotalMessage>> = BTreeMap::new();
+    for (index, event) in trace.events.iter().enumerate() {
+        let mut network = Network::replay_without(trace, index);
+        let without = run(&mut network);
+        network.close();
+
+        for id in 0..trace.num_nodes {
+            if outcome.get(&id) != without.get(&id) {
+                pivotal.entry(id).or_default().push(PivotalMessage {
+                    index,
+                    from: event.from,
+                    to: event.to,
+                    description: format!("{:?}", event.msg),
+                });
+            }
+        }
+    }
+    PivotalReport { outcome, pivotal }
+}
+
+/// Where the delivery latency of a node went, along its critical path
+/// Going back from the delivery, the path follows the first message of the
+/// quorum each node acted on, to its sender and down to the input of the
+/// run: the latency splits into the time nodes waited for the rest of their
+/// quorum and the time the messages of the path spent in transit
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DeliveryBreakdown {
+    pub latency: Duration,
+    // Nodes held part of a quorum and waited for the stragglers, inherent
+    // to the protocol
+    pub quorum: Duration,
+    // Messages waited for the uplink of their sender
+    pub queuing: Duration,
+    // Messages were on the links
+    pub transit: Duration,
+    // Rest of the latency: nodes handling messages or with messages waiting
+    // in their inbox
+    pub processing: Duration,
+    // Messages on the path
+    pub hops: usize,
+}
+
+impl DeliveryBreakdown {
+    /// Part of the latency due to the network rather than the protocol
+    pub fn environment(&self) -> Duration {
+        self.queuing + self.transit
+    }
+}
+
+impl fmt::Display for DeliveryBreakdown {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} over {} hops: quorum {:?}, queuing {:?}, transit {:?}, processing {:?}",
+            self.latency, self.hops, self.quorum, self.queuing, self.transit, self.processing
+        )
+    }
+}
+
+/// Messages of the same step of the same instance
+fn same_step(a: &Message, b: &Message) -> bool {
+    match (a, b) {
+        (Message::PROTOCOL(a), Message::PROTOCOL(b)) => {
+            a.instance == b.instance && a.payload.phase() == b.payload.phase()
+        }
+        _ => false,
+    }
+}
+
+/// Breakdown of the delivery latency of every honest node of the recorded
+/// run, `delivered` being the delivery time of the nodes
+/// Times are those of the concurrent schedule, they mean nothing in
+/// lockstep schedules
+pub fn delivery_breakdown(
+    trace: &Trace,
+    delivered: &BTreeMap<NodeId, Duration>,
+) -> BTreeMap<NodeId, DeliveryBreakdown> {
+    let honest = trace.num_nodes - trace.num_malicious;
+    delivered
+        .iter()
+        .filter(|(id, _)| **id < honest)
+        .map(|(id, latency)| (*id, critical_path(trace, *id, *latency)))
+        .collect()
+}
+
+fn critical_path(trace: &Trace, id: NodeId, latency: Duration) -> DeliveryBreakdown {
+    let mut breakdown = DeliveryBreakdown {
+        latency,
+        ..DeliveryBreakdown::default()
+    };
+    let (mut node, mut at) = (id, latency);
+    // Every hop goes back in the trace, the bound only guards against
+    // deliveries recorded at the same time
+    for _ in 0..trace.len() {
+        let received = |event: &&TraceEvent| event.to == node && event.at <= at;
+        // Message the node acted on, and the first one of its quorum
+        let trigger = match trace.events.iter().rev().find(received) {
+            Some(trigger) => trigger,
+            None => break,
+        };
+        let first = trace
+            .events
+            .iter()
+            .filter(received)
+            .find(|event| same_step(&event.msg, &trigger.msg))
+            .unwrap_or(trigger);
+        breakdown.quorum += trigger.at - first.at;
+        // Inputs and timers of the node start the path
+        if first.from >= trace.num_nodes || first.from == node {
+            break;
+        }
+        breakdown.hops += 1;
+        breakdown.queuing += first.queued;
+        breakdown.transit += first.at.saturating_sub(first.sent + first.queued);
+        node = first.from;
+        at = first.sent;
+    }
+    breakdown.processing =
+        latency.saturating_sub(breakdown.quorum + breakdown.queuing + breakdown.transit);
+    breakdown
+}
@@ -0,0 +1,147 @@
+use crate::audit::AuditEntry;
+use crate::manifest::Manifest;
+use crate::monitor::Violation;
+use crate::node::{NodeId, NodePanic};
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Deliveries kept in the artifact of a node
+pub const LAST_EVENTS: usize = 100;
+
+/// File of an artifact directory telling why the run ended abnormally
+pub const SUMMARY_FILE: &str = "summary.txt";
+
+/// Why a run ended abnormally
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Abnormal {
+    // Protocol code of these nodes panicked
+    Panic(Vec<NodeId>),
+    // Number of violations of the monitored invariants
+    Violation(usize),
+    // Run was stopped with good nodes still running, by the time limit,
+    // an idle network or a lockstep run left without deliverable message
+    Timeout { cause: &'static str, running: usize },
+}
+
+impl fmt::Display for Abnormal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Abnormal::Panic(nodes) => write!(f, "panic of nodes {:?}", nodes),
+            Abnormal::Violation(count) => write!(f, "{} invariant violations", count),
+            Abnormal::Timeout { cause, running } => {
+                write!(
+                    f,
+                    "timeout ({}), {} good nodes still running",
+                    cause, running
+                )
+            }
+        }
+    }
+}
+
+/// What is left of a node at the end of an abnormal run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodeArtifact {
+    pub node: NodeId,
+    pub behaviour: String,
+    pub lifecycle: String,
+    // Audit entries of its protocol instances, None if the node panicked
+    // or was lost
+    pub state: Option<Vec<AuditEntry>>,
+    // Messages the network still held for the node
+    pub mailbox: Vec<String>,
+    // Last `LAST_EVENTS` deliveries to and from the node, oldest first
+    pub events: Vec<String>,
+}
+
+impl fmt::Display for NodeArtifact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "node {}: {}, {}",
+            self.node, self.behaviour, self.lifecycle
+        )?;
+        match &self.state {
+            Some(entries) => {
+                writeln!(f, "\n[state] {} entries", entries.len())?;
+                for entry in entries {
+                    writeln!(f, "{} {} = {}", entry.instance, entry.field, entry.value)?;
+                }
+            }
+            None => writeln!(f, "\n[state] unavailable")?,
+        }
+        writeln!(f, "\n[mailbox] {} messages", self.mailbox.len())?;
+        for msg in &self.mailbox {
+            writeln!(f, "{}", msg)?;
+        }
+        writeln!(f, "\n[events] last {}", self.events.len())?;
+        for event in &self.events {
+            writeln!(f, "{}", event)?;
+        }
+        Ok(())
+    }
+}
+
+/// Directory receiving the post-mortem of every abnormal run, one
+/// subdirectory per run with a summary and a file per node, so that a bug
+/// report holds what is needed without running again
+pub struct Artifacts {
+    dir: PathBuf,
+}
+
+impl Artifacts {
+    pub fn create(dir: &Path) -> Result<Self, String> {
+        fs::create_dir_all(dir).map_err(|e| format!("{}: {}", dir.display(), e))?;
+        Ok(Artifacts {
+            dir: dir.to_path_buf(),
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Directory of run number `run` in `dir`
+    pub fn run_dir(dir: &Path, run: usize) -> PathBuf {
+        dir.join(format!("run-{}", run))
+    }
+
+    /// File of node `id` in the directory of run number `run`
+    pub fn path(dir: &Path, run: usize, id: NodeId) -> PathBuf {
+        Artifacts::run_dir(dir, run).join(format!("node-{}.txt", id))
+    }
+
+    /// Write the post-mortem of run number `run`, returns its directory
+    pub(crate) fn write(
+        &self,
+        run: usize,
+        manifest: &Manifest,
+        reason: &Abnormal,
+        panics: &[NodePanic],
+        violations: &[Violation],
+        nodes: &[NodeArtifact],
+    ) -> Result<PathBuf, String> {
+        let error = |path: &Path, e: std::io::Error| format!("{}: {}", path.display(), e);
+        let dir = Artifacts::run_dir(&self.dir, run);
+        fs::create_dir_all(&dir).map_err(|e| error(&dir, e))?;
+
+        let mut summary = manifest.header("#");
+        writeln!(summary, "run {}: {}", run, reason).unwrap();
+        for report in panics {
+            writeln!(summary, "\n{}", report).unwrap();
+        }
+        for violation in violations {
+            writeln!(summary, "\n{}", violation).unwrap();
+        }
+        let path = dir.join(SUMMARY_FILE);
+        fs::write(&path, summary).map_err(|e| error(&path, e))?;
+
+        for artifact in nodes {
+            let path = Artifacts::path(&self.dir, run, artifact.node);
+            fs::write(&path, artifact.to_string()).map_err(|e| error(&path, e))?;
+        }
+        Ok(dir)
+    }
+}
@@ -0,0 +1,306 @@
+use crate::network::{Output, Value};
+use crate::node::NodeId;
+use crate::stats::Phase;
+use crate::trace::Trace;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+const PHASES: [Phase; 6] = [
+    Phase::Leader,
+    Phase::Init,
+    Phase::Echo,
+    Phase::Ready,
+    Phase::Vote,
+    Phase::Control,
+];
+
+/// Outputs of the nodes at some point of a run, malicious nodes come last
+struct RunState<'a> {
+    outputs: BTreeMap<NodeId, &'a Output>,
+    honest: usize,
+}
+
+impl RunState<'_> {
+    fn honest_outputs(&self) -> impl Iterator<Item = &Output> {
+        self.outputs.range(..self.honest).map(|(_, output)| *output)
+    }
+}
+
+/// Property of the outputs of the nodes at some point of a run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Condition {
+    // Every honest node output the value
+    AllHonestDelivered(Value),
+    // Two honest nodes output different values, giving up doesn't count
+    HonestDisagree,
+    // Node output the value
+    Delivered(NodeId, Value),
+}
+
+impl Condition {
+    fn holds(&self, state: &RunState) -> bool {
+        match self {
+            Condition::AllHonestDelivered(v) => {
+                let delivered = state
+                    .honest_outputs()
+                    .filter(|output| output.value() == Some(*v))
+                    .count();
+                delivered == state.honest
+            }
+            Condition::HonestDisagree => {
+                let mut outputs = state
+                    .honest_outputs()
+                    .filter(|output| **output != Output::Stalled);
+                match outputs.next() {
+                    Some(first) => outputs.any(|output| !output.agrees(first)),
+                    None => false,
+                }
+            }
+            Condition::Delivered(id, v) => {
+                state.outputs.get(id).and_then(|output| output.value()) == Some(*v)
+            }
+        }
+    }
+}
+
+pub fn all_honest_delivered(v: Value) -> Condition {
+    Condition::AllHonestDelivered(v)
+}
+
+pub fn two_honest_deliver_different() -> Condition {
+    Condition::HonestDisagree
+}
+
+pub fn delivered(id: NodeId, v: Value) -> Condition {
+    Condition::Delivered(id, v)
+}
+
+/// Deliveries counted by an assertion
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MessageFilter {
+    All,
+    OfType(Phase),
+    From(NodeId),
+    To(NodeId),
+}
+
+impl MessageFilter {
+    /// Number of deliveries of `trace` the filter takes
+    pub fn count(&self, trace: &Trace) -> usize {
+        trace
+            .events
+            .iter()
+            .filter(|event| match self {
+                MessageFilter::All => true,
+                MessageFilter::OfType(phase) => event.msg.phase() == *phase,
+                MessageFilter::From(id) => event.from == *id,
+                MessageFilter::To(id) => event.to == *id,
+            })
+            .count()
+    }
+}
+
+pub fn all_messages() -> MessageFilter {
+    MessageFilter::All
+}
+
+pub fn messages_of_type(phase: Phase) -> MessageFilter {
+    MessageFilter::OfType(phase)
+}
+
+pub fn messages_from(id: NodeId) -> MessageFilter {
+    MessageFilter::From(id)
+}
+
+pub fn messages_to(id: NodeId) -> MessageFilter {
+    MessageFilter::To(id)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    AtMost,
+    AtLeast,
+    Exactly,
+}
+
+/// Expectation on a whole run, checked against its trace once it is over
+/// Conditions are checked before the first output and after every output
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Assertion {
+    Eventually(Condition),
+    Never(Condition),
+    Always(Condition),
+    Count(MessageFilter, Comparison, usize),
+}
+
+pub fn eventually(condition: Condition) -> Assertion {
+    Assertion::Eventually(condition)
+}
+
+pub fn never(condition: Condition) -> Assertion {
+    Assertion::Never(condition)
+}
+
+pub fn always(condition: Condition) -> Assertion {
+    Assertion::Always(condition)
+}
+
+impl Assertion {
+    pub fn holds(&self, trace: &Trace) -> bool {
+        let honest = trace.num_nodes - trace.num_malicious;
+        let mut state = RunState {
+            outputs: BTreeMap::new(),
+            honest,
+        };
+        let mut states = vec![];
+        let condition = match self {
+            Assertion::Count(filter, comparison, k) => {
+                let count = filter.count(trace);
+                return match comparison {
+                    Comparison::AtMost => count <= *k,
+                    Comparison::AtLeast => count >= *k,
+                    Comparison::Exactly => count == *k,
+                };
+            }
+            Assertion::Eventually(condition)
+            | Assertion::Never(condition)
+            | Assertion::Always(condition) => condition,
+        };
+        states.push(condition.holds(&state));
+        for (_, id, output) in trace.outputs.iter() {
+            state.outputs.insert(*id, output);
+            states.push(condition.holds(&state));
+        }
+        match self {
+            Assertion::Eventually(_) => states.contains(&true),
+            Assertion::Never(_) => !states.contains(&true),
+            _ => !states.contains(&false),
+        }
+    }
+}
+
+/// Same syntax as `from_str`
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Condition::AllHonestDelivered(v) => write!(f, "all-honest-delivered {}", v),
+            Condition::HonestDisagree => write!(f, "honest-disagree"),
+            Condition::Delivered(id, v) => write!(f, "delivered {} {}", id, v),
+        }
+    }
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    /// `all-honest-delivered V`, `honest-disagree` or `delivered NODE V`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid condition: {}", s);
+        let words: Vec<&str> = s.split_whitespace().collect();
+        let number = |i: usize| {
+            words
+                .get(i)
+                .and_then(|w| w.parse().ok())
+                .ok_or_else(invalid)
+        };
+        match words.first() {
+            Some(&"all-honest-delivered") if words.len() == 2 => {
+                Ok(Condition::AllHonestDelivered(number(1)?))
+            }
+            Some(&"honest-disagree") if words.len() == 1 => Ok(Condition::HonestDisagree),
+            Some(&"delivered") if words.len() == 3 => {
+                Ok(Condition::Delivered(number(1)?, number(2)?))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for MessageFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MessageFilter::All => write!(f, "all"),
+            MessageFilter::OfType(phase) => write!(f, "{}", format!("{:?}", phase).to_lowercase()),
+            MessageFilter::From(id) => write!(f, "from:{}", id),
+            MessageFilter::To(id) => write!(f, "to:{}", id),
+        }
+    }
+}
+
+impl FromStr for MessageFilter {
+    type Err = String;
+
+    /// `all`, a phase like `ready`, `from:NODE` or `to:NODE`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let node = |id: &str| id.parse().map_err(|_| format!("Invalid node: {}", id));
+        if s == "all" {
+            return Ok(MessageFilter::All);
+        }
+        if let Some(id) = s.strip_prefix("from:") {
+            return node(id).map(MessageFilter::From);
+        }
+        if let Some(id) = s.strip_prefix("to:") {
+            return node(id).map(MessageFilter::To);
+        }
+        PHASES
+            .iter()
+            .find(|phase| format!("{:?}", phase).to_lowercase() == s)
+            .map(|phase| MessageFilter::OfType(*phase))
+            .ok_or_else(|| format!("Unknown messages: {}", s))
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Comparison::AtMost => write!(f, "<="),
+            Comparison::AtLeast => write!(f, ">="),
+            Comparison::Exactly => write!(f, "=="),
+        }
+    }
+}
+
+impl fmt::Display for Assertion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Assertion::Eventually(condition) => write!(f, "eventually {}", condition),
+            Assertion::Never(condition) => write!(f, "never {}", condition),
+            Assertion::Always(condition) => write!(f, "always {}", condition),
+            Assertion::Count(filter, comparison, k) => {
+                write!(f, "count {} {} {}", filter, comparison, k)
+            }
+        }
+    }
+}
+
+impl FromStr for Assertion {
+    type Err = String;
+
+    /// `eventually CONDITION`, `never CONDITION`, `always CONDITION` or
+    /// `count MESSAGES <= K`, with `>=` and `==` as well
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid assertion: {}", s);
+        let (quantifier, rest) = s.split_once(' ').ok_or_else(invalid)?;
+        match quantifier {
+            "eventually" => rest.parse().map(Assertion::Eventually),
+            "never" => rest.parse().map(Assertion::Never),
+            "always" => rest.parse().map(Assertion::Always),
+            "count" => {
+                let words: Vec<&str> = rest.split_whitespace().collect();
+                if words.len() != 3 {
+                    return Err(invalid());
+                }
+                let comparison = match words[1] {
+                    "<=" => Comparison::AtMost,
+                    ">=" => Comparison::AtLeast,
+                    "==" => Comparison::Exactly,
+                    _ => return Err(invalid()),
+                };
+                let k = words[2].parse().map_err(|_| invalid())?;
+                Ok(Assertion::Count(words[0].parse()?, comparison, k))
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use crate::node::NodeId;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Part of the final state of a node that every honest node should agree on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AuditEntry {
+    // Protocol instance keeping the state
+    pub instance: String,
+    pub field: &'static str,
+    pub value: String,
+}
+
+/// Entry on which honest nodes disagree
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub instance: String,
+    pub field: &'static str,
+    // Nodes holding each value
+    pub values: BTreeMap<String, Vec<NodeId>>,
+}
+
+/// Cross-check of the final states of the honest nodes after a run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AuditReport {
+    // Nodes whose state was collected
+    pub nodes: Vec<NodeId>,
+    // Number of entries compared
+    pub checked: usize,
+    pub divergences: Vec<Divergence>,
+}
+
+impl AuditReport {
+    /// Compare the entries of every node
+    /// An entry missing on some nodes is only compared among the others,
+    /// e.g. a node may deliver before having echoed anything
+    pub fn compare(states: &BTreeMap<NodeId, Vec<AuditEntry>>) -> Self {
+        let mut values: BTreeMap<(String, &'static str), BTreeMap<String, Vec<NodeId>>> =
+            BTreeMap::new();
+        for (id, entries) in states {
+            for entry in entries {
+                values
+                    .entry((entry.instance.clone(), entry.field))
+                    .or_default()
+                    .entry(entry.value.clone())
+                    .or_default()
+                    .push(*id);
+            }
+        }
+
+        let checked = values.len();
+        let divergences = values
+            .into_iter()
+            .filter(|(_, values)| values.len() > 1)
+            .map(|((instance, field), values)| Divergence {
+                instance,
+                field,
+                values,
+            })
+            .collect();
+        AuditReport {
+            nodes: states.keys().cloned().collect(),
+            checked,
+            divergences,
+        }
+    }
+
+    pub fn is_consistent(&self) -> bool {
+        self.divergences.is_empty()
+    }
+}
+
+impl fmt::Display for AuditReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Audit of {} nodes: {} entries, {} divergences",
+            self.nodes.len(),
+            self.checked,
+            self.divergences.len()
+        )?;
+        for divergence in &self.divergences {
+            write!(f, "  {} {}:", divergence.instance, divergence.field)?;
+            for (value, nodes) in &divergence.values {
+                write!(f, " {} on {:?}", value, nodes)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,400 @@
+use crate::manifest::Manifest;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Samples of the metrics of a benchmark, stored to detect regressions in
+/// later builds
+/// Every metric is better when lower: latency, messages, bytes...
+#[derive(Clone, Debug, PartialEq)]
+pub struct Baseline {
+    pub manifest: Manifest,
+    pub metrics: BTreeMap<String, Vec<f64>>,
+}
+
+/// Difference of a metric between a baseline and the current build
+#[derive(Clone, Debug, PartialEq)]
+pub struct Comparison {
+    pub metric: String,
+    pub baseline: f64,
+    pub current: f64,
+    // Welch's t statistic, positive when the current build is worse
+    pub t: f64,
+    // Difference unlikely to be noise, at the 95% level
+    pub significant: bool,
+}
+
+impl Comparison {
+    /// Relative change of the mean, 0.1 for 10% worse
+    pub fn change(&self) -> f64 {
+        if self.baseline == 0.0 {
+            return if self.current == 0.0 {
+                0.0
+            } else {
+                f64::INFINITY
+            };
+        }
+        (self.current - self.baseline) / self.baseline
+    }
+
+    pub fn is_regression(&self) -> bool {
+        self.significant && self.current > self.baseline
+    }
+}
+
+impl fmt::Display for Comparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:<12} {:>14.1} {:>14.1} {:>+8.1}% {:>8.2}{}",
+            self.metric,
+            self.baseline,
+            self.current,
+            self.change() * 100.0,
+            self.t,
+            if self.is_regression() {
+                "  REGRESSION"
+            } else if self.significant {
+                "  improvement"
+            } else {
+                ""
+            }
+        )
+    }
+}
+
+fn mean(samples: &[f64]) -> f64 {
+    samples.iter().sum::<f64>() / samples.len() as f64
+}
+
+fn variance(samples: &[f64]) -> f64 {
+    if samples.len() < 2 {
+        return 0.0;
+    }
+    let mean = mean(samples);
+    samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (samples.len() - 1) as f64
+}
+
+/// Two-sided critical value of Student's t distribution at the 95% level
+fn critical_t(df: f64) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    match df.floor() as usize {
+        0 => TABLE[0],
+        df if df <= TABLE.len() => TABLE[df - 1],
+        _ => 1.96,
+    }
+}
+
+impl Baseline {
+    pub fn new(manifest: Manifest) -> Self {
+        Baseline {
+            manifest,
+            metrics: BTreeMap::new(),
+        }
+    }
+
+    /// Add a sample of `metric`
+    pub fn record(&mut self, metric: &str, sample: f64) {
+        self.metrics
+            .entry(metric.to_string())
+            .or_default()
+            .push(sample);
+    }
+
+    /// Welch's t-test of every metric of `current` against this baseline
+    pub fn compare(&self, current: &Baseline) -> Vec<Comparison> {
+        let mut comparisons = vec![];
+        for (metric, before) in self.metrics.iter() {
+            let after = match current.metrics.get(metric) {
+                Some(after) if !after.is_empty() && !before.is_empty() => after,
+                _ => continue,
+            };
+            let (m1, m2) = (mean(before), mean(after));
+            let (s1, s2) = (
+                variance(before) / before.len() as f64,
+                variance(after) / after.len() as f64,
+            );
+            let (t, significant) = if s1 + s2 == 0.0 {
+                // No noise at all, any difference is real
+                let t = match m2 - m1 {
+                    d if d > 0.0 => f64::INFINITY,
+                    d if d < 0.0 => f64::NEG_INFINITY,
+                    _ => 0.0,
+                };
+                (t, m1 != m2)
+            } else {
+                let t = (m2 - m1) / (s1 + s2).sqrt();
+                // Welch-Satterthwaite degrees of freedom
+                let df = (s1 + s2).powi(2)
+                    / (s1.powi(2) / (before.len() as f64 - 1.0).max(1.0)
+                        + s2.powi(2) / (after.len() as f64 - 1.0).max(1.0));
+                (t, t.abs() > critical_t(df))
+            };
+            comparisons.push(Comparison {
+                metric: metric.clone(),
+                baseline: m1,
+                current: m2,
+                t,
+                significant,
+            });
+        }
+        comparisons
+    }
+
+    pub fn to_json(&self) -> String {
+        let manifest = &self.manifest;
+        let config: Vec<String> = manifest
+            .config
+            .iter()
+            .map(|(key, value)| format!("{}: {}", quote(key), quote(value)))
+            .collect();
+        let features: Vec<String> = manifest.features.iter().map(|f| quote(f)).collect();
+        let metrics: Vec<String> = self
+            .metrics
+            .iter()
+            .map(|(metric, samples)| {
+                let samples: Vec<String> = samples.iter().map(f64::to_string).collect();
+                format!("    {}: [{}]", quote(metric), samples.join(", "))
+            })
+            .collect();
+        format!(
+            "{{\n  \"manifest\": {{\n    \"crate_version\": {},\n    \"git_hash\": {},\n    \
+             \"features\": [{}],\n    \"seed\": {},\n    \"config\": {{{}}}\n  }},\n  \
+             \"metrics\": {{\n{}\n  }}\n}}\n",
+            quote(&manifest.crate_version),
+            quote(&manifest.git_hash),
+            features.join(", "),
+            manifest
+                .seed
+                .map_or(String::from("null"), |seed| seed.to_string()),
+            config.join(", "),
+            metrics.join(",\n")
+        )
+    }
+
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let mut parser = Parser {
+            text: text.as_bytes(),
+            at: 0,
+        };
+        let json = parser.value()?;
+        parser.skip_whitespace();
+        if parser.at != text.len() {
+            return Err(format!("Trailing characters at {}", parser.at));
+        }
+
+        let manifest = json.get("manifest")?;
+        let string = |json: &Json, key: &str| match json.get(key)? {
+            Json::Str(s) => Ok(s.clone()),
+            _ => Err(format!("{} is not a string", key)),
+        };
+        let mut baseline = Baseline::new(Manifest {
+            crate_version: string(manifest, "crate_version")?,
+            git_hash: string(manifest, "git_hash")?,
+            features: match manifest.get("features")? {
+                Json::Array(features) => features
+                    .iter()
+                    .map(|feature| match feature {
+                        Json::Str(feature) => Ok(feature.clone()),
+                        _ => Err(String::from("Feature is not a string")),
+                    })
+                    .collect::<Result<_, _>>()?,
+                _ => return Err(String::from("features is not an array")),
+            },
+            seed: match manifest.get("seed")? {
+                Json::Null => None,
+                Json::Number(seed) => Some(seed.parse().map_err(|_| "Invalid seed")?),
+                _ => return Err(String::from("seed is not a number")),
+            },
+            config: vec![],
+        });
+        match manifest.get("config")? {
+            Json::Object(entries) => {
+                for (key, value) in entries {
+                    match value {
+                        Json::Str(value) => baseline.manifest.set(key, value),
+                        _ => return Err(format!("{} is not a string", key)),
+                    }
+                }
+            }
+            _ => return Err(String::from("config is not an object")),
+        }
+
+        match json.get("metrics")? {
+            Json::Object(metrics) => {
+                for (metric, samples) in metrics {
+                    let samples = match samples {
+                        Json::Array(samples) => samples,
+                        _ => return Err(format!("{} is not an array", metric)),
+                    };
+                    for sample in samples {
+                        match sample {
+                            Json::Number(sample) => baseline
+                                .record(metric, sample.parse().map_err(|_| "Invalid sample")?),
+                            _ => return Err(format!("Sample of {} is not a number", metric)),
+                        }
+                    }
+                }
+            }
+            _ => return Err(String::from("metrics is not an object")),
+        }
+        Ok(baseline)
+    }
+}
+
+fn quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// The subset of JSON used by baselines
+#[derive(Debug)]
+enum Json {
+    Null,
+    // Kept as written, seeds don't fit in a f64
+    Number(String),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Result<&Json, String> {
+        match self {
+            Json::Object(entries) => entries
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, value)| value)
+                .ok_or_else(|| format!("Missing {}", key)),
+            _ => Err(format!("Not an object, looking for {}", key)),
+        }
+    }
+}
+
+struct Parser<'a> {
+    text: &'a [u8],
+    at: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.at < self.text.len() && self.text[self.at].is_ascii_whitespace() {
+            self.at += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.text.get(self.at).cloned()
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        match self.peek() {
+            Some(found) if found == c => {
+                self.at += 1;
+                Ok(())
+            }
+            _ => Err(format!("Expected '{}' at {}", c as char, self.at)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        match self.peek() {
+            Some(b'{') => {
+                self.at += 1;
+                let mut entries = vec![];
+                if self.peek() == Some(b'}') {
+                    self.at += 1;
+                    return Ok(Json::Object(entries));
+                }
+                loop {
+                    let key = self.string()?;
+                    self.expect(b':')?;
+                    entries.push((key, self.value()?));
+                    match self.peek() {
+                        Some(b',') => self.at += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b'}')?;
+                Ok(Json::Object(entries))
+            }
+            Some(b'[') => {
+                self.at += 1;
+                let mut values = vec![];
+                if self.peek() == Some(b']') {
+                    self.at += 1;
+                    return Ok(Json::Array(values));
+                }
+                loop {
+                    values.push(self.value()?);
+                    match self.peek() {
+                        Some(b',') => self.at += 1,
+                        _ => break,
+                    }
+                }
+                self.expect(b']')?;
+                Ok(Json::Array(values))
+            }
+            Some(b'"') => Ok(Json::Str(self.string()?)),
+            Some(b'n') if self.text[self.at..].starts_with(b"null") => {
+                self.at += 4;
+                Ok(Json::Null)
+            }
+            Some(_) => {
+                let start = self.at;
+                while self.at < self.text.len()
+                    && matches!(
+                        self.text[self.at],
+                        b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'
+                    )
+                {
+                    self.at += 1;
+                }
+                let number = std::str::from_utf8(&self.text[start..self.at]).unwrap();
+                match number.parse::<f64>() {
+                    Ok(_) => Ok(Json::Number(number.to_string())),
+                    Err(_) => Err(format!("Invalid value at {}", start)),
+                }
+            }
+            None => Err(String::from("Unexpected end of JSON")),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let mut s = vec![];
+        loop {
+            match self.text.get(self.at) {
+                Some(b'"') => break,
+                Some(b'\\') => {
+                    s.push(match self.text.get(self.at + 1) {
+                        Some(b'n') => b'\n',
+                        Some(c) => *c,
+                        None => return Err(String::from("Unterminated string")),
+                    });
+                    self.at += 2;
+                }
+                Some(c) => {
+                    s.push(*c);
+                    self.at += 1;
+                }
+                None => return Err(String::from("Unterminated string")),
+            }
+        }
+        self.at += 1;
+        String::from_utf8(s).map_err(|e| e.to_string())
+    }
+}
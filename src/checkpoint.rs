@@ -0,0 +1,208 @@
+use crate::manifest::Manifest;
+use log::{info, warn};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Result of one configuration of a campaign, as a line of a checkpoint
+/// file
+pub trait Checkpointed: Sized {
+    /// Single line without tabulations
+    fn encode(&self) -> String;
+
+    fn decode(line: &str) -> Result<Self, String>;
+}
+
+/// Progress of an experiment campaign: results of the configurations
+/// already run, by name
+/// Saved to its file as configurations complete, a campaign started again
+/// with the same file and parameters skips what was done
+///
+/// File is the manifest of the campaign as comment lines, then one line
+/// per configuration: its name, a tabulation and its result
+///
+/// Large campaigns are split between worker processes: every worker runs
+/// its shard of the configurations to a file of its own, the files are
+/// merged into the checkpoint of the whole campaign
+/// No control plane farms the workers out over the network, those of other
+/// machines are started and their files gathered by hand
+#[derive(Debug)]
+pub struct Checkpoint {
+    path: Option<PathBuf>,
+    manifest: Manifest,
+    completed: Vec<(String, String)>,
+    // Configurations completed between two saves
+    pub every: usize,
+    unsaved: usize,
+    // Index of the worker and number of workers, the configurations of the
+    // other workers are left to them
+    pub shard: Option<(usize, usize)>,
+}
+
+impl Checkpoint {
+    /// Progress kept in memory only
+    pub fn new(manifest: Manifest) -> Self {
+        Checkpoint {
+            path: None,
+            manifest,
+            completed: vec![],
+            every: 1,
+            unsaved: 0,
+            shard: None,
+        }
+    }
+
+    /// Progress saved to `path`, resumed from it if it exists
+    /// A file of a campaign with other parameters is refused
+    pub fn open(path: &Path, manifest: Manifest) -> Result<Self, String> {
+        let mut checkpoint = Checkpoint::new(manifest);
+        checkpoint.path = Some(path.to_path_buf());
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(_) => return Ok(checkpoint),
+        };
+
+        let saved = Manifest::parse(&text, "#")?;
+        if saved.seed != checkpoint.manifest.seed || saved.config != checkpoint.manifest.config {
+            return Err(format!(
+                "{} is the checkpoint of another campaign",
+                path.display()
+            ));
+        }
+        if saved.git_hash != checkpoint.manifest.git_hash {
+            warn!(
+                "Resuming a campaign of build {} with build {}",
+                saved.git_hash, checkpoint.manifest.git_hash
+            );
+        }
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            let (name, result) = line
+                .split_once('\t')
+                .ok_or_else(|| format!("Invalid checkpoint line: {}", line))?;
+            checkpoint
+                .completed
+                .push((name.to_string(), result.to_string()));
+        }
+        info!(
+            "Resuming from {}: {} configurations done",
+            path.display(),
+            checkpoint.completed.len()
+        );
+        Ok(checkpoint)
+    }
+
+    /// Number of configurations done
+    pub fn len(&self) -> usize {
+        self.completed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.completed.is_empty()
+    }
+
+    /// Result of the configuration `name` if it was run, a result that
+    /// can't be read is run again
+    pub fn get<T: Checkpointed>(&self, name: &str) -> Option<T> {
+        let (_, line) = self.completed.iter().find(|(n, _)| n == name)?;
+        match T::decode(line) {
+            Ok(result) => Some(result),
+            Err(e) => {
+                warn!("Running {} again, invalid checkpoint: {}", name, e);
+                None
+            }
+        }
+    }
+
+    /// Whether the configuration at `position` in the campaign is run here
+    /// rather than by another worker
+    pub fn assigned(&self, position: usize) -> bool {
+        self.shard
+            .is_none_or(|(index, count)| position % count == index)
+    }
+
+    /// Add the results of the worker that saved to `path`, returns how
+    /// many configurations it completed
+    /// Results already in the checkpoint are kept
+    pub fn merge(&mut self, path: &Path) -> Result<usize, String> {
+        if !path.exists() {
+            return Err(format!("{} does not exist", path.display()));
+        }
+        let worker = Checkpoint::open(path, self.manifest.clone())?;
+        for (name, result) in worker.completed.iter() {
+            if self.completed.iter().all(|(n, _)| n != name) {
+                self.completed.push((name.clone(), result.clone()));
+            }
+        }
+        self.save()?;
+        Ok(worker.len())
+    }
+
+    /// Configuration `name` is done, saved once `every` of them are
+    pub fn record<T: Checkpointed>(&mut self, name: &str, result: &T) -> Result<(), String> {
+        self.completed.retain(|(n, _)| n != name);
+        self.completed.push((name.to_string(), result.encode()));
+        self.unsaved += 1;
+        if self.unsaved >= self.every {
+            self.save()?;
+        }
+        Ok(())
+    }
+
+    /// Write the progress to the file, replaced at once so that an
+    /// interruption leaves the previous one
+    pub fn save(&mut self) -> Result<(), String> {
+        let path = match &self.path {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let mut text = self.manifest.header("#");
+        for (name, result) in self.completed.iter() {
+            text.push_str(&format!("{}\t{}\n", name, result));
+        }
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text).map_err(|e| format!("Could not write {}: {}", tmp.display(), e))?;
+        fs::rename(&tmp, path).map_err(|e| format!("Could not write {}: {}", path.display(), e))?;
+        self.unsaved = 0;
+        Ok(())
+    }
+}
+
+/// Counters of a configuration, separated by spaces
+impl<const N: usize> Checkpointed for [u64; N] {
+    fn encode(&self) -> String {
+        let fields: Vec<String> = self.iter().map(u64::to_string).collect();
+        fields.join(" ")
+    }
+
+    fn decode(line: &str) -> Result<Self, String> {
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .map(|field| {
+                field
+                    .parse()
+                    .map_err(|_| format!("Invalid field: {}", field))
+            })
+            .collect::<Result<_, _>>()?;
+        fields
+            .try_into()
+            .map_err(|fields: Vec<u64>| format!("{} fields instead of {}", fields.len(), N))
+    }
+}
+
+/// Counters of a configuration with as many as it needs, separated by
+/// spaces
+impl Checkpointed for Vec<u64> {
+    fn encode(&self) -> String {
+        let fields: Vec<String> = self.iter().map(u64::to_string).collect();
+        fields.join(" ")
+    }
+
+    fn decode(line: &str) -> Result<Self, String> {
+        line.split_whitespace()
+            .map(|field| {
+                field
+                    .parse()
+                    .map_err(|_| format!("Invalid field: {}", field))
+            })
+            .collect()
+    }
+}
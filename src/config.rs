@@ -0,0 +1,743 @@
+use crate::latency::Latency;
+use crate::node::{MaliciousKind, NodeId};
+use crate::protocols::ProtocolConfig;
+use crate::quorum::FaultModel;
+use crate::rng::{Rng, SeededRng};
+use crate::topology::Topology;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Conditions of the links between nodes
+/// Times only apply with the concurrent schedule
+#[derive(Clone, Debug, PartialEq)]
+pub struct NetworkConfig {
+    // Probability that a message is lost in transit
+    pub drop_rate: f64,
+    // Links losing their messages at another rate, from and to
+    pub link_drop_rates: BTreeMap<(NodeId, NodeId), f64>,
+    // Time a message spends in transit
+    pub latency: Latency,
+    // Extra transit time drawn uniformly in `[0, jitter)` for every message
+    pub jitter: Duration,
+    // Bytes per second of every link, a message takes `size / bandwidth`
+    // to be transmitted
+    pub bandwidth: Option<u64>,
+    // Cap on what every node sends, messages beyond it queue at the sender
+    pub uplink: Option<UplinkCap>,
+    // Messages every honest node takes from each of its peers, those beyond
+    // it are dropped on arrival without being handled
+    pub peer_limit: Option<PeerLimit>,
+    // Time nodes take to handle the messages they receive
+    pub cpu: Option<CpuModel>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            drop_rate: 0.0,
+            link_drop_rates: BTreeMap::new(),
+            latency: Latency::Constant(Duration::ZERO),
+            jitter: Duration::ZERO,
+            bandwidth: None,
+            uplink: None,
+            peer_limit: None,
+            cpu: None,
+        }
+    }
+}
+
+/// Typical network conditions
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Preset {
+    // Datacenter: sub-millisecond, gigabit links
+    Lan,
+    // Nodes spread over continents
+    Wan,
+    // Slow, unreliable links with a lot of variation
+    LossyMobile,
+    // Delays vary much more than they last, messages get heavily reordered
+    AdversarialAsync,
+}
+
+impl Preset {
+    pub const ALL: [Preset; 4] = [
+        Preset::Lan,
+        Preset::Wan,
+        Preset::LossyMobile,
+        Preset::AdversarialAsync,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Preset::Lan => "lan",
+            Preset::Wan => "wan",
+            Preset::LossyMobile => "lossy-mobile",
+            Preset::AdversarialAsync => "adversarial-async",
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Preset::ALL
+            .iter()
+            .find(|preset| preset.name() == s.to_lowercase())
+            .cloned()
+            .ok_or_else(|| format!("Unknown network preset: {}", s))
+    }
+}
+
+impl NetworkConfig {
+    /// Conditions of a typical network
+    pub fn preset(preset: Preset) -> Self {
+        let ms = Duration::from_millis;
+        match preset {
+            Preset::Lan => NetworkConfig {
+                drop_rate: 0.0,
+                link_drop_rates: BTreeMap::new(),
+                latency: Latency::Constant(Duration::from_micros(200)),
+                jitter: Duration::from_micros(50),
+                // 1 Gbit/s
+                bandwidth: Some(125_000_000),
+                uplink: None,
+                peer_limit: None,
+                cpu: None,
+            },
+            Preset::Wan => NetworkConfig {
+                drop_rate: 0.001,
+                link_drop_rates: BTreeMap::new(),
+                latency: Latency::Constant(ms(50)),
+                jitter: ms(10),
+                // 100 Mbit/s
+                bandwidth: Some(12_500_000),
+                uplink: None,
+                peer_limit: None,
+                cpu: None,
+            },
+            Preset::LossyMobile => NetworkConfig {
+                drop_rate: 0.05,
+                link_drop_rates: BTreeMap::new(),
+                latency: Latency::Constant(ms(80)),
+                jitter: ms(40),
+                // 10 Mbit/s
+                bandwidth: Some(1_250_000),
+                uplink: None,
+                peer_limit: None,
+                cpu: None,
+            },
+            Preset::AdversarialAsync => NetworkConfig {
+                drop_rate: 0.0,
+                link_drop_rates: BTreeMap::new(),
+                latency: Latency::Constant(ms(5)),
+                jitter: ms(100),
+                bandwidth: None,
+                uplink: None,
+                peer_limit: None,
+                cpu: None,
+            },
+        }
+    }
+
+    /// Links of `from` to every other node lose their messages at `rate`
+    pub fn lossy_links(mut self, from: NodeId, num_nodes: usize, rate: f64) -> Self {
+        for to in (0..num_nodes).filter(|to| *to != from) {
+            self.link_drop_rates.insert((from, to), rate);
+        }
+        self
+    }
+
+    /// Probability that a message from `from` to `to` is lost
+    pub fn drop_rate(&self, from: NodeId, to: NodeId) -> f64 {
+        self.link_drop_rates
+            .get(&(from, to))
+            .copied()
+            .unwrap_or(self.drop_rate)
+    }
+
+    /// Time a message of `bytes` bytes spends on the link from `from` to `to`
+    /// Draws from `rng` only when there is jitter
+    pub fn transit(&self, from: NodeId, to: NodeId, bytes: usize, rng: &mut dyn Rng) -> Duration {
+        let mut delay = self.latency.delay(from, to);
+        if self.jitter > Duration::ZERO {
+            delay += self.jitter.mul_f64(rng.gen_f64());
+        }
+        if let Some(bandwidth) = self.bandwidth.filter(|bandwidth| *bandwidth > 0) {
+            delay += Duration::from_secs_f64(bytes as f64 / bandwidth as f64);
+        }
+        delay
+    }
+}
+
+/// Processing time of the messages on the nodes: a node handles one
+/// message at a time, the next ones wait in its inbox
+/// Nodes run at their own speed, to model fast and slow machines
+#[derive(Clone, Debug, PartialEq)]
+pub struct CpuModel {
+    // Time a node of speed 1 takes to handle a message
+    pub per_message: Duration,
+    // Extra time per byte of the message
+    pub per_byte: Duration,
+    // Multiplier of the speed of a node, 2.0 handles messages twice as
+    // fast, nodes missing run at speed 1
+    pub speeds: BTreeMap<NodeId, f64>,
+}
+
+impl CpuModel {
+    /// Same cost on every node
+    pub fn uniform(per_message: Duration) -> Self {
+        CpuModel {
+            per_message,
+            per_byte: Duration::ZERO,
+            speeds: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_speed(mut self, node: NodeId, speed: f64) -> Self {
+        self.speeds.insert(node, speed);
+        self
+    }
+
+    pub fn speed(&self, node: NodeId) -> f64 {
+        self.speeds.get(&node).copied().unwrap_or(1.0)
+    }
+
+    /// Time `node` takes to handle a message of `bytes` bytes
+    pub fn cost(&self, node: NodeId, bytes: usize) -> Duration {
+        let cost = self.per_message + self.per_byte * bytes as u32;
+        match self.speed(node) {
+            speed if speed > 0.0 => cost.div_f64(speed),
+            _ => cost,
+        }
+    }
+}
+
+impl fmt::Display for CpuModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} per message", self.per_message)?;
+        if self.per_byte > Duration::ZERO {
+            write!(f, ", {:?} per byte", self.per_byte)?;
+        }
+        for (node, speed) in self.speeds.iter() {
+            write!(f, ", node {} x{}", node, speed)?;
+        }
+        Ok(())
+    }
+}
+
+/// Uplink of a node as a token bucket: it fills at `rate` bytes per second
+/// up to `burst` bytes and every message sent takes its size out of it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UplinkCap {
+    pub rate: u64,
+    pub burst: u64,
+}
+
+impl fmt::Display for UplinkCap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} B/s, burst {} B", self.rate, self.burst)
+    }
+}
+
+impl FromStr for UplinkCap {
+    type Err = String;
+
+    /// `RATE` or `RATE:BURST` in bytes, the burst defaults to one second
+    /// of traffic
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |n: &str| match n.parse::<u64>() {
+            Ok(n) if n > 0 => Ok(n),
+            _ => Err(format!("Invalid uplink cap: {}", s)),
+        };
+        match s.split_once(':') {
+            Some((rate, burst)) => Ok(UplinkCap {
+                rate: parse(rate)?,
+                burst: parse(burst)?,
+            }),
+            None => Ok(UplinkCap {
+                rate: parse(s)?,
+                burst: parse(s)?,
+            }),
+        }
+    }
+}
+
+/// Rate limit of an honest node on each of its peers, as a token bucket of
+/// messages: it fills at `rate` messages per second up to `burst`
+/// messages, a flooding peer is throttled without slowing down the others
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerLimit {
+    pub rate: u64,
+    pub burst: u64,
+}
+
+impl fmt::Display for PeerLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} msg/s per peer, burst {}", self.rate, self.burst)
+    }
+}
+
+impl FromStr for PeerLimit {
+    type Err = String;
+
+    /// `RATE` or `RATE:BURST` in messages, the burst defaults to one second
+    /// of messages
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let cap: UplinkCap = s
+            .parse()
+            .map_err(|_| format!("Invalid peer limit: {}", s))?;
+        Ok(PeerLimit {
+            rate: cap.rate,
+            burst: cap.burst,
+        })
+    }
+}
+
+/// Token bucket of the uplink of a node during a run
+#[derive(Clone, Debug)]
+pub(crate) struct TokenBucket {
+    cap: UplinkCap,
+    // Bytes in the bucket at `at`, the bucket starts full
+    tokens: f64,
+    // Time the last message queued leaves the node, later than now while
+    // messages are queued
+    at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(cap: UplinkCap, now: Instant) -> Self {
+        TokenBucket {
+            cap,
+            tokens: cap.burst as f64,
+            at: now,
+        }
+    }
+
+    /// Bucket of the messages a node takes from one of its peers
+    pub fn per_peer(limit: PeerLimit, now: Instant) -> Self {
+        let cap = UplinkCap {
+            rate: limit.rate,
+            burst: limit.burst,
+        };
+        TokenBucket::new(cap, now)
+    }
+
+    /// Take `amount` tokens at `at` if the bucket has them, nothing waits
+    /// for the bucket to refill
+    pub fn take(&mut self, at: Instant, amount: usize) -> bool {
+        let start = self.at.max(at);
+        let refill = self.cap.rate as f64 * (start - self.at).as_secs_f64();
+        self.tokens = (self.tokens + refill).min(self.cap.burst as f64);
+        self.at = start;
+        if self.tokens < amount as f64 {
+            return false;
+        }
+        self.tokens -= amount as f64;
+        true
+    }
+
+    /// Queue a message of `bytes` bytes sent at `now`, returns how long it
+    /// waits behind the messages queued before it for enough tokens
+    pub fn send(&mut self, now: Instant, bytes: usize) -> Duration {
+        let rate = self.cap.rate as f64;
+        let start = self.at.max(now);
+        let refill = rate * (start - self.at).as_secs_f64();
+        self.tokens = (self.tokens + refill).min(self.cap.burst as f64);
+        let missing = bytes as f64 - self.tokens;
+        if missing > 0.0 {
+            self.tokens = 0.0;
+            self.at = start + Duration::from_secs_f64(missing / rate);
+        } else {
+            self.tokens -= bytes as f64;
+            self.at = start;
+        }
+        self.at - now
+    }
+}
+
+/// What the network does when the protocol code of a node panics
+/// The node is crashed until the next run in both cases
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    // Other nodes go on without the panicked node
+    #[default]
+    Continue,
+    // Run is over, nodes' outputs so far are returned
+    Abort,
+}
+
+/// Which nodes are the malicious ones
+/// Leaders, kings and the links of sparse topologies go by id, malicious
+/// nodes with the highest ids leave the first leaders honest
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub enum Placement {
+    // Nodes with the highest ids
+    #[default]
+    Highest,
+    // Nodes drawn uniformly with this seed
+    Random(u64),
+    // These nodes, the first ones lie in hybrid models and the others crash
+    Explicit(Vec<NodeId>),
+    // Nodes of every region of the geographic latency in turn, the ones
+    // with the highest ids of their region first
+    Stratified,
+}
+
+impl Placement {
+    /// Malicious nodes among `num_nodes`, the regions of the nodes are read
+    /// off `latency`
+    pub fn malicious(
+        &self,
+        num_nodes: usize,
+        num_malicious: usize,
+        latency: &Latency,
+    ) -> Result<Vec<NodeId>, Diagnostic> {
+        match self {
+            Placement::Highest => {
+                Ok((num_nodes.saturating_sub(num_malicious)..num_nodes).collect())
+            }
+            Placement::Random(seed) => {
+                let mut rng = SeededRng::new(*seed, 0);
+                let mut nodes: Vec<NodeId> = (0..num_nodes).collect();
+                let mut malicious = vec![];
+                while malicious.len() < num_malicious && !nodes.is_empty() {
+                    malicious.push(nodes.swap_remove(rng.gen_range(nodes.len())));
+                }
+                Ok(malicious)
+            }
+            Placement::Explicit(nodes) => {
+                let mut distinct = nodes.clone();
+                distinct.sort_unstable();
+                distinct.dedup();
+                if let Some(id) = nodes.iter().find(|id| **id >= num_nodes) {
+                    Err(Diagnostic::new(
+                        "placement",
+                        format!("node {} is not one of the {} nodes", id, num_nodes),
+                        format!("use ids below {}", num_nodes),
+                    ))
+                } else if distinct.len() != num_malicious {
+                    Err(Diagnostic::new(
+                        "placement",
+                        format!(
+                            "{} distinct nodes placed for {} malicious nodes",
+                            distinct.len(),
+                            num_malicious
+                        ),
+                        String::from("list every malicious node once"),
+                    ))
+                } else {
+                    Ok(nodes.clone())
+                }
+            }
+            Placement::Stratified => {
+                let regions = match latency {
+                    Latency::Geo { regions, .. } if regions.len() >= num_nodes => regions,
+                    _ => {
+                        return Err(Diagnostic::new(
+                            "placement",
+                            String::from("nodes have no region to spread the malicious ones over"),
+                            String::from("use a geographic latency with a region for every node"),
+                        ))
+                    }
+                };
+                let mut strata: BTreeMap<usize, Vec<NodeId>> = BTreeMap::new();
+                for (id, region) in regions.iter().take(num_nodes).enumerate() {
+                    strata.entry(*region).or_default().push(id);
+                }
+                let mut malicious = vec![];
+                while malicious.len() < num_malicious.min(num_nodes) {
+                    for nodes in strata.values_mut() {
+                        if malicious.len() < num_malicious {
+                            malicious.extend(nodes.pop());
+                        }
+                    }
+                }
+                Ok(malicious)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Placement::Highest => write!(f, "highest"),
+            Placement::Random(seed) => write!(f, "random:{}", seed),
+            Placement::Explicit(nodes) => {
+                let nodes: Vec<String> = nodes.iter().map(NodeId::to_string).collect();
+                write!(f, "nodes:{}", nodes.join(","))
+            }
+            Placement::Stratified => write!(f, "stratified"),
+        }
+    }
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    /// `highest`, `random:SEED`, `nodes:ID,ID...` or `stratified`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid placement: {}", s);
+        match s.split_once(':') {
+            None if s == "highest" => Ok(Placement::Highest),
+            None if s == "stratified" => Ok(Placement::Stratified),
+            Some(("random", seed)) => seed.parse().map(Placement::Random).map_err(|_| invalid()),
+            Some(("nodes", nodes)) => nodes
+                .split(',')
+                .filter(|id| !id.is_empty())
+                .map(|id| id.parse().map_err(|_| invalid()))
+                .collect::<Result<Vec<NodeId>, String>>()
+                .map(Placement::Explicit),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Whole setup of a network, checked before the network is built
+#[derive(Clone, Debug, PartialEq)]
+pub struct Config {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kind: MaliciousKind,
+    pub fault_model: FaultModel,
+    pub placement: Placement,
+    pub network: NetworkConfig,
+    pub topology: Topology,
+    pub routing: bool,
+    pub protocols: ProtocolConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            num_nodes: 4,
+            num_malicious: 0,
+            kind: MaliciousKind::Silent,
+            fault_model: FaultModel::Byzantine,
+            placement: Placement::Highest,
+            network: NetworkConfig::default(),
+            topology: Topology::Complete,
+            routing: false,
+            protocols: ProtocolConfig::default(),
+        }
+    }
+}
+
+/// Inconsistency of a configuration, and how to fix it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    // Setting at fault
+    pub field: &'static str,
+    pub problem: String,
+    pub hint: String,
+}
+
+impl Diagnostic {
+    fn new(field: &'static str, problem: String, hint: String) -> Self {
+        Diagnostic {
+            field,
+            problem,
+            hint,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}, {}", self.field, self.problem, self.hint)
+    }
+}
+
+impl Config {
+    /// Every inconsistency of the setup, instead of a panic or a run that
+    /// can't succeed
+    pub fn validate(&self) -> Result<(), Vec<Diagnostic>> {
+        let mut diagnostics = vec![];
+        let (n, f) = (self.num_nodes, self.num_malicious);
+        if n == 0 {
+            diagnostics.push(Diagnostic::new(
+                "num_nodes",
+                String::from("network has no node"),
+                String::from("use at least 1 node"),
+            ));
+        }
+        // Quorums of n - f nodes only intersect in an honest node if n > 3f,
+        // or in a node at all if n > 2f when nodes only crash
+        let model = self.fault_model;
+        if f > model.max_faulty(n) && n > 0 {
+            diagnostics.push(Diagnostic::new(
+                "num_malicious",
+                format!(
+                    "{} nodes tolerate at most {} malicious nodes, not {}",
+                    n,
+                    model.max_faulty(n),
+                    f
+                ),
+                format!(
+                    "use at least {} nodes or fewer malicious nodes",
+                    model.min_nodes(f)
+                ),
+            ));
+        }
+        if f > 0 && !model.allows(&self.kind) {
+            diagnostics.push(Diagnostic::new(
+                "kind",
+                format!("{} nodes fail outside of the {} model", self.kind, model),
+                String::from("use silent or crashing nodes, or the byzantine model"),
+            ));
+        }
+        if let MaliciousKind::SendOmission(percent) | MaliciousKind::ReceiveOmission(percent) =
+            self.kind
+        {
+            if percent > 100 {
+                diagnostics.push(Diagnostic::new(
+                    "kind",
+                    format!("nodes can't omit {}% of the messages", percent),
+                    String::from("use a percentage up to 100"),
+                ));
+            }
+        }
+        if let Err(diagnostic) = self.placement.malicious(n, f, &self.network.latency) {
+            diagnostics.push(diagnostic);
+        }
+        self.validate_network(&mut diagnostics);
+        self.validate_topology(&mut diagnostics);
+        self.validate_timeouts(&mut diagnostics);
+        if diagnostics.is_empty() {
+            Ok(())
+        } else {
+            Err(diagnostics)
+        }
+    }
+
+    fn validate_network(&self, diagnostics: &mut Vec<Diagnostic>) {
+        let network = &self.network;
+        if !(0.0..=1.0).contains(&network.drop_rate) {
+            diagnostics.push(Diagnostic::new(
+                "network.drop_rate",
+                format!("{} is not a probability", network.drop_rate),
+                String::from("use a rate between 0 and 1"),
+            ));
+        }
+        for ((from, to), rate) in network.link_drop_rates.iter() {
+            if !(0.0..=1.0).contains(rate) {
+                diagnostics.push(Diagnostic::new(
+                    "network.link_drop_rates",
+                    format!(
+                        "{} on the link from {} to {} is not a probability",
+                        rate, from, to
+                    ),
+                    String::from("use a rate between 0 and 1"),
+                ));
+            }
+        }
+        if let Latency::Geo { regions, .. } = &network.latency {
+            if regions.len() < self.num_nodes {
+                diagnostics.push(Diagnostic::new(
+                    "network.latency",
+                    format!(
+                        "only {} of the {} nodes have a region, the others get no latency",
+                        regions.len(),
+                        self.num_nodes
+                    ),
+                    String::from("give every node a region"),
+                ));
+            }
+        }
+        if let Some(uplink) = network.uplink {
+            if uplink.rate == 0 || uplink.burst == 0 {
+                diagnostics.push(Diagnostic::new(
+                    "network.uplink",
+                    format!("nodes with an uplink of {} never send anything", uplink),
+                    String::from("use a rate and a burst of at least 1 byte"),
+                ));
+            }
+        }
+        if let Some(limit) = network.peer_limit {
+            if limit.rate == 0 || limit.burst == 0 {
+                diagnostics.push(Diagnostic::new(
+                    "network.peer_limit",
+                    format!("honest nodes limited to {} never hear from anyone", limit),
+                    String::from("use a rate and a burst of at least 1 message"),
+                ));
+            }
+        }
+    }
+
+    fn validate_topology(&self, diagnostics: &mut Vec<Diagnostic>) {
+        if self.topology == Topology::Grid(0) {
+            diagnostics.push(Diagnostic::new(
+                "topology",
+                String::from("grid has no column"),
+                String::from("use a width of at least 1"),
+            ));
+            return;
+        }
+        // Whichever nodes are malicious, the honest ones have to reach each
+        // other without them
+        let connectivity = self.topology.connectivity(self.num_nodes);
+        if self.num_malicious > 0 && connectivity <= self.num_malicious {
+            diagnostics.push(Diagnostic::new(
+                "topology",
+                format!(
+                    "removing {} of the {} nodes disconnects the {} topology, {} malicious nodes can cut honest nodes off",
+                    connectivity, self.num_nodes, self.topology, self.num_malicious
+                ),
+                String::from("use a denser topology or fewer malicious nodes"),
+            ));
+        }
+    }
+
+    fn validate_timeouts(&self, diagnostics: &mut Vec<Diagnostic>) {
+        if self.protocols.broadcast.catch_up == Some(Duration::ZERO) {
+            diagnostics.push(Diagnostic::new(
+                "protocols.broadcast.catch_up",
+                String::from("nodes lagging behind ask for the READY of the others without pause"),
+                String::from("use a period of the order of the latency"),
+            ));
+        }
+        if self.protocols.graded.timeout == Duration::ZERO {
+            diagnostics.push(Diagnostic::new(
+                "protocols.graded.timeout",
+                String::from("nodes grade what they have before any READY arrives"),
+                String::from("use a timeout longer than two hops of the slowest link"),
+            ));
+        }
+        let timeout = match self.protocols.broadcast.liveness_timeout {
+            Some(timeout) => timeout,
+            None => return,
+        };
+        if self.topology == Topology::Grid(0) {
+            return;
+        }
+        let n = self.num_nodes;
+        let links = (0..n).flat_map(|a| {
+            (0..n)
+                .filter(move |b| self.topology.linked(a, *b, n))
+                .map(move |b| self.network.latency.delay(a, b))
+        });
+        let fastest = match links.min() {
+            Some(fastest) => fastest,
+            None => return,
+        };
+        if timeout <= fastest {
+            diagnostics.push(Diagnostic::new(
+                "protocols.broadcast.liveness_timeout",
+                format!(
+                    "{:?} is shorter than the fastest link ({:?}), nodes give up before any message arrives",
+                    timeout, fastest
+                ),
+                String::from("use a timeout longer than the slowest link"),
+            ));
+        }
+    }
+}
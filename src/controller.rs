@@ -0,0 +1,259 @@
+use crate::network::NetworkMessage;
+use crate::node::NodeId;
+use crate::stats::Phase;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// What the adversary scheduling the messages knows of them
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Knowledge {
+    // Reads every message in flight, the adversary of the asynchronous model
+    #[default]
+    Omniscient,
+    // Only reads the messages its malicious nodes send or receive, it sees
+    // the others go by without their content
+    Local,
+}
+
+impl fmt::Display for Knowledge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Knowledge::Omniscient => write!(f, "omniscient"),
+            Knowledge::Local => write!(f, "local"),
+        }
+    }
+}
+
+/// What experiments see of a message in flight
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageView {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub phase: Phase,
+    pub description: String,
+    // Whether the adversary can read the message, the phase and the
+    // description of the others are Control and empty
+    pub known: bool,
+}
+
+impl MessageView {
+    pub(crate) fn new(msg: &NetworkMessage) -> Self {
+        MessageView {
+            from: msg.from,
+            to: msg.to,
+            phase: msg.msg.phase(),
+            description: format!("{:?}", msg.msg),
+            known: true,
+        }
+    }
+
+    /// Message out of sight of the adversary, only its link shows
+    pub(crate) fn hidden(msg: &NetworkMessage) -> Self {
+        MessageView {
+            from: msg.from,
+            to: msg.to,
+            phase: Phase::Control,
+            description: String::new(),
+            known: false,
+        }
+    }
+}
+
+type Predicate = Box<dyn Fn(&MessageView) -> bool + Send>;
+
+struct DropRule {
+    remaining: usize,
+    predicate: Predicate,
+}
+
+#[derive(Default)]
+struct Controls {
+    drops: Vec<DropRule>,
+    // Extra time spent in transit on a link
+    link_delays: HashMap<(NodeId, NodeId), Duration>,
+    // Messages to deliver before anything else, one per request
+    forced: VecDeque<Predicate>,
+    // Messages to deliver before the others for as long as there are some
+    prioritized: Vec<Predicate>,
+    knowledge: Knowledge,
+    // Nodes whose messages the adversary reads with local knowledge
+    malicious: BTreeSet<NodeId>,
+    dropped: usize,
+    delivered: usize,
+}
+
+impl Controls {
+    /// What the requests see of `msg`
+    fn view(&self, msg: &NetworkMessage) -> MessageView {
+        let known = self.knowledge == Knowledge::Omniscient
+            || self.malicious.contains(&msg.from)
+            || self.malicious.contains(&msg.to);
+        if known {
+            MessageView::new(msg)
+        } else {
+            MessageView::hidden(msg)
+        }
+    }
+}
+
+/// Handle on the network for hand-crafted attack scenarios
+/// It can be cloned and used from another thread while a run is going on,
+/// requests apply to the messages in flight from then on, in every run
+/// until they are fulfilled
+#[derive(Clone, Default)]
+pub struct NetworkController {
+    controls: Arc<Mutex<Controls>>,
+}
+
+impl NetworkController {
+    pub(crate) fn new() -> Self {
+        NetworkController::default()
+    }
+
+    /// Drop the next `k` messages sent by nodes matching `predicate`
+    pub fn drop_next<P>(&self, k: usize, predicate: P)
+    where
+        P: Fn(&MessageView) -> bool + Send + 'static,
+    {
+        self.controls.lock().unwrap().drops.push(DropRule {
+            remaining: k,
+            predicate: Box::new(predicate),
+        });
+    }
+
+    /// Add `delay` to the transit time of the messages from `from` to `to`
+    /// In lockstep schedules, messages of delayed links are only delivered
+    /// when no other message can be
+    pub fn delay_link(&self, from: NodeId, to: NodeId, delay: Duration) {
+        self.controls
+            .lock()
+            .unwrap()
+            .link_delays
+            .insert((from, to), delay);
+    }
+
+    pub fn restore_link(&self, from: NodeId, to: NodeId) {
+        self.controls
+            .lock()
+            .unwrap()
+            .link_delays
+            .remove(&(from, to));
+    }
+
+    /// Deliver the first buffered message matching `predicate` before any
+    /// other one, as soon as there is such a message
+    /// Only messages held by the network can be forced: messages waiting in
+    /// lockstep schedules or delayed in the concurrent one
+    pub fn force_deliver<P>(&self, predicate: P)
+    where
+        P: Fn(&MessageView) -> bool + Send + 'static,
+    {
+        self.controls
+            .lock()
+            .unwrap()
+            .forced
+            .push_back(Box::new(predicate));
+    }
+
+    /// Deliver buffered messages matching `predicate` before any other one,
+    /// in every run until cleared
+    /// Forced deliveries still come first
+    pub fn prioritize<P>(&self, predicate: P)
+    where
+        P: Fn(&MessageView) -> bool + Send + 'static,
+    {
+        self.controls
+            .lock()
+            .unwrap()
+            .prioritized
+            .push(Box::new(predicate));
+    }
+
+    /// Give the requests the knowledge of the adversary controlling the
+    /// `malicious` nodes, every message is known by default
+    /// Knowledge outlasts `clear`, it is a model rather than a request
+    pub fn set_knowledge(&self, knowledge: Knowledge, malicious: BTreeSet<NodeId>) {
+        let mut controls = self.controls.lock().unwrap();
+        controls.knowledge = knowledge;
+        controls.malicious = malicious;
+    }
+
+    pub fn knowledge(&self) -> Knowledge {
+        self.controls.lock().unwrap().knowledge
+    }
+
+    /// Remove every request
+    pub fn clear(&self) {
+        let mut controls = self.controls.lock().unwrap();
+        controls.drops.clear();
+        controls.link_delays.clear();
+        controls.forced.clear();
+        controls.prioritized.clear();
+    }
+
+    /// Number of messages dropped so far
+    pub fn dropped(&self) -> usize {
+        self.controls.lock().unwrap().dropped
+    }
+
+    /// Number of messages force-delivered so far
+    pub fn forced(&self) -> usize {
+        self.controls.lock().unwrap().delivered
+    }
+
+    /// Whether a drop request takes `msg`
+    pub(crate) fn should_drop(&self, msg: &NetworkMessage) -> bool {
+        let mut controls = self.controls.lock().unwrap();
+        if controls.drops.is_empty() {
+            return false;
+        }
+        let view = controls.view(msg);
+        let rule = controls
+            .drops
+            .iter_mut()
+            .find(|rule| rule.remaining > 0 && (rule.predicate)(&view));
+        match rule {
+            Some(rule) => {
+                rule.remaining -= 1;
+                controls.drops.retain(|rule| rule.remaining > 0);
+                controls.dropped += 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn link_delay(&self, from: NodeId, to: NodeId) -> Option<Duration> {
+        self.controls
+            .lock()
+            .unwrap()
+            .link_delays
+            .get(&(from, to))
+            .cloned()
+    }
+
+    /// Index in `buffered` of the message to deliver first, if a request
+    /// matches one
+    pub(crate) fn forced_index<'a, I>(&self, buffered: I) -> Option<usize>
+    where
+        I: Iterator<Item = &'a NetworkMessage>,
+    {
+        let mut controls = self.controls.lock().unwrap();
+        if controls.forced.is_empty() && controls.prioritized.is_empty() {
+            return None;
+        }
+        let views: Vec<MessageView> = buffered.map(|msg| controls.view(msg)).collect();
+        if let Some(predicate) = controls.forced.front() {
+            if let Some(index) = views.iter().position(predicate) {
+                controls.forced.pop_front();
+                controls.delivered += 1;
+                return Some(index);
+            }
+        }
+        views
+            .iter()
+            .position(|view| controls.prioritized.iter().any(|predicate| predicate(view)))
+    }
+}
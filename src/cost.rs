@@ -0,0 +1,197 @@
+use crate::protocols::ProtocolId;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Cryptographic operation of a node, counted by the protocols and by the
+/// network for secured links
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum CryptoOp {
+    Hash,
+    // Tag or check of a message on an authenticated link
+    Mac,
+    // Encryption or decryption of a message on an encrypted link
+    Cipher,
+    Sign,
+    Verify,
+    // Share of a threshold coin and combination of the shares
+    CoinShare,
+    CoinCombine,
+    // Threshold encryption of a value, share of its decryption and
+    // combination of the shares
+    Encrypt,
+    DecryptShare,
+    DecryptCombine,
+    // Share of a threshold signature and combination of the shares
+    SignShare,
+    SignCombine,
+    // Verifiable sharing of a secret, check of a share against the
+    // commitment and reconstruction of the secret
+    Deal,
+    ShareVerify,
+    Reconstruct,
+}
+
+impl CryptoOp {
+    pub const ALL: [CryptoOp; 15] = [
+        CryptoOp::Hash,
+        CryptoOp::Mac,
+        CryptoOp::Cipher,
+        CryptoOp::Sign,
+        CryptoOp::Verify,
+        CryptoOp::CoinShare,
+        CryptoOp::CoinCombine,
+        CryptoOp::Encrypt,
+        CryptoOp::DecryptShare,
+        CryptoOp::DecryptCombine,
+        CryptoOp::SignShare,
+        CryptoOp::SignCombine,
+        CryptoOp::Deal,
+        CryptoOp::ShareVerify,
+        CryptoOp::Reconstruct,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            CryptoOp::Hash => "hash",
+            CryptoOp::Mac => "mac",
+            CryptoOp::Cipher => "cipher",
+            CryptoOp::Sign => "sign",
+            CryptoOp::Verify => "verify",
+            CryptoOp::CoinShare => "coin_share",
+            CryptoOp::CoinCombine => "coin_combine",
+            CryptoOp::Encrypt => "encrypt",
+            CryptoOp::DecryptShare => "decrypt_share",
+            CryptoOp::DecryptCombine => "decrypt_combine",
+            CryptoOp::SignShare => "sign_share",
+            CryptoOp::SignCombine => "sign_combine",
+            CryptoOp::Deal => "deal",
+            CryptoOp::ShareVerify => "share_verify",
+            CryptoOp::Reconstruct => "reconstruct",
+        }
+    }
+}
+
+impl fmt::Display for CryptoOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Price of the resources a protocol uses, in arbitrary units such as
+/// microjoules: every message sent, every byte of it and every operation
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostModel {
+    pub per_message: f64,
+    pub per_byte: f64,
+    // Operations missing cost nothing
+    pub ops: BTreeMap<CryptoOp, f64>,
+}
+
+impl Default for CostModel {
+    /// Rough relative costs: asymmetric and threshold cryptography are
+    /// orders of magnitude dearer than hashing
+    fn default() -> Self {
+        CostModel {
+            per_message: 1.0,
+            per_byte: 0.01,
+            ops: BTreeMap::from([
+                (CryptoOp::Hash, 0.1),
+                (CryptoOp::Mac, 0.2),
+                (CryptoOp::Cipher, 0.5),
+                (CryptoOp::Sign, 20.0),
+                (CryptoOp::Verify, 50.0),
+                (CryptoOp::CoinShare, 100.0),
+                (CryptoOp::CoinCombine, 200.0),
+                (CryptoOp::Encrypt, 100.0),
+                (CryptoOp::DecryptShare, 100.0),
+                (CryptoOp::DecryptCombine, 200.0),
+                (CryptoOp::SignShare, 100.0),
+                (CryptoOp::SignCombine, 200.0),
+                (CryptoOp::Deal, 100.0),
+                (CryptoOp::ShareVerify, 50.0),
+                (CryptoOp::Reconstruct, 200.0),
+            ]),
+        }
+    }
+}
+
+impl CostModel {
+    pub fn op(&self, op: CryptoOp) -> f64 {
+        self.ops.get(&op).copied().unwrap_or(0.0)
+    }
+}
+
+impl fmt::Display for CostModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} per message, {} per byte",
+            self.per_message, self.per_byte
+        )?;
+        for (op, cost) in self.ops.iter() {
+            write!(f, ", {} {}", cost, op)?;
+        }
+        Ok(())
+    }
+}
+
+/// Resources used by the messages of a protocol during a run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProtocolCost {
+    pub messages: usize,
+    pub bytes: usize,
+    pub ops: BTreeMap<CryptoOp, usize>,
+}
+
+impl ProtocolCost {
+    pub fn total(&self, model: &CostModel) -> f64 {
+        let ops: f64 = self
+            .ops
+            .iter()
+            .map(|(op, k)| model.op(*op) * *k as f64)
+            .sum();
+        model.per_message * self.messages as f64 + model.per_byte * self.bytes as f64 + ops
+    }
+}
+
+/// Cost of a run, per protocol
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostReport {
+    pub model: CostModel,
+    pub protocols: BTreeMap<ProtocolId, ProtocolCost>,
+}
+
+impl CostReport {
+    pub fn cost(&self, protocol: ProtocolId) -> f64 {
+        self.protocols
+            .get(&protocol)
+            .map_or(0.0, |cost| cost.total(&self.model))
+    }
+
+    pub fn total(&self) -> f64 {
+        self.protocols
+            .values()
+            .map(|cost| cost.total(&self.model))
+            .sum()
+    }
+}
+
+impl fmt::Display for CostReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (protocol, cost) in self.protocols.iter() {
+            write!(
+                f,
+                "protocol {}: {:.1} for {} messages, {} bytes",
+                protocol,
+                cost.total(&self.model),
+                cost.messages,
+                cost.bytes
+            )?;
+            for (op, k) in cost.ops.iter() {
+                write!(f, ", {} {}", k, op)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,3 @@
+pub mod secret_sharing;
+pub mod threshold_enc;
+pub mod threshold_sig;
@@ -0,0 +1,223 @@
+use crate::network::Value;
+use crate::node::NodeId;
+use crate::rng::mix;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Share of a node of a secret, a point of the polynomial of the dealer
+pub type SecretShare = u64;
+
+/// Commitment of the dealer to the polynomial of a sharing, checked
+/// against by every share
+/// Stands for the commitments to the coefficients of the polynomial, as in
+/// Feldman's scheme, the coefficients themselves are kept out of reach of
+/// the nodes by the simulation
+#[derive(Clone, PartialEq, Eq)]
+pub struct SharingCommitment {
+    pub digest: u64,
+    coefficients: Vec<u64>,
+}
+
+impl SharingCommitment {
+    /// Bytes of the commitments to the coefficients
+    pub fn size(&self) -> usize {
+        self.coefficients.len() * std::mem::size_of::<u64>()
+    }
+}
+
+/// Coefficients stay out of the logs
+impl fmt::Debug for SharingCommitment {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Commitment({:x})", self.digest)
+    }
+}
+
+/// Verifiable secret sharing: a dealer splits a secret into one share per
+/// node and commits to the sharing, every node checks its share against
+/// the commitment, any `threshold` valid shares give the secret and
+/// fewer tell nothing about it
+pub trait VerifiableSecretSharing: fmt::Display + Send + Sync {
+    /// Shares needed to reconstruct
+    fn threshold(&self) -> usize;
+
+    /// Commitment and shares of `num_nodes` nodes of `secret`, the sharing
+    /// is drawn from `seed`
+    fn share(
+        &self,
+        secret: Value,
+        seed: u64,
+        num_nodes: usize,
+    ) -> (SharingCommitment, Vec<SecretShare>);
+
+    /// Whether `share` is the share of `node` of the sharing of `commitment`
+    fn verify_share(
+        &self,
+        commitment: &SharingCommitment,
+        node: NodeId,
+        share: SecretShare,
+    ) -> bool;
+
+    /// Secret of `commitment` from the valid ones of `shares`, None without
+    /// enough of them
+    fn reconstruct(
+        &self,
+        commitment: &SharingCommitment,
+        shares: &BTreeMap<NodeId, SecretShare>,
+    ) -> Option<Value>;
+}
+
+/// Prime of the field of the shares, secrets are below it
+const PRIME: u64 = (1 << 61) - 1;
+
+fn mul(a: u64, b: u64) -> u64 {
+    ((a as u128 * b as u128) % PRIME as u128) as u64
+}
+
+fn add(a: u64, b: u64) -> u64 {
+    (a + b) % PRIME
+}
+
+fn sub(a: u64, b: u64) -> u64 {
+    (a + PRIME - b) % PRIME
+}
+
+/// Inverse by Fermat's little theorem, a^(p - 2)
+fn inverse(a: u64) -> u64 {
+    let (mut base, mut exponent, mut result) = (a, PRIME - 2, 1);
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// Point of node `node` on the polynomial, nodes are 1-based so that 0 is
+/// the secret
+fn point(node: NodeId) -> u64 {
+    node as u64 + 1
+}
+
+fn evaluate(coefficients: &[u64], x: u64) -> u64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0, |y, coefficient| add(mul(y, x), *coefficient))
+}
+
+/// Shamir's sharing over the field of the Mersenne prime 2^61 - 1, the
+/// secret is the value at 0 of a polynomial of degree `threshold - 1`
+/// Shares and reconstruction are genuine, only the commitment is simulated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulatedSharing {
+    pub threshold: usize,
+}
+
+impl Default for SimulatedSharing {
+    fn default() -> Self {
+        SimulatedSharing { threshold: 1 }
+    }
+}
+
+impl VerifiableSecretSharing for SimulatedSharing {
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn share(
+        &self,
+        secret: Value,
+        seed: u64,
+        num_nodes: usize,
+    ) -> (SharingCommitment, Vec<SecretShare>) {
+        let mut coefficients = vec![secret as u64 % PRIME];
+        coefficients.extend((1..self.threshold as u64).map(|j| mix(seed ^ mix(j)) % PRIME));
+        let digest = coefficients
+            .iter()
+            .fold(mix(seed), |digest, coefficient| mix(digest ^ coefficient));
+        let shares = (0..num_nodes)
+            .map(|node| evaluate(&coefficients, point(node)))
+            .collect();
+        let commitment = SharingCommitment {
+            digest,
+            coefficients,
+        };
+        (commitment, shares)
+    }
+
+    fn verify_share(
+        &self,
+        commitment: &SharingCommitment,
+        node: NodeId,
+        share: SecretShare,
+    ) -> bool {
+        evaluate(&commitment.coefficients, point(node)) == share
+    }
+
+    /// Lagrange interpolation at 0 of the first `threshold` valid shares
+    fn reconstruct(
+        &self,
+        commitment: &SharingCommitment,
+        shares: &BTreeMap<NodeId, SecretShare>,
+    ) -> Option<Value> {
+        let valid: Vec<(u64, u64)> = shares
+            .iter()
+            .filter(|(node, share)| self.verify_share(commitment, **node, **share))
+            .map(|(node, share)| (point(*node), *share))
+            .take(self.threshold)
+            .collect();
+        if valid.len() < self.threshold {
+            return None;
+        }
+        let secret = valid.iter().fold(0, |secret, (x, y)| {
+            let basis = valid
+                .iter()
+                .filter(|(other, _)| other != x)
+                .fold(1, |basis, (other, _)| {
+                    mul(basis, mul(*other, inverse(sub(*other, *x))))
+                });
+            add(secret, mul(*y, basis))
+        });
+        Some(secret as Value)
+    }
+}
+
+impl fmt::Display for SimulatedSharing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "simulated verifiable secret sharing, threshold {}",
+            self.threshold
+        )
+    }
+}
+
+/// Secret sharing scheme of the nodes, set when the network is created
+#[derive(Clone)]
+pub struct SharingConfig {
+    pub scheme: Arc<dyn VerifiableSecretSharing>,
+}
+
+impl Default for SharingConfig {
+    fn default() -> Self {
+        SharingConfig {
+            scheme: Arc::new(SimulatedSharing::default()),
+        }
+    }
+}
+
+impl fmt::Debug for SharingConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.scheme)
+    }
+}
+
+/// Schemes are told apart by their description
+impl PartialEq for SharingConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.scheme.to_string() == other.scheme.to_string()
+    }
+}
@@ -0,0 +1,167 @@
+use crate::network::Value;
+use crate::node::NodeId;
+use crate::rng::mix;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Value encrypted under the key of the nodes
+/// The label, such as the epoch and the proposer of a transaction, is
+/// bound to the ciphertext: shares for one label don't decrypt another
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Ciphertext {
+    pub label: u64,
+    pub body: u64,
+}
+
+/// Share of a node for decrypting a ciphertext
+pub type DecryptionShare = u64;
+
+/// Secret part of the key held by a node, dealt when the network is
+/// created
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct KeyShare {
+    pub node: NodeId,
+    secret: u64,
+}
+
+/// Secret stays out of the logs
+impl fmt::Debug for KeyShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyShare({})", self.node)
+    }
+}
+
+/// Threshold encryption: anyone encrypts under the key of the nodes, any
+/// `threshold` valid decryption shares of a ciphertext give its value and
+/// fewer tell nothing about it
+pub trait ThresholdEncryption: fmt::Display + Send + Sync {
+    /// Decryption shares needed to decrypt
+    fn threshold(&self) -> usize;
+
+    fn encrypt(&self, label: u64, v: Value) -> Ciphertext;
+
+    /// Share of the holder of `key` for decrypting `ciphertext`
+    fn decryption_share(&self, key: &KeyShare, ciphertext: &Ciphertext) -> DecryptionShare;
+
+    /// Whether `share` is the share of `node` for `ciphertext`
+    fn verify_share(&self, node: NodeId, ciphertext: &Ciphertext, share: DecryptionShare) -> bool;
+
+    /// Value of `ciphertext` from valid shares, None without enough of them
+    fn combine(
+        &self,
+        ciphertext: &Ciphertext,
+        shares: &BTreeMap<NodeId, DecryptionShare>,
+    ) -> Option<Value>;
+}
+
+/// Simulated scheme: a dealer trusted by every node derives the key and
+/// the key shares from its seed
+/// Bodies are the values under a pad of the label, nothing keeps the
+/// nodes from computing it without the shares, the simulation just
+/// doesn't let them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulatedThreshold {
+    pub seed: u64,
+    pub threshold: usize,
+}
+
+impl SimulatedThreshold {
+    /// Scheme and key shares of `num_nodes` nodes, any `threshold` of them
+    /// decrypt
+    pub fn deal(seed: u64, num_nodes: usize, threshold: usize) -> (Self, Vec<KeyShare>) {
+        let scheme = SimulatedThreshold { seed, threshold };
+        let keys = (0..num_nodes).map(|node| scheme.key_share(node)).collect();
+        (scheme, keys)
+    }
+
+    fn key_share(&self, node: NodeId) -> KeyShare {
+        KeyShare {
+            node,
+            secret: mix(mix(self.seed) ^ mix(node as u64)),
+        }
+    }
+
+    fn pad(&self, label: u64) -> u64 {
+        mix(!mix(self.seed) ^ label)
+    }
+}
+
+impl Default for SimulatedThreshold {
+    fn default() -> Self {
+        SimulatedThreshold {
+            seed: 0,
+            threshold: 1,
+        }
+    }
+}
+
+impl ThresholdEncryption for SimulatedThreshold {
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn encrypt(&self, label: u64, v: Value) -> Ciphertext {
+        Ciphertext {
+            label,
+            body: v as u64 ^ self.pad(label),
+        }
+    }
+
+    fn decryption_share(&self, key: &KeyShare, ciphertext: &Ciphertext) -> DecryptionShare {
+        mix(key.secret ^ mix(ciphertext.label) ^ ciphertext.body)
+    }
+
+    fn verify_share(&self, node: NodeId, ciphertext: &Ciphertext, share: DecryptionShare) -> bool {
+        self.decryption_share(&self.key_share(node), ciphertext) == share
+    }
+
+    fn combine(
+        &self,
+        ciphertext: &Ciphertext,
+        shares: &BTreeMap<NodeId, DecryptionShare>,
+    ) -> Option<Value> {
+        if shares.len() < self.threshold {
+            return None;
+        }
+        Some((ciphertext.body ^ self.pad(ciphertext.label)) as Value)
+    }
+}
+
+impl fmt::Display for SimulatedThreshold {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "simulated threshold encryption, seed {}, threshold {}",
+            self.seed, self.threshold
+        )
+    }
+}
+
+/// Public part of the key of the nodes, set when the network deals the key
+/// shares
+#[derive(Clone)]
+pub struct EncryptionConfig {
+    pub scheme: Arc<dyn ThresholdEncryption>,
+}
+
+impl Default for EncryptionConfig {
+    fn default() -> Self {
+        EncryptionConfig {
+            scheme: Arc::new(SimulatedThreshold::default()),
+        }
+    }
+}
+
+impl fmt::Debug for EncryptionConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.scheme)
+    }
+}
+
+/// Schemes are told apart by their description
+impl PartialEq for EncryptionConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.scheme.to_string() == other.scheme.to_string()
+    }
+}
@@ -0,0 +1,159 @@
+use crate::node::NodeId;
+use crate::rng::mix;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Share of a node of the signature of a message
+pub type SignatureShare = u64;
+
+/// Signature of the nodes, the same whichever shares it was combined from
+pub type Signature = u64;
+
+/// Secret part of the signing key held by a node, dealt when the network
+/// is created
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct SigningKey {
+    pub node: NodeId,
+    secret: u64,
+}
+
+/// Secret stays out of the logs
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SigningKey({})", self.node)
+    }
+}
+
+/// Threshold signatures, such as BLS: any `threshold` valid shares of the
+/// signature of a message give the signature and fewer tell nothing
+/// about it
+/// Signatures are unique, the base of threshold coins, and checked by
+/// anyone, the base of quorum certificates
+pub trait ThresholdSignature: fmt::Display + Send + Sync {
+    /// Signature shares needed to sign
+    fn threshold(&self) -> usize;
+
+    /// Share of the holder of `key` of the signature of `message`
+    fn sign_share(&self, key: &SigningKey, message: u64) -> SignatureShare;
+
+    /// Whether `share` is the share of `node` of the signature of `message`
+    fn verify_share(&self, node: NodeId, message: u64, share: SignatureShare) -> bool;
+
+    /// Signature of `message` from valid shares, None without enough of them
+    fn combine(&self, message: u64, shares: &BTreeMap<NodeId, SignatureShare>)
+        -> Option<Signature>;
+
+    /// Whether `signature` is the signature of the nodes of `message`
+    fn verify(&self, message: u64, signature: Signature) -> bool;
+}
+
+/// Mock scheme for fast runs: a dealer trusted by every node derives the
+/// key and the key shares from its seed
+/// Signatures are a hash of the message under the seed, nothing keeps the
+/// nodes from computing them without the shares, the simulation just
+/// doesn't let them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SimulatedSignatures {
+    pub seed: u64,
+    pub threshold: usize,
+}
+
+impl SimulatedSignatures {
+    /// Scheme and signing keys of `num_nodes` nodes, any `threshold` of
+    /// them sign
+    pub fn deal(seed: u64, num_nodes: usize, threshold: usize) -> (Self, Vec<SigningKey>) {
+        let scheme = SimulatedSignatures { seed, threshold };
+        let keys = (0..num_nodes)
+            .map(|node| scheme.signing_key(node))
+            .collect();
+        (scheme, keys)
+    }
+
+    fn signing_key(&self, node: NodeId) -> SigningKey {
+        SigningKey {
+            node,
+            secret: mix(mix(self.seed).rotate_left(32) ^ mix(node as u64)),
+        }
+    }
+
+    fn signature(&self, message: u64) -> Signature {
+        mix(mix(self.seed).rotate_left(16) ^ mix(message))
+    }
+}
+
+impl Default for SimulatedSignatures {
+    fn default() -> Self {
+        SimulatedSignatures {
+            seed: 0,
+            threshold: 1,
+        }
+    }
+}
+
+impl ThresholdSignature for SimulatedSignatures {
+    fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    fn sign_share(&self, key: &SigningKey, message: u64) -> SignatureShare {
+        mix(key.secret ^ mix(message))
+    }
+
+    fn verify_share(&self, node: NodeId, message: u64, share: SignatureShare) -> bool {
+        self.sign_share(&self.signing_key(node), message) == share
+    }
+
+    fn combine(
+        &self,
+        message: u64,
+        shares: &BTreeMap<NodeId, SignatureShare>,
+    ) -> Option<Signature> {
+        if shares.len() < self.threshold {
+            return None;
+        }
+        Some(self.signature(message))
+    }
+
+    fn verify(&self, message: u64, signature: Signature) -> bool {
+        self.signature(message) == signature
+    }
+}
+
+impl fmt::Display for SimulatedSignatures {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "simulated threshold signatures, seed {}, threshold {}",
+            self.seed, self.threshold
+        )
+    }
+}
+
+/// Public part of the signing key of the nodes, set when the network deals
+/// the signing keys
+#[derive(Clone)]
+pub struct SignatureConfig {
+    pub scheme: Arc<dyn ThresholdSignature>,
+}
+
+impl Default for SignatureConfig {
+    fn default() -> Self {
+        SignatureConfig {
+            scheme: Arc::new(SimulatedSignatures::default()),
+        }
+    }
+}
+
+impl fmt::Debug for SignatureConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.scheme)
+    }
+}
+
+/// Schemes are told apart by their description
+impl PartialEq for SignatureConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.scheme.to_string() == other.scheme.to_string()
+    }
+}
@@ -0,0 +1,210 @@
+use crate::logs::{NodeLogs, INDEX_FILE};
+use crate::manifest::Manifest;
+use crate::node::NodeId;
+use std::collections::{btree_map::Entry, BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Runs recorded in a directory of node logs
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recording {
+    pub manifest: Manifest,
+    // Events of every node in the order the network saw them, without
+    // their time
+    pub events: Vec<(NodeId, String)>,
+    // Output of the nodes, by run and node
+    pub outputs: BTreeMap<(usize, NodeId), String>,
+    // Messages per phase, by run and phase
+    pub messages: BTreeMap<(usize, String), usize>,
+}
+
+impl Recording {
+    /// Read the logs written to `dir` by `Network::log_nodes`
+    pub fn load(dir: &Path) -> Result<Self, String> {
+        let read = |path: &Path| {
+            fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))
+        };
+        let index = read(&dir.join(INDEX_FILE))?;
+        let manifest = Manifest::parse(&index, "#")?;
+
+        let mut files: BTreeMap<NodeId, Vec<String>> = BTreeMap::new();
+        let mut recording = Recording {
+            manifest,
+            events: vec![],
+            outputs: BTreeMap::new(),
+            messages: BTreeMap::new(),
+        };
+        // Run each node is in
+        let mut runs: BTreeMap<NodeId, usize> = BTreeMap::new();
+        for entry in index.lines().filter(|line| !line.starts_with('#')) {
+            let invalid = || format!("Invalid index entry: {}", entry);
+            let fields: Vec<&str> = entry.split(' ').collect();
+            if fields.len() != 3 {
+                return Err(invalid());
+            }
+            let id: NodeId = fields[1].parse().map_err(|_| invalid())?;
+            let line: usize = fields[2].parse().map_err(|_| invalid())?;
+            if let Entry::Vacant(file) = files.entry(id) {
+                let text = read(&NodeLogs::path(dir, id))?;
+                file.insert(text.lines().map(String::from).collect());
+            }
+            let event = files[&id]
+                .get(line.wrapping_sub(1))
+                .and_then(|line| line.split_once(' '))
+                .map(|(_, event)| event.to_string())
+                .ok_or_else(invalid)?;
+
+            if let Some(run) = event.strip_prefix("run ") {
+                runs.insert(id, run.parse().map_err(|_| invalid())?);
+            } else if let Some(output) = event.strip_prefix("output ") {
+                let run = runs.get(&id).cloned().unwrap_or_default();
+                recording.outputs.insert((run, id), output.to_string());
+            }
+            recording.events.push((id, event));
+        }
+
+        let runs: BTreeSet<usize> = runs.values().cloned().collect();
+        for run in runs {
+            let path = NodeLogs::stats_path(dir, run);
+            if !path.exists() {
+                continue;
+            }
+            for line in read(&path)?
+                .lines()
+                .filter(|line| !line.starts_with('#'))
+                .skip(1)
+            {
+                let fields: Vec<&str> = line.split(',').collect();
+                match (fields.first(), fields.get(1).map(|n| n.parse())) {
+                    (Some(phase), Some(Ok(messages))) => {
+                        recording
+                            .messages
+                            .insert((run, phase.to_string()), messages);
+                    }
+                    _ => return Err(format!("Invalid stats line: {}", line)),
+                }
+            }
+        }
+        Ok(recording)
+    }
+}
+
+/// Value of an entry in both recordings, None when missing from one
+type Change<T> = (Option<T>, Option<T>);
+
+/// Where two recordings differ
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunDiff {
+    // Build and configuration entries, seed included
+    pub config: BTreeMap<String, Change<String>>,
+    pub outputs: BTreeMap<(usize, NodeId), Change<String>>,
+    pub messages: BTreeMap<(usize, String), Change<usize>>,
+    // Position of the first event that differs, with the event of each
+    // recording
+    pub first_divergence: Option<(usize, Change<(NodeId, String)>)>,
+}
+
+/// Entries of `a` and `b` whose values differ
+fn changes<K: Ord + Clone, V: PartialEq + Clone>(
+    a: &BTreeMap<K, V>,
+    b: &BTreeMap<K, V>,
+) -> BTreeMap<K, Change<V>> {
+    let keys: BTreeSet<&K> = a.keys().chain(b.keys()).collect();
+    keys.into_iter()
+        .filter(|key| a.get(key) != b.get(key))
+        .map(|key| (key.clone(), (a.get(key).cloned(), b.get(key).cloned())))
+        .collect()
+}
+
+fn config(manifest: &Manifest) -> BTreeMap<String, String> {
+    let mut config: BTreeMap<String, String> = manifest.config.iter().cloned().collect();
+    config.insert(
+        String::from("crate_version"),
+        manifest.crate_version.clone(),
+    );
+    config.insert(String::from("git_hash"), manifest.git_hash.clone());
+    config.insert(String::from("features"), manifest.features.join(","));
+    if let Some(seed) = manifest.seed {
+        config.insert(String::from("seed"), seed.to_string());
+    }
+    config
+}
+
+impl RunDiff {
+    pub fn new(a: &Recording, b: &Recording) -> Self {
+        let first_divergence = (0..a.events.len().max(b.events.len()))
+            .find(|i| a.events.get(*i) != b.events.get(*i))
+            .map(|i| (i, (a.events.get(i).cloned(), b.events.get(i).cloned())));
+        RunDiff {
+            config: changes(&config(&a.manifest), &config(&b.manifest)),
+            outputs: changes(&a.outputs, &b.outputs),
+            messages: changes(&a.messages, &b.messages),
+            first_divergence,
+        }
+    }
+
+    /// Both recordings saw the same events, whatever their configuration
+    pub fn same_runs(&self) -> bool {
+        self.first_divergence.is_none()
+    }
+}
+
+fn show<T: fmt::Display>(value: &Option<T>) -> String {
+    value
+        .as_ref()
+        .map_or(String::from("-"), |value| value.to_string())
+}
+
+impl fmt::Display for RunDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.config.is_empty() {
+            writeln!(f, "Same configuration")?;
+        }
+        for (key, (a, b)) in self.config.iter() {
+            writeln!(f, "config {}: {} | {}", key, show(a), show(b))?;
+        }
+        if self.outputs.is_empty() {
+            writeln!(f, "Same outputs")?;
+        }
+        for ((run, node), (a, b)) in self.outputs.iter() {
+            writeln!(
+                f,
+                "run {} node {} output: {} | {}",
+                run,
+                node,
+                show(a),
+                show(b)
+            )?;
+        }
+        if self.messages.is_empty() {
+            writeln!(f, "Same messages per phase")?;
+        }
+        for ((run, phase), (a, b)) in self.messages.iter() {
+            writeln!(
+                f,
+                "run {} {} messages: {} | {}",
+                run,
+                phase,
+                show(a),
+                show(b)
+            )?;
+        }
+        match &self.first_divergence {
+            None => write!(f, "Same events"),
+            Some((i, (a, b))) => {
+                let event = |event: &Option<(NodeId, String)>| match event {
+                    Some((node, event)) => format!("node {} {}", node, event),
+                    None => String::from("end of the recording"),
+                };
+                write!(
+                    f,
+                    "First difference at event {}: {} | {}",
+                    i,
+                    event(a),
+                    event(b)
+                )
+            }
+        }
+    }
+}
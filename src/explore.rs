@@ -0,0 +1,2243 @@
+use crate::baseline::Baseline;
+use crate::checkpoint::Checkpoint;
+use crate::config::{CpuModel, NetworkConfig, PeerLimit, Placement};
+use crate::latency::Latency;
+use crate::manifest::Manifest;
+use crate::network::{Network, Value, NETWORK_ID};
+use crate::node::{AfterDelivery, MaliciousKind, NodeId};
+use crate::protocols::bracha_broadcast::{BroadcastConfig, Strategy, TieBreak};
+use crate::protocols::smr::{Arrivals, ClientConfig, LoadConfig, SmrConfig};
+use crate::quorum::FaultModel;
+use crate::rng::{Rng, RngSource, SeededRng};
+use crate::scenario::LeaderPolicy;
+use crate::scheduler::Schedule;
+use crate::stats::Milestone;
+use log::{info, warn};
+use std::collections::BTreeSet;
+use std::time::Duration;
+
+/// Point on the boundary between runs satisfying the protocol properties
+/// and runs violating them
+#[derive(Clone, Debug, PartialEq)]
+pub struct ContourPoint {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub delay: Duration,
+    // Lowest drop rate found where violations reach the threshold
+    pub drop_rate: f64,
+    pub failure_rate: f64,
+}
+
+/// Adaptive search of the (n, f, drop-rate, delay) space: for every
+/// (n, f, delay) the drop rate where violations begin is found by bisection
+/// instead of sweeping a dense grid
+#[derive(Clone, Debug)]
+pub struct ContourSearch {
+    // (n, f) couples to explore
+    pub sizes: Vec<(usize, usize)>,
+    pub delays: Vec<Duration>,
+    pub kind: MaliciousKind,
+    // Runs used to estimate the failure rate of a point
+    pub runs: usize,
+    // Failure rate from which a point is on the violating side
+    pub threshold: f64,
+    // Width of the drop rate interval where the search stops
+    pub precision: f64,
+    // Runs that don't terminate in time are failures
+    pub time_limit: Duration,
+}
+
+impl Default for ContourSearch {
+    fn default() -> Self {
+        ContourSearch {
+            sizes: vec![(4, 1), (7, 2), (10, 3)],
+            delays: vec![Duration::ZERO, Duration::from_millis(1)],
+            kind: MaliciousKind::Silent,
+            runs: 5,
+            threshold: 0.5,
+            precision: 0.05,
+            time_limit: Duration::from_millis(100),
+        }
+    }
+}
+
+impl ContourSearch {
+    /// Parameters of the search, embedded in its dataset
+    pub fn manifest(&self) -> Manifest {
+        let sizes: Vec<String> = self
+            .sizes
+            .iter()
+            .map(|(n, f)| format!("{}/{}", n, f))
+            .collect();
+        let delays: Vec<String> = self.delays.iter().map(|d| format!("{:?}", d)).collect();
+        Manifest::new()
+            .with("search", "contour")
+            .with("sizes", sizes.join(" "))
+            .with("delays", delays.join(" "))
+            .with("kind", format!("{:?}", self.kind))
+            .with("runs", self.runs)
+            .with("threshold", self.threshold)
+            .with("precision", self.precision)
+            .with("time_limit", format!("{:?}", self.time_limit))
+            .with("rng", "os")
+    }
+
+    /// Find the boundary for `protocol`, a run returning whether the
+    /// protocol properties hold
+    pub fn run<F>(&self, protocol: F) -> Vec<ContourPoint>
+    where
+        F: Fn(&mut Network) -> bool,
+    {
+        let mut checkpoint = Checkpoint::new(self.manifest());
+        self.resume(&mut checkpoint, protocol).unwrap()
+    }
+
+    /// Search the (n, f, delay) points `checkpoint` doesn't have yet,
+    /// recording them as they are found
+    /// Points of the other shards are left out of the contour
+    pub fn resume<F>(
+        &self,
+        checkpoint: &mut Checkpoint,
+        protocol: F,
+    ) -> Result<Vec<ContourPoint>, String>
+    where
+        F: Fn(&mut Network) -> bool,
+    {
+        let mut contour = vec![];
+        for (s, &(num_nodes, num_malicious)) in self.sizes.iter().enumerate() {
+            // Created for the first point of the size left to search
+            let mut network: Option<Network> = None;
+
+            for (d, &delay) in self.delays.iter().enumerate() {
+                let name = format!("{}/{} {}us", num_nodes, num_malicious, delay.as_micros());
+                if let Some([drop_rate, failure_rate]) = checkpoint.get::<[u64; 2]>(&name) {
+                    contour.push(ContourPoint {
+                        num_nodes,
+                        num_malicious,
+                        delay,
+                        drop_rate: f64::from_bits(drop_rate),
+                        failure_rate: f64::from_bits(failure_rate),
+                    });
+                    continue;
+                }
+                if !checkpoint.assigned(s * self.delays.len() + d) {
+                    continue;
+                }
+                let network = network.get_or_insert_with(|| {
+                    let mut network = Network::new(num_nodes, num_malicious, self.kind.clone());
+                    network.set_time_limit(self.time_limit);
+                    network
+                });
+                let mut failure_rate = |drop_rate: f64| {
+                    network.set_config(NetworkConfig {
+                        drop_rate,
+                        latency: Latency::Constant(delay),
+                        ..NetworkConfig::default()
+                    });
+                    let failures = (0..self.runs).filter(|_| !protocol(network)).count();
+                    failures as f64 / self.runs as f64
+                };
+
+                // Invariant: violations are below the threshold at `low`
+                // and reach it at `high`
+                let (mut low, mut high) = (0.0, 1.0);
+                let mut high_failure_rate = 1.0;
+                let zero_failure_rate = failure_rate(0.0);
+                if zero_failure_rate >= self.threshold {
+                    high = 0.0;
+                    high_failure_rate = zero_failure_rate;
+                }
+                while high - low > self.precision {
+                    let middle = (low + high) / 2.0;
+                    let rate = failure_rate(middle);
+                    if rate >= self.threshold {
+                        high = middle;
+                        high_failure_rate = rate;
+                    } else {
+                        low = middle;
+                    }
+                }
+
+                let point = ContourPoint {
+                    num_nodes,
+                    num_malicious,
+                    delay,
+                    drop_rate: high,
+                    failure_rate: high_failure_rate,
+                };
+                info!("Contour point {:?}", point);
+                // Rates are saved bit for bit
+                checkpoint.record(&name, &[high.to_bits(), high_failure_rate.to_bits()])?;
+                contour.push(point);
+            }
+            if let Some(network) = network {
+                network.close();
+            }
+        }
+        Ok(contour)
+    }
+}
+
+/// Contour dataset as CSV, after the manifest of the search
+pub fn contour_csv(contour: &[ContourPoint], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str("num_nodes,num_malicious,delay_us,drop_rate,failure_rate\n");
+    for point in contour {
+        csv.push_str(&format!(
+            "{},{},{},{:.4},{:.2}\n",
+            point.num_nodes,
+            point.num_malicious,
+            point.delay.as_micros(),
+            point.drop_rate,
+            point.failure_rate
+        ));
+    }
+    csv
+}
+
+/// How the values delivered by honest nodes compare to the value of the
+/// leader, over the runs of one attack and one tie-break rule
+#[derive(Clone, Debug, PartialEq)]
+pub struct TieBreakOutcome {
+    pub kind: MaliciousKind,
+    pub tie_break: TieBreak,
+    pub runs: usize,
+    // Every honest node delivered the value of the leader
+    pub leader_wins: usize,
+    // Some honest node delivered another value
+    pub other_wins: usize,
+    // Neither, some honest nodes didn't deliver
+    pub undecided: usize,
+}
+
+/// Leader proposes a value of the domain, Byzantine nodes push competing
+/// values: count how often the value of the leader is not the one
+/// delivered under every (attack, tie-break rule)
+#[derive(Clone, Debug)]
+pub struct TieBreakExperiment {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub domain: Vec<Value>,
+    pub kinds: Vec<MaliciousKind>,
+    pub rules: Vec<TieBreak>,
+    pub runs: usize,
+    // Runs are deterministic and reproducible from the seed
+    pub seed: u64,
+    // Runs that don't terminate in time are undecided
+    pub time_limit: Duration,
+}
+
+impl Default for TieBreakExperiment {
+    fn default() -> Self {
+        TieBreakExperiment {
+            num_nodes: 7,
+            num_malicious: 2,
+            domain: (0..4).collect(),
+            kinds: vec![MaliciousKind::Random, MaliciousKind::Mirror],
+            rules: vec![
+                TieBreak::Quorum,
+                TieBreak::Plurality,
+                TieBreak::Lowest,
+                TieBreak::OwnEcho,
+            ],
+            runs: 20,
+            seed: 0,
+            time_limit: Duration::from_millis(200),
+        }
+    }
+}
+
+impl TieBreakExperiment {
+    pub fn run(&self) -> Vec<TieBreakOutcome> {
+        let mut checkpoint = Checkpoint::new(self.manifest());
+        self.resume(&mut checkpoint).unwrap()
+    }
+
+    /// Run the (attack, tie-break rule) couples `checkpoint` doesn't have
+    /// yet, recording them as they complete
+    /// Every couple has a network of its own, resumed campaigns give the
+    /// outcomes of uninterrupted ones
+    /// Couples of the other shards are left out of the outcomes
+    pub fn resume(&self, checkpoint: &mut Checkpoint) -> Result<Vec<TieBreakOutcome>, String> {
+        let mut outcomes = vec![];
+        for (k, kind) in self.kinds.iter().enumerate() {
+            for (r, &tie_break) in self.rules.iter().enumerate() {
+                let name = format!("{:?} {:?}", kind, tie_break);
+                if let Some([leader_wins, other_wins, undecided]) =
+                    checkpoint.get::<[u64; 3]>(&name)
+                {
+                    outcomes.push(TieBreakOutcome {
+                        kind: kind.clone(),
+                        tie_break,
+                        runs: self.runs,
+                        leader_wins: leader_wins as usize,
+                        other_wins: other_wins as usize,
+                        undecided: undecided as usize,
+                    });
+                    continue;
+                }
+                if !checkpoint.assigned(k * self.rules.len() + r) {
+                    continue;
+                }
+                let mut network = Network::with_rng(
+                    self.num_nodes,
+                    self.num_malicious,
+                    kind.clone(),
+                    RngSource::Seeded(self.seed),
+                );
+                network.set_schedule(Schedule::Deterministic);
+                network.set_time_limit(self.time_limit);
+                network.set_broadcast_config(BroadcastConfig {
+                    domain: self.domain.clone(),
+                    tie_break,
+                    ..BroadcastConfig::default()
+                });
+                // Same proposals for every rule and attack
+                let mut proposals = SeededRng::new(self.seed, NETWORK_ID as u64);
+
+                let mut outcome = TieBreakOutcome {
+                    kind: kind.clone(),
+                    tie_break,
+                    runs: self.runs,
+                    leader_wins: 0,
+                    other_wins: 0,
+                    undecided: 0,
+                };
+                for _ in 0..self.runs {
+                    let v = self.domain[proposals.gen_range(self.domain.len())];
+                    let (success, results) = network.bracha_broadcast(v, 0);
+                    if results.values().any(|delivered| *delivered != v) {
+                        outcome.other_wins += 1;
+                    } else if success {
+                        outcome.leader_wins += 1;
+                    } else {
+                        outcome.undecided += 1;
+                    }
+                }
+                info!("Tie-break outcome {:?}", outcome);
+                network.close();
+                let counts = [outcome.leader_wins, outcome.other_wins, outcome.undecided];
+                checkpoint.record(&name, &counts.map(|k| k as u64))?;
+                outcomes.push(outcome);
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Parameters of the experiment, embedded in its dataset
+    pub fn manifest(&self) -> Manifest {
+        let domain: Vec<String> = self.domain.iter().map(Value::to_string).collect();
+        let kinds: Vec<String> = self.kinds.iter().map(|kind| format!("{:?}", kind)).collect();
+        let rules: Vec<String> = self.rules.iter().map(|rule| format!("{:?}", rule)).collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "tie-break")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("domain", domain.join(" "))
+            .with("kinds", kinds.join(" "))
+            .with("rules", rules.join(" "))
+            .with("runs", self.runs)
+            .with("schedule", "deterministic")
+            .with("time_limit", format!("{:?}", self.time_limit));
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Tie-break outcomes as CSV, after the manifest of the experiment
+pub fn tie_break_csv(outcomes: &[TieBreakOutcome], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str("kind,tie_break,runs,leader_wins,other_wins,undecided\n");
+    for outcome in outcomes {
+        csv.push_str(&format!(
+            "{:?},{:?},{},{},{},{}\n",
+            outcome.kind,
+            outcome.tie_break,
+            outcome.runs,
+            outcome.leader_wins,
+            outcome.other_wins,
+            outcome.undecided
+        ));
+    }
+    csv
+}
+
+/// Cost of broadcasting a batch of values
+#[derive(Clone, Debug, PartialEq)]
+pub struct BatchingPoint {
+    pub batch_size: usize,
+    // One batched instance, or one Bracha instance per value
+    pub batched: bool,
+    pub messages: usize,
+    pub bytes: usize,
+    pub duration: Duration,
+    // Every honnest node delivered every value
+    pub success: bool,
+}
+
+/// Compare a batched reliable broadcast with per-value Bracha instances
+#[derive(Clone, Debug)]
+pub struct BatchingBenchmark {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kind: MaliciousKind,
+    pub batch_sizes: Vec<usize>,
+    pub seed: u64,
+}
+
+impl Default for BatchingBenchmark {
+    fn default() -> Self {
+        BatchingBenchmark {
+            num_nodes: 10,
+            num_malicious: 0,
+            kind: MaliciousKind::Silent,
+            batch_sizes: vec![1, 4, 16, 64],
+            seed: 0,
+        }
+    }
+}
+
+impl BatchingBenchmark {
+    pub fn run(&self) -> Vec<BatchingPoint> {
+        let mut network = Network::with_rng(
+            self.num_nodes,
+            self.num_malicious,
+            self.kind.clone(),
+            RngSource::Seeded(self.seed),
+        );
+        let mut points = vec![];
+        for &batch_size in self.batch_sizes.iter() {
+            let batch: Vec<Value> = (1..=batch_size).collect();
+
+            let (success, _) = network.batched_broadcast(&batch, 0);
+            let stats = network.stats();
+            points.push(BatchingPoint {
+                batch_size,
+                batched: true,
+                messages: stats.total_messages(),
+                bytes: stats.total_bytes(),
+                duration: stats.elapsed(),
+                success,
+            });
+
+            let mut per_value = BatchingPoint {
+                batch_size,
+                batched: false,
+                messages: 0,
+                bytes: 0,
+                duration: Duration::ZERO,
+                success: true,
+            };
+            for v in batch.iter() {
+                let (success, _) = network.bracha_broadcast(*v, 0);
+                let stats = network.stats();
+                per_value.messages += stats.total_messages();
+                per_value.bytes += stats.total_bytes();
+                per_value.duration += stats.elapsed();
+                per_value.success &= success;
+            }
+            points.push(per_value);
+        }
+        network.close();
+        points
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let sizes: Vec<String> = self.batch_sizes.iter().map(usize::to_string).collect();
+        let mut manifest = Manifest::new()
+            .with("benchmark", "batching")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kind", format!("{:?}", self.kind))
+            .with("batch_sizes", sizes.join(" "))
+            .with("schedule", "concurrent");
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Batching benchmark as CSV, after its manifest
+pub fn batching_csv(points: &[BatchingPoint], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str("batch_size,mode,messages,bytes,duration_us,success\n");
+    for point in points {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{}\n",
+            point.batch_size,
+            if point.batched { "batched" } else { "per-value" },
+            point.messages,
+            point.bytes,
+            point.duration.as_micros(),
+            point.success
+        ));
+    }
+    csv
+}
+
+/// Cost and latency of a variant of the honest nodes
+#[derive(Clone, Debug, PartialEq)]
+pub struct StrategyOutcome {
+    pub strategy: Strategy,
+    pub runs: usize,
+    pub successes: usize,
+    // Averages over the runs
+    pub messages: f64,
+    pub bytes: f64,
+    // Price of the messages and operations under the default cost model
+    pub cost: f64,
+    pub latency: Duration,
+}
+
+/// Run Bracha broadcast with every variant of the honest nodes on the same
+/// network to compare their latency and message costs
+#[derive(Clone, Debug)]
+pub struct StrategyComparison {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kind: MaliciousKind,
+    pub strategies: Vec<Strategy>,
+    pub runs: usize,
+    pub delay: Duration,
+    // Probability that a message on a link of the leader is lost, INIT
+    // then only reaches some of the nodes unless they relay it
+    pub leader_drop_rate: f64,
+    pub seed: u64,
+    // Runs that don't terminate in time are failures
+    pub time_limit: Duration,
+}
+
+impl Default for StrategyComparison {
+    fn default() -> Self {
+        let strategies = ["standard", "eager-init", "eager-init+lazy-echo", "no-relay"];
+        StrategyComparison {
+            num_nodes: 10,
+            num_malicious: 3,
+            kind: MaliciousKind::Silent,
+            strategies: strategies.iter().map(|s| s.parse().unwrap()).collect(),
+            runs: 5,
+            delay: Duration::from_millis(1),
+            leader_drop_rate: 0.0,
+            seed: 0,
+            time_limit: Duration::from_millis(500),
+        }
+    }
+}
+
+impl StrategyComparison {
+    pub fn run(&self) -> Vec<StrategyOutcome> {
+        let mut network = Network::with_rng(
+            self.num_nodes,
+            self.num_malicious,
+            self.kind.clone(),
+            RngSource::Seeded(self.seed),
+        );
+        network.set_time_limit(self.time_limit);
+        let config = NetworkConfig {
+            drop_rate: 0.0,
+            latency: Latency::Constant(self.delay),
+            ..NetworkConfig::default()
+        };
+        network.set_config(config.lossy_links(0, self.num_nodes, self.leader_drop_rate));
+
+        let mut outcomes = vec![];
+        for &strategy in self.strategies.iter() {
+            network.set_broadcast_config(BroadcastConfig {
+                strategy,
+                ..BroadcastConfig::default()
+            });
+            let (mut successes, mut messages, mut bytes) = (0, 0, 0);
+            let mut cost = 0.0;
+            let mut latency = Duration::ZERO;
+            for _ in 0..self.runs {
+                if network.bracha_broadcast(7, 0).0 {
+                    successes += 1;
+                }
+                let stats = network.stats();
+                messages += stats.total_messages();
+                bytes += stats.total_bytes();
+                cost += network.cost_report().total();
+                latency += stats.elapsed();
+            }
+            let outcome = StrategyOutcome {
+                strategy,
+                runs: self.runs,
+                successes,
+                messages: messages as f64 / self.runs as f64,
+                bytes: bytes as f64 / self.runs as f64,
+                cost: cost / self.runs as f64,
+                latency: latency / self.runs as u32,
+            };
+            info!("Strategy outcome {:?}", outcome);
+            outcomes.push(outcome);
+        }
+        network.close();
+        outcomes
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let strategies: Vec<String> = self.strategies.iter().map(Strategy::name).collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "strategies")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kind", format!("{:?}", self.kind))
+            .with("strategies", strategies.join(" "))
+            .with("runs", self.runs)
+            .with("latency", format!("{:?}", self.delay))
+            .with("leader_drop_rate", self.leader_drop_rate)
+            .with("schedule", "concurrent")
+            .with("time_limit", format!("{:?}", self.time_limit));
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Strategy comparison as CSV, after its manifest
+pub fn strategies_csv(outcomes: &[StrategyOutcome], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str("strategy,runs,successes,messages,bytes,cost,latency_us\n");
+    for outcome in outcomes {
+        csv.push_str(&format!(
+            "{},{},{},{:.1},{:.1},{:.1},{}\n",
+            outcome.strategy.name(),
+            outcome.runs,
+            outcome.successes,
+            outcome.messages,
+            outcome.bytes,
+            outcome.cost,
+            outcome.latency.as_micros()
+        ));
+    }
+    csv
+}
+
+/// How the nodes lagging behind fared over the runs of one behaviour of
+/// the nodes that delivered
+#[derive(Clone, Debug, PartialEq)]
+pub struct HelpingOutcome {
+    pub after_delivery: AfterDelivery,
+    pub runs: usize,
+    pub successes: usize,
+    // Slow nodes that gave up without delivering, over all the runs
+    pub stranded: usize,
+    // Averages over the runs
+    pub messages: f64,
+    pub state_transfers: f64,
+}
+
+/// Links to the slow nodes are slower and lose messages: compare how many
+/// slow nodes are stranded when the nodes that delivered stop, and when
+/// they keep answering the state-transfer requests of the slow ones
+#[derive(Clone, Debug)]
+pub struct HelpingExperiment {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    // Honest nodes with the highest ids are slow
+    pub slow_nodes: usize,
+    // Extra time of the messages to the slow nodes
+    pub slow_delay: Duration,
+    // Probability that a message to a slow node is lost
+    pub drop_rate: f64,
+    // Period of the state-transfer requests of the nodes that haven't
+    // delivered, nodes give up after the liveness timeout without progress
+    pub catch_up: Duration,
+    pub liveness_timeout: Duration,
+    pub policies: Vec<AfterDelivery>,
+    pub runs: usize,
+    // Runs are deterministic and reproducible from the seed
+    pub seed: u64,
+    pub time_limit: Duration,
+}
+
+impl Default for HelpingExperiment {
+    fn default() -> Self {
+        HelpingExperiment {
+            num_nodes: 7,
+            num_malicious: 0,
+            slow_nodes: 2,
+            slow_delay: Duration::from_millis(5),
+            drop_rate: 0.5,
+            catch_up: Duration::from_millis(10),
+            liveness_timeout: Duration::from_millis(100),
+            policies: vec![AfterDelivery::Stop, AfterDelivery::Participate],
+            runs: 20,
+            seed: 0,
+            time_limit: Duration::from_millis(500),
+        }
+    }
+}
+
+impl HelpingExperiment {
+    pub fn slow(&self) -> std::ops::Range<NodeId> {
+        let honest = self.num_nodes - self.num_malicious;
+        honest.saturating_sub(self.slow_nodes)..honest
+    }
+
+    pub fn run(&self) -> Vec<HelpingOutcome> {
+        let mut outcomes = vec![];
+        for &after_delivery in self.policies.iter() {
+            let mut network = Network::with_rng(
+                self.num_nodes,
+                self.num_malicious,
+                MaliciousKind::Silent,
+                RngSource::Seeded(self.seed),
+            );
+            network.set_schedule(Schedule::Deterministic);
+            network.set_time_limit(self.time_limit);
+            network.set_after_delivery(after_delivery);
+            network.set_broadcast_config(BroadcastConfig {
+                liveness_timeout: Some(self.liveness_timeout),
+                catch_up: Some(self.catch_up),
+                ..BroadcastConfig::default()
+            });
+            let mut config = NetworkConfig::default();
+            for to in self.slow() {
+                for from in (0..self.num_nodes).filter(|from| *from != to) {
+                    config.link_drop_rates.insert((from, to), self.drop_rate);
+                    network.controller().delay_link(from, to, self.slow_delay);
+                }
+            }
+            network.set_config(config);
+
+            let mut outcome = HelpingOutcome {
+                after_delivery,
+                runs: self.runs,
+                successes: 0,
+                stranded: 0,
+                messages: 0.0,
+                state_transfers: 0.0,
+            };
+            for _ in 0..self.runs {
+                let (success, results) = network.bracha_broadcast(7, 0);
+                if success {
+                    outcome.successes += 1;
+                }
+                outcome.stranded += self.slow().filter(|id| !results.contains_key(id)).count();
+                outcome.messages += network.stats().total_messages() as f64;
+                outcome.state_transfers +=
+                    network.counter("state_transfers").values().sum::<usize>() as f64;
+            }
+            outcome.messages /= self.runs as f64;
+            outcome.state_transfers /= self.runs as f64;
+            info!("Helping outcome {:?}", outcome);
+            network.close();
+            outcomes.push(outcome);
+        }
+        outcomes
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let policies: Vec<&str> = self.policies.iter().map(AfterDelivery::name).collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "helping")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("slow_nodes", self.slow_nodes)
+            .with("slow_delay", format!("{:?}", self.slow_delay))
+            .with("drop_rate", self.drop_rate)
+            .with("catch_up", format!("{:?}", self.catch_up))
+            .with("liveness_timeout", format!("{:?}", self.liveness_timeout))
+            .with("policies", policies.join(" "))
+            .with("runs", self.runs)
+            .with("schedule", "deterministic")
+            .with("time_limit", format!("{:?}", self.time_limit));
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Helping outcomes as CSV, after the manifest of the experiment
+pub fn helping_csv(outcomes: &[HelpingOutcome], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str("after_delivery,runs,successes,stranded,messages,state_transfers\n");
+    for outcome in outcomes {
+        csv.push_str(&format!(
+            "{},{},{},{},{:.1},{:.1}\n",
+            outcome.after_delivery.name(),
+            outcome.runs,
+            outcome.successes,
+            outcome.stranded,
+            outcome.messages,
+            outcome.state_transfers
+        ));
+    }
+    csv
+}
+
+/// What an attack metric sees of a run
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunObservation {
+    // Termination, agreement and validity hold
+    pub success: bool,
+    // Honest nodes delivered different outputs
+    pub disagreement: bool,
+    pub messages: usize,
+    pub latency: Duration,
+}
+
+impl RunObservation {
+    /// Observation of the last run of `network`
+    pub fn of(network: &Network, success: bool) -> Self {
+        let stats = network.stats();
+        RunObservation {
+            success,
+            disagreement: network.behaviour_stats().honest.delivered.len() > 1,
+            messages: stats.total_messages(),
+            latency: stats.elapsed(),
+        }
+    }
+}
+
+/// Effectiveness of an attack over a batch of runs, higher is more
+/// effective
+pub trait AttackMetric {
+    fn name(&self) -> &'static str;
+
+    /// Score of the runs under attack, `baseline` being the same runs
+    /// without malicious nodes
+    fn score(&self, runs: &[RunObservation], baseline: &[RunObservation]) -> f64;
+}
+
+fn mean<F: Fn(&RunObservation) -> f64>(runs: &[RunObservation], f: F) -> f64 {
+    if runs.is_empty() {
+        return 0.0;
+    }
+    runs.iter().map(f).sum::<f64>() / runs.len() as f64
+}
+
+/// Probability that honest nodes don't agree
+pub struct AgreementViolation;
+
+impl AttackMetric for AgreementViolation {
+    fn name(&self) -> &'static str {
+        "agreement_violation"
+    }
+
+    fn score(&self, runs: &[RunObservation], _baseline: &[RunObservation]) -> f64 {
+        mean(runs, |run| run.disagreement as usize as f64)
+    }
+}
+
+/// Probability that one of the properties of the protocol is violated
+pub struct FailureRate;
+
+impl AttackMetric for FailureRate {
+    fn name(&self) -> &'static str {
+        "failure_rate"
+    }
+
+    fn score(&self, runs: &[RunObservation], _baseline: &[RunObservation]) -> f64 {
+        mean(runs, |run| !run.success as usize as f64)
+    }
+}
+
+/// Mean latency added by the attack, in microseconds
+pub struct AddedLatency;
+
+impl AttackMetric for AddedLatency {
+    fn name(&self) -> &'static str {
+        "added_latency_us"
+    }
+
+    fn score(&self, runs: &[RunObservation], baseline: &[RunObservation]) -> f64 {
+        let latency = |run: &RunObservation| run.latency.as_micros() as f64;
+        mean(runs, latency) - mean(baseline, latency)
+    }
+}
+
+/// Mean number of messages induced by the attack
+pub struct ExtraMessages;
+
+impl AttackMetric for ExtraMessages {
+    fn name(&self) -> &'static str {
+        "extra_messages"
+    }
+
+    fn score(&self, runs: &[RunObservation], baseline: &[RunObservation]) -> f64 {
+        let messages = |run: &RunObservation| run.messages as f64;
+        mean(runs, messages) - mean(baseline, messages)
+    }
+}
+
+/// Metric named `name`
+pub fn attack_metric(name: &str) -> Result<Box<dyn AttackMetric>, String> {
+    match name {
+        "agreement_violation" => Ok(Box::new(AgreementViolation)),
+        "failure_rate" => Ok(Box::new(FailureRate)),
+        "added_latency_us" => Ok(Box::new(AddedLatency)),
+        "extra_messages" => Ok(Box::new(ExtraMessages)),
+        _ => Err(format!("Unknown attack metric: {}", name)),
+    }
+}
+
+/// Scores of an attack, in the order of the metrics
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackScore {
+    pub kind: MaliciousKind,
+    pub scores: Vec<f64>,
+}
+
+/// Run Bracha broadcast under every attack and without malicious nodes to
+/// rank the attacks by their effectiveness
+#[derive(Clone, Debug)]
+pub struct AttackComparison {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kinds: Vec<MaliciousKind>,
+    pub runs: usize,
+    pub delay: Duration,
+    // Time nodes take to handle their messages, floods only slow down
+    // nodes that pay for them
+    pub cpu: Option<CpuModel>,
+    // Rate limit of the honest nodes on each of their peers
+    pub peer_limit: Option<PeerLimit>,
+    // Malicious nodes of the attacks, node 0 leads the broadcasts
+    pub placement: Placement,
+    pub schedule: Schedule,
+    pub seed: u64,
+    // Runs that don't terminate in time are failures
+    pub time_limit: Duration,
+}
+
+impl Default for AttackComparison {
+    fn default() -> Self {
+        AttackComparison {
+            num_nodes: 10,
+            num_malicious: 3,
+            kinds: vec![
+                MaliciousKind::Silent,
+                MaliciousKind::Random,
+                MaliciousKind::Mirror,
+            ],
+            runs: 5,
+            delay: Duration::from_millis(1),
+            cpu: None,
+            peer_limit: None,
+            placement: Placement::Highest,
+            schedule: Schedule::Concurrent,
+            seed: 0,
+            time_limit: Duration::from_millis(500),
+        }
+    }
+}
+
+impl AttackComparison {
+    /// Panics if the placement doesn't fit the comparison
+    fn observe(&self, num_malicious: usize, kind: MaliciousKind) -> Vec<RunObservation> {
+        let config = NetworkConfig {
+            drop_rate: 0.0,
+            latency: Latency::Constant(self.delay),
+            cpu: self.cpu.clone(),
+            peer_limit: self.peer_limit,
+            ..NetworkConfig::default()
+        };
+        // Baseline runs have no malicious node to place
+        let malicious = match num_malicious {
+            0 => vec![],
+            _ => self
+                .placement
+                .malicious(self.num_nodes, num_malicious, &config.latency)
+                .unwrap_or_else(|diagnostic| panic!("{}", diagnostic)),
+        };
+        let mut network = Network::with_malicious_nodes(
+            self.num_nodes,
+            malicious,
+            kind,
+            RngSource::Seeded(self.seed),
+            FaultModel::Byzantine,
+        );
+        network.set_schedule(self.schedule.clone());
+        network.set_time_limit(self.time_limit);
+        network.set_config(config);
+        let runs = (0..self.runs)
+            .map(|_| {
+                let (success, _) = network.bracha_broadcast(7, 0);
+                RunObservation::of(&network, success)
+            })
+            .collect();
+        network.close();
+        runs
+    }
+
+    /// Scores of every attack, most effective first according to the first
+    /// metric
+    pub fn run(&self, metrics: &[Box<dyn AttackMetric>]) -> Vec<AttackScore> {
+        let mut checkpoint = Checkpoint::new(self.manifest(metrics));
+        self.resume(&mut checkpoint, metrics).unwrap()
+    }
+
+    /// Observe the attacks `checkpoint` doesn't have yet, recording the
+    /// runs of each as they complete
+    /// Every shard observes the baseline it scores its attacks against,
+    /// attacks of the other shards are left out of the scores
+    pub fn resume(
+        &self,
+        checkpoint: &mut Checkpoint,
+        metrics: &[Box<dyn AttackMetric>],
+    ) -> Result<Vec<AttackScore>, String> {
+        let baseline = self.resume_observe(checkpoint, "baseline", 0, MaliciousKind::Silent)?;
+        let mut scores = vec![];
+        for (k, kind) in self.kinds.iter().enumerate() {
+            let name = format!("{:?}", kind);
+            if checkpoint.get::<Vec<u64>>(&name).is_none() && !checkpoint.assigned(k) {
+                continue;
+            }
+            let runs = self.resume_observe(checkpoint, &name, self.num_malicious, kind.clone())?;
+            let score = AttackScore {
+                kind: kind.clone(),
+                scores: metrics.iter().map(|m| m.score(&runs, &baseline)).collect(),
+            };
+            info!("Attack score {:?}", score);
+            scores.push(score);
+        }
+        scores.sort_by(|a, b| {
+            let first = |score: &AttackScore| score.scores.first().cloned().unwrap_or_default();
+            first(b).total_cmp(&first(a))
+        });
+        Ok(scores)
+    }
+
+    /// Runs of `checkpoint` named `name`, observed and recorded if it
+    /// doesn't have them
+    /// Every run is saved as its success, disagreement, messages and
+    /// latency in nanoseconds
+    fn resume_observe(
+        &self,
+        checkpoint: &mut Checkpoint,
+        name: &str,
+        num_malicious: usize,
+        kind: MaliciousKind,
+    ) -> Result<Vec<RunObservation>, String> {
+        if let Some(fields) = checkpoint.get::<Vec<u64>>(name) {
+            if fields.len() == 4 * self.runs {
+                return Ok(fields
+                    .chunks(4)
+                    .map(|run| RunObservation {
+                        success: run[0] == 1,
+                        disagreement: run[1] == 1,
+                        messages: run[2] as usize,
+                        latency: Duration::from_nanos(run[3]),
+                    })
+                    .collect());
+            }
+            warn!("Running {} again, invalid checkpoint: {} fields", name, fields.len());
+        }
+        let runs = self.observe(num_malicious, kind);
+        let fields: Vec<u64> = runs
+            .iter()
+            .flat_map(|run| {
+                [
+                    run.success as u64,
+                    run.disagreement as u64,
+                    run.messages as u64,
+                    run.latency.as_nanos() as u64,
+                ]
+            })
+            .collect();
+        checkpoint.record(name, &fields)?;
+        Ok(runs)
+    }
+
+    pub fn manifest(&self, metrics: &[Box<dyn AttackMetric>]) -> Manifest {
+        let kinds: Vec<String> = self.kinds.iter().map(|k| format!("{:?}", k)).collect();
+        let metrics: Vec<&str> = metrics.iter().map(|m| m.name()).collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "attacks")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kinds", kinds.join(" "))
+            .with("metrics", metrics.join(" "))
+            .with("runs", self.runs)
+            .with("latency", format!("{:?}", self.delay))
+            .with(
+                "cpu",
+                self.cpu
+                    .as_ref()
+                    .map_or(String::from("free"), |cpu| cpu.to_string()),
+            )
+            .with(
+                "peer_limit",
+                self.peer_limit
+                    .map_or(String::from("unlimited"), |limit| limit.to_string()),
+            )
+            .with("placement", &self.placement)
+            .with("schedule", self.schedule.name())
+            .with("time_limit", format!("{:?}", self.time_limit));
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Attack ranking as CSV, after its manifest
+pub fn attacks_csv(
+    scores: &[AttackScore],
+    metrics: &[Box<dyn AttackMetric>],
+    manifest: &Manifest,
+) -> String {
+    let mut csv = manifest.header("#");
+    let names: Vec<&str> = metrics.iter().map(|m| m.name()).collect();
+    csv.push_str(&format!("rank,kind,{}\n", names.join(",")));
+    for (rank, score) in scores.iter().enumerate() {
+        let values: Vec<String> = score.scores.iter().map(|s| format!("{:.3}", s)).collect();
+        csv.push_str(&format!(
+            "{},{:?},{}\n",
+            rank + 1,
+            score.kind,
+            values.join(",")
+        ));
+    }
+    csv
+}
+
+/// How the broadcasts of the leaders a policy picks fared under an attack
+#[derive(Clone, Debug, PartialEq)]
+pub struct LeaderOutcome {
+    pub kind: MaliciousKind,
+    pub leader: LeaderPolicy,
+    pub runs: usize,
+    // Runs the leader picked was malicious
+    pub malicious_leaders: usize,
+    // Runs every honest node delivered the value of the leader
+    pub successes: usize,
+    // Runs no two honest nodes delivered different values
+    pub agreements: usize,
+    // Average over the runs
+    pub latency: Duration,
+}
+
+impl LeaderOutcome {
+    pub fn success_rate(&self) -> f64 {
+        self.successes as f64 / self.runs.max(1) as f64
+    }
+}
+
+/// Run Bracha broadcast under every attack with the leaders of every
+/// policy, an honest leader against a malicious one by default
+#[derive(Clone, Debug)]
+pub struct LeaderExperiment {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kinds: Vec<MaliciousKind>,
+    pub leaders: Vec<LeaderPolicy>,
+    pub runs: usize,
+    pub delay: Duration,
+    // Time honest nodes wait for a leader that sends nothing before giving
+    // up
+    pub liveness_timeout: Duration,
+    pub schedule: Schedule,
+    pub seed: u64,
+    // Runs that don't terminate in time are failures
+    pub time_limit: Duration,
+}
+
+impl Default for LeaderExperiment {
+    fn default() -> Self {
+        LeaderExperiment {
+            num_nodes: 10,
+            num_malicious: 3,
+            kinds: vec![
+                MaliciousKind::Silent,
+                MaliciousKind::Random,
+                MaliciousKind::Mirror,
+                MaliciousKind::Equivocate,
+            ],
+            leaders: vec![LeaderPolicy::RandomHonest(0), LeaderPolicy::Malicious],
+            runs: 5,
+            delay: Duration::from_millis(1),
+            liveness_timeout: Duration::from_millis(100),
+            schedule: Schedule::Concurrent,
+            seed: 0,
+            time_limit: Duration::from_millis(500),
+        }
+    }
+}
+
+impl LeaderExperiment {
+    /// Panics if a policy has no leader to pick
+    pub fn run(&self) -> Vec<LeaderOutcome> {
+        let mut checkpoint = Checkpoint::new(self.manifest());
+        self.resume(&mut checkpoint).unwrap()
+    }
+
+    /// Run the (attack, policy) couples `checkpoint` doesn't have yet,
+    /// recording them as they complete
+    /// Every couple has a network of its own, resumed campaigns give the
+    /// outcomes of uninterrupted ones with the deterministic schedule
+    /// Couples of the other shards are left out of the outcomes
+    pub fn resume(&self, checkpoint: &mut Checkpoint) -> Result<Vec<LeaderOutcome>, String> {
+        let mut outcomes = vec![];
+        for (k, kind) in self.kinds.iter().enumerate() {
+            for (l, policy) in self.leaders.iter().enumerate() {
+                let name = format!("{:?} {}", kind, policy);
+                if let Some([malicious_leaders, successes, agreements, ns]) =
+                    checkpoint.get::<[u64; 4]>(&name)
+                {
+                    outcomes.push(LeaderOutcome {
+                        kind: kind.clone(),
+                        leader: policy.clone(),
+                        runs: self.runs,
+                        malicious_leaders: malicious_leaders as usize,
+                        successes: successes as usize,
+                        agreements: agreements as usize,
+                        latency: Duration::from_nanos(ns),
+                    });
+                    continue;
+                }
+                if !checkpoint.assigned(k * self.leaders.len() + l) {
+                    continue;
+                }
+                let mut network = self.network(kind);
+                let malicious = network.malicious_nodes();
+                let mut outcome = LeaderOutcome {
+                    kind: kind.clone(),
+                    leader: policy.clone(),
+                    runs: self.runs,
+                    malicious_leaders: 0,
+                    successes: 0,
+                    agreements: 0,
+                    latency: Duration::ZERO,
+                };
+                for run in 0..self.runs {
+                    let leader = policy
+                        .leader(&network, run as u64)
+                        .unwrap_or_else(|e| panic!("{}", e));
+                    let (success, results) = network.bracha_broadcast(7, leader);
+                    let delivered: BTreeSet<Value> = network
+                        .good_nodes()
+                        .iter()
+                        .filter_map(|id| results.get(id).copied())
+                        .collect();
+                    outcome.malicious_leaders += malicious.contains(&leader) as usize;
+                    outcome.successes += success as usize;
+                    outcome.agreements += (delivered.len() <= 1) as usize;
+                    outcome.latency += network.stats().elapsed();
+                }
+                outcome.latency /= self.runs.max(1) as u32;
+                info!("Leader outcome {:?}", outcome);
+                network.close();
+                let counts = [
+                    outcome.malicious_leaders as u64,
+                    outcome.successes as u64,
+                    outcome.agreements as u64,
+                    outcome.latency.as_nanos() as u64,
+                ];
+                checkpoint.record(&name, &counts)?;
+                outcomes.push(outcome);
+            }
+        }
+        Ok(outcomes)
+    }
+
+    /// Network of the runs under attack `kind`
+    fn network(&self, kind: &MaliciousKind) -> Network {
+        let mut network = Network::with_rng(
+            self.num_nodes,
+            self.num_malicious,
+            kind.clone(),
+            RngSource::Seeded(self.seed),
+        );
+        network.set_schedule(self.schedule.clone());
+        network.set_time_limit(self.time_limit);
+        network.set_config(NetworkConfig {
+            drop_rate: 0.0,
+            latency: Latency::Constant(self.delay),
+            ..NetworkConfig::default()
+        });
+        network.set_broadcast_config(BroadcastConfig {
+            liveness_timeout: Some(self.liveness_timeout),
+            ..BroadcastConfig::default()
+        });
+        network
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let kinds: Vec<String> = self.kinds.iter().map(|k| format!("{:?}", k)).collect();
+        let leaders: Vec<String> = self.leaders.iter().map(LeaderPolicy::to_string).collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "leaders")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kinds", kinds.join(" "))
+            .with("leaders", leaders.join(" "))
+            .with("runs", self.runs)
+            .with("latency", format!("{:?}", self.delay))
+            .with("liveness_timeout", format!("{:?}", self.liveness_timeout))
+            .with("schedule", self.schedule.name())
+            .with("time_limit", format!("{:?}", self.time_limit));
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Leader comparison as CSV, after its manifest
+pub fn leaders_csv(outcomes: &[LeaderOutcome], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str(
+        "kind,leader,runs,malicious_leaders,successes,success_rate,agreements,latency_us\n",
+    );
+    for outcome in outcomes {
+        csv.push_str(&format!(
+            "{:?},{},{},{},{},{:.3},{},{}\n",
+            outcome.kind,
+            outcome.leader,
+            outcome.runs,
+            outcome.malicious_leaders,
+            outcome.successes,
+            outcome.success_rate(),
+            outcome.agreements,
+            outcome.latency.as_micros()
+        ));
+    }
+    csv
+}
+
+/// Consensus instance of a throughput run
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThroughputSample {
+    pub instance: usize,
+    // Values committed by the instance, none if it failed
+    pub values: usize,
+    pub duration: Duration,
+    // Instance is part of the measurement window
+    pub measured: bool,
+}
+
+impl ThroughputSample {
+    /// Values committed per second
+    pub fn rate(&self) -> f64 {
+        match self.duration.as_secs_f64() {
+            secs if secs > 0.0 => self.values as f64 / secs,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Throughput of repeated consensus over its measurement window
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThroughputReport {
+    pub samples: Vec<ThroughputSample>,
+    // First instance of the steady state, None if it was not reached
+    // before the cool-down
+    pub steady_from: Option<usize>,
+    // Values per second over the measured instances
+    pub throughput: f64,
+}
+
+impl ThroughputReport {
+    pub fn measured(&self) -> impl Iterator<Item = &ThroughputSample> {
+        self.samples.iter().filter(|sample| sample.measured)
+    }
+}
+
+/// Run consecutive batched broadcasts on the same network and measure
+/// their throughput without the warm-up and cool-down instances
+/// With `steady_window` set, the measurement only starts once that many
+/// consecutive instances have a rate within `tolerance` of their mean
+#[derive(Clone, Debug)]
+pub struct ThroughputExperiment {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kind: MaliciousKind,
+    pub batch_size: usize,
+    pub instances: usize,
+    // Instances excluded from the measurement at the start and at the end
+    pub warmup: usize,
+    pub cooldown: usize,
+    // 0 disables the steady-state detection
+    pub steady_window: usize,
+    // Relative deviation from the mean of the window, 0.1 for 10%
+    pub tolerance: f64,
+    pub delay: Duration,
+    pub seed: u64,
+}
+
+impl Default for ThroughputExperiment {
+    fn default() -> Self {
+        ThroughputExperiment {
+            num_nodes: 10,
+            num_malicious: 0,
+            kind: MaliciousKind::Silent,
+            batch_size: 16,
+            instances: 30,
+            warmup: 5,
+            cooldown: 5,
+            steady_window: 5,
+            tolerance: 0.25,
+            delay: Duration::ZERO,
+            seed: 0,
+        }
+    }
+}
+
+impl ThroughputExperiment {
+    pub fn run(&self) -> ThroughputReport {
+        let mut checkpoint = Checkpoint::new(self.manifest());
+        self.resume(&mut checkpoint).unwrap()
+    }
+
+    /// Run the instances `checkpoint` doesn't have yet, recording them as
+    /// they complete
+    /// Instances left after an interruption, or of a shard, run one after
+    /// the other on a network of their own: they warm it up again
+    /// Instances of the other shards are left out of the report
+    pub fn resume(&self, checkpoint: &mut Checkpoint) -> Result<ThroughputReport, String> {
+        // Created for the first instance left to run
+        let mut network: Option<Network> = None;
+        let mut samples = vec![];
+        for instance in 0..self.instances {
+            let name = format!("instance {}", instance);
+            if let Some([values, ns]) = checkpoint.get::<[u64; 2]>(&name) {
+                samples.push(ThroughputSample {
+                    instance,
+                    values: values as usize,
+                    duration: Duration::from_nanos(ns),
+                    measured: false,
+                });
+                continue;
+            }
+            if !checkpoint.assigned(instance) {
+                continue;
+            }
+            let network = network.get_or_insert_with(|| {
+                let mut network = Network::with_rng(
+                    self.num_nodes,
+                    self.num_malicious,
+                    self.kind.clone(),
+                    RngSource::Seeded(self.seed),
+                );
+                network.set_config(NetworkConfig {
+                    drop_rate: 0.0,
+                    latency: Latency::Constant(self.delay),
+                    ..NetworkConfig::default()
+                });
+                network
+            });
+            let first = instance * self.batch_size + 1;
+            let batch: Vec<Value> = (first..first + self.batch_size).collect();
+            let (success, _) = network.batched_broadcast(&batch, 0);
+            let sample = ThroughputSample {
+                instance,
+                values: if success { batch.len() } else { 0 },
+                duration: network.stats().elapsed(),
+                measured: false,
+            };
+            let counts = [sample.values as u64, sample.duration.as_nanos() as u64];
+            checkpoint.record(&name, &counts)?;
+            samples.push(sample);
+        }
+        if let Some(network) = network {
+            network.close();
+        }
+
+        // Shards only have some of the instances, the window is one of
+        // instance numbers
+        let end = self.instances.saturating_sub(self.cooldown);
+        let before = |bound: usize| samples.iter().filter(|s| s.instance < bound).count();
+        let steady_from = self
+            .steady_state(&samples[..before(end.max(self.warmup))])
+            .map(|position| samples[position].instance);
+        let start = steady_from.unwrap_or(self.warmup);
+        let (mut values, mut duration) = (0, Duration::ZERO);
+        for sample in samples.iter_mut().filter(|s| (start..end).contains(&s.instance)) {
+            sample.measured = true;
+            values += sample.values;
+            duration += sample.duration;
+        }
+        let report = ThroughputReport {
+            samples,
+            steady_from,
+            throughput: match duration.as_secs_f64() {
+                secs if secs > 0.0 => values as f64 / secs,
+                _ => 0.0,
+            },
+        };
+        info!(
+            "Throughput {:.0} values/s, steady from {:?}",
+            report.throughput, report.steady_from
+        );
+        Ok(report)
+    }
+
+    /// First instance after the warm-up starting a window of stable rates
+    fn steady_state(&self, samples: &[ThroughputSample]) -> Option<usize> {
+        if self.steady_window == 0 {
+            return None;
+        }
+        (self.warmup..samples.len())
+            .map(|start| (start, &samples[start..]))
+            .filter(|(_, rest)| rest.len() >= self.steady_window)
+            .find(|(_, rest)| {
+                let window = &rest[..self.steady_window];
+                let mean = window.iter().map(ThroughputSample::rate).sum::<f64>()
+                    / self.steady_window as f64;
+                mean > 0.0
+                    && window
+                        .iter()
+                        .all(|sample| (sample.rate() - mean).abs() <= self.tolerance * mean)
+            })
+            .map(|(start, _)| start)
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let mut manifest = Manifest::new()
+            .with("experiment", "throughput")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kind", format!("{:?}", self.kind))
+            .with("batch_size", self.batch_size)
+            .with("instances", self.instances)
+            .with("warmup", self.warmup)
+            .with("cooldown", self.cooldown)
+            .with("steady_window", self.steady_window)
+            .with("tolerance", self.tolerance)
+            .with("latency", format!("{:?}", self.delay))
+            .with("schedule", "concurrent");
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Instances of a throughput run as CSV, after its manifest
+pub fn throughput_csv(report: &ThroughputReport, manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str("instance,values,duration_us,rate,measured\n");
+    for sample in report.samples.iter() {
+        csv.push_str(&format!(
+            "{},{},{},{:.1},{}\n",
+            sample.instance,
+            sample.values,
+            sample.duration.as_micros(),
+            sample.rate(),
+            sample.measured
+        ));
+    }
+    csv
+}
+
+/// Bracha broadcast runs whose latency and traffic are compared from one
+/// build to the other
+#[derive(Clone, Debug, PartialEq)]
+pub struct BroadcastBenchmark {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kind: MaliciousKind,
+    pub runs: usize,
+    pub delay: Duration,
+    pub seed: u64,
+}
+
+impl Default for BroadcastBenchmark {
+    fn default() -> Self {
+        BroadcastBenchmark {
+            num_nodes: 10,
+            num_malicious: 3,
+            kind: MaliciousKind::Random,
+            runs: 20,
+            delay: Duration::from_millis(1),
+            seed: 0,
+        }
+    }
+}
+
+impl BroadcastBenchmark {
+    /// Benchmark that produced a baseline
+    pub fn from_manifest(manifest: &Manifest) -> Result<Self, String> {
+        let get = |key: &str| {
+            manifest
+                .get(key)
+                .ok_or_else(|| format!("No {} in the manifest", key))
+        };
+        let parse = |key: &str| -> Result<u64, String> {
+            get(key)?
+                .parse()
+                .map_err(|_| format!("Invalid {} in the manifest", key))
+        };
+        if manifest.get("benchmark") != Some("broadcast") {
+            return Err(String::from("Not a broadcast benchmark"));
+        }
+        Ok(BroadcastBenchmark {
+            num_nodes: parse("num_nodes")? as usize,
+            num_malicious: parse("num_malicious")? as usize,
+            kind: get("kind")?.parse()?,
+            runs: parse("runs")? as usize,
+            delay: Duration::from_micros(parse("latency_us")?),
+            seed: manifest.seed.ok_or("No seed in the manifest")?,
+        })
+    }
+
+    /// Samples of latency, messages and bytes, one per run
+    pub fn run(&self) -> Baseline {
+        let mut network = Network::with_rng(
+            self.num_nodes,
+            self.num_malicious,
+            self.kind.clone(),
+            RngSource::Seeded(self.seed),
+        );
+        network.set_config(NetworkConfig {
+            drop_rate: 0.0,
+            latency: Latency::Constant(self.delay),
+            ..NetworkConfig::default()
+        });
+
+        let mut baseline = Baseline::new(self.manifest());
+        for _ in 0..self.runs {
+            if !network.bracha_broadcast(7, 0).0 {
+                warn!("Benchmark run failed");
+            }
+            let stats = network.stats();
+            baseline.record("latency_us", stats.elapsed().as_micros() as f64);
+            baseline.record("messages", stats.total_messages() as f64);
+            baseline.record("bytes", stats.total_bytes() as f64);
+        }
+        network.close();
+        baseline
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let mut manifest = Manifest::new()
+            .with("benchmark", "broadcast")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kind", format!("{:?}", self.kind).to_lowercase())
+            .with("runs", self.runs)
+            .with("latency_us", self.delay.as_micros())
+            .with("schedule", "concurrent");
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Rollbacks of speculative replicas under some latency variation and
+/// number of faulty nodes
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpeculationPoint {
+    pub jitter: Duration,
+    pub num_malicious: usize,
+    pub runs: usize,
+    // Runs where every good replica ended in the same state
+    pub successes: usize,
+    // Commands committed by the good replicas over all the runs
+    pub commits: usize,
+    pub speculated: usize,
+    pub rollbacks: usize,
+    // Speculative executions undone by the rollbacks
+    pub undone: usize,
+    pub duration: Duration,
+}
+
+impl SpeculationPoint {
+    /// Rollbacks per committed command
+    pub fn rollback_rate(&self) -> f64 {
+        match self.commits {
+            0 => 0.0,
+            commits => self.rollbacks as f64 / commits as f64,
+        }
+    }
+}
+
+/// Run a speculative replicated state machine, every good node submitting
+/// a command, with more and more reordering and faulty nodes
+#[derive(Clone, Debug)]
+pub struct SpeculationExperiment {
+    pub num_nodes: usize,
+    pub malicious: Vec<usize>,
+    pub kind: MaliciousKind,
+    pub delay: Duration,
+    pub jitters: Vec<Duration>,
+    pub runs: usize,
+    pub time_limit: Duration,
+    pub seed: u64,
+}
+
+impl Default for SpeculationExperiment {
+    fn default() -> Self {
+        SpeculationExperiment {
+            num_nodes: 10,
+            malicious: vec![0, 3],
+            kind: MaliciousKind::Silent,
+            delay: Duration::from_millis(1),
+            jitters: vec![
+                Duration::ZERO,
+                Duration::from_millis(1),
+                Duration::from_millis(5),
+            ],
+            runs: 5,
+            time_limit: Duration::from_secs(5),
+            seed: 0,
+        }
+    }
+}
+
+impl SpeculationExperiment {
+    pub fn run(&self) -> Vec<SpeculationPoint> {
+        let mut checkpoint = Checkpoint::new(self.manifest());
+        self.resume(&mut checkpoint).unwrap()
+    }
+
+    /// Run the (faulty nodes, jitter) couples `checkpoint` doesn't have
+    /// yet, recording them as they complete
+    /// Couples of the other shards are left out of the points
+    pub fn resume(&self, checkpoint: &mut Checkpoint) -> Result<Vec<SpeculationPoint>, String> {
+        let mut points = vec![];
+        for (m, &num_malicious) in self.malicious.iter().enumerate() {
+            let mut network = Network::with_rng(
+                self.num_nodes,
+                num_malicious,
+                self.kind.clone(),
+                RngSource::Seeded(self.seed),
+            );
+            network.set_time_limit(self.time_limit);
+            network.set_smr_config(SmrConfig {
+                speculative: true,
+                ..SmrConfig::default()
+            });
+            let inputs = (0..self.num_nodes).map(|id| (id, id + 1)).collect();
+            for (j, &jitter) in self.jitters.iter().enumerate() {
+                let name = format!("{} {}us", num_malicious, jitter.as_micros());
+                if let Some([successes, commits, speculated, rollbacks, undone, us]) =
+                    checkpoint.get::<[u64; 6]>(&name)
+                {
+                    points.push(SpeculationPoint {
+                        jitter,
+                        num_malicious,
+                        runs: self.runs,
+                        successes: successes as usize,
+                        commits: commits as usize,
+                        speculated: speculated as usize,
+                        rollbacks: rollbacks as usize,
+                        undone: undone as usize,
+                        duration: Duration::from_micros(us),
+                    });
+                    continue;
+                }
+                if !checkpoint.assigned(m * self.jitters.len() + j) {
+                    continue;
+                }
+                network.set_config(NetworkConfig {
+                    latency: Latency::Constant(self.delay),
+                    jitter,
+                    ..NetworkConfig::default()
+                });
+                let mut point = SpeculationPoint {
+                    jitter,
+                    num_malicious,
+                    runs: self.runs,
+                    successes: 0,
+                    commits: 0,
+                    speculated: 0,
+                    rollbacks: 0,
+                    undone: 0,
+                    duration: Duration::ZERO,
+                };
+                for _ in 0..self.runs {
+                    let (success, results) = network.smr(&inputs);
+                    point.successes += success as usize;
+                    point.commits += results.len() * (self.num_nodes - num_malicious);
+                    point.speculated += network.counter("speculated").values().sum::<usize>();
+                    point.rollbacks += network.counter("rollbacks").values().sum::<usize>();
+                    point.undone += network.counter("undone").values().sum::<usize>();
+                    point.duration += network.stats().elapsed();
+                }
+                info!(
+                    "Jitter {:?}, {} faulty: {:.3} rollbacks per commit",
+                    jitter,
+                    num_malicious,
+                    point.rollback_rate()
+                );
+                let counts = [
+                    point.successes as u64,
+                    point.commits as u64,
+                    point.speculated as u64,
+                    point.rollbacks as u64,
+                    point.undone as u64,
+                    point.duration.as_micros() as u64,
+                ];
+                checkpoint.record(&name, &counts)?;
+                points.push(point);
+            }
+            network.close();
+        }
+        Ok(points)
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let malicious: Vec<String> = self.malicious.iter().map(usize::to_string).collect();
+        let jitters: Vec<String> = self
+            .jitters
+            .iter()
+            .map(|jitter| jitter.as_micros().to_string())
+            .collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "speculation")
+            .with("num_nodes", self.num_nodes)
+            .with("malicious", malicious.join(" "))
+            .with("kind", format!("{:?}", self.kind))
+            .with("latency", format!("{:?}", self.delay))
+            .with("jitters_us", jitters.join(" "))
+            .with("runs", self.runs)
+            .with("schedule", "concurrent");
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Speculation experiment as CSV, after its manifest
+pub fn speculation_csv(points: &[SpeculationPoint], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str(
+        "jitter_us,num_malicious,runs,successes,commits,speculated,rollbacks,undone,rollback_rate,duration_us\n",
+    );
+    for point in points {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{:.4},{}\n",
+            point.jitter.as_micros(),
+            point.num_malicious,
+            point.runs,
+            point.successes,
+            point.commits,
+            point.speculated,
+            point.rollbacks,
+            point.undone,
+            point.rollback_rate(),
+            point.duration.as_micros()
+        ));
+    }
+    csv
+}
+
+/// Throughput and client latency of the replicated state machine under
+/// some offered load
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadPoint {
+    pub arrivals: Arrivals,
+    pub runs: usize,
+    pub successes: usize,
+    // Commands committed over all the runs
+    pub commands: usize,
+    pub abandoned: usize,
+    // Submissions the leader rejected for lack of room in its mempool
+    pub rejected: usize,
+    pub duration: Duration,
+    // Submission to reply of every command answered, sorted
+    pub latencies: Vec<Duration>,
+}
+
+impl LoadPoint {
+    /// Commands committed per second
+    pub fn throughput(&self) -> f64 {
+        match self.duration.as_secs_f64() {
+            secs if secs > 0.0 => self.commands as f64 / secs,
+            _ => 0.0,
+        }
+    }
+
+    /// Latency under which `p` percent of the commands were answered
+    pub fn latency(&self, p: f64) -> Duration {
+        if self.latencies.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies[rank.clamp(1, self.latencies.len()) - 1]
+    }
+}
+
+/// Sweep the load offered to the replicated state machine by the clients of
+/// the good nodes: open loops at every rate, then closed loops with every
+/// number of outstanding commands
+#[derive(Clone, Debug)]
+pub struct LoadExperiment {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kind: MaliciousKind,
+    pub delay: Duration,
+    pub client: ClientConfig,
+    // Commands of every client per run
+    pub commands: usize,
+    // Commands per second of every open-loop client
+    pub rates: Vec<f64>,
+    pub outstanding: Vec<usize>,
+    pub think: Duration,
+    // Capacity of the mempool of the leader, unbounded if None
+    pub mempool: Option<usize>,
+    pub runs: usize,
+    pub time_limit: Duration,
+    pub seed: u64,
+}
+
+impl Default for LoadExperiment {
+    fn default() -> Self {
+        LoadExperiment {
+            num_nodes: 4,
+            num_malicious: 0,
+            kind: MaliciousKind::Silent,
+            delay: Duration::from_millis(1),
+            client: ClientConfig::default(),
+            commands: 20,
+            rates: vec![100.0, 200.0, 400.0, 800.0],
+            outstanding: vec![1, 2, 4, 8],
+            think: Duration::ZERO,
+            mempool: None,
+            runs: 3,
+            time_limit: Duration::from_secs(10),
+            seed: 0,
+        }
+    }
+}
+
+impl LoadExperiment {
+    pub fn run(&self) -> Vec<LoadPoint> {
+        let mut checkpoint = Checkpoint::new(self.manifest());
+        self.resume(&mut checkpoint).unwrap()
+    }
+
+    /// Run the arrival processes `checkpoint` doesn't have yet, recording
+    /// them as they complete
+    /// A process is saved as its counters, its duration and the latencies
+    /// of its commands in nanoseconds
+    /// Processes of the other shards are left out of the points
+    pub fn resume(&self, checkpoint: &mut Checkpoint) -> Result<Vec<LoadPoint>, String> {
+        // Created for the first process left to run
+        let mut network: Option<Network> = None;
+        let inputs = (0..self.num_nodes).map(|id| (id, id + 1)).collect();
+        let open = self.rates.iter().map(|&rate| Arrivals::Open { rate });
+        let closed = self.outstanding.iter().map(|&outstanding| Arrivals::Closed {
+            outstanding,
+            think: self.think,
+        });
+
+        let mut points = vec![];
+        for (position, arrivals) in open.chain(closed).enumerate() {
+            let name = arrivals.to_string();
+            match checkpoint.get::<Vec<u64>>(&name) {
+                Some(fields) if fields.len() >= 5 => {
+                    points.push(LoadPoint {
+                        arrivals,
+                        runs: self.runs,
+                        successes: fields[0] as usize,
+                        commands: fields[1] as usize,
+                        abandoned: fields[2] as usize,
+                        rejected: fields[3] as usize,
+                        duration: Duration::from_nanos(fields[4]),
+                        latencies: fields[5..].iter().copied().map(Duration::from_nanos).collect(),
+                    });
+                    continue;
+                }
+                Some(fields) => {
+                    warn!("Running {} again, invalid checkpoint: {} fields", name, fields.len())
+                }
+                None => (),
+            }
+            if !checkpoint.assigned(position) {
+                continue;
+            }
+            let network = network.get_or_insert_with(|| {
+                let mut network = Network::with_rng(
+                    self.num_nodes,
+                    self.num_malicious,
+                    self.kind.clone(),
+                    RngSource::Seeded(self.seed),
+                );
+                network.set_time_limit(self.time_limit);
+                network.set_config(NetworkConfig {
+                    latency: Latency::Constant(self.delay),
+                    ..NetworkConfig::default()
+                });
+                network
+            });
+            network.set_smr_config(SmrConfig {
+                client: Some(self.client.clone()),
+                load: Some(LoadConfig {
+                    arrivals,
+                    commands: self.commands,
+                }),
+                mempool: self.mempool,
+                ..SmrConfig::default()
+            });
+            let mut point = LoadPoint {
+                arrivals,
+                runs: self.runs,
+                successes: 0,
+                commands: 0,
+                abandoned: 0,
+                rejected: 0,
+                duration: Duration::ZERO,
+                latencies: vec![],
+            };
+            for _ in 0..self.runs {
+                let (success, _) = network.smr(&inputs);
+                point.successes += success as usize;
+                if success {
+                    point.commands += self.commands * (self.num_nodes - self.num_malicious);
+                }
+                point.abandoned += network.counter("abandoned").values().sum::<usize>();
+                point.rejected += network.counter("rejected").values().sum::<usize>();
+                point.duration += network.stats().elapsed();
+                point.latencies.extend(
+                    network
+                        .samples("client_latency_us")
+                        .into_iter()
+                        .map(|(_, us)| Duration::from_micros(us as u64)),
+                );
+            }
+            point.latencies.sort();
+            info!(
+                "{}: {:.0} commands/s, p50 {:?}",
+                arrivals,
+                point.throughput(),
+                point.latency(50.0)
+            );
+            let mut fields = vec![
+                point.successes as u64,
+                point.commands as u64,
+                point.abandoned as u64,
+                point.rejected as u64,
+                point.duration.as_nanos() as u64,
+            ];
+            fields.extend(point.latencies.iter().map(|latency| latency.as_nanos() as u64));
+            checkpoint.record(&name, &fields)?;
+            points.push(point);
+        }
+        if let Some(network) = network {
+            network.close();
+        }
+        Ok(points)
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let rates: Vec<String> = self.rates.iter().map(f64::to_string).collect();
+        let outstanding: Vec<String> = self.outstanding.iter().map(usize::to_string).collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "load")
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kind", format!("{:?}", self.kind))
+            .with("latency", format!("{:?}", self.delay))
+            .with("client_timeout", format!("{:?}", self.client.timeout))
+            .with("client_retries", self.client.retries)
+            .with("commands", self.commands)
+            .with("rates", rates.join(" "))
+            .with("outstanding", outstanding.join(" "))
+            .with("think", format!("{:?}", self.think))
+            .with(
+                "mempool",
+                self.mempool.map_or("unbounded".to_string(), |k| k.to_string()),
+            )
+            .with("runs", self.runs)
+            .with("schedule", "concurrent");
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Load experiment as CSV, after its manifest
+/// Open loops have no outstanding commands, closed loops no rate
+pub fn load_csv(points: &[LoadPoint], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str(
+        "process,rate,outstanding,runs,successes,commands,abandoned,rejected,throughput,latency_p50_us,latency_p99_us,duration_us\n",
+    );
+    for point in points {
+        let (process, rate, outstanding) = match point.arrivals {
+            Arrivals::Open { rate } => ("open", rate.to_string(), String::new()),
+            Arrivals::Closed { outstanding, .. } => {
+                ("closed", String::new(), outstanding.to_string())
+            }
+        };
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{},{},{:.1},{},{},{}\n",
+            process,
+            rate,
+            outstanding,
+            point.runs,
+            point.successes,
+            point.commands,
+            point.abandoned,
+            point.rejected,
+            point.throughput(),
+            point.latency(50.0).as_micros(),
+            point.latency(99.0).as_micros(),
+            point.duration.as_micros()
+        ));
+    }
+    csv
+}
+
+/// Quorum formation of a broadcast with some slow nodes, led by a fast or a
+/// slow node
+#[derive(Clone, Debug, PartialEq)]
+pub struct HeterogeneityPoint {
+    pub num_slow: usize,
+    pub slow_leader: bool,
+    pub runs: usize,
+    pub successes: usize,
+    // Means over the runs of the median time the nodes formed their quorum
+    // of ECHO and sent READY, and of the median and last deliveries
+    pub ready: Duration,
+    pub delivered: Duration,
+    pub last_delivered: Duration,
+}
+
+/// Run Bracha broadcasts with a CPU model where the last nodes are slower
+/// than the others, the leader being node 0, fast, or the last node, slow
+#[derive(Clone, Debug)]
+pub struct HeterogeneityExperiment {
+    pub num_nodes: usize,
+    pub slow: Vec<usize>,
+    // Speed multiplier of the slow nodes
+    pub slow_speed: f64,
+    pub per_message: Duration,
+    pub delay: Duration,
+    pub runs: usize,
+    pub seed: u64,
+}
+
+impl Default for HeterogeneityExperiment {
+    fn default() -> Self {
+        HeterogeneityExperiment {
+            num_nodes: 10,
+            slow: vec![0, 1, 3, 5],
+            slow_speed: 0.2,
+            per_message: Duration::from_micros(100),
+            delay: Duration::from_millis(1),
+            runs: 5,
+            seed: 0,
+        }
+    }
+}
+
+impl HeterogeneityExperiment {
+    pub fn run(&self) -> Vec<HeterogeneityPoint> {
+        let mut checkpoint = Checkpoint::new(self.manifest());
+        self.resume(&mut checkpoint).unwrap()
+    }
+
+    /// Run the (slow nodes, leader) couples `checkpoint` doesn't have yet,
+    /// recording them as they complete
+    /// Couples of the other shards are left out of the points
+    pub fn resume(&self, checkpoint: &mut Checkpoint) -> Result<Vec<HeterogeneityPoint>, String> {
+        // Created for the first couple left to run
+        let mut network: Option<Network> = None;
+        // Slow leaders only once there are slow nodes
+        let couples: Vec<(usize, bool)> = self
+            .slow
+            .iter()
+            .map(|&num_slow| num_slow.min(self.num_nodes))
+            .flat_map(|num_slow| [(num_slow, false), (num_slow, true)])
+            .filter(|&(num_slow, slow_leader)| num_slow > 0 || !slow_leader)
+            .collect();
+        let mut points = vec![];
+        for (position, &(num_slow, slow_leader)) in couples.iter().enumerate() {
+            let name = format!("{} slow, slow leader {}", num_slow, slow_leader);
+            if let Some([successes, ready, delivered, last_delivered]) =
+                checkpoint.get::<[u64; 4]>(&name)
+            {
+                points.push(HeterogeneityPoint {
+                    num_slow,
+                    slow_leader,
+                    runs: self.runs,
+                    successes: successes as usize,
+                    ready: Duration::from_nanos(ready),
+                    delivered: Duration::from_nanos(delivered),
+                    last_delivered: Duration::from_nanos(last_delivered),
+                });
+                continue;
+            }
+            if !checkpoint.assigned(position) {
+                continue;
+            }
+            let network = network.get_or_insert_with(|| {
+                Network::with_rng(
+                    self.num_nodes,
+                    0,
+                    MaliciousKind::Silent,
+                    RngSource::Seeded(self.seed),
+                )
+            });
+            let cpu = (self.num_nodes - num_slow..self.num_nodes).fold(
+                CpuModel::uniform(self.per_message),
+                |cpu, id| cpu.with_speed(id, self.slow_speed),
+            );
+            network.set_config(NetworkConfig {
+                latency: Latency::Constant(self.delay),
+                cpu: Some(cpu),
+                ..NetworkConfig::default()
+            });
+            let leader = if slow_leader { self.num_nodes - 1 } else { 0 };
+            let mut point = HeterogeneityPoint {
+                num_slow,
+                slow_leader,
+                runs: self.runs,
+                successes: 0,
+                ready: Duration::ZERO,
+                delivered: Duration::ZERO,
+                last_delivered: Duration::ZERO,
+            };
+            for _ in 0..self.runs {
+                let (success, _) = network.bracha_broadcast(7, leader);
+                point.successes += success as usize;
+                let stats = network.stats();
+                if let Some(ready) = stats.latency(Milestone::FirstReady) {
+                    point.ready += ready.percentile(50.0);
+                }
+                if let Some(delivered) = stats.latency(Milestone::Delivered) {
+                    point.delivered += delivered.percentile(50.0);
+                    point.last_delivered += delivered.max();
+                }
+            }
+            let runs = self.runs.max(1) as u32;
+            point.ready /= runs;
+            point.delivered /= runs;
+            point.last_delivered /= runs;
+            info!(
+                "{} slow nodes, slow leader {}: median delivery {:?}",
+                num_slow, slow_leader, point.delivered
+            );
+            let counts = [
+                point.successes as u64,
+                point.ready.as_nanos() as u64,
+                point.delivered.as_nanos() as u64,
+                point.last_delivered.as_nanos() as u64,
+            ];
+            checkpoint.record(&name, &counts)?;
+            points.push(point);
+        }
+        if let Some(network) = network {
+            network.close();
+        }
+        Ok(points)
+    }
+
+    pub fn manifest(&self) -> Manifest {
+        let slow: Vec<String> = self.slow.iter().map(usize::to_string).collect();
+        let mut manifest = Manifest::new()
+            .with("experiment", "heterogeneity")
+            .with("num_nodes", self.num_nodes)
+            .with("slow", slow.join(" "))
+            .with("slow_speed", self.slow_speed)
+            .with("per_message", format!("{:?}", self.per_message))
+            .with("latency", format!("{:?}", self.delay))
+            .with("runs", self.runs)
+            .with("schedule", "concurrent");
+        manifest.seed = Some(self.seed);
+        manifest
+    }
+}
+
+/// Heterogeneity experiment as CSV, after its manifest
+pub fn heterogeneity_csv(points: &[HeterogeneityPoint], manifest: &Manifest) -> String {
+    let mut csv = manifest.header("#");
+    csv.push_str("num_slow,slow_leader,runs,successes,ready_us,delivered_us,last_delivered_us\n");
+    for point in points {
+        csv.push_str(&format!(
+            "{},{},{},{},{},{},{}\n",
+            point.num_slow,
+            point.slow_leader,
+            point.runs,
+            point.successes,
+            point.ready.as_micros(),
+            point.delivered.as_micros(),
+            point.last_delivered.as_micros()
+        ));
+    }
+    csv
+}
@@ -0,0 +1,132 @@
+use crate::network::{Value, NETWORK_ID};
+use crate::node::NodeId;
+use crate::protocols::InstanceId;
+use std::fmt;
+use std::sync::Arc;
+
+/// Message as an honest node receives it, before any instance handles it
+#[derive(Clone, Debug)]
+pub struct Inbound<'a> {
+    pub from: NodeId,
+    pub instance: &'a InstanceId,
+    // Value carried, None for messages without one
+    pub value: Option<Value>,
+    // Whether the node runs or can run the protocol of the instance
+    pub runnable: bool,
+}
+
+/// Why an honest node dropped a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Rejection {
+    // Sender is neither a node nor the network
+    UnknownSender,
+    // Value is outside of the values of the application
+    OutOfRange,
+    // Instance is not one the nodes start
+    WrongInstance,
+}
+
+impl Rejection {
+    /// Counter of the messages rejected for this reason
+    pub fn counter(&self) -> &'static str {
+        match self {
+            Rejection::UnknownSender => "rejected_unknown_sender",
+            Rejection::OutOfRange => "rejected_out_of_range",
+            Rejection::WrongInstance => "rejected_wrong_instance",
+        }
+    }
+}
+
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Rejection::UnknownSender => write!(f, "unknown sender"),
+            Rejection::OutOfRange => write!(f, "out-of-range value"),
+            Rejection::WrongInstance => write!(f, "wrong instance"),
+        }
+    }
+}
+
+/// Input validation of the messages honest nodes receive, standing for the
+/// decoding and sanity checks of a real implementation: protocol code only
+/// sees the messages that pass it
+pub trait InboundFilter: fmt::Display + Send + Sync {
+    /// Why `node` of a network of `num_nodes` nodes drops `msg`, None if it
+    /// accepts it
+    fn check(&self, node: NodeId, num_nodes: usize, msg: &Inbound) -> Option<Rejection>;
+}
+
+/// Every message is accepted
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AcceptAll;
+
+impl InboundFilter for AcceptAll {
+    fn check(&self, _node: NodeId, _num_nodes: usize, _msg: &Inbound) -> Option<Rejection> {
+        None
+    }
+}
+
+impl fmt::Display for AcceptAll {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "accept all")
+    }
+}
+
+/// Basic checks: the sender is a node or the network, the value is at
+/// most `max_value` if set, and the instance is of a protocol the node
+/// runs, under a root instance the network starts
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SanityChecks {
+    pub max_value: Option<Value>,
+}
+
+impl InboundFilter for SanityChecks {
+    fn check(&self, _node: NodeId, num_nodes: usize, msg: &Inbound) -> Option<Rejection> {
+        if msg.from >= num_nodes && msg.from != NETWORK_ID {
+            return Some(Rejection::UnknownSender);
+        }
+        if !msg.runnable || msg.instance.root_tag() != 0 {
+            return Some(Rejection::WrongInstance);
+        }
+        match (msg.value, self.max_value) {
+            (Some(v), Some(max)) if v > max => Some(Rejection::OutOfRange),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SanityChecks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.max_value {
+            Some(max) => write!(f, "sanity checks, values up to {}", max),
+            None => write!(f, "sanity checks"),
+        }
+    }
+}
+
+/// Filter of the honest nodes, every message is accepted by default
+#[derive(Clone)]
+pub struct FilterConfig {
+    pub filter: Arc<dyn InboundFilter>,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        FilterConfig {
+            filter: Arc::new(AcceptAll),
+        }
+    }
+}
+
+impl fmt::Debug for FilterConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.filter)
+    }
+}
+
+/// Filters are told apart by their description
+impl PartialEq for FilterConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.filter.to_string() == other.filter.to_string()
+    }
+}
@@ -0,0 +1,183 @@
+use crate::node::NodeId;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+// Median round-trip times in milliseconds between some AWS regions, as
+// reported by public inter-region ping measurements
+const AWS_RTT_CSV: &str = "\
+region,us-east-1,us-west-2,eu-west-1,ap-northeast-1,ap-southeast-2,sa-east-1
+us-east-1,0,67,68,145,198,115
+us-west-2,67,0,125,97,139,178
+eu-west-1,68,125,0,205,255,180
+ap-northeast-1,145,97,205,0,105,255
+ap-southeast-2,198,139,255,105,0,310
+sa-east-1,115,178,180,255,310,0
+";
+
+/// Round-trip times between regions
+#[derive(Clone, Debug, PartialEq)]
+pub struct RttMatrix {
+    pub regions: Vec<String>,
+    // rtt_ms[i][j]: round-trip time from region i to region j
+    pub rtt_ms: Vec<Vec<f64>>,
+}
+
+impl RttMatrix {
+    /// Inter-region RTTs of a public cloud, shipped with the crate
+    pub fn aws() -> Self {
+        RttMatrix::from_matrix_csv(AWS_RTT_CSV).unwrap()
+    }
+
+    /// Parse a square matrix: a header line with the region names, then one
+    /// line per region starting with its name
+    pub fn from_matrix_csv(csv: &str) -> Result<Self, String> {
+        let mut lines = csv.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().ok_or("Empty RTT matrix")?;
+        let regions: Vec<String> = header
+            .split(',')
+            .skip(1)
+            .map(|region| region.trim().to_string())
+            .collect();
+
+        let mut rtt_ms = vec![];
+        for (i, line) in lines.enumerate() {
+            let mut cells = line.split(',');
+            let region = cells.next().unwrap_or_default().trim();
+            if regions.get(i).map(String::as_str) != Some(region) {
+                return Err(format!(
+                    "Line {} should describe region {:?}",
+                    i + 2,
+                    regions.get(i)
+                ));
+            }
+            let row = cells
+                .map(|cell| cell.trim().parse::<f64>())
+                .collect::<Result<Vec<f64>, _>>()
+                .map_err(|e| format!("Line {}: {}", i + 2, e))?;
+            if row.len() != regions.len() {
+                return Err(format!("Line {} should have {} RTTs", i + 2, regions.len()));
+            }
+            rtt_ms.push(row);
+        }
+
+        if rtt_ms.len() != regions.len() {
+            return Err(format!(
+                "Expected {} regions, found {}",
+                regions.len(),
+                rtt_ms.len()
+            ));
+        }
+        Ok(RttMatrix { regions, rtt_ms })
+    }
+
+    /// Parse measurements given as `source,destination,rtt_ms` lines, the
+    /// format of most public ping datasets
+    /// Missing directions are assumed symmetric, repeated pairs are averaged
+    pub fn from_pairs_csv(csv: &str) -> Result<Self, String> {
+        let mut measurements = vec![];
+        let mut regions: Vec<String> = vec![];
+        for (i, line) in csv.lines().enumerate() {
+            let cells: Vec<&str> = line.split(',').map(str::trim).collect();
+            if cells.len() != 3 {
+                continue;
+            }
+            let rtt = match cells[2].parse::<f64>() {
+                Ok(rtt) => rtt,
+                // Header line
+                Err(_) if i == 0 => continue,
+                Err(e) => return Err(format!("Line {}: {}", i + 1, e)),
+            };
+            for region in &cells[..2] {
+                if !regions.iter().any(|r| r == region) {
+                    regions.push(region.to_string());
+                }
+            }
+            let from = regions.iter().position(|r| r == cells[0]).unwrap();
+            let to = regions.iter().position(|r| r == cells[1]).unwrap();
+            measurements.push((from, to, rtt));
+        }
+
+        let n = regions.len();
+        let mut sums = vec![vec![(0.0, 0); n]; n];
+        for (from, to, rtt) in measurements {
+            sums[from][to].0 += rtt;
+            sums[from][to].1 += 1;
+        }
+        let mut rtt_ms = vec![vec![0.0; n]; n];
+        for i in 0..n {
+            for j in 0..n {
+                let (sum, count) = if sums[i][j].1 > 0 {
+                    sums[i][j]
+                } else {
+                    sums[j][i]
+                };
+                if i != j && count == 0 {
+                    return Err(format!("No RTT between {} and {}", regions[i], regions[j]));
+                }
+                rtt_ms[i][j] = if count > 0 { sum / count as f64 } else { 0.0 };
+            }
+        }
+        Ok(RttMatrix { regions, rtt_ms })
+    }
+
+    /// Load a user-provided matrix or pairs CSV file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let csv = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        RttMatrix::from_matrix_csv(&csv).or_else(|_| RttMatrix::from_pairs_csv(&csv))
+    }
+
+    /// One-way latency between two regions
+    pub fn latency(&self, from: usize, to: usize) -> Duration {
+        // Half of the round trip, in microseconds
+        Duration::from_micros((self.rtt_ms[from][to] * 500.0).round() as u64)
+    }
+}
+
+/// Time messages spend in transit
+#[derive(Clone, Debug, PartialEq)]
+pub enum Latency {
+    Constant(Duration),
+    // Latency between the regions of the nodes, `regions[id]` is the region
+    // of node `id` in the matrix
+    Geo {
+        matrix: RttMatrix,
+        regions: Vec<usize>,
+    },
+}
+
+impl Latency {
+    /// Spread `num_nodes` nodes over the regions of `matrix` in turn
+    pub fn geo(matrix: RttMatrix, num_nodes: usize) -> Self {
+        let regions = (0..num_nodes).map(|id| id % matrix.regions.len()).collect();
+        Latency::Geo { matrix, regions }
+    }
+
+    pub fn delay(&self, from: NodeId, to: NodeId) -> Duration {
+        match self {
+            Latency::Constant(delay) => *delay,
+            Latency::Geo { matrix, regions } => match (regions.get(from), regions.get(to)) {
+                (Some(from), Some(to)) => matrix.latency(*from, *to),
+                // Messages from the network itself
+                _ => Duration::ZERO,
+            },
+        }
+    }
+}
+
+/// Constant delay, or the region of every node
+impl fmt::Display for Latency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Latency::Constant(delay) => write!(f, "{:?}", delay),
+            Latency::Geo { matrix, regions } => {
+                let regions: Vec<&str> = regions
+                    .iter()
+                    .map(|region| matrix.regions[*region].as_str())
+                    .collect();
+                write!(f, "geo[{}]", regions.join(" "))
+            }
+        }
+    }
+}
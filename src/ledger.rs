@@ -0,0 +1,176 @@
+use crate::network::NetworkMessage;
+use crate::node::NodeId;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Unique id of a message routed during a run, in creation order
+pub type MessageId = usize;
+
+/// Id of the messages the network doesn't route: control messages and
+/// messages not relayed yet
+pub const UNTRACKED: MessageId = MessageId::MAX;
+
+/// Why the network discarded a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DropCause {
+    // Random loss of the link
+    Link,
+    // Drop request of the controller
+    Controller,
+    // Discarded by the man-in-the-middle or by authentication
+    Attack,
+    // Destination node is gone
+    DeadNode,
+    // Delivery left out of a replay
+    Omitted,
+    // Beyond the rate limit of the receiver on the sender
+    Throttled,
+}
+
+/// What became of a message
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fate {
+    InFlight,
+    Delivered,
+    Dropped(DropCause),
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    from: NodeId,
+    to: NodeId,
+    fate: Fate,
+}
+
+/// Every message routed by the network during a run, from its creation to
+/// its delivery or drop
+#[derive(Clone, Debug, Default)]
+pub struct Ledger {
+    entries: Vec<Entry>,
+    // Messages delivered or dropped more than once
+    resolved_twice: Vec<MessageId>,
+}
+
+/// Post-run check that no message vanished inside the network:
+/// created = delivered + dropped + in flight at the end
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Conservation {
+    pub created: usize,
+    pub delivered: usize,
+    pub dropped: BTreeMap<DropCause, usize>,
+    pub in_flight: usize,
+    // Messages neither delivered, dropped nor held by the network
+    pub missing: Vec<MessageId>,
+    // Messages delivered or dropped more than once, or still held once
+    // delivered or dropped
+    pub duplicated: Vec<MessageId>,
+}
+
+impl Ledger {
+    pub fn new() -> Self {
+        Ledger::default()
+    }
+
+    /// Give `msg` the next id
+    pub(crate) fn create(&mut self, msg: &mut NetworkMessage) {
+        msg.id = self.entries.len();
+        self.entries.push(Entry {
+            from: msg.from,
+            to: msg.to,
+            fate: Fate::InFlight,
+        });
+    }
+
+    pub(crate) fn deliver(&mut self, id: MessageId) {
+        self.resolve(id, Fate::Delivered);
+    }
+
+    pub(crate) fn drop(&mut self, id: MessageId, cause: DropCause) {
+        self.resolve(id, Fate::Dropped(cause));
+    }
+
+    fn resolve(&mut self, id: MessageId, fate: Fate) {
+        match self.entries.get_mut(id) {
+            Some(entry) if entry.fate == Fate::InFlight => entry.fate = fate,
+            Some(_) => self.resolved_twice.push(id),
+            None => (),
+        }
+    }
+
+    /// Number of messages created during the run
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn fate(&self, id: MessageId) -> Option<Fate> {
+        self.entries.get(id).map(|entry| entry.fate)
+    }
+
+    /// Sender and destination of message `id`
+    pub fn link(&self, id: MessageId) -> Option<(NodeId, NodeId)> {
+        self.entries.get(id).map(|entry| (entry.from, entry.to))
+    }
+
+    /// Balance of the run, `held` being the messages still buffered by the
+    /// network
+    pub(crate) fn audit(&self, held: &[MessageId]) -> Conservation {
+        let mut conservation = Conservation {
+            created: self.entries.len(),
+            duplicated: self.resolved_twice.clone(),
+            ..Conservation::default()
+        };
+        let held: BTreeSet<MessageId> = held.iter().cloned().collect();
+        for &id in held.iter() {
+            match self.fate(id) {
+                Some(Fate::InFlight) => conservation.in_flight += 1,
+                _ => conservation.duplicated.push(id),
+            }
+        }
+        for (id, entry) in self.entries.iter().enumerate() {
+            match entry.fate {
+                Fate::Delivered => conservation.delivered += 1,
+                Fate::Dropped(cause) => *conservation.dropped.entry(cause).or_default() += 1,
+                Fate::InFlight if !held.contains(&id) => conservation.missing.push(id),
+                Fate::InFlight => (),
+            }
+        }
+        conservation
+    }
+}
+
+impl Conservation {
+    pub fn total_dropped(&self) -> usize {
+        self.dropped.values().sum()
+    }
+
+    pub fn holds(&self) -> bool {
+        self.missing.is_empty()
+            && self.duplicated.is_empty()
+            && self.created == self.delivered + self.total_dropped() + self.in_flight
+    }
+}
+
+impl fmt::Display for Conservation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} messages created, {} delivered, {} dropped {:?}, {} in flight",
+            self.created,
+            self.delivered,
+            self.total_dropped(),
+            self.dropped,
+            self.in_flight
+        )?;
+        if !self.missing.is_empty() {
+            write!(f, ", missing {:?}", self.missing)?;
+        }
+        if !self.duplicated.is_empty() {
+            write!(f, ", duplicated {:?}", self.duplicated)?;
+        }
+        Ok(())
+    }
+}
@@ -1,17 +1,4110 @@
 #![allow(unused_must_use)]
 #![allow(non_camel_case_types)]
 #![allow(dead_code)]
+pub mod adversary;
+pub mod analysis;
+pub mod assertions;
+pub mod artifacts;
+pub mod audit;
+#[cfg(feature = "experiments")]
+pub mod baseline;
+#[cfg(feature = "experiments")]
+pub mod checkpoint;
+pub mod config;
+pub mod controller;
+pub mod cost;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+#[cfg(feature = "experiments")]
+pub mod diff;
+#[cfg(feature = "experiments")]
+pub mod explore;
+pub mod filter;
+pub mod latency;
+pub mod ledger;
+pub mod logs;
+pub mod manifest;
+pub mod merkle;
+pub mod monitor;
 pub mod network;
 pub mod node;
 pub mod protocols;
+pub mod quorum;
+pub mod resources;
+pub mod rng;
+#[cfg(feature = "experiments")]
+pub mod scenario;
+pub mod scheduler;
+pub mod stats;
+pub mod termination;
+pub mod topology;
+pub mod trace;
+pub mod validity;
+pub mod whatif;
 
 
 #[cfg(test)]
 mod tests {
-    use crate::network::Network;
+    use crate::adversary::{LinkAttack, LinkSecurity};
+    use crate::analysis::pivotal_messages;
+    use crate::artifacts::{Artifacts, LAST_EVENTS, SUMMARY_FILE};
+    use crate::assertions::{
+        all_honest_delivered, always, delivered, eventually, messages_from, messages_of_type,
+        never, two_honest_deliver_different, Assertion,
+    };
+    use crate::controller::Knowledge;
+    use crate::config::{
+        Config, CpuModel, NetworkConfig, PanicPolicy, Placement, Preset, TokenBucket, UplinkCap,
+    };
+    #[cfg(feature = "experiments")]
+    use crate::config::PeerLimit;
+    use crate::cost::{CostModel, CryptoOp};
+    #[cfg(feature = "crypto")]
+    use crate::crypto::secret_sharing::{SimulatedSharing, VerifiableSecretSharing};
+    #[cfg(feature = "crypto")]
+    use crate::crypto::threshold_enc::{
+        Ciphertext, DecryptionShare, SimulatedThreshold, ThresholdEncryption,
+    };
+    #[cfg(feature = "crypto")]
+    use crate::crypto::threshold_sig::{SignatureShare, SimulatedSignatures, ThresholdSignature};
+    #[cfg(feature = "experiments")]
+    use crate::diff::{Recording, RunDiff};
+    #[cfg(feature = "experiments")]
+    use crate::baseline::Baseline;
+    #[cfg(feature = "experiments")]
+    use crate::checkpoint::Checkpoint;
+    #[cfg(feature = "experiments")]
+    use crate::explore::{
+        attack_metric, attacks_csv, leaders_csv, AddedLatency, AgreementViolation,
+        AttackComparison, AttackMetric, BroadcastBenchmark, ContourSearch, ExtraMessages,
+        FailureRate, HelpingExperiment, LeaderExperiment, LeaderOutcome, ThroughputExperiment,
+        TieBreakExperiment,
+    };
+    use crate::filter::{Rejection, SanityChecks};
+    use crate::latency::{Latency, RttMatrix};
+    use crate::ledger::{DropCause, Fate, Ledger};
+    use crate::logs::{NodeLogs, INDEX_FILE};
+    use crate::manifest::Manifest;
+    use crate::merkle::MerkleTree;
+    use crate::monitor::Invariant;
+    use crate::network::{Message, Network, NetworkMessage, Output, Value};
+    use crate::node::{
+        AfterDelivery, CrashPoint, FaultSchedule, HandlerSwitch, JoinAt, LateJoin, Lifecycle,
+        MaliciousKind, NodeId, MALICIOUS_VALUE,
+    };
+    use crate::protocols::bracha_broadcast::{BroadcastConfig, Strategy, TieBreak};
+    #[cfg(feature = "crypto")]
+    use crate::protocols::common_coin::message as coin_message;
+    use crate::protocols::common_coin::TrustedDealer;
+    use crate::protocols::flooding::FloodConfig;
+    use crate::protocols::graded_broadcast::Grade;
+    #[cfg(feature = "crypto")]
+    use crate::protocols::honey_badger::HoneyBadgerConfig;
+    use crate::protocols::mempool::{MempoolConfig, MempoolSync};
+    use crate::protocols::paxos::PaxosConfig;
+    #[cfg(feature = "crypto")]
+    use crate::protocols::pbft::PbftConfig;
+    use crate::protocols::phase_king::PhaseKingConfig;
+    use crate::protocols::smr::{Arrivals, ClientConfig, LoadConfig, ReadConsistency, SmrConfig};
+    #[cfg(feature = "crypto")]
+    use crate::protocols::testing::CERTIFYING;
+    use crate::protocols::testing::{
+        EQUIVOCATING, FORGETFUL, PANICKING, RELAY_PANICKING, TICKING, TICK_PERIOD,
+    };
+    #[cfg(feature = "crypto")]
+    use crate::protocols::HONEY_BADGER;
+    #[cfg(feature = "crypto")]
+    use crate::protocols::InstanceId;
+    use crate::protocols::{
+        self, acs, Handler, ProtocolConfig, BATCHED_BROADCAST, BINARY_AGREEMENT, BRACHA_BROADCAST,
+        COMMON_COIN, PAXOS, PHASE_KING, PROTOCOLS, ROUTING,
+    };
+    use crate::quorum::{FaultModel, Parameters, Quorums};
+    use crate::resources::ResourceCounts;
+    use crate::rng::{ReplayRng, Rng, RngSource, SeededRng};
+    #[cfg(feature = "experiments")]
+    use crate::scenario::{self, LeaderPolicy, RecordedScenario, TagExpr, SCENARIOS};
+    use crate::scheduler::Schedule;
+    use crate::trace::TraceEvent;
+    use crate::validity::{AnyValue, MissingData, ValidityOracle};
+    use crate::stats::{Milestone, Phase};
+    use crate::termination::Termination;
+    use crate::topology::Topology;
+    use crate::whatif::{fork, Branch, Snapshot};
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+    use std::time::{Duration, Instant};
+
     #[test]
     fn it_works() {
-        let network = Network::new(10);
-        network.run();
+        let mut network = Network::new(10, 0, MaliciousKind::Silent);
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+    }
+
+    #[test]
+    fn node_threads_are_reused() {
+        let mut network = Network::new(10, 3, MaliciousKind::Random);
+        for v in 1..5 {
+            let (success, _) = network.bracha_broadcast(v, 0);
+            assert!(success);
+        }
+        network.close();
+    }
+
+    #[test]
+    fn recorded_randomness_replays() {
+        let mut network = Network::with_rng(
+            10,
+            3,
+            MaliciousKind::Random,
+            RngSource::Recording(Box::new(RngSource::Seeded(42))),
+        );
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        let randomness = network.randomness();
+        network.close();
+
+        // Only random nodes draw, from their own seeded stream
+        assert!(randomness.draws.keys().all(|id| *id >= 7));
+        let draws = &randomness.draws[&7];
+        let mut seeded = SeededRng::new(42, 7);
+        assert!(draws.iter().all(|draw| *draw == seeded.next_u64()));
+
+        let mut replay = ReplayRng::new(7, draws.clone());
+        assert!(draws.iter().all(|draw| *draw == replay.next_u64()));
+    }
+
+    #[test]
+    fn replay_deterministic_run() {
+        let record = || {
+            let mut network = Network::with_rng(
+                10,
+                3,
+                MaliciousKind::Random,
+                RngSource::Recording(Box::new(RngSource::Seeded(7))),
+            );
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.bracha_broadcast(7, 0);
+            (success, results, network.trace().clone())
+        };
+        let (success, results, trace) = record();
+        assert!(success);
+        // Same seed, same run
+        assert!(record().2.same_deliveries(&trace));
+
+        let mut replay = Network::replay(&trace);
+        let (replay_success, replay_results) = replay.bracha_broadcast(7, 0);
+        assert_eq!((replay_success, replay_results), (success, results));
+        assert!(replay.trace().same_deliveries(&trace));
+    }
+
+    #[test]
+    fn ready_received_before_the_echo_quorum() {
+        let run = |hold: &dyn Fn(&Network)| {
+            let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_time_limit(Duration::from_millis(200));
+            hold(&network);
+            let (success, results) = network.bracha_broadcast(7, 0);
+            network.close();
+            (success, results)
+        };
+        // Node 2 gets the READY of node 0 before the ECHO completing its
+        // quorum, it keeps it for the READY of node 1
+        let (success, results) = run(&|network| {
+            network.controller().delay_link(1, 2, Duration::from_millis(50))
+        });
+        assert!(success, "{:?}", results);
+        // Node 2 never gets the ECHO of node 1, the READY of nodes 0 and 1
+        // have it READY too and make its quorum, no other READY comes
+        let (success, results) = run(&|network| {
+            network
+                .controller()
+                .drop_next(1, |msg| msg.from == 1 && msg.to == 2 && msg.phase == Phase::Echo)
+        });
+        assert!(success, "{:?}", results);
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn contour_of_bracha_broadcast() {
+        let search = ContourSearch {
+            sizes: vec![(4, 1)],
+            delays: vec![Duration::ZERO],
+            runs: 2,
+            precision: 0.25,
+            time_limit: Duration::from_millis(20),
+            ..ContourSearch::default()
+        };
+        let contour = search.run(|network| network.bracha_broadcast(7, 0).0);
+        assert_eq!(contour.len(), 1);
+        // Reliable links never violate the properties, lossy ones always do
+        assert!(contour[0].drop_rate > 0.0);
+        assert!(contour[0].failure_rate >= 0.5);
+    }
+
+    #[test]
+    fn geo_distributed_latency() {
+        let pairs = "source,destination,rtt_ms\nparis,tokyo,220\ntokyo,paris,240\nparis,nyc,80\nnyc,tokyo,160\n";
+        let matrix = RttMatrix::from_pairs_csv(pairs).unwrap();
+        assert_eq!(matrix.regions, vec!["paris", "tokyo", "nyc"]);
+        assert_eq!(matrix.latency(0, 1), Duration::from_millis(110));
+        assert_eq!(matrix.latency(1, 0), Duration::from_millis(120));
+        assert_eq!(matrix.latency(2, 0), Duration::from_millis(40));
+
+        let mut network = Network::new(4, 1, MaliciousKind::Silent);
+        network.set_config(NetworkConfig {
+            latency: Latency::geo(matrix, 4),
+            ..NetworkConfig::default()
+        });
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        // INIT/ECHO then READY have to cross at least one slow link
+        assert!(network.stats().elapsed() >= Duration::from_millis(80));
+    }
+
+    #[test]
+    fn crash_at_the_worst_moment() {
+        for point in [CrashPoint::DuringReady(2), CrashPoint::BeforeDelivery] {
+            let mut network = Network::new(10, 3, MaliciousKind::Crash(point));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.bracha_broadcast(7, 0);
+            assert!(success);
+            // Crashed nodes never deliver
+            assert!(results.keys().all(|id| *id < 7));
+        }
+    }
+
+    #[test]
+    fn leader_message_is_pivotal() {
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(3));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+
+        let report = pivotal_messages(network.trace(), |network| network.bracha_broadcast(7, 0).1);
+        assert_eq!(report.outcome.len(), 4);
+        for id in 0..4 {
+            // Nothing happens without the leader being asked to broadcast
+            assert_eq!(report.pivotal_for(id)[0].index, 0);
+        }
+    }
+
+    #[test]
+    fn what_if_branches() {
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(5));
+        network.set_schedule(Schedule::Deterministic);
+        network.bracha_broadcast(7, 0);
+        let snapshot = Snapshot::take(network.trace(), 4);
+
+        let mut lossy = Branch::new("lossy", 2);
+        lossy.config.drop_rate = 1.0;
+        let branches = [Branch::new("first", 1), Branch::new("second", 2), lossy];
+        let report = fork(&snapshot, &branches, |network| network.bracha_broadcast(7, 0));
+        assert!(report.branches[0].success);
+        assert!(report.branches[1].success);
+        // Nothing sent after the snapshot arrives
+        assert!(!report.branches[2].success);
+    }
+
+    #[test]
+    fn parallel_broadcasts_of_sub_instances() {
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Random, RngSource::Seeded(5));
+        network.set_schedule(Schedule::Deterministic);
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, 100 + id)).collect();
+        let (success, results) = network.parallel_broadcast(&inputs);
+        assert!(success);
+        for output in results.values() {
+            match output {
+                Output::Set(values) => assert!(values.len() >= 5),
+                _ => panic!("Expected a set of values"),
+            }
+        }
+        // Bracha still runs as a root instance
+        assert!(network.bracha_broadcast(7, 0).0);
+    }
+
+    #[test]
+    fn audit_of_honest_states() {
+        let mut network = Network::with_rng(10, 0, MaliciousKind::Silent, RngSource::Seeded(3));
+        network.set_schedule(Schedule::Deterministic);
+        assert!(network.bracha_broadcast(7, 0).0);
+        assert!(network.audit().is_consistent());
+        assert_eq!(network.audit().nodes.len(), 10);
+
+        // Delivery still succeeds but some nodes have echoed another value
+        for to in 1..3 {
+            network.compromise_link(0, to, LinkAttack::Tamper(3));
+        }
+        assert!(network.bracha_broadcast(7, 0).0);
+        let audit = network.audit();
+        assert_eq!(audit.divergences.len(), 1);
+        assert_eq!(audit.divergences[0].field, "echo");
+        assert_eq!(audit.divergences[0].values["3"], vec![1, 2]);
+    }
+
+    #[test]
+    fn manifest_in_artifacts() {
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Mirror, RngSource::Seeded(9));
+        network.set_schedule(Schedule::Deterministic);
+        network.compromise_link(0, 1, LinkAttack::Drop);
+        network.bracha_broadcast(7, 0);
+
+        let csv = network.stats_csv();
+        let manifest = Manifest::parse(&csv, "#").unwrap();
+        assert_eq!(&manifest, &network.trace().manifest);
+        assert_eq!(manifest.crate_version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(manifest.seed, Some(9));
+        assert_eq!(manifest.get("num_nodes"), Some("4"));
+        assert_eq!(manifest.get("schedule"), Some("deterministic"));
+        assert_eq!(manifest.get("compromised_links"), Some("0->1 Drop"));
+        // Data follows the manifest
+        let data: Vec<&str> = csv.lines().filter(|line| !line.starts_with('#')).collect();
+        assert_eq!(data[0], "phase,messages,bytes,duration_us");
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn tie_break_rules_under_byzantine_echoes() {
+        let experiment = TieBreakExperiment {
+            kinds: vec![MaliciousKind::Random],
+            rules: vec![TieBreak::Quorum, TieBreak::Lowest],
+            runs: 10,
+            ..TieBreakExperiment::default()
+        };
+        let outcomes = experiment.run();
+        for outcome in outcomes.iter() {
+            assert_eq!(
+                outcome.leader_wins + outcome.other_wins + outcome.undecided,
+                10
+            );
+        }
+        // Bracha's rule never delivers a value the leader didn't propose,
+        // picking the lowest echoed value lets the Byzantine value through
+        assert_eq!(outcomes[0].other_wins, 0);
+        assert!(outcomes[1].other_wins > 0);
+    }
+
+    #[test]
+    fn batched_broadcast_with_merkle_proofs() {
+        let batch: Vec<Value> = (10..15).collect();
+        let tree = MerkleTree::new(&batch);
+        for (index, value) in batch.iter().enumerate() {
+            assert!(tree.proof(index).verify(tree.root(), *value));
+            assert!(!tree.proof(index).verify(tree.root(), value + 1));
+        }
+
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Random, RngSource::Seeded(1));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, results) = network.batched_broadcast(&batch, 0);
+        assert!(success);
+        assert!(results.values().all(|delivered| *delivered == batch));
+        assert!(network.audit().is_consistent());
+
+        // ECHO and READY are shared by the whole batch
+        let batched = network.stats().total_messages();
+        let mut per_value = 0;
+        for v in batch.iter() {
+            assert!(network.bracha_broadcast(*v, 0).0);
+            per_value += network.stats().total_messages();
+        }
+        assert!(batched * 2 < per_value);
+    }
+
+    #[test]
+    fn network_controller_mid_run() {
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(2));
+        network.set_schedule(Schedule::Deterministic);
+        let controller = network.controller();
+
+        // Node 1 never gets enough READY to deliver
+        controller.drop_next(3, |msg| msg.to == 1 && msg.phase == Phase::Ready);
+        let (success, results) = network.bracha_broadcast(7, 0);
+        assert!(!success);
+        assert!(!results.contains_key(&1));
+        assert_eq!(controller.dropped(), 3);
+        // Request is fulfilled
+        assert!(network.bracha_broadcast(7, 0).0);
+
+        // INIT to node 3 overtakes every other message
+        controller.force_deliver(|msg| msg.to == 3 && msg.phase == Phase::Init);
+        assert!(network.bracha_broadcast(7, 0).0);
+        assert_eq!(controller.forced(), 1);
+        let events = &network.trace().events;
+        assert_eq!((events[1].from, events[1].to), (0, 3));
+        assert_eq!(events[1].msg.phase(), Phase::Init);
+
+        // Messages from the leader to node 2 come after all the others, the
+        // run can be over before
+        controller.delay_link(0, 2, Duration::from_millis(10));
+        assert!(network.bracha_broadcast(7, 0).0);
+        let from_leader = |e: &TraceEvent| e.from == 0 && e.to == 2;
+        let events = network.trace().events.iter();
+        assert!(events.skip_while(|e| !from_leader(e)).all(from_leader));
+        network.close();
+    }
+
+    #[test]
+    fn node_panics_are_contained() {
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(2));
+        network.set_schedule(Schedule::Deterministic);
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, id as Value)).collect();
+
+        let results = network.run_protocol(PANICKING, &inputs);
+        assert!(results.is_empty());
+        let mut panicked: Vec<NodeId> = network.panics().iter().map(|p| p.node).collect();
+        panicked.sort();
+        // Silent node doesn't start the protocol
+        assert_eq!(panicked, vec![0, 1, 2]);
+        let report = network.panics().iter().find(|p| p.node == 1).unwrap();
+        assert!(report.message.contains("started with 1"));
+        assert!(report.last_message.contains("START"));
+        assert!(!report.backtrace.is_empty());
+
+        network.set_panic_policy(PanicPolicy::Abort);
+        network.run_protocol(PANICKING, &inputs);
+        assert_eq!(network.panics().len(), 1);
+
+        // Panicked nodes are back after a reset
+        let (success, _) = network.bracha_broadcast(5, 0);
+        assert!(success);
+        assert!(network.panics().is_empty());
+        network.close();
+    }
+
+    #[test]
+    fn nodes_schedule_messages_to_themselves() {
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(3));
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, id as Value + 1)).collect();
+        for schedule in [Schedule::Concurrent, Schedule::Deterministic] {
+            let concurrent = !schedule.is_lockstep();
+            network.set_schedule(schedule);
+            let start = std::time::Instant::now();
+            let results = network.run_protocol(TICKING, &inputs);
+            assert_eq!(results.len(), 4);
+            for (id, output) in results.iter() {
+                assert_eq!(*output, Output::Value(*id as Value + 1));
+            }
+            // Timers don't go through the links: only START and END count
+            assert_eq!(network.stats().total_messages(), 8);
+            if concurrent {
+                assert!(start.elapsed() >= TICK_PERIOD * 4);
+            }
+        }
+        network.close();
+    }
+
+    #[test]
+    fn downgraded_node_loses_liveness() {
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(8));
+        network.set_schedule(Schedule::Deterministic);
+        // Node 2 hears from node 1 last: READY of the leader comes before
+        // its ECHO quorum
+        network.controller().delay_link(1, 2, Duration::from_millis(10));
+
+        let (success, _) = network.bracha_broadcast(5, 0);
+        assert!(success);
+
+        // Downgrade once INIT and ECHO of the leader have been processed
+        network.switch_handler(HandlerSwitch {
+            node: 2,
+            protocol: BRACHA_BROADCAST,
+            handler: Handler::Legacy,
+            after: 2,
+        });
+        assert!(network.manifest().get("handler_switches").is_some());
+        let (success, results) = network.bracha_broadcast(5, 0);
+        assert!(!success);
+        let mut delivered: Vec<&NodeId> = results.keys().collect();
+        delivered.sort();
+        assert_eq!(delivered, vec![&0, &1]);
+
+        network.clear_switches();
+        let (success, _) = network.bracha_broadcast(5, 0);
+        assert!(success);
+        network.close();
+    }
+
+    #[test]
+    fn rolling_upgrade_across_versions() {
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(4));
+        network.set_schedule(Schedule::Deterministic);
+        assert!(network.set_version(0, BRACHA_BROADCAST, "3.0").is_err());
+
+        // 1.0 nodes don't read the frames of 2.0, they give up on half of
+        // the network
+        for id in [2, 3] {
+            network.set_version(id, BRACHA_BROADCAST, "2.0").unwrap();
+        }
+        let (success, results) = network.bracha_broadcast(5, 0);
+        assert!(!success);
+        assert!(results.is_empty());
+        assert_eq!(network.manifest().get("versions"), Some("2 0:2.0 | 3 0:2.0"));
+
+        // Upgrading to 1.1 first makes the mix safe
+        for id in [0, 1] {
+            network.set_version(id, BRACHA_BROADCAST, "1.1").unwrap();
+        }
+        let (success, results) = network.bracha_broadcast(5, 0);
+        assert!(success);
+        assert!(results.values().all(|v| *v == 5));
+        network.close();
+    }
+
+    #[test]
+    fn messages_are_conserved() {
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(9));
+        // Losses may keep some nodes from delivering
+        network.set_time_limit(Duration::from_secs(1));
+        network.set_config(NetworkConfig {
+            drop_rate: 0.05,
+            ..NetworkConfig::default()
+        });
+        network.compromise_link(0, 1, LinkAttack::Drop);
+        network.compromise_link(0, 2, LinkAttack::Inject(3));
+        network.controller().drop_next(2, |view| view.phase == Phase::Ready);
+        network.kill(6);
+        for schedule in [Schedule::Concurrent, Schedule::Deterministic] {
+            network.set_schedule(schedule);
+            network.bracha_broadcast(5, 0);
+            let conservation = network.conservation();
+            assert!(conservation.holds(), "{}", conservation);
+            assert_eq!(conservation.created, network.ledger().len());
+            for cause in [DropCause::Attack, DropCause::DeadNode] {
+                assert!(conservation.dropped.get(&cause).is_some_and(|n| *n > 0));
+            }
+        }
+
+        // Message the network forgot about
+        let mut ledger = Ledger::new();
+        let mut msgs: Vec<NetworkMessage> = (0..3)
+            .map(|to| NetworkMessage::new(0, to, Message::ACK))
+            .collect();
+        for msg in msgs.iter_mut() {
+            ledger.create(msg);
+        }
+        ledger.deliver(msgs[0].id);
+        ledger.deliver(msgs[0].id);
+        let conservation = ledger.audit(&[msgs[2].id]);
+        assert!(!conservation.holds());
+        assert_eq!(conservation.missing, vec![msgs[1].id]);
+        assert_eq!(conservation.duplicated, vec![msgs[0].id]);
+        assert_eq!(ledger.fate(msgs[2].id), Some(Fate::InFlight));
+        network.close();
+    }
+
+    #[test]
+    fn dead_node_threads_are_crashed() {
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(6));
+        network.set_schedule(Schedule::Deterministic);
+        network.kill(3);
+        let (success, results) = network.bracha_broadcast(5, 0);
+        // Run is degraded: the others still deliver without node 3
+        assert!(!success);
+        assert_eq!(network.lost_nodes(), &[3]);
+        assert_eq!(results.len(), 3);
+        assert!(results.values().all(|v| *v == 5));
+
+        // Dead node stays out of the next runs
+        network.set_schedule(Schedule::Concurrent);
+        let (success, results) = network.bracha_broadcast(5, 1);
+        assert!(!success);
+        assert_eq!(results.len(), 3);
+
+        // Without a quorum the run stops once nothing can be delivered
+        network.set_schedule(Schedule::Deterministic);
+        network.kill(2);
+        let (_, results) = network.bracha_broadcast(5, 0);
+        assert_eq!(network.lost_nodes(), &[3, 2]);
+        assert!(results.is_empty());
+        network.close();
+    }
+
+    #[test]
+    fn per_node_log_files() {
+        let dir = std::env::temp_dir().join(format!("node-logs-{}", std::process::id()));
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(3));
+        network.set_schedule(Schedule::Deterministic);
+        network.log_nodes(&dir).unwrap();
+        assert!(network.bracha_broadcast(5, 0).0);
+
+        let read = |path| std::fs::read_to_string(path).unwrap();
+        let node = read(NodeLogs::path(&dir, 1));
+        assert!(node.starts_with("# crate_version"));
+        assert!(node.contains("<- 0 PROTOCOL(<INIT, 5>)"));
+        assert!(node.contains("output Value(5)"));
+
+        // Every index entry points to an event of the node at the same time
+        let files: Vec<Vec<String>> = (0..4)
+            .map(|id| read(NodeLogs::path(&dir, id)).lines().map(String::from).collect())
+            .collect();
+        let index = read(dir.join(INDEX_FILE));
+        let entries: Vec<&str> = index.lines().filter(|line| !line.starts_with('#')).collect();
+        let events: usize = files
+            .iter()
+            .map(|lines| lines.iter().filter(|line| !line.starts_with('#')).count())
+            .sum();
+        assert_eq!(entries.len(), events);
+        for entry in entries {
+            let fields: Vec<&str> = entry.split(' ').collect();
+            let id: NodeId = fields[1].parse().unwrap();
+            let line: usize = fields[2].parse().unwrap();
+            assert!(files[id][line - 1].starts_with(fields[0]));
+        }
+        network.close();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn abnormal_runs_dump_artifacts() {
+        let dir = std::env::temp_dir().join(format!("artifacts-{}", std::process::id()));
+        let read = |path| std::fs::read_to_string(path).unwrap();
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(2));
+        network.set_schedule(Schedule::Deterministic);
+        network.dump_artifacts(&dir).unwrap();
+
+        // Runs that end normally leave nothing behind
+        assert!(network.bracha_broadcast(5, 0).0);
+        assert_eq!(network.artifacts(), None);
+        assert!(!Artifacts::run_dir(&dir, 1).exists());
+
+        // Panic: the state of the panicked nodes is gone, not the rest
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, id as Value)).collect();
+        network.run_protocol(PANICKING, &inputs);
+        assert_eq!(network.artifacts(), Some(Artifacts::run_dir(&dir, 2).as_path()));
+        let summary = read(Artifacts::run_dir(&dir, 2).join(SUMMARY_FILE));
+        assert!(summary.starts_with("# crate_version"));
+        assert!(summary.contains("run 2: panic of nodes [0, 1, 2]"));
+        assert!(summary.contains("Panicking protocol started with 1"));
+        let panicked = read(Artifacts::path(&dir, 2, 1));
+        assert!(panicked.starts_with("node 1: Good, Failed"));
+        assert!(panicked.contains("[state] unavailable"));
+        assert!(panicked.contains("Network -> 1 START"));
+        let silent = read(Artifacts::path(&dir, 2, 3));
+        assert!(silent.starts_with("node 3: Malicious(Silent), Running"));
+        assert!(silent.contains("[state] 0 entries"));
+        network.close();
+
+        // Timeout: the honest node left waiting keeps its state and its last
+        // deliveries, at most `LAST_EVENTS` of them
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Equivocate, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_time_limit(Duration::from_millis(100));
+        network.dump_artifacts(&dir).unwrap();
+        assert!(!network.consistent_broadcast(42, 3).0);
+        let summary = read(Artifacts::run_dir(&dir, 1).join(SUMMARY_FILE));
+        assert!(summary.contains("run 1: timeout (no deliverable message), 1 good nodes"));
+        let waiting = read(Artifacts::path(&dir, 1, 0));
+        assert!(waiting.starts_with("node 0: Good, Running"));
+        assert!(waiting.contains("16:0 echo = 42"));
+        assert!(waiting.contains("3 -> 0 PROTOCOL(<SEND, 42>)"));
+        let events = waiting.split("[events] last ").nth(1).unwrap();
+        let count: usize = events.lines().next().unwrap().parse().unwrap();
+        assert!(count > 0 && count <= LAST_EVENTS);
+        assert_eq!(events.lines().count(), count + 1);
+
+        // Messages still in transit at the time limit are in the mailboxes
+        network.set_schedule(Schedule::Concurrent);
+        network.set_time_limit(Duration::from_millis(20));
+        network.set_config(NetworkConfig {
+            latency: Latency::Constant(Duration::from_secs(1)),
+            ..NetworkConfig::default()
+        });
+        assert!(!network.bracha_broadcast(42, 0).0);
+        let summary = read(Artifacts::run_dir(&dir, 2).join(SUMMARY_FILE));
+        assert!(summary.contains("run 2: timeout (time limit), 3 good nodes"));
+        let waiting = read(Artifacts::path(&dir, 2, 1));
+        assert!(waiting.contains("0 -> 1 PROTOCOL(<INIT, 42>)"));
+        assert!(!waiting.contains("[mailbox] 0 messages"));
+        network.close();
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn diff_of_recorded_runs() {
+        let record = |seed, name: &str| {
+            let dir = std::env::temp_dir().join(format!("diff-{}-{}", name, std::process::id()));
+            let mut network =
+                Network::with_rng(4, 1, MaliciousKind::Random, RngSource::Seeded(seed));
+            network.set_schedule(Schedule::Deterministic);
+            network.log_nodes(&dir).unwrap();
+            network.bracha_broadcast(5, 0);
+            network.close();
+            let recording = Recording::load(&dir).unwrap();
+            std::fs::remove_dir_all(&dir).unwrap();
+            recording
+        };
+        let a = record(3, "a");
+        assert_eq!(a.outputs.get(&(1, 0)).map(String::as_str), Some("Value(5)"));
+        assert!(a.messages.contains_key(&(1, String::from("Echo"))));
+
+        let diff = RunDiff::new(&a, &record(3, "b"));
+        assert_eq!(diff, RunDiff::default());
+
+        let diff = RunDiff::new(&a, &record(4, "c"));
+        assert_eq!(
+            diff.config.get("seed"),
+            Some(&(Some(String::from("3")), Some(String::from("4"))))
+        );
+        let (at, (event_a, event_b)) = diff.first_divergence.clone().unwrap();
+        assert_eq!(a.events[..at], record(4, "d").events[..at]);
+        assert_ne!(event_a, event_b);
+        assert!(diff.to_string().contains("First difference at event"));
+    }
+
+    #[test]
+    fn milestone_latencies_of_a_straggler() {
+        let mut network = Network::with_rng(7, 0, MaliciousKind::Silent, RngSource::Seeded(8));
+        let straggler = 5;
+        for from in 0..7 {
+            network
+                .controller()
+                .delay_link(from, straggler, Duration::from_millis(40));
+        }
+        assert!(network.bracha_broadcast(5, 0).0);
+
+        let stats = network.stats();
+        let delivered = stats.latency(Milestone::Delivered).unwrap();
+        assert_eq!(delivered.len(), 7);
+        assert_eq!(delivered.slowest(), Some(straggler));
+        assert!(delivered.max() - delivered.percentile(50.0) >= Duration::from_millis(30));
+        let histogram = delivered.histogram(4);
+        assert_eq!(histogram.iter().map(|(_, count)| count).sum::<usize>(), 7);
+        assert_eq!(histogram.last().unwrap().1, 1);
+
+        // Nodes go through the milestones in order
+        let echo = stats.milestone(Milestone::FirstEcho).unwrap();
+        let ready = stats.milestone(Milestone::FirstReady).unwrap();
+        for (id, at) in stats.milestone(Milestone::Delivered).unwrap() {
+            assert!(echo[id] <= ready[id] && ready[id] <= *at);
+        }
+        assert_eq!(network.milestones_csv().lines().filter(|l| !l.starts_with('#')).count(), 22);
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn throughput_measurement_windows() {
+        let mut experiment = ThroughputExperiment {
+            num_nodes: 4,
+            batch_size: 4,
+            instances: 10,
+            warmup: 2,
+            cooldown: 3,
+            steady_window: 2,
+            tolerance: f64::INFINITY,
+            ..ThroughputExperiment::default()
+        };
+        let report = experiment.run();
+        assert_eq!(report.samples.len(), 10);
+        assert!(report.samples.iter().all(|sample| sample.values == 4));
+        assert_eq!(report.steady_from, Some(2));
+        let measured: Vec<usize> = report.measured().map(|sample| sample.instance).collect();
+        assert_eq!(measured, vec![2, 3, 4, 5, 6]);
+        assert!(report.throughput > 0.0);
+
+        // Without a steady state the measurement starts after the warm-up
+        experiment.tolerance = -1.0;
+        let report = experiment.run();
+        assert_eq!(report.steady_from, None);
+        assert_eq!(report.measured().count(), 5);
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn regressions_against_a_baseline() {
+        let benchmark = BroadcastBenchmark {
+            num_nodes: 4,
+            num_malicious: 1,
+            runs: 6,
+            seed: u64::MAX,
+            ..BroadcastBenchmark::default()
+        };
+        let baseline = benchmark.run();
+        assert_eq!(baseline.metrics["messages"].len(), 6);
+        let stored = Baseline::from_json(&baseline.to_json()).unwrap();
+        assert_eq!(stored, baseline);
+        assert_eq!(BroadcastBenchmark::from_manifest(&stored.manifest), Ok(benchmark));
+        assert!(stored.compare(&baseline).iter().all(|c| !c.significant));
+
+        // Current build sends 50% more messages but is faster
+        let mut current = baseline.clone();
+        for messages in current.metrics.get_mut("messages").unwrap() {
+            *messages *= 1.5;
+        }
+        for latency in current.metrics.get_mut("latency_us").unwrap() {
+            *latency *= 0.5;
+        }
+        let regressions: Vec<String> = stored
+            .compare(&current)
+            .into_iter()
+            .filter(|c| c.is_regression())
+            .map(|c| c.metric)
+            .collect();
+        assert_eq!(regressions, vec!["messages"]);
+    }
+
+    #[test]
+    fn network_presets() {
+        for preset in Preset::ALL {
+            assert_eq!(preset.name().parse(), Ok(preset));
+        }
+        let wan = NetworkConfig::preset(Preset::Wan);
+        let mut rng = SeededRng::new(0, 0);
+        for _ in 0..100 {
+            let transit = wan.transit(0, 1, 0, &mut rng);
+            assert!(transit >= Duration::from_millis(50) && transit < Duration::from_millis(60));
+        }
+        let slow = NetworkConfig {
+            bandwidth: Some(1000),
+            ..NetworkConfig::default()
+        };
+        assert_eq!(slow.transit(0, 1, 500, &mut rng), Duration::from_millis(500));
+
+        let mut elapsed = vec![];
+        for preset in [Preset::Lan, Preset::AdversarialAsync] {
+            let mut network =
+                Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(1));
+            network.set_config(NetworkConfig::preset(preset));
+            assert!(network.bracha_broadcast(3, 0).0);
+            assert!(network.manifest().get("jitter").is_some());
+            elapsed.push(network.stats().elapsed());
+            network.close();
+        }
+        assert!(elapsed[0] < elapsed[1]);
+    }
+
+    #[test]
+    fn honest_strategy_variants() {
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(4));
+        network.set_schedule(Schedule::Deterministic);
+        let mut run = |strategy: &str| {
+            network.set_broadcast_config(BroadcastConfig {
+                strategy: strategy.parse().unwrap(),
+                ..BroadcastConfig::default()
+            });
+            let (success, _) = network.bracha_broadcast(7, 0);
+            (success, network.stats().phase(Phase::Init).unwrap().messages)
+        };
+
+        let (success, standard_inits) = run("standard");
+        assert!(success);
+        let (success, eager_inits) = run("eager-init");
+        assert!(success);
+        assert!(eager_inits > standard_inits);
+        // Only the leader supports the value unless INIT is forwarded
+        assert!(!run("lazy-echo").0);
+        assert!(run("eager-init+lazy-echo").0);
+        assert!(run("no-relay").0);
+        assert_eq!(
+            "eager-init+no-relay".parse::<Strategy>().unwrap().name(),
+            "eager-init+no-relay"
+        );
+    }
+
+    #[test]
+    fn init_relaying_past_lossy_leader_links() {
+        let mut successes = HashMap::new();
+        for strategy in ["standard", "eager-init"] {
+            let mut delivered = 0;
+            for seed in 0..10 {
+                let mut network =
+                    Network::with_rng(7, 1, MaliciousKind::Silent, RngSource::Seeded(seed));
+                network.set_schedule(Schedule::Deterministic);
+                network.set_config(NetworkConfig::default().lossy_links(0, 7, 0.6));
+                network.set_broadcast_config(BroadcastConfig {
+                    strategy: strategy.parse().unwrap(),
+                    ..BroadcastConfig::default()
+                });
+                if network.bracha_broadcast(7, 0).0 {
+                    delivered += 1;
+                }
+                assert!(network.manifest().get("link_drop_rates").is_some());
+                network.close();
+            }
+            successes.insert(strategy, delivered);
+        }
+        // Nodes the INIT reached relay it to the others
+        assert!(successes["eager-init"] > successes["standard"], "{:?}", successes);
+
+        let config = NetworkConfig {
+            drop_rate: 0.1,
+            ..NetworkConfig::default()
+        }
+        .lossy_links(2, 4, 0.5);
+        assert_eq!(config.drop_rate(2, 0), 0.5);
+        assert_eq!(config.drop_rate(0, 2), 0.1);
+    }
+
+    #[test]
+    fn delivery_apart_from_termination() {
+        let mut runs = vec![];
+        for after in [AfterDelivery::Stop, AfterDelivery::Participate] {
+            let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_after_delivery(after);
+            // Node 3 only hears from the leader once the others delivered
+            network.controller().delay_link(0, 3, Duration::from_millis(10));
+            let (success, results) = network.bracha_broadcast(7, 0);
+            assert!(success);
+            let handled: usize = network.counter("handled_after_delivery").values().sum();
+            runs.push((results, network.lifecycles().clone(), handled));
+            if after == AfterDelivery::Participate {
+                assert_eq!(network.manifest().get("after_delivery"), Some("participate"));
+            }
+            network.close();
+        }
+        let (stop, participate) = (&runs[0], &runs[1]);
+        assert_eq!(stop.0, participate.0);
+        assert!(stop.1.values().all(|lifecycle| matches!(lifecycle, Lifecycle::Terminated(_))));
+        assert!(participate.1.values().all(|l| matches!(l, Lifecycle::Participating(_))));
+        // Messages of the laggard reach nodes that delivered before it
+        assert_eq!(stop.2, 0);
+        assert!(participate.2 > 0);
+        assert_eq!("participate".parse(), Ok(AfterDelivery::Participate));
+
+        // Node that delivered and panics while it helps is not counted out
+        // twice, the run waits for the others
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_after_delivery(AfterDelivery::Participate);
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, id as Value)).collect();
+        let results = network.run_protocol(RELAY_PANICKING, &inputs);
+        assert_eq!(results.len(), 4, "{:?}", results);
+        assert!(network.panics().iter().all(|p| p.node == 0));
+        assert!(!network.panics().is_empty());
+        network.close();
+    }
+
+    #[cfg(feature = "experiments")]
+    #[test]
+    fn helping_rescues_slow_nodes() {
+        let experiment = HelpingExperiment::default();
+        let outcomes = experiment.run();
+        let (stop, participate) = (&outcomes[0], &outcomes[1]);
+        assert_eq!(stop.after_delivery, AfterDelivery::Stop);
+        // Nodes that delivered stopped, lost READY are never sent again
+        assert!(stop.stranded > 0);
+        // Nodes that delivered answer the requests of the slow ones
+        assert_eq!(participate.stranded, 0, "{:?}", outcomes);
+        assert!(participate.successes > stop.successes);
+        assert!(participate.state_transfers > stop.state_transfers);
+        assert_eq!(experiment.manifest().get("policies"), Some("stop participate"));
+    }
+
+    #[test]
+    fn stats_per_phase() {
+        let mut network = Network::new(10, 0, MaliciousKind::Silent);
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        let stats = network.stats();
+        assert_eq!(stats.phase(Phase::Leader).unwrap().messages, 1);
+        assert_eq!(stats.phase(Phase::Init).unwrap().messages, 9);
+        // Every node echoes and readies to all the others
+        assert_eq!(stats.phase(Phase::Echo).unwrap().messages, 90);
+        assert_eq!(stats.phase(Phase::Ready).unwrap().messages, 90);
+        assert_eq!(stats.phase(Phase::Control).unwrap().messages, 10);
+    }
+
+    #[test]
+    fn mitm_on_unauthenticated_links() {
+        let mut network = Network::new(10, 0, MaliciousKind::Silent);
+        for to in 1..10 {
+            network.compromise_link(0, to, LinkAttack::Tamper(3));
+        }
+        let (success, results) = network.bracha_broadcast(7, 0);
+        assert!(!success);
+        assert!(results.values().all(|v| *v == 3));
+    }
+
+    #[test]
+    fn mitm_on_authenticated_links() {
+        let mut network = Network::new(10, 0, MaliciousKind::Silent);
+        network.set_link_security(LinkSecurity {
+            authenticated: true,
+            encrypted: true,
+        });
+        network.compromise_link(0, 1, LinkAttack::Inject(3));
+        network.compromise_link(0, 2, LinkAttack::Eavesdrop);
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        // INIT, ECHO and READY of the leader
+        assert_eq!(network.mitm().stats().rejected, 3);
+        assert!(network.mitm().transcript().is_empty());
+    }
+
+    #[test]
+    fn amnesia_breaks_crash_recovery() {
+        // Crash before taking part in the broadcast, nothing to forget yet
+        let mut network = Network::with_rng(7, 1, MaliciousKind::Amnesia(2), RngSource::Seeded(1));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, results) = network.bracha_broadcast(5, 0);
+        assert!(success);
+        assert_eq!(results.get(&6), Some(&5));
+        assert!(!network.amnesia()[0].violates());
+        network.close();
+
+        // Crash once READY is sent: the node sends it again and the READYs
+        // it forgot never come back
+        let mut network = Network::with_rng(7, 1, MaliciousKind::Amnesia(10), RngSource::Seeded(1));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, results) = network.bracha_broadcast(5, 0);
+        // Honest nodes tolerate it as one faulty node
+        assert!(success);
+        assert!(!results.contains_key(&6));
+        let report = &network.amnesia()[0];
+        assert_eq!((report.node, report.after), (6, 10));
+        assert!(report.resent.contains(&Phase::Ready));
+        assert!(report.undelivered);
+        assert_eq!(
+            report.to_string(),
+            "Node 6 lost its state after 10 messages, sent {Ready} again, never delivered"
+        );
+        network.close();
+    }
+
+    #[test]
+    fn omission_faults() {
+        // Node 3 processes everything but none of its messages go out
+        let kind = "send-omission:100".parse().unwrap();
+        let mut network = Network::with_rng(4, 1, kind, RngSource::Seeded(2));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, results) = network.bracha_broadcast(5, 0);
+        assert!(success);
+        assert_eq!(results.get(&3), Some(&5));
+        let echoes = network.stats().milestone(Milestone::FirstEcho).unwrap();
+        assert!(!echoes.contains_key(&3));
+        network.close();
+
+        // Node 3 sends as asked but ignores its peers, only the request of
+        // the network gets through
+        let kind = "receive-omission:100".parse().unwrap();
+        let mut network = Network::with_rng(4, 1, kind, RngSource::Seeded(2));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, results) = network.bracha_broadcast(5, 3);
+        assert!(success);
+        assert!(!results.contains_key(&3));
+        network.close();
+
+        // Occasional omissions of a faulty node are tolerated
+        let kinds = [
+            MaliciousKind::SendOmission(30),
+            MaliciousKind::ReceiveOmission(30),
+        ];
+        for kind in kinds {
+            let mut network = Network::with_rng(7, 2, kind, RngSource::Seeded(2));
+            let (success, _) = network.bracha_broadcast(5, 0);
+            assert!(success);
+            network.close();
+        }
+        assert!("send-omission:101".parse::<MaliciousKind>().is_err());
+    }
+
+    #[test]
+    fn fault_schedules_per_node() {
+        let schedule: FaultSchedule = "20,50".parse().unwrap();
+        assert!(!schedule.is_faulty(19));
+        assert!(schedule.is_faulty(20) && schedule.is_faulty(69));
+        assert!(!schedule.is_faulty(70));
+        assert_eq!(schedule.to_string(), "20,50");
+        assert!(FaultSchedule::default().is_faulty(0));
+
+        let run = |schedule: FaultSchedule| {
+            let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(1));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_fault_schedule(3, schedule);
+            assert!(network.manifest().get("fault_schedules").is_some());
+            let (success, results) = network.bracha_broadcast(5, 0);
+            assert!(success);
+            let messages = network.stats().total_messages();
+            network.close();
+            (results.contains_key(&3), messages)
+        };
+        // Silent from the start, or never
+        assert_eq!(run(FaultSchedule::default()), (false, 25));
+        assert_eq!(run(FaultSchedule::new(vec![])), (true, 32));
+        // Stops after echoing INIT
+        assert_eq!(run(FaultSchedule::new(vec![2])), (false, 28));
+        // Misses INIT and the first ECHOs, then catches up with the READYs
+        assert_eq!(run(FaultSchedule::new(vec![0, 3])), (true, 29));
+    }
+
+    #[test]
+    fn statistics_per_behaviour() {
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(1));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, _) = network.bracha_broadcast(5, 0);
+        assert!(success);
+        let stats = network.behaviour_stats();
+        assert_eq!((stats.honest.nodes, stats.malicious.nodes), (3, 1));
+        assert_eq!(stats.malicious.sent, 0);
+        assert_eq!(stats.honest.influenced, 0);
+        assert_eq!(stats.honest.delivered.get(&Output::Value(5)), Some(&3));
+        network.close();
+
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Mirror, RngSource::Seeded(1));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, _) = network.bracha_broadcast(5, 0);
+        assert!(success);
+        let stats = network.behaviour_stats();
+        assert_eq!(stats.malicious.sent, 24);
+        assert_eq!((stats.honest.sent, stats.honest.influenced), (66, 30));
+        assert!((stats.influence() - 30.0 / 66.0).abs() < 1e-9);
+        assert!(stats.malicious.delivered.is_empty());
+        // Every message but the request of the network
+        assert_eq!(
+            stats.honest.sent + stats.malicious.sent + 1,
+            network.ledger().len()
+        );
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn peer_limits_throttle_flooding_peers() {
+        let now = Instant::now();
+        let mut bucket = TokenBucket::per_peer(PeerLimit { rate: 10, burst: 2 }, now);
+        assert!(bucket.take(now, 1) && bucket.take(now, 1));
+        assert!(!bucket.take(now, 1));
+        assert!(bucket.take(now + Duration::from_millis(100), 1));
+        assert_eq!("50".parse(), Ok(PeerLimit { rate: 50, burst: 50 }));
+
+        // Honest peers stay within the limit, Random peers answer every
+        // message with a broadcast and are cut off at the honest nodes
+        let limit = PeerLimit { rate: 100, burst: 4 };
+        let cpu = CpuModel::uniform(Duration::from_millis(1));
+        for kind in [MaliciousKind::Silent, MaliciousKind::Random] {
+            let mut network = Network::with_rng(10, 3, kind.clone(), RngSource::Seeded(0));
+            network.set_config(NetworkConfig {
+                latency: Latency::Constant(Duration::from_millis(1)),
+                cpu: Some(cpu.clone()),
+                peer_limit: Some(limit),
+                ..NetworkConfig::default()
+            });
+            assert!(network.bracha_broadcast(7, 0).0);
+            let throttled = network.counter("throttled");
+            let conservation = network.conservation();
+            assert!(conservation.holds(), "{}", conservation);
+            let dropped = conservation.dropped.get(&DropCause::Throttled).copied();
+            assert_eq!(dropped.unwrap_or(0), throttled.values().sum::<usize>());
+            let honest: Vec<NodeId> = match kind {
+                MaliciousKind::Silent => vec![],
+                _ => (0..7).collect(),
+            };
+            assert_eq!(throttled.keys().copied().collect::<Vec<_>>(), honest);
+            assert!(network.manifest().to_string().contains("peer_limit"));
+            network.close();
+        }
+
+        // Flooding costs honest nodes the time to handle the flood, the limit
+        // takes most of it back
+        let added_latency = |peer_limit| {
+            let comparison = AttackComparison {
+                kinds: vec![MaliciousKind::Random],
+                cpu: Some(cpu.clone()),
+                peer_limit,
+                ..AttackComparison::default()
+            };
+            let metrics: Vec<Box<dyn AttackMetric>> = vec![Box::new(AddedLatency)];
+            comparison.run(&metrics)[0].scores[0]
+        };
+        let unlimited = added_latency(None);
+        let limited = added_latency(Some(limit));
+        assert!(limited < unlimited, "{} {}", limited, unlimited);
     }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn attacks_ranked_by_metric() {
+        let comparison = AttackComparison {
+            num_nodes: 4,
+            num_malicious: 1,
+            kinds: vec![MaliciousKind::Silent, MaliciousKind::Random],
+            runs: 3,
+            schedule: Schedule::Deterministic,
+            ..AttackComparison::default()
+        };
+        let metrics: Vec<Box<dyn AttackMetric>> = vec![
+            Box::new(ExtraMessages),
+            Box::new(FailureRate),
+            Box::new(AgreementViolation),
+        ];
+        let scores = comparison.run(&metrics);
+        // Random messages cost more than silence, neither breaks the broadcast
+        assert_eq!(scores[0].kind, MaliciousKind::Random);
+        assert!(scores[0].scores[0] > 0.0);
+        assert_eq!(scores[1].kind, MaliciousKind::Silent);
+        assert!(scores[1].scores[0] < 0.0);
+        assert!(scores.iter().all(|score| score.scores[1..] == [0.0, 0.0]));
+
+        let csv = attacks_csv(&scores, &metrics, &comparison.manifest(&metrics));
+        assert!(csv.contains("rank,kind,extra_messages,failure_rate,agreement_violation\n"));
+        assert!(csv.contains("\n2,Silent,"));
+        assert!(attack_metric("added_latency_us").is_ok());
+        assert!(attack_metric("speed").is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn scenario_library() {
+        for scenario in SCENARIOS.iter() {
+            for nodes in [4, 10] {
+                let outcome = scenario.run(nodes);
+                assert!(outcome.passed, "{}", outcome);
+            }
+        }
+        // Honest nodes echo different values and none of them delivers
+        let outcome = scenario::find("equivocating-leader").unwrap().run(10);
+        assert!(outcome.outputs.is_empty());
+        assert!(scenario::find("sybil").is_none());
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn scenario_matrix_by_tags() {
+        let parse = |expr: &str| expr.parse::<TagExpr>().unwrap();
+        let expr = parse("fault:byzantine & !size:large | (protocol:common-subset)");
+        assert_eq!(
+            expr.to_string(),
+            "((fault:byzantine & !size:large) | protocol:common-subset)"
+        );
+        assert!(expr.matches(&["fault:byzantine", "size:small"]));
+        assert!(!expr.matches(&["fault:byzantine", "size:large"]));
+        assert!(expr.matches(&["protocol:common-subset", "size:large"]));
+        for invalid in ["", "a &", "(a | b", "a b", "& a"] {
+            assert!(invalid.parse::<TagExpr>().is_err(), "{}", invalid);
+        }
+        assert_eq!(scenario::size_class(7), "size:medium");
+
+        // Every scenario is tagged with its fault model and protocol
+        for scenario in SCENARIOS.iter() {
+            let tags = scenario.tags_at(4);
+            assert!(tags.iter().any(|tag| tag.starts_with("fault:")));
+            assert!(tags.iter().any(|tag| tag.starts_with("protocol:")));
+            assert!(tags.contains(&"size:small"));
+        }
+
+        let report = scenario::run_matrix(&parse("fault:crash & size:small"));
+        let names: Vec<&str> = report.outcomes.iter().map(|o| o.scenario).collect();
+        assert_eq!(names, ["ready-withholding", "silent-leader", "silent-senders"]);
+        assert!(report.outcomes.iter().all(|outcome| outcome.nodes == 4));
+        assert_eq!(report.passed(), 3);
+        assert!(report.failed().is_empty());
+        assert_eq!(report.by_tag()["protocol:bracha"], (2, 2));
+        assert!(report.to_string().ends_with("3 passed, 0 failed"));
+        assert!(scenario::run_matrix(&parse("protocol:paxos")).outcomes.is_empty());
+    }
+
+    #[test]
+    fn common_subset_despite_silent_senders() {
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, 100 + id)).collect();
+        let honest: BTreeMap<NodeId, Value> = (0..5).map(|id| (id, 100 + id)).collect();
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            for seed in 0..3 {
+                let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(seed));
+                network.set_schedule(Schedule::Deterministic);
+                let (success, results) = network.common_subset(&inputs);
+                assert!(success, "{:?} {}: {:?}", kind, seed, results);
+                // Silent senders are left out instead of stalling the nodes
+                if kind == MaliciousKind::Silent {
+                    assert_eq!(results[&0], Output::Set(honest.clone()));
+                }
+                network.close();
+            }
+        }
+
+        // Even when every node is honest, at least n - f broadcasts make it
+        let mut network = Network::new(7, 0, MaliciousKind::Silent);
+        let (success, results) = network.common_subset(&inputs);
+        assert!(success);
+        assert_eq!(results.len(), 7);
+        network.close();
+    }
+
+    #[test]
+    fn acs_outputs_at_least_n_minus_f_inputs() {
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, 100 + id)).collect();
+        assert!(protocols::create(acs::ACS, &ProtocolConfig::default()).is_some());
+        assert_eq!(protocols::describe(acs::ACS, 7), Some(acs::describe(7)));
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let results = network.run_protocol(acs::ACS, &inputs);
+            let subsets: BTreeSet<&Output> = (0..5).map(|id| &results[&id]).collect();
+            assert_eq!(subsets.len(), 1, "{:?}", results);
+            match subsets.first() {
+                Some(Output::Set(values)) => assert!(values.len() >= 7 - 2, "{:?}", values),
+                output => panic!("{:?}", output),
+            }
+            assert!(network.counter("rejected").is_empty());
+            network.close();
+        }
+    }
+
+    #[test]
+    fn flooding_over_sparse_topologies() {
+        let ring = Topology::Ring;
+        assert_eq!(ring.neighbours(0, 9), vec![1, 8]);
+        let grid: Topology = "grid:3".parse().unwrap();
+        assert_eq!(grid.neighbours(4, 9), vec![1, 3, 5, 7]);
+        assert_eq!(grid.distances(0, 9)[8], Some(4));
+        assert!("mesh".parse::<Topology>().is_err());
+
+        let inputs: HashMap<NodeId, Value> = (0..9).map(|id| (id, 100 + id)).collect();
+        let run = |topology: Topology, num_malicious: usize, ttl: Option<u32>, suppress: bool| {
+            let kind = MaliciousKind::Silent;
+            let mut network = Network::with_rng(9, num_malicious, kind, RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_time_limit(Duration::from_millis(200));
+            network.set_topology(topology);
+            network.set_flood_config(FloodConfig {
+                ttl,
+                suppress_duplicates: suppress,
+            });
+            let (success, _) = network.flood(&inputs);
+            assert_eq!(
+                network.manifest().get("topology"),
+                Some(topology.to_string().as_str())
+            );
+            let report = network.flood_report().clone();
+            network.close();
+            (success, report)
+        };
+
+        // Values go round the ring, a short TTL only covers half of it
+        let (success, report) = run(ring, 0, None, true);
+        assert!(success);
+        assert_eq!(report.coverage(), 1.0);
+        assert!(report.stretch() >= 1.0 && report.redundancy() >= 1.0);
+        let (success, report) = run(ring, 0, Some(2), true);
+        assert!(!success);
+        assert_eq!(report.coverage(), 0.5);
+
+        // Honest nodes wait forever for the silent node: duplicate
+        // suppression or a TTL stops the copies going round the cycles,
+        // without any only the time limit does
+        let (_, suppressed) = run(grid, 1, None, true);
+        let (_, ttl) = run(grid, 1, Some(4), false);
+        let (_, unbounded) = run(grid, 1, None, false);
+        assert!(suppressed.copies < ttl.copies && ttl.copies < unbounded.copies);
+        assert_eq!(suppressed.coverage(), ttl.coverage());
+    }
+
+    #[test]
+    fn silent_leader_liveness_timeout() {
+        // Without a timeout nothing happens until the run is stopped
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_time_limit(Duration::from_millis(200));
+        let (success, results) = network.bracha_broadcast(7, 6);
+        assert!(!success && results.is_empty());
+        assert!(network.stalled().is_empty());
+
+        // Honest nodes give up once nothing moved for the timeout
+        let timeout = Duration::from_millis(20);
+        network.set_broadcast_config(BroadcastConfig {
+            liveness_timeout: Some(timeout),
+            ..BroadcastConfig::default()
+        });
+        let (success, results) = network.bracha_broadcast(7, 6);
+        assert!(!success && results.is_empty());
+        assert_eq!(network.stalled().len(), 5);
+        let stalled = network.stats().latency(Milestone::Stalled).unwrap();
+        assert_eq!(stalled.len(), 5);
+        assert!(stalled.min() >= timeout);
+        network.close();
+
+        // Honest leader keeps the broadcast moving, nobody gives up
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_broadcast_config(BroadcastConfig {
+            liveness_timeout: Some(timeout),
+            ..BroadcastConfig::default()
+        });
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        assert!(network.stalled().is_empty());
+        network.close();
+    }
+
+    #[test]
+    fn routing_over_sparse_topologies() {
+        let ring = Topology::Ring;
+        assert_eq!(
+            ring.routes(0, 7),
+            vec![None, Some(1), Some(1), Some(1), Some(6), Some(6), Some(6)]
+        );
+        let grid: Topology = "grid:3".parse().unwrap();
+        assert_eq!(grid.routes(0, 9)[8], Some(1));
+
+        let run = |topology: Topology, routing: bool, num_malicious: usize, leader: NodeId| {
+            let kind = MaliciousKind::Silent;
+            let mut network = Network::with_rng(7, num_malicious, kind, RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_time_limit(Duration::from_millis(200));
+            network.set_topology(topology);
+            network.set_routing(routing);
+            let (success, results) = network.bracha_broadcast(7, leader);
+            assert_eq!(network.manifest().get("routing").is_some(), routing);
+            let messages = network.stats().total_messages();
+            network.close();
+            (success, results, messages)
+        };
+
+        // Neighbours alone are too few for the thresholds of the broadcast
+        let (success, _, _) = run(ring, false, 0, 0);
+        assert!(!success);
+
+        // Relayed messages reach every node, at the cost of more messages
+        let (success, results, relayed) = run(ring, true, 0, 0);
+        assert!(success);
+        assert_eq!(results.len(), 7);
+        let (success, _, direct) = run(Topology::Complete, true, 0, 0);
+        assert!(success);
+        assert!(relayed > direct);
+        assert_eq!(run(Topology::Complete, false, 0, 0).2, direct);
+
+        // Silent node drops what it should relay: tolerated on the complete
+        // topology, on the ring the nodes next to it miss too many messages
+        // to deliver
+        assert!(run(Topology::Complete, true, 1, 3).0);
+        let (success, results, _) = run(ring, true, 1, 3);
+        assert!(!success);
+        assert!(results.contains_key(&3) && !results.contains_key(&0));
+    }
+
+    #[test]
+    fn uplink_caps_queue_messages() {
+        let cap: UplinkCap = "1000:100".parse().unwrap();
+        assert_eq!(
+            cap,
+            UplinkCap {
+                rate: 1000,
+                burst: 100
+            }
+        );
+        assert!("0:100".parse::<UplinkCap>().is_err());
+        let now = Instant::now();
+        let ms = Duration::from_millis;
+        let mut bucket = TokenBucket::new(cap, now);
+        // Burst goes out at once, the next messages wait for tokens and
+        // for the ones queued before them
+        assert_eq!(bucket.send(now, 100), Duration::ZERO);
+        assert_eq!(bucket.send(now, 50), ms(50));
+        assert_eq!(bucket.send(now, 50), ms(100));
+        assert_eq!(bucket.send(now + ms(1000), 100), Duration::ZERO);
+
+        // Every node sends to all the others: the more nodes, the longer the
+        // queue at each sender
+        let run = |num_nodes: usize, uplink: Option<UplinkCap>| {
+            let mut network =
+                Network::with_rng(num_nodes, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+            network.set_config(NetworkConfig {
+                uplink,
+                ..NetworkConfig::default()
+            });
+            let (success, _) = network.bracha_broadcast(7, 0);
+            assert!(success);
+            let delivered = network.stats().latency(Milestone::Delivered).unwrap();
+            let queued = network.stats().queued().values().max().cloned();
+            network.close();
+            (delivered.max(), queued.unwrap_or_default())
+        };
+        let cap = Some(UplinkCap {
+            rate: 2_000,
+            burst: 200,
+        });
+        let (uncapped, none) = run(16, None);
+        assert_eq!(none, Duration::ZERO);
+        let (small, small_queue) = run(4, cap);
+        let (large, large_queue) = run(16, cap);
+        assert!(large_queue > small_queue);
+        assert!(large > small && large > uncapped);
+        assert!(large >= ms(50));
+    }
+
+    #[test]
+    fn delivery_breakdown_of_latency() {
+        let ms = Duration::from_millis;
+        let run = |config: NetworkConfig| {
+            let mut network = Network::with_rng(7, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+            network.set_config(config);
+            assert!(network.bracha_broadcast(7, 0).0);
+            let breakdown = network.delivery_breakdown();
+            network.close();
+            assert_eq!(breakdown.len(), 7);
+            for b in breakdown.values() {
+                assert_eq!(b.quorum + b.environment() + b.processing, b.latency);
+            }
+            breakdown
+        };
+
+        // Leader echoes its own INIT: the first ECHO of every quorum is one
+        // link away, the others two, nodes wait for the others
+        let constant = NetworkConfig {
+            latency: Latency::Constant(ms(10)),
+            ..NetworkConfig::default()
+        };
+        for b in run(constant.clone()).values() {
+            assert!(b.hops >= 2);
+            assert!(b.transit >= ms(10) * b.hops as u32);
+            assert!(b.quorum > Duration::ZERO && b.quorum <= b.latency);
+            assert_eq!(b.queuing, Duration::ZERO);
+        }
+
+        // Stragglers keep the nodes waiting for their quorums
+        let jittery = run(NetworkConfig {
+            jitter: ms(30),
+            ..constant.clone()
+        });
+        assert!(jittery.values().any(|b| b.quorum > ms(5)));
+
+        // Capped uplinks show up as queuing
+        let capped = run(NetworkConfig {
+            uplink: Some(UplinkCap {
+                rate: 2_000,
+                burst: 100,
+            }),
+            ..constant
+        });
+        assert!(capped.values().any(|b| b.queuing > ms(5)));
+    }
+
+    #[test]
+    fn termination_policies() {
+        // Run ends with the third delivery instead of the last one
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_termination(Termination::Quorum(3));
+        let (success, results) = network.bracha_broadcast(7, 0);
+        assert!(!success);
+        assert_eq!(results.len(), 3);
+        assert_eq!(network.manifest().get("termination"), Some("quorum:3"));
+        // Nodes left running don't get in the way of the next run
+        network.set_termination(Termination::AllGood);
+        assert!(network.bracha_broadcast(7, 0).0);
+        network.close();
+
+        // Silent leader: honest nodes wait forever, without a time limit
+        // only idleness stops the run
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_termination(Termination::Idle(Duration::from_millis(50)));
+        let (success, results) = network.bracha_broadcast(7, 6);
+        assert!(!success && results.is_empty());
+        assert!(network.stats().elapsed() < Duration::from_secs(5));
+
+        // Timers left in transit keep the run alive until they fire
+        network.set_broadcast_config(BroadcastConfig {
+            liveness_timeout: Some(Duration::from_millis(100)),
+            ..BroadcastConfig::default()
+        });
+        network.bracha_broadcast(7, 6);
+        assert_eq!(network.stalled().len(), 5);
+
+        network.close();
+
+        // Any predicate over the deliveries so far
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_termination(Termination::When(|progress| {
+            progress.delivered() == 1 && progress.in_flight > 0
+        }));
+        let (_, results) = network.bracha_broadcast(7, 0);
+        assert_eq!(results.len(), 1);
+        network.close();
+    }
+
+    #[test]
+    fn resources_are_released() {
+        let counts = |threads, channels| ResourceCounts { threads, channels };
+        // One thread and one channel per node, and the channel of the network
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(0));
+        assert_eq!(network.resources().live(), counts(4, 5));
+        for _ in 0..3 {
+            assert!(network.bracha_broadcast(7, 0).0);
+        }
+        assert_eq!(network.resources().live(), counts(4, 5));
+
+        // Thread of a dead node is gone with its channel
+        network.kill(3);
+        network.bracha_broadcast(7, 0);
+        assert_eq!(network.resources().live(), counts(3, 4));
+        network.close();
+
+        // Dropping a network stops its nodes as well
+        let network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(0));
+        let resources = network.resources().clone();
+        drop(network);
+        assert_eq!(resources.live(), counts(0, 0));
+
+        // Even in the middle of a run, as a test bails out: nodes stop
+        // without handling the messages left in their queue
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Random, RngSource::Seeded(0));
+        let resources = network.resources().clone();
+        network.set_termination(Termination::When(|progress| {
+            assert_eq!(progress.delivered(), 0, "giving up");
+            false
+        }));
+        let inputs = (0..7).map(|id| (id, id)).collect();
+        let start = Instant::now();
+        let bailed = std::panic::catch_unwind(std::panic::AssertUnwindSafe(move || {
+            network.common_subset(&inputs);
+        }));
+        assert!(bailed.is_err());
+        assert!(start.elapsed() < Duration::from_secs(1), "{:?}", start.elapsed());
+        assert_eq!(resources.live(), counts(0, 0));
+    }
+
+    #[test]
+    fn config_validation_diagnostics() {
+        let fields = |config: &Config| -> Vec<&'static str> {
+            match config.validate() {
+                Ok(()) => vec![],
+                Err(diagnostics) => diagnostics.iter().map(|d| d.field).collect(),
+            }
+        };
+        assert_eq!(Config::default().validate(), Ok(()));
+
+        // Quorums of 6 nodes don't survive 2 malicious nodes
+        let config = Config {
+            num_nodes: 6,
+            num_malicious: 2,
+            ..Config::default()
+        };
+        let diagnostics = config.validate().unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].field, "num_malicious");
+        assert!(diagnostics[0].hint.contains("7 nodes"));
+
+        // 2 nodes cut a ring apart, a grid of 3 columns takes 3
+        assert_eq!(Topology::Ring.connectivity(7), 2);
+        assert_eq!(Topology::Grid(3).connectivity(9), 2);
+        assert_eq!(Topology::Grid(1).connectivity(4), 1);
+        assert_eq!(Topology::Complete.connectivity(7), 6);
+        let ring = Config {
+            num_nodes: 7,
+            num_malicious: 1,
+            topology: Topology::Ring,
+            ..Config::default()
+        };
+        assert_eq!(ring.validate(), Ok(()));
+        let ring = Config {
+            num_malicious: 2,
+            ..ring
+        };
+        assert_eq!(fields(&ring), vec!["topology"]);
+
+        // Nodes would give up before the first message arrives
+        let impatient = Config {
+            network: NetworkConfig {
+                latency: Latency::Constant(Duration::from_millis(10)),
+                ..NetworkConfig::default()
+            },
+            protocols: ProtocolConfig {
+                broadcast: BroadcastConfig {
+                    liveness_timeout: Some(Duration::from_millis(5)),
+                    ..BroadcastConfig::default()
+                },
+                ..ProtocolConfig::default()
+            },
+            ..Config::default()
+        };
+        assert_eq!(fields(&impatient), vec!["protocols.broadcast.liveness_timeout"]);
+
+        // Every problem is reported at once, no network is built
+        let config = Config {
+            num_nodes: 4,
+            num_malicious: 2,
+            topology: Topology::Grid(2),
+            ..impatient
+        };
+        assert_eq!(fields(&config).len(), 3);
+        let config = Config {
+            topology: Topology::Grid(0),
+            ..config
+        };
+        assert_eq!(fields(&config), vec!["num_malicious", "topology"]);
+        assert!(Network::from_config(&config, RngSource::Seeded(0)).is_err());
+
+        let mut network = Network::from_config(&Config {
+                num_nodes: 7,
+                topology: Topology::Ring,
+                routing: true,
+                ..Config::default()
+            }, RngSource::Seeded(0)).unwrap();
+        network.set_schedule(Schedule::Deterministic);
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn scenario_recorded_by_hand() {
+        // Execution steered by hand: node 1 hears the ECHO of node 2 first
+        // and never gets the READY of node 0, malicious node 3 turns silent
+        // after two messages
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(5));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_fault_schedule(3, FaultSchedule::new(vec![2]));
+        let controller = network.controller();
+        controller.force_deliver(|msg| msg.from == 2 && msg.to == 1 && msg.phase == Phase::Echo);
+        controller.drop_next(1, |msg| msg.from == 0 && msg.to == 1 && msg.phase == Phase::Ready);
+        let broadcast = |network: &mut Network| -> HashMap<NodeId, Output> {
+            let (_, results) = network.bracha_broadcast(7, 0);
+            results
+                .into_iter()
+                .map(|(id, v)| (id, Output::Value(v)))
+                .collect()
+        };
+        let outputs = broadcast(&mut network);
+        assert_eq!(controller.forced(), 1);
+        assert_eq!(controller.dropped(), 1);
+        let recorded = RecordedScenario::record(&network, &outputs);
+        network.close();
+
+        let path = std::env::temp_dir().join(format!("scenario-{}", std::process::id()));
+        recorded.save(&path).unwrap();
+        let loaded = RecordedScenario::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded, recorded);
+        assert_eq!(loaded.manifest.get("kind"), Some("silent"));
+        // Node 1 misses a READY and never delivers
+        assert_eq!(loaded.outputs.keys().cloned().collect::<Vec<_>>(), vec![0, 2]);
+
+        // Replay takes the same steps without the controller
+        loaded.replay(broadcast).unwrap();
+        let network = loaded.network().unwrap();
+        assert_eq!(network.manifest().get("schedule"), Some("script"));
+        assert_eq!(network.manifest().get("fault_schedules"), Some("3 2"));
+        network.close();
+
+        // Regression: the recorded outputs must come out of the deliveries
+        let mut truncated = loaded.clone();
+        truncated.deliveries.truncate(5);
+        assert!(truncated.replay(broadcast).is_err());
+        let mut changed = loaded.clone();
+        changed.outputs.insert(0, String::from("Value(8)"));
+        assert!(changed.replay(broadcast).is_err());
+        assert!(RecordedScenario::parse("deliver 0 x").is_err());
+    }
+
+    #[test]
+    fn run_assertions() {
+        let n = 7;
+        let mut network = Network::with_rng(n, 2, MaliciousKind::Silent, RngSource::Seeded(2));
+        network.set_schedule(Schedule::Deterministic);
+        let broadcast = |network: &mut Network| -> HashMap<NodeId, Output> {
+            let (_, results) = network.bracha_broadcast(7, 0);
+            results
+                .into_iter()
+                .map(|(id, v)| (id, Output::Value(v)))
+                .collect()
+        };
+        broadcast(&mut network);
+        let trace = network.trace();
+        assert!(eventually(all_honest_delivered(7)).holds(trace));
+        assert!(never(two_honest_deliver_different()).holds(trace));
+        assert!(messages_of_type(Phase::Ready).count(trace) <= n * n);
+        assert_eq!(messages_of_type(Phase::Leader).count(trace), 1);
+        assert_eq!(messages_from(6).count(trace), 0);
+        assert!(eventually(delivered(0, 7)).holds(trace));
+        // Nobody delivered before the run started
+        assert!(!always(all_honest_delivered(7)).holds(trace));
+        assert!(!eventually(all_honest_delivered(8)).holds(trace));
+        network.close();
+
+        // Same assertions in the syntax of scenario files
+        for text in [
+            "eventually all-honest-delivered 7",
+            "never honest-disagree",
+            "always delivered 3 7",
+            "count ready <= 49",
+            "count from:6 == 0",
+            "count to:1 >= 1",
+        ] {
+            let assertion: Assertion = text.parse().unwrap();
+            assert_eq!(assertion.to_string(), text);
+        }
+        assert!("sometimes honest-disagree".parse::<Assertion>().is_err());
+        assert!("count gossip <= 3".parse::<Assertion>().is_err());
+
+        // Expectations of a scenario file are checked on replay
+        #[cfg(feature = "experiments")]
+        {
+            let mut network =
+                Network::with_rng(n, 2, MaliciousKind::Silent, RngSource::Seeded(2));
+            network.set_schedule(Schedule::Deterministic);
+            let outputs = broadcast(&mut network);
+            let mut recorded = RecordedScenario::record(&network, &outputs);
+            network.close();
+            recorded.expectations = vec![
+                eventually(all_honest_delivered(7)),
+                "count ready <= 49".parse().unwrap(),
+            ];
+            let loaded = RecordedScenario::parse(&recorded.to_string()).unwrap();
+            assert_eq!(loaded, recorded);
+            loaded.replay(broadcast).unwrap();
+
+            let mut strict = loaded.clone();
+            strict.expectations.push("count ready <= 10".parse().unwrap());
+            let error = strict.replay(broadcast).unwrap_err();
+            assert!(error.contains("count ready <= 10"));
+        }
+    }
+
+    #[test]
+    fn derived_parameters_are_explained() {
+        let network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        let parameters = network.parameters();
+        assert_eq!(parameters.get("max_faulty_nodes"), Some(2));
+        assert_eq!(parameters.get("min_honnest_nodes"), Some(5));
+        assert_eq!(parameters.get("echo_quorum"), Some(4));
+        assert_eq!(parameters.get("ready_amplification"), Some(3));
+        assert_eq!(parameters.get("crash_quorum"), Some(4));
+        assert_eq!(parameters.get("quorum_overlap"), Some(3));
+        let text = parameters.to_string();
+        assert_eq!(text.lines().count(), parameters.thresholds.len() + 1);
+        assert!(text.contains("echo_quorum = 4: min_honnest_nodes - 1"));
+        network.close();
+
+        // Nodes count n / 3 liars, one more than 6 nodes tolerate
+        let parameters = Parameters::new(6, 1, FaultModel::Byzantine);
+        assert_eq!(parameters.get("max_faulty_nodes"), Some(1));
+        assert_eq!(parameters.get("max_malicious_nodes"), Some(2));
+        assert!(parameters.to_string().contains("when 3 divides n"));
+        let hybrid = Parameters::new(6, 2, FaultModel::Hybrid { byzantine: 1, crash: 1 });
+        assert_eq!(hybrid.get("max_malicious_nodes"), Some(1));
+        assert_eq!(hybrid.get("min_honnest_nodes"), Some(4));
+    }
+
+    #[test]
+    fn crash_model_with_majority_quorums() {
+        let byzantine = Quorums::new(FaultModel::Byzantine, 7);
+        assert_eq!((byzantine.max_faulty, byzantine.quorum()), (2, 5));
+        let crash = Quorums::new(FaultModel::Crash, 7);
+        assert_eq!((crash.max_faulty, crash.quorum(), crash.with_correct()), (3, 4, 4));
+        assert!(byzantine.intersect() && crash.intersect());
+        // Majorities are too small to intersect in a correct node when
+        // faulty nodes lie
+        let majorities = Quorums {
+            model: FaultModel::Byzantine,
+            ..crash
+        };
+        assert!(!majorities.intersect());
+
+        // 3 crashes out of 7: majority-ack broadcast goes on, Bracha's
+        // quorums of 5 never fill up
+        let crashed = |kind| {
+            let mut network =
+                Network::with_fault_model(7, 3, kind, RngSource::Seeded(0), FaultModel::Crash);
+            network.set_schedule(Schedule::Deterministic);
+            network
+        };
+        let mut network = crashed(MaliciousKind::Silent);
+        assert_eq!(network.manifest().get("fault_model"), Some("crash"));
+        let (success, results) = network.majority_broadcast(7, 0);
+        assert!(success);
+        assert!((0..4).all(|id| results.get(&id) == Some(&7)));
+        let (success, results) = network.bracha_broadcast(7, 0);
+        assert!(!success && results.is_empty());
+
+        // Silent leader: nobody ever hears of a value
+        let (success, results) = network.majority_broadcast(7, 6);
+        assert!(!success && results.is_empty());
+        network.close();
+
+        // Leader crashes after sending to a single node, the ACK of that
+        // node is enough for the others to deliver
+        let mut network = crashed(MaliciousKind::Crash(CrashPoint::DuringReady(1)));
+        let (success, results) = network.majority_broadcast(5, 6);
+        assert!(success);
+        assert!((0..4).all(|id| results.get(&id) == Some(&5)));
+        network.close();
+
+        // Validation picks the thresholds of the fault model
+        let crash = Config {
+            num_nodes: 7,
+            num_malicious: 3,
+            fault_model: FaultModel::Crash,
+            ..Config::default()
+        };
+        assert_eq!(crash.validate(), Ok(()));
+        let byzantine = Config {
+            fault_model: FaultModel::Byzantine,
+            ..crash.clone()
+        };
+        assert_eq!(byzantine.validate().unwrap_err()[0].field, "num_malicious");
+        let lying = Config {
+            kind: MaliciousKind::Equivocate,
+            ..crash
+        };
+        assert_eq!(lying.validate().unwrap_err()[0].field, "kind");
+    }
+
+    #[test]
+    fn terminating_broadcast_outputs_bottom() {
+        // Honest sender: every honest node outputs its value
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.terminating_broadcast(42, 0);
+            assert!(success, "{:?}: {:?}", kind, results);
+            assert!((0..5).all(|id| results[&id] == Some(42)), "{:?}", results);
+            network.close();
+        }
+
+        // Mute sender: Bracha broadcast waits forever, honest nodes of the
+        // terminating one vote at their timeout and all output bottom
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_time_limit(Duration::from_millis(200));
+        let (success, results) = network.bracha_broadcast(42, 3);
+        assert!(!success && (0..3).all(|id| !results.contains_key(&id)));
+        let (success, results) = network.terminating_broadcast(42, 3);
+        assert!(success, "{:?}", results);
+        assert!((0..3).all(|id| results[&id].is_none()), "{:?}", results);
+        assert_eq!(network.counter("timeouts").len(), 3);
+        network.close();
+
+        // Faulty sender sending different values: honest nodes agree on one
+        // of them or on bottom
+        for kind in [MaliciousKind::Mirror, MaliciousKind::Equivocate] {
+            for seed in 0..5 {
+                let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(seed));
+                network.set_schedule(Schedule::Deterministic);
+                let (success, results) = network.terminating_broadcast(42, 6);
+                assert!(success, "{:?} {}: {:?}", kind, seed, results);
+                network.close();
+            }
+        }
+    }
+
+    #[test]
+    fn phase_king_survives_byzantine_kings() {
+        let agree = |kind: MaliciousKind, first_king, seed, inputs: &HashMap<NodeId, Value>| {
+            let mut network = Network::with_rng(9, 2, kind.clone(), RngSource::Seeded(seed));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_phase_king_config(PhaseKingConfig {
+                first_king,
+                ..PhaseKingConfig::default()
+            });
+            let (success, results) = network.phase_king(inputs);
+            assert!(success, "{:?} {}: {:?} {:?}", kind, seed, inputs, results);
+            network.close();
+            results[&0]
+        };
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            for seed in 0..8 {
+                // Honest nodes with the same input decide it, even with the
+                // faulty nodes 7 and 8 as the kings of the first phases
+                let same: HashMap<NodeId, Value> = (0..9).map(|id| (id, 5)).collect();
+                assert_eq!(agree(kind.clone(), 7, seed, &same), 5);
+
+                // Split inputs: the honest king of the last phase settles
+                // them, or an honest one of the first phases
+                let split: HashMap<NodeId, Value> =
+                    (0..9).map(|id| (id, 1 + (id + seed as usize) % 3)).collect();
+                for first_king in [0, 7, 8] {
+                    agree(kind.clone(), first_king, seed, &split);
+                }
+            }
+        }
+
+        // Concurrent nodes, rounds last long enough for every message
+        let mut network = Network::with_rng(5, 1, MaliciousKind::Mirror, RngSource::Seeded(0));
+        let inputs = (0..5).map(|id| (id, id % 2)).collect();
+        let (success, results) = network.phase_king(&inputs);
+        assert!(success, "{:?}", results);
+        network.close();
+    }
+
+    #[test]
+    fn late_joining_node() {
+        let late = |schedule, at, buffer| {
+            let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+            network.set_schedule(schedule);
+            network.set_time_limit(Duration::from_millis(200));
+            network.set_late_join(3, LateJoin { at, buffer });
+            let (success, results) = network.bracha_broadcast(7, 0);
+            (success, results, network)
+        };
+        // Node 3 gets the SEND, three ECHO and three READY, buffered it
+        // handles them once it joins and delivers like the others
+        let (success, results, network) = late(Schedule::Deterministic, JoinAt::Messages(3), true);
+        assert!(success, "{:?}", results);
+        assert_eq!(network.samples("joined_after"), vec![(3, 3)]);
+        assert_eq!(network.counter("buffered")[&3], 3);
+        assert_eq!(network.manifest().get("late_joins"), Some("3 after 3 messages, buffered"));
+        network.close();
+
+        // Missing a few messages, the READY of the others bring it along,
+        // missing six only the last READY is left
+        let (success, _, network) = late(Schedule::Deterministic, JoinAt::Messages(3), false);
+        assert!(success);
+        assert_eq!(network.counter("missed")[&3], 3);
+        network.close();
+        let (success, results, network) = late(Schedule::Deterministic, JoinAt::Messages(6), false);
+        assert!(!success && !results.contains_key(&3), "{:?}", results);
+        assert!((0..3).all(|id| results.get(&id) == Some(&7)));
+        assert_eq!(network.counter("missed")[&3], 6);
+        network.close();
+
+        // Joining after some time, once every message reached it
+        let after = JoinAt::After(Duration::from_millis(50));
+        let (success, _, network) = late(Schedule::Concurrent, after, true);
+        assert!(success);
+        assert_eq!(network.samples("joined_after").len(), 1);
+        network.close();
+        let (success, results, network) = late(Schedule::Concurrent, after, false);
+        assert!(!success && !results.contains_key(&3), "{:?}", results);
+        network.close();
+    }
+
+    #[test]
+    fn late_node_handles_its_buffer_as_it_arrived() {
+        // Node 3 stops after echoing INIT, the messages it buffered count
+        // as the ones it got at the time, it behaves as if it were on time
+        let run = |schedule: Vec<usize>, join: Option<usize>| {
+            let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(1));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_fault_schedule(3, FaultSchedule::new(schedule));
+            if let Some(k) = join {
+                let at = JoinAt::Messages(k);
+                network.set_late_join(3, LateJoin { at, buffer: true });
+            }
+            let (success, results) = network.bracha_broadcast(5, 0);
+            assert!(success);
+            let messages = network.stats().total_messages();
+            network.close();
+            (results.contains_key(&3), messages)
+        };
+        assert_eq!(run(vec![2], Some(4)), run(vec![2], None));
+        assert_eq!(run(vec![0, 3], Some(5)), run(vec![0, 3], None));
+
+        // Node 6 delivers before the end of its buffer, helping it goes on
+        // with the rest
+        let run = |join: Option<usize>| {
+            let mut network = Network::with_rng(7, 0, MaliciousKind::Silent, RngSource::Seeded(1));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_after_delivery(AfterDelivery::Participate);
+            if let Some(k) = join {
+                let at = JoinAt::Messages(k);
+                network.set_late_join(6, LateJoin { at, buffer: true });
+            }
+            let (success, results) = network.bracha_broadcast(5, 0);
+            assert!(success && results[&6] == 5, "{:?}", results);
+            let handled = network.counter("handled_after_delivery").get(&6).copied();
+            network.close();
+            handled
+        };
+        assert_eq!(run(Some(13)), run(None));
+        assert!(run(None).is_some());
+    }
+
+    #[test]
+    fn adversary_knowledge_models() {
+        let inputs = HashMap::from([(0, 4), (1, 9)]);
+        let duel = |knowledge| {
+            let mut network = Network::with_fault_model(
+                5,
+                1,
+                MaliciousKind::Silent,
+                RngSource::Seeded(0),
+                FaultModel::Crash,
+            );
+            network.set_schedule(Schedule::Deterministic);
+            network.set_paxos_config(PaxosConfig {
+                leader_election: false,
+                ..PaxosConfig::default()
+            });
+            network.set_time_limit(Duration::from_millis(500));
+            network.set_adversary_knowledge(knowledge);
+            network
+                .controller()
+                .prioritize(|view| !view.description.starts_with("PROTOCOL(<ACCEPT,"));
+            let (success, _) = network.paxos(&inputs);
+            let prepares = messages_of_type(Phase::Init).count(network.trace());
+            let manifest = network.manifest();
+            network.close();
+            (success, prepares, manifest)
+        };
+        // Holding the ACCEPTs back takes reading them: the omniscient
+        // scheduler has the proposers preempt each other, the one that only
+        // reads the messages of the crashed node can't tell the ACCEPTs of
+        // the others apart
+        let (success, prepares, manifest) = duel(Knowledge::Omniscient);
+        assert!(!success && prepares > 100, "{} PREPAREs", prepares);
+        assert_eq!(manifest.get("knowledge"), None);
+        let (success, prepares, manifest) = duel(Knowledge::Local);
+        assert!(success, "{} PREPAREs", prepares);
+        assert_eq!(manifest.get("knowledge"), Some("local"));
+
+        // Messages out of sight only show their link
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_adversary_knowledge(Knowledge::Local);
+        let controller = network.controller();
+        let seen = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let log = seen.clone();
+        controller.drop_next(usize::MAX, move |view| {
+            log.lock().unwrap().push(view.clone());
+            false
+        });
+        assert!(network.bracha_broadcast(7, 0).0);
+        network.close();
+        let seen = seen.lock().unwrap();
+        assert!(seen.iter().any(|view| !view.known));
+        for view in seen.iter() {
+            assert_eq!(view.known, view.from == 3 || view.to == 3, "{:?}", view);
+            assert_eq!(view.known, !view.description.is_empty(), "{:?}", view);
+        }
+    }
+
+    #[test]
+    fn paxos_dueling_proposers() {
+        let inputs = HashMap::from([(0, 4), (1, 9)]);
+        let crashed = |config: PaxosConfig| {
+            let mut network = Network::with_fault_model(
+                5,
+                1,
+                MaliciousKind::Silent,
+                RngSource::Seeded(0),
+                FaultModel::Crash,
+            );
+            network.set_schedule(Schedule::Deterministic);
+            network.set_paxos_config(config);
+            network.set_time_limit(Duration::from_millis(500));
+            network
+        };
+
+        // Adversarial scheduler: ACCEPTs wait for every other message, each
+        // proposer preempts the other before it gets a value accepted
+        let mut network = crashed(PaxosConfig {
+            leader_election: false,
+            ..PaxosConfig::default()
+        });
+        network
+            .controller()
+            .prioritize(|view| !view.description.starts_with("PROTOCOL(<ACCEPT,"));
+        let (success, results) = network.paxos(&inputs);
+        assert!(!success && results.is_empty());
+        let prepares = messages_of_type(Phase::Init).count(network.trace());
+        assert!(prepares > 100, "{} PREPAREs", prepares);
+        network.close();
+
+        // Only the leader proposes, the same scheduler can't stop it
+        let mut network = crashed(PaxosConfig::default());
+        network
+            .controller()
+            .prioritize(|view| !view.description.starts_with("PROTOCOL(<ACCEPT,"));
+        let (success, results) = network.paxos(&inputs);
+        assert!(success);
+        assert!((0..4).all(|id| results.get(&id) == Some(&4)));
+        network.close();
+
+        // Everything node 0 sends is lost: the others suspect it once their
+        // timer runs out and node 1 leads in its place, node 0 still learns
+        let mut network = crashed(PaxosConfig::default());
+        network.controller().drop_next(usize::MAX, |view| view.from == 0);
+        let (success, results) = network.paxos(&HashMap::from([(1, 9), (2, 4)]));
+        assert!(success);
+        assert!((0..4).all(|id| results.get(&id) == results.get(&1)));
+        let prepares = network
+            .trace()
+            .events
+            .iter()
+            .filter(|event| event.from == 1 && event.msg.phase() == Phase::Init);
+        assert_eq!(prepares.count(), 4);
+        network.close();
+    }
+
+    #[test]
+    fn hybrid_fault_budgets() {
+        let hybrid = FaultModel::Hybrid {
+            byzantine: 1,
+            crash: 1,
+        };
+        assert_eq!("hybrid:1,1".parse(), Ok(hybrid));
+        assert_eq!(hybrid.to_string(), "hybrid:1,1");
+        // 1 liar and 1 crash fit in 6 nodes, 2 liars need 7
+        let quorums = Quorums::new(hybrid, 6);
+        assert_eq!((quorums.max_faulty, quorums.quorum(), quorums.with_honest()), (2, 4, 2));
+        assert!(quorums.intersect());
+        assert_eq!(FaultModel::Byzantine.max_faulty(6), 1);
+        assert_eq!(hybrid.max_faulty(5), 0);
+        let too_few = Quorums {
+            num_nodes: 5,
+            ..quorums
+        };
+        assert!(!too_few.intersect());
+
+        // Bracha with the hybrid thresholds: node 4 equivocates, node 5 is
+        // silent
+        let mut network = Network::with_fault_model(
+            6,
+            2,
+            MaliciousKind::Equivocate,
+            RngSource::Seeded(0),
+            hybrid,
+        );
+        network.set_schedule(Schedule::Deterministic);
+        assert_eq!(network.manifest().get("fault_model"), Some("hybrid:1,1"));
+        let (success, results) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        assert!((0..4).all(|id| results.get(&id) == Some(&7)));
+        assert!(!results.contains_key(&5));
+        network.bracha_broadcast(7, 4);
+        assert!(never(two_honest_deliver_different()).holds(network.trace()));
+        let inputs = (0..6).map(|id| (id, id as Value)).collect();
+        let (success, _) = network.common_subset(&inputs);
+        assert!(success);
+        network.close();
+
+        let config = Config {
+            num_nodes: 6,
+            num_malicious: 2,
+            kind: MaliciousKind::Equivocate,
+            fault_model: hybrid,
+            ..Config::default()
+        };
+        assert_eq!(config.validate(), Ok(()));
+        let small = Config {
+            num_nodes: 5,
+            ..config
+        };
+        let diagnostic = &small.validate().unwrap_err()[0];
+        assert_eq!(diagnostic.field, "num_malicious");
+        assert!(diagnostic.hint.contains("at least 6 nodes"));
+    }
+
+    #[test]
+    fn speculative_smr_rolls_back() {
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 10 + id)).collect();
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, _) = network.smr(&inputs);
+        assert!(success);
+        assert!(network.counter("speculated").is_empty());
+
+        // Node 3 hears of the command of node 1 last, after the leader
+        // ordered it
+        network.set_smr_config(SmrConfig {
+            speculative: true,
+            ..SmrConfig::default()
+        });
+        network.controller().delay_link(1, 3, Duration::from_millis(10));
+        let (success, _) = network.smr(&inputs);
+        assert!(success);
+        assert_eq!(network.counter("speculated")[&0], 4);
+        let rollbacks = network.counter("rollbacks");
+        assert!(!rollbacks.contains_key(&0));
+        assert!(rollbacks[&3] > 0);
+        assert!(network.counter("undone")[&3] >= rollbacks[&3]);
+        assert_eq!(network.manifest().get("smr"), Some("speculative"));
+        network.close();
+    }
+
+    #[test]
+    fn smr_read_consistency() {
+        // Node 3 hears of everything 50ms late, real time so that reads
+        // interleave with the commits
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 10 + id)).collect();
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_config(NetworkConfig {
+            latency: Latency::Constant(Duration::from_millis(1)),
+            ..NetworkConfig::default()
+        });
+        for from in 0..3 {
+            network
+                .controller()
+                .delay_link(from, 3, Duration::from_millis(50));
+        }
+        let mut run = |consistency| {
+            network.set_smr_config(SmrConfig {
+                reads: 6,
+                read_period: Duration::from_millis(5),
+                consistency,
+                ..SmrConfig::default()
+            });
+            let (success, _) = network.smr(&inputs);
+            assert!(success);
+            let staleness: Vec<usize> = network
+                .samples("staleness")
+                .into_iter()
+                .map(|(_, staleness)| staleness)
+                .collect();
+            let failed = network.counter("failed_reads").values().sum::<usize>();
+            assert_eq!(staleness.len() + failed, 4 * 6);
+            staleness
+        };
+
+        // Local reads of node 3 miss what the others committed
+        let local = run(ReadConsistency::Local);
+        assert_eq!(local.len(), 4 * 6);
+        assert!(local.iter().any(|staleness| *staleness > 0));
+        // Reads through the leader never miss a commit, node 3 waits for
+        // them past the next read
+        let linearizable = run(ReadConsistency::Linearizable);
+        assert!(!linearizable.is_empty());
+        assert!(linearizable.iter().all(|staleness| *staleness == 0));
+        assert_eq!(network.manifest().get("smr_reads"), Some("6 linearizable every 5ms"));
+        network.close();
+    }
+
+    #[test]
+    fn smr_client_retries_and_redirects() {
+        // Clients start away from the leader except node 3, whose link to
+        // the leader is 50ms slow
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 10 + id)).collect();
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_config(NetworkConfig {
+            latency: Latency::Constant(Duration::from_millis(1)),
+            ..NetworkConfig::default()
+        });
+        network
+            .controller()
+            .delay_link(3, 0, Duration::from_millis(50));
+        let mut run = |retries| {
+            network.set_smr_config(SmrConfig {
+                client: Some(ClientConfig {
+                    timeout: Duration::from_millis(10),
+                    retries,
+                    ..ClientConfig::default()
+                }),
+                ..SmrConfig::default()
+            });
+            let (success, _) = network.smr(&inputs);
+            assert!(success);
+            assert_eq!(network.counter("redirects").values().sum::<usize>(), 3);
+            assert!(!network.counter("redirects").contains_key(&3));
+            let latencies = network.samples("client_latency_us");
+            (latencies, network.counter("retries"), network.counter("abandoned"))
+        };
+
+        // Node 3 retries through node 1, that forwards to the leader
+        let (latencies, retries, abandoned) = run(3);
+        assert_eq!(latencies.len(), 4);
+        let (_, latency) = latencies.iter().find(|(id, _)| *id == 3).unwrap();
+        assert!(*latency >= 10_000 && *latency < 50_000);
+        assert_eq!(retries, BTreeMap::from([(3, 1)]));
+        assert!(abandoned.is_empty());
+        // Without retries node 3 gives up, the command is still committed
+        let (latencies, _, abandoned) = run(0);
+        assert_eq!(latencies.len(), 3);
+        assert_eq!(abandoned, BTreeMap::from([(3, 1)]));
+        assert_eq!(network.manifest().get("smr_client"), Some("timeout 10ms, 0 retries"));
+        network.close();
+    }
+
+    #[test]
+    fn smr_load_generators() {
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 10 + id)).collect();
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_config(NetworkConfig {
+            latency: Latency::Constant(Duration::from_millis(1)),
+            ..NetworkConfig::default()
+        });
+        let open = Arrivals::Open { rate: 1000.0 };
+        let closed = Arrivals::Closed {
+            outstanding: 2,
+            think: Duration::from_millis(1),
+        };
+        for arrivals in [open, closed] {
+            network.set_smr_config(SmrConfig {
+                load: Some(LoadConfig {
+                    arrivals,
+                    commands: 5,
+                }),
+                ..SmrConfig::default()
+            });
+            let (success, _) = network.smr(&inputs);
+            assert!(success);
+            // Every generated command is committed and answered once
+            assert_eq!(network.samples("client_latency_us").len(), 4 * 5);
+            assert!(network.counter("committed").values().all(|k| *k == 4 * 5));
+        }
+        let manifest = network.manifest();
+        assert_eq!(manifest.get("smr_load"), Some("5 x closed 2 think 1ms"));
+        network.close();
+    }
+
+    #[test]
+    fn smr_mempool_backpressure() {
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 10 + id)).collect();
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_config(NetworkConfig {
+            latency: Latency::Constant(Duration::from_millis(1)),
+            ..NetworkConfig::default()
+        });
+        let mut run = |mempool| {
+            network.set_smr_config(SmrConfig {
+                load: Some(LoadConfig {
+                    arrivals: Arrivals::Closed {
+                        outstanding: 4,
+                        think: Duration::ZERO,
+                    },
+                    commands: 4,
+                }),
+                mempool,
+                ..SmrConfig::default()
+            });
+            let (success, _) = network.smr(&inputs);
+            assert!(success);
+            assert_eq!(network.samples("client_latency_us").len(), 4 * 4);
+            let rejected = network.counter("rejected").values().sum::<usize>();
+            let backpressured = network.counter("backpressured").values().sum::<usize>();
+            assert_eq!(rejected, backpressured);
+            let mempool = network.samples("mempool").into_iter().map(|(_, k)| k).max();
+            (rejected, mempool.unwrap())
+        };
+
+        // 16 commands at once queue up at the leader
+        let (rejected, mempool) = run(None);
+        assert_eq!(rejected, 0);
+        assert!(mempool > 2);
+        // Clients back off instead, all commands still go through
+        let (rejected, mempool) = run(Some(2));
+        assert!(rejected > 0);
+        assert!(mempool < 2);
+        assert_eq!(network.manifest().get("smr_mempool"), Some("2"));
+        network.close();
+    }
+
+    #[test]
+    fn slow_node_cpu() {
+        let cpu = CpuModel::uniform(Duration::from_millis(2)).with_speed(3, 0.25);
+        assert_eq!(cpu.cost(0, 100), Duration::from_millis(2));
+        assert_eq!(cpu.cost(3, 100), Duration::from_millis(8));
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_config(NetworkConfig {
+            cpu: Some(cpu),
+            ..NetworkConfig::default()
+        });
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        // Node 3 handles as many messages as the others, 4 times slower,
+        // the quorums of the others form without it
+        let busy = network.stats().busy().clone();
+        assert_eq!(busy[&3], busy[&1] * 4);
+        let delivered = network.stats().latency(Milestone::Delivered).unwrap();
+        assert_eq!(delivered.slowest(), Some(3));
+        assert!(delivered.percentile(75.0) < busy[&3]);
+        assert_eq!(network.manifest().get("cpu"), Some("2ms per message, node 3 x0.25"));
+        network.close();
+    }
+
+    #[test]
+    fn ben_or_agreement() {
+        let split: HashMap<NodeId, Value> = (0..6).map(|id| (id, id % 2)).collect();
+        let ones: HashMap<NodeId, Value> = (0..6).map(|id| (id, 1)).collect();
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            for seed in 0..3 {
+                let mut network = Network::with_rng(6, 1, kind.clone(), RngSource::Seeded(seed));
+                network.set_schedule(Schedule::Deterministic);
+                let (success, results) = network.binary_agreement(&split);
+                assert!(success, "{:?} {}: {:?}", kind, seed, results);
+                // Honest nodes that agree from the start decide right away
+                let (success, results) = network.binary_agreement(&ones);
+                assert!(success);
+                assert!((0..5).all(|id| results[&id] == 1));
+                assert!(network.counter("rounds").values().all(|rounds| *rounds == 1));
+                network.close();
+            }
+        }
+    }
+
+    #[test]
+    fn cost_per_protocol() {
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_link_security(LinkSecurity {
+            authenticated: true,
+            encrypted: false,
+        });
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        let report = network.cost_report();
+        let bracha = &report.protocols[&BRACHA_BROADCAST];
+        // INIT of the leader, ECHO and READY of every node to the 3 others
+        assert_eq!(bracha.messages, 3 + 12 + 12);
+        assert_eq!(bracha.ops, BTreeMap::from([(CryptoOp::Mac, 2 * bracha.messages)]));
+        let model = CostModel::default();
+        let expected = bracha.messages as f64 * (1.0 + 2.0 * model.op(CryptoOp::Mac))
+            + bracha.bytes as f64 * model.per_byte;
+        assert!((report.total() - expected).abs() < 1e-6);
+
+        // Merkle proofs are checked by every receiver
+        network.set_link_security(LinkSecurity::default());
+        network.set_cost_model(CostModel {
+            per_message: 0.0,
+            per_byte: 0.0,
+            ..CostModel::default()
+        });
+        let (success, _) = network.batched_broadcast(&[1, 2, 3, 4], 0);
+        assert!(success);
+        let report = network.cost_report();
+        let hashes = report.protocols[&BATCHED_BROADCAST].ops[&CryptoOp::Hash];
+        // 7 to build the tree, 3 for every proof checked by 3 nodes
+        assert_eq!(hashes, 7 + 4 * 3 * 3);
+        assert!((report.cost(BATCHED_BROADCAST) - hashes as f64 * 0.1).abs() < 1e-6);
+        network.close();
+    }
+
+    #[test]
+    fn common_coin_despite_forged_shares() {
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 0)).collect();
+        let mut tosses = vec![];
+        for seed in 0..8 {
+            let mut network = Network::with_rng(4, 1, MaliciousKind::Mirror, RngSource::Seeded(0));
+            network.set_coin_source(TrustedDealer::new(seed));
+            let results = network.run_protocol(COMMON_COIN, &inputs);
+            let coins: Vec<Option<Value>> = (0..3)
+                .map(|id| results.get(&id).and_then(Output::value))
+                .collect();
+            assert!(coins.iter().all(|coin| coin.is_some() && *coin == coins[0]));
+            tosses.push(coins[0].unwrap());
+            // The malicious node flips the bits of its share
+            assert!(network.counter("invalid_shares").values().sum::<usize>() > 0);
+            if seed > 0 {
+                let dealer = format!("trusted dealer, seed {}", seed);
+                assert_eq!(network.manifest().get("coin"), Some(dealer.as_str()));
+            }
+            network.close();
+        }
+        assert!(tosses.contains(&0) && tosses.contains(&1));
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn common_coin_of_threshold_signatures() {
+        // Without a coin source the coin is the combined signature of the
+        // instance, any f + 1 valid shares give the same one
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 0)).collect();
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Mirror, RngSource::Seeded(0));
+        let results = network.run_protocol(COMMON_COIN, &inputs);
+        let root = InstanceId::root(COMMON_COIN);
+        let (scheme, keys) = SimulatedSignatures::deal(0, 4, 2);
+        let shares: BTreeMap<NodeId, SignatureShare> = [1, 2]
+            .into_iter()
+            .map(|id| (id, scheme.sign_share(&keys[id], coin_message(&root))))
+            .collect();
+        let signature = scheme.combine(coin_message(&root), &shares).unwrap();
+        let coin = (signature & 1) as Value;
+        assert!((0..3).all(|id| results.get(&id).and_then(Output::value) == Some(coin)));
+        // The malicious node flips the bits of its share
+        assert!(network.counter("invalid_shares").values().sum::<usize>() > 0);
+        assert_eq!(network.manifest().get("coin"), None);
+        network.close();
+
+        // Every round of an instance tosses a coin of its own
+        assert_ne!(coin_message(&root), coin_message(&root.child(COMMON_COIN, 1)));
+    }
+
+    #[test]
+    fn same_seed_same_protocol_state() {
+        // Every network hashes with keys of its own, runs only repeat if
+        // nodes iterate over their state in a fixed order
+        let broadcast = |tie_break: TieBreak| {
+            let mut network =
+                Network::with_rng(7, 2, MaliciousKind::Equivocate, RngSource::Seeded(3));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_broadcast_config(BroadcastConfig {
+                tie_break,
+                ..BroadcastConfig::default()
+            });
+            // Equivocating leader makes the nodes break ties between echoes
+            let (_, results) = network.bracha_broadcast(7, 6);
+            let run = (results, network.trace().clone(), network.audit().clone());
+            network.close();
+            run
+        };
+        for tie_break in [TieBreak::Plurality, TieBreak::Lowest, TieBreak::OwnEcho] {
+            let (results, trace, audit) = broadcast(tie_break);
+            let (again, again_trace, again_audit) = broadcast(tie_break);
+            assert_eq!(results, again);
+            assert!(trace.same_deliveries(&again_trace));
+            assert_eq!(audit, again_audit);
+        }
+
+        let subset = || {
+            let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 10 + id)).collect();
+            let mut network =
+                Network::with_rng(4, 1, MaliciousKind::Equivocate, RngSource::Seeded(3));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.common_subset(&inputs);
+            assert!(success);
+            let run = (results, network.trace().clone());
+            network.close();
+            run
+        };
+        let (results, trace) = subset();
+        let (again, again_trace) = subset();
+        assert_eq!(results, again);
+        assert!(trace.same_deliveries(&again_trace));
+    }
+
+    #[test]
+    fn aba_on_common_coin() {
+        let split: HashMap<NodeId, bool> = (0..4).map(|id| (id, id % 2 == 0)).collect();
+        let ones: HashMap<NodeId, bool> = (0..4).map(|id| (id, true)).collect();
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(4, 1, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let mut rounds = vec![];
+            for seed in 0..8 {
+                network.set_coin_source(TrustedDealer::new(seed));
+                let (success, decisions, _) = network.aba(&split);
+                assert!(success, "{:?} {}: {:?}", kind, seed, decisions);
+                // Unanimous nodes decide in the first round the coin agrees
+                let (success, decisions, k) = network.aba(&ones);
+                assert!(success);
+                assert!((0..3).all(|id| decisions[&id]));
+                assert!(k >= 1);
+                rounds.push(k);
+            }
+            assert!(rounds.iter().any(|k| *k > 1), "{:?}", rounds);
+            network.close();
+        }
+    }
+
+    #[test]
+    fn online_monitors() {
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 10 * id)).collect();
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.monitor(Invariant::NoHonestEquivocation);
+        network.monitor(Invariant::HonestAgreement);
+        let results = network.run_protocol(EQUIVOCATING, &inputs);
+        assert_eq!(results.len(), 4);
+        let count = |network: &Network, invariant| {
+            network
+                .violations()
+                .iter()
+                .filter(|violation| violation.invariant == invariant)
+                .count()
+        };
+        // Every node equivocates once, whatever the number of copies, and
+        // the last three outputs differ from the first one
+        assert_eq!(count(&network, Invariant::NoHonestEquivocation), 4);
+        assert_eq!(count(&network, Invariant::HonestAgreement), 3);
+        let first = &network.violations()[0];
+        assert!(first.description.contains("after"), "{}", first);
+
+        // Run ends right after the second vote of the first node
+        network.set_stop_on_violation(true);
+        let results = network.run_protocol(EQUIVOCATING, &inputs);
+        assert!(results.is_empty());
+        assert_eq!(network.violations().len(), 1);
+        let monitors = "no-honest-equivocation honest-agreement";
+        assert_eq!(network.manifest().get("monitors"), Some(monitors));
+        network.close();
+
+        // Malicious nodes may equivocate, honest Bracha nodes never do
+        let mut network =
+            Network::with_rng(7, 2, MaliciousKind::Equivocate, RngSource::Seeded(0));
+        network.monitor(Invariant::NoHonestEquivocation);
+        network.monitor(Invariant::HonestAgreement);
+        network.set_stop_on_violation(true);
+        let (success, _) = network.bracha_broadcast(7, 0);
+        assert!(success);
+        assert!(network.violations().is_empty());
+        network.close();
+
+        let mut network =
+            Network::with_rng(4, 1, MaliciousKind::Equivocate, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.monitor(Invariant::NoHonestEquivocation);
+        let (success, _) = network.common_subset(&inputs);
+        assert!(success);
+        assert!(network.violations().is_empty(), "{:?}", network.violations());
+        network.close();
+    }
+
+    #[test]
+    fn broken_invariants_caught_in_debug_builds() {
+        let inputs: HashMap<NodeId, Value> = (0..4).map(|id| (id, 1)).collect();
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.run_protocol(FORGETFUL, &inputs);
+        if cfg!(debug_assertions) {
+            // Nodes panic on the message breaking the invariant, with their
+            // state in the report
+            assert_eq!(network.panics().len(), 4);
+            let report = &network.panics()[0];
+            assert!(report.message.contains("started without an input"));
+            assert!(report.message.contains("Forgetful { started: true"));
+            assert!(report.last_message.contains("START"));
+        } else {
+            assert!(network.panics().is_empty());
+        }
+
+        // Correct handlers keep their invariants whatever the faulty nodes do
+        for kind in [MaliciousKind::Random, MaliciousKind::Equivocate] {
+            let mut network = Network::with_rng(7, 2, kind, RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.bracha_broadcast(7, 0);
+            network.batched_broadcast(&[1, 2, 3], 0);
+            assert!(network.panics().is_empty());
+            network.close();
+        }
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn honey_badger_total_order() {
+        let queues: HashMap<NodeId, Vec<Value>> = (0..7)
+            .map(|id| (id, (0..3).map(|k| 100 * id + k).collect()))
+            .collect();
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.honey_badger(3, &queues);
+            assert!(success, "{:?}: {:?}", kind, results);
+            // Nodes propose their transactions in the order of their queue
+            let order = &results[&0];
+            for id in 0..5 {
+                let own: Vec<&Value> = order.iter().filter(|tx| **tx / 100 == id).collect();
+                assert!(own.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", order);
+            }
+            network.close();
+        }
+
+        // Without faulty nodes every proposal makes it, queues are drained
+        // in as many epochs as they hold transactions
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        let (success, results) = network.honey_badger(3, &queues);
+        assert!(success);
+        assert_eq!(results.len(), 4);
+        let mut committed = results[&0].clone();
+        committed.sort();
+        let mut expected: Vec<Value> = (0..4).flat_map(|id| queues[&id].clone()).collect();
+        expected.sort();
+        assert_eq!(committed, expected);
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn threshold_encrypted_honey_badger() {
+        let (scheme, keys) = SimulatedThreshold::deal(1, 4, 2);
+        let ciphertext = scheme.encrypt(7, 42);
+        let shares: BTreeMap<NodeId, DecryptionShare> = keys
+            .iter()
+            .map(|key| (key.node, scheme.decryption_share(key, &ciphertext)))
+            .collect();
+        assert!(shares.iter().all(|(id, share)| scheme.verify_share(*id, &ciphertext, *share)));
+        assert!(!scheme.verify_share(0, &ciphertext, !shares[&0]));
+        // Shares are bound to the node and to the label
+        assert!(!scheme.verify_share(1, &ciphertext, shares[&0]));
+        let relabeled = Ciphertext { label: 8, ..ciphertext };
+        assert!(!scheme.verify_share(0, &relabeled, shares[&0]));
+        let two: BTreeMap<NodeId, DecryptionShare> = shares.clone().into_iter().take(2).collect();
+        assert_eq!(scheme.combine(&ciphertext, &two), Some(42));
+        let one: BTreeMap<NodeId, DecryptionShare> = shares.into_iter().take(1).collect();
+        assert_eq!(scheme.combine(&ciphertext, &one), None);
+
+        let queues: HashMap<NodeId, Vec<Value>> = (0..7)
+            .map(|id| (id, (0..2).map(|k| 100 * id + k).collect()))
+            .collect();
+        for kind in [MaliciousKind::Mirror, MaliciousKind::Equivocate] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_honey_badger_config(HoneyBadgerConfig {
+                encrypted: true,
+                ..HoneyBadgerConfig::default()
+            });
+            let (success, results) = network.honey_badger(2, &queues);
+            assert!(success, "{:?}: {:?}", kind, results);
+            // Batches hold the transactions, not their ciphertexts
+            assert!(results[&0].iter().all(|tx| *tx % 100 < 2 && *tx / 100 < 7));
+            if kind == MaliciousKind::Mirror {
+                assert!(network.counter("invalid_shares").values().sum::<usize>() > 0);
+            }
+            assert_eq!(network.manifest().get("honey_badger"), Some("encrypted"));
+            let report = network.cost_report();
+            assert!(report.protocols[&HONEY_BADGER].ops[&CryptoOp::DecryptShare] > 0);
+            network.close();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn honey_badger_keeps_early_shares_of_the_next_epochs_only() {
+        let queues: HashMap<NodeId, Vec<Value>> = (0..4)
+            .map(|id| (id, (0..2).map(|k| 100 * id + k).collect()))
+            .collect();
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Random, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_honey_badger_config(HoneyBadgerConfig {
+            encrypted: true,
+            ..HoneyBadgerConfig::default()
+        });
+        let (success, results) = network.honey_badger(2, &queues);
+        assert!(success, "{:?}", results);
+        // Shares the random node makes up for any epoch are dropped
+        assert!(network.counter("dropped_shares").values().sum::<usize>() > 0);
+        network.close();
+    }
+
+    #[test]
+    fn mempool_digests_save_bandwidth() {
+        // Every transaction is submitted to three nodes in a row
+        let pools: HashMap<NodeId, Vec<Value>> = (0..7)
+            .map(|id| (id, (0..40).filter(|tx| (id + 7 - tx % 7) % 7 < 3).collect()))
+            .collect();
+        let mut bytes = HashMap::new();
+        for sync in [
+            MempoolSync::Full,
+            MempoolSync::Announce,
+            MempoolSync::Bloom {
+                bits: 512,
+                hashes: 3,
+            },
+        ] {
+            for kind in [MaliciousKind::Silent, MaliciousKind::Equivocate] {
+                let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+                network.set_schedule(Schedule::Deterministic);
+                network.set_mempool_config(MempoolConfig {
+                    sync,
+                    ..MempoolConfig::default()
+                });
+                let (success, results) = network.mempool(&pools);
+                assert!(success, "{} {:?}: {:?}", sync, kind, results);
+                let duplicates: usize = network.counter("duplicate_transactions").values().sum();
+                if kind == MaliciousKind::Silent {
+                    assert_eq!(results[&0], (0..40).collect::<Vec<Value>>());
+                    bytes.insert(sync.to_string(), network.stats().total_bytes());
+                    // Every holder pushes what a filter misses, only
+                    // requests go to a single node
+                    assert_eq!(duplicates == 0, sync == MempoolSync::Announce, "{}", sync);
+                }
+                network.close();
+            }
+        }
+        assert!(bytes["announce"] * 2 < bytes["full"], "{:?}", bytes);
+        assert!(bytes["bloom 512 bits 3 hashes"] * 2 < bytes["full"], "{:?}", bytes);
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn threshold_signatures_certify_inputs() {
+        let (scheme, keys) = SimulatedSignatures::deal(1, 4, 2);
+        let shares: BTreeMap<NodeId, SignatureShare> = keys
+            .iter()
+            .map(|key| (key.node, scheme.sign_share(key, 7)))
+            .collect();
+        assert!(shares.iter().all(|(id, share)| scheme.verify_share(*id, 7, *share)));
+        assert!(!scheme.verify_share(0, 7, !shares[&0]));
+        assert!(!scheme.verify_share(1, 7, shares[&0]));
+        assert!(!scheme.verify_share(0, 8, shares[&0]));
+        // Any two shares give the same signature, one gives none
+        let first: BTreeMap<NodeId, SignatureShare> = shares.clone().into_iter().take(2).collect();
+        let last: BTreeMap<NodeId, SignatureShare> = shares.clone().into_iter().skip(2).collect();
+        let signature = scheme.combine(7, &first).unwrap();
+        assert_eq!(scheme.combine(7, &last), Some(signature));
+        assert!(scheme.verify(7, signature) && !scheme.verify(8, signature));
+        let one: BTreeMap<NodeId, SignatureShare> = shares.into_iter().take(1).collect();
+        assert_eq!(scheme.combine(7, &one), None);
+
+        // Nodes sign with the keys dealt with the network
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, 42)).collect();
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Mirror, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        let results = network.run_protocol(CERTIFYING, &inputs);
+        let signatures: Vec<Option<Value>> = (0..5)
+            .map(|id| results.get(&id).and_then(Output::value))
+            .collect();
+        let signature = signatures[0].unwrap() as u64;
+        assert!(signatures.iter().all(|s| *s == signatures[0]));
+        let config = network.signatures();
+        assert_eq!(config.scheme.threshold(), 3);
+        assert!(config.scheme.verify(42, signature));
+        assert!(network.counter("invalid_shares").values().sum::<usize>() > 0);
+        network.close();
+    }
+
+    #[test]
+    fn subjective_validity_in_common_subset() {
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, id + 1)).collect();
+        // Data of the input of node 0 is missing at every honest node, that
+        // of node 1 only at nodes 2 and 3
+        let oracle = MissingData::default()
+            .withhold(1, 0..5)
+            .withhold(2, [2, 3]);
+        for kind in [MaliciousKind::Silent, MaliciousKind::Equivocate] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_validity_oracle(oracle.clone());
+            let (success, results) = network.common_subset(&inputs);
+            assert!(success, "{:?}: {:?}", kind, results);
+            match &results[&0] {
+                Output::Set(values) => {
+                    assert!(!values.contains_key(&0));
+                    assert!(values.values().all(|v| (0..5).any(|id| oracle.is_valid(id, *v))));
+                }
+                output => panic!("{:?}", output),
+            }
+            assert!(network.counter("rejected").values().sum::<usize>() >= 5 + 2);
+            let views = "missing data, 0: 1 | 1: 1 | 2: 1 2 | 3: 1 2 | 4: 1";
+            assert_eq!(network.manifest().get("validity"), Some(views));
+            network.close();
+        }
+
+        // Every node accepts every value without an oracle: only agreements
+        // that decided 1 count for the n - f the nodes wait for
+        assert!(AnyValue.accepts_all() && !oracle.accepts_all());
+        for (kind, seed) in [
+            (MaliciousKind::Silent, 1),
+            (MaliciousKind::Equivocate, 2),
+            (MaliciousKind::Random, 3),
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(seed));
+            network.set_time_limit(Duration::from_secs(5));
+            let (success, results) = network.common_subset(&inputs);
+            assert!(success, "{:?}: {:?}", kind, results);
+            for id in 0..5 {
+                assert!(matches!(&results[&id], Output::Set(values) if values.len() >= 5));
+            }
+            assert!(network.counter("rejected").is_empty());
+            network.close();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn pbft_changes_views_past_faulty_primaries() {
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, id + 10)).collect();
+        // Honest primary: its input is decided in the first view
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, results) = network.pbft(&inputs);
+        assert!(success, "{:?}", results);
+        assert!((0..5).all(|id| results[&id] == 10));
+        assert!(network.counter("view_changes").is_empty());
+        network.close();
+
+        // Faulty primaries, one after the other: nodes move on until an
+        // honest primary starts a view
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_pbft_config(PbftConfig {
+                primary: 5,
+                ..PbftConfig::default()
+            });
+            let (success, results) = network.pbft(&inputs);
+            assert!(success, "{:?}: {:?}", kind, results);
+            if kind != MaliciousKind::Mirror {
+                assert_eq!(results[&0], 10);
+                assert!(network.counter("view_changes").values().sum::<usize>() >= 5 * 2);
+            }
+            if kind == MaliciousKind::Equivocate {
+                assert!(network.counter("invalid_signatures").values().sum::<usize>() > 0);
+            }
+            network.close();
+        }
+
+        // Same with the real timers of concurrent runs
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Equivocate, RngSource::Seeded(1));
+        network.set_pbft_config(PbftConfig {
+            primary: 6,
+            view_timeout: Duration::from_millis(20),
+        });
+        let (success, results) = network.pbft(&inputs);
+        assert!(success, "{:?}", results);
+        network.close();
+    }
+
+    #[test]
+    fn consistent_broadcast_lacks_totality() {
+        // Honest leader: both primitives deliver its value, the consistent
+        // one without the READY phase
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Random,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.consistent_broadcast(42, 0);
+            assert!(success, "{:?}: {:?}", kind, results);
+            let consistent = network.stats().total_messages();
+            let (success, _) = network.bracha_broadcast(42, 0);
+            assert!(success, "{:?}", kind);
+            assert!(consistent < network.stats().total_messages());
+            network.close();
+        }
+
+        // Equivocating leader: node 0 gets its value, nodes 1 and 2 another
+        // one, they deliver it with the leader's ECHO and node 0 is left
+        // waiting, Bracha broadcast carries node 0 along with the READY
+        let mut runs = vec![];
+        for bracha in [false, true] {
+            let mut network =
+                Network::with_rng(4, 1, MaliciousKind::Equivocate, RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_time_limit(Duration::from_millis(100));
+            let (success, results) = if bracha {
+                network.bracha_broadcast(42, 3)
+            } else {
+                network.consistent_broadcast(42, 3)
+            };
+            assert!(!success);
+            let honest: Vec<Option<Value>> = (0..3).map(|id| results.get(&id).cloned()).collect();
+            runs.push(honest);
+            network.close();
+        }
+        let (consistent, bracha) = (&runs[0], &runs[1]);
+        // Consistency: honest nodes that deliver agree
+        assert_eq!(consistent[0], None);
+        assert!(consistent[1].is_some() && consistent[1] == consistent[2]);
+        // Totality: if an honest node delivers, every honest node does
+        assert!(bracha.iter().all(|v| v.is_some() && *v == bracha[0]));
+    }
+
+    #[test]
+    fn graded_broadcast_grades_within_one() {
+        let graded = |network: &mut Network, leader| {
+            let (success, results) = network.graded_broadcast(42, leader);
+            let grades: BTreeMap<NodeId, (Option<Value>, Grade)> = results.into_iter().collect();
+            (success, grades)
+        };
+        // Honest leader: every honest node grades its value 2, as soon as
+        // n - f READY arrive
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Random,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            // Random nodes flood lockstep runs, see `Schedule::Deterministic`
+            let schedule = match kind {
+                MaliciousKind::Random => Schedule::Concurrent,
+                _ => Schedule::Deterministic,
+            };
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(schedule);
+            let (success, grades) = graded(&mut network, 0);
+            assert!(success, "{:?}: {:?}", kind, grades);
+            assert!((0..5).all(|id| grades[&id] == (Some(42), 2)));
+            assert!(network.counter("timeouts").is_empty());
+            network.close();
+        }
+
+        // Faulty leader: honest grades stay within one of each other on a
+        // single value, whatever the leader sends
+        for (n, f, kind, expected) in [
+            (4, 1, MaliciousKind::Silent, (None, 0)),
+            (4, 1, MaliciousKind::Equivocate, (Some(MALICIOUS_VALUE), 1)),
+            (7, 2, MaliciousKind::Silent, (None, 0)),
+        ] {
+            let mut network = Network::with_rng(n, f, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, grades) = graded(&mut network, n - 1);
+            assert!(success, "{:?}: {:?}", kind, grades);
+            assert!((0..n - f).all(|id| grades[&id] == expected), "{:?}", grades);
+            assert_eq!(network.counter("timeouts").len(), n - f);
+            network.close();
+        }
+        for kind in [MaliciousKind::Mirror, MaliciousKind::Equivocate] {
+            for seed in 0..5 {
+                let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(seed));
+                let (success, grades) = graded(&mut network, 6);
+                assert!(success, "{:?} {}: {:?}", kind, seed, grades);
+                network.close();
+            }
+        }
+    }
+
+
+    #[test]
+    fn leader_election_resists_bias() {
+        let elect = |kind: MaliciousKind, f, seed| {
+            let schedule = match kind {
+                MaliciousKind::Random => Schedule::Concurrent,
+                _ => Schedule::Deterministic,
+            };
+            let mut network = Network::with_rng(7, f, kind.clone(), RngSource::Seeded(seed));
+            network.set_schedule(schedule);
+            network.set_coin_source(TrustedDealer::new(seed));
+            let (success, results) = network.leader_election();
+            assert!(success, "{:?} {}: {:?}", kind, seed, results);
+            let invalid: usize = network.counter("invalid_shares").values().sum();
+            let redraws = network.counter("redraws").len();
+            network.close();
+            (results[&0], invalid, redraws)
+        };
+        // Each coin source elects a leader, every node has its turn
+        let seeds = 0..32;
+        let leaders: Vec<NodeId> = seeds
+            .clone()
+            .map(|seed| elect(MaliciousKind::Silent, 0, seed).0)
+            .collect();
+        assert!((0..7).all(|id| leaders.contains(&id)), "{:?}", leaders);
+        assert!(seeds.clone().any(|seed| elect(MaliciousKind::Silent, 0, seed).2 > 0));
+
+        // Faulty nodes withholding or forging shares elect the same leaders
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Random,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            for seed in seeds.clone() {
+                let (leader, invalid, _) = elect(kind.clone(), 2, seed);
+                assert_eq!(leader, leaders[seed as usize], "{:?} {}", kind, seed);
+                if kind == MaliciousKind::Mirror {
+                    assert!(invalid > 0);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn atomic_broadcast_logs_are_prefixes() {
+        let queues: HashMap<NodeId, Vec<Value>> = (0..7)
+            .map(|id| (id, (0..2).map(|k| 100 * id + k).collect()))
+            .collect();
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.atomic_broadcast(6, &queues);
+            assert!(success, "{:?}: {:?}", kind, results);
+            // Every honest node delivers the same log, and outputs it
+            let logs = network.delivery_logs();
+            for id in 0..5 {
+                assert_eq!(logs.get(&id), Some(&results[&0]), "{:?}", kind);
+                assert_eq!(results[&id], results[&0]);
+            }
+            // Slots decide a value each at most, starting from another
+            // candidate: every honest node gets its first value in
+            assert!(results[&0].len() <= 6);
+            assert!((0..5).all(|id| results[&0].contains(&(100 * id))), "{:?}", results);
+            network.close();
+        }
+
+        // Stopped before the last slots, honest nodes may be at different
+        // slots, their logs still are prefixes of each other
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Concurrent);
+        network.set_time_limit(Duration::from_millis(20));
+        let (success, _) = network.atomic_broadcast(50, &queues);
+        assert!(!success);
+        assert_eq!(network.check_delivery_logs(), Ok(()));
+        let logs = network.delivery_logs();
+        assert!((0..5).all(|id| logs.get(&id).map_or(0, Vec::len) < 14), "{:?}", logs);
+        network.close();
+    }
+
+    #[test]
+    fn honest_nodes_filter_malformed_messages() {
+        let malformed = |seed| {
+            let mut network =
+                Network::with_rng(7, 2, MaliciousKind::Malformed, RngSource::Seeded(seed));
+            network.set_schedule(Schedule::Deterministic);
+            network
+        };
+        let reasons = [
+            Rejection::UnknownSender,
+            Rejection::OutOfRange,
+            Rejection::WrongInstance,
+        ];
+        // Messages reach the protocol code unchecked by default
+        let mut network = malformed(0);
+        assert!(network.bracha_broadcast(42, 6).0);
+        assert!(reasons.iter().all(|r| network.counter(r.counter()).is_empty()));
+        network.close();
+
+        // Honest nodes drop every malformed copy, each reason is counted
+        let filter = SanityChecks {
+            max_value: Some(1000),
+        };
+        for seed in 0..3 {
+            let mut network = malformed(seed);
+            network.set_inbound_filter(filter);
+            let (success, results) = network.bracha_broadcast(42, 6);
+            assert!(success, "{}: {:?}", seed, results);
+            assert!((0..5).all(|id| results[&id] == 42));
+            for reason in reasons {
+                let rejected = network.counter(reason.counter());
+                assert!(!rejected.is_empty(), "{}: {}", seed, reason);
+                assert!(rejected.keys().all(|id| *id < 5));
+            }
+            network.close();
+        }
+    }
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn avss_reconstructs_despite_byzantine_nodes() {
+        // Any f + 1 shares give the secret, f of them tell nothing
+        let scheme = SimulatedSharing { threshold: 3 };
+        let (commitment, shares) = scheme.share(42, 7, 7);
+        let first = |k: usize| -> BTreeMap<NodeId, u64> {
+            shares.iter().copied().enumerate().take(k).collect()
+        };
+        assert_eq!(scheme.reconstruct(&commitment, &first(2)), None);
+        assert_eq!(scheme.reconstruct(&commitment, &first(3)), Some(42));
+        let mut forged = first(3);
+        forged.insert(0, shares[0] + 1);
+        assert!(!scheme.verify_share(&commitment, 0, forged[&0]));
+        assert_eq!(scheme.reconstruct(&commitment, &forged), None);
+
+        // Honest dealer: every honest node outputs its secret, whatever the
+        // faulty nodes reveal
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Random,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.avss(42, 0);
+            assert!(success, "{:?}: {:?}", kind, results);
+            assert!((0..5).all(|id| results[&id] == 42));
+            assert!(network.counter("completed").len() >= 5);
+            if kind == MaliciousKind::Random {
+                assert!(!network.counter("invalid_shares").is_empty());
+            }
+            network.close();
+        }
+
+        // Faulty dealer: honest nodes that reconstruct agree
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Equivocate, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.set_time_limit(Duration::from_millis(100));
+        let (_, results) = network.avss(42, 6);
+        let honest: BTreeSet<Value> = (0..5).filter_map(|id| results.get(&id).copied()).collect();
+        assert!(honest.len() <= 1, "{:?}", results);
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn delivering_nodes_assemble_certificates() {
+        for seed in 0..4 {
+            let rng = RngSource::Seeded(seed);
+            let mut network = Network::with_rng(7, 2, MaliciousKind::Random, rng);
+            let (success, results, certificates) = network.certified_broadcast(42, 0);
+            assert!(success, "seed {}: {:?}", seed, results);
+            // Every honest node proves its own delivery, with the READY of
+            // at least f + 1 other honest nodes
+            let signatures = network.signatures();
+            for id in 0..5 {
+                let certificate = &certificates[&id];
+                assert_eq!(certificate.value, 42);
+                assert!(certificate.verify(&signatures, 5), "{}: {:?}", id, certificate);
+                assert!(certificate.signatures.contains_key(&id));
+                assert!(certificate.signatures.keys().filter(|signer| **signer < 5).count() >= 3);
+            }
+            assert!(certificates.values().all(|certificate| certificate.value == 42));
+            network.close();
+        }
+
+        // Unsigned READY don't count towards the delivery of certified
+        // broadcasts
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Random, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, _, _) = network.certified_broadcast(42, 0);
+        assert!(success);
+        let invalid: usize = network.counter("invalid_signatures").values().sum();
+        assert!(invalid > 0);
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn broadcast_returns_delivery_certificate() {
+        let signed = |network: &Network| {
+            let cost = network.cost_report().protocols[&BRACHA_BROADCAST].clone();
+            cost.ops.get(&CryptoOp::Sign).copied().unwrap_or(0)
+        };
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Random,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results, certificates) = network.certified_broadcast(42, 0);
+            assert!(success, "{:?}: {:?}", kind, results);
+            assert!(signed(&network) >= 5);
+            // Certificate is checked with the public key alone
+            let certificate = certificates[&0].clone();
+            let signatures = network.signatures();
+            assert_eq!(certificate.value, 42);
+            assert!(certificate.verify(&signatures, 5));
+
+            let mut forged = certificate.clone();
+            forged.value = 43;
+            assert!(!forged.verify(&signatures, 5));
+            let mut forged = certificate.clone();
+            let signer = *forged.signatures.keys().next().unwrap();
+            forged.signatures.insert(signer, 0);
+            assert!(!forged.verify(&signatures, 5));
+            let mut short = certificate.clone();
+            while short.signatures.len() >= 5 {
+                short.signatures.pop_first();
+            }
+            assert!(!short.verify(&signatures, 5));
+
+            // Plain broadcasts don't sign
+            let (success, _) = network.bracha_broadcast(42, 0);
+            assert!(success);
+            assert_eq!(signed(&network), 0);
+            network.close();
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn authenticated_broadcast_drops_forged_senders() {
+        let invalid = |network: &Network| {
+            network.counter("invalid_signatures").values().sum::<usize>()
+        };
+        // Faulty node forges ECHO and READY of its value in the name of the
+        // honest ones, plain broadcasts take them for a quorum
+        let mut broken = 0;
+        for seed in 0..4 {
+            let kind = MaliciousKind::Impersonate;
+            let mut network = Network::with_rng(4, 1, kind, RngSource::Seeded(seed));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.bracha_broadcast(42, 0);
+            if !success {
+                broken += 1;
+                assert!(results.values().any(|v| *v == MALICIOUS_VALUE), "{:?}", results);
+            }
+            assert_eq!(invalid(&network), 0);
+
+            // Forged copies carry the signature of the faulty node
+            let (success, results) = network.authenticated_broadcast(42, 0);
+            assert!(success, "{}: {:?}", seed, results);
+            assert!(invalid(&network) > 0);
+            network.close();
+        }
+        assert!(broken > 0);
+
+        // Also when the network forges them on a link
+        let mut network = Network::with_rng(4, 1, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.compromise_link(0, 1, LinkAttack::Impersonate(2));
+        let (success, _) = network.authenticated_broadcast(42, 0);
+        assert!(success);
+        let impersonated = network.mitm().stats().impersonated;
+        assert!(impersonated > 0);
+        assert_eq!(network.counter("invalid_signatures")[&1], impersonated);
+        network.close();
+    }
+
+    #[test]
+    fn mvba_decides_a_valid_input() {
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, id + 10)).collect();
+        for kind in [
+            MaliciousKind::Silent,
+            MaliciousKind::Mirror,
+            MaliciousKind::Equivocate,
+        ] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            let (success, results) = network.mvba(&inputs);
+            assert!(success, "{:?}: {:?}", kind, results);
+            assert!((0..5).all(|id| results[&id] == 10));
+            network.close();
+        }
+
+        // Data of the input of node 0 is missing at every honest node: the
+        // election of node 0 is rejected and that of node 1 decides
+        let oracle = MissingData::default().withhold(10, 0..5);
+        for kind in [MaliciousKind::Silent, MaliciousKind::Equivocate] {
+            let mut network = Network::with_rng(7, 2, kind.clone(), RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network.set_validity_oracle(oracle.clone());
+            let (success, results) = network.mvba(&inputs);
+            assert!(success, "{:?}: {:?}", kind, results);
+            assert!((0..5).all(|id| results[&id] != 10));
+            assert!(network.counter("rejected").values().sum::<usize>() >= 5);
+            assert!(network.counter("elections").values().sum::<usize>() >= 5 * 2);
+            network.close();
+        }
+
+        // Same with the real timers of concurrent runs
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Equivocate, RngSource::Seeded(1));
+        network.set_validity_oracle(oracle);
+        let (success, results) = network.mvba(&inputs);
+        assert!(success, "{:?}", results);
+        network.close();
+    }
+
+    #[test]
+    fn protocols_describe_themselves() {
+        for &id in PROTOCOLS {
+            let description = protocols::describe(id, 7).unwrap();
+            assert_eq!(description.id, id);
+            assert_eq!(protocols::find(description.name), Some(id));
+            assert!(description.children.iter().all(|child| PROTOCOLS.contains(child)));
+            // Kinds name a single message of their payload
+            let kinds: BTreeSet<_> = description
+                .messages
+                .iter()
+                .map(|schema| (schema.payload, &schema.kind))
+                .collect();
+            assert_eq!(kinds.len(), description.messages.len(), "{}", description);
+        }
+        assert!(protocols::describe(ROUTING, 7).is_none());
+        assert_eq!(protocols::find("bracha"), None);
+
+        // Thresholds are those the nodes count to
+        let bracha = protocols::describe(BRACHA_BROADCAST, 7).unwrap();
+        let parameters = Parameters::new(7, 2, FaultModel::Byzantine);
+        for name in ["echo_quorum", "v2_echo_quorum", "ready_amplification", "ready_delivery"] {
+            assert_eq!(bracha.threshold(name), parameters.get(name), "{}", name);
+        }
+        let echo = bracha.message("ECHO").unwrap();
+        assert_eq!((echo.phase, echo.size, echo.commitment), (Phase::Echo, 9, Some("ECHO")));
+        assert_eq!(bracha.versions, &["1.0", "1.1", "2.0"]);
+
+        let agreement = protocols::describe(BINARY_AGREEMENT, 7).unwrap();
+        assert_eq!(agreement.label(Phase::Vote), "BVAL/AUX/TERM");
+        assert_eq!(agreement.children, vec![COMMON_COIN]);
+        let paxos = protocols::describe(PAXOS, 7).unwrap();
+        assert_eq!(paxos.fault_model, FaultModel::Crash);
+        assert_eq!(paxos.threshold("crash_quorum"), Some(4));
+        let king = protocols::describe(PHASE_KING, 9).unwrap();
+        assert_eq!(king.threshold("phases"), Some(3));
+
+        // Kinds are those of the traces
+        let mut network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        network.set_schedule(Schedule::Deterministic);
+        network.bracha_broadcast(42, 0);
+        let events: Vec<String> =
+            network.trace().events.iter().map(|event| format!("{:?}", event)).collect();
+        for kind in ["INIT", "ECHO", "READY"] {
+            assert!(bracha.message(kind).is_some());
+            assert!(events.iter().any(|event| event.contains(&format!("<{},", kind))));
+        }
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn vaba_decides_an_externally_valid_value() {
+        // Malicious nodes are the first ones so that their views are elected
+        // and abandoned, the malicious value 0 fails the predicate
+        for (n, f) in [(4, 1), (7, 2)] {
+            let inputs: HashMap<NodeId, Value> = (0..n).map(|id| (id, id + 10)).collect();
+            for kind in [MaliciousKind::Silent, MaliciousKind::Mirror, MaliciousKind::Equivocate] {
+                for seed in 0..2 {
+                    let malicious = (0..f).collect();
+                    let mut network = Network::with_malicious_nodes(
+                        n, malicious, kind.clone(), RngSource::Seeded(seed), FaultModel::Byzantine);
+                    network.set_schedule(Schedule::Deterministic);
+                    let (success, results) = network.vaba(&inputs, |v| v >= 10);
+                    assert!(success, "{:?} with {} nodes, seed {}", kind, n, seed);
+                    let decided: BTreeSet<Value> = results.values().copied().collect();
+                    assert_eq!(decided.len(), 1);
+                    assert!(decided.iter().all(|v| *v >= 10));
+                    // Every honest node went through a view at least
+                    let views: usize = network.counter("views").values().sum();
+                    assert!(views >= n - f);
+                    network.close();
+                }
+            }
+        }
+
+        // Random messages under the concurrent schedule
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, id + 10)).collect();
+        let mut network = Network::with_rng(7, 2, MaliciousKind::Random, RngSource::Seeded(0));
+        network.set_time_limit(Duration::from_secs(5));
+        let (success, results) = network.vaba(&inputs, |v| v >= 10);
+        assert!(success);
+        assert!(results.values().all(|v| *v >= 10));
+        network.close();
+    }
+
+    #[test]
+    #[cfg(feature = "crypto")]
+    fn vaba_rejects_keys_replayed_after_a_lock() {
+        // Nodes 3 and 4 never get to their commit in the first view, the
+        // views that elect them end with the honest nodes locked on their
+        // value and faulty node 6 proposes its own key of that view again
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, id + 10)).collect();
+        let mut replayed = 0;
+        for seed in 0..16 {
+            let mut network = Network::with_malicious_nodes(
+                7, vec![6], MaliciousKind::Replay, RngSource::Seeded(seed), FaultModel::Byzantine);
+            network.set_schedule(Schedule::Deterministic);
+            network.controller().drop_next(12, |msg| {
+                (3..5).contains(&msg.from) && msg.description.contains("<SEND, 0, 4,")
+            });
+            let (success, results) = network.vaba(&inputs, |_| true);
+            assert!(success, "seed {}: {:?}", seed, results);
+            if network.counter("rejected").values().sum::<usize>() > 0 {
+                replayed += 1;
+                assert!((0..6).all(|id| [13, 14].contains(&results[&id])), "{:?}", results);
+            }
+            network.close();
+        }
+        assert!(replayed > 0);
+    }
+
+    #[test]
+    fn malicious_nodes_are_placed() {
+        let placed = |placement: Placement, latency: Latency| {
+            let config = Config {
+                num_nodes: 7,
+                num_malicious: 2,
+                kind: MaliciousKind::Equivocate,
+                placement,
+                network: NetworkConfig {
+                    latency,
+                    ..NetworkConfig::default()
+                },
+                ..Config::default()
+            };
+            Network::from_config(&config, RngSource::Seeded(0))
+        };
+        let constant = Latency::Constant(Duration::ZERO);
+        let network = placed(Placement::Highest, constant.clone()).unwrap();
+        assert_eq!(network.malicious_nodes(), BTreeSet::from([5, 6]));
+        assert_eq!(network.good_nodes(), &[0, 1, 2, 3, 4]);
+        network.close();
+
+        // Seeded draws are reproducible and don't stick to the highest ids
+        let mut placements = BTreeSet::new();
+        for seed in 0..8 {
+            let draw = Placement::Random(seed).malicious(7, 2, &constant).unwrap();
+            assert_eq!(draw, Placement::Random(seed).malicious(7, 2, &constant).unwrap());
+            let distinct: BTreeSet<NodeId> = draw.iter().copied().collect();
+            assert_eq!(distinct.len(), 2);
+            placements.insert(distinct);
+        }
+        assert!(placements.len() > 1);
+        assert!(placements.iter().any(|nodes| nodes.contains(&0)), "{:?}", placements);
+
+        // Faulty leader of the broadcast, the honest nodes still agree
+        let mut network = placed(Placement::Explicit(vec![0, 3]), constant.clone()).unwrap();
+        assert_eq!(network.malicious_nodes(), BTreeSet::from([0, 3]));
+        network.set_schedule(Schedule::Deterministic);
+        let (success, results) = network.bracha_broadcast(42, 1);
+        assert!(success, "{:?}", results);
+        network.close();
+        let diagnostics = placed(Placement::Explicit(vec![0]), constant.clone()).err().unwrap();
+        assert_eq!(diagnostics[0].field, "placement");
+        assert!(placed(Placement::Explicit(vec![0, 7]), constant.clone()).is_err());
+
+        // One malicious node per region, the AWS matrix has 6 of them
+        let geo = Latency::geo(RttMatrix::aws(), 7);
+        let stratified = Placement::Stratified.malicious(7, 2, &geo).unwrap();
+        assert_eq!(stratified, vec![6, 1]);
+        assert!(placed(Placement::Stratified, constant).is_err());
+        let network = placed(Placement::Stratified, geo).unwrap();
+        assert_eq!(network.malicious_nodes(), BTreeSet::from([1, 6]));
+        network.close();
+
+        for placement in [
+            Placement::Highest,
+            Placement::Random(3),
+            Placement::Explicit(vec![2, 5]),
+            Placement::Stratified,
+        ] {
+            assert_eq!(placement.to_string().parse::<Placement>(), Ok(placement));
+        }
+        assert!("nodes:a".parse::<Placement>().is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "experiments")]
+    fn leaders_chosen_by_policy() {
+        let network = Network::with_malicious_nodes(
+            7, vec![1, 4], MaliciousKind::Silent, RngSource::Seeded(0), FaultModel::Byzantine);
+        let leaders = |policy: LeaderPolicy| -> BTreeSet<NodeId> {
+            (0..20).map(|run| policy.leader(&network, run).unwrap()).collect()
+        };
+        assert_eq!(leaders(LeaderPolicy::Fixed(4)), BTreeSet::from([4]));
+        assert_eq!(leaders(LeaderPolicy::Malicious), BTreeSet::from([4]));
+        let honest = leaders(LeaderPolicy::RandomHonest(0));
+        assert!(honest.len() > 1 && honest.iter().all(|id| ![1, 4].contains(id)));
+        let any = leaders(LeaderPolicy::RandomAny(0));
+        assert!(any.contains(&1) || any.contains(&4));
+        // Draws are reproducible
+        assert_eq!(LeaderPolicy::RandomAny(3).leader(&network, 5),
+            LeaderPolicy::RandomAny(3).leader(&network, 5));
+        assert!(LeaderPolicy::Fixed(7).leader(&network, 0).is_err());
+        network.close();
+        let network = Network::with_rng(4, 0, MaliciousKind::Silent, RngSource::Seeded(0));
+        assert!(LeaderPolicy::Malicious.leader(&network, 0).is_err());
+        network.close();
+
+        for policy in [
+            LeaderPolicy::Fixed(2),
+            LeaderPolicy::RandomHonest(1),
+            LeaderPolicy::RandomAny(5),
+            LeaderPolicy::Malicious,
+        ] {
+            assert_eq!(policy.to_string().parse::<LeaderPolicy>(), Ok(policy));
+        }
+        assert!("honest".parse::<LeaderPolicy>().is_err());
+
+        // Honest leaders deliver under every attack, malicious ones never
+        // break agreement
+        let experiment = LeaderExperiment {
+            num_nodes: 4,
+            num_malicious: 1,
+            kinds: vec![MaliciousKind::Silent, MaliciousKind::Equivocate],
+            runs: 2,
+            schedule: Schedule::Deterministic,
+            ..LeaderExperiment::default()
+        };
+        let outcomes = experiment.run();
+        assert_eq!(outcomes.len(), 4);
+        for outcome in outcomes.iter() {
+            assert_eq!(outcome.agreements, outcome.runs, "{:?}", outcome);
+            match outcome.leader {
+                LeaderPolicy::Malicious => assert_eq!(outcome.malicious_leaders, outcome.runs),
+                _ => assert_eq!(outcome.success_rate(), 1.0, "{:?}", outcome),
+            }
+        }
+        // A silent leader leaves every honest node waiting
+        assert_eq!(outcomes[1].kind, MaliciousKind::Silent);
+        assert_eq!(outcomes[1].successes, 0);
+
+        let csv = leaders_csv(&outcomes, &experiment.manifest());
+        assert!(csv.contains("# leaders: honest:0 malicious\n"));
+        assert!(csv.contains("\nSilent,malicious,2,2,0,0.000,2,"));
+    }
+
+    #[cfg(feature = "experiments")]
+    #[test]
+    fn campaigns_resume_from_checkpoints() {
+        let path = std::env::temp_dir().join(format!("campaign-{}.ckpt", std::process::id()));
+        let experiment = TieBreakExperiment {
+            kinds: vec![MaliciousKind::Mirror],
+            rules: vec![TieBreak::Quorum, TieBreak::Lowest],
+            runs: 3,
+            ..TieBreakExperiment::default()
+        };
+        let uninterrupted = experiment.run();
+        let mut checkpoint = Checkpoint::open(&path, experiment.manifest()).unwrap();
+        assert!(checkpoint.is_empty());
+        assert_eq!(experiment.resume(&mut checkpoint).unwrap(), uninterrupted);
+
+        // Campaign interrupted after its first configuration, with a result
+        // no run could give to tell it apart
+        let saved = std::fs::read_to_string(&path).unwrap();
+        let first = saved.lines().find(|line| !line.starts_with('#')).unwrap();
+        assert_eq!(first, "Mirror Quorum\t3 0 0");
+        let mut interrupted: String = saved
+            .lines()
+            .filter(|line| line.starts_with('#'))
+            .map(|line| format!("{}\n", line))
+            .collect();
+        interrupted.push_str("Mirror Quorum\t0 0 3\n");
+        std::fs::write(&path, interrupted).unwrap();
+        let mut checkpoint = Checkpoint::open(&path, experiment.manifest()).unwrap();
+        assert_eq!(checkpoint.len(), 1);
+        let resumed = experiment.resume(&mut checkpoint).unwrap();
+        assert_eq!(resumed[0].undecided, 3);
+        assert_eq!(resumed[1], uninterrupted[1]);
+        assert_eq!(Checkpoint::open(&path, experiment.manifest()).unwrap().len(), 2);
+
+        // File of a campaign with other parameters
+        let other = TieBreakExperiment {
+            runs: 4,
+            ..experiment.clone()
+        };
+        assert!(Checkpoint::open(&path, other.manifest()).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "experiments")]
+    #[test]
+    fn sharded_campaigns_merge() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("shard-{}-{}.ckpt", name, std::process::id()));
+        let experiment = TieBreakExperiment {
+            kinds: vec![MaliciousKind::Mirror, MaliciousKind::Equivocate],
+            rules: vec![TieBreak::Quorum, TieBreak::Lowest],
+            runs: 2,
+            ..TieBreakExperiment::default()
+        };
+        let uninterrupted = experiment.run();
+
+        // Workers run every other couple, each to its own file
+        let workers = [path("0"), path("1")];
+        for (index, worker) in workers.iter().enumerate() {
+            let mut checkpoint = Checkpoint::open(worker, experiment.manifest()).unwrap();
+            checkpoint.shard = Some((index, 2));
+            let outcomes = experiment.resume(&mut checkpoint).unwrap();
+            assert_eq!(outcomes.len(), 2);
+            assert_eq!(outcomes[0], uninterrupted[index]);
+            assert_eq!(outcomes[1], uninterrupted[index + 2]);
+        }
+
+        // Merged campaign has every outcome, nothing is left to run
+        let merged = path("all");
+        let mut checkpoint = Checkpoint::open(&merged, experiment.manifest()).unwrap();
+        for worker in workers.iter() {
+            assert_eq!(checkpoint.merge(worker).unwrap(), 2);
+        }
+        assert_eq!(Checkpoint::open(&merged, experiment.manifest()).unwrap().len(), 4);
+        checkpoint.shard = Some((0, 4));
+        assert_eq!(experiment.resume(&mut checkpoint).unwrap(), uninterrupted);
+
+        // Workers of another campaign, or that never saved
+        let other = TieBreakExperiment {
+            runs: 3,
+            ..experiment.clone()
+        };
+        let mut checkpoint = Checkpoint::new(other.manifest());
+        assert!(checkpoint.merge(&workers[0]).is_err());
+        assert!(checkpoint.merge(&path("missing")).is_err());
+        for file in workers.iter().chain([&merged]) {
+            std::fs::remove_file(file).unwrap();
+        }
+    }
+
+    #[cfg(feature = "experiments")]
+    #[test]
+    fn every_campaign_resumes_from_checkpoints() {
+        let dir = std::env::temp_dir();
+        let path = |name: &str| dir.join(format!("every-{}-{}.ckpt", name, std::process::id()));
+
+        // Deterministic campaigns split between two workers give the
+        // outcomes of uninterrupted ones
+        let leaders = LeaderExperiment {
+            num_nodes: 4,
+            num_malicious: 1,
+            kinds: vec![MaliciousKind::Silent, MaliciousKind::Equivocate],
+            runs: 2,
+            schedule: Schedule::Deterministic,
+            ..LeaderExperiment::default()
+        };
+        let uninterrupted = leaders.run();
+        let mut merged = Checkpoint::new(leaders.manifest());
+        for index in 0..2 {
+            let worker = path(&format!("leaders-{}", index));
+            let mut checkpoint = Checkpoint::open(&worker, leaders.manifest()).unwrap();
+            checkpoint.shard = Some((index, 2));
+            assert_eq!(leaders.resume(&mut checkpoint).unwrap().len(), 2);
+            assert_eq!(merged.merge(&worker).unwrap(), 2);
+            std::fs::remove_file(&worker).unwrap();
+        }
+        merged.shard = Some((0, 4));
+        let resumed = leaders.resume(&mut merged).unwrap();
+        let counts = |outcome: &LeaderOutcome| {
+            (outcome.malicious_leaders, outcome.successes, outcome.agreements)
+        };
+        assert_eq!(resumed.len(), uninterrupted.len());
+        for (resumed, uninterrupted) in resumed.iter().zip(uninterrupted.iter()) {
+            assert_eq!(counts(resumed), counts(uninterrupted));
+        }
+
+        // Attacks are scored against the baseline every worker observes
+        let attacks = AttackComparison {
+            num_nodes: 4,
+            num_malicious: 1,
+            kinds: vec![MaliciousKind::Silent, MaliciousKind::Random],
+            runs: 2,
+            schedule: Schedule::Deterministic,
+            ..AttackComparison::default()
+        };
+        let metrics: Vec<Box<dyn AttackMetric>> = vec![Box::new(ExtraMessages)];
+        let uninterrupted = attacks.run(&metrics);
+        let worker = path("attacks");
+        let mut checkpoint = Checkpoint::open(&worker, attacks.manifest(&metrics)).unwrap();
+        checkpoint.shard = Some((1, 2));
+        let scores = attacks.resume(&mut checkpoint, &metrics).unwrap();
+        assert_eq!(scores.len(), 1);
+        assert!(uninterrupted.contains(&scores[0]));
+        let mut checkpoint = Checkpoint::open(&worker, attacks.manifest(&metrics)).unwrap();
+        assert_eq!(checkpoint.len(), 2);
+        assert_eq!(attacks.resume(&mut checkpoint, &metrics).unwrap(), uninterrupted);
+        std::fs::remove_file(&worker).unwrap();
+
+        // Completed campaigns are read back as they were saved
+        let throughput = ThroughputExperiment {
+            num_nodes: 4,
+            batch_size: 2,
+            instances: 4,
+            warmup: 1,
+            cooldown: 1,
+            steady_window: 0,
+            ..ThroughputExperiment::default()
+        };
+        let worker = path("throughput");
+        let mut checkpoint = Checkpoint::open(&worker, throughput.manifest()).unwrap();
+        let report = throughput.resume(&mut checkpoint).unwrap();
+        let mut checkpoint = Checkpoint::open(&worker, throughput.manifest()).unwrap();
+        assert_eq!(checkpoint.len(), 4);
+        assert_eq!(throughput.resume(&mut checkpoint).unwrap(), report);
+        std::fs::remove_file(&worker).unwrap();
+
+        let contour = ContourSearch {
+            sizes: vec![(4, 1)],
+            delays: vec![Duration::ZERO],
+            runs: 1,
+            precision: 0.5,
+            time_limit: Duration::from_millis(20),
+            ..ContourSearch::default()
+        };
+        let worker = path("contour");
+        let broadcast = |network: &mut Network| network.bracha_broadcast(7, 0).0;
+        let mut checkpoint = Checkpoint::open(&worker, contour.manifest()).unwrap();
+        let points = contour.resume(&mut checkpoint, broadcast).unwrap();
+        let mut checkpoint = Checkpoint::open(&worker, contour.manifest()).unwrap();
+        let resumed = contour.resume(&mut checkpoint, |_: &mut Network| unreachable!());
+        assert_eq!(resumed.unwrap(), points);
+        std::fs::remove_file(&worker).unwrap();
+    }
+
+    /// Canonical seeded run of every protocol and the digest of its trace
+    fn golden_runs() -> Vec<(&'static str, u64)> {
+        let inputs: HashMap<NodeId, Value> = (0..7).map(|id| (id, 10 + id)).collect();
+        let seeded = |num_nodes: usize, num_malicious: usize, kind: MaliciousKind| {
+            let mut network =
+                Network::with_rng(num_nodes, num_malicious, kind, RngSource::Seeded(0));
+            network.set_schedule(Schedule::Deterministic);
+            network
+        };
+        let crashed = |num_nodes: usize, num_malicious: usize| {
+            let kind = MaliciousKind::Silent;
+            let mut network = Network::with_fault_model(
+                num_nodes,
+                num_malicious,
+                kind,
+                RngSource::Seeded(0),
+                FaultModel::Crash,
+            );
+            network.set_schedule(Schedule::Deterministic);
+            network
+        };
+        let digest = |network: Network| {
+            let digest = network.trace().digest();
+            network.close();
+            digest
+        };
+        let mut runs = vec![];
+
+        let mut network = seeded(7, 2, MaliciousKind::Equivocate);
+        network.bracha_broadcast(7, 6);
+        runs.push(("bracha_broadcast", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        network.parallel_broadcast(&inputs);
+        runs.push(("parallel_broadcast", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Mirror);
+        network.batched_broadcast(&[1, 2, 3], 0);
+        runs.push(("batched_broadcast", digest(network)));
+
+        let mut network = seeded(6, 1, MaliciousKind::Mirror);
+        network.binary_agreement(&inputs);
+        runs.push(("ben_or", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        network.aba(&(0..4).map(|id| (id, id % 2 == 0)).collect());
+        runs.push(("binary_agreement", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Mirror);
+        network.run_protocol(COMMON_COIN, &inputs);
+        runs.push(("common_coin", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        network.common_subset(&inputs);
+        runs.push(("common_subset", digest(network)));
+
+        #[cfg(feature = "crypto")]
+        {
+            let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+            let queues = (0..4).map(|id| (id, vec![100 * id, 100 * id + 1])).collect();
+            network.honey_badger(2, &queues);
+            runs.push(("honey_badger", digest(network)));
+        }
+
+        let mut network = seeded(6, 1, MaliciousKind::Silent);
+        network.set_topology(Topology::Ring);
+        network.flood(&inputs);
+        runs.push(("flooding", digest(network)));
+
+        let mut network = seeded(7, 0, MaliciousKind::Silent);
+        network.set_topology(Topology::Ring);
+        network.set_routing(true);
+        network.bracha_broadcast(7, 0);
+        runs.push(("routing", digest(network)));
+
+        let mut network = crashed(5, 2);
+        network.majority_broadcast(7, 0);
+        runs.push(("majority_broadcast", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        network.consistent_broadcast(7, 3);
+        runs.push(("consistent_broadcast", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        network.graded_broadcast(7, 3);
+        runs.push(("graded_broadcast", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Silent);
+        network.terminating_broadcast(7, 3);
+        runs.push(("terminating_broadcast", digest(network)));
+
+        let mut network = seeded(5, 1, MaliciousKind::Equivocate);
+        network.phase_king(&inputs);
+        runs.push(("phase_king", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Mirror);
+        network.leader_election();
+        runs.push(("leader_election", digest(network)));
+
+        #[cfg(feature = "crypto")]
+        {
+            let mut network = seeded(7, 2, MaliciousKind::Random);
+            network.avss(7, 0);
+            runs.push(("avss", digest(network)));
+        }
+
+        let mut network = crashed(5, 2);
+        network.paxos(&inputs);
+        runs.push(("paxos", digest(network)));
+
+        #[cfg(feature = "crypto")]
+        {
+            let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+            network.set_pbft_config(PbftConfig {
+                primary: 3,
+                ..PbftConfig::default()
+            });
+            network.pbft(&inputs);
+            runs.push(("pbft", digest(network)));
+        }
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        network.set_mempool_config(MempoolConfig {
+            sync: MempoolSync::Announce,
+            ..MempoolConfig::default()
+        });
+        let pools: HashMap<NodeId, Vec<Value>> =
+            (0..4).map(|id| (id, vec![id, (id + 1) % 4])).collect();
+        network.mempool(&pools);
+        runs.push(("mempool", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        network.set_validity_oracle(MissingData::default().withhold(10, 0..3));
+        network.mvba(&inputs);
+        runs.push(("mvba", digest(network)));
+
+        let mut network = seeded(4, 1, MaliciousKind::Equivocate);
+        let queues = (0..4).map(|id| (id, vec![100 * id, 100 * id + 1])).collect();
+        network.atomic_broadcast(4, &queues);
+        runs.push(("atomic_broadcast", digest(network)));
+
+        let mut network = seeded(4, 0, MaliciousKind::Silent);
+        network.smr(&inputs);
+        runs.push(("smr", digest(network)));
+        runs
+    }
+
+    #[test]
+    fn golden_traces() {
+        // Digests of the canonical runs, to regenerate after a change of
+        // behaviour that is meant: GOLDEN_UPDATE=1 cargo test golden_traces
+        // The coins differ with the `crypto` feature, the digests of its
+        // builds that differ are those of `NAME+crypto`
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/golden_traces.txt");
+        let runs = golden_runs();
+        let text = std::fs::read_to_string(path).unwrap_or_default();
+        let mut golden: Vec<(String, u64)> = text
+            .lines()
+            .filter(|line| !line.starts_with('#') && !line.trim().is_empty())
+            .map(|line| {
+                let (name, digest) = line.split_once(' ').unwrap();
+                (name.to_string(), u64::from_str_radix(digest.trim(), 16).unwrap())
+            })
+            .collect();
+        let get = |golden: &[(String, u64)], name: &str| {
+            golden.iter().find(|(n, _)| n == name).map(|(_, digest)| *digest)
+        };
+
+        if std::env::var_os("GOLDEN_UPDATE").is_some() {
+            // Digests of the other builds are kept
+            for (name, digest) in runs.iter() {
+                let crypto = format!("{}+crypto", name);
+                let key = match get(&golden, name) {
+                    Some(default) if cfg!(feature = "crypto") && default != *digest => crypto,
+                    Some(_) if cfg!(feature = "crypto") => {
+                        golden.retain(|(n, _)| *n != crypto);
+                        name.to_string()
+                    }
+                    _ => name.to_string(),
+                };
+                match golden.iter_mut().find(|(n, _)| *n == key) {
+                    Some(entry) => entry.1 = *digest,
+                    None => golden.push((key, *digest)),
+                }
+            }
+            let mut text = String::from(GOLDEN_HEADER);
+            for (name, digest) in golden.iter() {
+                text.push_str(&format!("{} {:016x}\n", name, digest));
+            }
+            std::fs::write(path, text).unwrap();
+            return;
+        }
+        for (name, digest) in runs {
+            let crypto = get(&golden, &format!("{}+crypto", name))
+                .filter(|_| cfg!(feature = "crypto"));
+            assert_eq!(
+                crypto.or_else(|| get(&golden, name)),
+                Some(digest),
+                "Trace of the {} run changed, see {}",
+                name,
+                path
+            );
+        }
+    }
+
+    const GOLDEN_HEADER: &str = "\
+# Trace digests of the canonical seeded runs of `golden_runs` in src/lib.rs
+# Regenerate after a change of behaviour that is meant, with and without
+# the crypto feature:
+#   GOLDEN_UPDATE=1 cargo test golden_traces
+#   GOLDEN_UPDATE=1 cargo test --all-features golden_traces
+";
 }
@@ -0,0 +1,109 @@
+use crate::manifest::Manifest;
+use crate::node::NodeId;
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// File listing the events of all the nodes in the order the network saw
+/// them, one `time node line` entry per event
+pub const INDEX_FILE: &str = "index.log";
+
+struct NodeFile {
+    writer: BufWriter<File>,
+    // Number of lines written so far
+    lines: usize,
+}
+
+/// Event stream of every node in its own file, like the logs of the
+/// replicas of a real system, with an index to put them back in order
+pub struct NodeLogs {
+    dir: PathBuf,
+    files: BTreeMap<NodeId, NodeFile>,
+    index: BufWriter<File>,
+}
+
+impl NodeLogs {
+    /// Create the files of `nodes` in `dir`, each one starting with
+    /// `manifest`
+    pub fn create(dir: &Path, nodes: &[NodeId], manifest: &Manifest) -> Result<Self, String> {
+        let error = |path: &Path, e: std::io::Error| format!("{}: {}", path.display(), e);
+        fs::create_dir_all(dir).map_err(|e| error(dir, e))?;
+        let header = manifest.header("#");
+
+        let mut files = BTreeMap::new();
+        for id in nodes {
+            let path = NodeLogs::path(dir, *id);
+            let mut writer = BufWriter::new(File::create(&path).map_err(|e| error(&path, e))?);
+            writer
+                .write_all(header.as_bytes())
+                .map_err(|e| error(&path, e))?;
+            let lines = header.lines().count();
+            files.insert(*id, NodeFile { writer, lines });
+        }
+
+        let path = dir.join(INDEX_FILE);
+        let mut index = BufWriter::new(File::create(&path).map_err(|e| error(&path, e))?);
+        index
+            .write_all(header.as_bytes())
+            .map_err(|e| error(&path, e))?;
+
+        Ok(NodeLogs {
+            dir: dir.to_path_buf(),
+            files,
+            index,
+        })
+    }
+
+    /// File of node `id` in `dir`
+    pub fn path(dir: &Path, id: NodeId) -> PathBuf {
+        dir.join(format!("node-{}.log", id))
+    }
+
+    /// File of the traffic per phase of run number `run` in `dir`
+    pub fn stats_path(dir: &Path, run: usize) -> PathBuf {
+        dir.join(format!("stats-{}.csv", run))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Append `event` of node `id`, happening `at` since the start of
+    /// the run
+    /// Events of unknown nodes, like the network, are ignored
+    pub(crate) fn log(&mut self, id: NodeId, at: Duration, event: &str) -> Result<(), String> {
+        let file = match self.files.get_mut(&id) {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        let at = at.as_secs_f64();
+        writeln!(file.writer, "{:.6} {}", at, event).map_err(|e| e.to_string())?;
+        file.lines += 1;
+        writeln!(self.index, "{:.6} {} {}", at, id, file.lines).map_err(|e| e.to_string())
+    }
+
+    /// Mark the start of run number `run` in every file
+    pub(crate) fn start_run(&mut self, run: usize) -> Result<(), String> {
+        let ids: Vec<NodeId> = self.files.keys().cloned().collect();
+        for id in ids {
+            self.log(id, Duration::ZERO, &format!("run {}", run))?;
+        }
+        Ok(())
+    }
+
+    /// Store the traffic of run number `run` next to the events
+    pub(crate) fn write_stats(&self, run: usize, csv: &str) -> Result<(), String> {
+        let path = NodeLogs::stats_path(&self.dir, run);
+        fs::write(&path, csv).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Write what is buffered so that the files can be read
+    pub fn flush(&mut self) -> Result<(), String> {
+        for file in self.files.values_mut() {
+            file.writer.flush().map_err(|e| e.to_string())?;
+        }
+        self.index.flush().map_err(|e| e.to_string())
+    }
+}
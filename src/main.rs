@@ -1,15 +1,682 @@
+use distributed::baseline::Baseline;
+use distributed::checkpoint::Checkpoint;
+use distributed::config::NetworkConfig;
+use distributed::diff::{Recording, RunDiff};
+use distributed::explore::{
+    attack_metric, attacks_csv, batching_csv, contour_csv, helping_csv, heterogeneity_csv,
+    leaders_csv, load_csv, speculation_csv, strategies_csv, throughput_csv, tie_break_csv,
+    AttackComparison, BatchingBenchmark, BroadcastBenchmark, ContourSearch, HelpingExperiment,
+    HeterogeneityExperiment, LeaderExperiment, LoadExperiment, SpeculationExperiment,
+    StrategyComparison, ThroughputExperiment, TieBreakExperiment,
+};
+use distributed::manifest::Manifest;
 use distributed::network::Network;
-use log::{trace, warn};
+use distributed::scenario::{self, TagExpr, SCENARIOS};
+use distributed::scheduler::Schedule;
+use distributed::node::MaliciousKind;
+use distributed::protocols::flooding::FloodConfig;
+use distributed::protocols::{self, PROTOCOLS};
+use distributed::stats::Milestone;
+use distributed::topology::Topology;
+use log::{info, trace, warn};
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
 fn main() {
     pretty_env_logger::init();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    run(&args);
+}
+
+/// Subcommand named by the first argument, the broadcast by default
+fn run(args: &[String]) {
+    match args.first().map(String::as_str) {
+        Some("contour") => contour(&args[1..]),
+        Some("tie-break") => tie_break(&args[1..]),
+        Some("batching") => batching(&args[1..]),
+        Some("strategies") => strategies(&args[1..]),
+        Some("helping") => helping(&args[1..]),
+        Some("attacks") => attacks(&args[1..]),
+        Some("leaders") => leaders(&args[1..]),
+        Some("throughput") => throughput(&args[1..]),
+        Some("speculation") => speculation(&args[1..]),
+        Some("load") => load(&args[1..]),
+        Some("heterogeneity") => heterogeneity(&args[1..]),
+        Some("baseline") => baseline(&args[1..]),
+        Some("compare-baseline") => compare_baseline(&args[1..]),
+        Some("diff") => diff(&args[1..]),
+        Some("scenario") => scenario(&args[1..]),
+        Some("matrix") => matrix(&args[1..]),
+        Some("flood") => flood(&args[1..]),
+        Some("describe") => describe(&args[1..]),
+        Some("coordinate") => coordinate(&args[1..]),
+        _ => broadcast(args),
+    }
+}
+
+/// Campaigns resumed from checkpoints, whose configurations can be split
+/// between workers
+const SHARDED: [&str; 8] = [
+    "contour",
+    "tie-break",
+    "attacks",
+    "leaders",
+    "throughput",
+    "speculation",
+    "load",
+    "heterogeneity",
+];
+
+/// Value following `--name` in the arguments
+fn flag<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|i| args.get(i + 1))
+        .map(String::as_str)
+}
+
+/// Progress of the campaign, `--resume FILE` saves it to FILE as the
+/// configurations complete and skips those already in it
+/// Campaigns are split between workers with `--shard I/N`, worker I of N,
+/// and the files of the workers gathered with `--merge FILE,FILE`
+/// There is no network control plane to farm the workers out over: they
+/// are processes started by `coordinate` on this machine, or started by
+/// hand on others and their files copied back for `--merge`
+fn campaign(args: &[String], manifest: Manifest) -> Checkpoint {
+    let mut checkpoint = match flag(args, "--resume") {
+        Some(path) => Checkpoint::open(Path::new(path), manifest)
+            .unwrap_or_else(|e| panic!("Invalid --resume: {}", e)),
+        None => Checkpoint::new(manifest),
+    };
+    if let Some(shard) = flag(args, "--shard") {
+        let (index, count) = shard.split_once('/').expect("Invalid --shard");
+        let index: usize = index.parse().expect("Invalid --shard");
+        let count: usize = count.parse().expect("Invalid --shard");
+        assert!(index < count, "Invalid --shard: worker {} of {}", index, count);
+        checkpoint.shard = Some((index, count));
+    }
+    for path in flag(args, "--merge").into_iter().flat_map(|paths| paths.split(',')) {
+        let merged = checkpoint
+            .merge(Path::new(path))
+            .unwrap_or_else(|e| panic!("Invalid --merge: {}", e));
+        info!("Merged {} configurations from {}", merged, path);
+    }
+    checkpoint
+}
+
+/// Campaign run by worker processes of this binary, each on its shard of
+/// the configurations to the checkpoint `FILE.I`, then merged into `FILE`
+/// `coordinate CAMPAIGN --workers N --resume FILE [ARGS]`
+/// Coordination is local only: the workers are processes of this machine,
+/// those of other machines are started by hand with `--shard`
+/// A coordinator started again resumes every worker from its file, the
+/// results are those of `CAMPAIGN --resume FILE [ARGS]`
+fn coordinate(args: &[String]) {
+    let name = args.first().map(String::as_str).unwrap_or_default();
+    assert!(
+        SHARDED.contains(&name),
+        "Invalid campaign, one of {}",
+        SHARDED.join(", ")
+    );
+    assert!(flag(args, "--shard").is_none(), "Workers are sharded by the coordinator");
+    let workers: usize = flag(args, "--workers")
+        .expect("Missing --workers")
+        .parse()
+        .expect("Invalid --workers");
+    assert!(workers > 0, "Invalid --workers");
+    let path = flag(args, "--resume").expect("Missing --resume").to_string();
+    let mut shared = vec![];
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        match arg.as_str() {
+            "--workers" | "--resume" | "--merge" => {
+                rest.next();
+            }
+            _ => shared.push(arg.clone()),
+        }
+    }
+
+    let exe = std::env::current_exe().expect("Could not find the binary to run the workers");
+    let files: Vec<String> = (0..workers).map(|i| format!("{}.{}", path, i)).collect();
+    let children: Vec<_> = files
+        .iter()
+        .enumerate()
+        .map(|(i, file)| {
+            info!("Starting worker {} of {} on {}", i, workers, file);
+            Command::new(&exe)
+                .args(&shared)
+                .args(["--shard", &format!("{}/{}", i, workers), "--resume", file])
+                // Workers print the results of their shard only
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap_or_else(|e| panic!("Could not start worker {}: {}", i, e))
+        })
+        .collect();
+    for (i, mut child) in children.into_iter().enumerate() {
+        let status = child.wait().expect("Could not wait for the workers");
+        assert!(status.success(), "Worker {} of {} failed: {}", i, workers, status);
+    }
+
+    // Workers left without configurations saved nothing
+    let saved: Vec<String> = files.into_iter().filter(|file| Path::new(file).exists()).collect();
+    let mut merged = shared;
+    merged.extend([String::from("--resume"), path]);
+    if !saved.is_empty() {
+        merged.extend([String::from("--merge"), saved.join(",")]);
+    }
+    run(&merged);
+}
+
+/// `[--log-dir DIR]` writes the events of each node to its own file
+/// `[--artifacts DIR]` dumps the state, mailbox and last events of every
+/// node when the run ends abnormally
+/// `[--preset lan|wan|lossy-mobile|adversarial-async]` sets the conditions
+/// of the links
+/// `[--uplink RATE[:BURST]]` caps what every node sends, in bytes per
+/// second
+/// `[--peer-limit RATE[:BURST]]` caps what honest nodes take from each
+/// peer, in messages per second
+/// `[--after-delivery stop|participate]` lets the nodes that delivered help
+/// the others
+fn broadcast(args: &[String]) {
     trace!("Starting...");
-    let mut network = Network::new(10, 0, 1);
+    let mut network = Network::new(10, 0, MaliciousKind::Silent);
     trace!("Network created...");
+    if let Some(dir) = flag(args, "--log-dir") {
+        network
+            .log_nodes(Path::new(dir))
+            .expect("Invalid --log-dir");
+    }
+    if let Some(dir) = flag(args, "--artifacts") {
+        network
+            .dump_artifacts(Path::new(dir))
+            .expect("Invalid --artifacts");
+    }
+    let mut config = match flag(args, "--preset") {
+        Some(preset) => NetworkConfig::preset(preset.parse().expect("Invalid --preset")),
+        None => NetworkConfig::default(),
+    };
+    if let Some(uplink) = flag(args, "--uplink") {
+        config.uplink = Some(uplink.parse().expect("Invalid --uplink"));
+    }
+    if let Some(limit) = flag(args, "--peer-limit") {
+        config.peer_limit = Some(limit.parse().expect("Invalid --peer-limit"));
+    }
+    network.set_config(config);
+    if let Some(after) = flag(args, "--after-delivery") {
+        network.set_after_delivery(after.parse().expect("Invalid --after-delivery"));
+    }
     let (success, results) = network.bracha_broadcast(7, 0);
     if success {
         trace!("Bracha broadcast successful: {:?}", results)
     } else {
         warn!("Bracha broadcast failed: {:?}", results)
     }
+    trace!("Network statistics:\n{}", network.stats());
+    trace!("Network statistics CSV:\n{}", network.stats_csv());
+    if let Some(delivered) = network.stats().latency(Milestone::Delivered) {
+        trace!("Delivery latency:\n{}", delivered);
+    }
+    if let Some((node, queued)) = network
+        .stats()
+        .queued()
+        .iter()
+        .max_by_key(|(_, queued)| **queued)
+    {
+        trace!("Longest uplink queuing: {:?} at node {}", queued, node);
+    }
+    for (node, breakdown) in network.delivery_breakdown() {
+        trace!("Node {} delivered after {}", node, breakdown);
+    }
+}
+
+/// Boundary of the drop rates where Bracha broadcast starts failing
+/// `contour [--attack silent|random|mirror] [--runs N] [--time-limit-ms T]
+/// [--resume FILE] [--shard I/N] [--merge FILE,FILE]`
+fn contour(args: &[String]) {
+    let mut search = ContourSearch::default();
+    if let Some(attack) = flag(args, "--attack") {
+        search.kind = attack.parse().expect("Invalid --attack");
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        search.runs = runs.parse().expect("Invalid --runs");
+    }
+    if let Some(limit) = flag(args, "--time-limit-ms") {
+        search.time_limit = Duration::from_millis(limit.parse().expect("Invalid --time-limit-ms"));
+    }
+
+    let mut checkpoint = campaign(args, search.manifest());
+    let contour = search
+        .resume(&mut checkpoint, |network| network.bracha_broadcast(7, 0).0)
+        .expect("Could not save the campaign");
+    print!("{}", contour_csv(&contour, &search.manifest()));
+}
+
+/// How often Byzantine nodes get a value other than the leader's delivered
+/// `tie-break [--rules quorum,plurality,lowest,own-echo] [--runs N] [--seed S]
+/// [--resume FILE] [--shard I/N] [--merge FILE,FILE]`
+fn tie_break(args: &[String]) {
+    let mut experiment = TieBreakExperiment::default();
+    if let Some(rules) = flag(args, "--rules") {
+        experiment.rules = rules
+            .split(',')
+            .map(|rule| rule.parse().expect("Invalid --rules"))
+            .collect();
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        experiment.runs = runs.parse().expect("Invalid --runs");
+    }
+    if let Some(seed) = flag(args, "--seed") {
+        experiment.seed = seed.parse().expect("Invalid --seed");
+    }
+
+    let mut checkpoint = campaign(args, experiment.manifest());
+    let outcomes = experiment.resume(&mut checkpoint).expect("Could not save the campaign");
+    print!("{}", tie_break_csv(&outcomes, &experiment.manifest()));
+}
+
+/// Cost of a batched broadcast against one broadcast per value
+/// `batching [--nodes N] [--sizes 1,4,16]`
+fn batching(args: &[String]) {
+    let mut benchmark = BatchingBenchmark::default();
+    if let Some(nodes) = flag(args, "--nodes") {
+        benchmark.num_nodes = nodes.parse().expect("Invalid --nodes");
+    }
+    if let Some(sizes) = flag(args, "--sizes") {
+        benchmark.batch_sizes = sizes
+            .split(',')
+            .map(|size| size.parse().expect("Invalid --sizes"))
+            .collect();
+    }
+
+    let points = benchmark.run();
+    print!("{}", batching_csv(&points, &benchmark.manifest()));
+}
+
+/// Latency and messages of the variants of the honest nodes
+/// `strategies [--strategies standard,eager-init+lazy-echo] [--runs N] [--leader-drop-rate P]`
+fn strategies(args: &[String]) {
+    let mut comparison = StrategyComparison::default();
+    if let Some(strategies) = flag(args, "--strategies") {
+        comparison.strategies = strategies
+            .split(',')
+            .map(|strategy| strategy.parse().expect("Invalid --strategies"))
+            .collect();
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        comparison.runs = runs.parse().expect("Invalid --runs");
+    }
+    if let Some(rate) = flag(args, "--leader-drop-rate") {
+        comparison.leader_drop_rate = rate.parse().expect("Invalid --leader-drop-rate");
+    }
+
+    let outcomes = comparison.run();
+    print!("{}", strategies_csv(&outcomes, &comparison.manifest()));
+}
+
+/// Slow nodes stranded when the nodes that delivered stop helping
+/// `helping [--policies stop,participate] [--runs N] [--drop-rate P] [--slow-nodes K]`
+fn helping(args: &[String]) {
+    let mut experiment = HelpingExperiment::default();
+    if let Some(policies) = flag(args, "--policies") {
+        experiment.policies = policies
+            .split(',')
+            .map(|policy| policy.parse().expect("Invalid --policies"))
+            .collect();
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        experiment.runs = runs.parse().expect("Invalid --runs");
+    }
+    if let Some(rate) = flag(args, "--drop-rate") {
+        experiment.drop_rate = rate.parse().expect("Invalid --drop-rate");
+    }
+    if let Some(slow) = flag(args, "--slow-nodes") {
+        experiment.slow_nodes = slow.parse().expect("Invalid --slow-nodes");
+    }
+
+    let outcomes = experiment.run();
+    print!("{}", helping_csv(&outcomes, &experiment.manifest()));
+}
+
+/// Attacks ranked by the first metric
+/// `attacks [--attacks silent,random,mirror] [--metrics failure_rate,extra_messages] [--runs N]`
+/// `[--peer-limit RATE[:BURST]]` has honest nodes throttle flooding peers
+/// `[--placement highest|random:SEED|nodes:ID,ID]` chooses the malicious nodes
+/// `[--resume FILE] [--shard I/N] [--merge FILE,FILE]`
+fn attacks(args: &[String]) {
+    let mut comparison = AttackComparison::default();
+    if let Some(attacks) = flag(args, "--attacks") {
+        comparison.kinds = attacks
+            .split(',')
+            .map(|attack| attack.parse().expect("Invalid --attacks"))
+            .collect();
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        comparison.runs = runs.parse().expect("Invalid --runs");
+    }
+    if let Some(limit) = flag(args, "--peer-limit") {
+        comparison.peer_limit = Some(limit.parse().expect("Invalid --peer-limit"));
+    }
+    if let Some(placement) = flag(args, "--placement") {
+        comparison.placement = placement.parse().expect("Invalid --placement");
+    }
+    let metrics = flag(args, "--metrics")
+        .unwrap_or("agreement_violation,failure_rate,added_latency_us,extra_messages")
+        .split(',')
+        .map(|metric| attack_metric(metric).expect("Invalid --metrics"))
+        .collect::<Vec<_>>();
+
+    let manifest = comparison.manifest(&metrics);
+    let mut checkpoint = campaign(args, manifest.clone());
+    let scores = comparison
+        .resume(&mut checkpoint, &metrics)
+        .expect("Could not save the campaign");
+    print!("{}", attacks_csv(&scores, &metrics, &manifest));
+}
+
+/// Success rates of the broadcasts of honest and malicious leaders under
+/// every attack
+/// `leaders [--attacks silent,random,mirror] [--leaders honest:SEED,malicious] [--runs N]`
+/// Policies are `fixed:ID`, `honest:SEED`, `any:SEED` and `malicious`
+/// `[--resume FILE] [--shard I/N] [--merge FILE,FILE]`
+fn leaders(args: &[String]) {
+    let mut experiment = LeaderExperiment::default();
+    if let Some(attacks) = flag(args, "--attacks") {
+        experiment.kinds = attacks
+            .split(',')
+            .map(|attack| attack.parse().expect("Invalid --attacks"))
+            .collect();
+    }
+    if let Some(leaders) = flag(args, "--leaders") {
+        experiment.leaders = leaders
+            .split(',')
+            .map(|leader| leader.parse().expect("Invalid --leaders"))
+            .collect();
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        experiment.runs = runs.parse().expect("Invalid --runs");
+    }
+
+    let mut checkpoint = campaign(args, experiment.manifest());
+    let outcomes = experiment.resume(&mut checkpoint).expect("Could not save the campaign");
+    print!("{}", leaders_csv(&outcomes, &experiment.manifest()));
+}
+
+/// Throughput of consecutive batched broadcasts
+/// `throughput [--instances N] [--warmup N] [--cooldown N] [--steady-window N] [--batch N]
+/// [--resume FILE] [--shard I/N] [--merge FILE,FILE]`
+fn throughput(args: &[String]) {
+    let mut experiment = ThroughputExperiment::default();
+    if let Some(instances) = flag(args, "--instances") {
+        experiment.instances = instances.parse().expect("Invalid --instances");
+    }
+    if let Some(warmup) = flag(args, "--warmup") {
+        experiment.warmup = warmup.parse().expect("Invalid --warmup");
+    }
+    if let Some(cooldown) = flag(args, "--cooldown") {
+        experiment.cooldown = cooldown.parse().expect("Invalid --cooldown");
+    }
+    if let Some(window) = flag(args, "--steady-window") {
+        experiment.steady_window = window.parse().expect("Invalid --steady-window");
+    }
+    if let Some(batch) = flag(args, "--batch") {
+        experiment.batch_size = batch.parse().expect("Invalid --batch");
+    }
+
+    let mut checkpoint = campaign(args, experiment.manifest());
+    let report = experiment.resume(&mut checkpoint).expect("Could not save the campaign");
+    print!("{}", throughput_csv(&report, &experiment.manifest()));
+}
+
+/// Rollbacks of speculative replicas as links get more and more jittery
+/// `speculation [--nodes N] [--malicious 0,3] [--jitters-ms 0,1,5] [--runs N]
+/// [--resume FILE] [--shard I/N] [--merge FILE,FILE]`
+fn speculation(args: &[String]) {
+    let mut experiment = SpeculationExperiment::default();
+    if let Some(nodes) = flag(args, "--nodes") {
+        experiment.num_nodes = nodes.parse().expect("Invalid --nodes");
+    }
+    if let Some(malicious) = flag(args, "--malicious") {
+        experiment.malicious = malicious
+            .split(',')
+            .map(|f| f.parse().expect("Invalid --malicious"))
+            .collect();
+    }
+    if let Some(jitters) = flag(args, "--jitters-ms") {
+        experiment.jitters = jitters
+            .split(',')
+            .map(|ms| Duration::from_millis(ms.parse().expect("Invalid --jitters-ms")))
+            .collect();
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        experiment.runs = runs.parse().expect("Invalid --runs");
+    }
+
+    let mut checkpoint = campaign(args, experiment.manifest());
+    let points = experiment.resume(&mut checkpoint).expect("Could not save the campaign");
+    print!("{}", speculation_csv(&points, &experiment.manifest()));
+}
+
+/// Throughput and client latency of the replicated state machine as the
+/// offered load grows
+/// `load [--nodes N] [--commands N] [--rates 100,200] [--outstanding 1,4]
+/// [--think-ms N] [--timeout-ms N] [--mempool N] [--runs N] [--resume FILE]
+/// [--shard I/N] [--merge FILE,FILE]`
+fn load(args: &[String]) {
+    let mut experiment = LoadExperiment::default();
+    if let Some(nodes) = flag(args, "--nodes") {
+        experiment.num_nodes = nodes.parse().expect("Invalid --nodes");
+    }
+    if let Some(commands) = flag(args, "--commands") {
+        experiment.commands = commands.parse().expect("Invalid --commands");
+    }
+    if let Some(rates) = flag(args, "--rates") {
+        experiment.rates = rates
+            .split(',')
+            .map(|rate| rate.parse().expect("Invalid --rates"))
+            .collect();
+    }
+    if let Some(outstanding) = flag(args, "--outstanding") {
+        experiment.outstanding = outstanding
+            .split(',')
+            .map(|k| k.parse().expect("Invalid --outstanding"))
+            .collect();
+    }
+    if let Some(think) = flag(args, "--think-ms") {
+        experiment.think = Duration::from_millis(think.parse().expect("Invalid --think-ms"));
+    }
+    if let Some(timeout) = flag(args, "--timeout-ms") {
+        let timeout = timeout.parse().expect("Invalid --timeout-ms");
+        experiment.client.timeout = Duration::from_millis(timeout);
+    }
+    if let Some(mempool) = flag(args, "--mempool") {
+        experiment.mempool = Some(mempool.parse().expect("Invalid --mempool"));
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        experiment.runs = runs.parse().expect("Invalid --runs");
+    }
+
+    let mut checkpoint = campaign(args, experiment.manifest());
+    let points = experiment.resume(&mut checkpoint).expect("Could not save the campaign");
+    print!("{}", load_csv(&points, &experiment.manifest()));
+}
+
+/// Quorum formation of a broadcast with more and more slow nodes
+/// `heterogeneity [--nodes N] [--slow 0,1,3] [--slow-speed 0.2] [--cost-us N]
+/// [--runs N] [--resume FILE] [--shard I/N] [--merge FILE,FILE]`
+fn heterogeneity(args: &[String]) {
+    let mut experiment = HeterogeneityExperiment::default();
+    if let Some(nodes) = flag(args, "--nodes") {
+        experiment.num_nodes = nodes.parse().expect("Invalid --nodes");
+    }
+    if let Some(slow) = flag(args, "--slow") {
+        experiment.slow = slow
+            .split(',')
+            .map(|k| k.parse().expect("Invalid --slow"))
+            .collect();
+    }
+    if let Some(speed) = flag(args, "--slow-speed") {
+        experiment.slow_speed = speed.parse().expect("Invalid --slow-speed");
+    }
+    if let Some(cost) = flag(args, "--cost-us") {
+        experiment.per_message = Duration::from_micros(cost.parse().expect("Invalid --cost-us"));
+    }
+    if let Some(runs) = flag(args, "--runs") {
+        experiment.runs = runs.parse().expect("Invalid --runs");
+    }
+
+    let mut checkpoint = campaign(args, experiment.manifest());
+    let points = experiment.resume(&mut checkpoint).expect("Could not save the campaign");
+    print!("{}", heterogeneity_csv(&points, &experiment.manifest()));
+}
+
+/// Store the latency and traffic of the broadcast benchmark
+/// `baseline --out FILE [--runs N] [--attack silent|random|mirror]`
+fn baseline(args: &[String]) {
+    let mut benchmark = BroadcastBenchmark::default();
+    if let Some(runs) = flag(args, "--runs") {
+        benchmark.runs = runs.parse().expect("Invalid --runs");
+    }
+    if let Some(attack) = flag(args, "--attack") {
+        benchmark.kind = attack.parse().expect("Invalid --attack");
+    }
+    let out = flag(args, "--out").expect("Missing --out");
+
+    let baseline = benchmark.run();
+    fs::write(out, baseline.to_json()).expect("Could not write the baseline");
+}
+
+/// Run the benchmark of a stored baseline again and flag the significant
+/// regressions, exits with an error if there is any
+/// `compare-baseline FILE`
+fn compare_baseline(args: &[String]) {
+    let path = args.first().expect("Missing baseline file");
+    let json = fs::read_to_string(path).expect("Could not read the baseline");
+    let baseline = Baseline::from_json(&json).expect("Invalid baseline");
+    let benchmark = BroadcastBenchmark::from_manifest(&baseline.manifest)
+        .expect("Baseline of an unknown benchmark");
+
+    let current = benchmark.run();
+    println!(
+        "baseline {} ({}), current {} ({})",
+        baseline.manifest.git_hash,
+        baseline.manifest.crate_version,
+        current.manifest.git_hash,
+        current.manifest.crate_version
+    );
+    let comparisons = baseline.compare(&current);
+    for comparison in comparisons.iter() {
+        println!("{}", comparison);
+    }
+    if comparisons
+        .iter()
+        .any(|comparison| comparison.is_regression())
+    {
+        std::process::exit(1);
+    }
+}
+
+/// Where two recorded runs differ: configuration, outputs, messages per
+/// phase and first event, exits with an error if the runs differ
+/// `diff DIR_A DIR_B`, directories written with `--log-dir`
+fn diff(args: &[String]) {
+    let load = |dir: Option<&String>| {
+        let dir = dir.expect("Missing recording directory");
+        Recording::load(Path::new(dir)).unwrap_or_else(|e| panic!("Invalid recording: {}", e))
+    };
+    let diff = RunDiff::new(&load(args.first()), &load(args.get(1)));
+    println!("{}", diff);
+    if !diff.same_runs() || !diff.outputs.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Run one of the canonical attacks on Bracha broadcast, or list them
+/// `scenario [NAME] [--nodes N]`
+fn scenario(args: &[String]) {
+    let name = match args.first() {
+        Some(name) if !name.starts_with("--") => name,
+        _ => {
+            for scenario in SCENARIOS.iter() {
+                println!("{:<20} {}", scenario.name, scenario.description);
+                println!("{:<20} {}", "", scenario.tags.join(" "));
+            }
+            return;
+        }
+    };
+    let scenario = scenario::find(name).unwrap_or_else(|| panic!("Unknown scenario: {}", name));
+    let nodes = flag(args, "--nodes").map_or(10, |n| n.parse().expect("Invalid --nodes"));
+    let outcome = scenario.run(nodes);
+    println!("{}", outcome);
+    if !outcome.passed {
+        std::process::exit(1);
+    }
+}
+
+/// Run the scenarios whose tags match the expression at every size of the
+/// test matrix, `all` by default
+/// `matrix [EXPRESSION]`, e.g. `matrix 'fault:byzantine & !size:large'`
+fn matrix(args: &[String]) {
+    let selection: TagExpr = args
+        .first()
+        .map_or(Ok(TagExpr::All), |expr| expr.parse())
+        .unwrap_or_else(|e| panic!("{}", e));
+    let report = scenario::run_matrix(&selection);
+    println!("{}", report);
+    for outcome in report.failed() {
+        println!("\n{}", outcome);
+    }
+    if !report.failed().is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Copies, coverage and stretch of every node flooding its value
+/// `flood [--nodes N] [--topology complete|ring|grid:W] [--ttl T] [--no-suppression]
+/// [--time-limit-ms T]`
+/// Nodes process one message at a time, the run ends once no copy is left
+fn flood(args: &[String]) {
+    let nodes = flag(args, "--nodes").map_or(9, |n| n.parse().expect("Invalid --nodes"));
+    let topology: Topology = flag(args, "--topology")
+        .map_or(Ok(Topology::Ring), str::parse)
+        .expect("Invalid --topology");
+    let limit =
+        flag(args, "--time-limit-ms").map_or(1000, |t| t.parse().expect("Invalid --time-limit-ms"));
+    let mut network = Network::new(nodes, 0, MaliciousKind::Silent);
+    network.set_schedule(Schedule::Deterministic);
+    network.set_time_limit(Duration::from_millis(limit));
+    network.set_topology(topology);
+    network.set_flood_config(FloodConfig {
+        ttl: flag(args, "--ttl").map(|ttl| ttl.parse().expect("Invalid --ttl")),
+        suppress_duplicates: !args.iter().any(|arg| arg == "--no-suppression"),
+    });
+    let inputs = (0..nodes).map(|id| (id, id)).collect();
+    let (success, _) = network.flood(&inputs);
+    println!(
+        "{} nodes over {}: {}",
+        nodes,
+        topology,
+        network.flood_report()
+    );
+    if !success {
+        std::process::exit(1);
+    }
+}
+
+/// Messages, state and thresholds of a protocol, of every protocol by
+/// default
+/// `describe [PROTOCOL] [--nodes N]`, thresholds are those of N nodes
+fn describe(args: &[String]) {
+    let nodes = flag(args, "--nodes").map_or(4, |n| n.parse().expect("Invalid --nodes"));
+    let ids = match args.first() {
+        Some(name) if !name.starts_with("--") => {
+            vec![protocols::find(name).unwrap_or_else(|| panic!("Unknown protocol: {}", name))]
+        }
+        _ => PROTOCOLS.to_vec(),
+    };
+    for id in ids {
+        println!("{}", protocols::describe(id, nodes).unwrap());
+    }
 }
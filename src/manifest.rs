@@ -0,0 +1,136 @@
+use std::fmt;
+
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+// Set by the build script
+const GIT_HASH: &str = env!("GIT_HASH");
+const FEATURES: &str = env!("FEATURES");
+
+/// What produced an artifact: build of the simulator and configuration of
+/// the simulation, enough to reproduce it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Manifest {
+    pub crate_version: String,
+    pub git_hash: String,
+    // Cargo features enabled in the build
+    pub features: Vec<String>,
+    pub seed: Option<u64>,
+    // Configuration of the simulation, in insertion order
+    pub config: Vec<(String, String)>,
+}
+
+impl Default for Manifest {
+    fn default() -> Self {
+        Manifest::new()
+    }
+}
+
+impl Manifest {
+    /// Manifest of the running build, without configuration
+    pub fn new() -> Self {
+        Manifest {
+            crate_version: CRATE_VERSION.to_string(),
+            git_hash: GIT_HASH.to_string(),
+            features: FEATURES
+                .split(',')
+                .filter(|feature| !feature.is_empty())
+                .map(String::from)
+                .collect(),
+            seed: None,
+            config: vec![],
+        }
+    }
+
+    /// Add a configuration entry
+    pub fn with(mut self, key: &str, value: impl fmt::Display) -> Self {
+        self.set(key, value);
+        self
+    }
+
+    /// Add or replace a configuration entry
+    pub fn set(&mut self, key: &str, value: impl fmt::Display) {
+        let value = value.to_string().replace('\n', " ");
+        match self.config.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.config.push((key.to_string(), value)),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.config
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Manifest as comment lines starting with `comment`, to be put at the
+    /// top of a file
+    pub fn header(&self, comment: &str) -> String {
+        self.to_string()
+            .lines()
+            .map(|line| format!("{} {}\n", comment, line))
+            .collect()
+    }
+
+    /// Read the manifest from the comment lines at the top of a file
+    pub fn parse(text: &str, comment: &str) -> Result<Self, String> {
+        let mut manifest = Manifest {
+            crate_version: String::new(),
+            git_hash: String::new(),
+            features: vec![],
+            seed: None,
+            config: vec![],
+        };
+        for line in text.lines() {
+            let line = match line.strip_prefix(comment) {
+                Some(line) => line.trim(),
+                None => break,
+            };
+            let (key, value) = line
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid manifest line: {}", line))?;
+            let value = value.trim();
+            match key {
+                "crate_version" => manifest.crate_version = value.to_string(),
+                "git_hash" => manifest.git_hash = value.to_string(),
+                "features" => {
+                    manifest.features = value
+                        .split(',')
+                        .filter(|feature| !feature.is_empty())
+                        .map(String::from)
+                        .collect()
+                }
+                "seed" => {
+                    manifest.seed = match value {
+                        "none" => None,
+                        seed => Some(
+                            seed.parse()
+                                .map_err(|_| format!("Invalid seed: {}", seed))?,
+                        ),
+                    }
+                }
+                key => manifest.set(key, value),
+            }
+        }
+        if manifest.crate_version.is_empty() {
+            return Err("No manifest found".to_string());
+        }
+        Ok(manifest)
+    }
+}
+
+/// One `key: value` line per entry
+impl fmt::Display for Manifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "crate_version: {}", self.crate_version)?;
+        writeln!(f, "git_hash: {}", self.git_hash)?;
+        writeln!(f, "features: {}", self.features.join(","))?;
+        match self.seed {
+            Some(seed) => writeln!(f, "seed: {}", seed)?,
+            None => writeln!(f, "seed: none")?,
+        }
+        for (key, value) in &self.config {
+            writeln!(f, "{}: {}", key, value)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+use crate::network::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Hash of a leaf or of an inner node
+/// SipHash with fixed keys: collisions are unlikely enough for simulations
+/// but it offers no protection against an adversary looking for them
+pub type Digest = u64;
+
+fn hash_leaf(index: usize, value: Value) -> Digest {
+    let mut hasher = DefaultHasher::new();
+    (0u8, index, value).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_node(left: Digest, right: Digest) -> Digest {
+    let mut hasher = DefaultHasher::new();
+    (1u8, left, right).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Merkle tree over a batch of values, the last node of a level is paired
+/// with itself when the level has an odd length
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleTree {
+    // levels[0] are the leaves, the last level is the root
+    levels: Vec<Vec<Digest>>,
+}
+
+impl MerkleTree {
+    pub fn new(values: &[Value]) -> Self {
+        let leaves = values
+            .iter()
+            .enumerate()
+            .map(|(index, value)| hash_leaf(index, *value))
+            .collect();
+        let mut levels: Vec<Vec<Digest>> = vec![leaves];
+        while levels.last().unwrap().len() > 1 {
+            let level = levels.last().unwrap();
+            let parents = level
+                .chunks(2)
+                .map(|pair| hash_node(pair[0], *pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            levels.push(parents);
+        }
+        MerkleTree { levels }
+    }
+
+    /// Digest committing to the whole batch
+    pub fn root(&self) -> Digest {
+        self.levels.last().unwrap().first().cloned().unwrap_or(0)
+    }
+
+    /// Number of values in the batch
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Hashes computed to build the tree
+    pub fn hashes(&self) -> usize {
+        self.levels.iter().map(Vec::len).sum()
+    }
+
+    /// Proof that the value at `index` belongs to the batch
+    pub fn proof(&self, index: usize) -> MerkleProof {
+        let mut siblings = vec![];
+        let mut i = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = if i.is_multiple_of(2) { i + 1 } else { i - 1 };
+            siblings.push(*level.get(sibling).unwrap_or(&level[i]));
+            i /= 2;
+        }
+        MerkleProof { index, siblings }
+    }
+}
+
+/// Path from a value up to the root of its batch
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MerkleProof {
+    pub index: usize,
+    // Sibling of every node of the path, from the leaf up
+    pub siblings: Vec<Digest>,
+}
+
+impl MerkleProof {
+    /// Whether `value` is the value at `index` of the batch of `root`
+    pub fn verify(&self, root: Digest, value: Value) -> bool {
+        let mut digest = hash_leaf(self.index, value);
+        let mut i = self.index;
+        for sibling in &self.siblings {
+            digest = if i.is_multiple_of(2) {
+                hash_node(digest, *sibling)
+            } else {
+                hash_node(*sibling, digest)
+            };
+            i /= 2;
+        }
+        digest == root
+    }
+
+    /// Hashes computed to verify the proof
+    pub fn hashes(&self) -> usize {
+        1 + self.siblings.len()
+    }
+
+    /// Number of bytes needed to encode the proof
+    pub fn size(&self) -> usize {
+        std::mem::size_of::<usize>() + self.siblings.len() * std::mem::size_of::<Digest>()
+    }
+}
@@ -0,0 +1,175 @@
+use crate::network::Output;
+use crate::node::NodeId;
+use crate::protocols::{Envelope, InstanceId};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Safety property the network watches while the run goes on, on every
+/// message sent and every output of the honest nodes
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Invariant {
+    // Honest node sends two versions of a message it commits to, such as
+    // two different READY in a broadcast, see `Commitment`
+    NoHonestEquivocation,
+    // Two honest nodes output different values, giving up doesn't count
+    HonestAgreement,
+}
+
+impl fmt::Display for Invariant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Invariant::NoHonestEquivocation => write!(f, "no-honest-equivocation"),
+            Invariant::HonestAgreement => write!(f, "honest-agreement"),
+        }
+    }
+}
+
+impl FromStr for Invariant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "no-honest-equivocation" => Ok(Invariant::NoHonestEquivocation),
+            "honest-agreement" => Ok(Invariant::HonestAgreement),
+            _ => Err(format!("Unknown invariant: {}", s)),
+        }
+    }
+}
+
+/// Invariant broken by a node, as soon as the network saw it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub invariant: Invariant,
+    pub node: NodeId,
+    // Events of the trace before the violation and time into the run
+    pub at: usize,
+    pub elapsed: Duration,
+    pub description: String,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} violated by node {} after {} events ({:?}): {}",
+            self.invariant, self.node, self.at, self.elapsed, self.description
+        )
+    }
+}
+
+/// Online monitors of the network and what they saw of the current run
+#[derive(Debug, Default)]
+pub(crate) struct Monitors {
+    pub invariants: Vec<Invariant>,
+    // End the run at the first violation
+    pub stop: bool,
+    // First version of every message committed to, by sender and name
+    commitments: BTreeMap<(NodeId, InstanceId, &'static str, u64), (u64, String)>,
+    // First honest output of the run
+    first_output: Option<(NodeId, Output)>,
+    pub violations: Vec<Violation>,
+}
+
+impl Monitors {
+    fn watches(&self, invariant: Invariant) -> bool {
+        self.invariants.contains(&invariant)
+    }
+
+    /// Forget the previous run, the invariants are kept
+    pub fn clear(&mut self) {
+        self.commitments.clear();
+        self.first_output = None;
+        self.violations.clear();
+    }
+
+    /// Honest node `from` sent `envelope`, returns the violation it makes
+    pub fn sent(
+        &mut self,
+        from: NodeId,
+        envelope: &Envelope,
+        at: usize,
+        elapsed: Duration,
+    ) -> Option<&Violation> {
+        if !self.watches(Invariant::NoHonestEquivocation) {
+            return None;
+        }
+        let commitment = envelope.commitment()?;
+        let key = (
+            from,
+            envelope.instance.clone(),
+            commitment.kind,
+            commitment.slot,
+        );
+        let (value, first) = match self.commitments.get(&key) {
+            Some(first) => first,
+            None => {
+                let description = format!("{:?}", envelope);
+                self.commitments
+                    .insert(key, (commitment.value, description));
+                return None;
+            }
+        };
+        if *value == commitment.value {
+            return None;
+        }
+        let description = format!("sent {:?} after {}", envelope, first);
+        // Later copies of the second version are the same violation
+        self.commitments
+            .insert(key, (commitment.value, description.clone()));
+        self.violate(
+            Invariant::NoHonestEquivocation,
+            from,
+            at,
+            elapsed,
+            description,
+        )
+    }
+
+    /// Honest node `node` output `output`, returns the violation it makes
+    pub fn output(
+        &mut self,
+        node: NodeId,
+        output: &Output,
+        at: usize,
+        elapsed: Duration,
+    ) -> Option<&Violation> {
+        if !self.watches(Invariant::HonestAgreement) || *output == Output::Stalled {
+            return None;
+        }
+        let (first, first_output) = match &self.first_output {
+            Some(first) => first,
+            None => {
+                self.first_output = Some((node, output.clone()));
+                return None;
+            }
+        };
+        if first_output.agrees(output) {
+            return None;
+        }
+        let description = format!(
+            "output {:?}, node {} output {:?}",
+            output, first, first_output
+        );
+        self.violate(Invariant::HonestAgreement, node, at, elapsed, description)
+    }
+
+    fn violate(
+        &mut self,
+        invariant: Invariant,
+        node: NodeId,
+        at: usize,
+        elapsed: Duration,
+        description: String,
+    ) -> Option<&Violation> {
+        self.violations.push(Violation {
+            invariant,
+            node,
+            at,
+            elapsed,
+            description,
+        });
+        self.violations.last()
+    }
+}
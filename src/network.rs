@@ -1,24 +1,249 @@
+use crate::adversary::*;
+use crate::analysis::{delivery_breakdown, DeliveryBreakdown};
+use crate::artifacts::{Abnormal, Artifacts, NodeArtifact, LAST_EVENTS};
+use crate::audit::{AuditEntry, AuditReport};
+use crate::config::{Config, Diagnostic, NetworkConfig, PanicPolicy, TokenBucket};
+use crate::controller::{Knowledge, NetworkController};
+use crate::cost::{CostModel, CostReport, CryptoOp, ProtocolCost};
+#[cfg(feature = "crypto")]
+use crate::crypto::secret_sharing::{SharingConfig, SimulatedSharing};
+#[cfg(feature = "crypto")]
+use crate::crypto::threshold_enc::{EncryptionConfig, SimulatedThreshold};
+#[cfg(feature = "crypto")]
+use crate::crypto::threshold_sig::{SignatureConfig, SignatureShare, SimulatedSignatures};
+use crate::filter::{FilterConfig, InboundFilter};
+use crate::ledger::{Conservation, DropCause, Ledger, MessageId, UNTRACKED};
+use crate::logs::NodeLogs;
+use crate::manifest::Manifest;
+use crate::monitor::{Invariant, Monitors, Violation};
 use crate::node::*;
-use crate::protocols::bracha_broadcast::BroadcastMessage;
-use log::{debug, trace, warn};
-use std::collections::HashMap;
+#[cfg(feature = "crypto")]
+use crate::protocols::avss::AvssConfig;
+use crate::protocols::batched_broadcast::BatchMessage;
+#[cfg(feature = "crypto")]
+use crate::protocols::bracha_broadcast::DeliveryCertificate;
+use crate::protocols::bracha_broadcast::{
+    BroadcastConfig, BroadcastMessage, LivenessCheck, StateRequest,
+};
+use crate::protocols::common_coin::{CoinConfig, CoinSource};
+use crate::protocols::flooding::{FloodConfig, FloodMessage};
+use crate::protocols::graded_broadcast::{Grade, GradedConfig};
+use crate::protocols::atomic_broadcast::DELIVERY_LOG;
+#[cfg(feature = "crypto")]
+use crate::protocols::honey_badger::HoneyBadgerConfig;
+use crate::protocols::mempool::MempoolConfig;
+use crate::protocols::paxos::PaxosConfig;
+#[cfg(feature = "crypto")]
+use crate::protocols::pbft::PbftConfig;
+use crate::protocols::phase_king::{phase_king_faults, PhaseKingConfig};
+use crate::protocols::smr::SmrConfig;
+use crate::protocols::terminating_broadcast::TerminatingConfig;
+use crate::protocols::{
+    fault_model, versions, Envelope, InstanceId, ProtocolConfig, ProtocolId, ProtocolMessage,
+    ATOMIC_BROADCAST, BEN_OR, BINARY_AGREEMENT, COMMON_SUBSET, CONSISTENT_BROADCAST, FLOODING,
+    GRADED_BROADCAST, LEADER_ELECTION, MAJORITY_BROADCAST, MEMPOOL, MVBA, PARALLEL_BROADCAST,
+    PAXOS, PHASE_KING, SMR, TERMINATING_BROADCAST,
+};
+#[cfg(feature = "crypto")]
+use crate::protocols::{AVSS, HONEY_BADGER, PBFT, VABA};
+use crate::quorum::{FaultModel, Parameters};
+use crate::resources::{ResourceCounts, ResourceGuard, Token};
+use crate::rng::*;
+use crate::scheduler::Schedule;
+use crate::stats::*;
+use crate::termination::{RunProgress, Termination};
+use crate::topology::Topology;
+use crate::trace::Trace;
+#[cfg(feature = "crypto")]
+use crate::validity::Predicate;
+use crate::validity::{ValidityConfig, ValidityOracle};
+use crate::whatif::{Branch, Snapshot};
+use log::{error, trace, warn};
+use std::cmp::{Ordering as CmpOrdering, Reverse};
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
 use std::fmt;
-use std::sync::mpsc::{channel, Receiver, Sender};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, SendError, Sender};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
 use std::time;
 
 pub const NETWORK_ID: NodeId = 10000;
 pub type Value = usize;
 
-#[derive(Debug, Clone)]
+/// What a node outputs at the end of a protocol
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Output {
+    Value(Value),
+    // Values output by sub-instances, indexed by the node they come from
+    Set(BTreeMap<NodeId, Value>),
+    // Ordered values, e.g. a batch
+    Sequence(Vec<Value>),
+    // Value with the confidence of the node in it, none at grade 0
+    Graded(Option<Value>, Grade),
+    // Value with the signatures of the READY the node delivered it on
+    #[cfg(feature = "crypto")]
+    Certified(DeliveryCertificate),
+    // No progress before the liveness timeout, the node gave up
+    Stalled,
+    // Nothing to output, the designated sender is faulty
+    Bottom,
+}
+
+impl Output {
+    /// Output of a single value protocol
+    pub fn value(&self) -> Option<Value> {
+        match self {
+            Output::Value(v) => Some(*v),
+            #[cfg(feature = "crypto")]
+            Output::Certified(certificate) => Some(certificate.value),
+            Output::Set(_)
+            | Output::Sequence(_)
+            | Output::Graded(..)
+            | Output::Stalled
+            | Output::Bottom => None,
+        }
+    }
+
+    /// Certificate the node delivered with, if any
+    #[cfg(feature = "crypto")]
+    pub fn certificate(&self) -> Option<&DeliveryCertificate> {
+        match self {
+            Output::Certified(certificate) => Some(certificate),
+            _ => None,
+        }
+    }
+
+    /// Whether the nodes outputting `self` and `other` agree, certified
+    /// values agree whoever signed their certificates
+    pub fn agrees(&self, other: &Output) -> bool {
+        #[cfg(feature = "crypto")]
+        if self.certificate().is_some() || other.certificate().is_some() {
+            return self.value().is_some() && self.value() == other.value();
+        }
+        self == other
+    }
+
+    /// Output of a graded protocol
+    pub fn graded(&self) -> Option<(Option<Value>, Grade)> {
+        match self {
+            Output::Graded(v, grade) => Some((*v, *grade)),
+            _ => None,
+        }
+    }
+
+    fn size(&self) -> usize {
+        match self {
+            Output::Value(_) => std::mem::size_of::<Value>(),
+            Output::Set(values) => {
+                values.len() * (std::mem::size_of::<NodeId>() + std::mem::size_of::<Value>())
+            }
+            Output::Sequence(values) => values.len() * std::mem::size_of::<Value>(),
+            Output::Graded(..) => std::mem::size_of::<Value>() + std::mem::size_of::<Grade>(),
+            #[cfg(feature = "crypto")]
+            Output::Certified(certificate) => {
+                std::mem::size_of::<Value>()
+                    + certificate.signatures.len()
+                        * (std::mem::size_of::<NodeId>() + std::mem::size_of::<SignatureShare>())
+            }
+            Output::Stalled | Output::Bottom => 0,
+        }
+    }
+}
+
+// Variants are named like the messages of the papers
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq)]
 pub(crate) enum Message {
-    BROADCAST(BroadcastMessage),
+    // Message of one of the protocols run by the nodes
+    PROTOCOL(Envelope),
+
+    // Sent by the network: node starts the root instance of a protocol
+    // with this input
+    START(ProtocolId, Value),
 
     // Sent by the network: node has to terminate
-    // Sent by a node: protocol has finished and node delivers this value
-    END(Value),
+    // Sent by a node: protocol has finished and node delivers this output
+    END(Output),
+
+    // Sent by the network: node has to clear its state for a new run
+    // Sent by a node: node is ready for a new run
+    RESET,
+
+    // Sent by a node in lockstep: node has processed the last message
+    ACK,
+
+    // Sent by the network: node reports its final state
+    AUDIT,
+
+    // Sent by a node: final state of the node
+    STATE(Vec<AuditEntry>),
+
+    // Sent by a node: protocol code panicked, node is crashed until the
+    // next reset
+    PANIC(NodePanic),
+
+    // Sent by a node: deliver the message back to the node after the delay
+    TIMER(time::Duration, Envelope),
+
+    // Sent by a node: node crashed after processing this number of messages
+    // and recovered without its protocol state
+    RECOVERED(usize),
+
+    // Sent by the network: late node joins the run
+    JOIN,
 }
 use Message::*;
 
+impl Message {
+    pub(crate) fn protocol<M: ProtocolMessage>(msg: M) -> Self {
+        PROTOCOL(Envelope::new(msg))
+    }
+
+    pub(crate) fn phase(&self) -> Phase {
+        match self {
+            PROTOCOL(envelope) => envelope.payload.phase(),
+            START(..) | END(_) | RESET | ACK | AUDIT | STATE(_) | PANIC(_) | TIMER(..)
+            | RECOVERED(_) | JOIN => Phase::Control,
+        }
+    }
+
+    /// Number of bytes needed to encode the message: message tag and payload
+    pub(crate) fn size(&self) -> usize {
+        1 + match self {
+            // Instance and protocol message
+            PROTOCOL(envelope) => envelope.size(),
+            START(..) => std::mem::size_of::<ProtocolId>() + std::mem::size_of::<Value>(),
+            END(output) => output.size(),
+            RESET | ACK | AUDIT | JOIN => 0,
+            STATE(entries) => entries
+                .iter()
+                .map(|entry| entry.instance.len() + entry.field.len() + entry.value.len())
+                .sum(),
+            PANIC(report) => report.message.len(),
+            TIMER(_, envelope) => std::mem::size_of::<time::Duration>() + envelope.size(),
+            RECOVERED(_) => std::mem::size_of::<usize>(),
+        }
+    }
+
+    /// Same message carrying the value `v`
+    pub(crate) fn with_value(&self, v: Value) -> Self {
+        match self {
+            PROTOCOL(envelope) => PROTOCOL(envelope.with_value(v)),
+            START(protocol, _) => START(*protocol, v),
+            END(output) => END(output.clone()),
+            RESET => RESET,
+            ACK => ACK,
+            AUDIT => AUDIT,
+            STATE(entries) => STATE(entries.clone()),
+            PANIC(report) => PANIC(report.clone()),
+            TIMER(delay, envelope) => TIMER(*delay, envelope.with_value(v)),
+            RECOVERED(processed) => RECOVERED(*processed),
+            JOIN => JOIN,
+        }
+    }
+}
+
 unsafe impl Send for Message {}
 unsafe impl Sync for Message {}
 
@@ -26,6 +251,10 @@ pub(crate) struct NetworkMessage {
     pub from: NodeId,
     pub to: NodeId,
     pub msg: Message,
+    // Given by the network when it routes the message
+    pub id: MessageId,
+    // Sent by a malicious node, or by a node that heard from one
+    pub influenced: bool,
 }
 
 impl fmt::Debug for NetworkMessage {
@@ -48,160 +277,2918 @@ impl fmt::Debug for NetworkMessage {
 
 impl NetworkMessage {
     pub fn new(from: NodeId, to: NodeId, msg: Message) -> Self {
-        NetworkMessage { from, to, msg }
+        NetworkMessage {
+            from,
+            to,
+            msg,
+            id: UNTRACKED,
+            influenced: false,
+        }
     }
 }
 
 unsafe impl Send for NetworkMessage {}
 unsafe impl Sync for NetworkMessage {}
 
+// How often the router checks for dead node threads while it waits
+const THREAD_POLL: time::Duration = time::Duration::from_millis(50);
+
+// How long closing a network waits for each node thread to stop
+const JOIN_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+// What the router gets while waiting for the nodes
+enum Received {
+    Message(NetworkMessage),
+    // Thread of a node is gone
+    NodeLost,
+    // Time limit reached or every node is gone
+    Over,
+}
+
+// Message scheduled by a node in lockstep schedules, on a virtual clock
+struct Timer {
+    at: time::Duration,
+    seq: usize,
+    msg: NetworkMessage,
+}
+
+// Message held by the network until its delivery time
+struct Delayed {
+    at: time::Instant,
+    // Keeps messages with the same delivery time in sending order
+    seq: usize,
+    msg: NetworkMessage,
+}
+
+impl PartialEq for Delayed {
+    fn eq(&self, other: &Self) -> bool {
+        (self.at, self.seq) == (other.at, other.seq)
+    }
+}
+
+impl Eq for Delayed {}
+
+impl PartialOrd for Delayed {
+    fn partial_cmp(&self, other: &Self) -> Option<CmpOrdering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Delayed {
+    fn cmp(&self, other: &Self) -> CmpOrdering {
+        (self.at, self.seq).cmp(&(other.at, other.seq))
+    }
+}
+
 pub struct Network {
     num_nodes: usize,
+    num_malicious: usize,
+    kind: MaliciousKind,
+    // Failures the malicious nodes are tolerated for
+    fault_model: FaultModel,
     // Nodes of the network, node id corresponds to its index
     nodes: HashMap<NodeId, (Node, Sender<NetworkMessage>)>,
     node_behaviours: HashMap<Behaviour, Vec<NodeId>>,
     rx: Receiver<NetworkMessage>,
     time_limit: Option<time::Duration>,
+    termination: Termination,
+    // Adversary sitting on some links of the network
+    mitm: ManInTheMiddle,
+    // Requests of experiments on the messages in flight
+    controller: NetworkController,
+    link_security: LinkSecurity,
+    // Invariants checked while the runs go on
+    monitors: Monitors,
+    cost_model: CostModel,
+    // Messages of the last run per protocol, the operations are counted by
+    // the nodes
+    costs: BTreeMap<ProtocolId, ProtocolCost>,
+    // Traffic of the last run, overall and per behaviour class
+    stats: NetworkStats,
+    behaviour_stats: BehaviourStats,
+    flood: FloodReport,
+    // Nodes that heard from a malicious node during the last run, directly
+    // or not
+    influenced: BTreeSet<NodeId>,
+    // Consistency of the final states of the honest nodes of the last run
+    audit: AuditReport,
+    // Nodes whose protocol code panicked during the last run
+    panics: Vec<NodePanic>,
+    // Nodes that recovered without their state during the last run, and
+    // the protocol messages sent by amnesiac nodes to tell what they resent
+    amnesia: Vec<AmnesiaReport>,
+    sent: HashSet<(NodeId, NodeId, InstanceId, Phase)>,
+    // Time the network got each message from its sender and time it
+    // waited for the uplink of the sender
+    departures: HashMap<MessageId, (time::Duration, time::Duration)>,
+    // Nodes that gave up the last run after their liveness timeout
+    stalled: BTreeSet<NodeId>,
+    // Where every node stood when the last run ended
+    lifecycles: BTreeMap<NodeId, Lifecycle>,
+    // Fate of the messages of the last run and its balance
+    ledger: Ledger,
+    conservation: Conservation,
+    // Nodes whose thread is gone, they are crashed from then on
+    lost: Vec<NodeId>,
+    // Event stream of every node, when written to files
+    logs: Option<NodeLogs>,
+    // Post-mortems of abnormal runs, and the one of the last run
+    artifacts: Option<Artifacts>,
+    dumped: Option<PathBuf>,
+    on_panic: PanicPolicy,
+    // Number of runs done with the current node threads
+    runs: usize,
+    rng: Box<dyn Rng>,
+    rng_source: RngSource,
+    // Random draws of the nodes and the network when recording
+    randomness: Arc<Mutex<Randomness>>,
+    schedule: Schedule,
+    // Shared with the nodes: acknowledge every processed message
+    shared: Shared,
+    // Counts the channel of the network as open while it lives
+    channel: Token,
+    // Messages in flight waiting for the scheduler
+    pending: Vec<NetworkMessage>,
+    // Deliveries of the last run
+    trace: Trace,
+    config: NetworkConfig,
+    // Number of deliveries before the link conditions and attacks apply
+    faults_from: usize,
+    // Messages in transit in the concurrent schedule, earliest first
+    delayed: BinaryHeap<Reverse<Delayed>>,
+    num_delayed: usize,
+    // Uplinks of the nodes when they are capped
+    uplinks: HashMap<NodeId, TokenBucket>,
+    // Messages each honest node takes from each peer, by receiver and
+    // sender, when they are limited
+    peer_buckets: HashMap<(NodeId, NodeId), TokenBucket>,
+    // Time each node is done with the messages it received, with a CPU model
+    cpus: HashMap<NodeId, time::Instant>,
+    // Messages scheduled by the nodes in lockstep schedules and the time of
+    // the last one delivered
+    timers: Vec<Timer>,
+    clock: time::Duration,
 }
 
 impl Network {
     /// Create new network with `num_nodes` nodes
     pub fn new(num_nodes: usize, num_malicious: usize, kind: MaliciousKind) -> Self {
-        // Number of "bad" nodes shall be less than a third of the nodes
-        assert!((num_malicious as f32) < (num_nodes as f32) / 3.0);
+        Network::with_rng(num_nodes, num_malicious, kind, RngSource::Os)
+    }
+
+    /// Create the network set up by `config`, or tell everything wrong with
+    /// the setup
+    pub fn from_config(config: &Config, rng: RngSource) -> Result<Self, Vec<Diagnostic>> {
+        config.validate()?;
+        let malicious = config
+            .placement
+            .malicious(config.num_nodes, config.num_malicious, &config.network.latency)
+            .map_err(|diagnostic| vec![diagnostic])?;
+        let mut network = Network::with_malicious_nodes(
+            config.num_nodes,
+            malicious,
+            config.kind.clone(),
+            rng,
+            config.fault_model,
+        );
+        network.set_config(config.network.clone());
+        network.set_topology(config.topology);
+        network.set_routing(config.routing);
+        // Keys of the nodes were dealt with the network
+        let mut protocols = network.shared.config.write().unwrap();
+        *protocols = ProtocolConfig {
+            #[cfg(feature = "crypto")]
+            encryption: protocols.encryption.clone(),
+            #[cfg(feature = "crypto")]
+            signatures: protocols.signatures.clone(),
+            #[cfg(feature = "crypto")]
+            sharing: protocols.sharing.clone(),
+            ..config.protocols.clone()
+        };
+        drop(protocols);
+        Ok(network)
+    }
+
+    /// Create new network whose nodes draw their randomness from `rng`
+    /// Panics with inconsistent parameters, see `Config::validate`
+    pub fn with_rng(
+        num_nodes: usize,
+        num_malicious: usize,
+        kind: MaliciousKind,
+        rng: RngSource,
+    ) -> Self {
+        Network::with_fault_model(num_nodes, num_malicious, kind, rng, FaultModel::Byzantine)
+    }
+
+    /// Create new network whose malicious nodes fail within `fault_model`,
+    /// up to half of the nodes minus one can only crash
+    /// In hybrid models the first malicious nodes, up to the byzantine
+    /// budget, are of `kind`, the others are silent from the start
+    pub fn with_fault_model(
+        num_nodes: usize,
+        num_malicious: usize,
+        kind: MaliciousKind,
+        rng: RngSource,
+        fault_model: FaultModel,
+    ) -> Self {
+        let malicious = (num_nodes.saturating_sub(num_malicious)..num_nodes).collect();
+        Network::with_malicious_nodes(num_nodes, malicious, kind, rng, fault_model)
+    }
+
+    /// Create new network whose malicious nodes are `malicious`, see
+    /// `Placement` to choose them
+    /// In hybrid models the first ones, up to the byzantine budget, are of
+    /// `kind`, the others are silent from the start
+    pub fn with_malicious_nodes(
+        num_nodes: usize,
+        malicious: Vec<NodeId>,
+        kind: MaliciousKind,
+        rng: RngSource,
+        fault_model: FaultModel,
+    ) -> Self {
+        // Number of "bad" nodes shall be less than a third of the nodes, or
+        // half of them if they only crash
+        let num_malicious = malicious.len();
+        assert!(num_malicious <= fault_model.max_faulty(num_nodes));
+        assert!(malicious.iter().all(|id| *id < num_nodes), "{:?}", malicious);
 
-        let num_good = num_nodes - num_malicious;
         let mut nodes = HashMap::new();
         let (tx, network_rx): (Sender<NetworkMessage>, Receiver<NetworkMessage>) = channel();
+        let randomness = Arc::new(Mutex::new(Randomness::default()));
+        let shared = Shared {
+            fault_model,
+            ..Shared::default()
+        };
+        #[cfg(feature = "crypto")]
+        let shared = Network::deal_keys(shared, num_nodes);
 
+        let num_lying = match fault_model {
+            FaultModel::Hybrid { byzantine, .. } => byzantine,
+            _ => num_nodes,
+        };
         let mut good_nodes = vec![];
         let mut malicious_nodes = vec![];
+        let mut crashed_nodes = vec![];
         for id in 0..num_nodes {
             let (network_tx, rx): (Sender<NetworkMessage>, Receiver<NetworkMessage>) = channel();
             let mut neighbour_nodes = (0..num_nodes).collect::<Vec<NodeId>>();
             neighbour_nodes.remove(id);
-            let behaviour = if id < num_good {
-                good_nodes.push(id);
-                Behaviour::Good
-            } else {
-                malicious_nodes.push(id);
-                Behaviour::Malicious(kind.clone())
+            let behaviour = match malicious.iter().position(|bad| *bad == id) {
+                None => {
+                    good_nodes.push(id);
+                    Behaviour::Good
+                }
+                Some(rank) if rank < num_lying => {
+                    malicious_nodes.push(id);
+                    Behaviour::Malicious(kind.clone())
+                }
+                Some(_) => {
+                    crashed_nodes.push(id);
+                    Behaviour::Malicious(MaliciousKind::Silent)
+                }
             };
-            let node = Node::new(id, tx.clone(), rx, behaviour, neighbour_nodes);
+            let node_rng = rng.build(id, &randomness);
+            let node = Node::new(
+                id,
+                tx.clone(),
+                rx,
+                behaviour,
+                neighbour_nodes,
+                node_rng,
+                shared.clone(),
+            );
             nodes.insert(id, (node, network_tx));
         }
 
         let mut node_behaviours = HashMap::new();
         node_behaviours.insert(Behaviour::Good, good_nodes);
-        node_behaviours.insert(Behaviour::Malicious(kind), malicious_nodes);
+        node_behaviours.insert(Behaviour::Malicious(kind.clone()), malicious_nodes);
+        if !crashed_nodes.is_empty() {
+            node_behaviours
+                .entry(Behaviour::Malicious(MaliciousKind::Silent))
+                .or_insert_with(Vec::new)
+                .extend(crashed_nodes);
+        }
 
         Network {
             num_nodes,
+            num_malicious,
+            kind: kind.clone(),
+            fault_model,
             nodes,
             node_behaviours,
             rx: network_rx,
             time_limit: None,
+            termination: Termination::default(),
+            mitm: ManInTheMiddle::new(),
+            controller: NetworkController::new(),
+            link_security: LinkSecurity::default(),
+            monitors: Monitors::default(),
+            cost_model: CostModel::default(),
+            costs: BTreeMap::new(),
+            stats: NetworkStats::new(),
+            behaviour_stats: BehaviourStats::default(),
+            flood: FloodReport::default(),
+            influenced: BTreeSet::new(),
+            audit: AuditReport::default(),
+            panics: vec![],
+            amnesia: vec![],
+            sent: HashSet::new(),
+            departures: HashMap::new(),
+            stalled: BTreeSet::new(),
+            lifecycles: BTreeMap::new(),
+            ledger: Ledger::new(),
+            conservation: Conservation::default(),
+            lost: vec![],
+            logs: None,
+            artifacts: None,
+            dumped: None,
+            on_panic: PanicPolicy::default(),
+            runs: 0,
+            rng: rng.build(NETWORK_ID, &randomness),
+            rng_source: rng,
+            randomness,
+            schedule: Schedule::Concurrent,
+            channel: shared.resources.channel(),
+            shared,
+            pending: vec![],
+            trace: Trace::new(num_nodes, num_malicious, kind),
+            config: NetworkConfig::default(),
+            faults_from: 0,
+            delayed: BinaryHeap::new(),
+            num_delayed: 0,
+            uplinks: HashMap::new(),
+            peer_buckets: HashMap::new(),
+            cpus: HashMap::new(),
+            timers: vec![],
+            clock: time::Duration::ZERO,
         }
     }
 
-    pub fn bracha_broadcast(
+    /// Keys of the threshold schemes dealt to `num_nodes` nodes, any f + 1
+    /// nodes decrypt and sign, f as the nodes count it
+    #[cfg(feature = "crypto")]
+    fn deal_keys(shared: Shared, num_nodes: usize) -> Shared {
+        let (max_malicious_nodes, _) = thresholds(num_nodes, shared.fault_model);
+        let (scheme, key_shares) = SimulatedThreshold::deal(0, num_nodes, max_malicious_nodes + 1);
+        let (signatures, signing_keys) =
+            SimulatedSignatures::deal(0, num_nodes, max_malicious_nodes + 1);
+        let shared = Shared {
+            key_shares: Arc::new(key_shares),
+            signing_keys: Arc::new(signing_keys),
+            ..shared
+        };
+        let mut protocols = shared.config.write().unwrap();
+        protocols.encryption = EncryptionConfig {
+            scheme: Arc::new(scheme),
+        };
+        protocols.signatures = SignatureConfig {
+            scheme: Arc::new(signatures),
+        };
+        protocols.sharing = SharingConfig {
+            scheme: Arc::new(SimulatedSharing {
+                threshold: max_malicious_nodes + 1,
+            }),
+        };
+        drop(protocols);
+        shared
+    }
+
+    /// Create a network replaying the deliveries and random draws of `trace`
+    /// The same protocol has to be run with the same inputs
+    pub fn replay(trace: &Trace) -> Self {
+        let fault_model = trace
+            .manifest
+            .get("fault_model")
+            .and_then(|model| model.parse().ok())
+            .unwrap_or_default();
+        let mut network = Network::with_fault_model(
+            trace.num_nodes,
+            trace.num_malicious,
+            trace.kind.clone(),
+            RngSource::Replay(trace.randomness.clone()),
+            fault_model,
+        );
+        network.set_schedule(Schedule::Replay(trace.clone()));
+        network
+    }
+
+    /// Let a man-in-the-middle attack the link from `from` to `to`
+    pub fn compromise_link(&mut self, from: NodeId, to: NodeId, attack: LinkAttack) {
+        self.mitm.compromise(from, to, attack);
+    }
+
+    /// Enable authentication and/or encryption on all the links
+    pub fn set_link_security(&mut self, security: LinkSecurity) {
+        self.link_security = security;
+    }
+
+    /// Set the conditions of the links
+    pub fn set_config(&mut self, config: NetworkConfig) {
+        self.config = config;
+    }
+
+    /// Whether runs go on when a node panics
+    pub fn set_panic_policy(&mut self, policy: PanicPolicy) {
+        self.on_panic = policy;
+    }
+
+    /// Write the events of each node to its own file in `dir`, from the
+    /// next run on
+    pub fn log_nodes(&mut self, dir: &Path) -> Result<(), String> {
+        let nodes: Vec<NodeId> = (0..self.num_nodes).collect();
+        self.logs = Some(NodeLogs::create(dir, &nodes, &self.manifest())?);
+        Ok(())
+    }
+
+    /// Write the state, mailbox and last events of every node to `dir`
+    /// when a run ends abnormally, from the next run on, see `artifacts`
+    pub fn dump_artifacts(&mut self, dir: &Path) -> Result<(), String> {
+        self.artifacts = Some(Artifacts::create(dir)?);
+        Ok(())
+    }
+
+    /// Stop runs lasting longer than `limit`
+    pub fn set_time_limit(&mut self, limit: time::Duration) {
+        self.time_limit = Some(limit);
+    }
+
+    /// End runs once `termination` holds, without waiting for every good
+    /// node
+    pub fn set_termination(&mut self, termination: Termination) {
+        self.termination = termination;
+    }
+
+    /// Check `invariant` on what the honest nodes send and output from the
+    /// next run on, see `violations`
+    pub fn monitor(&mut self, invariant: Invariant) {
+        if !self.monitors.invariants.contains(&invariant) {
+            self.monitors.invariants.push(invariant);
+        }
+    }
+
+    /// End runs at the first violation of a monitored invariant, the nodes
+    /// still running are left idle until the next run
+    pub fn set_stop_on_violation(&mut self, stop: bool) {
+        self.monitors.stop = stop;
+    }
+
+    /// Create a network replaying `trace` without its delivery `omitted`
+    pub fn replay_without(trace: &Trace, omitted: usize) -> Self {
+        let mut network = Network::replay(trace);
+        network.set_schedule(Schedule::ReplayWithout {
+            trace: trace.clone(),
+            omitted,
+            dropped: false,
+            diverged: false,
+        });
+        network
+    }
+
+    /// Create a network restoring `snapshot` then continuing as `branch`
+    pub fn branch(snapshot: &Snapshot, branch: &Branch) -> Self {
+        let trace = &snapshot.trace;
+        let mut network = Network::replay(trace);
+        network.set_schedule(Schedule::Branch {
+            snapshot: trace.clone(),
+        });
+        // The scheduler of the branch makes its own choices
+        network.rng = Box::new(SeededRng::new(branch.seed, NETWORK_ID as u64));
+        network.set_config(branch.config.clone());
+        for (from, to, attack) in branch.attacks.iter() {
+            network.compromise_link(*from, *to, attack.clone());
+        }
+        // Messages sent while processing the deliveries of the snapshot are
+        // part of its state
+        network.faults_from = trace.len() + 1;
+        network
+    }
+
+    pub fn set_schedule(&mut self, schedule: Schedule) {
+        self.shared
+            .lockstep
+            .store(schedule.is_lockstep(), Ordering::SeqCst);
+        self.schedule = schedule;
+    }
+
+    /// Make a node switch protocol handler in the middle of the next runs,
+    /// e.g. to silently downgrade to an older release
+    pub fn switch_handler(&mut self, switch: HandlerSwitch) {
+        self.shared.switches.write().unwrap().push(switch);
+    }
+
+    /// Every node runs the current handlers again from the next run on
+    pub fn clear_switches(&mut self) {
+        self.shared.switches.write().unwrap().clear();
+    }
+
+    /// Run the release `tag` of `protocol` on `node` from the next run on,
+    /// to mix releases as in a rolling upgrade
+    pub fn set_version(
         &mut self,
-        v: Value,
-        leader_node: NodeId,
-    ) -> (bool, HashMap<NodeId, Value>) {
-        // Start a broadcast
-        if let Some((node, tx)) = self.nodes.get(&leader_node) {
-            let bc_msg = Message::BROADCAST(BroadcastMessage::BC_LEADER(v));
-            let msg = NetworkMessage::new(NETWORK_ID, node.id, bc_msg);
-            trace!("{:?}", msg);
-            tx.send(msg);
+        node: NodeId,
+        protocol: ProtocolId,
+        tag: &str,
+    ) -> Result<(), String> {
+        if !versions(protocol).contains(&tag) {
+            return Err(format!("Protocol {} has no version {}", protocol, tag));
         }
-        let results = self.run_network();
+        self.shared
+            .versions
+            .write()
+            .unwrap()
+            .insert((node, protocol), tag.to_string());
+        Ok(())
+    }
 
-        // Termination: all honnest nodes have terminated
-        let termination =
-            results.len() == self.node_behaviours.get(&Behaviour::Good).unwrap().len();
+    /// Make the malicious node `node` alternate correct and faulty phases
+    /// from the next run on, instead of being faulty from the start
+    pub fn set_fault_schedule(&mut self, node: NodeId, schedule: FaultSchedule) {
+        self.shared.faults.write().unwrap().insert(node, schedule);
+    }
 
-        // Agreement: all honnest nodes output the same value
-        let first = results.values().next().unwrap();
-        let agreement = results.values().all(|res| res == first);
+    /// Have `node` join the next runs late, see `LateJoin`
+    pub fn set_late_join(&mut self, node: NodeId, join: LateJoin) {
+        self.shared.joins.write().unwrap().insert(node, join);
+    }
 
-        // Validity: outputs of honnest nodes are equal to broadcasted value
-        let validity = agreement && *first == v;
+    /// Every node starts with the runs again
+    pub fn clear_late_joins(&mut self) {
+        self.shared.joins.write().unwrap().clear();
+    }
 
-        (termination && agreement && validity, results)
+    /// Parameters of the Bracha instances of the next runs
+    pub fn set_broadcast_config(&mut self, config: BroadcastConfig) {
+        self.shared.config.write().unwrap().broadcast = config;
     }
 
-    fn run_network(&mut self) -> HashMap<NodeId, Value> {
-        let mut good_running_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap().len();
-        let mut results = HashMap::new();
-        loop {
-            let network_msg = self.rx.recv().unwrap();
-            match network_msg.msg {
-                // Node has terminated and outputs v
-                END(v) => {
-                    let node_id = network_msg.from;
+    /// Parameters of the flooding instances of the next runs
+    pub fn set_flood_config(&mut self, config: FloodConfig) {
+        self.shared.config.write().unwrap().flood = config;
+    }
 
-                    // Store result of the node
-                    results.insert(node_id, v);
-
-                    let (node, _) = self
-                        .nodes
-                        .remove(&node_id)
-                        .expect("Can't terminate a node twice");
-
-                    node.thread
-                        .join()
-                        .expect(&format!("oops, thread {} panicked", node.id));
-
-                    if self
-                        .node_behaviours
-                        .get(&Behaviour::Good)
-                        .unwrap()
-                        .contains(&node_id)
-                    {
-                        // If a good node terminates
-                        good_running_nodes = good_running_nodes - 1;
-
-                        if good_running_nodes == 0 {
-                            // If there are no more good nodes
-                            // Send a termination message to all the bad nodes
-
-                            warn!(
-                                "Good nodes {:?} have terminated",
-                                self.node_behaviours.get(&Behaviour::Good).unwrap()
-                            );
-
-                            // Send termination message to all bad nodes
-                            for (node, tx) in self.nodes.values() {
-                                tx.send(NetworkMessage::new(NETWORK_ID, node.id, END(0)));
-                            }
-
-                            // Wait for the bad nodes to end
-                            for (node, _) in self.nodes.into_values() {
-                                node.thread.join().unwrap();
-                            }
-                            break;
-                        }
-                    }
-                }
+    /// Parameters of the Paxos instances of the next runs
+    pub fn set_paxos_config(&mut self, config: PaxosConfig) {
+        self.shared.config.write().unwrap().paxos = config;
+    }
 
-                // Relay message to destination node
-                _ => {
-                    trace!("{:?}", network_msg);
-                    if network_msg.to != NETWORK_ID {
-                        if let Some((_, tx)) = self.nodes.get(&network_msg.to) {
-                            // If the node is still up transmit the message
-                            tx.send(network_msg);
-                        } else {
-                            warn!("Destination node is down: {:?}", network_msg);
-                        }
-                    }
-                }
-            }
+    /// Parameters of the PBFT instances of the next runs
+    #[cfg(feature = "crypto")]
+    pub fn set_pbft_config(&mut self, config: PbftConfig) {
+        self.shared.config.write().unwrap().pbft = config;
+    }
+
+    /// Parameters of the replicated state machines of the next runs, the
+    /// number of commands is set by every run
+    pub fn set_smr_config(&mut self, config: SmrConfig) {
+        self.shared.config.write().unwrap().smr = config;
+    }
+
+    /// Parameters of the atomic broadcasts of the next runs, the epochs and
+    /// the queues are set by every run
+    #[cfg(feature = "crypto")]
+    pub fn set_honey_badger_config(&mut self, config: HoneyBadgerConfig) {
+        self.shared.config.write().unwrap().honey_badger = config;
+    }
+
+    /// Parameters of the graded broadcasts of the next runs, the leader is
+    /// set by every run
+    pub fn set_graded_config(&mut self, config: GradedConfig) {
+        self.shared.config.write().unwrap().graded = config;
+    }
+
+    /// Parameters of the terminating broadcasts of the next runs, the sender
+    /// is set by every run
+    pub fn set_terminating_config(&mut self, config: TerminatingConfig) {
+        self.shared.config.write().unwrap().terminating = config;
+    }
+
+    /// Parameters of the phase king agreements of the next runs
+    pub fn set_phase_king_config(&mut self, config: PhaseKingConfig) {
+        self.shared.config.write().unwrap().phase_king = config;
+    }
+
+    /// Parameters of the mempool gossip of the next runs, the pools are set
+    /// by every run
+    pub fn set_mempool_config(&mut self, config: MempoolConfig) {
+        self.shared.config.write().unwrap().mempool = config;
+    }
+
+    /// Coins tossed by the instances created from now on
+    pub fn set_coin_source<C: CoinSource + 'static>(&mut self, source: C) {
+        self.shared.config.write().unwrap().coin = CoinConfig {
+            source: Some(Arc::new(source)),
+        };
+    }
+
+    /// Validity views of the nodes for the instances created from now on
+    pub fn set_validity_oracle<O: ValidityOracle + 'static>(&mut self, oracle: O) {
+        self.shared.config.write().unwrap().validity = ValidityConfig {
+            oracle: Arc::new(oracle),
+        };
+    }
+
+    /// Inbound filter of the honest nodes from now on
+    pub fn set_inbound_filter<F: InboundFilter + 'static>(&mut self, filter: F) {
+        self.shared.config.write().unwrap().filter = FilterConfig {
+            filter: Arc::new(filter),
+        };
+    }
+
+    /// Prices of the resources in the cost reports from now on
+    pub fn set_cost_model(&mut self, model: CostModel) {
+        self.cost_model = model;
+    }
+
+    /// Links the nodes send messages on from the next run on, every pair of
+    /// nodes is linked by default
+    pub fn set_topology(&mut self, topology: Topology) {
+        *self.shared.topology.write().unwrap() = topology;
+    }
+
+    /// Public part of the signing key dealt to the nodes, to check the
+    /// signatures they combine
+    #[cfg(feature = "crypto")]
+    pub fn signatures(&self) -> SignatureConfig {
+        self.shared.config.read().unwrap().signatures.clone()
+    }
+
+    pub fn fault_model(&self) -> FaultModel {
+        self.fault_model
+    }
+
+    /// Thresholds the nodes derive from the number of nodes and the fault
+    /// model, with their justification
+    pub fn parameters(&self) -> Parameters {
+        Parameters::new(self.num_nodes, self.num_malicious, self.fault_model)
+    }
+
+    pub fn topology(&self) -> Topology {
+        *self.shared.topology.read().unwrap()
+    }
+
+    /// Let the nodes send to every other node from the next run on, nodes
+    /// relay the messages between nodes that are not linked along shortest
+    /// paths of the topology
+    /// Without routing nodes only send to the nodes they are linked to
+    pub fn set_routing(&mut self, routing: bool) {
+        self.shared.routing.store(routing, Ordering::SeqCst);
+    }
+
+    pub fn routing(&self) -> bool {
+        self.shared.routing.load(Ordering::SeqCst)
+    }
+
+    /// Let the nodes handle messages after they delivered from the next run
+    /// on, their instances keep relaying for the nodes lagging behind
+    /// Nodes stop as they deliver by default
+    pub fn set_after_delivery(&mut self, after: AfterDelivery) {
+        *self.shared.after_delivery.write().unwrap() = after;
+    }
+
+    /// Handle to drop, delay or force the delivery of messages, even while
+    /// a run is going on
+    pub fn controller(&self) -> NetworkController {
+        self.controller.clone()
+    }
+
+    /// What the requests of the controller know of the messages: every one
+    /// of them by default, only the messages of the malicious nodes with
+    /// local knowledge, see `Knowledge`
+    /// Malicious nodes are the ones at the time of the call
+    pub fn set_adversary_knowledge(&mut self, knowledge: Knowledge) {
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let malicious = (0..self.num_nodes)
+            .filter(|id| !good_nodes.contains(id))
+            .collect();
+        self.controller.set_knowledge(knowledge, malicious);
+    }
+
+    pub fn mitm(&self) -> &ManInTheMiddle {
+        &self.mitm
+    }
+
+    /// Random draws made during the last run, empty unless the source is
+    /// `RngSource::Recording`
+    pub fn randomness(&self) -> Randomness {
+        self.randomness.lock().unwrap().clone()
+    }
+
+    /// Per-phase traffic of the last run
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+
+    /// Build and full configuration of the network, embedded in the
+    /// artifacts of its runs
+    pub fn manifest(&self) -> Manifest {
+        let links: Vec<String> = self
+            .mitm
+            .links()
+            .iter()
+            .map(|((from, to), attack)| format!("{}->{} {:?}", from, to, attack))
+            .collect();
+        let mut manifest = Manifest::new()
+            .with("num_nodes", self.num_nodes)
+            .with("num_malicious", self.num_malicious)
+            .with("kind", format!("{:?}", self.kind))
+            .with("rng", self.rng_source.name())
+            .with("schedule", self.schedule.name())
+            .with("drop_rate", self.config.drop_rate)
+            .with("latency", &self.config.latency)
+            .with("jitter", format!("{:?}", self.config.jitter))
+            .with(
+                "bandwidth",
+                self.config
+                    .bandwidth
+                    .map_or(String::from("unlimited"), |bandwidth| bandwidth.to_string()),
+            )
+            .with(
+                "uplink",
+                self.config
+                    .uplink
+                    .map_or(String::from("unlimited"), |uplink| uplink.to_string()),
+            )
+            .with(
+                "cpu",
+                self.config
+                    .cpu
+                    .as_ref()
+                    .map_or(String::from("free"), |cpu| cpu.to_string()),
+            )
+            .with("authenticated", self.link_security.authenticated)
+            .with("encrypted", self.link_security.encrypted)
+            .with("cost_model", &self.cost_model)
+            .with("compromised_links", links.join(" | "))
+            .with("on_panic", format!("{:?}", self.on_panic));
+        let after_delivery = *self.shared.after_delivery.read().unwrap();
+        if after_delivery != AfterDelivery::Stop {
+            manifest.set("after_delivery", after_delivery.name());
         }
-        results
+        if !self.config.link_drop_rates.is_empty() {
+            let rates: Vec<String> = self
+                .config
+                .link_drop_rates
+                .iter()
+                .map(|((from, to), rate)| format!("{}->{} {}", from, to, rate))
+                .collect();
+            manifest.set("link_drop_rates", rates.join(" | "));
+        }
+        if let Some(limit) = self.time_limit {
+            manifest.set("time_limit", format!("{:?}", limit));
+        }
+        if !matches!(self.termination, Termination::AllGood) {
+            manifest.set("termination", self.termination);
+        }
+        if !self.monitors.invariants.is_empty() {
+            let invariants: Vec<String> = self
+                .monitors
+                .invariants
+                .iter()
+                .map(Invariant::to_string)
+                .collect();
+            manifest.set("monitors", invariants.join(" "));
+            manifest.set("stop_on_violation", self.monitors.stop);
+        }
+        let switches: Vec<String> = self
+            .shared
+            .switches
+            .read()
+            .unwrap()
+            .iter()
+            .map(|s| format!("{} {}:{:?} after {}", s.node, s.protocol, s.handler, s.after))
+            .collect();
+        if !switches.is_empty() {
+            manifest.set("handler_switches", switches.join(" | "));
+        }
+        let versions: Vec<String> = self
+            .shared
+            .versions
+            .read()
+            .unwrap()
+            .iter()
+            .map(|((node, protocol), tag)| format!("{} {}:{}", node, protocol, tag))
+            .collect();
+        if !versions.is_empty() {
+            manifest.set("versions", versions.join(" | "));
+        }
+        if self.fault_model != FaultModel::Byzantine {
+            manifest.set("fault_model", self.fault_model);
+        }
+        let topology = self.topology();
+        if topology != Topology::Complete {
+            manifest.set("topology", topology);
+        }
+        if self.routing() {
+            manifest.set("routing", true);
+        }
+        let flood = self.shared.config.read().unwrap().flood.clone();
+        if flood != FloodConfig::default() {
+            manifest.set(
+                "flood",
+                format!(
+                    "ttl {} suppress_duplicates {}",
+                    flood
+                        .ttl
+                        .map_or(String::from("none"), |ttl| ttl.to_string()),
+                    flood.suppress_duplicates
+                ),
+            );
+        }
+        let coin = self.shared.config.read().unwrap().coin.clone();
+        if let Some(source) = coin.source {
+            manifest.set("coin", source);
+        }
+        let validity = self.shared.config.read().unwrap().validity.clone();
+        if validity != ValidityConfig::default() {
+            manifest.set("validity", validity.oracle);
+        }
+        if let Some(limit) = self.config.peer_limit {
+            manifest.set("peer_limit", limit);
+        }
+        let knowledge = self.controller.knowledge();
+        if knowledge != Knowledge::default() {
+            manifest.set("knowledge", knowledge);
+        }
+        let filter = self.shared.config.read().unwrap().filter.clone();
+        if filter != FilterConfig::default() {
+            manifest.set("filter", filter.filter);
+        }
+        let paxos = self.shared.config.read().unwrap().paxos.clone();
+        if paxos != PaxosConfig::default() {
+            manifest.set(
+                "paxos",
+                format!(
+                    "leader_election {} election_timeout {:?}",
+                    paxos.leader_election, paxos.election_timeout
+                ),
+            );
+        }
+        #[cfg(feature = "crypto")]
+        if self.shared.config.read().unwrap().honey_badger.encrypted {
+            manifest.set("honey_badger", "encrypted");
+        }
+        let mempool = self.shared.config.read().unwrap().mempool.clone();
+        if mempool != MempoolConfig::default() {
+            manifest.set(
+                "mempool",
+                format!(
+                    "{} tx_size {} rounds {} period {:?}",
+                    mempool.sync, mempool.tx_size, mempool.rounds, mempool.period
+                ),
+            );
+        }
+        let smr = self.shared.config.read().unwrap().smr.clone();
+        if smr.speculative {
+            manifest.set("smr", "speculative");
+        }
+        if smr.reads > 0 {
+            manifest.set(
+                "smr_reads",
+                format!("{} {} every {:?}", smr.reads, smr.consistency, smr.read_period),
+            );
+        }
+        if let Some(client) = &smr.client {
+            manifest.set(
+                "smr_client",
+                format!("timeout {:?}, {} retries", client.timeout, client.retries),
+            );
+        }
+        if let Some(load) = &smr.load {
+            manifest.set("smr_load", format!("{} x {}", load.commands, load.arrivals));
+        }
+        if let Some(mempool) = smr.mempool {
+            manifest.set("smr_mempool", mempool);
+        }
+        let faults: Vec<String> = self
+            .shared
+            .faults
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(node, schedule)| format!("{} {}", node, schedule))
+            .collect();
+        if !faults.is_empty() {
+            manifest.set("fault_schedules", faults.join(" | "));
+        }
+        let joins: Vec<String> = self
+            .shared
+            .joins
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(node, join)| format!("{} {}", node, join))
+            .collect();
+        if !joins.is_empty() {
+            manifest.set("late_joins", joins.join(" | "));
+        }
+        manifest.seed = self.rng_source.seed();
+        manifest
+    }
+
+    /// Traffic of the last run as CSV
+    pub fn stats_csv(&self) -> String {
+        self.stats.csv(&self.trace.manifest)
+    }
+
+    /// Protocol milestones of every node during the last run as CSV
+    pub fn milestones_csv(&self) -> String {
+        self.stats.milestones_csv(&self.trace.manifest)
+    }
+
+    /// Fate of every message routed during the last run
+    pub fn ledger(&self) -> &Ledger {
+        &self.ledger
+    }
+
+    /// Balance of the messages of the last run
+    pub fn conservation(&self) -> &Conservation {
+        &self.conservation
+    }
+
+    /// Nodes that follow the protocols, in increasing ids
+    pub fn good_nodes(&self) -> &[NodeId] {
+        &self.node_behaviours[&Behaviour::Good]
+    }
+
+    /// Nodes that don't, whatever their kind
+    pub fn malicious_nodes(&self) -> BTreeSet<NodeId> {
+        let good = self.good_nodes();
+        (0..self.num_nodes).filter(|id| !good.contains(id)).collect()
+    }
+
+    /// Traffic and outputs of the last run per behaviour class
+    pub fn behaviour_stats(&self) -> &BehaviourStats {
+        &self.behaviour_stats
+    }
+
+    /// Copies, coverage and paths of the last flooding run
+    pub fn flood_report(&self) -> &FloodReport {
+        &self.flood
+    }
+
+    /// Cross-check of the final states of the honest nodes of the last run
+    pub fn audit(&self) -> &AuditReport {
+        &self.audit
+    }
+
+    /// Directory of the post-mortem of the last run, if it ended abnormally
+    /// and artifacts are dumped
+    pub fn artifacts(&self) -> Option<&Path> {
+        self.dumped.as_deref()
+    }
+
+    /// Panics of the nodes during the last run, these nodes are crashed
+    /// and have no output
+    pub fn panics(&self) -> &[NodePanic] {
+        &self.panics
+    }
+
+    /// Nodes that crashed and recovered without their protocol state during
+    /// the last run, with what went wrong because of it
+    pub fn amnesia(&self) -> &[AmnesiaReport] {
+        &self.amnesia
+    }
+
+    /// Nodes that saw no progress for their liveness timeout during the
+    /// last run and gave up instead of waiting forever
+    pub fn stalled(&self) -> &BTreeSet<NodeId> {
+        &self.stalled
+    }
+
+    /// Whether every node delivered and whether it still took part in the
+    /// protocol when the last run ended
+    pub fn lifecycles(&self) -> &BTreeMap<NodeId, Lifecycle> {
+        &self.lifecycles
+    }
+
+    /// Violations of the monitored invariants during the last run, in the
+    /// order they occurred
+    pub fn violations(&self) -> &[Violation] {
+        &self.monitors.violations
     }
 
-    pub fn close(self) 
+    /// Counter `name` of every node that counted it during the last run
+    pub fn counter(&self, name: &str) -> BTreeMap<NodeId, usize> {
+        self.shared
+            .counters
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|((_, counter), _)| *counter == name)
+            .map(|((id, _), k)| (*id, *k))
+            .collect()
+    }
+
+    /// Samples of `name` taken during the last run, with the node that took
+    /// them, in the order they were taken
+    pub fn samples(&self, name: &str) -> Vec<(NodeId, usize)> {
+        self.shared
+            .samples
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(_, sample, _)| *sample == name)
+            .map(|(id, _, value)| (*id, *value))
+            .collect()
+    }
+
+    /// Messages, bytes and cryptographic operations of every protocol during
+    /// the last run, priced by the cost model
+    /// Secured links cost an operation when the message is sent and one
+    /// when it is received
+    pub fn cost_report(&self) -> CostReport {
+        let mut protocols = self.costs.clone();
+        for ((protocol, op), k) in self.shared.ops.read().unwrap().iter() {
+            *protocols
+                .entry(*protocol)
+                .or_default()
+                .ops
+                .entry(*op)
+                .or_default() += k;
+        }
+        CostReport {
+            model: self.cost_model.clone(),
+            protocols,
+        }
+    }
+
+    /// Nodes whose thread died, they are left out of the runs from then on
+    pub fn lost_nodes(&self) -> &[NodeId] {
+        &self.lost
+    }
+
+    /// Deliveries and random draws of the last run
+    pub fn trace(&self) -> &Trace {
+        &self.trace
+    }
+
+    /// Split of the delivery latency of the honest nodes of the last run
+    /// between waiting for quorums and the network
+    pub fn delivery_breakdown(&self) -> BTreeMap<NodeId, DeliveryBreakdown> {
+        let delivered = self
+            .stats
+            .milestone(Milestone::Delivered)
+            .cloned()
+            .unwrap_or_default();
+        delivery_breakdown(&self.trace, &delivered)
+    }
+
+    pub fn bracha_broadcast(
+        &mut self,
+        v: Value,
+        leader_node: NodeId,
+    ) -> (bool, HashMap<NodeId, Value>) {
+        let outputs = self.broadcast_outputs(v, leader_node);
+        self.check_broadcast(v, outputs)
+    }
+
+    /// Outputs of the nodes of a Bracha broadcast of `v` by `leader_node`
+    fn broadcast_outputs(&mut self, v: Value, leader_node: NodeId) -> HashMap<NodeId, Output> {
+        self.start_run();
+
+        // Start a broadcast
+        let bc_msg = Message::protocol(BroadcastMessage::BC_LEADER(v));
+        self.inject(NetworkMessage::new(NETWORK_ID, leader_node, bc_msg));
+        // Bracha broadcast has no liveness when the leader fails, nodes only
+        // terminate if they check on their progress
+        let (timeout, catch_up) = {
+            let config = self.shared.config.read().unwrap();
+            (config.broadcast.liveness_timeout, config.broadcast.catch_up)
+        };
+        if let Some(timeout) = timeout {
+            for id in 0..self.num_nodes {
+                let check = Message::protocol(LivenessCheck);
+                self.schedule_timer(NetworkMessage::new(id, id, check), timeout);
+            }
+        }
+        // Nodes lagging behind ask for the READY they lost
+        if let Some(period) = catch_up {
+            for id in 0..self.num_nodes {
+                let request = Message::protocol(StateRequest);
+                self.schedule_timer(NetworkMessage::new(id, id, request), period);
+            }
+        }
+        self.run_network()
+    }
+
+    /// Values the broadcast delivered, and whether every honest node
+    /// delivered `v`
+    fn check_broadcast(
+        &self,
+        v: Value,
+        outputs: HashMap<NodeId, Output>,
+    ) -> (bool, HashMap<NodeId, Value>) {
+        let results: HashMap<NodeId, Value> = outputs
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+
+        // Termination: all honnest nodes have terminated, faulty nodes may
+        // deliver too
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let honest: Vec<&Value> = good_nodes.iter().filter_map(|id| results.get(id)).collect();
+        let termination = honest.len() == good_nodes.len();
+
+        // Agreement: all honnest nodes output the same value
+        let first = honest.first().cloned();
+        let agreement = honest.iter().all(|res| Some(*res) == first);
+
+        // Validity: outputs of honnest nodes are equal to broadcasted value
+        let validity = agreement && first == Some(&v);
+
+        (termination && agreement && validity, results)
+    }
+
+    /// Bracha broadcast with signed READY, along with the certificates the
+    /// nodes delivered with, faulty nodes included
+    /// Fails unless the certificate of every honest node proves `v`
+    #[cfg(feature = "crypto")]
+    pub fn certified_broadcast(
+        &mut self,
+        v: Value,
+        leader_node: NodeId,
+    ) -> (bool, HashMap<NodeId, Value>, HashMap<NodeId, DeliveryCertificate>) {
+        let signed = self.shared.config.read().unwrap().broadcast.signed;
+        self.shared.config.write().unwrap().broadcast.signed = true;
+        let outputs = self.broadcast_outputs(v, leader_node);
+        self.shared.config.write().unwrap().broadcast.signed = signed;
+
+        let certificates: HashMap<NodeId, DeliveryCertificate> = outputs
+            .iter()
+            .filter_map(|(id, output)| output.certificate().map(|c| (*id, c.clone())))
+            .collect();
+        let (success, results) = self.check_broadcast(v, outputs);
+        let quorum = self.parameters().get("min_honnest_nodes").unwrap();
+        let signatures = self.signatures();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let certified = good_nodes.iter().all(|id| {
+            certificates
+                .get(id)
+                .is_some_and(|c| c.value == v && c.verify(&signatures, quorum))
+        });
+        (success && certified, results, certificates)
+    }
+
+    /// Bracha broadcast where every message is signed by its sender, honest
+    /// nodes drop the ones whose `from` was forged by the network or by
+    /// faulty nodes
+    #[cfg(feature = "crypto")]
+    pub fn authenticated_broadcast(
+        &mut self,
+        v: Value,
+        leader_node: NodeId,
+    ) -> (bool, HashMap<NodeId, Value>) {
+        let authenticated = self.shared.config.read().unwrap().broadcast.authenticated;
+        self.shared.config.write().unwrap().broadcast.authenticated = true;
+        let result = self.bracha_broadcast(v, leader_node);
+        self.shared.config.write().unwrap().broadcast.authenticated = authenticated;
+        result
+    }
+
+    /// Consistent broadcast of `v` by `leader_node`: honest nodes that
+    /// deliver agree, some of them may never deliver when the leader is
+    /// faulty
+    /// Succeeds when every honest node delivers `v`, as Bracha broadcast
+    pub fn consistent_broadcast(
+        &mut self,
+        v: Value,
+        leader_node: NodeId,
+    ) -> (bool, HashMap<NodeId, Value>) {
+        let inputs = HashMap::from([(leader_node, v)]);
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(CONSISTENT_BROADCAST, &inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let success = good_nodes.iter().all(|id| results.get(id) == Some(&v));
+        (success, results)
+    }
+
+    /// Graded broadcast of `v` by `leader_node`, every node outputs a value
+    /// with its grade
+    /// Succeeds when the grades of the honest nodes are within one of each
+    /// other, those above 0 on the same value, and every honest node grades
+    /// 2 the value of an honest leader
+    pub fn graded_broadcast(
+        &mut self,
+        v: Value,
+        leader_node: NodeId,
+    ) -> (bool, HashMap<NodeId, (Option<Value>, Grade)>) {
+        self.shared.config.write().unwrap().graded.leader = leader_node;
+        // Every node starts its timer, only the leader sends its input
+        let inputs = (0..self.num_nodes).map(|id| (id, v)).collect();
+        let results: HashMap<NodeId, (Option<Value>, Grade)> = self
+            .run_protocol(GRADED_BROADCAST, &inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.graded().map(|graded| (id, graded)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let honest: Vec<&(Option<Value>, Grade)> =
+            good_nodes.iter().filter_map(|id| results.get(id)).collect();
+        let termination = honest.len() == good_nodes.len();
+        let grades = honest.iter().map(|(_, grade)| *grade);
+        let spread = grades.clone().max().unwrap_or(0) - grades.min().unwrap_or(0);
+        let values: BTreeSet<Value> = honest.iter().filter_map(|(value, _)| *value).collect();
+        let consistency = spread <= 1 && values.len() <= 1;
+        let validity = !good_nodes.contains(&leader_node)
+            || honest.iter().all(|graded| **graded == (Some(v), 2));
+        (termination && consistency && validity, results)
+    }
+
+    /// Phase king agreement on the inputs, in synchronous rounds
+    /// Succeeds when every honest node decides the same value, the input of
+    /// the honest nodes if they all have the same
+    /// It tolerates f faulty nodes for n > 4f, see `phase_king_faults`
+    pub fn phase_king(
+        &mut self,
+        inputs: &HashMap<NodeId, Value>,
+    ) -> (bool, HashMap<NodeId, Value>) {
+        let tolerated = phase_king_faults(self.num_nodes);
+        if self.num_malicious > tolerated {
+            warn!(
+                "Phase king tolerates {} faulty nodes out of {}, not {}",
+                tolerated, self.num_nodes, self.num_malicious
+            );
+        }
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(PHASE_KING, inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes decide after f + 1 phases
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+
+        // Agreement: all honnest nodes decide the same value
+        let decided = good_nodes.first().and_then(|id| results.get(id));
+        let agreement = good_nodes.iter().all(|id| results.get(id) == decided);
+
+        // Validity: honnest nodes with the same input decide it
+        let honest: BTreeSet<Option<&Value>> =
+            good_nodes.iter().map(|id| inputs.get(id)).collect();
+        let validity = honest.len() > 1 || honest.into_iter().next().flatten() == decided;
+
+        (termination && agreement && validity, results)
+    }
+
+    /// Terminating reliable broadcast of `v` by `sender`, every node outputs
+    /// the value of the sender or None, the bottom value, when it votes the
+    /// sender didn't deliver
+    /// Succeeds when every honest node terminates on the same output, the
+    /// value of the sender if it is honest
+    pub fn terminating_broadcast(
+        &mut self,
+        v: Value,
+        sender: NodeId,
+    ) -> (bool, HashMap<NodeId, Option<Value>>) {
+        self.shared.config.write().unwrap().terminating.sender = sender;
+        // Every node starts its timer, only the sender sends its input
+        let inputs = (0..self.num_nodes).map(|id| (id, v)).collect();
+        let results: HashMap<NodeId, Option<Value>> = self
+            .run_protocol(TERMINATING_BROADCAST, &inputs)
+            .into_iter()
+            .filter_map(|(id, output)| match output {
+                Output::Value(v) => Some((id, Some(v))),
+                Output::Bottom => Some((id, None)),
+                _ => None,
+            })
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+        let output = good_nodes.first().and_then(|id| results.get(id));
+        let agreement = good_nodes.iter().all(|id| results.get(id) == output);
+        let validity = !good_nodes.contains(&sender) || output == Some(&Some(v));
+        (termination && agreement && validity, results)
+    }
+
+    /// Leader election with the coins of the nodes, every node takes part
+    /// Succeeds when every honest node elects the same leader, the coins
+    /// elect another one with another coin source, see
+    /// `Network::set_coin_source`
+    pub fn leader_election(&mut self) -> (bool, HashMap<NodeId, NodeId>) {
+        let inputs = (0..self.num_nodes).map(|id| (id, 0)).collect();
+        let results: HashMap<NodeId, NodeId> = self
+            .run_protocol(LEADER_ELECTION, &inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|leader| (id, leader)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let leaders: BTreeSet<NodeId> = good_nodes
+            .iter()
+            .filter_map(|id| results.get(id).copied())
+            .collect();
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+        (termination && leaders.len() == 1, results)
+    }
+
+    /// Reliable broadcast of the crash model, nodes deliver the value of
+    /// the leader once a majority acknowledged it
+    /// It tolerates up to half of the nodes minus one crashing, see
+    /// `Network::with_fault_model`
+    pub fn majority_broadcast(
+        &mut self,
+        v: Value,
+        leader_node: NodeId,
+    ) -> (bool, HashMap<NodeId, Value>) {
+        let inputs = HashMap::from([(leader_node, v)]);
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(MAJORITY_BROADCAST, &inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+
+        // Termination, agreement and validity at once: every correct node
+        // delivers the value of the leader
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let success = good_nodes.iter().all(|id| results.get(id) == Some(&v));
+        (success, results)
+    }
+
+    /// Consensus of the crash model: nodes of `inputs` propose their value,
+    /// every node accepts and learns
+    pub fn paxos(&mut self, inputs: &HashMap<NodeId, Value>) -> (bool, HashMap<NodeId, Value>) {
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(PAXOS, inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+
+        // Every correct node decides, on the same value, which some node
+        // proposed
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let decided = good_nodes.first().and_then(|id| results.get(id));
+        let success = decided.is_some_and(|v| inputs.values().any(|input| input == v))
+            && good_nodes.iter().all(|id| results.get(id) == decided);
+        (success, results)
+    }
+
+    /// Leader-based consensus of the partially synchronous model, the
+    /// primary of every view proposes its input, see `counter("view_changes")`
+    /// Succeeds when every honest node decides the same value, faulty
+    /// primaries may have it be any value
+    #[cfg(feature = "crypto")]
+    pub fn pbft(&mut self, inputs: &HashMap<NodeId, Value>) -> (bool, HashMap<NodeId, Value>) {
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(PBFT, inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have decided
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+
+        // Agreement: all honnest nodes decide the same value
+        let decided = good_nodes.first().and_then(|id| results.get(id));
+        let agreement = good_nodes.iter().all(|id| results.get(id) == decided);
+
+        (termination && agreement, results)
+    }
+
+    /// Randomized binary agreement of Ben-Or, any input but 0 is a vote
+    /// for 1, it needs more than 5 times as many nodes as faulty ones
+    /// Succeeds when every honest node decides the same value, the input
+    /// of all of them if they share it, see `counter("rounds")`
+    pub fn binary_agreement(
+        &mut self,
+        inputs: &HashMap<NodeId, Value>,
+    ) -> (bool, HashMap<NodeId, Value>) {
+        if self.num_malicious > (self.num_nodes - 1) / 5 {
+            warn!(
+                "Ben-Or agreement tolerates {} faulty nodes out of {}, not {}",
+                (self.num_nodes - 1) / 5,
+                self.num_nodes,
+                self.num_malicious
+            );
+        }
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(BEN_OR, inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have decided
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+
+        // Agreement: all honnest nodes decide the same value
+        let decided = good_nodes.first().and_then(|id| results.get(id));
+        let agreement = good_nodes.iter().all(|id| results.get(id) == decided);
+
+        // Validity: the value is the input of some honnest node
+        let votes: Vec<Value> = good_nodes
+            .iter()
+            .filter_map(|id| inputs.get(id).map(|input| (*input != 0) as Value))
+            .collect();
+        let validity = decided.is_some_and(|v| votes.contains(v));
+
+        (termination && agreement && validity, results)
+    }
+
+    /// Binary agreement of Mostéfaoui, Moumen and Raynal, driven by the
+    /// common coin
+    /// Succeeds when every honest node decides the same value, the input
+    /// of one of them, also returns the most rounds an honest node went
+    /// through
+    pub fn aba(
+        &mut self,
+        inputs: &HashMap<NodeId, bool>,
+    ) -> (bool, HashMap<NodeId, bool>, usize) {
+        let votes: HashMap<NodeId, Value> =
+            inputs.iter().map(|(id, b)| (*id, *b as Value)).collect();
+        let decisions: HashMap<NodeId, bool> = self
+            .run_protocol(BINARY_AGREEMENT, &votes)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v != 0)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have decided
+        let termination = good_nodes.iter().all(|id| decisions.contains_key(id));
+
+        // Agreement: all honnest nodes decide the same value
+        let decided = good_nodes.first().and_then(|id| decisions.get(id));
+        let agreement = good_nodes.iter().all(|id| decisions.get(id) == decided);
+
+        // Validity: the value is the input of some honnest node
+        let validity =
+            decided.is_some_and(|b| good_nodes.iter().any(|id| inputs.get(id) == Some(b)));
+
+        let rounds = self
+            .counter("rounds")
+            .into_iter()
+            .filter(|(id, _)| good_nodes.contains(id))
+            .map(|(_, rounds)| rounds)
+            .max()
+            .unwrap_or(0);
+        (termination && agreement && validity, decisions, rounds)
+    }
+
+    /// Replicated state machine: every good node of `inputs` submits its
+    /// command, or the commands of its load generator, malicious nodes
+    /// submit none
+    /// Replicas output their state once every command is committed, see
+    /// `counter` for the rollbacks of speculative runs and `samples` for
+    /// the latency clients see
+    pub fn smr(&mut self, inputs: &HashMap<NodeId, Value>) -> (bool, HashMap<NodeId, Value>) {
+        let inputs: HashMap<NodeId, Value> = inputs
+            .iter()
+            .filter(|(id, _)| self.good_nodes().contains(id))
+            .map(|(id, command)| (*id, *command))
+            .collect();
+        let mut config = self.shared.config.write().unwrap();
+        let per_client = config.smr.load.as_ref().map_or(1, |load| load.commands);
+        config.smr.commands = inputs.len() * per_client;
+        drop(config);
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(SMR, &inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+
+        // Every correct replica ends in the same state, the one of some
+        // order of the commands
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let state = good_nodes.first().and_then(|id| results.get(id));
+        let success = state.is_some() && good_nodes.iter().all(|id| results.get(id) == state);
+        (success, results)
+    }
+
+    /// Reliable broadcast of a whole batch in a single instance
+    pub fn batched_broadcast(
+        &mut self,
+        values: &[Value],
+        leader_node: NodeId,
+    ) -> (bool, HashMap<NodeId, Vec<Value>>) {
+        self.start_run();
+        let bb_msg = Message::protocol(BatchMessage::BB_LEADER(values.to_vec()));
+        self.inject(NetworkMessage::new(NETWORK_ID, leader_node, bb_msg));
+        let results: HashMap<NodeId, Vec<Value>> = self
+            .run_network()
+            .into_iter()
+            .filter_map(|(id, output)| match output {
+                Output::Sequence(batch) => Some((id, batch)),
+                _ => None,
+            })
+            .collect();
+
+        // Termination, agreement and validity at once: every honnest node
+        // delivers the batch of the leader
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let success = results.len() == good_nodes.len()
+            && results.values().all(|batch| batch.as_slice() == values);
+        (success, results)
+    }
+
+    /// Every node reliably broadcasts its input, nodes output the inputs of
+    /// at least `num_nodes - num_malicious` of them
+    /// Nodes may output different sets, only the values are checked
+    pub fn parallel_broadcast(
+        &mut self,
+        inputs: &HashMap<NodeId, Value>,
+    ) -> (bool, HashMap<NodeId, Output>) {
+        let results = self.run_protocol(PARALLEL_BROADCAST, inputs);
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have terminated
+        let termination = results.len() == good_nodes.len();
+
+        // Validity: output values of honnest senders are their inputs
+        let validity = results.values().all(|output| match output {
+            Output::Set(values) => {
+                values.len() >= self.num_nodes - self.num_malicious
+                    && values
+                        .iter()
+                        .filter(|(id, _)| good_nodes.contains(id))
+                        .all(|(id, v)| inputs.get(id) == Some(v))
+            }
+            _ => false,
+        });
+
+        (termination && validity, results)
+    }
+
+    /// Every node reliably broadcasts its input and the nodes agree on the
+    /// broadcasts kept, unlike Bracha broadcast it terminates whatever the
+    /// faulty nodes do
+    pub fn common_subset(
+        &mut self,
+        inputs: &HashMap<NodeId, Value>,
+    ) -> (bool, HashMap<NodeId, Output>) {
+        let results = self.run_protocol(COMMON_SUBSET, inputs);
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have terminated
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+
+        // Agreement: all honnest nodes output the same set
+        let honest: Vec<&Output> = good_nodes.iter().filter_map(|id| results.get(id)).collect();
+        let agreement = honest.iter().all(|output| Some(output) == honest.first());
+
+        // Validity: the set holds enough values, those of honnest senders
+        // are their inputs
+        // Honnest senders whose input some honnest node rejects may be left
+        // out, but every value of the set is valid to some honnest node
+        let oracle = self.shared.config.read().unwrap().validity.oracle.clone();
+        let valid = |v: &Value| good_nodes.iter().any(|id| oracle.is_valid(*id, *v));
+        let disputed = good_nodes
+            .iter()
+            .filter_map(|id| inputs.get(id))
+            .filter(|v| good_nodes.iter().any(|id| !oracle.is_valid(*id, **v)))
+            .count();
+        let validity = honest.iter().all(|output| match output {
+            Output::Set(values) => {
+                values.len() + disputed >= self.num_nodes - self.num_malicious
+                    && values.values().all(valid)
+                    && values
+                        .iter()
+                        .filter(|(id, _)| good_nodes.contains(id))
+                        .all(|(id, v)| inputs.get(id) == Some(v))
+            }
+            _ => false,
+        });
+
+        (termination && agreement && validity, results)
+    }
+
+    /// Verifiable sharing of `secret` by `dealer`, reconstructed by every
+    /// node once the sharing completes
+    /// Succeeds when every honest node outputs the same secret, that of the
+    /// dealer if it is honest
+    #[cfg(feature = "crypto")]
+    pub fn avss(&mut self, secret: Value, dealer: NodeId) -> (bool, HashMap<NodeId, Value>) {
+        self.shared.config.write().unwrap().avss = AvssConfig { dealer };
+        let inputs = HashMap::from([(dealer, secret)]);
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(AVSS, &inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let reconstructed = good_nodes.first().and_then(|id| results.get(id));
+        let agreement = good_nodes.iter().all(|id| results.get(id) == reconstructed);
+        let validity = !good_nodes.contains(&dealer) || reconstructed == Some(&secret);
+        (reconstructed.is_some() && agreement && validity, results)
+    }
+
+    /// Multi-valued agreement on the input of one of the nodes, under the
+    /// validity oracle of the network
+    /// Succeeds when every honest node decides the same value, the input
+    /// of some node valid to some honest node
+    pub fn mvba(&mut self, inputs: &HashMap<NodeId, Value>) -> (bool, HashMap<NodeId, Value>) {
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(MVBA, inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have decided
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+
+        // Agreement: all honnest nodes decide the same value
+        let decided = good_nodes.first().and_then(|id| results.get(id));
+        let agreement = good_nodes.iter().all(|id| results.get(id) == decided);
+
+        // External validity: the value was proposed and some honnest node
+        // accepts it
+        let oracle = self.shared.config.read().unwrap().validity.oracle.clone();
+        let validity = decided.is_some_and(|v| {
+            inputs.values().any(|input| input == v)
+                && good_nodes.iter().any(|id| oracle.is_valid(*id, *v))
+        });
+
+        (termination && agreement && validity, results)
+    }
+
+    /// Validated asynchronous agreement on a value of `validity_predicate`,
+    /// which every node checks, see `protocols::vaba`
+    /// The inputs of the honest nodes are expected to be valid, faulty
+    /// nodes may propose any value
+    /// Succeeds when every honest node decides the same valid value
+    #[cfg(feature = "crypto")]
+    pub fn vaba<P>(
+        &mut self,
+        inputs: &HashMap<NodeId, Value>,
+        validity_predicate: P,
+    ) -> (bool, HashMap<NodeId, Value>)
+    where
+        P: Fn(Value) -> bool + Send + Sync + 'static,
+    {
+        let predicate = Arc::new(validity_predicate);
+        let validity = self.shared.config.read().unwrap().validity.clone();
+        self.shared.config.write().unwrap().validity = ValidityConfig {
+            oracle: Arc::new(Predicate(predicate.clone())),
+        };
+        let results: HashMap<NodeId, Value> = self
+            .run_protocol(VABA, inputs)
+            .into_iter()
+            .filter_map(|(id, output)| output.value().map(|v| (id, v)))
+            .collect();
+        self.shared.config.write().unwrap().validity = validity;
+
+        let good_nodes = self.good_nodes();
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+        let decided = good_nodes.first().and_then(|id| results.get(id));
+        let agreement = good_nodes.iter().all(|id| results.get(id) == decided);
+        let validity = decided.is_some_and(|v| predicate(*v));
+        (termination && agreement && validity, results)
+    }
+
+    /// Atomic broadcast of the transactions queued by the nodes over
+    /// `epochs` epochs, nodes output the batches they committed one after
+    /// the other
+    /// Queues are expected to be disjoint for the progress check
+    #[cfg(feature = "crypto")]
+    pub fn honey_badger(
+        &mut self,
+        epochs: u32,
+        inputs: &HashMap<NodeId, Vec<Value>>,
+    ) -> (bool, HashMap<NodeId, Vec<Value>>) {
+        let mut config = self.shared.config.write().unwrap();
+        config.honey_badger.epochs = epochs;
+        config.honey_badger.queues = inputs.iter().map(|(id, q)| (*id, q.clone())).collect();
+        drop(config);
+        let starts: HashMap<NodeId, Value> = inputs.keys().map(|id| (*id, 0)).collect();
+        let results: HashMap<NodeId, Vec<Value>> = self
+            .run_protocol(HONEY_BADGER, &starts)
+            .into_iter()
+            .filter_map(|(id, output)| match output {
+                Output::Sequence(committed) => Some((id, committed)),
+                _ => None,
+            })
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have committed every epoch
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+
+        // Total order: all honnest nodes commit the same transactions in the
+        // same order, each of them once
+        let order = good_nodes.first().and_then(|id| results.get(id));
+        let total_order = good_nodes.iter().all(|id| results.get(id) == order);
+        let once = results.values().all(|committed| {
+            let distinct: HashSet<&Value> = committed.iter().collect();
+            distinct.len() == committed.len()
+        });
+
+        // Progress: every epoch commits the proposals of at least
+        // `num_nodes - 2 * num_malicious` honnest nodes, until one of them
+        // may have run out of transactions
+        let queues: Vec<&Vec<Value>> = good_nodes.iter().filter_map(|id| inputs.get(id)).collect();
+        let honest: HashSet<Value> = queues.iter().copied().flatten().copied().collect();
+        let shortest = queues.iter().map(|queue| queue.len()).min().unwrap_or(0);
+        let expected = shortest.min(epochs as usize) * (self.num_nodes - 2 * self.num_malicious);
+        let progress = order.is_some_and(|committed| {
+            committed.iter().filter(|tx| honest.contains(tx)).count() >= expected
+        });
+
+        (termination && total_order && once && progress, results)
+    }
+
+    /// Total-order broadcast of the values queued by the nodes, one
+    /// multi-valued agreement per slot for `slots` slots, nodes output their
+    /// delivery log once every slot is decided
+    /// Succeeds when every honest node decides every slot and the delivery
+    /// logs pass `Network::check_delivery_logs`
+    pub fn atomic_broadcast(
+        &mut self,
+        slots: u32,
+        queues: &HashMap<NodeId, Vec<Value>>,
+    ) -> (bool, HashMap<NodeId, Vec<Value>>) {
+        let mut config = self.shared.config.write().unwrap();
+        config.atomic.slots = slots;
+        config.atomic.queues = queues.iter().map(|(id, q)| (*id, q.clone())).collect();
+        drop(config);
+        let starts: HashMap<NodeId, Value> = (0..self.num_nodes).map(|id| (id, 0)).collect();
+        let results: HashMap<NodeId, Vec<Value>> = self
+            .run_protocol(ATOMIC_BROADCAST, &starts)
+            .into_iter()
+            .filter_map(|(id, output)| match output {
+                Output::Sequence(log) => Some((id, log)),
+                _ => None,
+            })
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+        (termination && self.check_delivery_logs().is_ok(), results)
+    }
+
+    /// Values every node delivered during the last atomic broadcast, in the
+    /// order it delivered them, including the nodes that didn't decide
+    /// every slot
+    pub fn delivery_logs(&self) -> BTreeMap<NodeId, Vec<Value>> {
+        let mut logs: BTreeMap<NodeId, Vec<Value>> = BTreeMap::new();
+        for (id, value) in self.samples(DELIVERY_LOG) {
+            logs.entry(id).or_default().push(value);
+        }
+        logs
+    }
+
+    /// Safety of the atomic broadcast: of any two honest nodes, the delivery
+    /// log of one is a prefix of the log of the other, and no log delivers
+    /// a value twice
+    /// It holds when the run stops early too, nodes are then at different
+    /// slots
+    pub fn check_delivery_logs(&self) -> Result<(), String> {
+        let logs = self.delivery_logs();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let empty = vec![];
+        let honest: Vec<(NodeId, &Vec<Value>)> = good_nodes
+            .iter()
+            .map(|id| (*id, logs.get(id).unwrap_or(&empty)))
+            .collect();
+        for (id, log) in &honest {
+            let distinct: HashSet<&Value> = log.iter().collect();
+            if distinct.len() != log.len() {
+                return Err(format!("node {} delivered a value twice", id));
+            }
+        }
+        // Every log is a prefix of the longest one
+        let longest = honest.iter().max_by_key(|(_, log)| log.len());
+        if let Some((longest_id, longest)) = longest {
+            for (id, log) in &honest {
+                if let Some(at) = (0..log.len()).find(|i| log[*i] != longest[*i]) {
+                    return Err(format!(
+                        "nodes {} and {} delivered {} and {} at position {}",
+                        id, longest_id, log[at], longest[at], at
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Nodes gossip the transactions submitted to them, every node outputs
+    /// its sorted pool after the rounds of the mempool config
+    /// Succeeds when every honest node holds every transaction submitted
+    /// to an honest node it sees as valid, the bandwidth is in `stats()`
+    pub fn mempool(
+        &mut self,
+        pools: &HashMap<NodeId, Vec<Value>>,
+    ) -> (bool, HashMap<NodeId, Vec<Value>>) {
+        let mut config = self.shared.config.write().unwrap();
+        config.mempool.pools = pools.iter().map(|(id, pool)| (*id, pool.clone())).collect();
+        let oracle = config.validity.oracle.clone();
+        drop(config);
+        let starts: HashMap<NodeId, Value> = (0..self.num_nodes).map(|id| (id, 0)).collect();
+        let results: HashMap<NodeId, Vec<Value>> = self
+            .run_protocol(MEMPOOL, &starts)
+            .into_iter()
+            .filter_map(|(id, output)| match output {
+                Output::Sequence(pool) => Some((id, pool)),
+                _ => None,
+            })
+            .collect();
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+
+        // Termination: all honnest nodes have output their pool
+        let termination = good_nodes.iter().all(|id| results.contains_key(id));
+
+        // Availability: transactions submitted to honnest nodes reach all
+        // of them
+        let submitted: HashSet<Value> = good_nodes
+            .iter()
+            .filter_map(|id| pools.get(id))
+            .flatten()
+            .copied()
+            .collect();
+        let availability = good_nodes.iter().all(|id| {
+            results.get(id).is_some_and(|pool| {
+                submitted
+                    .iter()
+                    .all(|tx| !oracle.is_valid(*id, *tx) || pool.contains(tx))
+            })
+        });
+
+        (termination && availability, results)
+    }
+
+    /// Every node floods its input over the topology, nodes output the
+    /// inputs of all the nodes
+    /// Copies are counted in `flood_report()` against the shortest paths
+    pub fn flood(&mut self, inputs: &HashMap<NodeId, Value>) -> (bool, HashMap<NodeId, Output>) {
+        let results = self.run_protocol(FLOODING, inputs);
+
+        let topology = self.topology();
+        let distances: Vec<Vec<Option<usize>>> = (0..self.num_nodes)
+            .map(|id| topology.distances(id, self.num_nodes))
+            .collect();
+        self.flood.pairs = distances.iter().flatten().filter(|d| **d > Some(0)).count();
+        for ((origin, to), (_, distance)) in self.flood.first.iter_mut() {
+            *distance = distances[*origin][*to].unwrap_or_default();
+        }
+
+        // Every honnest node outputs the inputs of the honnest nodes
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap();
+        let success = good_nodes.iter().all(|id| match results.get(id) {
+            Some(Output::Set(values)) => {
+                values.len() == inputs.len()
+                    && good_nodes.iter().all(|id| values.get(id) == inputs.get(id))
+            }
+            _ => false,
+        });
+        (success, results)
+    }
+
+    /// Start the root instance of `protocol` on every node with an input
+    /// and return the outputs of the nodes
+    pub fn run_protocol(
+        &mut self,
+        protocol: ProtocolId,
+        inputs: &HashMap<NodeId, Value>,
+    ) -> HashMap<NodeId, Output> {
+        // Byzantine protocols take the thresholds of hybrid networks
+        let model = match (fault_model(protocol), self.fault_model) {
+            (FaultModel::Byzantine, hybrid @ FaultModel::Hybrid { .. }) => hybrid,
+            (model, _) => model,
+        };
+        let tolerated = model.max_faulty(self.num_nodes);
+        if self.num_malicious > tolerated {
+            warn!(
+                "Protocol {} tolerates {} faulty nodes out of {}, not {}",
+                protocol, tolerated, self.num_nodes, self.num_malicious
+            );
+        }
+        self.start_run();
+        for id in 0..self.num_nodes {
+            if let Some(input) = inputs.get(&id) {
+                self.inject(NetworkMessage::new(NETWORK_ID, id, START(protocol, *input)));
+            }
+        }
+        self.run_network()
+    }
+
+    /// Clear what is left from the previous run
+    fn start_run(&mut self) {
+        // Node threads are reused from one run to the other
+        if self.runs > 0 {
+            self.reset();
+        }
+        self.runs += 1;
+
+        self.stats = NetworkStats::new();
+        self.behaviour_stats = BehaviourStats::new(
+            self.num_nodes - self.num_malicious,
+            self.num_malicious,
+        );
+        self.flood = FloodReport::default();
+        self.influenced.clear();
+        self.panics.clear();
+        self.dumped = None;
+        self.amnesia.clear();
+        self.sent.clear();
+        self.departures.clear();
+        self.stalled.clear();
+        self.lifecycles.clear();
+        self.monitors.clear();
+        self.shared.counters.write().unwrap().clear();
+        self.shared.samples.write().unwrap().clear();
+        self.shared.ops.write().unwrap().clear();
+        self.costs.clear();
+        self.ledger = Ledger::new();
+        self.randomness.lock().unwrap().draws.clear();
+        self.pending.clear();
+        self.delayed.clear();
+        self.uplinks.clear();
+        self.peer_buckets.clear();
+        self.cpus.clear();
+        self.timers.clear();
+        self.clock = time::Duration::ZERO;
+        let joins = self.shared.joins.read().unwrap().clone();
+        for (id, join) in joins {
+            if let JoinAt::After(delay) = join.at {
+                self.schedule_timer(NetworkMessage::new(NETWORK_ID, id, JOIN), delay);
+            }
+        }
+        self.trace = Trace::new(self.num_nodes, self.num_malicious, self.kind.clone());
+        self.trace.manifest = self.manifest();
+        let run = self.runs;
+        if let Some(logs) = self.logs.as_mut() {
+            let result = logs.start_run(run);
+            self.check_logs(result);
+        }
+    }
+
+    /// Append an event to the log of node `id`, if logs are enabled
+    fn log<F: FnOnce() -> String>(&mut self, id: NodeId, event: F) {
+        if let Some(logs) = self.logs.as_mut() {
+            let result = logs.log(id, self.stats.elapsed(), &event());
+            self.check_logs(result);
+        }
+    }
+
+    /// Stop logging after a write error
+    fn check_logs(&mut self, result: Result<(), String>) {
+        if let Err(e) = result {
+            warn!("Node logs disabled: {}", e);
+            self.logs = None;
+        }
+    }
+
+    /// Send a message from the network to a node
+    fn inject(&mut self, mut msg: NetworkMessage) {
+        trace!("{:?}", msg);
+        self.ledger.create(&mut msg);
+        self.stats.record(msg.msg.phase(), msg.msg.size());
+        if self.schedule.is_lockstep() {
+            self.pending.push(msg);
+        } else {
+            self.deliver(msg);
+        }
+    }
+
+    /// Route a message sent by a node
+    fn relay(&mut self, mut msg: NetworkMessage) {
+        trace!("{:?}", msg);
+        if msg.to == NETWORK_ID {
+            return;
+        }
+        self.ledger.create(&mut msg);
+        self.departures
+            .insert(msg.id, (self.stats.elapsed(), time::Duration::ZERO));
+        self.log(msg.from, || format!("-> {} {:?}", msg.to, msg.msg));
+        if self.lost.contains(&msg.to) {
+            trace!("Discarded {:?}", msg);
+            self.ledger.drop(msg.id, DropCause::DeadNode);
+            return;
+        }
+
+        // Faults of a branch only hit messages sent after its snapshot
+        if self.trace.len() < self.faults_from {
+            self.pending.push(msg);
+            return;
+        }
+
+        if self.controller.should_drop(&msg) {
+            trace!("Controller dropped {:?}", msg);
+            self.ledger.drop(msg.id, DropCause::Controller);
+            return;
+        }
+
+        // Message might be attacked while in transit
+        let id = msg.id;
+        let intercepted = self.mitm.intercept(msg, &self.link_security);
+        if intercepted.iter().all(|msg| msg.id != id) {
+            self.ledger.drop(id, DropCause::Attack);
+        }
+        for mut msg in intercepted {
+            if msg.id == UNTRACKED {
+                // Forged by the adversary
+                self.ledger.create(&mut msg);
+            }
+            let drop_rate = self.config.drop_rate(msg.from, msg.to);
+            if drop_rate > 0.0 && self.rng.gen_bool(drop_rate) {
+                trace!("Lost {:?}", msg);
+                self.ledger.drop(msg.id, DropCause::Link);
+                continue;
+            }
+
+            if self.schedule.is_lockstep() {
+                self.pending.push(msg);
+                continue;
+            }
+            let delay = self.queue(&msg)
+                + self
+                    .config
+                    .transit(msg.from, msg.to, msg.msg.size(), self.rng.as_mut())
+                + self
+                    .controller
+                    .link_delay(msg.from, msg.to)
+                    .unwrap_or_default();
+            if self.throttled(&msg, delay) {
+                trace!("Throttled {:?}", msg);
+                self.ledger.drop(msg.id, DropCause::Throttled);
+                continue;
+            }
+            let delay = delay + self.process(&msg, delay);
+            if delay > time::Duration::ZERO {
+                self.num_delayed += 1;
+                self.delayed.push(Reverse(Delayed {
+                    at: time::Instant::now() + delay,
+                    seq: self.num_delayed,
+                    msg,
+                }));
+            } else {
+                self.deliver(msg);
+            }
+        }
+    }
+
+    /// Add a message sent by a node to the cost of its protocol
+    fn record_cost(&mut self, msg: &Message) {
+        let envelope = match msg {
+            PROTOCOL(envelope) => envelope,
+            _ => return,
+        };
+        let cost = self.costs.entry(envelope.protocol()).or_default();
+        cost.messages += 1;
+        cost.bytes += msg.size();
+        if self.link_security.authenticated {
+            *cost.ops.entry(CryptoOp::Mac).or_default() += 2;
+        }
+        if self.link_security.encrypted {
+            *cost.ops.entry(CryptoOp::Cipher).or_default() += 2;
+        }
+    }
+
+    /// Time `msg` waits at its sender for the uplink, messages sent beyond
+    /// the cap queue behind the previous ones
+    fn queue(&mut self, msg: &NetworkMessage) -> time::Duration {
+        let cap = match self.config.uplink {
+            Some(cap) if msg.from < self.num_nodes => cap,
+            _ => return time::Duration::ZERO,
+        };
+        let now = time::Instant::now();
+        let queued = self
+            .uplinks
+            .entry(msg.from)
+            .or_insert_with(|| TokenBucket::new(cap, now))
+            .send(now, msg.msg.size());
+        self.stats.record_queued(msg.from, queued);
+        if let Some((_, delay)) = self.departures.get_mut(&msg.id) {
+            *delay = queued;
+        }
+        queued
+    }
+
+    /// Whether `msg`, arriving after `delay`, is beyond the limit of its
+    /// honest receiver on its sender, counted in `throttled` at the receiver
+    fn throttled(&mut self, msg: &NetworkMessage, delay: time::Duration) -> bool {
+        let limit = match self.config.peer_limit {
+            Some(limit) if msg.from < self.num_nodes && msg.from != msg.to => limit,
+            _ => return false,
+        };
+        let honest = self
+            .node_behaviours
+            .get(&Behaviour::Good)
+            .is_some_and(|good| good.contains(&msg.to));
+        if !honest {
+            return false;
+        }
+        let now = time::Instant::now();
+        let accepted = self
+            .peer_buckets
+            .entry((msg.to, msg.from))
+            .or_insert_with(|| TokenBucket::per_peer(limit, now))
+            .take(now + delay, 1);
+        if !accepted {
+            *self
+                .shared
+                .counters
+                .write()
+                .unwrap()
+                .entry((msg.to, "throttled"))
+                .or_default() += 1;
+        }
+        !accepted
+    }
+
+    /// Time `msg`, arriving after `delay`, waits for its receiver to handle
+    /// the messages before it, and the time the receiver takes to handle it
+    /// Messages are delivered once handled
+    fn process(&mut self, msg: &NetworkMessage, delay: time::Duration) -> time::Duration {
+        let cpu = match &self.config.cpu {
+            Some(cpu) if msg.to < self.num_nodes => cpu,
+            _ => return time::Duration::ZERO,
+        };
+        let arrival = time::Instant::now() + delay;
+        let cost = cpu.cost(msg.to, msg.msg.size());
+        let free = self.cpus.entry(msg.to).or_insert(arrival);
+        *free = (*free).max(arrival) + cost;
+        self.stats.record_busy(msg.to, cost);
+        *free - arrival
+    }
+
+    /// Wait for the next message sent by a node while delivering the
+    /// delayed messages that are due
+    fn receive(&mut self, deadline: Option<time::Instant>) -> Received {
+        loop {
+            let forced = self
+                .controller
+                .forced_index(self.delayed.iter().map(|Reverse(delayed)| &delayed.msg));
+            if let Some(index) = forced {
+                let mut delayed = std::mem::take(&mut self.delayed).into_vec();
+                let Reverse(forced) = delayed.remove(index);
+                self.delayed = delayed.into();
+                self.deliver(forced.msg);
+            }
+
+            let now = time::Instant::now();
+            while let Some(Reverse(delayed)) = self.delayed.peek() {
+                if delayed.at > now {
+                    break;
+                }
+                let Reverse(delayed) = self.delayed.pop().unwrap();
+                self.deliver(delayed.msg);
+            }
+            if deadline.is_some_and(|deadline| deadline <= now) {
+                return Received::Over;
+            }
+
+            let wake_up = match (self.delayed.peek(), deadline) {
+                (Some(Reverse(delayed)), Some(deadline)) => Some(delayed.at.min(deadline)),
+                (Some(Reverse(delayed)), None) => Some(delayed.at),
+                (None, deadline) => deadline,
+            };
+            let timeout = wake_up.map_or(THREAD_POLL, |wake_up| (wake_up - now).min(THREAD_POLL));
+            match self.rx.recv_timeout(timeout) {
+                Ok(msg) => return Received::Message(msg),
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.check_threads() {
+                        return Received::NodeLost;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.check_threads();
+                    warn!("All nodes are gone");
+                    return Received::Over;
+                }
+            }
+        }
+    }
+
+    /// Crash the nodes whose thread has finished
+    /// Returns whether there were any
+    fn check_threads(&mut self) -> bool {
+        let finished: Vec<NodeId> = self
+            .nodes
+            .iter()
+            .filter(|(_, (node, _))| node.thread.is_finished())
+            .map(|(id, _)| *id)
+            .collect();
+        for id in finished.iter() {
+            self.lose(*id);
+        }
+        !finished.is_empty()
+    }
+
+    /// Remove node `id` whose thread is gone, with the messages in flight
+    /// to it
+    fn lose(&mut self, id: NodeId) {
+        let (node, _) = match self.nodes.remove(&id) {
+            Some(node) => node,
+            None => return,
+        };
+        warn!("Node {} is disconnected, it is crashed from now on", id);
+        self.log(id, || String::from("disconnected"));
+        if node.thread.is_finished() && node.thread.join().is_err() {
+            warn!("Node {} thread panicked outside of the protocol code", id);
+        }
+        self.lost.push(id);
+        let ledger = &mut self.ledger;
+        let mut keep = |msg: &NetworkMessage| {
+            if msg.to == id {
+                ledger.drop(msg.id, DropCause::DeadNode);
+            }
+            msg.to != id
+        };
+        self.pending.retain(|msg| keep(msg));
+        self.delayed.retain(|Reverse(delayed)| keep(&delayed.msg));
+        self.timers.retain(|timer| keep(&timer.msg));
+    }
+
+    /// Returns whether the destination node got the message
+    fn deliver(&mut self, msg: NetworkMessage) -> bool {
+        let at = self.stats.elapsed();
+        let departure = self
+            .departures
+            .get(&msg.id)
+            .cloned()
+            .unwrap_or((at, time::Duration::ZERO));
+        self.trace.record(at, departure, &msg);
+        self.log(msg.to, || {
+            let from = match msg.from {
+                NETWORK_ID => String::from("Network"),
+                from => from.to_string(),
+            };
+            format!("<- {} {:?}", from, msg.msg)
+        });
+        let (to, id, influenced) = (msg.to, msg.id, msg.influenced);
+        let copy = match &msg.msg {
+            PROTOCOL(envelope) => envelope
+                .open::<FloodMessage>()
+                .map(|FloodMessage::FL_VALUE { origin, hops, .. }| (*origin, *hops)),
+            _ => None,
+        };
+        let delivered = match self.nodes.get(&to) {
+            // If the node is still up transmit the message
+            Some((_, tx)) => match tx.send(msg) {
+                Ok(()) => true,
+                Err(SendError(msg)) => {
+                    self.lose(to);
+                    trace!("Discarded {:?}", msg);
+                    false
+                }
+            },
+            None if self.lost.contains(&to) => {
+                trace!("Discarded {:?}", msg);
+                false
+            }
+            None => {
+                warn!("Destination node is down: {:?}", msg);
+                false
+            }
+        };
+        if delivered {
+            self.ledger.deliver(id);
+            if influenced {
+                self.influenced.insert(to);
+            }
+            if let Some((origin, hops)) = copy {
+                self.flood.copies += 1;
+                if origin != to {
+                    self.flood
+                        .first
+                        .entry((origin, to))
+                        .or_insert((hops as usize, 0));
+                }
+            }
+        } else {
+            self.ledger.drop(id, DropCause::DeadNode);
+        }
+        delivered
+    }
+
+    /// Let the schedule pick among the messages in flight, messages of
+    /// delayed links only when there is nothing else
+    fn next_scheduled(&mut self) -> Option<NetworkMessage> {
+        let delivered = self.trace.len();
+        let (held, ready): (Vec<NetworkMessage>, Vec<NetworkMessage>) = self
+            .pending
+            .drain(..)
+            .partition(|msg| self.controller.link_delay(msg.from, msg.to).is_some());
+        let mut omitted = vec![];
+        let next = if ready.is_empty() {
+            self.pending = held;
+            self.schedule.next(
+                &mut self.pending,
+                delivered,
+                self.rng.as_mut(),
+                &mut omitted,
+            )
+        } else {
+            self.pending = ready;
+            let next = self.schedule.next(
+                &mut self.pending,
+                delivered,
+                self.rng.as_mut(),
+                &mut omitted,
+            );
+            self.pending.extend(held);
+            next
+        };
+        for msg in omitted {
+            trace!("Omitted {:?}", msg);
+            self.ledger.drop(msg.id, DropCause::Omitted);
+        }
+        next
+    }
+
+    fn schedule_timer(&mut self, mut msg: NetworkMessage, delay: time::Duration) {
+        trace!("Timer in {:?}: {:?}", delay, msg);
+        self.ledger.create(&mut msg);
+        self.num_delayed += 1;
+        if self.schedule.is_lockstep() {
+            self.timers.push(Timer {
+                at: self.clock + delay,
+                seq: self.num_delayed,
+                msg,
+            });
+        } else {
+            self.delayed.push(Reverse(Delayed {
+                at: time::Instant::now() + delay,
+                seq: self.num_delayed,
+                msg,
+            }));
+        }
+    }
+
+    /// Earliest timer of the lockstep schedules, the virtual clock moves
+    /// to its deadline
+    fn next_timer(&mut self) -> Option<NetworkMessage> {
+        let (index, _) = self
+            .timers
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, timer)| (timer.at, timer.seq))?;
+        let timer = self.timers.remove(index);
+        self.clock = timer.at;
+        Some(timer.msg)
+    }
+
+    fn run_network(&mut self) -> HashMap<NodeId, Output> {
+        let good_nodes = self.node_behaviours.get(&Behaviour::Good).unwrap().clone();
+        let mut results = HashMap::new();
+        // Nodes lost in previous runs are crashed
+        let mut crashed: BTreeSet<NodeId> = self.lost.iter().cloned().collect();
+        let mut good_running_nodes = good_nodes.iter().filter(|id| !crashed.contains(id)).count();
+        // Node processing the last message delivered in lockstep
+        let mut busy = None;
+        let deadline = self.time_limit.map(|limit| time::Instant::now() + limit);
+        // Last time a node sent something or a message was in transit, and
+        // number of deliveries by then
+        let mut last_activity = time::Instant::now();
+        let mut deliveries = 0;
+        // Why the run was stopped with good nodes still running
+        let mut stopped = None;
+
+        while good_running_nodes > 0 {
+            for id in self.lost.iter() {
+                if crashed.insert(*id) {
+                    if good_nodes.contains(id) && !results.contains_key(id) {
+                        good_running_nodes -= 1;
+                    }
+                    // Lost node is not going to acknowledge its message
+                    if busy == Some(*id) {
+                        busy = None;
+                    }
+                }
+            }
+            let progress = RunProgress {
+                results: &results,
+                good_nodes: &good_nodes,
+                running: good_running_nodes,
+                elapsed: self.stats.elapsed(),
+                in_flight: self.pending.len() + self.delayed.len() + self.timers.len(),
+            };
+            if self.termination.ended(&progress) {
+                if good_running_nodes > 0 {
+                    warn!(
+                        "Run ended by the {} policy, {} good nodes are still running",
+                        self.termination, good_running_nodes
+                    );
+                }
+                break;
+            }
+
+            if self.schedule.is_lockstep() && busy.is_none() {
+                if deadline.is_some_and(|deadline| time::Instant::now() >= deadline) {
+                    warn!(
+                        "Run stopped, {} good nodes are still running",
+                        good_running_nodes
+                    );
+                    stopped = Some("time limit");
+                    break;
+                }
+                // Every node is idle, let the scheduler choose what happens next
+                let next = match self.controller.forced_index(self.pending.iter()) {
+                    Some(index) => Some(self.pending.remove(index)),
+                    None if self.pending.is_empty() => self.next_timer(),
+                    None => self.next_scheduled().or_else(|| self.next_timer()),
+                };
+                match next {
+                    Some(msg) => {
+                        let to = msg.to;
+                        if self.deliver(msg) {
+                            busy = Some(to);
+                        }
+                    }
+                    None => {
+                        warn!(
+                            "No message can be delivered, {} good nodes are still running",
+                            good_running_nodes
+                        );
+                        stopped = Some("no deliverable message");
+                        break;
+                    }
+                }
+                continue;
+            }
+
+            let idle = self.termination.idle().map(|idle| last_activity + idle);
+            let wake_up = match (deadline, idle) {
+                (Some(deadline), Some(idle)) => Some(deadline.min(idle)),
+                (deadline, idle) => deadline.or(idle),
+            };
+            let mut network_msg = match self.receive(wake_up) {
+                Received::Message(network_msg) => network_msg,
+                Received::NodeLost => continue,
+                Received::Over if deadline.is_some_and(|d| d <= time::Instant::now()) => {
+                    warn!(
+                        "Time limit reached, {} good nodes are still running",
+                        good_running_nodes
+                    );
+                    stopped = Some("time limit");
+                    break;
+                }
+                // Delayed messages and timers may still wake the nodes up
+                Received::Over
+                    if idle.is_some()
+                        && (!self.delayed.is_empty() || self.trace.len() > deliveries) =>
+                {
+                    last_activity = time::Instant::now();
+                    deliveries = self.trace.len();
+                    continue;
+                }
+                Received::Over if idle.is_some() => {
+                    warn!(
+                        "Network is idle, {} good nodes are still running",
+                        good_running_nodes
+                    );
+                    stopped = Some("idle network");
+                    break;
+                }
+                Received::Over => {
+                    warn!(
+                        "Run stopped, {} good nodes are still running",
+                        good_running_nodes
+                    );
+                    break;
+                }
+            };
+            if network_msg.msg != ACK {
+                last_activity = time::Instant::now();
+            }
+            match network_msg.msg {
+                // Node is done with the message it was delivered
+                ACK => {
+                    if busy == Some(network_msg.from) {
+                        busy = None;
+                    }
+                    continue;
+                }
+
+                // Node has terminated and outputs v
+                END(ref output) => {
+                    self.stats
+                        .record(network_msg.msg.phase(), network_msg.msg.size());
+                    let node_id = network_msg.from;
+                    if let Output::Stalled = output {
+                        self.stats.reach(node_id, Milestone::Stalled);
+                        self.stalled.insert(node_id);
+                    } else {
+                        self.stats.reach(node_id, Milestone::Delivered);
+                    }
+                    self.log(node_id, || format!("output {:?}", output));
+                    self.trace.output(node_id, output);
+                    *self
+                        .behaviour_stats
+                        .class(!good_nodes.contains(&node_id))
+                        .delivered
+                        .entry(output.clone())
+                        .or_default() += 1;
+
+                    // Store result of the node
+                    if results.insert(node_id, output.clone()).is_some() {
+                        panic!("Node {} can't terminate twice", node_id);
+                    }
+
+                    if good_nodes.contains(&node_id) {
+                        // If a good node terminates
+                        good_running_nodes -= 1;
+                        let (at, elapsed) = (self.trace.len(), self.stats.elapsed());
+                        if self.violated(|monitors| monitors.output(node_id, output, at, elapsed)) {
+                            break;
+                        }
+                    }
+                }
+
+                // Node is crashed and never terminates
+                PANIC(ref report) => {
+                    self.log(report.node, || format!("panic: {}", report.message));
+                    // Nodes that delivered and go on helping were counted out
+                    // already
+                    if good_nodes.contains(&report.node) && !results.contains_key(&report.node) {
+                        good_running_nodes -= 1;
+                    }
+                    self.panics.push(report.clone());
+                    if self.on_panic == PanicPolicy::Abort {
+                        error!("Run aborted: node {} panicked", report.node);
+                        break;
+                    }
+                }
+
+                // Node goes on without its protocol state
+                RECOVERED(after) => {
+                    let node = network_msg.from;
+                    self.log(node, || format!("amnesia after {} messages", after));
+                    self.amnesia.push(AmnesiaReport {
+                        node,
+                        after,
+                        resent: BTreeSet::new(),
+                        undelivered: false,
+                    });
+                }
+
+                // Node gets the message back later, without going through
+                // the links
+                TIMER(delay, envelope) => {
+                    let id = network_msg.from;
+                    self.schedule_timer(NetworkMessage::new(id, id, PROTOCOL(envelope)), delay);
+                }
+
+                // Only the network can reset nodes
+                RESET => warn!("Unexpected reset: {:?}", network_msg),
+
+                // Relay message to destination node
+                _ => {
+                    self.stats
+                        .record(network_msg.msg.phase(), network_msg.msg.size());
+                    self.stats
+                        .record_sent(network_msg.from, network_msg.msg.phase());
+                    self.record_cost(&network_msg.msg);
+                    if matches!(self.kind, MaliciousKind::Amnesia(_)) {
+                        self.track_resent(&network_msg);
+                    }
+                    let from = network_msg.from;
+                    let malicious = !good_nodes.contains(&from);
+                    network_msg.influenced = malicious || self.influenced.contains(&from);
+                    let class = self.behaviour_stats.class(malicious);
+                    class.sent += 1;
+                    if !malicious && network_msg.influenced {
+                        class.influenced += 1;
+                    }
+                    let (at, elapsed) = (self.trace.len(), self.stats.elapsed());
+                    let stop = match &network_msg.msg {
+                        PROTOCOL(envelope) if !malicious => {
+                            self.violated(|monitors| monitors.sent(from, envelope, at, elapsed))
+                        }
+                        _ => false,
+                    };
+                    self.relay(network_msg);
+                    if stop {
+                        break;
+                    }
+                }
+            }
+        }
+
+        if good_running_nodes == 0 {
+            // If there are no more good nodes the run is over,
+            // bad nodes are left idle until the next reset
+            warn!("Good nodes {:?} have terminated", good_nodes);
+        }
+        let after_delivery = *self.shared.after_delivery.read().unwrap();
+        for id in 0..self.num_nodes {
+            let failed = crashed.contains(&id) || self.panics.iter().any(|p| p.node == id);
+            let lifecycle = match results.get(&id) {
+                Some(output) if failed || after_delivery == AfterDelivery::Stop => {
+                    Lifecycle::Terminated(output.clone())
+                }
+                Some(output) => Lifecycle::Participating(output.clone()),
+                None if failed => Lifecycle::Failed,
+                None => Lifecycle::Running,
+            };
+            self.lifecycles.insert(id, lifecycle);
+        }
+        let delivered = good_nodes.iter().any(|id| results.contains_key(id));
+        for report in self.amnesia.iter_mut() {
+            report.undelivered = delivered && !results.contains_key(&report.node);
+            warn!("{}", report);
+        }
+        self.trace.randomness = self.randomness();
+        let held: Vec<MessageId> = self
+            .pending
+            .iter()
+            .chain(self.delayed.iter().map(|Reverse(delayed)| &delayed.msg))
+            .chain(self.timers.iter().map(|timer| &timer.msg))
+            .map(|msg| msg.id)
+            .collect();
+        self.conservation = self.ledger.audit(&held);
+        if !self.conservation.holds() {
+            error!("Messages are not conserved: {}", self.conservation);
+        }
+        if let Some(logs) = self.logs.as_mut() {
+            let result = logs
+                .flush()
+                .and_then(|()| logs.write_stats(self.runs, &self.stats.csv(&self.trace.manifest)));
+            self.check_logs(result);
+        }
+        let abnormal = if !self.panics.is_empty() {
+            let panicked: BTreeSet<NodeId> = self.panics.iter().map(|report| report.node).collect();
+            Some(Abnormal::Panic(panicked.into_iter().collect()))
+        } else if !self.monitors.violations.is_empty() {
+            Some(Abnormal::Violation(self.monitors.violations.len()))
+        } else {
+            stopped
+                .filter(|_| good_running_nodes > 0)
+                .map(|cause| Abnormal::Timeout {
+                    cause,
+                    running: good_running_nodes,
+                })
+        };
+        // State of panicked nodes is left as it was at the panic, that of
+        // every other node goes to the artifacts
+        let dump = abnormal.is_some() && self.artifacts.is_some();
+        let audited: Vec<NodeId> = (0..self.num_nodes)
+            .filter(|id| dump || good_nodes.contains(id))
+            .filter(|id| self.panics.iter().all(|report| report.node != *id))
+            .collect();
+        let states = self.node_states(&audited);
+        let honest = states
+            .iter()
+            .filter(|(id, _)| good_nodes.contains(id))
+            .map(|(id, entries)| (*id, entries.clone()))
+            .collect();
+        self.audit = AuditReport::compare(&honest);
+        if !self.audit.is_consistent() {
+            warn!("{}", self.audit);
+        }
+        if let Some(reason) = abnormal.filter(|_| dump) {
+            self.write_artifacts(&reason, states);
+        }
+        // Threads of lost nodes are gone, the others wait for the next run
+        let live = self.resources().live();
+        if live.threads > self.nodes.len() {
+            error!(
+                "{} threads for {} nodes after run {}",
+                live.threads,
+                self.nodes.len(),
+                self.runs
+            );
+        }
+        results
+    }
+
+    /// Report the violation found by `check`, if any
+    /// Returns true if the run should stop
+    fn violated<F>(&mut self, check: F) -> bool
+    where
+        F: FnOnce(&mut Monitors) -> Option<&Violation>,
+    {
+        match check(&mut self.monitors) {
+            Some(violation) => {
+                error!("{}", violation);
+                if self.monitors.stop {
+                    error!("Run stopped at the first violation");
+                }
+                self.monitors.stop
+            }
+            None => false,
+        }
+    }
+
+    /// Record a protocol message of a possibly amnesiac node, and whether it
+    /// was already sent before the node lost its state
+    fn track_resent(&mut self, msg: &NetworkMessage) {
+        let envelope = match &msg.msg {
+            PROTOCOL(envelope) => envelope,
+            _ => return,
+        };
+        let phase = msg.msg.phase();
+        let key = (msg.from, msg.to, envelope.instance.clone(), phase);
+        if !self.sent.insert(key) {
+            if let Some(report) = self.amnesia.iter_mut().find(|r| r.node == msg.from) {
+                report.resent.insert(phase);
+            }
+        }
+    }
+
+    /// Collect the final state of `nodes`
+    fn node_states(&mut self, nodes: &[NodeId]) -> BTreeMap<NodeId, Vec<AuditEntry>> {
+        self.ask(nodes, AUDIT, |msg| match msg {
+            STATE(entries) => Some(entries),
+            _ => None,
+        })
+        .into_iter()
+        .collect()
+    }
+
+    /// Dump what is left of every node after an abnormal run: its final
+    /// state, the messages still held for it and its last deliveries
+    fn write_artifacts(
+        &mut self,
+        reason: &Abnormal,
+        mut states: BTreeMap<NodeId, Vec<AuditEntry>>,
+    ) {
+        let artifacts = match self.artifacts.as_ref() {
+            Some(artifacts) => artifacts,
+            None => return,
+        };
+        let held: Vec<&NetworkMessage> = self
+            .pending
+            .iter()
+            .chain(self.delayed.iter().map(|Reverse(delayed)| &delayed.msg))
+            .chain(self.timers.iter().map(|timer| &timer.msg))
+            .collect();
+        let sender = |from: NodeId| match from {
+            NETWORK_ID => String::from("Network"),
+            from => from.to_string(),
+        };
+        let nodes: Vec<NodeArtifact> = (0..self.num_nodes)
+            .map(|id| {
+                let behaviour = self
+                    .node_behaviours
+                    .iter()
+                    .find(|(_, ids)| ids.contains(&id))
+                    .map_or(String::from("unknown"), |(behaviour, _)| {
+                        format!("{:?}", behaviour)
+                    });
+                let lifecycle = self
+                    .lifecycles
+                    .get(&id)
+                    .map_or(String::from("unknown"), |lifecycle| format!("{:?}", lifecycle));
+                let mailbox = held
+                    .iter()
+                    .filter(|msg| msg.to == id)
+                    .map(|msg| format!("{} -> {} {:?}", sender(msg.from), msg.to, msg.msg))
+                    .collect();
+                let mut events: Vec<String> = self
+                    .trace
+                    .events
+                    .iter()
+                    .rev()
+                    .filter(|event| event.to == id || event.from == id)
+                    .take(LAST_EVENTS)
+                    .map(|event| {
+                        let at = event.at.as_secs_f64();
+                        let (from, to) = (sender(event.from), event.to);
+                        format!("{:.6} {} -> {} {:?}", at, from, to, event.msg)
+                    })
+                    .collect();
+                events.reverse();
+                NodeArtifact {
+                    node: id,
+                    behaviour,
+                    lifecycle,
+                    state: states.remove(&id),
+                    mailbox,
+                    events,
+                }
+            })
+            .collect();
+        let result = artifacts.write(
+            self.runs,
+            &self.trace.manifest,
+            reason,
+            &self.panics,
+            &self.monitors.violations,
+            &nodes,
+        );
+        match result {
+            Ok(dir) => {
+                error!(
+                    "Run {} ended abnormally: {}, artifacts in {}",
+                    self.runs,
+                    reason,
+                    dir.display()
+                );
+                self.dumped = Some(dir);
+            }
+            Err(e) => {
+                warn!("Artifacts disabled: {}", e);
+                self.artifacts = None;
+            }
+        }
+    }
+
+    /// Send `request` to `nodes` and wait for their answer, nodes that are
+    /// lost in the meantime don't answer
+    /// Messages that are not answers belong to the finished run
+    fn ask<T, F>(&mut self, nodes: &[NodeId], request: Message, answer: F) -> Vec<(NodeId, T)>
+    where
+        F: Fn(Message) -> Option<T>,
+    {
+        let mut asked = BTreeSet::new();
+        for id in nodes {
+            let sent = match self.nodes.get(id) {
+                Some((_, tx)) => tx
+                    .send(NetworkMessage::new(NETWORK_ID, *id, request.clone()))
+                    .is_ok(),
+                None => false,
+            };
+            if sent {
+                asked.insert(*id);
+            } else {
+                self.lose(*id);
+            }
+        }
+
+        let mut answers = vec![];
+        while asked.iter().any(|id| self.nodes.contains_key(id)) {
+            match self.rx.recv_timeout(THREAD_POLL) {
+                Ok(msg) => {
+                    let from = msg.from;
+                    if let Some(value) = answer(msg.msg) {
+                        if asked.remove(&from) {
+                            answers.push((from, value));
+                        }
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => {
+                    self.check_threads();
+                }
+                Err(RecvTimeoutError::Disconnected) => {
+                    self.check_threads();
+                    break;
+                }
+            }
+        }
+        answers
+    }
+
+    /// Bring all the nodes back to their initial state so that a new run
+    /// can start without spawning new threads
+    pub fn reset(&mut self) {
+        // Nodes acknowledge the reset once their state is cleared
+        let nodes: Vec<NodeId> = self.nodes.keys().cloned().collect();
+        self.ask(&nodes, RESET, |msg| match msg {
+            RESET => Some(()),
+            _ => None,
+        });
+        trace!("Network reset");
+    }
+
+    /// Interrupt node `id` as if its thread died
+    #[cfg(test)]
+    pub(crate) fn kill(&mut self, id: NodeId) {
+        if let Some((node, tx)) = self.nodes.get(&id) {
+            tx.send(NetworkMessage::new(NETWORK_ID, id, END(Output::Value(0))));
+            while !node.thread.is_finished() {
+                std::thread::yield_now();
+            }
+        }
+    }
+
+    /// Interrupt the nodes that are still running and wait for them
+    /// Panics in debug builds if a thread or channel outlives the network
+    pub fn close(self) {
+        let resources = self.shared.resources.clone();
+        drop(self);
+        let leaked = resources.live();
+        if leaked != ResourceCounts::default() {
+            error!("Network closed with {} still live", leaked);
+        }
+        debug_assert_eq!(leaked, ResourceCounts::default());
+    }
+
+    /// Threads and channels of the network, they stay counted as long as
+    /// they are live even once the network is gone
+    pub fn resources(&self) -> &ResourceGuard {
+        &self.shared.resources
+    }
+
+    /// Interrupt the nodes and join their threads, nodes that don't stop
+    /// within `JOIN_TIMEOUT` are left behind as zombies
+    /// Nodes stop after the message they are handling, even in the middle
+    /// of a run: the ones still queued are dropped
+    fn shutdown(&mut self) {
+        self.shared.interrupted.store(true, Ordering::SeqCst);
+        for (node, tx) in self.nodes.values() {
+            tx.send(NetworkMessage::new(NETWORK_ID, node.id, END(Output::Value(0))));
+        }
+        let deadline = time::Instant::now() + JOIN_TIMEOUT;
+        for (id, (node, tx)) in self.nodes.drain() {
+            // Closing the channel of the node stops it even if END is lost
+            drop(tx);
+            while !node.thread.is_finished() && time::Instant::now() < deadline {
+                std::thread::sleep(time::Duration::from_millis(1));
+            }
+            if !node.thread.is_finished() {
+                error!("Node {} thread doesn't stop, it is left running", id);
+            } else if node.thread.join().is_err() {
+                warn!("Node {} thread panicked outside of the protocol code", id);
+            }
+        }
+    }
+}
+
+impl Drop for Network {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
 }
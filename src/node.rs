@@ -1,16 +1,46 @@
+use crate::audit::AuditEntry;
+use crate::cost::CryptoOp;
+use crate::filter::Inbound;
+#[cfg(feature = "crypto")]
+use crate::crypto::threshold_enc::KeyShare;
+#[cfg(feature = "crypto")]
+use crate::crypto::threshold_sig::SigningKey;
 use crate::network::{Message::*, *};
-use crate::protocols::bracha_broadcast::*;
-use log::debug;
+use crate::protocols::compose::{Instance, Instances};
+use crate::protocols::routing::Relay;
+use crate::protocols::{
+    create, Envelope, Handler, InstanceId, Protocol, ProtocolConfig, ProtocolId, ProtocolMessage,
+    StateMap,
+};
+use crate::quorum::{FaultModel, Quorums};
+use crate::resources::ResourceGuard;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use crate::topology::Topology;
+use log::{debug, error, warn};
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
 use std::hash::Hash;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Once, RwLock};
 use std::thread::{self, JoinHandle};
+use std::time::Duration;
 
 pub type NodeId = usize;
 
-// Faulty nodes stop sending message after SILENT_AFTER messages
-const SILENT_AFTER: usize = 0;
 pub(crate) const MALICIOUS_VALUE: Value = 0;
 
+// Sent by Malformed nodes, outside of the values of any application
+pub(crate) const OUT_OF_RANGE_VALUE: Value = Value::MAX;
+
+// Root tag of the instances Malformed nodes send to, never started
+pub(crate) const STRAY_ROOT_TAG: u32 = u32::MAX;
+
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub enum Behaviour {
     Good,
@@ -20,17 +50,412 @@ use Behaviour::*;
 
 #[derive(Clone, Hash, PartialEq, Eq, Debug)]
 pub enum MaliciousKind {
-    // Stop sending message after SILENT_AFTER messages
+    // Ignore every message
     Silent,
     // Send random message
     Random,
     // Does the same as other nodes but with another value
     Mirror,
+    // Behave correctly until crashing at a precise protocol point
+    Crash(CrashPoint),
+    // Behave correctly, crash after processing this number of messages and
+    // recover right away without its protocol state, like a node with no
+    // write-ahead log
+    Amnesia(usize),
+    // Behave correctly but fail to send each message to a peer with this
+    // probability, in percent
+    SendOmission(usize),
+    // Behave correctly but fail to process each message of a peer with this
+    // probability, in percent
+    ReceiveOmission(usize),
+    // Behave correctly but send another value to the second half of the
+    // peers
+    Equivocate,
+    // Behave correctly but follow each message with a malformed copy: from
+    // a sender that is not a node, with an out-of-range value or in an
+    // instance nobody started
+    Malformed,
+    // Behave correctly but follow each message with copies of the
+    // malicious value in the name of the other nodes
+    Impersonate,
+    // Behave correctly but propose again the latest proposal proven for
+    // itself instead of the one adopted from the leaders, in the protocols
+    // whose proposals carry such proofs
+    Replay,
 }
 use MaliciousKind::*;
 
+/// Protocol points where a node can be set to crash
+#[derive(Clone, Copy, Hash, PartialEq, Eq, Debug)]
+pub enum CrashPoint {
+    // Crash while sending READY, after reaching this number of peers
+    DuringReady(usize),
+    // Crash right before delivering the value
+    BeforeDelivery,
+}
+
+impl FromStr for MaliciousKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "silent" => Ok(Silent),
+            "random" => Ok(Random),
+            "mirror" => Ok(Mirror),
+            "equivocate" => Ok(Equivocate),
+            "malformed" => Ok(Malformed),
+            "impersonate" => Ok(Impersonate),
+            "replay" => Ok(Replay),
+            "crash-delivery" => Ok(Crash(CrashPoint::BeforeDelivery)),
+            s if s.starts_with("crash-ready:") => s["crash-ready:".len()..]
+                .parse()
+                .map(|peers| Crash(CrashPoint::DuringReady(peers)))
+                .map_err(|_| format!("Invalid number of peers: {}", s)),
+            s if s.starts_with("amnesia:") => s["amnesia:".len()..]
+                .parse()
+                .map(Amnesia)
+                .map_err(|_| format!("Invalid number of messages: {}", s)),
+            s if s.starts_with("send-omission:") => {
+                parse_percent(&s["send-omission:".len()..]).map(SendOmission)
+            }
+            s if s.starts_with("receive-omission:") => {
+                parse_percent(&s["receive-omission:".len()..]).map(ReceiveOmission)
+            }
+            _ => Err(format!("Unknown malicious kind: {}", s)),
+        }
+    }
+}
+
+/// Same syntax as `from_str`
+impl fmt::Display for MaliciousKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Silent => write!(f, "silent"),
+            Random => write!(f, "random"),
+            Mirror => write!(f, "mirror"),
+            Equivocate => write!(f, "equivocate"),
+            Malformed => write!(f, "malformed"),
+            Impersonate => write!(f, "impersonate"),
+            Replay => write!(f, "replay"),
+            Crash(CrashPoint::BeforeDelivery) => write!(f, "crash-delivery"),
+            Crash(CrashPoint::DuringReady(peers)) => write!(f, "crash-ready:{}", peers),
+            Amnesia(messages) => write!(f, "amnesia:{}", messages),
+            SendOmission(percent) => write!(f, "send-omission:{}", percent),
+            ReceiveOmission(percent) => write!(f, "receive-omission:{}", percent),
+        }
+    }
+}
+
+fn parse_percent(s: &str) -> Result<usize, String> {
+    match s.parse() {
+        Ok(percent) if percent <= 100 => Ok(percent),
+        _ => Err(format!("Invalid percentage: {}", s)),
+    }
+}
+
+/// When a malicious node misbehaves, by number of processed messages:
+/// lengths of alternate correct and faulty phases, starting with a correct
+/// one, the phase after the last length lasts until the end of the run
+/// e.g. `20,50` is correct for 20 messages, faulty for 50, then correct again
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FaultSchedule {
+    pub phases: Vec<usize>,
+}
+
+impl Default for FaultSchedule {
+    /// Faulty from the start
+    fn default() -> Self {
+        FaultSchedule { phases: vec![0] }
+    }
+}
+
+impl FaultSchedule {
+    pub fn new(phases: Vec<usize>) -> Self {
+        FaultSchedule { phases }
+    }
+
+    /// Whether the node is faulty once it has processed `processed` messages
+    pub fn is_faulty(&self, processed: usize) -> bool {
+        let mut end = 0;
+        for (i, length) in self.phases.iter().enumerate() {
+            end += length;
+            if processed < end {
+                return i % 2 == 1;
+            }
+        }
+        self.phases.len() % 2 == 1
+    }
+}
+
+/// When a late node joins the run
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum JoinAt {
+    // Time after the start of the run, the virtual time of the lockstep
+    // schedules, where timers fire once no message is in flight
+    After(Duration),
+    // Number of messages that reached the node before
+    Messages(usize),
+}
+
+/// Node starting after the others, to see how the protocols bring it up
+/// Its input is handled once it joins, the messages of the other nodes that
+/// reach it before are handled then too when buffered, missed otherwise
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LateJoin {
+    pub at: JoinAt,
+    pub buffer: bool,
+}
+
+impl fmt::Display for LateJoin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.at {
+            JoinAt::After(delay) => write!(f, "after {:?}", delay)?,
+            JoinAt::Messages(k) => write!(f, "after {} messages", k)?,
+        }
+        match self.buffer {
+            true => write!(f, ", buffered"),
+            false => write!(f, ", missed"),
+        }
+    }
+}
+
+impl FromStr for FaultSchedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .map(|length| {
+                length
+                    .trim()
+                    .parse()
+                    .map_err(|_| format!("Invalid phase length: {}", length))
+            })
+            .collect::<Result<_, _>>()
+            .map(FaultSchedule::new)
+    }
+}
+
+impl fmt::Display for FaultSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phases: Vec<String> = self.phases.iter().map(usize::to_string).collect();
+        write!(f, "{}", phases.join(","))
+    }
+}
+
 const DEBUG_NODES: [NodeId; 2] = [0, 1];
 
+/// Run counters of every node, by name
+pub(crate) type Counters = Arc<RwLock<BTreeMap<(NodeId, &'static str), usize>>>;
+/// Samples taken by every node, by name
+pub(crate) type Samples = Arc<RwLock<Vec<(NodeId, &'static str, usize)>>>;
+/// Cryptographic operations of all the nodes, by protocol
+pub(crate) type CryptoOps = Arc<RwLock<BTreeMap<(ProtocolId, CryptoOp), usize>>>;
+
+/// State the network shares with all its nodes
+#[derive(Clone, Default)]
+pub(crate) struct Shared {
+    // Acknowledge every processed message
+    pub lockstep: Arc<AtomicBool>,
+    // Network is gone, nodes stop without handling the messages they have
+    // left
+    pub interrupted: Arc<AtomicBool>,
+    // Parameters of the protocol instances created from now on
+    pub config: Arc<RwLock<ProtocolConfig>>,
+    // Handler switches of the scenario, applied by the nodes themselves
+    pub switches: Arc<RwLock<Vec<HandlerSwitch>>>,
+    // Release of a protocol run by a node, when not the default one
+    pub versions: Arc<RwLock<BTreeMap<(NodeId, ProtocolId), String>>>,
+    // Faulty phases of the malicious nodes, when not faulty from the start
+    pub faults: Arc<RwLock<BTreeMap<NodeId, FaultSchedule>>>,
+    // Nodes joining the runs after they start
+    pub joins: Arc<RwLock<BTreeMap<NodeId, LateJoin>>>,
+    // Links between the nodes, read at the start of every run
+    pub topology: Arc<RwLock<Topology>>,
+    // Nodes relay the messages between nodes that are not linked
+    pub routing: Arc<AtomicBool>,
+    // Threads and channels of the network
+    pub resources: ResourceGuard,
+    // Failures of the malicious nodes, set when the network is created
+    pub fault_model: FaultModel,
+    // Events counted by the protocols of every node during the current run
+    pub counters: Counters,
+    // Measurements taken by the protocols during the current run
+    pub samples: Samples,
+    // Cryptographic operations of the protocols during the current run
+    pub ops: CryptoOps,
+    // Threshold encryption key shares dealt to the nodes when the network
+    // is created, every node keeps its own
+    #[cfg(feature = "crypto")]
+    pub key_shares: Arc<Vec<KeyShare>>,
+    // Threshold signature keys dealt to the nodes along with them
+    #[cfg(feature = "crypto")]
+    pub signing_keys: Arc<Vec<SigningKey>>,
+    // What the nodes do once they delivered
+    pub after_delivery: Arc<RwLock<AfterDelivery>>,
+}
+
+/// What a node does once the root instance of its run delivered
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AfterDelivery {
+    // Node terminates as it delivers, it stops handling messages
+    #[default]
+    Stop,
+    // Node handles messages until the run ends, its instances keep relaying
+    // for the nodes lagging behind and answer their state-transfer requests
+    Participate,
+}
+
+impl AfterDelivery {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AfterDelivery::Stop => "stop",
+            AfterDelivery::Participate => "participate",
+        }
+    }
+}
+
+impl FromStr for AfterDelivery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "stop" => Ok(AfterDelivery::Stop),
+            "participate" => Ok(AfterDelivery::Participate),
+            _ => Err(format!("Unknown behaviour after delivery: {}", s)),
+        }
+    }
+}
+
+/// Where a node stood when the last run ended, delivering and terminating
+/// are apart once nodes participate after delivery
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Lifecycle {
+    // Node was still waiting for its output
+    Running,
+    // Node delivered and was still handling messages
+    Participating(Output),
+    // Node delivered and stopped handling messages
+    Terminated(Output),
+    // Protocol code of the node panicked or its thread is gone
+    Failed,
+}
+
+/// Scenario step: node runs `handler` for `protocol` once it has processed
+/// `after` messages of the run, its instances keep their state
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HandlerSwitch {
+    pub node: NodeId,
+    pub protocol: ProtocolId,
+    pub handler: Handler,
+    pub after: usize,
+}
+
+/// Panic of the protocol code of a node, caught so that the simulation can
+/// go on without the node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NodePanic {
+    pub node: NodeId,
+    // Panic message and location
+    pub message: String,
+    pub backtrace: String,
+    // Message the node was processing
+    pub last_message: String,
+}
+
+impl fmt::Display for NodePanic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Node {} panicked: {}", self.node, self.message)?;
+        writeln!(f, "while processing {}", self.last_message)?;
+        write!(f, "{}", self.backtrace)
+    }
+}
+
+/// Node that recovered from a crash without its protocol state, and the
+/// properties of a crash-recovery model it broke because of it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AmnesiaReport {
+    pub node: NodeId,
+    // Messages processed before the crash
+    pub after: usize,
+    // Phases of the messages sent again to a peer after the recovery, a
+    // node remembering its state sends each of them once
+    pub resent: BTreeSet<Phase>,
+    // Node never delivered although honest nodes did
+    pub undelivered: bool,
+}
+
+impl AmnesiaReport {
+    /// Amnesia broke the protocol: correct nodes of a crash-recovery model
+    /// don't vote twice and eventually deliver
+    pub fn violates(&self) -> bool {
+        !self.resent.is_empty() || self.undelivered
+    }
+}
+
+impl fmt::Display for AmnesiaReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Node {} lost its state after {} messages",
+            self.node, self.after
+        )?;
+        if !self.resent.is_empty() {
+            write!(f, ", sent {:?} again", self.resent)?;
+        }
+        if self.undelivered {
+            write!(f, ", never delivered")?;
+        }
+        if !self.violates() {
+            write!(f, ", no harm done")?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    // Node thread is running protocol code, its panics are reported to the
+    // network
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+    // Message and backtrace of the last caught panic
+    static CAUGHT: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+}
+
+/// Keep the message and backtrace of the panics of protocol code for the
+/// node to report them, other panics go to the previous hook
+fn install_panic_hook() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if !CATCHING.with(Cell::get) {
+                return previous(info);
+            }
+            let mut message = info.payload_as_str().unwrap_or("unknown cause").to_string();
+            if let Some(location) = info.location() {
+                message = format!("{} at {}", message, location);
+            }
+            let backtrace = Backtrace::force_capture().to_string();
+            CAUGHT.with(|caught| *caught.borrow_mut() = Some((message, backtrace)));
+        }));
+    });
+}
+
+/// Nodes that may lie and nodes a node can wait for, as the protocols of
+/// the nodes count them
+pub(crate) fn thresholds(num_nodes: usize, fault_model: FaultModel) -> (usize, usize) {
+    // Number of malicious nodes must be inferior to 1/3
+    let mut max_malicious_nodes = num_nodes / 3;
+    let mut min_honnest_nodes = num_nodes - max_malicious_nodes;
+    // Byzantine protocols of hybrid networks: only the byzantine nodes
+    // lie, crashed ones don't answer either
+    if let FaultModel::Hybrid { .. } = fault_model {
+        let quorums = Quorums::new(fault_model, num_nodes);
+        max_malicious_nodes = quorums.max_byzantine();
+        min_honnest_nodes = quorums.quorum();
+    }
+    (max_malicious_nodes, min_honnest_nodes)
+}
+
 // Struct to store parameters necessary for the network
 pub(crate) struct Node {
     pub id: NodeId,
@@ -45,49 +470,149 @@ impl Node {
         rx: Receiver<NetworkMessage>,
         behaviour: Behaviour,
         neighbour_nodes: Vec<NodeId>,
+        rng: Box<dyn Rng>,
+        shared: Shared,
     ) -> Node {
         // Parameters
         let num_nodes = neighbour_nodes.len() + 1;
-        // Number of malicious nodes must be inferior to 1/3
-        let max_malicious_nodes = num_nodes / 3;
-        let min_honnest_nodes = num_nodes - max_malicious_nodes;
+        let (max_malicious_nodes, min_honnest_nodes) = thresholds(num_nodes, shared.fault_model);
         let mut node = NodeInternals {
             id,
             behaviour: behaviour.clone(),
             num_nodes,
             max_malicious_nodes,
             min_honnest_nodes,
+            links: neighbour_nodes.clone(),
+            routes: (0..num_nodes).map(|to| (to != id).then_some(to)).collect(),
             neighbour_nodes,
             tx,
             rx,
-            bc_state: BroadcastState::new(),
+            rng,
+            #[cfg(feature = "crypto")]
+            key_share: shared.key_shares[id],
+            #[cfg(feature = "crypto")]
+            signing_key: shared.signing_keys[id],
+            delivered: false,
+            terminated: false,
+            crashed: false,
+            instances: Instances::new(),
+            current: None,
+            outputs: VecDeque::new(),
+            config: shared.config.clone(),
+            switches: shared.switches.clone(),
+            handlers: StateMap::new(),
+            versions: shared.versions.clone(),
+            faults: shared.faults.clone(),
+            joins: shared.joins.clone(),
+            after_delivery: shared.after_delivery.clone(),
+            joining: None,
+            early: vec![],
+            topology: shared.topology.clone(),
+            routing: shared.routing.clone(),
+            counters: shared.counters.clone(),
+            samples: shared.samples.clone(),
+            ops: shared.ops.clone(),
+            faulty: false,
         };
 
+        install_panic_hook();
+        let thread_token = shared.resources.thread();
+        let channel_token = shared.resources.channel();
+
         // Start thread to handle all the node computations
         let thread = thread::Builder::new()
             .name(format!("Node {}", id))
             .spawn(move || {
+                // Thread and its channel count as live until it returns
+                let _resources = (thread_token, channel_token);
                 let mut num_msg_received = 0;
-                loop {
-                    let msg = node.rx.recv().unwrap();
-                    num_msg_received = num_msg_received + 1;
-                    match node.handle_msg(msg, num_msg_received) {
-                        // Continue processing message
-                        ProtocolState::InProcess => (),
-
-                        // Returns output of the protocol and terminate
-                        ProtocolState::Terminated(v) => {
-                            node.tx
-                                .send(NetworkMessage::new(node.id, NETWORK_ID, END(v)));
+                // Network is gone when the channel is closed
+                while let Ok(msg) = node.rx.recv() {
+                    if shared.interrupted.load(Ordering::SeqCst) {
+                        break;
+                    }
+                    if let RESET = msg.msg {
+                        node.reset();
+                        num_msg_received = 0;
+                        node.tx.send(NetworkMessage::new(node.id, NETWORK_ID, RESET));
+                        continue;
+                    }
+
+                    if let AUDIT = msg.msg {
+                        let state = STATE(node.audit());
+                        node.tx.send(NetworkMessage::new(node.id, NETWORK_ID, state));
+                        continue;
+                    }
+
+                    if node.terminated || node.crashed {
+                        // Protocol is over for this node until the next reset
+                        if let END(_) = msg.msg {
                             break;
                         }
+                        // Nodes that output still relay for the others
+                        if !node.crashed {
+                            node.pass_on(&msg);
+                        }
+                    } else {
+                        num_msg_received += 1;
+                        if node.delivered {
+                            node.count("handled_after_delivery", 1);
+                        }
+                        CATCHING.with(|catching| catching.set(true));
+                        let state = panic::catch_unwind(AssertUnwindSafe(|| {
+                            node.handle_msg(&msg, num_msg_received)
+                        }));
+                        CATCHING.with(|catching| catching.set(false));
+                        match state {
+                            // Continue processing message
+                            Ok(ProtocolState::InProcess) => {
+                                if node.behaviour() == Malicious(Amnesia(num_msg_received)) {
+                                    node.forget();
+                                    node.tx.send(NetworkMessage::new(
+                                        node.id,
+                                        NETWORK_ID,
+                                        RECOVERED(num_msg_received),
+                                    ));
+                                }
+                            }
+
+                            // Returns output of the protocol and wait for a reset
+                            // Protocols may output again, the node only delivers once
+                            Ok(ProtocolState::Terminated(v)) => {
+                                if !node.delivered {
+                                    node.tx
+                                        .send(NetworkMessage::new(node.id, NETWORK_ID, END(v)));
+                                    node.delivered = true;
+                                }
+                                let after = *shared.after_delivery.read().unwrap();
+                                node.terminated = after == AfterDelivery::Stop;
+                            }
 
-                        // Terminate the thread
-                        ProtocolState::Interrupted => break,
+                            // Terminate the thread
+                            Ok(ProtocolState::Interrupted) => break,
+
+                            // State of the node can't be trusted anymore,
+                            // it is crashed until the next reset
+                            Err(_) => {
+                                let report = node.panic_report(&msg);
+                                error!("{}", report);
+                                node.tx.send(NetworkMessage::new(
+                                    node.id,
+                                    NETWORK_ID,
+                                    PANIC(report),
+                                ));
+                                node.crashed = true;
+                            }
+                        }
+                    }
+
+                    // Let the network schedule the next message
+                    if shared.lockstep.load(Ordering::SeqCst) {
+                        node.tx.send(NetworkMessage::new(node.id, NETWORK_ID, ACK));
                     }
                 }
             })
-            .expect(&format!("Could not spawn thread {}", id));
+            .unwrap_or_else(|_| panic!("Could not spawn thread {}", id));
         Node {
             id,
             behaviour,
@@ -98,7 +623,7 @@ impl Node {
 
 pub(crate) enum ProtocolState {
     InProcess,
-    Terminated(Value),
+    Terminated(Output),
     Interrupted,
 }
 
@@ -107,54 +632,735 @@ pub(crate) struct NodeInternals {
     pub(crate) id: NodeId,
     pub(crate) behaviour: Behaviour,
     pub(crate) num_nodes: usize,
+    // More nodes than this always include one that doesn't lie
     pub(crate) max_malicious_nodes: usize,
+    // Nodes a node can wait for
     pub(crate) min_honnest_nodes: usize,
+    // Nodes the protocols send to, every other node when routing
     pub(crate) neighbour_nodes: Vec<NodeId>,
+    // Nodes linked to this one by the topology
+    pub(crate) links: Vec<NodeId>,
+    // Next link towards every node, computed from the topology
+    pub(crate) routes: Vec<Option<NodeId>>,
     pub(crate) tx: Sender<NetworkMessage>,
     pub(crate) rx: Receiver<NetworkMessage>,
+    pub(crate) rng: Box<dyn Rng>,
+    // Share of the threshold encryption key of the nodes
+    #[cfg(feature = "crypto")]
+    pub(crate) key_share: KeyShare,
+    // Share of the threshold signature key of the nodes
+    #[cfg(feature = "crypto")]
+    pub(crate) signing_key: SigningKey,
+    // Node has output a value for the current run, and has stopped
+    // handling messages
+    pub(crate) delivered: bool,
+    pub(crate) terminated: bool,
+    // Node has crashed for the current run
+    pub(crate) crashed: bool,
 
-    pub(crate) bc_state: BroadcastState,
+    // Protocol instances of the current run, messages are routed by
+    // instance id
+    pub(crate) instances: Instances,
+    // Instance handling the current message or event
+    pub(crate) current: Option<InstanceId>,
+    // Outputs of instances that their parents have not handled yet
+    pub(crate) outputs: VecDeque<(InstanceId, Output)>,
+    pub(crate) config: Arc<RwLock<ProtocolConfig>>,
+    pub(crate) switches: Arc<RwLock<Vec<HandlerSwitch>>>,
+    // Handlers switched to during the current run
+    pub(crate) handlers: StateMap<ProtocolId, Handler>,
+    pub(crate) versions: Arc<RwLock<BTreeMap<(NodeId, ProtocolId), String>>>,
+    pub(crate) faults: Arc<RwLock<BTreeMap<NodeId, FaultSchedule>>>,
+    pub(crate) joins: Arc<RwLock<BTreeMap<NodeId, LateJoin>>>,
+    pub(crate) after_delivery: Arc<RwLock<AfterDelivery>>,
+    // How the node joins the current run, None once it joined
+    pub(crate) joining: Option<LateJoin>,
+    // Messages the node got before joining, with the number of messages
+    // that had reached it then, handled when it joins
+    pub(crate) early: Vec<(usize, NetworkMessage)>,
+    pub(crate) topology: Arc<RwLock<Topology>>,
+    pub(crate) routing: Arc<AtomicBool>,
+    pub(crate) counters: Counters,
+    pub(crate) samples: Samples,
+    pub(crate) ops: CryptoOps,
+    // Malicious node is in a faulty phase, it behaves correctly otherwise
+    pub(crate) faulty: bool,
 }
 
 impl NodeInternals {
     /// Handle all the incoming messages
     /// Returns true to wait for new messages, false to terminate the node
-    fn handle_msg(&mut self, msg: NetworkMessage, num_msg: usize) -> ProtocolState {
-        match msg.msg {
-            BROADCAST(bc_msg) => match &self.behaviour {
-                Good => handle_broadcast(self, msg.from, bc_msg),
+    fn handle_msg(&mut self, msg: &NetworkMessage, num_msg: usize) -> ProtocolState {
+        if num_msg == 1 {
+            let topology = *self.topology.read().unwrap();
+            self.links = topology.neighbours(self.id, self.num_nodes);
+            self.routes = topology.routes(self.id, self.num_nodes);
+            self.neighbour_nodes = if self.routing.load(Ordering::SeqCst) {
+                (0..self.num_nodes).filter(|id| *id != self.id).collect()
+            } else {
+                self.links.clone()
+            };
+            self.joining = self.joins.read().unwrap().get(&self.id).copied();
+            self.joining = self.joining.filter(|join| join.at != JoinAt::Messages(0));
+            self.early.clear();
+        }
+        if let Some(join) = self.joining {
+            if msg.msg == JOIN {
+                return self.join(num_msg - 1);
+            }
+            if msg.from == NETWORK_ID || join.buffer {
+                self.count("buffered", 1);
+                let early = NetworkMessage {
+                    msg: msg.msg.clone(),
+                    ..*msg
+                };
+                self.early.push((num_msg, early));
+            } else {
+                self.count("missed", 1);
+            }
+            return match join.at {
+                JoinAt::Messages(k) if num_msg >= k => self.join(num_msg),
+                _ => ProtocolState::InProcess,
+            };
+        }
+        self.switch_handlers(num_msg - 1);
+        self.dispatch(msg, num_msg)
+    }
+
+    /// Join the run once `arrived` messages reached the node, handle the
+    /// ones kept until now in the order they came, each as the message it
+    /// was in the run
+    /// Node that delivers on one of them goes on with the others as it does
+    /// after delivery, it handles them or only relays them
+    /// `joined_after` samples the number of messages
+    fn join(&mut self, arrived: usize) -> ProtocolState {
+        debug!("NODE {} joins after {} messages", self.id, arrived);
+        self.joining = None;
+        self.sample("joined_after", arrived);
+        let after = *self.after_delivery.read().unwrap();
+        let mut joined = ProtocolState::InProcess;
+        for (num_msg, early) in std::mem::take(&mut self.early) {
+            let delivered = matches!(joined, ProtocolState::Terminated(_));
+            if self.crashed {
+                break;
+            }
+            if delivered && after == AfterDelivery::Stop {
+                self.pass_on(&early);
+                continue;
+            }
+            if delivered {
+                self.count("handled_after_delivery", 1);
+            }
+            match self.dispatch(&early, num_msg) {
+                ProtocolState::InProcess => {}
+                ProtocolState::Interrupted => return ProtocolState::Interrupted,
+                // Node only delivers once
+                state if !delivered => joined = state,
+                _ => {}
+            }
+        }
+        joined
+    }
+
+    /// Handle `msg`, the `num_msg`th message of the run
+    fn dispatch(&mut self, msg: &NetworkMessage, num_msg: usize) -> ProtocolState {
+        self.faulty = match self.behaviour {
+            Good => false,
+            Malicious(_) => self
+                .faults
+                .read()
+                .unwrap()
+                .get(&self.id)
+                .cloned()
+                .unwrap_or_default()
+                .is_faulty(num_msg - 1),
+        };
+        let state = match &msg.msg {
+            PROTOCOL(envelope) => match envelope.open::<Relay>() {
+                Some(relay) if relay.to != self.id => {
+                    self.relay(relay);
+                    ProtocolState::InProcess
+                }
+                Some(relay) => self.handle_envelope(relay.origin, &relay.envelope),
+                None => self.handle_envelope(msg.from, envelope),
+            },
+
+            // Network gives the node its input
+            START(protocol, input) => {
+                let input = *input;
+                self.run_instance(&InstanceId::root(*protocol), |protocol, node| {
+                    match node.behaviour() {
+                        Good => protocol.start(node, input),
+                        Malicious(kind) =>
+                            match kind {
+                                Silent => ProtocolState::InProcess,
+
+                                Random => {
+                                    node.send_random(protocol);
+                                    ProtocolState::InProcess
+                                }
+
+                                Mirror => protocol.start(node, MALICIOUS_VALUE),
+
+                                Crash(_)
+                                | Amnesia(_)
+                                | SendOmission(_)
+                                | ReceiveOmission(_)
+                                | Equivocate
+                                | Malformed
+                                | Impersonate
+                                | Replay => protocol.start(node, input),
+                            }
+                    }
+                })
+            }
+
+            // Network asks the node to terminate
+            END(_) => ProtocolState::Interrupted,
+
+            // Handled by the node loop
+            RESET => ProtocolState::InProcess,
+
+            // Handled by the node loop
+            AUDIT => ProtocolState::InProcess,
+
+            // Late node that already joined, or not late
+            JOIN => ProtocolState::InProcess,
+
+            // Only sent by nodes
+            ACK | STATE(_) | PANIC(_) | TIMER(..) | RECOVERED(_) => ProtocolState::InProcess,
+        };
+        match state {
+            ProtocolState::InProcess => self.settle(),
+            state => state,
+        }
+    }
+
+    /// Handle a protocol message sent by `from`, as the behaviour of the
+    /// node dictates
+    fn handle_envelope(&mut self, from: NodeId, envelope: &Envelope) -> ProtocolState {
+        if self.behaviour() == Good && self.rejects(from, envelope) {
+            return ProtocolState::InProcess;
+        }
+        self.run_instance(&envelope.instance.clone(), |protocol, node| {
+            match node.behaviour() {
+                Good => protocol.handle(node, from, envelope),
                 Malicious(kind) =>
                     match kind {
-                        Silent => {
-                            if num_msg < SILENT_AFTER {
-                                return handle_broadcast(self, msg.from, bc_msg);
-                            }
+                        Silent => ProtocolState::InProcess,
+
+                        Random => {
+                            node.send_random(protocol);
                             ProtocolState::InProcess
                         }
 
-                        Random => random_broadcast(self, msg.from , bc_msg),
+                        Mirror => protocol.handle(node, from, &envelope.malicious()),
+
+                        // Correct until the crash point
+                        Crash(_) => protocol.handle(node, from, envelope),
+
+                        // Correct, faults happen around the handler
+                        Amnesia(_) | SendOmission(_) | Equivocate | Malformed | Impersonate
+                        | Replay => {
+                            protocol.handle(node, from, envelope)
+                        }
 
-                        Mirror => handle_broadcast(self, msg.from, bc_msg.malicious()),
+                        // Inputs and timers are not received from
+                        // the links
+                        ReceiveOmission(percent) => {
+                            let peer = from != node.id && from != NETWORK_ID;
+                            if peer && node.rng.gen_bool(percent as f64 / 100.0) {
+                                debug!("NODE {} omitted {:?}", node.id, envelope);
+                                ProtocolState::InProcess
+                            } else {
+                                protocol.handle(node, from, envelope)
+                            }
+                        }
                     }
+            }
+        })
+    }
 
+    /// Whether the inbound filter drops `envelope`, counted by reason
+    fn rejects(&self, from: NodeId, envelope: &Envelope) -> bool {
+        let filter = self.config.read().unwrap().filter.filter.clone();
+        let runnable = self.instances.contains_key(&envelope.instance)
+            || self.new_protocol(envelope.protocol()).is_some();
+        let inbound = Inbound {
+            from,
+            instance: &envelope.instance,
+            value: envelope.value(),
+            runnable,
+        };
+        match filter.check(self.id, self.num_nodes, &inbound) {
+            Some(rejection) => {
+                debug!("NODE {} rejected {:?} from {}: {}", self.id, envelope, from, rejection);
+                self.count(rejection.counter(), 1);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pass `relay` on to the next link towards its destination
+    /// Faulty relays drop or alter it as they would their own messages
+    fn relay(&mut self, relay: &Relay) {
+        let relay = match self.behaviour() {
+            Malicious(Silent) => return,
+            Malicious(ReceiveOmission(percent)) if self.rng.gen_bool(percent as f64 / 100.0) => {
+                debug!("NODE {} omitted {:?}", self.id, relay);
+                return;
+            }
+            Malicious(Random | Mirror) => ProtocolMessage::malicious(relay),
+            _ => relay.clone(),
+        };
+        let to = relay.to;
+        self.send_to(to, PROTOCOL(Envelope::new(relay)));
+    }
+
+    /// Relay `msg` if it is for another node, once the node no longer runs
+    /// the protocol
+    fn pass_on(&mut self, msg: &NetworkMessage) {
+        if let PROTOCOL(envelope) = &msg.msg {
+            if let Some(relay) = envelope.open::<Relay>() {
+                if relay.to != self.id {
+                    self.relay(relay);
+                }
+            }
+        }
+    }
+
+    /// Run `run` on the instance `id`, created if this is the first time the
+    /// node hears about it
+    /// Outputs of the instance are queued for its parent
+    fn run_instance<F>(&mut self, id: &InstanceId, run: F) -> ProtocolState
+    where
+        F: FnOnce(&mut dyn Protocol, &mut NodeInternals) -> ProtocolState,
+    {
+        // Instance is taken out of the node while it runs so that it can
+        // use the node, even to spawn its own sub-instances
+        let mut instance = match self.instances.remove(id) {
+            Some(instance) => instance,
+            None => match self.new_protocol(id.protocol()) {
+                Some(protocol) => Instance::new(protocol),
+                None => {
+                    warn!("NODE {}: no protocol {}", self.id, id.protocol());
+                    return ProtocolState::InProcess;
+                }
             },
+        };
+        if instance.stopped {
+            self.instances.insert(id.clone(), instance);
+            return ProtocolState::InProcess;
+        }
 
-            // Network asks the node to terminate
-            END(_) => ProtocolState::Interrupted,
+        let caller = self.current.replace(id.clone());
+        let state = run(instance.protocol.as_mut(), self);
+        self.current = caller;
+        // Implementation bugs show up right after the message causing them
+        if cfg!(debug_assertions) {
+            if let Err(broken) = instance.protocol.check(self) {
+                panic!("NODE {}: instance {:?} {}", self.id, id, broken);
+            }
+        }
+
+        let state = match state {
+            ProtocolState::Terminated(output) => {
+                if instance.output.is_none() {
+                    instance.output = Some(output.clone());
+                    self.outputs.push_back((id.clone(), output));
+                }
+                ProtocolState::InProcess
+            }
+            state => state,
+        };
+        self.instances.insert(id.clone(), instance);
+        state
+    }
+
+    /// New instance of `protocol` running the release and the handler of
+    /// the node
+    fn new_protocol(&self, protocol: ProtocolId) -> Option<Box<dyn Protocol>> {
+        let mut instance = create(protocol, &self.config.read().unwrap())?;
+        if let Some(tag) = self.versions.read().unwrap().get(&(self.id, protocol)) {
+            if !instance.set_version(tag) {
+                warn!("NODE {}: protocol {} has no version {}", self.id, protocol, tag);
+            }
+        }
+        let handler = self.handler(protocol);
+        if handler != Handler::Current && !instance.switch(handler) {
+            warn!("NODE {}: protocol {} has no {:?} handler", self.id, protocol, handler);
+        }
+        Some(instance)
+    }
+
+    fn handler(&self, protocol: ProtocolId) -> Handler {
+        self.handlers.get(&protocol).cloned().unwrap_or_default()
+    }
+
+    /// Apply the switches of the scenario due after `processed` messages,
+    /// the latest one wins for each protocol
+    fn switch_handlers(&mut self, processed: usize) {
+        let mut due: StateMap<ProtocolId, (usize, Handler)> = StateMap::new();
+        for switch in self.switches.read().unwrap().iter() {
+            if switch.node != self.id || switch.after > processed {
+                continue;
+            }
+            let latest = due
+                .entry(switch.protocol)
+                .or_insert((switch.after, switch.handler));
+            if switch.after >= latest.0 {
+                *latest = (switch.after, switch.handler);
+            }
+        }
+
+        for (protocol, (_, handler)) in due {
+            if self.handler(protocol) == handler {
+                continue;
+            }
+            debug!("NODE {} runs the {:?} handler of protocol {}", self.id, handler, protocol);
+            self.handlers.insert(protocol, handler);
+            for (id, instance) in self.instances.iter_mut() {
+                if id.protocol() == protocol && !instance.protocol.switch(handler) {
+                    warn!("NODE {}: protocol {} has no {:?} handler", self.id, protocol, handler);
+                }
+            }
+        }
+    }
+
+    /// Hand the queued outputs to the parent instances
+    /// The node terminates once a root instance outputs
+    fn settle(&mut self) -> ProtocolState {
+        while let Some((id, output)) = self.outputs.pop_front() {
+            if self.crashed {
+                break;
+            }
+            match id.parent() {
+                None => {
+                    self.outputs.clear();
+                    return ProtocolState::Terminated(output);
+                }
+                Some(parent) => {
+                    let state = self.run_instance(&parent, |protocol, node| {
+                        protocol.child_output(node, &id, &output)
+                    });
+                    if let ProtocolState::Interrupted = state {
+                        return state;
+                    }
+                }
+            }
+        }
+        ProtocolState::InProcess
+    }
+
+    /// State of every instance that honest nodes should agree on
+    fn audit(&self) -> Vec<AuditEntry> {
+        let mut entries = vec![];
+        for (id, instance) in self.instances.iter() {
+            let mut fields = instance.protocol.audit();
+            if let Some(output) = &instance.output {
+                fields.push(("output", format!("{:?}", output)));
+            }
+            entries.extend(fields.into_iter().map(|(field, value)| AuditEntry {
+                instance: format!("{:?}", id),
+                field,
+                value,
+            }));
+        }
+        entries
+    }
+
+    /// Random behaviour: answer with a random message of the instance
+    fn send_random(&mut self, protocol: &dyn Protocol) {
+        if let Some(payload) = protocol.random_message(self.rng.as_mut()) {
+            let instance = self.current.clone().unwrap();
+            self.send_to_all(PROTOCOL(Envelope { instance, payload }));
+        }
+    }
+
+    /// Message of the instance being run
+    pub(crate) fn msg<M: ProtocolMessage>(&self, msg: M) -> Message {
+        let instance = self
+            .current
+            .clone()
+            .unwrap_or_else(|| InstanceId::root(M::PROTOCOL));
+        PROTOCOL(Envelope::within(instance, msg))
+    }
+
+    /// Start a sub-instance of the instance being run
+    /// Its output is handed to `Protocol::child_output` of the parent
+    pub(crate) fn spawn(&mut self, protocol: ProtocolId, tag: u32, input: Value) -> InstanceId {
+        let parent = self.current.clone().expect("Only instances can spawn instances");
+        let child = parent.child(protocol, tag);
+        self.run_instance(&child, |protocol, node| protocol.start(node, input));
+        child
+    }
+
+    /// Stop a sub-instance of the instance being run, its messages are
+    /// dropped from now on
+    pub(crate) fn stop(&mut self, protocol: ProtocolId, tag: u32) {
+        let parent = self.current.clone().expect("Only instances can stop instances");
+        let child = parent.child(protocol, tag);
+        match self.instances.get_mut(&child) {
+            Some(instance) => instance.stopped = true,
+            None => {
+                if let Some(protocol) = self.new_protocol(protocol) {
+                    let mut instance = Instance::new(protocol);
+                    instance.stopped = true;
+                    self.instances.insert(child, instance);
+                }
+            }
+        }
+    }
+
+    /// Report of the panic caught while processing `msg`
+    fn panic_report(&self, msg: &NetworkMessage) -> NodePanic {
+        let (message, backtrace) = CAUGHT
+            .with(|caught| caught.borrow_mut().take())
+            .unwrap_or_else(|| (String::from("unknown cause"), String::new()));
+        NodePanic {
+            node: self.id,
+            message,
+            backtrace,
+            last_message: format!("{:?}", msg),
+        }
+    }
+
+    /// Clear the protocol state for a new run
+    fn reset(&mut self) {
+        self.delivered = false;
+        self.terminated = false;
+        self.crashed = false;
+        self.instances.clear();
+        self.current = None;
+        self.outputs.clear();
+        self.handlers.clear();
+        // Drop messages of the previous run that are still queued
+        while self.rx.try_recv().is_ok() {}
+    }
+
+    /// Crash and recover with no protocol state: instances, pending outputs
+    /// and switched handlers are lost, the node goes on with the next message
+    fn forget(&mut self) {
+        debug!("NODE {} recovered without its protocol state", self.id);
+        self.instances.clear();
+        self.current = None;
+        self.outputs.clear();
+        self.handlers.clear();
+    }
+
+
+    pub(crate) fn send_to_all(&mut self, msg: Message) {
+        for id in self.neighbour_nodes.clone() {
+            self.send_to(id, msg.clone());
         }
     }
 
+    /// Send `msg` to the peer `to`, unless the node omits or alters it
+    pub(crate) fn send_to(&mut self, to: NodeId, msg: Message) {
+        let msg = match self.behaviour() {
+            Malicious(SendOmission(percent)) => {
+                if self.rng.gen_bool(percent as f64 / 100.0) {
+                    debug!("NODE {} omitted {:?} to {}", self.id, msg, to);
+                    return;
+                }
+                msg
+            }
+            Malicious(Equivocate) => {
+                let half = self.neighbour_nodes.len() / 2;
+                match self.neighbour_nodes.iter().position(|id| *id == to) {
+                    Some(i) if i >= half => msg.with_value(MALICIOUS_VALUE),
+                    _ => msg,
+                }
+            }
+            Malicious(Malformed) => {
+                if let Some(forged) = self.malformed(to, &msg) {
+                    self.tx.send(self.route(to, forged));
+                }
+                msg
+            }
+            // Copies go straight to `to`, as if the other nodes sent them
+            Malicious(Impersonate) => {
+                for id in (0..self.num_nodes).filter(|id| *id != self.id && *id != to) {
+                    let forged = msg.with_value(MALICIOUS_VALUE);
+                    self.tx.send(NetworkMessage::new(id, to, forged));
+                }
+                msg
+            }
+            _ => msg,
+        };
+        self.tx.send(self.route(to, msg));
+    }
+
+    /// Malformed copy of `msg` to `to`, None for messages that are not of
+    /// an instance
+    fn malformed(&mut self, to: NodeId, msg: &Message) -> Option<Message> {
+        let envelope = match msg {
+            PROTOCOL(envelope) if envelope.open::<Relay>().is_none() => envelope,
+            _ => return None,
+        };
+        let forged = match self.rng.next_u64() % 3 {
+            // Relayed on behalf of a node that does not exist
+            0 => Envelope::new(Relay {
+                origin: self.num_nodes + self.id,
+                to,
+                envelope: envelope.clone(),
+            }),
+            1 => envelope.with_value(OUT_OF_RANGE_VALUE),
+            _ => Envelope {
+                instance: envelope.instance.under_root(STRAY_ROOT_TAG),
+                payload: envelope.payload.clone_payload(),
+            },
+        };
+        Some(PROTOCOL(forged))
+    }
+
+    /// Message on the link towards `to`, wrapped in a relay if the nodes
+    /// are not linked
+    fn route(&self, to: NodeId, msg: Message) -> NetworkMessage {
+        if to >= self.num_nodes || self.links.contains(&to) {
+            return NetworkMessage::new(self.id, to, msg);
+        }
+        match (msg, self.routes[to]) {
+            (PROTOCOL(envelope), Some(next)) => {
+                let relay = match envelope.open::<Relay>() {
+                    Some(relay) => relay.clone(),
+                    None => Relay {
+                        origin: self.id,
+                        to,
+                        envelope,
+                    },
+                };
+                NetworkMessage::new(self.id, next, PROTOCOL(Envelope::new(relay)))
+            }
+            // No path, the network drops messages between nodes that are
+            // not linked
+            (msg, _) => NetworkMessage::new(self.id, to, msg),
+        }
+    }
 
-    pub(crate) fn send_to_all(&self, msg: Message) {
-        for id in self.neighbour_nodes.iter() {
-            self.tx
-                .send(NetworkMessage::new(self.id, *id, msg.clone()));
+    /// Behaviour of the node in the current phase of its fault schedule
+    fn behaviour(&self) -> Behaviour {
+        if self.faulty {
+            self.behaviour.clone()
+        } else {
+            Good
         }
     }
 
-    pub(crate) fn debug(&self) {
+    /// Thresholds of the protocols designed for `model`
+    pub(crate) fn quorums(&self, model: FaultModel) -> Quorums {
+        Quorums::new(model, self.num_nodes)
+    }
+
+    /// Protocol hook: whether the node is set to crash at `point`
+    pub(crate) fn crashes_at(&self, point: CrashPoint) -> bool {
+        matches!(self.behaviour(), Malicious(Crash(crash)) if crash == point)
+    }
+
+    /// Send READY to all the neighbours, or only to some of them if the node
+    /// crashes in the middle
+    /// Crash-model protocols send every message a crash may cut short this
+    /// way
+    pub(crate) fn send_ready(&mut self, msg: Message) {
+        if let Malicious(Crash(CrashPoint::DuringReady(peers))) = self.behaviour() {
+            for id in self.neighbour_nodes.clone().into_iter().take(peers) {
+                self.send_to(id, msg.clone());
+            }
+            debug!("NODE {} crashed after sending READY to {} peers", self.id, peers);
+            self.crashed = true;
+        } else {
+            self.send_to_all(msg);
+        }
+    }
+
+    /// Get `msg` back after `delay`, for timeouts, retries or periodic
+    /// rounds
+    /// In lockstep schedules the delay is virtual: the message comes back
+    /// once no other message can be delivered, earliest deadline first
+    pub(crate) fn schedule(&self, delay: Duration, msg: Message) {
+        match msg {
+            PROTOCOL(envelope) => {
+                self.tx
+                    .send(NetworkMessage::new(self.id, self.id, TIMER(delay, envelope)));
+            }
+            msg => warn!("NODE {}: can't schedule {:?}", self.id, msg),
+        }
+    }
+
+    /// Deliver `v` unless the node crashes right before
+    pub(crate) fn deliver(&mut self, v: Value) -> ProtocolState {
+        self.deliver_output(Output::Value(v))
+    }
+
+    pub(crate) fn deliver_output(&mut self, output: Output) -> ProtocolState {
+        if self.crashes_at(CrashPoint::BeforeDelivery) {
+            debug!("NODE {} crashed before delivering {:?}", self.id, output);
+            self.crashed = true;
+            return ProtocolState::InProcess;
+        }
+        ProtocolState::Terminated(output)
+    }
+
+    /// Protocol hook: add `k` to the run counter `name` of the node, read by
+    /// the network once the run is over
+    pub(crate) fn count(&self, name: &'static str, k: usize) {
+        *self
+            .counters
+            .write()
+            .unwrap()
+            .entry((self.id, name))
+            .or_default() += k;
+    }
+
+    /// Measurement hook: highest run counter `name` among the nodes, what
+    /// no node could know in a real deployment
+    pub(crate) fn highest_count(&self, name: &'static str) -> usize {
+        self.counters
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|((_, counter), _)| *counter == name)
+            .map(|(_, k)| *k)
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Protocol hook: record `value` as a sample of `name`, read by the
+    /// network once the run is over
+    pub(crate) fn sample(&self, name: &'static str, value: usize) {
+        self.samples.write().unwrap().push((self.id, name, value));
+    }
+
+    /// Whether the node proposes its own proven proposals again
+    pub(crate) fn replays(&self) -> bool {
+        self.behaviour() == Malicious(Replay)
+    }
+
+    /// External validity hook: whether the node accepts `v`, as its own
+    /// view of the validity of the values tells
+    pub(crate) fn is_valid(&self, v: Value) -> bool {
+        self.config.read().unwrap().validity.oracle.is_valid(self.id, v)
+    }
+
+    /// Whether every node accepts every value
+    pub(crate) fn accepts_all(&self) -> bool {
+        self.config.read().unwrap().validity.oracle.accepts_all()
+    }
+
+    /// Cost hook: the instance handling a message performed `k` times
+    /// `op`, on behalf of its protocol
+    pub(crate) fn crypto(&self, op: CryptoOp, k: usize) {
+        let protocol = match &self.current {
+            Some(instance) => instance.protocol(),
+            None => return,
+        };
+        *self.ops.write().unwrap().entry((protocol, op)).or_default() += k;
+    }
+
+    pub(crate) fn debug(&self, state: &dyn fmt::Debug) {
         if DEBUG_NODES.contains(&self.id) {
-            debug!("NODE {}: {:?}", self.id, self.bc_state);
+            debug!("NODE {}: {:?}", self.id, state);
         }
     }
 }
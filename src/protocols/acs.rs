@@ -0,0 +1,12 @@
+// Asynchronous common subset of Ben-Or, Kelmer and Rabin as HoneyBadger
+// composes it, n Bracha broadcasts and n binary agreements
+// This module is an alias of `common_subset`, which implements it under its
+// usual name: every node runs one Bracha child instance and one agreement
+// child instance per node, and messages reach the state of their instance
+// through their hierarchical InstanceId
+
+/// Protocol id of the asynchronous common subset, that of the common subset
+pub use crate::protocols::COMMON_SUBSET as ACS;
+
+/// Messages, state and thresholds of the asynchronous common subset
+pub use crate::protocols::common_subset::describe;
@@ -0,0 +1,141 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::rng::Rng;
+use std::collections::BTreeMap;
+
+/// Proposal of a node with nothing left to propose, never delivered
+pub const NO_PROPOSAL: Value = Value::MAX;
+
+/// Name of the samples of the deliveries, see `Network::delivery_logs`
+pub const DELIVERY_LOG: &str = "delivery_log";
+
+/// Parameters of the atomic broadcast layer
+#[derive(Clone, Debug, PartialEq)]
+pub struct AtomicConfig {
+    // Consensus instances to run before the nodes output their log
+    pub slots: u32,
+    // Values every node broadcasts, in the order it proposes them
+    pub queues: BTreeMap<NodeId, Vec<Value>>,
+}
+
+impl Default for AtomicConfig {
+    fn default() -> Self {
+        AtomicConfig {
+            slots: 1,
+            queues: BTreeMap::new(),
+        }
+    }
+}
+
+/// Total-order broadcast on top of consensus: slot after slot, every node
+/// proposes the first of its values not delivered yet to a multi-valued
+/// agreement, and delivers the value decided unless it already did
+/// Honest nodes decide the same value in every slot and go through the
+/// slots in order: their delivery logs are prefixes of each other at any
+/// time, and the same once every slot is decided
+/// A node moves on to the next slot as soon as it decided, agreements of
+/// the slots ahead of it that its peers started already run meanwhile
+pub(crate) struct AtomicBroadcast {
+    config: AtomicConfig,
+    agreements: Children,
+    // Slot of the agreement under way
+    slot: u32,
+    // Values of the node yet to be delivered
+    pending: Vec<Value>,
+    log: Vec<Value>,
+    done: bool,
+}
+
+impl AtomicBroadcast {
+    pub fn new(config: AtomicConfig) -> Self {
+        AtomicBroadcast {
+            config,
+            agreements: Children::new(),
+            slot: 0,
+            pending: vec![],
+            log: vec![],
+            done: false,
+        }
+    }
+
+    fn propose(&mut self, node: &mut NodeInternals) {
+        let proposal = self.pending.first().copied().unwrap_or(NO_PROPOSAL);
+        node.spawn(MVBA, self.slot, proposal);
+    }
+
+    /// Deliver the decisions of the slots in order, from the slot under way
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        while !self.done {
+            let decided = match self.agreements.output(self.slot).and_then(Output::value) {
+                Some(v) => v,
+                None => return ProtocolState::InProcess,
+            };
+            if decided != NO_PROPOSAL && !self.log.contains(&decided) {
+                self.log.push(decided);
+                self.pending.retain(|v| *v != decided);
+                node.sample(DELIVERY_LOG, decided);
+            }
+            self.slot += 1;
+            if self.slot == self.config.slots {
+                self.done = true;
+                return node.deliver_output(Output::Sequence(self.log.clone()));
+            }
+            self.propose(node);
+        }
+        ProtocolState::InProcess
+    }
+}
+
+impl Protocol for AtomicBroadcast {
+    /// Input is ignored, nodes propose the values of their queue
+    fn start(&mut self, node: &mut NodeInternals, _input: Value) -> ProtocolState {
+        self.pending = self
+            .config
+            .queues
+            .get(&node.id)
+            .cloned()
+            .unwrap_or_default();
+        if self.config.slots == 0 {
+            self.done = true;
+            return node.deliver_output(Output::Sequence(vec![]));
+        }
+        self.propose(node);
+        self.progress(node)
+    }
+
+    // Messages go to the agreements
+    fn handle(
+        &mut self,
+        _node: &mut NodeInternals,
+        _from: NodeId,
+        _msg: &Envelope,
+    ) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        if child.protocol() != MVBA || !self.agreements.record(child, output) {
+            return ProtocolState::InProcess;
+        }
+        self.progress(node)
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+/// Instances of the atomic broadcast
+pub fn describe(_num_nodes: usize) -> ProtocolDescription {
+    ProtocolDescription {
+        state: vec!["agreements", "slot", "pending", "log", "done"],
+        children: vec![MVBA],
+        ..ProtocolDescription::new(ATOMIC_BROADCAST)
+    }
+}
@@ -0,0 +1,322 @@
+use crate::cost::CryptoOp;
+use crate::crypto::secret_sharing::{
+    SecretShare, SharingCommitment, SharingConfig, VerifiableSecretSharing,
+};
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// Parameters of the sharings
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AvssConfig {
+    // Node sharing its input, nodes only take their share from it
+    pub dealer: NodeId,
+}
+
+/// Asynchronous verifiable secret sharing, then reconstruction: the dealer
+/// sends every node its share along with the commitment to the sharing,
+/// nodes ECHO the commitment once their share checks against it and READY
+/// its digest as in Bracha broadcast
+/// The sharing is complete once n - f nodes readied it, f + 1 honest nodes
+/// among them hold a valid share: every node RECONSTRUCTs with its own
+/// share, and outputs the secret from f + 1 valid ones
+/// Nodes the dealer left out or sent an invalid share still complete and
+/// reconstruct, faulty nodes only have their shares rejected
+#[derive(Clone, PartialEq)]
+pub(crate) enum AvssMessage {
+    AV_SHARE(SharingCommitment, SecretShare),
+    AV_ECHO(SharingCommitment),
+    AV_READY(u64),
+    AV_RECONSTRUCT(SecretShare),
+}
+use AvssMessage::*;
+
+impl ProtocolMessage for AvssMessage {
+    const PROTOCOL: ProtocolId = AVSS;
+
+    fn phase(&self) -> Phase {
+        match self {
+            AV_SHARE(..) => Phase::Init,
+            AV_ECHO(_) => Phase::Echo,
+            AV_READY(_) => Phase::Ready,
+            AV_RECONSTRUCT(_) => Phase::Vote,
+        }
+    }
+
+    /// Kind, commitment and share
+    fn size(&self) -> usize {
+        1 + match self {
+            AV_SHARE(commitment, _) => commitment.size() + std::mem::size_of::<SecretShare>(),
+            AV_ECHO(commitment) => commitment.size(),
+            AV_READY(_) => std::mem::size_of::<u64>(),
+            AV_RECONSTRUCT(_) => std::mem::size_of::<SecretShare>(),
+        }
+    }
+
+    /// Shares no longer match the commitment, commitments are kept
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            AV_SHARE(commitment, _) => AV_SHARE(commitment.clone(), v as SecretShare),
+            AV_ECHO(commitment) => AV_ECHO(commitment.clone()),
+            AV_READY(_) => AV_READY(v as u64),
+            AV_RECONSTRUCT(_) => AV_RECONSTRUCT(v as SecretShare),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    /// Nodes ready a single sharing
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            AV_READY(digest) => Some(Commitment::new("READY", 0, *digest)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for AvssMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AV_SHARE(commitment, share) => write!(f, "<SHARE, {:?}, {:x}>", commitment, share),
+            AV_ECHO(commitment) => write!(f, "<ECHO, {:?}>", commitment),
+            AV_READY(digest) => write!(f, "<READY, {:x}>", digest),
+            AV_RECONSTRUCT(share) => write!(f, "<RECONSTRUCT, {:x}>", share),
+        }
+    }
+}
+
+/// AVSS as run by a node
+pub(crate) struct Avss {
+    config: AvssConfig,
+    scheme: Arc<dyn VerifiableSecretSharing>,
+    // Valid share of the node, with the digest of its sharing
+    share: Option<(u64, SecretShare)>,
+    // Commitments heard of, by digest
+    commitments: BTreeMap<u64, SharingCommitment>,
+    // Nodes that echoed and readied each sharing, the node itself included
+    echoes: BTreeMap<u64, BTreeSet<NodeId>>,
+    readies: BTreeMap<u64, BTreeSet<NodeId>>,
+    readied: bool,
+    // Sharing readied by n - f nodes
+    completed: Option<u64>,
+    // Shares revealed by the nodes, checked once the sharing is known
+    revealed: BTreeMap<NodeId, SecretShare>,
+    delivered: bool,
+}
+
+impl Avss {
+    pub fn new(config: AvssConfig, sharing: SharingConfig) -> Self {
+        Avss {
+            config,
+            scheme: sharing.scheme,
+            share: None,
+            commitments: BTreeMap::new(),
+            echoes: BTreeMap::new(),
+            readies: BTreeMap::new(),
+            readied: false,
+            completed: None,
+            revealed: BTreeMap::new(),
+            delivered: false,
+        }
+    }
+
+    /// Check the share of the node, ECHO its commitment if it is valid
+    fn receive_share(
+        &mut self,
+        node: &mut NodeInternals,
+        commitment: &SharingCommitment,
+        share: SecretShare,
+    ) {
+        if self.share.is_some() {
+            return;
+        }
+        node.crypto(CryptoOp::ShareVerify, 1);
+        if !self.scheme.verify_share(commitment, node.id, share) {
+            node.count("invalid_shares", 1);
+            return;
+        }
+        self.share = Some((commitment.digest, share));
+        self.commitments
+            .insert(commitment.digest, commitment.clone());
+        self.echoes
+            .entry(commitment.digest)
+            .or_default()
+            .insert(node.id);
+        node.send_to_all(node.msg(AV_ECHO(commitment.clone())));
+        self.echo_quorum(node, commitment.digest);
+    }
+
+    fn echo_quorum(&mut self, node: &mut NodeInternals, digest: u64) {
+        let echoes = self.echoes.get(&digest).map_or(0, BTreeSet::len);
+        if echoes >= node.min_honnest_nodes {
+            self.ready(node, digest);
+        }
+    }
+
+    fn ready(&mut self, node: &mut NodeInternals, digest: u64) {
+        if self.readied {
+            return;
+        }
+        self.readied = true;
+        self.readies.entry(digest).or_default().insert(node.id);
+        node.send_to_all(node.msg(AV_READY(digest)));
+    }
+
+    /// READY of f + 1 nodes are READY of an honest one, those of n - f
+    /// complete the sharing: the node reveals its share
+    fn ready_quorum(&mut self, node: &mut NodeInternals, digest: u64) {
+        let readies = self.readies.get(&digest).map_or(0, BTreeSet::len);
+        if readies > node.max_malicious_nodes {
+            self.ready(node, digest);
+        }
+        let readies = self.readies.get(&digest).map_or(0, BTreeSet::len);
+        if readies < node.min_honnest_nodes || self.completed.is_some() {
+            return;
+        }
+        self.completed = Some(digest);
+        node.count("completed", 1);
+        if let Some((shared, share)) = self.share {
+            if shared == digest {
+                self.revealed.insert(node.id, share);
+                node.send_to_all(node.msg(AV_RECONSTRUCT(share)));
+            }
+        }
+    }
+
+    /// Secret of the completed sharing once f + 1 valid shares arrived
+    fn reconstruct(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let commitment = match self
+            .completed
+            .and_then(|digest| self.commitments.get(&digest))
+        {
+            Some(commitment) if !self.delivered => commitment,
+            _ => return ProtocolState::InProcess,
+        };
+        let threshold = self.scheme.threshold();
+        if self.revealed.len() < threshold {
+            return ProtocolState::InProcess;
+        }
+        let scheme = &self.scheme;
+        let before = self.revealed.len();
+        self.revealed
+            .retain(|from, share| scheme.verify_share(commitment, *from, *share));
+        node.crypto(CryptoOp::ShareVerify, before);
+        node.count("invalid_shares", before - self.revealed.len());
+        node.crypto(CryptoOp::Reconstruct, 1);
+        match self.scheme.reconstruct(commitment, &self.revealed) {
+            Some(secret) => {
+                self.delivered = true;
+                node.deliver(secret)
+            }
+            None => ProtocolState::InProcess,
+        }
+    }
+}
+
+impl Protocol for Avss {
+    /// Dealer shares its input
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.crypto(CryptoOp::Deal, 1);
+        let seed = node.rng.next_u64();
+        let (commitment, shares) = self.scheme.share(input, seed, node.num_nodes);
+        for id in node.neighbour_nodes.clone() {
+            node.send_to(id, node.msg(AV_SHARE(commitment.clone(), shares[id])));
+        }
+        self.receive_share(node, &commitment, shares[node.id]);
+        self.reconstruct(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<AvssMessage>() {
+            Some(AV_SHARE(commitment, share)) if from == self.config.dealer => {
+                self.receive_share(node, commitment, *share)
+            }
+            Some(AV_ECHO(commitment)) => {
+                if !self
+                    .echoes
+                    .entry(commitment.digest)
+                    .or_default()
+                    .insert(from)
+                {
+                    return ProtocolState::InProcess;
+                }
+                self.commitments
+                    .entry(commitment.digest)
+                    .or_insert_with(|| commitment.clone());
+                self.echo_quorum(node, commitment.digest);
+            }
+            Some(AV_READY(digest)) => {
+                self.readies.entry(*digest).or_default().insert(from);
+                self.ready_quorum(node, *digest);
+            }
+            Some(AV_RECONSTRUCT(share)) => {
+                self.revealed.entry(from).or_insert(*share);
+            }
+            _ => return ProtocolState::InProcess,
+        }
+        self.reconstruct(node)
+    }
+
+    /// Faulty node reveals a share of nothing
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(AV_RECONSTRUCT(MALICIOUS_VALUE as SecretShare)))
+    }
+
+    /// Honest nodes complete the same sharing
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.completed {
+            Some(digest) => vec![("completed", format!("{:x}", digest))],
+            None => vec![],
+        }
+    }
+}
+
+/// Messages, state and thresholds of the secret sharing
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    let (commitment, _) = SharingConfig::default().scheme.share(0, 0, num_nodes);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(AV_SHARE(commitment.clone(), 0)),
+            MessageSchema::of(AV_ECHO(commitment.clone())),
+            MessageSchema::of(AV_READY(commitment.digest)),
+            MessageSchema::of(AV_RECONSTRUCT(0)),
+        ],
+        state: vec![
+            "share",
+            "commitments",
+            "echoes",
+            "readies",
+            "readied",
+            "completed",
+            "revealed",
+            "delivered",
+        ],
+        thresholds: vec![
+            Threshold::new(
+                "echo_quorum",
+                quorum,
+                "n - f ECHOes of a sharing to READY it",
+            ),
+            Threshold::new(
+                "ready_amplification",
+                f + 1,
+                "f + 1 READY hold an honest one, the node READYs too",
+            ),
+            Threshold::new(
+                "sharing_completion",
+                quorum,
+                "n - f READY complete the sharing, the nodes reveal their share",
+            ),
+        ],
+        ..ProtocolDescription::new(AVSS)
+    }
+}
@@ -0,0 +1,323 @@
+use crate::cost::CryptoOp;
+use crate::merkle::{Digest, MerkleProof, MerkleTree};
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Reliable broadcast of a batch of values in a single instance: values are
+/// sent one by one with a proof against the Merkle root of the batch, ECHO
+/// and READY only carry the root
+#[derive(Clone, PartialEq)]
+pub(crate) enum BatchMessage {
+    // Node has been chosen as the initiator of the batch
+    BB_LEADER(Vec<Value>),
+    // Value of the batch of `root` with its proof
+    BB_VALUE {
+        root: Digest,
+        len: usize,
+        value: Value,
+        proof: MerkleProof,
+    },
+    BB_ECHO(Digest),
+    BB_READY(Digest),
+}
+use BatchMessage::*;
+
+impl ProtocolMessage for BatchMessage {
+    const PROTOCOL: ProtocolId = BATCHED_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        match self {
+            BB_LEADER(_) => Phase::Leader,
+            BB_VALUE { .. } => Phase::Init,
+            BB_ECHO(_) => Phase::Echo,
+            BB_READY(_) => Phase::Ready,
+        }
+    }
+
+    /// Kind and content
+    fn size(&self) -> usize {
+        1 + match self {
+            BB_LEADER(values) => values.len() * std::mem::size_of::<Value>(),
+            BB_VALUE { proof, .. } => {
+                std::mem::size_of::<Digest>()
+                    + std::mem::size_of::<usize>()
+                    + std::mem::size_of::<Value>()
+                    + proof.size()
+            }
+            BB_ECHO(_) | BB_READY(_) => std::mem::size_of::<Digest>(),
+        }
+    }
+
+    /// Values are replaced, roots are replaced by the value
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            BB_LEADER(values) => BB_LEADER(vec![v; values.len()]),
+            BB_VALUE {
+                root, len, proof, ..
+            } => BB_VALUE {
+                root: *root,
+                len: *len,
+                value: v,
+                proof: proof.clone(),
+            },
+            BB_ECHO(_) => BB_ECHO(v as Digest),
+            BB_READY(_) => BB_READY(v as Digest),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        match self {
+            BB_ECHO(_) | BB_READY(_) => self.clone(),
+            _ => ProtocolMessage::with_value(self, MALICIOUS_VALUE),
+        }
+    }
+
+    /// Nodes echo and ready a single root
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            BB_ECHO(root) => Some(Commitment::new("ECHO", 0, *root)),
+            BB_READY(root) => Some(Commitment::new("READY", 0, *root)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for BatchMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BB_LEADER(values) => write!(f, "<LEADER, {:?}>", values),
+            BB_VALUE {
+                root,
+                len,
+                value,
+                proof,
+            } => write!(f, "<VALUE, {:x}, {}/{}, {}>", root, proof.index, len, value),
+            BB_ECHO(root) => write!(f, "<ECHO, {:x}>", root),
+            BB_READY(root) => write!(f, "<READY, {:x}>", root),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct BatchState {
+    // Batch the values received belong to, the first valid one
+    root: Option<Digest>,
+    len: usize,
+    values: BTreeMap<usize, Value>,
+    echo: bool,
+    ready: bool,
+    echo_received: StateMap<Digest, StateSet<NodeId>>,
+    ready_received: StateMap<Digest, StateSet<NodeId>>,
+    // Root for which enough READY were received
+    delivered: Option<Digest>,
+}
+
+/// Batched reliable broadcast as run by a node
+pub(crate) struct BatchedBroadcast {
+    state: BatchState,
+}
+
+impl BatchedBroadcast {
+    pub fn new() -> Self {
+        BatchedBroadcast {
+            state: BatchState {
+                echo: true,
+                ready: true,
+                ..BatchState::default()
+            },
+        }
+    }
+
+    fn send_echo(&mut self, node: &mut NodeInternals, root: Digest) {
+        if self.state.echo {
+            node.send_to_all(node.msg(BB_ECHO(root)));
+            self.state.echo = false;
+        }
+    }
+
+    fn send_ready(&mut self, node: &mut NodeInternals, root: Digest) {
+        if self.state.ready {
+            node.send_ready(node.msg(BB_READY(root)));
+            self.state.ready = false;
+        }
+    }
+
+    /// Deliver the whole batch once its root is delivered and every value
+    /// has been received with a valid proof
+    fn try_deliver(&self, node: &mut NodeInternals) -> ProtocolState {
+        match (self.state.delivered, self.state.root) {
+            (Some(delivered), Some(root))
+                if delivered == root && self.state.values.len() == self.state.len =>
+            {
+                node.deliver_output(Output::Sequence(
+                    self.state.values.values().cloned().collect(),
+                ))
+            }
+            _ => ProtocolState::InProcess,
+        }
+    }
+}
+
+impl Protocol for BatchedBroadcast {
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        let msg = match msg.open::<BatchMessage>() {
+            Some(msg) => msg.clone(),
+            None => return ProtocolState::InProcess,
+        };
+        match msg {
+            BB_LEADER(values) => {
+                let tree = MerkleTree::new(&values);
+                node.crypto(CryptoOp::Hash, tree.hashes());
+                let root = tree.root();
+                for (index, value) in values.iter().enumerate() {
+                    node.send_to_all(node.msg(BB_VALUE {
+                        root,
+                        len: values.len(),
+                        value: *value,
+                        proof: tree.proof(index),
+                    }));
+                }
+                self.state.root = Some(root);
+                self.state.len = values.len();
+                self.state.values = values.into_iter().enumerate().collect();
+                self.send_echo(node, root);
+            }
+
+            BB_VALUE {
+                root,
+                len,
+                value,
+                proof,
+            } => {
+                let same_batch = self.state.root.is_none_or(|r| r == root);
+                if !same_batch || proof.index >= len {
+                    // Value of a competing batch
+                    return ProtocolState::InProcess;
+                }
+                node.crypto(CryptoOp::Hash, proof.hashes());
+                if !proof.verify(root, value) {
+                    // Forged value
+                    return ProtocolState::InProcess;
+                }
+                self.state.root = Some(root);
+                self.state.len = len;
+                self.state.values.insert(proof.index, value);
+                self.send_echo(node, root);
+            }
+
+            BB_ECHO(root) => {
+                let echo_received = self.state.echo_received.entry(root).or_default();
+                echo_received.insert(from);
+                // -1 because we don't send msg to ourselves
+                if echo_received.len() >= node.min_honnest_nodes - 1 {
+                    self.send_ready(node, root);
+                }
+            }
+
+            BB_READY(root) => {
+                let ready_received = self.state.ready_received.entry(root).or_default();
+                ready_received.insert(from);
+                let readies = ready_received.len();
+                if readies > node.max_malicious_nodes {
+                    // At least one of the READY comes from an honnest node
+                    self.send_ready(node, root);
+                }
+                if readies >= node.min_honnest_nodes - 1 {
+                    self.state.delivered = Some(root);
+                }
+            }
+        }
+        node.debug(&self.state);
+        self.try_deliver(node)
+    }
+
+    /// Nodes echo the root of the values they hold, and deliver it once
+    /// enough nodes readied it
+    fn check(&self, node: &NodeInternals) -> Result<(), String> {
+        let state = &self.state;
+        let broken = if !state.echo && state.root.is_none() {
+            "echoed without a root"
+        } else if state.values.len() > state.len {
+            "holds more values than the batch"
+        } else {
+            match state.delivered {
+                Some(root) => {
+                    let readies = state.ready_received.get(&root).map_or(0, StateSet::len);
+                    if readies >= node.min_honnest_nodes - 1 {
+                        return Ok(());
+                    }
+                    "delivered without a quorum of READY"
+                }
+                None => return Ok(()),
+            }
+        };
+        Err(format!("{}: {:?}", broken, state))
+    }
+
+    /// Malicious node pushes a root no honnest node holds values for
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        let root = rng.next_u64();
+        match rng.gen_range(2) {
+            0 => Some(Box::new(BB_ECHO(root))),
+            _ => Some(Box::new(BB_READY(root))),
+        }
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.state.root {
+            Some(root) => vec![("root", format!("{:x}", root))],
+            None => vec![],
+        }
+    }
+}
+
+/// Messages, state and thresholds of the batched broadcast
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    let tree = MerkleTree::new(&[0]);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(BB_LEADER(vec![0])),
+            MessageSchema::of(BB_VALUE {
+                root: tree.root(),
+                len: 1,
+                value: 0,
+                proof: tree.proof(0),
+            }),
+            MessageSchema::of(BB_ECHO(tree.root())),
+            MessageSchema::of(BB_READY(tree.root())),
+        ],
+        state: vec![
+            "root",
+            "len",
+            "values",
+            "echo_received",
+            "ready_received",
+            "delivered",
+        ],
+        thresholds: vec![
+            Threshold::new(
+                "echo_quorum",
+                quorum.saturating_sub(1),
+                "n - f - 1 ECHOes of the other nodes for a root to READY it",
+            ),
+            Threshold::new(
+                "ready_amplification",
+                f + 1,
+                "f + 1 READY hold an honest one, the node READYs too",
+            ),
+            Threshold::new(
+                "ready_delivery",
+                quorum.saturating_sub(1),
+                "n - f - 1 READY of the other nodes, and the values of the batch",
+            ),
+        ],
+        ..ProtocolDescription::new(BATCHED_BROADCAST)
+    }
+}
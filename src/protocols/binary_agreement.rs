@@ -0,0 +1,534 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Asynchronous binary agreement of Mostéfaoui, Moumen and Raynal: nodes
+/// vote in rounds until the values they saw agree with a common coin
+#[derive(Clone, PartialEq)]
+pub(crate) enum AgreementMessage {
+    // Value the node may decide in a round
+    BA_BVAL(u32, bool),
+    // Value of the node among the ones supported by enough nodes
+    BA_AUX(u32, bool),
+    // Node decided and stops voting, it counts as BVAL and AUX of its
+    // value in every later round
+    BA_TERM(bool),
+}
+use AgreementMessage::*;
+
+impl ProtocolMessage for AgreementMessage {
+    const PROTOCOL: ProtocolId = BINARY_AGREEMENT;
+
+    fn phase(&self) -> Phase {
+        Phase::Vote
+    }
+
+    /// Kind, round and value
+    fn size(&self) -> usize {
+        1 + std::mem::size_of::<u32>() + 1
+    }
+
+    /// Any value but `MALICIOUS_VALUE` is a vote for true
+    fn with_value(&self, v: Value) -> Self {
+        let b = v != MALICIOUS_VALUE;
+        match self {
+            BA_BVAL(round, _) => BA_BVAL(*round, b),
+            BA_AUX(round, _) => BA_AUX(*round, b),
+            BA_TERM(_) => BA_TERM(b),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        match self {
+            BA_BVAL(round, b) => BA_BVAL(*round, !b),
+            BA_AUX(round, b) => BA_AUX(*round, !b),
+            BA_TERM(b) => BA_TERM(!b),
+        }
+    }
+
+    /// Nodes may support both values in BVAL, not in AUX and TERM
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            BA_BVAL(..) => None,
+            BA_AUX(round, b) => Some(Commitment::new("AUX", *round as u64, *b as u64)),
+            BA_TERM(b) => Some(Commitment::new("TERM", 0, *b as u64)),
+        }
+    }
+}
+
+impl fmt::Debug for AgreementMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BA_BVAL(round, b) => write!(f, "<BVAL, {}, {}>", round, *b as u8),
+            BA_AUX(round, b) => write!(f, "<AUX, {}, {}>", round, *b as u8),
+            BA_TERM(b) => write!(f, "<TERM, {}>", *b as u8),
+        }
+    }
+}
+
+/// Votes received in a round, indexed by value
+#[derive(Debug, Default)]
+struct Round {
+    bval_received: [BTreeSet<NodeId>; 2],
+    bval_sent: [bool; 2],
+    // Values with enough BVAL, an honest node sent them
+    bin_values: BTreeSet<bool>,
+    // First AUX of every node
+    aux_received: BTreeMap<NodeId, bool>,
+    aux_sent: bool,
+    // Values of the AUX the node waited for, set when it tosses the coin
+    values: Option<BTreeSet<bool>>,
+}
+
+/// Binary agreement as run by a node, outputs 0 or 1
+/// Nodes without an input yet only record the votes they receive
+/// The coin of every round is tossed by a common coin sub-instance tagged
+/// with the round, nodes count the `rounds` they went through before
+/// deciding, see `NodeInternals::count`
+pub(crate) struct BinaryAgreement {
+    round: u32,
+    // Value the node votes for in the current round
+    est: Option<bool>,
+    rounds: BTreeMap<u32, Round>,
+    coins: BTreeMap<u32, bool>,
+    term_received: [BTreeSet<NodeId>; 2],
+    decided: Option<bool>,
+}
+
+impl BinaryAgreement {
+    pub fn new() -> Self {
+        BinaryAgreement {
+            round: 0,
+            est: None,
+            rounds: BTreeMap::new(),
+            coins: BTreeMap::new(),
+            term_received: [BTreeSet::new(), BTreeSet::new()],
+            decided: None,
+        }
+    }
+
+    fn send_bval(&mut self, node: &mut NodeInternals, b: bool) {
+        let round = self.rounds.entry(self.round).or_default();
+        if round.bval_sent[b as usize] {
+            return;
+        }
+        round.bval_sent[b as usize] = true;
+        round.bval_received[b as usize].insert(node.id);
+        node.send_to_all(node.msg(BA_BVAL(self.round, b)));
+    }
+
+    /// Decide `b` in the round `rounds` of the node, counted in `rounds`
+    fn decide(&mut self, node: &mut NodeInternals, b: bool, rounds: u32) -> ProtocolState {
+        self.decided = Some(b);
+        node.count("rounds", rounds as usize);
+        self.term_received[b as usize].insert(node.id);
+        node.send_to_all(node.msg(BA_TERM(b)));
+        node.deliver(b as Value)
+    }
+
+    /// Go through the rounds as far as the votes received allow
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        if self.decided.is_some() {
+            return ProtocolState::InProcess;
+        }
+        // At least one honest node decided
+        for b in [false, true] {
+            if self.term_received[b as usize].len() > node.max_malicious_nodes {
+                return self.decide(node, b, self.round + 1);
+            }
+        }
+        let est = match self.est {
+            Some(est) => est,
+            None => return ProtocolState::InProcess,
+        };
+        self.send_bval(node, est);
+
+        loop {
+            // Relay values supported by an honest node, keep the ones
+            // supported by enough of them
+            for b in [false, true] {
+                let round = self.rounds.entry(self.round).or_default();
+                let bval = round.bval_received[b as usize]
+                    .union(&self.term_received[b as usize])
+                    .count();
+                if bval > node.max_malicious_nodes {
+                    self.send_bval(node, b);
+                }
+                let round = self.rounds.entry(self.round).or_default();
+                if bval >= node.min_honnest_nodes && round.bin_values.insert(b) && !round.aux_sent {
+                    round.aux_sent = true;
+                    round.aux_received.insert(node.id, b);
+                    node.send_to_all(node.msg(BA_AUX(self.round, b)));
+                }
+            }
+
+            let round = self.rounds.entry(self.round).or_default();
+            let mut voters: BTreeMap<NodeId, bool> = BTreeMap::new();
+            for b in round.bin_values.iter() {
+                for id in self.term_received[*b as usize].iter() {
+                    voters.insert(*id, *b);
+                }
+            }
+            for (id, b) in round.aux_received.iter() {
+                if round.bin_values.contains(b) {
+                    voters.entry(*id).or_insert(*b);
+                }
+            }
+            if voters.len() < node.min_honnest_nodes {
+                return ProtocolState::InProcess;
+            }
+
+            if round.values.is_none() {
+                round.values = Some(voters.values().cloned().collect());
+                node.spawn(COMMON_COIN, self.round, 0);
+            }
+            let s = match self.coins.get(&self.round) {
+                Some(s) => *s,
+                None => return ProtocolState::InProcess,
+            };
+            let values = round.values.clone().unwrap_or_default();
+            self.round += 1;
+            match values.iter().next() {
+                Some(b) if values.len() == 1 => {
+                    if *b == s {
+                        return self.decide(node, *b, self.round);
+                    }
+                    self.est = Some(*b);
+                    self.send_bval(node, *b);
+                }
+                _ => {
+                    self.est = Some(s);
+                    self.send_bval(node, s);
+                }
+            }
+        }
+    }
+}
+
+impl Protocol for BinaryAgreement {
+    /// Any input but 0 is a vote for 1
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        if self.est.is_none() {
+            self.est = Some(input != 0);
+        }
+        self.progress(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<AgreementMessage>() {
+            Some(BA_BVAL(round, b)) => {
+                let round = self.rounds.entry(*round).or_default();
+                round.bval_received[*b as usize].insert(from);
+            }
+            Some(BA_AUX(round, b)) => {
+                let round = self.rounds.entry(*round).or_default();
+                round.aux_received.entry(from).or_insert(*b);
+            }
+            Some(BA_TERM(b)) => {
+                self.term_received[*b as usize].insert(from);
+            }
+            None => return ProtocolState::InProcess,
+        }
+        self.progress(node)
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        if child.protocol() != COMMON_COIN {
+            return ProtocolState::InProcess;
+        }
+        if let Some(v) = output.value() {
+            self.coins.entry(child.tag()).or_insert(v != 0);
+        }
+        self.progress(node)
+    }
+
+    /// Malicious node votes at random in the first round
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(BA_BVAL(0, rng.gen_bool(0.5))))
+    }
+}
+
+/// Randomized binary agreement of Ben-Or: nodes report their value, propose
+/// it if a majority of the reports agree, decide it if a majority of the
+/// proposals do, and toss their own coin when no value is supported
+/// Each node draws its coin, agreement takes an expected exponential number
+/// of rounds but needs no shared randomness
+/// Tolerates `t` faulty nodes with `n > 5t`
+#[derive(Clone, PartialEq)]
+pub(crate) enum BenOrMessage {
+    BO_REPORT(u32, bool),
+    // None: the reports did not agree
+    BO_PROPOSAL(u32, Option<bool>),
+}
+use BenOrMessage::*;
+
+impl ProtocolMessage for BenOrMessage {
+    const PROTOCOL: ProtocolId = BEN_OR;
+
+    fn phase(&self) -> Phase {
+        Phase::Vote
+    }
+
+    /// Kind, round and value
+    fn size(&self) -> usize {
+        1 + std::mem::size_of::<u32>() + 1
+    }
+
+    /// Any value but `MALICIOUS_VALUE` is a vote for true
+    fn with_value(&self, v: Value) -> Self {
+        let b = v != MALICIOUS_VALUE;
+        match self {
+            BO_REPORT(round, _) => BO_REPORT(*round, b),
+            BO_PROPOSAL(round, _) => BO_PROPOSAL(*round, Some(b)),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        match self {
+            BO_REPORT(round, b) => BO_REPORT(*round, !b),
+            BO_PROPOSAL(round, b) => BO_PROPOSAL(*round, Some(!b.unwrap_or(false))),
+        }
+    }
+
+    /// Proposals of no value are 2
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            BO_REPORT(round, b) => Some(Commitment::new("REPORT", *round as u64, *b as u64)),
+            BO_PROPOSAL(round, b) => {
+                let value = b.map_or(2, |b| b as u64);
+                Some(Commitment::new("PROPOSAL", *round as u64, value))
+            }
+        }
+    }
+}
+
+impl fmt::Debug for BenOrMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BO_REPORT(round, b) => write!(f, "<REPORT, {}, {}>", round, *b as u8),
+            BO_PROPOSAL(round, Some(b)) => write!(f, "<PROPOSAL, {}, {}>", round, *b as u8),
+            BO_PROPOSAL(round, None) => write!(f, "<PROPOSAL, {}, ?>", round),
+        }
+    }
+}
+
+/// Messages received in a Ben-Or round, the first of every node
+#[derive(Debug, Default)]
+struct BenOrRound {
+    reports: BTreeMap<NodeId, bool>,
+    proposals: BTreeMap<NodeId, Option<bool>>,
+    reported: bool,
+    proposed: bool,
+}
+
+/// Ben-Or agreement as run by a node, outputs 0 or 1
+/// Counts the `rounds` the node went through before deciding, see
+/// `NodeInternals::count`
+pub(crate) struct BenOr {
+    round: u32,
+    x: Option<bool>,
+    rounds: BTreeMap<u32, BenOrRound>,
+}
+
+impl BenOr {
+    pub fn new() -> Self {
+        BenOr {
+            round: 0,
+            x: None,
+            rounds: BTreeMap::new(),
+        }
+    }
+
+    /// Faulty nodes tolerated
+    fn faulty(num_nodes: usize) -> usize {
+        (num_nodes.max(1) - 1) / 5
+    }
+
+    fn report(&mut self, node: &mut NodeInternals, round: u32, b: bool) {
+        let state = self.rounds.entry(round).or_default();
+        if !state.reported {
+            state.reported = true;
+            state.reports.insert(node.id, b);
+            node.send_to_all(node.msg(BO_REPORT(round, b)));
+        }
+    }
+
+    fn propose(&mut self, node: &mut NodeInternals, round: u32, b: Option<bool>) {
+        let state = self.rounds.entry(round).or_default();
+        if !state.proposed {
+            state.proposed = true;
+            state.proposals.insert(node.id, b);
+            node.send_to_all(node.msg(BO_PROPOSAL(round, b)));
+        }
+    }
+
+    /// Every honest node ends the next round with `b`, the node takes its
+    /// part in it before it stops
+    fn decide(&mut self, node: &mut NodeInternals, b: bool) -> ProtocolState {
+        let next = self.round + 1;
+        self.report(node, next, b);
+        self.propose(node, next, Some(b));
+        node.count("rounds", next as usize);
+        node.deliver(b as Value)
+    }
+
+    /// Go through the rounds as far as the messages received allow
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let (n, t) = (node.num_nodes, BenOr::faulty(node.num_nodes));
+        // More than (n + t) / 2 of the nodes
+        let majority = |k: usize| 2 * k > n + t;
+        loop {
+            let x = match self.x {
+                Some(x) => x,
+                None => return ProtocolState::InProcess,
+            };
+            self.report(node, self.round, x);
+
+            let state = self.rounds.entry(self.round).or_default();
+            if !state.proposed {
+                if state.reports.len() < n - t {
+                    return ProtocolState::InProcess;
+                }
+                let ones = state.reports.values().filter(|b| **b).count();
+                let zeros = state.reports.len() - ones;
+                let proposal = match (majority(zeros), majority(ones)) {
+                    (true, _) => Some(false),
+                    (_, true) => Some(true),
+                    _ => None,
+                };
+                self.propose(node, self.round, proposal);
+            }
+
+            let state = self.rounds.entry(self.round).or_default();
+            if state.proposals.len() < n - t {
+                return ProtocolState::InProcess;
+            }
+            let support = |b: bool| {
+                state
+                    .proposals
+                    .values()
+                    .filter(|proposal| **proposal == Some(b))
+                    .count()
+            };
+            let (zeros, ones) = (support(false), support(true));
+            let (b, k) = if ones > zeros {
+                (true, ones)
+            } else {
+                (false, zeros)
+            };
+            if majority(k) {
+                return self.decide(node, b);
+            }
+            self.x = match k > t {
+                true => Some(b),
+                false => Some(node.rng.gen_bool(0.5)),
+            };
+            self.round += 1;
+        }
+    }
+}
+
+impl Protocol for BenOr {
+    /// Any input but 0 is a vote for 1
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        if self.x.is_none() {
+            self.x = Some(input != 0);
+        }
+        self.progress(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<BenOrMessage>() {
+            Some(BO_REPORT(round, b)) => {
+                let state = self.rounds.entry(*round).or_default();
+                state.reports.entry(from).or_insert(*b);
+            }
+            Some(BO_PROPOSAL(round, b)) => {
+                let state = self.rounds.entry(*round).or_default();
+                state.proposals.entry(from).or_insert(*b);
+            }
+            None => return ProtocolState::InProcess,
+        }
+        self.progress(node)
+    }
+
+    /// Malicious node reports at random in the first round
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(BO_REPORT(0, rng.gen_bool(0.5))))
+    }
+}
+
+/// Messages, state and thresholds of the agreement
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(BA_BVAL(0, false)),
+            MessageSchema::of(BA_AUX(0, false)),
+            MessageSchema::of(BA_TERM(false)),
+        ],
+        state: vec![
+            "round",
+            "est",
+            "rounds",
+            "coins",
+            "term_received",
+            "decided",
+        ],
+        thresholds: vec![
+            Threshold::new(
+                "bval_relay",
+                f + 1,
+                "f + 1 BVAL hold an honest one, the node sends BVAL of the value too",
+            ),
+            Threshold::new(
+                "bin_values",
+                quorum,
+                "n - f BVAL of a value to take it into the values of the round",
+            ),
+            Threshold::new(
+                "aux_quorum",
+                quorum,
+                "n - f AUX of values of the round before tossing the coin",
+            ),
+            Threshold::new(
+                "term_decision",
+                f + 1,
+                "f + 1 TERM hold an honest one, the node decides their value",
+            ),
+        ],
+        children: vec![COMMON_COIN],
+        ..ProtocolDescription::new(BINARY_AGREEMENT)
+    }
+}
+
+/// Messages, state and thresholds of Ben-Or's agreement
+pub fn describe_ben_or(num_nodes: usize) -> ProtocolDescription {
+    let t = BenOr::faulty(num_nodes);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(BO_REPORT(0, false)),
+            MessageSchema::of(BO_PROPOSAL(0, Some(false))),
+        ],
+        state: vec!["round", "x", "rounds"],
+        thresholds: vec![
+            Threshold::new("max_faulty", t, "(n - 1) / 5, the agreement needs n > 5t"),
+            Threshold::new(
+                "majority",
+                (num_nodes + t) / 2 + 1,
+                "more than (n + t) / 2 REPORT or PROPOSAL of a value",
+            ),
+        ],
+        ..ProtocolDescription::new(BEN_OR)
+    }
+}
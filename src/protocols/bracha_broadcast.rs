@@ -1,18 +1,223 @@
-use crate::network::{Message::*, *};
+#[cfg(feature = "crypto")]
+use crate::cost::CryptoOp;
+#[cfg(feature = "crypto")]
+use crate::crypto::threshold_sig::{SignatureConfig, SignatureShare};
+use crate::network::*;
 use crate::node::*;
-use std::collections::{HashMap, HashSet};
+use crate::protocols::*;
+use crate::quorum::Threshold;
+#[cfg(feature = "crypto")]
+use crate::rng::mix;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use log::debug;
+use std::cmp::Reverse;
+#[cfg(feature = "crypto")]
+use std::collections::BTreeMap;
 use std::fmt;
-use rand::{
-    distributions::{Distribution, Standard},
-    Rng,
-};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Rule picking the value to READY when ECHOes of several values compete
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum TieBreak {
+    // Wait for ECHOes of the same value from n - f nodes, as in Bracha's
+    // protocol
+    #[default]
+    Quorum,
+    // Once n - f nodes have echoed, READY the most echoed value, the lowest
+    // one among equally echoed values
+    Plurality,
+    // Once n - f nodes have echoed, READY the lowest echoed value
+    Lowest,
+    // Once n - f nodes have echoed, READY the value the node echoed itself
+    OwnEcho,
+}
+
+impl FromStr for TieBreak {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "quorum" => Ok(TieBreak::Quorum),
+            "plurality" => Ok(TieBreak::Plurality),
+            "lowest" => Ok(TieBreak::Lowest),
+            "own-echo" => Ok(TieBreak::OwnEcho),
+            _ => Err(format!("Unknown tie-break rule: {}", s)),
+        }
+    }
+}
+
+/// How honest nodes relay the broadcast, Bracha's protocol by default
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Strategy {
+    // Forward the first INIT to every node, it reaches the nodes the links
+    // of the leader miss or lose it on
+    pub eager_init: bool,
+    // Wait for INIT or ECHO of a value from f + 1 nodes before echoing it
+    // instead of echoing the INIT of the leader
+    pub lazy_echo: bool,
+    // Send READY after f + 1 READY, otherwise deliver on 2f + 1 READY
+    // without having sent READY
+    pub relay_ready: bool,
+}
+
+impl Default for Strategy {
+    fn default() -> Self {
+        Strategy::STANDARD
+    }
+}
+
+impl Strategy {
+    pub const STANDARD: Strategy = Strategy {
+        eager_init: false,
+        lazy_echo: false,
+        relay_ready: true,
+    };
+
+    /// Name of the variant, as parsed
+    pub fn name(&self) -> String {
+        let mut parts = vec![];
+        if self.eager_init {
+            parts.push("eager-init");
+        }
+        if self.lazy_echo {
+            parts.push("lazy-echo");
+        }
+        if !self.relay_ready {
+            parts.push("no-relay");
+        }
+        if parts.is_empty() {
+            parts.push("standard");
+        }
+        parts.join("+")
+    }
+}
+
+/// Variants combined with `+`, e.g. `eager-init+lazy-echo`
+impl FromStr for Strategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut strategy = Strategy::STANDARD;
+        for part in s.to_lowercase().split('+') {
+            match part {
+                "standard" => (),
+                "eager-init" => strategy.eager_init = true,
+                "lazy-echo" => strategy.lazy_echo = true,
+                "no-relay" => strategy.relay_ready = false,
+                _ => return Err(format!("Unknown strategy: {}", part)),
+            }
+        }
+        Ok(strategy)
+    }
+}
+
+/// Version tags of the releases of the broadcast
+pub const VERSIONS: [&str; 3] = ["1.0", "1.1", "2.0"];
+
+/// Releases of the broadcast, nodes of a rolling upgrade run several ones
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BroadcastVersion {
+    // Original release
+    #[default]
+    V1_0,
+    // 1.0 with a shim reading the frames of 2.0
+    V1_1,
+    // Sends frames and waits for ceil((n + f + 1) / 2) ECHOes, reads the
+    // messages of 1.x
+    V2_0,
+}
+
+impl BroadcastVersion {
+    pub fn tag(&self) -> &'static str {
+        match self {
+            BroadcastVersion::V1_0 => VERSIONS[0],
+            BroadcastVersion::V1_1 => VERSIONS[1],
+            BroadcastVersion::V2_0 => VERSIONS[2],
+        }
+    }
+
+    fn reads_frames(&self) -> bool {
+        *self != BroadcastVersion::V1_0
+    }
+
+    fn sends_frames(&self) -> bool {
+        *self == BroadcastVersion::V2_0
+    }
+
+    /// Number of ECHO of a value to READY it
+    /// -1 because we don't send msg to ourselves
+    fn echo_quorum(&self, node: &NodeInternals) -> usize {
+        match self {
+            BroadcastVersion::V2_0 => (node.num_nodes + node.max_malicious_nodes + 2) / 2 - 1,
+            _ => node.min_honnest_nodes - 1,
+        }
+    }
+}
+
+impl FromStr for BroadcastVersion {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "1.0" => Ok(BroadcastVersion::V1_0),
+            "1.1" => Ok(BroadcastVersion::V1_1),
+            "2.0" => Ok(BroadcastVersion::V2_0),
+            _ => Err(format!("Unknown broadcast version: {}", s)),
+        }
+    }
+}
+
+/// Parameters of the broadcast
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BroadcastConfig {
+    // Values a leader can legitimately propose, any value if empty
+    // Honest nodes ignore messages carrying other values
+    pub domain: Vec<Value>,
+    pub tie_break: TieBreak,
+    // Behaviour of honest nodes
+    pub strategy: Strategy,
+    // Time without progress after which a node gives up with
+    // `Output::Stalled`, None to wait forever
+    // Bracha broadcast alone never terminates when the leader is faulty and
+    // silent, nothing tells the nodes it won't speak
+    pub liveness_timeout: Option<Duration>,
+    // Period after which a node that hasn't delivered asks the others for
+    // their READY, None to never ask
+    // Lost READY are only sent again to the nodes asking for them, by the
+    // nodes still handling messages
+    pub catch_up: Option<Duration>,
+    // READY carry the signature share of the node on the value, nodes
+    // deliver the certificate of the READY they delivered on and drop the
+    // unsigned ones
+    #[cfg(feature = "crypto")]
+    pub signed: bool,
+    // Every message carries the signature of its sender, honest nodes drop
+    // the messages not signed by the node they come from
+    #[cfg(feature = "crypto")]
+    pub authenticated: bool,
+}
+
+impl BroadcastConfig {
+    pub fn allows(&self, v: Value) -> bool {
+        self.domain.is_empty() || self.domain.contains(&v)
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct BroadcastState {
     echo: bool,
     ready: bool,
-    echo_received: HashMap<Value, HashSet<NodeId>>,
-    ready_received: HashMap<Value, HashSet<NodeId>>,
+    // Values sent in ECHO and READY
+    echoed: Option<Value>,
+    readied: Option<Value>,
+    // INIT has been forwarded
+    forwarded: bool,
+    // Nodes that sent INIT or ECHO of each value
+    support: StateMap<Value, StateSet<NodeId>>,
+    echo_received: StateMap<Value, StateSet<NodeId>>,
+    ready_received: StateMap<Value, StateSet<NodeId>>,
 }
 
 impl BroadcastState {
@@ -20,13 +225,17 @@ impl BroadcastState {
         BroadcastState {
             echo: true,
             ready: true,
-            echo_received: HashMap::new(),
-            ready_received: HashMap::new(),
+            echoed: None,
+            readied: None,
+            forwarded: false,
+            support: StateMap::new(),
+            echo_received: StateMap::new(),
+            ready_received: StateMap::new(),
         }
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, PartialEq)]
 pub(crate) enum BroadcastMessage {
     BC_LEADER(Value),
     BC_INIT(Value),
@@ -36,113 +245,827 @@ pub(crate) enum BroadcastMessage {
 use BroadcastMessage::*;
 
 impl BroadcastMessage {
-    pub(crate) fn malicious(&self) -> Self {
+    pub(crate) fn value(&self) -> Value {
+        match self {
+            BC_LEADER(v) | BC_INIT(v) | BC_ECHO(v) | BC_READY(v) => *v,
+        }
+    }
+
+    /// ECHO or READY of the malicious value
+    pub(crate) fn random(rng: &mut dyn Rng) -> Self {
+        match rng.gen_range(2) {
+            0 => BC_ECHO(MALICIOUS_VALUE),
+            _ => BC_READY(MALICIOUS_VALUE),
+        }
+    }
+}
+
+impl ProtocolMessage for BroadcastMessage {
+    const PROTOCOL: ProtocolId = BRACHA_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        match self {
+            BC_LEADER(_) => Phase::Leader,
+            BC_INIT(_) => Phase::Init,
+            BC_ECHO(_) => Phase::Echo,
+            BC_READY(_) => Phase::Ready,
+        }
+    }
+
+    /// Kind and value
+    fn size(&self) -> usize {
+        1 + std::mem::size_of::<Value>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            BC_LEADER(_) => BC_LEADER(v),
+            BC_INIT(_) => BC_INIT(v),
+            BC_ECHO(_) => BC_ECHO(v),
+            BC_READY(_) => BC_READY(v),
+        }
+    }
+
+    fn malicious(&self) -> Self {
         match self {
             BC_LEADER(_) => BC_LEADER(MALICIOUS_VALUE),
             BC_INIT(_) => BC_INIT(MALICIOUS_VALUE),
-            BC_ECHO(_) =>BC_ECHO(MALICIOUS_VALUE),
+            BC_ECHO(_) => BC_ECHO(MALICIOUS_VALUE),
             BC_READY(_) => BC_ECHO(MALICIOUS_VALUE),
         }
     }
+
+    /// Nodes echo and ready a single value
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            BC_ECHO(v) => Some(Commitment::new("ECHO", 0, *v as u64)),
+            BC_READY(v) => Some(Commitment::new("READY", 0, *v as u64)),
+            _ => None,
+        }
+    }
+
+    fn value(&self) -> Option<Value> {
+        match self {
+            BC_LEADER(v) | BC_INIT(v) | BC_ECHO(v) | BC_READY(v) => Some(*v),
+        }
+    }
+}
+
+/// Bracha reliable broadcast as run by a node
+pub(crate) struct BrachaBroadcast {
+    config: BroadcastConfig,
+    state: BroadcastState,
+    handler: Handler,
+    version: BroadcastVersion,
+    // Broadcast messages handled so far and at the last liveness check
+    received: usize,
+    checked: usize,
+    delivered: bool,
+    // Shares of the signed READY of the other nodes, by value
+    #[cfg(feature = "crypto")]
+    shares: StateMap<Value, StateMap<NodeId, SignatureShare>>,
+}
+
+impl BrachaBroadcast {
+    pub fn new(config: BroadcastConfig) -> Self {
+        BrachaBroadcast {
+            config,
+            state: BroadcastState::new(),
+            handler: Handler::Current,
+            version: BroadcastVersion::default(),
+            received: 0,
+            checked: 0,
+            delivered: false,
+            #[cfg(feature = "crypto")]
+            shares: StateMap::new(),
+        }
+    }
+
+    /// Give up unless a broadcast message arrived since the last check
+    fn check_liveness(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        if self.delivered {
+            return ProtocolState::InProcess;
+        }
+        if self.received == self.checked {
+            debug!("NODE {} stalled after {} messages", node.id, self.received);
+            self.delivered = true;
+            return ProtocolState::Terminated(Output::Stalled);
+        }
+        self.checked = self.received;
+        if let Some(timeout) = self.config.liveness_timeout {
+            node.schedule(timeout, node.msg(LivenessCheck));
+        }
+        ProtocolState::InProcess
+    }
+
+    /// Timer of the node: ask the others for their READY until delivery
+    /// Request of another node: send it the READY of the node again
+    fn state_transfer(&mut self, node: &mut NodeInternals, from: NodeId) -> ProtocolState {
+        if from == node.id {
+            if self.delivered {
+                return ProtocolState::InProcess;
+            }
+            node.count("state_requests", 1);
+            node.send_to_all(node.msg(StateRequest));
+            if let Some(period) = self.config.catch_up {
+                node.schedule(period, node.msg(StateRequest));
+            }
+        } else if let Some(v) = self.state.readied {
+            node.count("state_transfers", 1);
+            node.send_to(from, ready(node, &self.config, self.version, v));
+        }
+        ProtocolState::InProcess
+    }
+
+    /// Message in the format of the release of the node, through the shim
+    /// for messages of other releases
+    /// Signed READY are READY once their share is checked, and so are the
+    /// other signed messages
+    /// Authenticated broadcasts only take unsigned messages from the network
+    /// Certified broadcasts only take signed READY from the other nodes
+    #[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+    fn decode(
+        &mut self,
+        node: &NodeInternals,
+        from: NodeId,
+        msg: &Envelope,
+    ) -> Option<BroadcastMessage> {
+        #[cfg(feature = "crypto")]
+        if let Some(signed) = msg.open::<SignedReady>() {
+            node.crypto(CryptoOp::Verify, 1);
+            let signatures = node.config.read().unwrap().signatures.clone();
+            if signed.verify(&signatures, from) {
+                self.shares
+                    .entry(signed.value)
+                    .or_default()
+                    .entry(from)
+                    .or_insert(signed.share);
+                return Some(BC_READY(signed.value));
+            }
+            node.count("invalid_signatures", 1);
+            return None;
+        }
+        #[cfg(feature = "crypto")]
+        if let Some(signed) = msg.open::<SignedBroadcast>() {
+            node.crypto(CryptoOp::Verify, 1);
+            let signatures = node.config.read().unwrap().signatures.clone();
+            if signed.verify(&signatures, from) {
+                return Some(signed.msg.clone());
+            }
+            node.count("invalid_signatures", 1);
+            return None;
+        }
+        let msg = match msg.open::<BroadcastMessage>() {
+            Some(msg) => Some(msg.clone()),
+            None => match msg.open::<BroadcastFrame>() {
+                Some(frame) if self.version.reads_frames() => frame.message(),
+                _ => None,
+            },
+        };
+        #[cfg(feature = "crypto")]
+        if msg.is_some() && self.config.authenticated && from != NETWORK_ID {
+            node.count("invalid_signatures", 1);
+            return None;
+        }
+        #[cfg(feature = "crypto")]
+        if matches!(msg, Some(BC_READY(_))) && self.config.signed && from != node.id {
+            node.count("invalid_signatures", 1);
+            return None;
+        }
+        msg
+    }
+
+    /// Certificate of the READY of `v` the node delivered on, its own
+    /// included if it readied `v`
+    /// Nodes that deliver without sending READY hold one signature short
+    /// of the quorum
+    #[cfg(feature = "crypto")]
+    fn certificate(&self, node: &NodeInternals, v: Value) -> DeliveryCertificate {
+        let mut signatures: BTreeMap<NodeId, SignatureShare> = self
+            .shares
+            .get(&v)
+            .into_iter()
+            .flatten()
+            .map(|(id, share)| (*id, *share))
+            .collect();
+        if self.state.readied == Some(v) {
+            // Same share as the one its READY carried
+            let scheme = node.config.read().unwrap().signatures.scheme.clone();
+            let share = scheme.sign_share(&node.signing_key, DeliveryCertificate::message(v));
+            signatures.insert(node.id, share);
+        }
+        DeliveryCertificate {
+            value: v,
+            signatures,
+        }
+    }
+}
+
+impl Protocol for BrachaBroadcast {
+    /// Node is the initiator of the broadcast
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        let leader = node.id;
+        handle_broadcast(
+            node,
+            &self.config,
+            &mut self.state,
+            self.handler,
+            self.version,
+            leader,
+            BC_LEADER(input),
+        )
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        if msg.open::<LivenessCheck>().is_some() && from == node.id {
+            return self.check_liveness(node);
+        }
+        if msg.open::<StateRequest>().is_some() {
+            return self.state_transfer(node, from);
+        }
+        let state = match self.decode(node, from, msg) {
+            Some(bc_msg) => {
+                self.received += 1;
+                handle_broadcast(
+                    node,
+                    &self.config,
+                    &mut self.state,
+                    self.handler,
+                    self.version,
+                    from,
+                    bc_msg,
+                )
+            }
+            None => ProtocolState::InProcess,
+        };
+        match state {
+            #[cfg(feature = "crypto")]
+            ProtocolState::Terminated(Output::Value(v)) if self.config.signed => {
+                self.delivered = true;
+                ProtocolState::Terminated(Output::Certified(self.certificate(node, v)))
+            }
+            ProtocolState::Terminated(_) => {
+                self.delivered = true;
+                state
+            }
+            state => state,
+        }
+    }
+
+    /// Malicious node tries to corrupt the broadcast to
+    /// the value MALICIOUS_VALUE by
+    /// sending random ECHO and READY messages
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(BroadcastMessage::random(rng)))
+    }
+
+    /// Honest nodes echo and ready the same value
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![];
+        if let Some(v) = self.state.echoed {
+            fields.push(("echo", v.to_string()));
+        }
+        if let Some(v) = self.state.readied {
+            fields.push(("ready", v.to_string()));
+        }
+        fields
+    }
+
+    fn check(&self, node: &NodeInternals) -> Result<(), String> {
+        check_broadcast(node, &self.state, self.version.echo_quorum(node))
+    }
+
+    /// Legacy handler is the release that forgot the READY received before
+    /// the ECHO quorum and only delivered on a READY received after its own
+    fn switch(&mut self, handler: Handler) -> bool {
+        self.handler = handler;
+        true
+    }
+
+    fn set_version(&mut self, tag: &str) -> bool {
+        match tag.parse() {
+            Ok(version) => {
+                self.version = version;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+}
+
+/// Message in the wire format of `version`, signed when the broadcast is
+/// authenticated
+/// Signed messages are messages of 1.x, which 2.0 reads too
+#[cfg_attr(not(feature = "crypto"), allow(unused_variables))]
+fn wire(
+    node: &NodeInternals,
+    config: &BroadcastConfig,
+    version: BroadcastVersion,
+    msg: BroadcastMessage,
+) -> Message {
+    #[cfg(feature = "crypto")]
+    if config.authenticated {
+        node.crypto(CryptoOp::Sign, 1);
+        let scheme = node.config.read().unwrap().signatures.scheme.clone();
+        let share = scheme.sign_share(&node.signing_key, SignedBroadcast::message(&msg));
+        return node.msg(SignedBroadcast { msg, share });
+    }
+    if version.sends_frames() {
+        node.msg(BroadcastFrame::from(&msg))
+    } else {
+        node.msg(msg)
+    }
+}
+
+/// READY of `v`, signed when delivery is certified
+/// Signed READY are messages of 1.x, which 2.0 reads too
+fn ready(
+    node: &NodeInternals,
+    config: &BroadcastConfig,
+    version: BroadcastVersion,
+    v: Value,
+) -> Message {
+    #[cfg(feature = "crypto")]
+    if config.signed {
+        node.crypto(CryptoOp::Sign, 1);
+        let scheme = node.config.read().unwrap().signatures.scheme.clone();
+        return node.msg(SignedReady {
+            value: v,
+            share: scheme.sign_share(&node.signing_key, DeliveryCertificate::message(v)),
+        });
+    }
+    wire(node, config, version, BC_READY(v))
 }
 
 /// Handle messages related to broadcast
 pub(crate) fn handle_broadcast(
     node: &mut NodeInternals,
+    config: &BroadcastConfig,
+    state: &mut BroadcastState,
+    handler: Handler,
+    version: BroadcastVersion,
     from: NodeId,
     msg: BroadcastMessage,
 ) -> ProtocolState {
+    if !config.allows(msg.value()) {
+        // Value can't have been proposed by an honnest leader
+        return ProtocolState::InProcess;
+    }
+
     match msg {
         // Node has been chosen as an initiator for broadcast
         BC_LEADER(v) => {
-            node.send_to_all(BROADCAST(BC_INIT(v)));
-            node.send_to_all(BROADCAST(BC_ECHO(v)));
-            node.bc_state.echo = false;
+            node.send_to_all(wire(node, config, version, BC_INIT(v)));
+            node.send_to_all(wire(node, config, version, BC_ECHO(v)));
+            state.echo = false;
+            state.echoed = Some(v);
+            state.forwarded = true;
         }
 
-        // Initiator node has initiated a broadcast
+        // Initiator node has initiated a broadcast, or another node
+        // forwarded it
         BC_INIT(v) => {
-            if node.bc_state.echo {
+            if config.strategy.eager_init && !state.forwarded {
+                node.send_to_all(wire(node, config, version, BC_INIT(v)));
+                state.forwarded = true;
+            }
+            if config.strategy.lazy_echo {
+                lazy_echo(node, config, state, version, from, v);
+            } else if state.echo {
                 // We haven't sent ECHO yet
-                node.send_to_all(BROADCAST(BC_ECHO(v)));
-                node.bc_state.echo = false;
+                node.send_to_all(wire(node, config, version, BC_ECHO(v)));
+                state.echo = false;
+                state.echoed = Some(v);
             }
         }
 
         // Sender node have received a value from the initiator node
         BC_ECHO(v) => {
-            // First ECHO with this value v received
-            if node.bc_state.echo_received.get(&v).is_none() {
-                // Init hashset for value v
-                node.bc_state.echo_received.insert(v, HashSet::new());
+            if config.strategy.lazy_echo {
+                lazy_echo(node, config, state, version, from, v);
             }
 
-            let echo_v_received = node.bc_state.echo_received.get_mut(&v).unwrap();
+            // First ECHO with this value v received: init hashset for value v
+            let echo_v_received = state.echo_received.entry(v).or_default();
             // Add sender node to list of nodes who sent <ECHO, v>
             echo_v_received.insert(from);
 
-            if node.bc_state.ready {
+            if state.ready {
                 // We haven't sent READY yet
-                if echo_v_received.len() >= (node.min_honnest_nodes - 1) {
-                    // Potentially we have received ECHO from all the honnest
-                    // nodes and might not receive any more ECHO messages
-                    // -1 because we don't send msg to ourselved
-
-                    node.send_to_all(BROADCAST(BC_READY(v)));
-                    // Init hashset for value v
-                    node.bc_state.ready_received.insert(v, HashSet::new());
-                    node.bc_state.ready = false
+                // Potentially we have received ECHO from all the honnest
+                // nodes and might not receive any more ECHO messages
+                let quorum = version.echo_quorum(node);
+                if let Some(v) = echo_choice(state, v, quorum, config.tie_break) {
+                    node.send_ready(ready(node, config, version, v));
+                    if handler == Handler::Legacy {
+                        state.ready_received.insert(v, StateSet::new());
+                    }
+                    state.ready = false;
+                    state.readied = Some(v);
+                    // READY of the others may have arrived first
+                    if handler == Handler::Current && ready_quorum(node, state, v) {
+                        return node.deliver(v);
+                    }
                 }
             }
-            node.debug();
+            node.debug(state);
         }
 
         // Sender node know that other nodes have also received a
         // value from the initiator
         BC_READY(v) => {
-            // First <READY, v> received
-            if node.bc_state.ready_received.get(&v).is_none() {
-                // Init hashset for value v
-                node.bc_state.ready_received.insert(v, HashSet::new());
-            }
-
-            let ready_v_received = node.bc_state.ready_received.get_mut(&v).unwrap();
+            // First <READY, v> received: init hashset for value v
+            let ready_v_received = state.ready_received.entry(v).or_default();
             ready_v_received.insert(from);
 
-            if node.bc_state.ready {
+            if state.ready {
                 // We haven't sent READY yet
 
-                if ready_v_received.len() > node.max_malicious_nodes {
+                if !config.strategy.relay_ready {
+                    if ready_v_received.len() >= node.min_honnest_nodes - 1 {
+                        return node.deliver(v);
+                    }
+                } else if ready_v_received.len() > node.max_malicious_nodes {
                     // At least one of the READY comes from an honnest node
 
-                    node.send_to_all(BROADCAST(BC_READY(v)));
-                    node.bc_state.ready = false
+                    node.send_ready(ready(node, config, version, v));
+                    state.ready = false;
+                    state.readied = Some(v);
+                    if handler == Handler::Current && ready_quorum(node, state, v) {
+                        return node.deliver(v);
+                    }
                 }
-                node.debug();
-            } else if node.bc_state.ready_received.get(&v).unwrap().len()
-                >= node.min_honnest_nodes - 1
-            {
-                return ProtocolState::Terminated(v);
+                node.debug(state);
+            } else if ready_quorum(node, state, v) {
+                return node.deliver(v);
             }
         }
     }
     ProtocolState::InProcess
 }
 
+/// Invariants of the state left by `handle_broadcast`: ECHO and READY
+/// are sent with a value, READY once `quorum` nodes echoed or f + 1 nodes
+/// readied
+fn check_broadcast(
+    node: &NodeInternals,
+    state: &BroadcastState,
+    quorum: usize,
+) -> Result<(), String> {
+    let broken = if state.echo == state.echoed.is_some() {
+        "echo flag disagrees with the value echoed"
+    } else if state.ready == state.readied.is_some() {
+        "ready flag disagrees with the value readied"
+    } else {
+        match state.readied {
+            Some(v) => {
+                let echoers: StateSet<&NodeId> = state.echo_received.values().flatten().collect();
+                let readies = state.ready_received.get(&v).map_or(0, StateSet::len);
+                if echoers.len() >= quorum || readies > node.max_malicious_nodes {
+                    return Ok(());
+                }
+                "readied without a quorum of ECHO or f + 1 READY"
+            }
+            None => return Ok(()),
+        }
+    };
+    Err(format!("{}: {:?}", broken, state))
+}
 
-/// Malicious node tries to corrupt the broadcast to
-/// the value MALICIOUS_VALUE by
-/// sending random ECHO and READY messages
-pub(crate) fn random_broadcast(
+/// Lazy variant: echo `v` once f + 1 nodes sent INIT or ECHO of it, at
+/// least one of them is honnest
+/// Unless INIT is forwarded the leader is the only support of a value
+fn lazy_echo(
     node: &mut NodeInternals,
-    _from: NodeId,
-    _msg: BroadcastMessage,
-) -> ProtocolState {
-    let random_msg: BroadcastMessage = rand::random();
-    node.send_to_all(BROADCAST(random_msg));
-    ProtocolState::InProcess
+    config: &BroadcastConfig,
+    state: &mut BroadcastState,
+    version: BroadcastVersion,
+    from: NodeId,
+    v: Value,
+) {
+    let support = state.support.entry(v).or_default();
+    support.insert(from);
+    if state.echo && support.len() > node.max_malicious_nodes {
+        node.send_to_all(wire(node, config, version, BC_ECHO(v)));
+        state.echo = false;
+        state.echoed = Some(v);
+    }
+}
+
+/// Enough READY for `v` to deliver it
+/// -1 because we don't send msg to ourselves
+fn ready_quorum(node: &NodeInternals, state: &BroadcastState, v: Value) -> bool {
+    state
+        .ready_received
+        .get(&v)
+        .is_some_and(|ready| ready.len() >= node.min_honnest_nodes - 1)
+}
+
+/// Value to READY after receiving an ECHO of `v`, None to keep waiting
+/// `quorum` ECHOes of the same value are always enough, the tie-break rule
+/// decides once `quorum` nodes have echoed competing values
+fn echo_choice(
+    state: &BroadcastState,
+    v: Value,
+    quorum: usize,
+    tie_break: TieBreak,
+) -> Option<Value> {
+    if state.echo_received.get(&v).map_or(0, StateSet::len) >= quorum {
+        return Some(v);
+    }
+    let echoers: StateSet<&NodeId> = state.echo_received.values().flatten().collect();
+    if echoers.len() < quorum {
+        return None;
+    }
+
+    let plurality = state
+        .echo_received
+        .iter()
+        .max_by_key(|(v, from)| (from.len(), Reverse(**v)))
+        .map(|(v, _)| *v);
+    match tie_break {
+        TieBreak::Quorum => None,
+        TieBreak::Plurality => plurality,
+        TieBreak::Lowest => state.echo_received.keys().min().cloned(),
+        TieBreak::OwnEcho => state.echoed.or(plurality),
+    }
+}
+
+/// Wire format of the 2.0 release: a single frame for every kind of
+/// message
+#[derive(Clone, PartialEq)]
+pub(crate) struct BroadcastFrame {
+    kind: u8,
+    value: Value,
+}
+
+impl From<&BroadcastMessage> for BroadcastFrame {
+    fn from(msg: &BroadcastMessage) -> Self {
+        let kind = match msg {
+            BC_LEADER(_) => 0,
+            BC_INIT(_) => 1,
+            BC_ECHO(_) => 2,
+            BC_READY(_) => 3,
+        };
+        BroadcastFrame {
+            kind,
+            value: msg.value(),
+        }
+    }
+}
+
+impl BroadcastFrame {
+    /// Message of the 1.x releases, None for unknown kinds
+    fn message(&self) -> Option<BroadcastMessage> {
+        match self.kind {
+            0 => Some(BC_LEADER(self.value)),
+            1 => Some(BC_INIT(self.value)),
+            2 => Some(BC_ECHO(self.value)),
+            3 => Some(BC_READY(self.value)),
+            _ => None,
+        }
+    }
+}
+
+impl ProtocolMessage for BroadcastFrame {
+    const PROTOCOL: ProtocolId = BRACHA_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        self.message()
+            .map_or(Phase::Control, |msg| ProtocolMessage::phase(&msg))
+    }
+
+    fn size(&self) -> usize {
+        1 + std::mem::size_of::<Value>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        BroadcastFrame {
+            kind: self.kind,
+            value: v,
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        match self.message() {
+            Some(msg) => BroadcastFrame::from(&ProtocolMessage::malicious(&msg)),
+            None => self.clone(),
+        }
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        self.message()
+            .and_then(|msg| ProtocolMessage::commitment(&msg))
+    }
+}
+
+/// Timer of a node checking that its broadcast still makes progress
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct LivenessCheck;
+
+impl ProtocolMessage for LivenessCheck {
+    const PROTOCOL: ProtocolId = BRACHA_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        Phase::Control
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn with_value(&self, _v: Value) -> Self {
+        LivenessCheck
+    }
+
+    fn malicious(&self) -> Self {
+        LivenessCheck
+    }
+}
+
+/// READY with the signature share of its sender on the value
+#[cfg(feature = "crypto")]
+#[derive(Clone, PartialEq)]
+pub(crate) struct SignedReady {
+    pub value: Value,
+    pub share: SignatureShare,
+}
+
+#[cfg(feature = "crypto")]
+impl SignedReady {
+    pub fn verify(&self, signatures: &SignatureConfig, from: NodeId) -> bool {
+        let message = DeliveryCertificate::message(self.value);
+        signatures.scheme.verify_share(from, message, self.share)
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl ProtocolMessage for SignedReady {
+    const PROTOCOL: ProtocolId = BRACHA_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        Phase::Ready
+    }
+
+    /// Kind, value and share
+    fn size(&self) -> usize {
+        1 + std::mem::size_of::<Value>() + std::mem::size_of::<SignatureShare>()
+    }
+
+    /// Share no longer matches the value
+    fn with_value(&self, v: Value) -> Self {
+        SignedReady {
+            value: v,
+            share: self.share,
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        Some(Commitment::new("READY", 0, self.value as u64))
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl fmt::Debug for SignedReady {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<READY, {}, signed {:x}>", self.value, self.share)
+    }
+}
+
+/// Message of the broadcast with the signature share of its sender on its
+/// kind and value
+#[cfg(feature = "crypto")]
+#[derive(Clone, PartialEq)]
+pub(crate) struct SignedBroadcast {
+    pub msg: BroadcastMessage,
+    pub share: SignatureShare,
 }
 
+#[cfg(feature = "crypto")]
+impl SignedBroadcast {
+    /// Message signed by the nodes that send `msg`
+    pub fn message(msg: &BroadcastMessage) -> u64 {
+        let kind = BroadcastFrame::from(msg).kind as u64;
+        mix(mix(u64::from_be_bytes(*b"BC_AUTH\0") ^ kind) ^ msg.value() as u64)
+    }
+
+    /// Whether `from` signed the message, whoever actually sent it
+    pub fn verify(&self, signatures: &SignatureConfig, from: NodeId) -> bool {
+        let message = SignedBroadcast::message(&self.msg);
+        signatures.scheme.verify_share(from, message, self.share)
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl ProtocolMessage for SignedBroadcast {
+    const PROTOCOL: ProtocolId = BRACHA_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        ProtocolMessage::phase(&self.msg)
+    }
+
+    /// Message and share
+    fn size(&self) -> usize {
+        ProtocolMessage::size(&self.msg) + std::mem::size_of::<SignatureShare>()
+    }
+
+    /// Share no longer matches the message
+    fn with_value(&self, v: Value) -> Self {
+        SignedBroadcast {
+            msg: ProtocolMessage::with_value(&self.msg, v),
+            share: self.share,
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        SignedBroadcast {
+            msg: ProtocolMessage::malicious(&self.msg),
+            share: self.share,
+        }
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        ProtocolMessage::commitment(&self.msg)
+    }
+
+    fn value(&self) -> Option<Value> {
+        Some(self.msg.value())
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl fmt::Debug for SignedBroadcast {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} signed {:x}", self.msg, self.share)
+    }
+}
+
+/// Signatures of a quorum of nodes on the READY of a value, anyone holding
+/// the public part of the signing key checks that the value was
+/// deliverable: f + 1 honest nodes among them readied it, every honest
+/// node READYs and delivers it
+/// Certificates tell the broadcasts apart by their value only
+#[cfg(feature = "crypto")]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DeliveryCertificate {
+    pub value: Value,
+    pub signatures: BTreeMap<NodeId, SignatureShare>,
+}
+
+#[cfg(feature = "crypto")]
+impl DeliveryCertificate {
+    /// Message signed by the nodes that READY `v`
+    pub fn message(v: Value) -> u64 {
+        mix(mix(u64::from_be_bytes(*b"BC_READY")) ^ v as u64)
+    }
+
+    /// Whether `quorum` distinct nodes signed the READY of the value
+    pub fn verify(&self, signatures: &SignatureConfig, quorum: usize) -> bool {
+        let message = DeliveryCertificate::message(self.value);
+        self.signatures.len() >= quorum
+            && self
+                .signatures
+                .iter()
+                .all(|(node, share)| signatures.scheme.verify_share(*node, message, *share))
+    }
+}
+
+/// Timer of a node catching up, and its request for the READY of the others
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct StateRequest;
+
+impl ProtocolMessage for StateRequest {
+    const PROTOCOL: ProtocolId = BRACHA_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        Phase::Control
+    }
+
+    fn size(&self) -> usize {
+        0
+    }
+
+    fn with_value(&self, _v: Value) -> Self {
+        StateRequest
+    }
+
+    fn malicious(&self) -> Self {
+        StateRequest
+    }
+}
+
+impl fmt::Debug for BroadcastFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message() {
+            Some(msg) => write!(f, "{:?}/2.0", msg),
+            None => write!(f, "<{}?, {}>/2.0", self.kind, self.value),
+        }
+    }
+}
 
 impl fmt::Debug for BroadcastMessage {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -156,12 +1079,60 @@ impl fmt::Debug for BroadcastMessage {
     }
 }
 
-
-impl Distribution<BroadcastMessage> for Standard {
-    fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> BroadcastMessage {
-        match rng.gen_range(0..2usize) { // rand 0.8
-            0 => BC_ECHO(MALICIOUS_VALUE),
-            _ => BC_READY(MALICIOUS_VALUE),
-        }
+/// Messages, state and thresholds of the broadcast, frames of 2.0 carry the
+/// same messages
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(BC_LEADER(0)),
+            MessageSchema::of(BC_INIT(0)),
+            MessageSchema::of(BC_ECHO(0)),
+            MessageSchema::of(BC_READY(0)),
+            #[cfg(feature = "crypto")]
+            MessageSchema::of(SignedReady { value: 0, share: 0 }),
+            #[cfg(feature = "crypto")]
+            MessageSchema::of(SignedBroadcast {
+                msg: BC_ECHO(0),
+                share: 0,
+            }),
+            MessageSchema::of(LivenessCheck),
+            MessageSchema::of(StateRequest),
+        ],
+        state: vec![
+            "echoed",
+            "readied",
+            "forwarded",
+            "support",
+            "echo_received",
+            "ready_received",
+            "received",
+            "delivered",
+            #[cfg(feature = "crypto")]
+            "shares",
+        ],
+        thresholds: vec![
+            Threshold::new(
+                "echo_quorum",
+                quorum.saturating_sub(1),
+                "n - f - 1 ECHOes of the other nodes to READY",
+            ),
+            Threshold::new(
+                "v2_echo_quorum",
+                ((num_nodes + f + 2) / 2).saturating_sub(1),
+                "ceil((n + f + 1) / 2) - 1 ECHOes of the other nodes to READY in 2.0",
+            ),
+            Threshold::new(
+                "ready_amplification",
+                f + 1,
+                "f + 1 READY hold an honest one, the node READYs too",
+            ),
+            Threshold::new(
+                "ready_delivery",
+                quorum.saturating_sub(1),
+                "n - f - 1 READY of the other nodes to deliver",
+            ),
+        ],
+        ..ProtocolDescription::new(BRACHA_BROADCAST)
     }
 }
@@ -0,0 +1,301 @@
+use crate::cost::CryptoOp;
+#[cfg(feature = "crypto")]
+use crate::crypto::threshold_sig::SignatureConfig;
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::mix;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+/// Share of a coin a node hands to the others
+pub type Share = u64;
+
+/// Shared randomness of the nodes: every node holds a share of every coin,
+/// any `f + 1` valid shares of a coin give its value and fewer tell
+/// nothing about it
+/// Coins are named by the instance tossing them, every node derives the
+/// same names
+pub trait CoinSource: fmt::Display + Send + Sync {
+    /// Share of `node` for the coin of instance `coin`
+    fn share(&self, node: NodeId, coin: &InstanceId) -> Share;
+
+    /// Whether `share` is the share of `node` for the coin of `coin`
+    fn verify(&self, node: NodeId, coin: &InstanceId, share: Share) -> bool;
+
+    /// Value of the coin of `coin` from enough valid shares
+    fn combine(&self, coin: &InstanceId, shares: &BTreeMap<NodeId, Share>) -> bool;
+}
+
+/// Message the nodes sign to toss the coin of instance `coin`, the root
+/// instance and the round tossing it
+pub(crate) fn message(coin: &InstanceId) -> u64 {
+    coin.steps().iter().fold(
+        mix(u64::from_be_bytes(*b"COIN\0\0\0\0")),
+        |z, (protocol, tag)| mix(z ^ ((*protocol as u64) << 32 | *tag as u64)),
+    )
+}
+
+/// Simulated threshold coin: a dealer trusted by every node derives the
+/// coins and the shares from its seed before the run
+/// Faulty nodes can't forge shares, but nothing keeps them from computing
+/// the coins, the simulation just doesn't let them
+/// Coins of the builds without the `crypto` feature
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TrustedDealer {
+    pub seed: u64,
+}
+
+impl TrustedDealer {
+    pub fn new(seed: u64) -> Self {
+        TrustedDealer { seed }
+    }
+
+    fn secret(&self, coin: &InstanceId) -> u64 {
+        coin.steps()
+            .iter()
+            .fold(mix(self.seed), |z, (protocol, tag)| {
+                mix(z ^ ((*protocol as u64) << 32 | *tag as u64))
+            })
+    }
+}
+
+impl CoinSource for TrustedDealer {
+    fn share(&self, node: NodeId, coin: &InstanceId) -> Share {
+        mix(self.secret(coin) ^ mix(node as u64))
+    }
+
+    fn verify(&self, node: NodeId, coin: &InstanceId, share: Share) -> bool {
+        self.share(node, coin) == share
+    }
+
+    fn combine(&self, coin: &InstanceId, _shares: &BTreeMap<NodeId, Share>) -> bool {
+        self.secret(coin) & 1 == 1
+    }
+}
+
+impl fmt::Display for TrustedDealer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "trusted dealer, seed {}", self.seed)
+    }
+}
+
+/// Coins tossed by the nodes, `source` if it is set
+/// By default a coin is the threshold signature of its `message` by the
+/// keys the network deals, a trusted dealer seeded with 0 without the
+/// `crypto` feature
+#[derive(Clone, Default)]
+pub struct CoinConfig {
+    pub source: Option<Arc<dyn CoinSource>>,
+}
+
+impl fmt::Debug for CoinConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.source {
+            Some(source) => write!(f, "{}", source),
+            None => write!(f, "default"),
+        }
+    }
+}
+
+/// Sources are told apart by their description
+impl PartialEq for CoinConfig {
+    fn eq(&self, other: &Self) -> bool {
+        let description = |config: &CoinConfig| config.source.as_ref().map(|s| s.to_string());
+        description(self) == description(other)
+    }
+}
+
+/// Where the shares and the value of the coins come from
+enum Coins {
+    Source(Arc<dyn CoinSource>),
+    #[cfg(feature = "crypto")]
+    Signatures(SignatureConfig),
+}
+
+impl Coins {
+    fn share(&self, node: &NodeInternals, coin: &InstanceId) -> Share {
+        match self {
+            Coins::Source(source) => source.share(node.id, coin),
+            #[cfg(feature = "crypto")]
+            Coins::Signatures(signatures) => signatures
+                .scheme
+                .sign_share(&node.signing_key, message(coin)),
+        }
+    }
+
+    fn verify(&self, node: NodeId, coin: &InstanceId, share: Share) -> bool {
+        match self {
+            Coins::Source(source) => source.verify(node, coin, share),
+            #[cfg(feature = "crypto")]
+            Coins::Signatures(signatures) => {
+                signatures.scheme.verify_share(node, message(coin), share)
+            }
+        }
+    }
+
+    /// None until there are enough valid shares
+    fn combine(&self, coin: &InstanceId, shares: &BTreeMap<NodeId, Share>) -> Option<bool> {
+        match self {
+            Coins::Source(source) => Some(source.combine(coin, shares)),
+            #[cfg(feature = "crypto")]
+            Coins::Signatures(signatures) => signatures
+                .scheme
+                .combine(message(coin), shares)
+                .map(|signature| signature & 1 == 1),
+        }
+    }
+}
+
+/// Common coin: every node sends its share once it is ready to toss the
+/// coin and outputs 0 or 1 once it holds `f + 1` valid shares
+#[derive(Clone, PartialEq)]
+pub(crate) enum CoinMessage {
+    COIN_SHARE(Share),
+}
+use CoinMessage::*;
+
+impl ProtocolMessage for CoinMessage {
+    const PROTOCOL: ProtocolId = COMMON_COIN;
+
+    fn phase(&self) -> Phase {
+        Phase::Vote
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Share>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        COIN_SHARE(v as Share)
+    }
+
+    fn malicious(&self) -> Self {
+        match self {
+            COIN_SHARE(share) => COIN_SHARE(!share),
+        }
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            COIN_SHARE(share) => Some(Commitment::new("COIN", 0, *share)),
+        }
+    }
+}
+
+impl fmt::Debug for CoinMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            COIN_SHARE(share) => write!(f, "<COIN, {:x}>", share),
+        }
+    }
+}
+
+/// Coin as tossed by a node, the instance is the name of the coin
+/// Shares received before the node tosses the coin are kept, invalid ones
+/// are counted in `invalid_shares`
+pub(crate) struct CommonCoin {
+    coins: Coins,
+    shares: BTreeMap<NodeId, Share>,
+    tossed: bool,
+    value: Option<bool>,
+}
+
+impl CommonCoin {
+    #[cfg(not(feature = "crypto"))]
+    pub fn new(config: CoinConfig) -> Self {
+        let source = config
+            .source
+            .unwrap_or_else(|| Arc::new(TrustedDealer::default()));
+        CommonCoin::with(Coins::Source(source))
+    }
+
+    /// Coins of `config`, threshold signatures with `signatures` by default
+    #[cfg(feature = "crypto")]
+    pub fn new(config: CoinConfig, signatures: SignatureConfig) -> Self {
+        CommonCoin::with(match config.source {
+            Some(source) => Coins::Source(source),
+            None => Coins::Signatures(signatures),
+        })
+    }
+
+    fn with(coins: Coins) -> Self {
+        CommonCoin {
+            coins,
+            shares: BTreeMap::new(),
+            tossed: false,
+            value: None,
+        }
+    }
+
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        if !self.tossed || self.value.is_some() || self.shares.len() <= node.max_malicious_nodes {
+            return ProtocolState::InProcess;
+        }
+        let coin = node.current.clone().unwrap();
+        let b = match self.coins.combine(&coin, &self.shares) {
+            Some(b) => b,
+            None => return ProtocolState::InProcess,
+        };
+        node.crypto(CryptoOp::CoinCombine, 1);
+        self.value = Some(b);
+        node.deliver(b as Value)
+    }
+}
+
+impl Protocol for CommonCoin {
+    /// Input is ignored, starting the instance tosses the coin
+    fn start(&mut self, node: &mut NodeInternals, _input: Value) -> ProtocolState {
+        if self.tossed {
+            return ProtocolState::InProcess;
+        }
+        self.tossed = true;
+        let coin = node.current.clone().unwrap();
+        let share = self.coins.share(node, &coin);
+        node.crypto(CryptoOp::CoinShare, 1);
+        self.shares.insert(node.id, share);
+        node.send_to_all(node.msg(COIN_SHARE(share)));
+        self.progress(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        let share = match msg.open::<CoinMessage>() {
+            Some(COIN_SHARE(share)) => *share,
+            None => return ProtocolState::InProcess,
+        };
+        if self.shares.contains_key(&from) {
+            return ProtocolState::InProcess;
+        }
+        let coin = node.current.clone().unwrap();
+        if !self.coins.verify(from, &coin, share) {
+            node.count("invalid_shares", 1);
+            return ProtocolState::InProcess;
+        }
+        self.shares.insert(from, share);
+        self.progress(node)
+    }
+
+    /// Malicious node sends a share it can't have
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(COIN_SHARE(rng.next_u64())))
+    }
+}
+
+/// Messages, state and threshold of the coin
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, _) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![MessageSchema::of(COIN_SHARE(0))],
+        state: vec!["shares", "tossed", "value"],
+        thresholds: vec![Threshold::new(
+            "coin_shares",
+            f + 1,
+            "f + 1 valid shares toss the coin, faulty nodes alone can't",
+        )],
+        ..ProtocolDescription::new(COMMON_COIN)
+    }
+}
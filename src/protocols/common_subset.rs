@@ -0,0 +1,144 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Every node reliably broadcasts its input and the nodes agree, one binary
+/// agreement per node, on the broadcasts that make it into the output
+/// Unlike a single Bracha instance, a silent sender can't stall the nodes:
+/// its agreement decides 0 once enough of the other broadcasts delivered
+/// Every honest node outputs the same set, of at least `min_honnest_nodes`
+/// values when every node accepts every value: nodes vote 0 once that many
+/// agreements decided 1
+/// Nodes vote against the broadcasts of values they see as invalid, the
+/// rejected ones are counted in `rejected`: only values valid to some
+/// honest node make it, honest senders whose value some honest node
+/// rejects may be left out
+/// Rejected broadcasts may decide 0 early, nodes then vote 0 once
+/// `min_honnest_nodes` agreements decided whatever the decision, or those
+/// that accepted them could wait forever: the set only holds at least
+/// `min_honnest_nodes` values minus the disputed ones
+pub(crate) struct CommonSubset {
+    broadcasts: Children,
+    agreements: Children,
+    // Agreements the node gave its input to
+    voted: BTreeSet<u32>,
+}
+
+impl CommonSubset {
+    pub fn new() -> Self {
+        CommonSubset {
+            broadcasts: Children::new(),
+            agreements: Children::new(),
+            voted: BTreeSet::new(),
+        }
+    }
+
+    fn vote(&mut self, node: &mut NodeInternals, tag: u32, accept: bool) {
+        if self.voted.insert(tag) && self.agreements.output(tag).is_none() {
+            node.spawn(BINARY_AGREEMENT, tag, accept as Value);
+        }
+    }
+
+    /// Values of the accepted broadcasts once every agreement decided and
+    /// all of them delivered
+    fn output(&self, node: &NodeInternals) -> Option<Output> {
+        if self.agreements.len() < node.num_nodes {
+            return None;
+        }
+        let mut values = BTreeMap::new();
+        for (tag, decision) in self.agreements.outputs() {
+            if decision.value() == Some(0) {
+                continue;
+            }
+            let v = self.broadcasts.output(*tag)?.value()?;
+            values.insert(*tag as NodeId, v);
+        }
+        Some(Output::Set(values))
+    }
+}
+
+impl Protocol for CommonSubset {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.spawn(BRACHA_BROADCAST, node.id as u32, input);
+        ProtocolState::InProcess
+    }
+
+    // Messages go to the broadcast and agreement instances
+    fn handle(
+        &mut self,
+        _node: &mut NodeInternals,
+        _from: NodeId,
+        _msg: &Envelope,
+    ) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        match child.protocol() {
+            BRACHA_BROADCAST => {
+                if !self.broadcasts.record(child, output) {
+                    return ProtocolState::InProcess;
+                }
+                let valid = output.value().is_none_or(|v| node.is_valid(v));
+                if !valid {
+                    node.count("rejected", 1);
+                }
+                self.vote(node, child.tag(), valid);
+            }
+            BINARY_AGREEMENT => {
+                if !self.agreements.record(child, output) {
+                    return ProtocolState::InProcess;
+                }
+                // Enough broadcasts made it, or were settled when some may
+                // be rejected, the others are left out
+                let settled = match node.accepts_all() {
+                    true => self
+                        .agreements
+                        .outputs()
+                        .values()
+                        .filter(|decision| decision.value() == Some(1))
+                        .count(),
+                    false => self.agreements.len(),
+                };
+                if settled >= node.min_honnest_nodes {
+                    for tag in 0..node.num_nodes as u32 {
+                        self.vote(node, tag, false);
+                    }
+                }
+            }
+            _ => return ProtocolState::InProcess,
+        }
+        node.debug(&self.agreements);
+        match self.output(node) {
+            Some(output) => ProtocolState::Terminated(output),
+            None => ProtocolState::InProcess,
+        }
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+/// Instances and threshold of the subset agreement
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (_, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        state: vec!["broadcasts", "agreements", "voted"],
+        thresholds: vec![Threshold::new(
+            "accepted_broadcasts",
+            quorum,
+            "n - f agreements decided 1 before the node votes 0 in the others",
+        )],
+        children: vec![BRACHA_BROADCAST, BINARY_AGREEMENT],
+        ..ProtocolDescription::new(COMMON_SUBSET)
+    }
+}
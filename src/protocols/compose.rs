@@ -0,0 +1,138 @@
+use crate::network::Output;
+use crate::protocols::{Protocol, ProtocolId};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Instance of a protocol: path from a root instance down to it, each step
+/// being the protocol of an instance and the tag given by its parent
+/// Every node derives the same ids, so messages of an instance started by a
+/// peer are routed to the matching local instance
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InstanceId(Vec<(ProtocolId, u32)>);
+
+impl InstanceId {
+    /// Instance started by the network
+    pub fn root(protocol: ProtocolId) -> Self {
+        InstanceId(vec![(protocol, 0)])
+    }
+
+    /// Sub-instance of `protocol` spawned by this instance
+    pub fn child(&self, protocol: ProtocolId, tag: u32) -> Self {
+        let mut path = self.0.clone();
+        path.push((protocol, tag));
+        InstanceId(path)
+    }
+
+    /// Instance that spawned this one, None for a root instance
+    pub fn parent(&self) -> Option<Self> {
+        match self.0.len() {
+            0 | 1 => None,
+            len => Some(InstanceId(self.0[..len - 1].to_vec())),
+        }
+    }
+
+    pub fn protocol(&self) -> ProtocolId {
+        self.0.last().map_or(0, |(protocol, _)| *protocol)
+    }
+
+    /// Tag given by the parent instance
+    pub fn tag(&self) -> u32 {
+        self.0.last().map_or(0, |(_, tag)| *tag)
+    }
+
+    /// Tag of the root instance, 0 for the instances started by the network
+    pub fn root_tag(&self) -> u32 {
+        self.0.first().map_or(0, |(_, tag)| *tag)
+    }
+
+    /// Same instance under the root instance tagged `tag`
+    pub(crate) fn under_root(&self, tag: u32) -> Self {
+        let mut path = self.0.clone();
+        if let Some(root) = path.first_mut() {
+            root.1 = tag;
+        }
+        InstanceId(path)
+    }
+
+    /// Protocol and tag of every step, from the root instance down
+    pub(crate) fn steps(&self) -> &[(ProtocolId, u32)] {
+        &self.0
+    }
+
+    /// Protocol id and tag of every step
+    pub(crate) fn size(&self) -> usize {
+        self.0.len() * (std::mem::size_of::<ProtocolId>() + std::mem::size_of::<u32>())
+    }
+}
+
+impl fmt::Debug for InstanceId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let steps: Vec<String> = self
+            .0
+            .iter()
+            .map(|(protocol, tag)| format!("{}:{}", protocol, tag))
+            .collect();
+        write!(f, "{}", steps.join("/"))
+    }
+}
+
+/// Protocol instance run by a node
+pub(crate) struct Instance {
+    pub protocol: Box<dyn Protocol>,
+    // First output of the instance, later ones are ignored
+    pub output: Option<Output>,
+    // Messages of a stopped instance are dropped
+    pub stopped: bool,
+}
+
+impl Instance {
+    pub(crate) fn new(protocol: Box<dyn Protocol>) -> Self {
+        Instance {
+            protocol,
+            output: None,
+            stopped: false,
+        }
+    }
+}
+
+/// Instances of a node, ordered so that iterating over them is reproducible
+pub(crate) type Instances = BTreeMap<InstanceId, Instance>;
+
+/// Outputs of the sub-instances of an instance, indexed by their tag
+/// Parents use it to decide when they are done
+#[derive(Debug, Default)]
+pub(crate) struct Children {
+    outputs: BTreeMap<u32, Output>,
+}
+
+impl Children {
+    pub(crate) fn new() -> Self {
+        Children::default()
+    }
+
+    /// Store the output of `child`, false if it already had one
+    pub(crate) fn record(&mut self, child: &InstanceId, output: &Output) -> bool {
+        if self.outputs.contains_key(&child.tag()) {
+            return false;
+        }
+        self.outputs.insert(child.tag(), output.clone());
+        true
+    }
+
+    pub(crate) fn output(&self, tag: u32) -> Option<&Output> {
+        self.outputs.get(&tag)
+    }
+
+    /// Number of sub-instances that have output
+    pub(crate) fn len(&self) -> usize {
+        self.outputs.len()
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.outputs.is_empty()
+    }
+
+    pub(crate) fn outputs(&self) -> &BTreeMap<u32, Output> {
+        &self.outputs
+    }
+}
@@ -0,0 +1,159 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Consistent broadcast, the echo broadcast without READY: the leader sends
+/// its value, every node echoes the first value it hears of to all, and
+/// delivers once ceil((n + f + 1) / 2) nodes echoed the same value
+/// Two such quorums share an honest node, which echoes a single value:
+/// honest nodes that deliver agree, but nothing makes them all deliver
+/// A faulty leader may have some honest nodes deliver and the others wait
+/// forever, Bracha broadcast rules that out with its READY phase
+#[derive(Clone, PartialEq)]
+pub(crate) enum ConsistentMessage {
+    CB_SEND(Value),
+    CB_ECHO(Value),
+}
+use ConsistentMessage::*;
+
+impl ProtocolMessage for ConsistentMessage {
+    const PROTOCOL: ProtocolId = CONSISTENT_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        match self {
+            CB_SEND(_) => Phase::Init,
+            CB_ECHO(_) => Phase::Echo,
+        }
+    }
+
+    /// Kind and value
+    fn size(&self) -> usize {
+        1 + std::mem::size_of::<Value>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            CB_SEND(_) => CB_SEND(v),
+            CB_ECHO(_) => CB_ECHO(v),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    /// Nodes echo a single value
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            CB_ECHO(v) => Some(Commitment::new("ECHO", 0, *v as u64)),
+            CB_SEND(_) => None,
+        }
+    }
+
+    fn value(&self) -> Option<Value> {
+        match self {
+            CB_SEND(v) | CB_ECHO(v) => Some(*v),
+        }
+    }
+}
+
+impl fmt::Debug for ConsistentMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CB_SEND(v) => write!(f, "<SEND, {}>", v),
+            CB_ECHO(v) => write!(f, "<ECHO, {}>", v),
+        }
+    }
+}
+
+/// Consistent broadcast as run by a node
+pub(crate) struct ConsistentBroadcast {
+    echoed: Option<Value>,
+    // Nodes that echoed each value, the node itself included
+    echoes: BTreeMap<Value, BTreeSet<NodeId>>,
+}
+
+impl ConsistentBroadcast {
+    pub fn new() -> Self {
+        ConsistentBroadcast {
+            echoed: None,
+            echoes: BTreeMap::new(),
+        }
+    }
+
+    /// Echo `v` unless the node already echoed a value
+    fn echo(&mut self, node: &mut NodeInternals, v: Value) {
+        if self.echoed.is_some() {
+            return;
+        }
+        self.echoed = Some(v);
+        self.echoes.entry(v).or_default().insert(node.id);
+        node.send_to_all(node.msg(CB_ECHO(v)));
+    }
+
+    fn progress(&self, node: &mut NodeInternals) -> ProtocolState {
+        let quorum = (node.num_nodes + node.max_malicious_nodes + 2) / 2;
+        let delivered = self
+            .echoes
+            .iter()
+            .find(|(_, echoes)| echoes.len() >= quorum)
+            .map(|(v, _)| *v);
+        match delivered {
+            Some(v) => node.deliver(v),
+            None => ProtocolState::InProcess,
+        }
+    }
+}
+
+impl Protocol for ConsistentBroadcast {
+    /// Leader sends its input and echoes it
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.send_to_all(node.msg(CB_SEND(input)));
+        self.echo(node, input);
+        self.progress(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<ConsistentMessage>() {
+            Some(CB_SEND(v)) => self.echo(node, *v),
+            Some(CB_ECHO(v)) => {
+                self.echoes.entry(*v).or_default().insert(from);
+            }
+            None => return ProtocolState::InProcess,
+        }
+        self.progress(node)
+    }
+
+    /// ECHO of the malicious value
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(CB_ECHO(MALICIOUS_VALUE)))
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.echoed {
+            Some(v) => vec![("echo", v.to_string())],
+            None => vec![],
+        }
+    }
+}
+
+/// Messages, state and threshold of the consistent broadcast
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, _) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![MessageSchema::of(CB_SEND(0)), MessageSchema::of(CB_ECHO(0))],
+        state: vec!["echoed", "echoes"],
+        thresholds: vec![Threshold::new(
+            "echo_quorum",
+            (num_nodes + f + 2) / 2,
+            "ceil((n + f + 1) / 2) ECHOes to deliver, any two such quorums share an \
+             honest node",
+        )],
+        ..ProtocolDescription::new(CONSISTENT_BROADCAST)
+    }
+}
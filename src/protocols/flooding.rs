@@ -0,0 +1,197 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Parameters of flooding
+#[derive(Clone, Debug, PartialEq)]
+pub struct FloodConfig {
+    // Links a value may cross from its origin, None for no limit
+    pub ttl: Option<u32>,
+    // Nodes only forward the first copy they receive from each origin,
+    // otherwise copies go round the cycles of the topology until their
+    // TTL runs out
+    pub suppress_duplicates: bool,
+}
+
+impl Default for FloodConfig {
+    fn default() -> Self {
+        FloodConfig {
+            ttl: None,
+            suppress_duplicates: true,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub(crate) enum FloodMessage {
+    // Value of `origin` after `hops` links, `ttl` more links allowed
+    FL_VALUE {
+        origin: NodeId,
+        value: Value,
+        ttl: Option<u32>,
+        hops: u32,
+    },
+}
+use FloodMessage::*;
+
+impl ProtocolMessage for FloodMessage {
+    const PROTOCOL: ProtocolId = FLOODING;
+
+    fn phase(&self) -> Phase {
+        Phase::Init
+    }
+
+    /// Origin, value, TTL and hop count
+    fn size(&self) -> usize {
+        std::mem::size_of::<NodeId>()
+            + std::mem::size_of::<Value>()
+            + 2 * std::mem::size_of::<u32>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        let FL_VALUE {
+            origin, ttl, hops, ..
+        } = self;
+        FL_VALUE {
+            origin: *origin,
+            value: v,
+            ttl: *ttl,
+            hops: *hops,
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    fn value(&self) -> Option<Value> {
+        let FL_VALUE { value, .. } = self;
+        Some(*value)
+    }
+}
+
+impl fmt::Debug for FloodMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let FL_VALUE {
+            origin,
+            value,
+            ttl,
+            hops,
+        } = self;
+        match ttl {
+            Some(ttl) => write!(
+                f,
+                "<VALUE, {}, {}, hop {}, ttl {}>",
+                origin, value, hops, ttl
+            ),
+            None => write!(f, "<VALUE, {}, {}, hop {}>", origin, value, hops),
+        }
+    }
+}
+
+/// Every node floods its input through the links of the topology and
+/// outputs the values of all the nodes once it has them
+/// Nodes forward a copy to their neighbours but the one it came from
+pub(crate) struct Flooding {
+    config: FloodConfig,
+    // First value received from each origin
+    values: BTreeMap<NodeId, Value>,
+}
+
+impl Flooding {
+    pub fn new(config: FloodConfig) -> Self {
+        Flooding {
+            config,
+            values: BTreeMap::new(),
+        }
+    }
+
+    /// Send a copy to every neighbour but `except`, unless the TTL ran out
+    fn forward(&self, node: &mut NodeInternals, except: NodeId, msg: FloodMessage) {
+        let FL_VALUE {
+            origin,
+            value,
+            ttl,
+            hops,
+        } = msg;
+        if ttl == Some(0) {
+            return;
+        }
+        let copy = FL_VALUE {
+            origin,
+            value,
+            ttl: ttl.map(|ttl| ttl - 1),
+            hops: hops + 1,
+        };
+        for id in node.links.clone() {
+            if id != except {
+                node.send_to(id, node.msg(copy.clone()));
+            }
+        }
+    }
+
+    fn output(&self, node: &NodeInternals) -> ProtocolState {
+        if self.values.len() < node.num_nodes {
+            return ProtocolState::InProcess;
+        }
+        ProtocolState::Terminated(Output::Set(self.values.clone()))
+    }
+}
+
+impl Protocol for Flooding {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        self.values.insert(node.id, input);
+        let msg = FL_VALUE {
+            origin: node.id,
+            value: input,
+            ttl: self.config.ttl,
+            hops: 0,
+        };
+        self.forward(node, node.id, msg);
+        self.output(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        let msg = match msg.open::<FloodMessage>() {
+            Some(msg) => msg.clone(),
+            None => return ProtocolState::InProcess,
+        };
+        let FL_VALUE { origin, value, .. } = msg;
+        let duplicate = self.values.contains_key(&origin);
+        if !duplicate {
+            self.values.insert(origin, value);
+        }
+        if !(duplicate && self.config.suppress_duplicates) {
+            self.forward(node, from, msg);
+        }
+        self.output(node)
+    }
+
+    /// Malicious node makes up a value of the first node
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(FL_VALUE {
+            origin: 0,
+            value: rng.gen_range(2) as Value,
+            ttl: self.config.ttl,
+            hops: 0,
+        }))
+    }
+}
+
+/// Messages and state of the flooding
+pub fn describe(_num_nodes: usize) -> ProtocolDescription {
+    ProtocolDescription {
+        messages: vec![MessageSchema::of(FL_VALUE {
+            origin: 0,
+            value: 0,
+            ttl: None,
+            hops: 0,
+        })],
+        state: vec!["values"],
+        ..ProtocolDescription::new(FLOODING)
+    }
+}
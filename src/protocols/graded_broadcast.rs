@@ -0,0 +1,255 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::Duration;
+
+/// Confidence of a node in the value it outputs: 2 if it knows every honest
+/// node outputs the value, 1 if it only knows none outputs another one, 0
+/// without any value
+pub type Grade = u8;
+
+/// Parameters of the graded broadcasts
+#[derive(Clone, Debug, PartialEq)]
+pub struct GradedConfig {
+    // Node broadcasting its input, set by every run
+    pub leader: NodeId,
+    // Time nodes give the READY of the others before grading what they
+    // have, the bound on the delays of the synchronous gradecast
+    pub timeout: Duration,
+}
+
+impl Default for GradedConfig {
+    fn default() -> Self {
+        GradedConfig {
+            leader: 0,
+            timeout: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Graded broadcast, or crusader agreement, the gradecast of Feldman and
+/// Micali: the leader sends its value, every node echoes the first value it
+/// hears of and READY the value n - f nodes echoed, two such quorums share
+/// an honest node so that honest nodes READY a single value
+/// Nodes grade 2 as soon as n - f nodes readied a value, at the timeout
+/// they grade 1 the value f + 1 nodes readied and 0 without one
+/// A node grading 2 heard of the READY of f + 1 honest nodes, the others
+/// hear of them before their timeout: honest grades differ by one at most,
+/// and honest nodes grading above 0 output the same value
+/// An honest leader has every honest node grade 2, a faulty one may have
+/// them grade anything as long as they stay within one of each other
+#[derive(Clone, PartialEq)]
+pub(crate) enum GradedMessage {
+    GB_SEND(Value),
+    GB_ECHO(Value),
+    GB_READY(Value),
+    // Timer of the node, grades what it has
+    GB_GRADE,
+}
+use GradedMessage::*;
+
+impl ProtocolMessage for GradedMessage {
+    const PROTOCOL: ProtocolId = GRADED_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        match self {
+            GB_SEND(_) => Phase::Init,
+            GB_ECHO(_) => Phase::Echo,
+            GB_READY(_) => Phase::Ready,
+            GB_GRADE => Phase::Control,
+        }
+    }
+
+    /// Kind and value
+    fn size(&self) -> usize {
+        match self {
+            GB_GRADE => 1,
+            _ => 1 + std::mem::size_of::<Value>(),
+        }
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            GB_SEND(_) => GB_SEND(v),
+            GB_ECHO(_) => GB_ECHO(v),
+            GB_READY(_) => GB_READY(v),
+            GB_GRADE => GB_GRADE,
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    /// Nodes echo and ready a single value
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            GB_ECHO(v) => Some(Commitment::new("ECHO", 0, *v as u64)),
+            GB_READY(v) => Some(Commitment::new("READY", 0, *v as u64)),
+            GB_SEND(_) | GB_GRADE => None,
+        }
+    }
+
+    fn value(&self) -> Option<Value> {
+        match self {
+            GB_SEND(v) | GB_ECHO(v) | GB_READY(v) => Some(*v),
+            GB_GRADE => None,
+        }
+    }
+}
+
+impl fmt::Debug for GradedMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GB_SEND(v) => write!(f, "<SEND, {}>", v),
+            GB_ECHO(v) => write!(f, "<ECHO, {}>", v),
+            GB_READY(v) => write!(f, "<READY, {}>", v),
+            GB_GRADE => write!(f, "<GRADE>"),
+        }
+    }
+}
+
+/// Graded broadcast as run by a node
+pub(crate) struct GradedBroadcast {
+    config: GradedConfig,
+    echoed: Option<Value>,
+    readied: Option<Value>,
+    // Nodes that echoed and readied each value, the node itself included
+    echoes: BTreeMap<Value, BTreeSet<NodeId>>,
+    readies: BTreeMap<Value, BTreeSet<NodeId>>,
+    graded: bool,
+}
+
+impl GradedBroadcast {
+    pub fn new(config: GradedConfig) -> Self {
+        GradedBroadcast {
+            config,
+            echoed: None,
+            readied: None,
+            echoes: BTreeMap::new(),
+            readies: BTreeMap::new(),
+            graded: false,
+        }
+    }
+
+    /// Echo `v` unless the node already echoed a value
+    fn echo(&mut self, node: &mut NodeInternals, v: Value) {
+        if self.echoed.is_some() {
+            return;
+        }
+        self.echoed = Some(v);
+        self.echoes.entry(v).or_default().insert(node.id);
+        node.send_to_all(node.msg(GB_ECHO(v)));
+    }
+
+    /// READY the value echoed by n - f nodes, grade 2 the value readied by
+    /// n - f nodes
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let threshold = node.min_honnest_nodes;
+        let quorum = |votes: &BTreeMap<Value, BTreeSet<NodeId>>| {
+            votes
+                .iter()
+                .find(|(_, nodes)| nodes.len() >= threshold)
+                .map(|(v, _)| *v)
+        };
+        if let Some(v) = quorum(&self.echoes).filter(|_| self.readied.is_none()) {
+            self.readied = Some(v);
+            self.readies.entry(v).or_default().insert(node.id);
+            node.send_to_all(node.msg(GB_READY(v)));
+        }
+        match quorum(&self.readies).filter(|_| !self.graded) {
+            Some(v) => {
+                self.graded = true;
+                node.deliver_output(Output::Graded(Some(v), 2))
+            }
+            None => ProtocolState::InProcess,
+        }
+    }
+
+    /// Timeout without n - f READY for a value: grade 1 the value readied
+    /// by an honest node, if any
+    fn grade(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        if self.graded {
+            return ProtocolState::InProcess;
+        }
+        self.graded = true;
+        let readied = self
+            .readies
+            .iter()
+            .find(|(_, nodes)| nodes.len() > node.max_malicious_nodes)
+            .map(|(v, _)| *v);
+        node.count("timeouts", 1);
+        match readied {
+            Some(v) => node.deliver_output(Output::Graded(Some(v), 1)),
+            None => node.deliver_output(Output::Graded(None, 0)),
+        }
+    }
+}
+
+impl Protocol for GradedBroadcast {
+    /// Leader sends its input and echoes it, every node starts its timer
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.schedule(self.config.timeout, node.msg(GB_GRADE));
+        if node.id == self.config.leader {
+            node.send_to_all(node.msg(GB_SEND(input)));
+            self.echo(node, input);
+        }
+        self.progress(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<GradedMessage>() {
+            Some(GB_SEND(v)) if from == self.config.leader => self.echo(node, *v),
+            Some(GB_ECHO(v)) => {
+                self.echoes.entry(*v).or_default().insert(from);
+            }
+            Some(GB_READY(v)) => {
+                self.readies.entry(*v).or_default().insert(from);
+            }
+            Some(GB_GRADE) if from == node.id => return self.grade(node),
+            _ => return ProtocolState::InProcess,
+        }
+        self.progress(node)
+    }
+
+    /// READY of the malicious value
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(GB_READY(MALICIOUS_VALUE)))
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.readied {
+            Some(v) => vec![("ready", v.to_string())],
+            None => vec![],
+        }
+    }
+}
+
+/// Messages, state and thresholds of the graded broadcast
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(GB_SEND(0)),
+            MessageSchema::of(GB_ECHO(0)),
+            MessageSchema::of(GB_READY(0)),
+            MessageSchema::of(GB_GRADE),
+        ],
+        state: vec!["echoed", "readied", "echoes", "readies", "graded"],
+        thresholds: vec![
+            Threshold::new("echo_quorum", quorum, "n - f ECHOes of a value to READY it"),
+            Threshold::new("grade_2", quorum, "n - f READY of a value grade it 2"),
+            Threshold::new(
+                "grade_1",
+                f + 1,
+                "f + 1 READY of a value at the timeout hold an honest one, grade 1",
+            ),
+        ],
+        ..ProtocolDescription::new(GRADED_BROADCAST)
+    }
+}
@@ -0,0 +1,419 @@
+use crate::cost::CryptoOp;
+use crate::crypto::threshold_enc::{
+    Ciphertext, DecryptionShare, EncryptionConfig, ThresholdEncryption,
+};
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt;
+use std::sync::Arc;
+
+/// Proposal of a node with nothing left to propose
+pub const NO_TRANSACTION: Value = Value::MAX;
+
+/// Parameters of the atomic broadcast
+#[derive(Clone, Debug, PartialEq)]
+pub struct HoneyBadgerConfig {
+    // Epochs to commit before the nodes output their sequence
+    pub epochs: u32,
+    // Transactions every node submits, in the order it proposes them
+    pub queues: BTreeMap<NodeId, Vec<Value>>,
+    // Proposals are encrypted under the key of the nodes and decrypted once
+    // the subset of the epoch is known, faulty nodes can't leave out the
+    // proposals of the transactions they don't like
+    pub encrypted: bool,
+    // Epochs after the current one whose shares are kept until their subset
+    // is known, shares of later epochs are dropped
+    pub max_future_epochs: u32,
+}
+
+impl Default for HoneyBadgerConfig {
+    fn default() -> Self {
+        HoneyBadgerConfig {
+            epochs: 1,
+            queues: BTreeMap::new(),
+            encrypted: false,
+            max_future_epochs: 3,
+        }
+    }
+}
+
+/// Share of a node for decrypting the proposal of `proposer` in `epoch`
+#[derive(Clone, PartialEq)]
+pub(crate) enum HoneyBadgerMessage {
+    DEC_SHARE {
+        epoch: u32,
+        proposer: NodeId,
+        share: DecryptionShare,
+    },
+}
+use HoneyBadgerMessage::*;
+
+impl ProtocolMessage for HoneyBadgerMessage {
+    const PROTOCOL: ProtocolId = HONEY_BADGER;
+
+    fn phase(&self) -> Phase {
+        Phase::Vote
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<u32>()
+            + std::mem::size_of::<NodeId>()
+            + std::mem::size_of::<DecryptionShare>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            DEC_SHARE {
+                epoch, proposer, ..
+            } => DEC_SHARE {
+                epoch: *epoch,
+                proposer: *proposer,
+                share: v as DecryptionShare,
+            },
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        match self {
+            DEC_SHARE {
+                epoch,
+                proposer,
+                share,
+            } => DEC_SHARE {
+                epoch: *epoch,
+                proposer: *proposer,
+                share: !share,
+            },
+        }
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            DEC_SHARE {
+                epoch,
+                proposer,
+                share,
+            } => Some(Commitment::new(
+                "DEC_SHARE",
+                label(*epoch, *proposer),
+                *share,
+            )),
+        }
+    }
+}
+
+impl fmt::Debug for HoneyBadgerMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DEC_SHARE {
+                epoch,
+                proposer,
+                share,
+            } => write!(f, "<DEC_SHARE, {}, {}, {:x}>", epoch, proposer, share),
+        }
+    }
+}
+
+/// Label of the encrypted proposal of `proposer` in `epoch`
+fn label(epoch: u32, proposer: NodeId) -> u64 {
+    (epoch as u64) << 32 | proposer as u64
+}
+
+/// HoneyBadgerBFT atomic broadcast: every epoch the nodes propose the
+/// oldest transaction of their queue to a common subset instance, the
+/// batch of the epoch is the set it outputs, sorted, without the empty
+/// proposals and the transactions already committed
+/// Epochs are committed in order, even when the subset of a later epoch
+/// is known first, nodes output the batches one after the other
+/// Encrypted proposals are decrypted with the shares of `threshold` nodes,
+/// invalid shares are counted in `invalid_shares`
+/// Shares received before the subset of their epoch are kept, at most one
+/// per sender and proposer for the next `max_future_epochs` epochs, the
+/// others are counted in `dropped_shares`
+pub(crate) struct HoneyBadger {
+    config: HoneyBadgerConfig,
+    scheme: Arc<dyn ThresholdEncryption>,
+    started: bool,
+    // Transactions of the node not committed yet
+    queue: VecDeque<Value>,
+    epochs: Children,
+    // Next epoch to commit
+    epoch: u32,
+    committed: Vec<Value>,
+    // Valid decryption shares of the proposals kept by the known subsets,
+    // and shares received before the subset, checked once it is known
+    shares: BTreeMap<(u32, NodeId), BTreeMap<NodeId, DecryptionShare>>,
+    early: BTreeMap<(u32, NodeId), BTreeMap<NodeId, DecryptionShare>>,
+    decrypted: BTreeMap<(u32, NodeId), Value>,
+}
+
+impl HoneyBadger {
+    pub fn new(config: HoneyBadgerConfig, encryption: EncryptionConfig) -> Self {
+        HoneyBadger {
+            config,
+            scheme: encryption.scheme,
+            started: false,
+            queue: VecDeque::new(),
+            epochs: Children::new(),
+            epoch: 0,
+            committed: vec![],
+            shares: BTreeMap::new(),
+            early: BTreeMap::new(),
+            decrypted: BTreeMap::new(),
+        }
+    }
+
+    fn propose(&mut self, node: &mut NodeInternals) {
+        let mut proposal = self.queue.front().copied().unwrap_or(NO_TRANSACTION);
+        if self.config.encrypted {
+            let ciphertext = self.scheme.encrypt(label(self.epoch, node.id), proposal);
+            node.crypto(CryptoOp::Encrypt, 1);
+            proposal = ciphertext.body as Value;
+        }
+        node.spawn(COMMON_SUBSET, self.epoch, proposal);
+    }
+
+    /// Encrypted proposal of `proposer` in `epoch`, once the subset of the
+    /// epoch kept it
+    fn ciphertext(&self, epoch: u32, proposer: NodeId) -> Option<Ciphertext> {
+        match self.epochs.output(epoch) {
+            Some(Output::Set(proposals)) => proposals.get(&proposer).map(|body| Ciphertext {
+                label: label(epoch, proposer),
+                body: *body as u64,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Keep the share of `from` if it is valid, decrypt the proposal once
+    /// there are enough of them
+    fn add_share(
+        &mut self,
+        node: &mut NodeInternals,
+        proposal: (u32, NodeId),
+        from: NodeId,
+        share: DecryptionShare,
+    ) {
+        let ciphertext = match self.ciphertext(proposal.0, proposal.1) {
+            Some(ciphertext) => ciphertext,
+            None => return,
+        };
+        if !self.scheme.verify_share(from, &ciphertext, share) {
+            node.count("invalid_shares", 1);
+            return;
+        }
+        let shares = self.shares.entry(proposal).or_default();
+        shares.insert(from, share);
+        if self.decrypted.contains_key(&proposal) {
+            return;
+        }
+        if let Some(v) = self.scheme.combine(&ciphertext, shares) {
+            node.crypto(CryptoOp::DecryptCombine, 1);
+            self.decrypted.insert(proposal, v);
+        }
+    }
+
+    /// Subset of `epoch` is known: hand out the shares of the proposals it
+    /// kept and check the shares received before
+    fn decrypt(&mut self, node: &mut NodeInternals, epoch: u32) {
+        let proposers: Vec<NodeId> = match self.epochs.output(epoch) {
+            Some(Output::Set(proposals)) => proposals.keys().copied().collect(),
+            _ => return,
+        };
+        let id = node.id;
+        for proposer in proposers {
+            let ciphertext = self.ciphertext(epoch, proposer).unwrap();
+            let share = self.scheme.decryption_share(&node.key_share, &ciphertext);
+            node.crypto(CryptoOp::DecryptShare, 1);
+            node.send_to_all(node.msg(DEC_SHARE {
+                epoch,
+                proposer,
+                share,
+            }));
+            self.add_share(node, (epoch, proposer), id, share);
+            let early = self.early.remove(&(epoch, proposer)).unwrap_or_default();
+            for (from, share) in early {
+                self.add_share(node, (epoch, proposer), from, share);
+            }
+        }
+    }
+
+    /// Transactions proposed in the current epoch, None until the subset
+    /// is known and every proposal it kept is decrypted
+    fn batch(&self) -> Option<BTreeSet<Value>> {
+        let proposals = match self.epochs.output(self.epoch) {
+            Some(Output::Set(proposals)) => proposals,
+            _ => return None,
+        };
+        if !self.config.encrypted {
+            return Some(proposals.values().copied().collect());
+        }
+        proposals
+            .keys()
+            .map(|proposer| self.decrypted.get(&(self.epoch, *proposer)).copied())
+            .collect()
+    }
+
+    /// Commit the epochs whose batch is known, in order
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        if !self.started {
+            return ProtocolState::InProcess;
+        }
+        while let Some(mut batch) = self.batch() {
+            batch.remove(&NO_TRANSACTION);
+            batch.retain(|tx| !self.committed.contains(tx));
+            node.debug(&format!("epoch {} batch {:?}", self.epoch, batch));
+            self.queue.retain(|tx| !batch.contains(tx));
+            self.committed.extend(batch);
+            self.epoch += 1;
+            if self.epoch == self.config.epochs {
+                return node.deliver_output(Output::Sequence(self.committed.clone()));
+            }
+            self.propose(node);
+        }
+        ProtocolState::InProcess
+    }
+}
+
+impl Protocol for HoneyBadger {
+    /// Input is ignored, the node proposes the transactions of its queue
+    fn start(&mut self, node: &mut NodeInternals, _input: Value) -> ProtocolState {
+        if self.started {
+            return ProtocolState::InProcess;
+        }
+        self.started = true;
+        let queue = self
+            .config
+            .queues
+            .get(&node.id)
+            .cloned()
+            .unwrap_or_default();
+        self.queue = queue.into();
+        if self.config.epochs == 0 {
+            return node.deliver_output(Output::Sequence(vec![]));
+        }
+        self.propose(node);
+        self.progress(node)
+    }
+
+    // Other messages go to the common subset instances
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        let (epoch, proposer, share) = match msg.open::<HoneyBadgerMessage>() {
+            Some(DEC_SHARE {
+                epoch,
+                proposer,
+                share,
+            }) => (*epoch, *proposer, *share),
+            None => return ProtocolState::InProcess,
+        };
+        let proposal = (epoch, proposer);
+        if self
+            .shares
+            .get(&proposal)
+            .is_some_and(|shares| shares.contains_key(&from))
+        {
+            return ProtocolState::InProcess;
+        }
+        if self.epochs.output(epoch).is_none() {
+            // Epochs without a subset yet are not committed
+            let horizon = self.epoch.saturating_add(self.config.max_future_epochs);
+            if proposer >= node.num_nodes || epoch > horizon {
+                node.count("dropped_shares", 1);
+                return ProtocolState::InProcess;
+            }
+            let early = self.early.entry(proposal).or_default();
+            early.entry(from).or_insert(share);
+            return ProtocolState::InProcess;
+        }
+        self.add_share(node, proposal, from, share);
+        self.progress(node)
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        if child.protocol() != COMMON_SUBSET || !self.epochs.record(child, output) {
+            return ProtocolState::InProcess;
+        }
+        if self.config.encrypted {
+            self.decrypt(node, child.tag());
+        }
+        self.progress(node)
+    }
+
+    /// Malicious node sends a share it can't have, of the current epoch or
+    /// of any later one
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        if !self.config.encrypted {
+            return None;
+        }
+        let epoch = match rng.gen_range(2) {
+            0 => self.epoch,
+            _ => rng.next_u64() as u32,
+        };
+        Some(Box::new(DEC_SHARE {
+            epoch,
+            proposer: rng.gen_range(self.config.queues.len().max(1)),
+            share: rng.next_u64(),
+        }))
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        vec![("committed", format!("{:?}", self.committed))]
+    }
+
+    fn check(&self, node: &NodeInternals) -> Result<(), String> {
+        let distinct: BTreeSet<&Value> = self.committed.iter().collect();
+        if distinct.len() < self.committed.len() {
+            return Err(format!("committed twice: {:?}", self.committed));
+        }
+        let horizon = self.epoch.saturating_add(self.config.max_future_epochs);
+        if let Some((epoch, proposer)) = self
+            .early
+            .keys()
+            .find(|(epoch, proposer)| *epoch > horizon || *proposer >= node.num_nodes)
+        {
+            return Err(format!(
+                "early share of proposer {} in epoch {} kept in epoch {}",
+                proposer, epoch, self.epoch
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Messages, state and threshold of the Honey Badger epochs
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, _) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![MessageSchema::of(DEC_SHARE {
+            epoch: 0,
+            proposer: 0,
+            share: 0,
+        })],
+        state: vec![
+            "queue",
+            "epoch",
+            "epochs",
+            "committed",
+            "shares",
+            "early",
+            "decrypted",
+        ],
+        thresholds: vec![Threshold::new(
+            "decryption_shares",
+            f + 1,
+            "f + 1 valid shares decrypt a proposal, faulty nodes alone can't",
+        )],
+        children: vec![COMMON_SUBSET],
+        ..ProtocolDescription::new(HONEY_BADGER)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+
+/// Coins tossed per draw to elect one of `num_nodes` nodes
+pub fn coins_per_draw(num_nodes: usize) -> u32 {
+    usize::BITS - num_nodes.saturating_sub(1).leading_zeros()
+}
+
+/// Random leader election: nodes toss the bits of the index of the leader
+/// with common coins, and draw again while the index is not a node, so that
+/// every node is elected with the same probability
+/// Coins are named by the election, parents elect a new leader per view or
+/// round by spawning an election per tag, and get the leader as its output
+/// Faulty nodes can't bias the coins, they can only withhold their shares
+/// or send invalid ones, in `invalid_shares`: the f + 1 honest shares of a
+/// coin toss it, every honest node elects the same leader
+/// Draws beyond the first are counted in `redraws`
+pub(crate) struct LeaderElection {
+    coins: Children,
+    // Draw under way, its coins are tagged from `draw * coins_per_draw`
+    draw: u32,
+    leader: Option<NodeId>,
+}
+
+impl LeaderElection {
+    pub fn new() -> Self {
+        LeaderElection {
+            coins: Children::new(),
+            draw: 0,
+            leader: None,
+        }
+    }
+
+    fn toss(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let bits = coins_per_draw(node.num_nodes);
+        for bit in 0..bits {
+            node.spawn(COMMON_COIN, self.draw * bits + bit, 0);
+        }
+        self.progress(node)
+    }
+
+    /// Leader once every coin of the draw is tossed, another draw if they
+    /// don't make the index of a node
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        if self.leader.is_some() {
+            return ProtocolState::InProcess;
+        }
+        let bits = coins_per_draw(node.num_nodes);
+        let mut index = 0;
+        for bit in 0..bits {
+            match self
+                .coins
+                .output(self.draw * bits + bit)
+                .and_then(Output::value)
+            {
+                Some(b) => index |= b << bit,
+                None => return ProtocolState::InProcess,
+            }
+        }
+        if index >= node.num_nodes {
+            self.draw += 1;
+            node.count("redraws", 1);
+            return self.toss(node);
+        }
+        self.leader = Some(index);
+        node.deliver(index)
+    }
+}
+
+impl Protocol for LeaderElection {
+    /// Input is ignored, starting the instance tosses the coins of the first
+    /// draw
+    fn start(&mut self, node: &mut NodeInternals, _input: Value) -> ProtocolState {
+        self.toss(node)
+    }
+
+    // Messages go to the coins
+    fn handle(
+        &mut self,
+        _node: &mut NodeInternals,
+        _from: NodeId,
+        _msg: &Envelope,
+    ) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        if child.protocol() != COMMON_COIN || !self.coins.record(child, output) {
+            return ProtocolState::InProcess;
+        }
+        self.progress(node)
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+
+    /// Honest nodes elect the same leader
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.leader {
+            Some(leader) => vec![("leader", leader.to_string())],
+            None => vec![],
+        }
+    }
+}
+
+/// Instances of the election
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    ProtocolDescription {
+        state: vec!["coins", "draw", "leader"],
+        thresholds: vec![Threshold::new(
+            "coins_per_draw",
+            coins_per_draw(num_nodes) as usize,
+            "coins tossed per draw, the bits of the index of the leader",
+        )],
+        children: vec![COMMON_COIN],
+        ..ProtocolDescription::new(LEADER_ELECTION)
+    }
+}
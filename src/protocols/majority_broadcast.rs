@@ -0,0 +1,151 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::{FaultModel, Quorums, Threshold};
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Reliable broadcast of the crash model: the leader sends its value, every
+/// node acknowledges the first value it hears of to all, and delivers once a
+/// majority acknowledged it
+/// A node delivering means a correct node acknowledged, every correct node
+/// then hears of the value and delivers too, even if the leader crashed in
+/// the middle of its send
+#[derive(Clone, PartialEq)]
+pub(crate) enum MajorityMessage {
+    MB_SEND(Value),
+    MB_ACK(Value),
+}
+use MajorityMessage::*;
+
+impl ProtocolMessage for MajorityMessage {
+    const PROTOCOL: ProtocolId = MAJORITY_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        match self {
+            MB_SEND(_) => Phase::Init,
+            MB_ACK(_) => Phase::Ready,
+        }
+    }
+
+    /// Kind and value
+    fn size(&self) -> usize {
+        1 + std::mem::size_of::<Value>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            MB_SEND(_) => MB_SEND(v),
+            MB_ACK(_) => MB_ACK(v),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    fn value(&self) -> Option<Value> {
+        match self {
+            MB_SEND(v) | MB_ACK(v) => Some(*v),
+        }
+    }
+}
+
+impl fmt::Debug for MajorityMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MB_SEND(v) => write!(f, "<SEND, {}>", v),
+            MB_ACK(v) => write!(f, "<ACK, {}>", v),
+        }
+    }
+}
+
+/// Majority-ack broadcast as run by a node, tolerates less than half of
+/// the nodes crashing
+pub(crate) struct MajorityBroadcast {
+    acked: Option<Value>,
+    acks: BTreeMap<Value, BTreeSet<NodeId>>,
+}
+
+impl MajorityBroadcast {
+    pub fn new() -> Self {
+        MajorityBroadcast {
+            acked: None,
+            acks: BTreeMap::new(),
+        }
+    }
+
+    /// Acknowledge `v` unless the node already acknowledged a value
+    fn ack(&mut self, node: &mut NodeInternals, v: Value) {
+        if self.acked.is_some() {
+            return;
+        }
+        self.acked = Some(v);
+        self.acks.entry(v).or_default().insert(node.id);
+        node.send_ready(node.msg(MB_ACK(v)));
+    }
+
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        if node.crashed {
+            return ProtocolState::InProcess;
+        }
+        let quorum = node.quorums(FaultModel::Crash).quorum();
+        let delivered = self
+            .acks
+            .iter()
+            .find(|(_, acks)| acks.len() >= quorum)
+            .map(|(v, _)| *v);
+        match delivered {
+            Some(v) => node.deliver(v),
+            None => ProtocolState::InProcess,
+        }
+    }
+}
+
+impl Protocol for MajorityBroadcast {
+    /// Leader sends its input
+    /// Crashing nodes may stop halfway through their SEND or their ACKs
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.send_ready(node.msg(MB_SEND(input)));
+        if node.crashed {
+            return ProtocolState::InProcess;
+        }
+        self.ack(node, input);
+        self.progress(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<MajorityMessage>() {
+            Some(MB_SEND(v)) => self.ack(node, *v),
+            Some(MB_ACK(v)) => {
+                self.acks.entry(*v).or_default().insert(from);
+                // Leader may have crashed before sending to this node
+                self.ack(node, *v);
+            }
+            None => return ProtocolState::InProcess,
+        }
+        self.progress(node)
+    }
+
+    /// Faulty nodes of the crash model only stop
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+/// Messages, state and threshold of the majority broadcast
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let quorum = Quorums::new(FaultModel::Crash, num_nodes).quorum();
+    ProtocolDescription {
+        messages: vec![MessageSchema::of(MB_SEND(0)), MessageSchema::of(MB_ACK(0))],
+        state: vec!["acked", "acks"],
+        thresholds: vec![Threshold::new(
+            "crash_quorum",
+            quorum,
+            "majority of ACK to deliver, any two majorities share a node",
+        )],
+        ..ProtocolDescription::new(MAJORITY_BROADCAST)
+    }
+}
@@ -0,0 +1,377 @@
+use crate::merkle::Digest;
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::rng::mix;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::fmt;
+use std::time::Duration;
+
+/// How the nodes learn the transactions submitted to the others
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MempoolSync {
+    // Nodes relay every transaction they learn to their links, the naive
+    // full-batch broadcast
+    Full,
+    // Nodes announce the digests of the transactions they learn, their
+    // links request the ones they miss from the first node announcing them
+    Announce,
+    // Nodes send a Bloom filter of their pool every round, their links push
+    // the transactions it misses; a false positive is left out until a
+    // filter of a later round, hashed differently, catches it
+    Bloom { bits: usize, hashes: u32 },
+}
+
+impl fmt::Display for MempoolSync {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MempoolSync::Full => write!(f, "full"),
+            MempoolSync::Announce => write!(f, "announce"),
+            MempoolSync::Bloom { bits, hashes } => {
+                write!(f, "bloom {} bits {} hashes", bits, hashes)
+            }
+        }
+    }
+}
+
+/// Parameters of the mempool gossip
+#[derive(Clone, Debug, PartialEq)]
+pub struct MempoolConfig {
+    pub sync: MempoolSync,
+    // Transactions submitted to every node, several nodes may get the same
+    pub pools: BTreeMap<NodeId, Vec<Value>>,
+    // Bytes of the payload a transaction stands for, its digest takes the
+    // size of a `Digest`
+    pub tx_size: usize,
+    // Gossip rounds before the nodes output their pool, and time between
+    // two of them
+    pub rounds: u32,
+    pub period: Duration,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        MempoolConfig {
+            sync: MempoolSync::Full,
+            pools: BTreeMap::new(),
+            tx_size: 250,
+            rounds: 3,
+            period: Duration::from_millis(10),
+        }
+    }
+}
+
+/// Bloom filter of the transactions of a pool, the seed changes the hashes
+#[derive(Clone, PartialEq)]
+pub(crate) struct BloomFilter {
+    seed: u64,
+    hashes: u32,
+    bits: Vec<u64>,
+}
+
+impl BloomFilter {
+    fn new(seed: u64, bits: usize, hashes: u32) -> Self {
+        BloomFilter {
+            seed,
+            hashes,
+            bits: vec![0; bits.div_ceil(64).max(1)],
+        }
+    }
+
+    fn positions(&self, tx: Value) -> impl Iterator<Item = usize> + '_ {
+        let len = self.bits.len() * 64;
+        (0..self.hashes as u64).map(move |i| {
+            mix(self.seed ^ mix(tx as u64 ^ i.wrapping_mul(0x9e37_79b9))) as usize % len
+        })
+    }
+
+    fn insert(&mut self, tx: Value) {
+        let positions: Vec<usize> = self.positions(tx).collect();
+        for bit in positions {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    fn contains(&self, tx: Value) -> bool {
+        self.positions(tx)
+            .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+}
+
+#[derive(Clone, PartialEq)]
+pub(crate) enum MempoolMessage {
+    // Transactions and the bytes each of them takes
+    MP_TXS(Vec<Value>, usize),
+    // Transactions named by their digest, the ones the sender has and the
+    // ones it misses
+    MP_IHAVE(Vec<Value>),
+    MP_IWANT(Vec<Value>),
+    MP_FILTER(BloomFilter),
+    // Timer of the next gossip round
+    MP_ROUND(u32),
+}
+use MempoolMessage::*;
+
+impl ProtocolMessage for MempoolMessage {
+    const PROTOCOL: ProtocolId = MEMPOOL;
+
+    /// Transactions travel in Init messages, digests and filters in Echo
+    /// ones so that the stats of the phases tell them apart
+    fn phase(&self) -> Phase {
+        match self {
+            MP_TXS(..) => Phase::Init,
+            MP_IHAVE(_) | MP_IWANT(_) | MP_FILTER(_) => Phase::Echo,
+            MP_ROUND(_) => Phase::Control,
+        }
+    }
+
+    fn size(&self) -> usize {
+        1 + match self {
+            MP_TXS(txs, tx_size) => txs.len() * tx_size,
+            MP_IHAVE(digests) | MP_IWANT(digests) => digests.len() * std::mem::size_of::<Digest>(),
+            MP_FILTER(filter) => {
+                std::mem::size_of::<u64>()
+                    + std::mem::size_of::<u32>()
+                    + filter.bits.len() * std::mem::size_of::<u64>()
+            }
+            MP_ROUND(_) => std::mem::size_of::<u32>(),
+        }
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            MP_TXS(_, tx_size) => MP_TXS(vec![v], *tx_size),
+            MP_IHAVE(_) => MP_IHAVE(vec![v]),
+            MP_IWANT(_) => MP_IWANT(vec![v]),
+            MP_FILTER(filter) => {
+                let mut filter = BloomFilter::new(filter.seed, 0, filter.hashes);
+                filter.insert(v);
+                MP_FILTER(filter)
+            }
+            MP_ROUND(round) => MP_ROUND(*round),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+}
+
+impl fmt::Debug for MempoolMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MP_TXS(txs, _) => write!(f, "<TXS, {:?}>", txs),
+            MP_IHAVE(digests) => write!(f, "<IHAVE, {:?}>", digests),
+            MP_IWANT(digests) => write!(f, "<IWANT, {:?}>", digests),
+            MP_FILTER(filter) => write!(
+                f,
+                "<FILTER, seed {}, {} bits>",
+                filter.seed,
+                filter.bits.len() * 64
+            ),
+            MP_ROUND(round) => write!(f, "<ROUND, {}>", round),
+        }
+    }
+}
+
+/// Mempool of the transactions submitted to the nodes: every round the
+/// nodes gossip to their links what they learnt since the last one, and
+/// output their sorted pool after `rounds` rounds
+/// Transactions reach a node more than once in Full sync, duplicates are
+/// counted in `duplicate_transactions`; digests let the nodes only get the
+/// ones they miss, at the cost of a round trip
+/// Transactions invalid to the node are dropped and counted in
+/// `invalid_transactions`, unsolicited ones in Announce sync are ignored
+pub(crate) struct Mempool {
+    config: MempoolConfig,
+    pool: StateSet<Value>,
+    // Transactions learnt since the last round and the node they came from
+    fresh: StateMap<Value, NodeId>,
+    // Missing transactions the node asked for and the node it asked, then
+    // every node that announced them
+    requested: StateMap<Value, NodeId>,
+    announced: StateMap<Value, StateSet<NodeId>>,
+}
+
+impl Mempool {
+    pub fn new(config: MempoolConfig) -> Self {
+        Mempool {
+            config,
+            pool: StateSet::new(),
+            fresh: StateMap::new(),
+            requested: StateMap::new(),
+            announced: StateMap::new(),
+        }
+    }
+
+    fn learn(&mut self, node: &NodeInternals, tx: Value, from: NodeId) {
+        if !node.is_valid(tx) {
+            node.count("invalid_transactions", 1);
+        } else if self.pool.insert(tx) {
+            self.fresh.insert(tx, from);
+        } else {
+            node.count("duplicate_transactions", 1);
+        }
+    }
+
+    /// Fresh transactions, but the ones `to` sent
+    fn fresh_for(&self, to: NodeId) -> Vec<Value> {
+        self.fresh
+            .iter()
+            .filter(|(_, from)| **from != to)
+            .map(|(tx, _)| *tx)
+            .collect()
+    }
+
+    fn gossip(&mut self, node: &mut NodeInternals, round: u32) {
+        for to in node.links.clone() {
+            let msg = match self.config.sync {
+                MempoolSync::Full => MP_TXS(self.fresh_for(to), self.config.tx_size),
+                MempoolSync::Announce => MP_IHAVE(self.fresh_for(to)),
+                MempoolSync::Bloom { bits, hashes } => {
+                    let mut filter = BloomFilter::new(round as u64, bits, hashes);
+                    for tx in self.pool.iter() {
+                        filter.insert(*tx);
+                    }
+                    MP_FILTER(filter)
+                }
+            };
+            if !matches!(&msg, MP_TXS(txs, _) | MP_IHAVE(txs) if txs.is_empty()) {
+                node.send_to(to, node.msg(msg));
+            }
+        }
+        self.fresh.clear();
+        if self.config.sync == MempoolSync::Announce {
+            self.request_again(node);
+        }
+    }
+
+    /// Ask the next announcer for the transactions still missing, the one
+    /// asked may be faulty or slow
+    fn request_again(&mut self, node: &mut NodeInternals) {
+        let mut requests: StateMap<NodeId, Vec<Value>> = StateMap::new();
+        for (tx, asked) in self.requested.iter_mut() {
+            let announcers = &self.announced[tx];
+            let next = announcers
+                .range(*asked + 1..)
+                .next()
+                .or_else(|| announcers.iter().next())
+                .copied();
+            if let Some(next) = next.filter(|next| next != asked) {
+                *asked = next;
+                requests.entry(next).or_default().push(*tx);
+            }
+        }
+        for (to, txs) in requests {
+            node.count("rerequests", txs.len());
+            node.send_to(to, node.msg(MP_IWANT(txs)));
+        }
+    }
+
+    fn announced(&mut self, node: &mut NodeInternals, from: NodeId, digests: &[Value]) {
+        let mut missing = vec![];
+        for tx in digests {
+            if self.pool.contains(tx) {
+                continue;
+            }
+            self.announced.entry(*tx).or_default().insert(from);
+            if !self.requested.contains_key(tx) {
+                self.requested.insert(*tx, from);
+                missing.push(*tx);
+            }
+        }
+        if !missing.is_empty() {
+            node.send_to(from, node.msg(MP_IWANT(missing)));
+        }
+    }
+
+    /// Transactions of the pool among `txs`, sent to `to`
+    fn push(&self, node: &mut NodeInternals, to: NodeId, txs: Vec<Value>) {
+        if !txs.is_empty() {
+            node.send_to(to, node.msg(MP_TXS(txs, self.config.tx_size)));
+        }
+    }
+}
+
+impl Protocol for Mempool {
+    /// Input is ignored, the node starts from the transactions submitted
+    /// to it
+    fn start(&mut self, node: &mut NodeInternals, _input: Value) -> ProtocolState {
+        let submitted = self.config.pools.get(&node.id).cloned().unwrap_or_default();
+        for tx in submitted {
+            self.learn(node, tx, node.id);
+        }
+        if self.config.rounds == 0 {
+            return node.deliver_output(Output::Sequence(self.pool.iter().copied().collect()));
+        }
+        self.gossip(node, 0);
+        node.schedule(self.config.period, node.msg(MP_ROUND(1)));
+        ProtocolState::InProcess
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<MempoolMessage>() {
+            Some(MP_TXS(txs, _)) => {
+                for tx in txs {
+                    let solicited = self.requested.remove(tx).is_some();
+                    if solicited || self.config.sync != MempoolSync::Announce {
+                        self.learn(node, *tx, from);
+                    }
+                }
+            }
+            Some(MP_IHAVE(digests)) => self.announced(node, from, digests),
+            Some(MP_IWANT(digests)) => {
+                let txs = digests
+                    .iter()
+                    .filter(|tx| self.pool.contains(tx))
+                    .copied()
+                    .collect();
+                self.push(node, from, txs);
+            }
+            Some(MP_FILTER(filter)) => {
+                let txs = self
+                    .pool
+                    .iter()
+                    .filter(|tx| !filter.contains(**tx))
+                    .copied()
+                    .collect();
+                self.push(node, from, txs);
+            }
+            Some(MP_ROUND(round)) if from == node.id => {
+                if *round >= self.config.rounds {
+                    let pool = self.pool.iter().copied().collect();
+                    return node.deliver_output(Output::Sequence(pool));
+                }
+                self.gossip(node, *round);
+                node.schedule(self.config.period, node.msg(MP_ROUND(round + 1)));
+            }
+            _ => {}
+        }
+        ProtocolState::InProcess
+    }
+
+    /// Malicious node announces a transaction nobody has
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(MP_IHAVE(vec![rng.next_u64() as Value])))
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        vec![("pool", format!("{:?}", self.pool))]
+    }
+}
+
+/// Messages and state of the mempool
+pub fn describe(_num_nodes: usize) -> ProtocolDescription {
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(MP_TXS(vec![0], 0)),
+            MessageSchema::of(MP_IHAVE(vec![0])),
+            MessageSchema::of(MP_IWANT(vec![0])),
+            MessageSchema::of(MP_FILTER(BloomFilter::new(0, 64, 1))),
+            MessageSchema::of(MP_ROUND(0)),
+        ],
+        state: vec!["pool", "fresh", "requested", "announced"],
+        ..ProtocolDescription::new(MEMPOOL)
+    }
+}
@@ -1 +1,562 @@
+#[cfg(feature = "crypto")]
+use crate::crypto::{secret_sharing, threshold_enc, threshold_sig};
+use crate::filter::FilterConfig;
+use crate::network::{Output, Value};
+use crate::node::{NodeId, NodeInternals, ProtocolState};
+use crate::quorum::FaultModel;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use crate::validity::ValidityConfig;
+use std::any::Any;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+
+pub mod acs;
+pub mod atomic_broadcast;
+#[cfg(feature = "crypto")]
+pub mod avss;
+pub mod batched_broadcast;
+pub mod binary_agreement;
 pub mod bracha_broadcast;
+pub mod common_coin;
+pub mod common_subset;
+pub mod compose;
+pub mod consistent_broadcast;
+pub mod graded_broadcast;
+pub mod flooding;
+#[cfg(feature = "crypto")]
+pub mod honey_badger;
+pub mod leader_election;
+pub mod majority_broadcast;
+pub mod mempool;
+pub mod mvba;
+pub mod parallel_broadcast;
+#[cfg(feature = "crypto")]
+pub mod pbft;
+pub mod phase_king;
+pub mod paxos;
+pub mod routing;
+pub mod schema;
+pub mod smr;
+pub mod terminating_broadcast;
+#[cfg(feature = "crypto")]
+pub mod vaba;
+#[cfg(test)]
+pub(crate) mod testing;
+
+pub(crate) use compose::{Children, InstanceId};
+pub use schema::{MessageSchema, ProtocolDescription};
+
+/// Namespace of the messages of a protocol
+pub type ProtocolId = u16;
+
+/// Collections of protocol state, ordered so that iterating over them gives
+/// the same order on every node and in every run
+pub(crate) type StateMap<K, V> = BTreeMap<K, V>;
+pub(crate) type StateSet<T> = BTreeSet<T>;
+
+pub const BRACHA_BROADCAST: ProtocolId = 0;
+pub const PARALLEL_BROADCAST: ProtocolId = 1;
+pub const BATCHED_BROADCAST: ProtocolId = 2;
+pub const BINARY_AGREEMENT: ProtocolId = 3;
+pub const COMMON_SUBSET: ProtocolId = 4;
+pub const FLOODING: ProtocolId = 5;
+pub const ROUTING: ProtocolId = 6;
+pub const MAJORITY_BROADCAST: ProtocolId = 7;
+pub const PAXOS: ProtocolId = 8;
+pub const SMR: ProtocolId = 9;
+pub const BEN_OR: ProtocolId = 10;
+pub const COMMON_COIN: ProtocolId = 11;
+pub const HONEY_BADGER: ProtocolId = 12;
+pub const PBFT: ProtocolId = 13;
+pub const MEMPOOL: ProtocolId = 14;
+pub const MVBA: ProtocolId = 15;
+pub const CONSISTENT_BROADCAST: ProtocolId = 16;
+pub const AVSS: ProtocolId = 17;
+pub const GRADED_BROADCAST: ProtocolId = 18;
+pub const LEADER_ELECTION: ProtocolId = 19;
+pub const ATOMIC_BROADCAST: ProtocolId = 20;
+pub const TERMINATING_BROADCAST: ProtocolId = 21;
+pub const PHASE_KING: ProtocolId = 22;
+pub const VABA: ProtocolId = 23;
+
+/// Protocols the nodes run, by id
+/// Those relying on the threshold schemes need the `crypto` feature
+pub const PROTOCOLS: &[ProtocolId] = &[
+    BRACHA_BROADCAST,
+    PARALLEL_BROADCAST,
+    BATCHED_BROADCAST,
+    BINARY_AGREEMENT,
+    COMMON_SUBSET,
+    FLOODING,
+    MAJORITY_BROADCAST,
+    PAXOS,
+    SMR,
+    BEN_OR,
+    COMMON_COIN,
+    #[cfg(feature = "crypto")]
+    HONEY_BADGER,
+    #[cfg(feature = "crypto")]
+    PBFT,
+    MEMPOOL,
+    MVBA,
+    CONSISTENT_BROADCAST,
+    #[cfg(feature = "crypto")]
+    AVSS,
+    GRADED_BROADCAST,
+    LEADER_ELECTION,
+    ATOMIC_BROADCAST,
+    TERMINATING_BROADCAST,
+    PHASE_KING,
+    #[cfg(feature = "crypto")]
+    VABA,
+];
+
+/// Name of protocol `id`, as the CLI takes it
+pub fn name(id: ProtocolId) -> Option<&'static str> {
+    let name = match id {
+        BRACHA_BROADCAST => "bracha_broadcast",
+        PARALLEL_BROADCAST => "parallel_broadcast",
+        BATCHED_BROADCAST => "batched_broadcast",
+        BINARY_AGREEMENT => "binary_agreement",
+        COMMON_SUBSET => "common_subset",
+        FLOODING => "flooding",
+        ROUTING => "routing",
+        MAJORITY_BROADCAST => "majority_broadcast",
+        PAXOS => "paxos",
+        SMR => "smr",
+        BEN_OR => "ben_or",
+        COMMON_COIN => "common_coin",
+        HONEY_BADGER => "honey_badger",
+        PBFT => "pbft",
+        MEMPOOL => "mempool",
+        MVBA => "mvba",
+        CONSISTENT_BROADCAST => "consistent_broadcast",
+        AVSS => "avss",
+        GRADED_BROADCAST => "graded_broadcast",
+        LEADER_ELECTION => "leader_election",
+        ATOMIC_BROADCAST => "atomic_broadcast",
+        TERMINATING_BROADCAST => "terminating_broadcast",
+        PHASE_KING => "phase_king",
+        VABA => "vaba",
+        _ => return None,
+    };
+    Some(name)
+}
+
+/// Protocol named `name`, None if the nodes run no such protocol
+pub fn find(name: &str) -> Option<ProtocolId> {
+    PROTOCOLS
+        .iter()
+        .copied()
+        .find(|id| self::name(*id) == Some(name))
+}
+
+/// Code a node runs for a protocol, nodes of a deployment don't all run
+/// the same release
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Handler {
+    #[default]
+    Current,
+    // Older release, without the fixes made since then
+    Legacy,
+}
+
+impl FromStr for Handler {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "current" => Ok(Handler::Current),
+            "legacy" => Ok(Handler::Legacy),
+            _ => Err(format!("Unknown handler: {}", s)),
+        }
+    }
+}
+
+/// Message an honest node sends a single version of in an instance, such
+/// as its READY: `kind` and `slot` name the message, sending it with two
+/// values is equivocating
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Commitment {
+    pub kind: &'static str,
+    // Round or ballot, 0 for messages sent once per instance
+    pub slot: u64,
+    pub value: u64,
+}
+
+impl Commitment {
+    pub fn new(kind: &'static str, slot: u64, value: u64) -> Self {
+        Commitment { kind, slot, value }
+    }
+}
+
+/// Message of a protocol, carried by the network in an `Envelope`
+pub(crate) trait ProtocolMessage:
+    Clone + PartialEq + fmt::Debug + Send + Sync + 'static
+{
+    const PROTOCOL: ProtocolId;
+
+    fn phase(&self) -> Phase;
+
+    /// Number of bytes needed to encode the message
+    fn size(&self) -> usize;
+
+    /// Same message carrying the value `v`
+    fn with_value(&self, v: Value) -> Self;
+
+    /// Message sent instead by a Mirror node
+    fn malicious(&self) -> Self;
+
+    /// What the sender commits to, None if honest nodes may send several
+    /// versions of the message
+    fn commitment(&self) -> Option<Commitment> {
+        None
+    }
+
+    /// Value carried, checked against the range of the application by the
+    /// inbound filters, None for messages without one
+    fn value(&self) -> Option<Value> {
+        None
+    }
+}
+
+/// Protocol message whose type is only known by its protocol
+pub(crate) trait Payload: fmt::Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+    fn clone_payload(&self) -> Box<dyn Payload>;
+    fn eq_payload(&self, other: &dyn Payload) -> bool;
+    fn phase(&self) -> Phase;
+    fn size(&self) -> usize;
+    fn with_value(&self, v: Value) -> Box<dyn Payload>;
+    fn malicious(&self) -> Box<dyn Payload>;
+    fn commitment(&self) -> Option<Commitment>;
+    fn value(&self) -> Option<Value>;
+}
+
+impl<M: ProtocolMessage> Payload for M {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn clone_payload(&self) -> Box<dyn Payload> {
+        Box::new(self.clone())
+    }
+
+    fn eq_payload(&self, other: &dyn Payload) -> bool {
+        other.as_any().downcast_ref::<M>() == Some(self)
+    }
+
+    fn phase(&self) -> Phase {
+        ProtocolMessage::phase(self)
+    }
+
+    fn size(&self) -> usize {
+        ProtocolMessage::size(self)
+    }
+
+    fn with_value(&self, v: Value) -> Box<dyn Payload> {
+        Box::new(ProtocolMessage::with_value(self, v))
+    }
+
+    fn malicious(&self) -> Box<dyn Payload> {
+        Box::new(ProtocolMessage::malicious(self))
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        ProtocolMessage::commitment(self)
+    }
+
+    fn value(&self) -> Option<Value> {
+        ProtocolMessage::value(self)
+    }
+}
+
+/// Protocol message tagged with the protocol instance it belongs to
+pub(crate) struct Envelope {
+    pub instance: InstanceId,
+    pub payload: Box<dyn Payload>,
+}
+
+impl Envelope {
+    /// Message of the root instance of protocol `M`
+    pub(crate) fn new<M: ProtocolMessage>(msg: M) -> Self {
+        Envelope::within(InstanceId::root(M::PROTOCOL), msg)
+    }
+
+    pub(crate) fn within<M: ProtocolMessage>(instance: InstanceId, msg: M) -> Self {
+        Envelope {
+            instance,
+            payload: Box::new(msg),
+        }
+    }
+
+    pub(crate) fn protocol(&self) -> ProtocolId {
+        self.instance.protocol()
+    }
+
+    /// Message of protocol `M`, None if the envelope belongs to another one
+    pub(crate) fn open<M: ProtocolMessage>(&self) -> Option<&M> {
+        if self.protocol() != M::PROTOCOL {
+            return None;
+        }
+        self.payload.as_any().downcast_ref::<M>()
+    }
+
+    /// Number of bytes needed to encode the instance and the payload
+    pub(crate) fn size(&self) -> usize {
+        self.instance.size() + self.payload.size()
+    }
+
+    pub(crate) fn commitment(&self) -> Option<Commitment> {
+        self.payload.commitment()
+    }
+
+    pub(crate) fn value(&self) -> Option<Value> {
+        self.payload.value()
+    }
+
+    pub(crate) fn with_value(&self, v: Value) -> Self {
+        Envelope {
+            instance: self.instance.clone(),
+            payload: self.payload.with_value(v),
+        }
+    }
+
+    pub(crate) fn malicious(&self) -> Self {
+        Envelope {
+            instance: self.instance.clone(),
+            payload: self.payload.malicious(),
+        }
+    }
+}
+
+impl Clone for Envelope {
+    fn clone(&self) -> Self {
+        Envelope {
+            instance: self.instance.clone(),
+            payload: self.payload.clone_payload(),
+        }
+    }
+}
+
+impl PartialEq for Envelope {
+    fn eq(&self, other: &Self) -> bool {
+        self.instance == other.instance && self.payload.eq_payload(other.payload.as_ref())
+    }
+}
+
+impl fmt::Debug for Envelope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.instance.parent().is_none() {
+            write!(f, "{:?}", self.payload)
+        } else {
+            write!(f, "{:?} {:?}", self.instance, self.payload)
+        }
+    }
+}
+
+/// Protocol run by the nodes, one value per instance owning its local state
+pub(crate) trait Protocol: Send {
+    /// Start the instance with the input of the node
+    fn start(&mut self, _node: &mut NodeInternals, _input: Value) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    /// Handle a message of this instance
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState;
+
+    /// Handle the output of a sub-instance spawned by this instance
+    fn child_output(
+        &mut self,
+        _node: &mut NodeInternals,
+        _child: &InstanceId,
+        _output: &Output,
+    ) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    /// Message sent by Random nodes whenever they receive a message
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>>;
+
+    /// Local state that should be the same on every honest node at the end
+    /// of a run, beyond the output
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        vec![]
+    }
+
+    /// Local invariants of the instance, checked after every message in
+    /// debug builds: what is broken, with the state of the instance
+    fn check(&self, _node: &NodeInternals) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Handle the next messages with `handler`, keeping the local state
+    /// Returns false if the protocol has no such handler
+    fn switch(&mut self, handler: Handler) -> bool {
+        handler == Handler::Current
+    }
+
+    /// Run the release tagged `tag`, one of `versions` of the protocol
+    /// Returns false if the protocol has no such release
+    fn set_version(&mut self, _tag: &str) -> bool {
+        false
+    }
+}
+
+/// Version tags of the releases of protocol `id` nodes can run side by
+/// side, the first one is run by default
+pub fn versions(id: ProtocolId) -> &'static [&'static str] {
+    match id {
+        BRACHA_BROADCAST => &bracha_broadcast::VERSIONS,
+        _ => &[],
+    }
+}
+
+/// Failures protocol `id` is designed for, networks with more faulty nodes
+/// than it tolerates break it
+pub fn fault_model(id: ProtocolId) -> FaultModel {
+    match id {
+        MAJORITY_BROADCAST | PAXOS => FaultModel::Crash,
+        _ => FaultModel::Byzantine,
+    }
+}
+
+/// Messages, state and thresholds of protocol `id` run by `num_nodes`
+/// nodes, None if nodes can't run it
+pub fn describe(id: ProtocolId, num_nodes: usize) -> Option<ProtocolDescription> {
+    let description = match id {
+        BRACHA_BROADCAST => bracha_broadcast::describe(num_nodes),
+        PARALLEL_BROADCAST => parallel_broadcast::describe(num_nodes),
+        BATCHED_BROADCAST => batched_broadcast::describe(num_nodes),
+        BINARY_AGREEMENT => binary_agreement::describe(num_nodes),
+        COMMON_SUBSET => common_subset::describe(num_nodes),
+        FLOODING => flooding::describe(num_nodes),
+        MAJORITY_BROADCAST => majority_broadcast::describe(num_nodes),
+        PAXOS => paxos::describe(num_nodes),
+        SMR => smr::describe(num_nodes),
+        BEN_OR => binary_agreement::describe_ben_or(num_nodes),
+        COMMON_COIN => common_coin::describe(num_nodes),
+        #[cfg(feature = "crypto")]
+        HONEY_BADGER => honey_badger::describe(num_nodes),
+        #[cfg(feature = "crypto")]
+        PBFT => pbft::describe(num_nodes),
+        MEMPOOL => mempool::describe(num_nodes),
+        MVBA => mvba::describe(num_nodes),
+        CONSISTENT_BROADCAST => consistent_broadcast::describe(num_nodes),
+        #[cfg(feature = "crypto")]
+        AVSS => avss::describe(num_nodes),
+        GRADED_BROADCAST => graded_broadcast::describe(num_nodes),
+        LEADER_ELECTION => leader_election::describe(num_nodes),
+        ATOMIC_BROADCAST => atomic_broadcast::describe(num_nodes),
+        TERMINATING_BROADCAST => terminating_broadcast::describe(num_nodes),
+        PHASE_KING => phase_king::describe(num_nodes),
+        #[cfg(feature = "crypto")]
+        VABA => vaba::describe(num_nodes),
+        _ => return None,
+    };
+    Some(description)
+}
+
+/// Parameters of the protocols, shared by the network with the nodes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ProtocolConfig {
+    pub atomic: atomic_broadcast::AtomicConfig,
+    #[cfg(feature = "crypto")]
+    pub avss: avss::AvssConfig,
+    pub broadcast: bracha_broadcast::BroadcastConfig,
+    pub coin: common_coin::CoinConfig,
+    #[cfg(feature = "crypto")]
+    pub encryption: threshold_enc::EncryptionConfig,
+    pub filter: FilterConfig,
+    pub flood: flooding::FloodConfig,
+    pub graded: graded_broadcast::GradedConfig,
+    #[cfg(feature = "crypto")]
+    pub honey_badger: honey_badger::HoneyBadgerConfig,
+    pub mempool: mempool::MempoolConfig,
+    pub paxos: paxos::PaxosConfig,
+    #[cfg(feature = "crypto")]
+    pub pbft: pbft::PbftConfig,
+    pub phase_king: phase_king::PhaseKingConfig,
+    #[cfg(feature = "crypto")]
+    pub sharing: secret_sharing::SharingConfig,
+    #[cfg(feature = "crypto")]
+    pub signatures: threshold_sig::SignatureConfig,
+    pub smr: smr::SmrConfig,
+    pub terminating: terminating_broadcast::TerminatingConfig,
+    pub validity: ValidityConfig,
+}
+
+/// New instance of the protocol `id`, None if nodes can't run it
+pub(crate) fn create(id: ProtocolId, config: &ProtocolConfig) -> Option<Box<dyn Protocol>> {
+    match id {
+        BRACHA_BROADCAST => Some(Box::new(bracha_broadcast::BrachaBroadcast::new(
+            config.broadcast.clone(),
+        ))),
+        PARALLEL_BROADCAST => Some(Box::new(parallel_broadcast::ParallelBroadcast::new())),
+        BATCHED_BROADCAST => Some(Box::new(batched_broadcast::BatchedBroadcast::new())),
+        BINARY_AGREEMENT => Some(Box::new(binary_agreement::BinaryAgreement::new())),
+        COMMON_SUBSET => Some(Box::new(common_subset::CommonSubset::new())),
+        FLOODING => Some(Box::new(flooding::Flooding::new(config.flood.clone()))),
+        MAJORITY_BROADCAST => Some(Box::new(majority_broadcast::MajorityBroadcast::new())),
+        PAXOS => Some(Box::new(paxos::Paxos::new(config.paxos.clone()))),
+        SMR => Some(Box::new(smr::Smr::new(config.smr.clone()))),
+        BEN_OR => Some(Box::new(binary_agreement::BenOr::new())),
+        #[cfg(not(feature = "crypto"))]
+        COMMON_COIN => Some(Box::new(common_coin::CommonCoin::new(config.coin.clone()))),
+        #[cfg(feature = "crypto")]
+        COMMON_COIN => Some(Box::new(common_coin::CommonCoin::new(
+            config.coin.clone(),
+            config.signatures.clone(),
+        ))),
+        #[cfg(feature = "crypto")]
+        HONEY_BADGER => Some(Box::new(honey_badger::HoneyBadger::new(
+            config.honey_badger.clone(),
+            config.encryption.clone(),
+        ))),
+        #[cfg(feature = "crypto")]
+        PBFT => Some(Box::new(pbft::Pbft::new(
+            config.pbft.clone(),
+            config.signatures.clone(),
+        ))),
+        MEMPOOL => Some(Box::new(mempool::Mempool::new(config.mempool.clone()))),
+        MVBA => Some(Box::new(mvba::Mvba::new())),
+        CONSISTENT_BROADCAST => Some(Box::new(consistent_broadcast::ConsistentBroadcast::new())),
+        GRADED_BROADCAST => Some(Box::new(graded_broadcast::GradedBroadcast::new(
+            config.graded.clone(),
+        ))),
+        #[cfg(feature = "crypto")]
+        AVSS => Some(Box::new(avss::Avss::new(
+            config.avss.clone(),
+            config.sharing.clone(),
+        ))),
+        LEADER_ELECTION => Some(Box::new(leader_election::LeaderElection::new())),
+        ATOMIC_BROADCAST => Some(Box::new(atomic_broadcast::AtomicBroadcast::new(
+            config.atomic.clone(),
+        ))),
+        TERMINATING_BROADCAST => Some(Box::new(
+            terminating_broadcast::TerminatingBroadcast::new(config.terminating.clone()),
+        )),
+        PHASE_KING => Some(Box::new(phase_king::PhaseKing::new(
+            config.phase_king.clone(),
+        ))),
+        #[cfg(feature = "crypto")]
+        VABA => Some(Box::new(vaba::Vaba::new(config.signatures.clone()))),
+        #[cfg(test)]
+        testing::PANICKING => Some(Box::new(testing::Panicking)),
+        #[cfg(test)]
+        testing::RELAY_PANICKING => Some(Box::new(testing::RelayPanicking)),
+        #[cfg(test)]
+        testing::TICKING => Some(Box::new(testing::Ticking::new())),
+        #[cfg(test)]
+        testing::EQUIVOCATING => Some(Box::new(testing::Equivocating)),
+        #[cfg(test)]
+        testing::FORGETFUL => Some(Box::new(testing::Forgetful::new())),
+        #[cfg(all(test, feature = "crypto"))]
+        testing::CERTIFYING => Some(Box::new(testing::Certifying::new(
+            config.signatures.clone(),
+        ))),
+        _ => None,
+    }
+}
@@ -0,0 +1,149 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+
+/// Multi-valued Byzantine agreement reduced to binary agreement: every
+/// node reliably broadcasts its input, then the nodes elect the broadcasts
+/// one after the other, one binary agreement per election, and decide the
+/// value of the first one they accept
+/// Node `(tag + round) % n` is the candidate of a round, nodes vote for it
+/// once `min_honnest_nodes` broadcasts delivered to them if its value
+/// delivered and is valid to them, the rejected ones are counted in
+/// `rejected`
+/// Instances a parent tags one after the other start from another node, so
+/// that a node doesn't win every instance
+/// An accepted candidate delivered to some honest node, it delivers to all
+/// of them: nodes decide the same value, valid to some honest node
+/// Rounds are counted in `elections`, a round that rejects its candidate
+/// moves on to the next node until the broadcasts of the honest nodes
+/// delivered everywhere
+pub(crate) struct Mvba {
+    broadcasts: Children,
+    agreements: Children,
+    // Round of the agreement under way, None before the first one
+    round: Option<u32>,
+    // Candidate elected by the agreements
+    elected: Option<NodeId>,
+}
+
+impl Mvba {
+    pub fn new() -> Self {
+        Mvba {
+            broadcasts: Children::new(),
+            agreements: Children::new(),
+            round: None,
+            elected: None,
+        }
+    }
+
+    /// Candidate of `round` for the instance being run
+    fn candidate(node: &NodeInternals, round: u32) -> NodeId {
+        let tag = node.current.as_ref().map_or(0, InstanceId::tag);
+        (tag as usize + round as usize) % node.num_nodes
+    }
+
+    /// Vote on the candidate of `round`
+    fn elect(&mut self, node: &mut NodeInternals, round: u32) {
+        self.round = Some(round);
+        node.count("elections", 1);
+        let candidate = Mvba::candidate(node, round) as u32;
+        let value = self.broadcasts.output(candidate).and_then(Output::value);
+        let accept = value.is_some_and(|v| node.is_valid(v));
+        if value.is_some() && !accept {
+            node.count("rejected", 1);
+        }
+        node.spawn(BINARY_AGREEMENT, round, accept as Value);
+    }
+
+    /// Value of the elected candidate, once its broadcast delivered
+    fn decide(&self, node: &mut NodeInternals) -> ProtocolState {
+        let value = self
+            .elected
+            .and_then(|candidate| self.broadcasts.output(candidate as u32))
+            .and_then(Output::value);
+        match value {
+            Some(v) => node.deliver(v),
+            None => ProtocolState::InProcess,
+        }
+    }
+}
+
+impl Protocol for Mvba {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.spawn(BRACHA_BROADCAST, node.id as u32, input);
+        ProtocolState::InProcess
+    }
+
+    // Messages go to the broadcast and agreement instances
+    fn handle(
+        &mut self,
+        _node: &mut NodeInternals,
+        _from: NodeId,
+        _msg: &Envelope,
+    ) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        match child.protocol() {
+            BRACHA_BROADCAST => {
+                if !self.broadcasts.record(child, output) {
+                    return ProtocolState::InProcess;
+                }
+                if self.round.is_none() && self.broadcasts.len() >= node.min_honnest_nodes {
+                    self.elect(node, 0);
+                }
+            }
+            BINARY_AGREEMENT => {
+                if !self.agreements.record(child, output) || self.elected.is_some() {
+                    return ProtocolState::InProcess;
+                }
+                let round = child.tag();
+                if self.round != Some(round) {
+                    return ProtocolState::InProcess;
+                }
+                if output.value() == Some(1) {
+                    self.elected = Some(Mvba::candidate(node, round));
+                    node.debug(&format!("round {} elected {:?}", round, self.elected));
+                } else {
+                    self.elect(node, round + 1);
+                }
+            }
+            _ => return ProtocolState::InProcess,
+        }
+        self.decide(node)
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.elected {
+            Some(candidate) => vec![("elected", candidate.to_string())],
+            None => vec![],
+        }
+    }
+}
+
+/// Instances and threshold of the multi-valued agreement
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (_, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        state: vec!["broadcasts", "agreements", "round", "elected"],
+        thresholds: vec![Threshold::new(
+            "delivered_broadcasts",
+            quorum,
+            "n - f broadcasts delivered before the first agreement",
+        )],
+        children: vec![BRACHA_BROADCAST, BINARY_AGREEMENT],
+        ..ProtocolDescription::new(MVBA)
+    }
+}
@@ -0,0 +1,80 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+
+/// Every node reliably broadcasts its input in its own Bracha instance,
+/// tagged by the id of the node
+/// Node outputs the values of the first `min_honnest_nodes` instances to
+/// deliver, which may differ from one node to the other
+pub(crate) struct ParallelBroadcast {
+    broadcasts: Children,
+}
+
+impl ParallelBroadcast {
+    pub fn new() -> Self {
+        ParallelBroadcast {
+            broadcasts: Children::new(),
+        }
+    }
+}
+
+impl Protocol for ParallelBroadcast {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.spawn(BRACHA_BROADCAST, node.id as u32, input);
+        ProtocolState::InProcess
+    }
+
+    // Messages go to the broadcast instances
+    fn handle(
+        &mut self,
+        _node: &mut NodeInternals,
+        _from: NodeId,
+        _msg: &Envelope,
+    ) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        if !self.broadcasts.record(child, output) {
+            return ProtocolState::InProcess;
+        }
+        node.debug(&self.broadcasts);
+        if self.broadcasts.len() < node.min_honnest_nodes {
+            return ProtocolState::InProcess;
+        }
+
+        let values = self
+            .broadcasts
+            .outputs()
+            .iter()
+            .filter_map(|(tag, output)| output.value().map(|v| (*tag as NodeId, v)))
+            .collect();
+        ProtocolState::Terminated(Output::Set(values))
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+/// Instances and threshold of the parallel broadcasts
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (_, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        state: vec!["broadcasts"],
+        thresholds: vec![Threshold::new(
+            "delivered_broadcasts",
+            quorum,
+            "n - f broadcasts delivered to output, the others may never deliver",
+        )],
+        children: vec![BRACHA_BROADCAST],
+        ..ProtocolDescription::new(PARALLEL_BROADCAST)
+    }
+}
@@ -0,0 +1,371 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::{FaultModel, Quorums, Threshold};
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::time::Duration;
+
+/// Parameters of Paxos
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaxosConfig {
+    // Only the node every node believes to be the leader proposes, others
+    // hand it their input
+    // Without it every node with an input proposes and retries right away
+    // when preempted, proposers may preempt each other forever
+    pub leader_election: bool,
+    // Time a node waits for a decision before it suspects the leader and
+    // moves on to the next node, doubled at every suspicion
+    pub election_timeout: Duration,
+}
+
+impl Default for PaxosConfig {
+    fn default() -> Self {
+        PaxosConfig {
+            leader_election: true,
+            election_timeout: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Proposal number, ordered by round then by proposer
+pub(crate) type Ballot = (u32, NodeId);
+
+/// Single-decree Paxos of the crash model: proposers get a majority of
+/// acceptors to promise to ignore lower ballots, then to accept the value
+/// of the highest ballot they accepted before, or the input of the proposer
+/// Every node accepts and learns, nodes decide once a majority accepted the
+/// same ballot
+#[derive(Clone, PartialEq)]
+pub(crate) enum PaxosMessage {
+    PX_PREPARE(Ballot),
+    // Promise, with the last proposal the acceptor accepted
+    PX_PROMISE(Ballot, Option<(Ballot, Value)>),
+    PX_ACCEPT(Ballot, Value),
+    PX_ACCEPTED(Ballot, Value),
+    // Ballot is lower than the one the acceptor promised
+    PX_NACK(Ballot, Ballot),
+    // Input of a node for the leader to propose
+    PX_FORWARD(Value),
+    // Timer of a node waiting for a decision from this leader
+    PX_SUSPECT(NodeId),
+}
+use PaxosMessage::*;
+
+impl ProtocolMessage for PaxosMessage {
+    const PROTOCOL: ProtocolId = PAXOS;
+
+    fn phase(&self) -> Phase {
+        match self {
+            PX_FORWARD(_) => Phase::Leader,
+            PX_PREPARE(_) => Phase::Init,
+            PX_PROMISE(..) | PX_NACK(..) => Phase::Echo,
+            PX_ACCEPT(..) | PX_ACCEPTED(..) => Phase::Ready,
+            PX_SUSPECT(_) => Phase::Control,
+        }
+    }
+
+    /// Kind, ballots and values
+    fn size(&self) -> usize {
+        let ballot = std::mem::size_of::<u32>() + std::mem::size_of::<NodeId>();
+        let value = std::mem::size_of::<Value>();
+        1 + match self {
+            PX_PREPARE(_) => ballot,
+            PX_PROMISE(_, accepted) => ballot + accepted.map_or(0, |_| ballot + value),
+            PX_ACCEPT(..) | PX_ACCEPTED(..) => ballot + value,
+            PX_NACK(..) => 2 * ballot,
+            PX_FORWARD(_) => value,
+            PX_SUSPECT(_) => std::mem::size_of::<NodeId>(),
+        }
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            PX_PROMISE(b, Some((accepted, _))) => PX_PROMISE(*b, Some((*accepted, v))),
+            PX_ACCEPT(b, _) => PX_ACCEPT(*b, v),
+            PX_ACCEPTED(b, _) => PX_ACCEPTED(*b, v),
+            PX_FORWARD(_) => PX_FORWARD(v),
+            msg => msg.clone(),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+}
+
+impl fmt::Debug for PaxosMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ballot = |(round, proposer): &Ballot| format!("{}.{}", round, proposer);
+        match self {
+            PX_PREPARE(b) => write!(f, "<PREPARE, {}>", ballot(b)),
+            PX_PROMISE(b, None) => write!(f, "<PROMISE, {}>", ballot(b)),
+            PX_PROMISE(b, Some((accepted, v))) => {
+                write!(f, "<PROMISE, {}, {} {}>", ballot(b), ballot(accepted), v)
+            }
+            PX_ACCEPT(b, v) => write!(f, "<ACCEPT, {}, {}>", ballot(b), v),
+            PX_ACCEPTED(b, v) => write!(f, "<ACCEPTED, {}, {}>", ballot(b), v),
+            PX_NACK(b, promised) => write!(f, "<NACK, {}, {}>", ballot(b), ballot(promised)),
+            PX_FORWARD(v) => write!(f, "<FORWARD, {}>", v),
+            PX_SUSPECT(leader) => write!(f, "<SUSPECT, {}>", leader),
+        }
+    }
+}
+
+/// Proposal of a node, from PREPARE to ACCEPT
+#[derive(Debug)]
+struct Proposal {
+    ballot: Ballot,
+    promises: BTreeMap<NodeId, Option<(Ballot, Value)>>,
+    accepting: bool,
+}
+
+/// Paxos as run by a node, proposer, acceptor and learner at once
+pub(crate) struct Paxos {
+    config: PaxosConfig,
+    // Value the node proposes unless it has to adopt an accepted one
+    input: Option<Value>,
+    proposal: Option<Proposal>,
+    // Acceptor
+    promised: Option<Ballot>,
+    accepted: Option<(Ballot, Value)>,
+    // Learner: acceptors of every ballot
+    accepted_by: BTreeMap<Ballot, (Value, BTreeSet<NodeId>)>,
+    // Leader election: nodes suspected to have crashed
+    suspected: BTreeSet<NodeId>,
+    timeout: Duration,
+}
+
+impl Paxos {
+    pub fn new(config: PaxosConfig) -> Self {
+        let timeout = config.election_timeout;
+        Paxos {
+            config,
+            input: None,
+            proposal: None,
+            promised: None,
+            accepted: None,
+            accepted_by: BTreeMap::new(),
+            suspected: BTreeSet::new(),
+            timeout,
+        }
+    }
+
+    /// Lowest node not suspected to have crashed
+    fn leader(&self, node: &NodeInternals) -> NodeId {
+        (0..node.num_nodes)
+            .find(|id| !self.suspected.contains(id))
+            .unwrap_or(node.id)
+    }
+
+    /// Propose if the node leads, hand the input to the leader otherwise
+    fn elect(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let input = match self.input {
+            Some(input) => input,
+            None => return ProtocolState::InProcess,
+        };
+        if !self.config.leader_election {
+            return self.prepare(node, 0);
+        }
+        let leader = self.leader(node);
+        if leader == node.id {
+            return self.prepare(node, 0);
+        }
+        node.send_to(leader, node.msg(PX_FORWARD(input)));
+        node.schedule(self.timeout, node.msg(PX_SUSPECT(leader)));
+        ProtocolState::InProcess
+    }
+
+    /// Start a proposal with a ballot of at least `round`
+    fn prepare(&mut self, node: &mut NodeInternals, round: u32) -> ProtocolState {
+        let round = match &self.proposal {
+            Some(proposal) => round.max(proposal.ballot.0 + 1),
+            None => round,
+        };
+        let ballot = (round, node.id);
+        self.proposal = Some(Proposal {
+            ballot,
+            promises: BTreeMap::new(),
+            accepting: false,
+        });
+        node.send_ready(node.msg(PX_PREPARE(ballot)));
+        if node.crashed {
+            return ProtocolState::InProcess;
+        }
+        match self.promise(ballot) {
+            Ok(accepted) => self.promised_to(node, node.id, ballot, accepted),
+            Err(_) => ProtocolState::InProcess,
+        }
+    }
+
+    /// Acceptor side of PREPARE: what it accepted, or the ballot it promised
+    fn promise(&mut self, ballot: Ballot) -> Result<Option<(Ballot, Value)>, Ballot> {
+        match self.promised {
+            Some(promised) if promised > ballot => Err(promised),
+            _ => {
+                self.promised = Some(ballot);
+                Ok(self.accepted)
+            }
+        }
+    }
+
+    /// Proposer side of PROMISE
+    fn promised_to(
+        &mut self,
+        node: &mut NodeInternals,
+        from: NodeId,
+        ballot: Ballot,
+        accepted: Option<(Ballot, Value)>,
+    ) -> ProtocolState {
+        let quorum = node.quorums(FaultModel::Crash).quorum();
+        let proposal = match self.proposal.as_mut() {
+            Some(proposal) if proposal.ballot == ballot && !proposal.accepting => proposal,
+            _ => return ProtocolState::InProcess,
+        };
+        proposal.promises.insert(from, accepted);
+        if proposal.promises.len() < quorum {
+            return ProtocolState::InProcess;
+        }
+        proposal.accepting = true;
+        // Value of the highest ballot accepted so far may already be chosen
+        let v = match proposal.promises.values().flatten().max() {
+            Some((_, v)) => *v,
+            None => self.input.unwrap_or_default(),
+        };
+        node.send_ready(node.msg(PX_ACCEPT(ballot, v)));
+        if node.crashed {
+            return ProtocolState::InProcess;
+        }
+        self.accept(node, ballot, v)
+    }
+
+    /// Acceptor side of ACCEPT
+    fn accept(&mut self, node: &mut NodeInternals, ballot: Ballot, v: Value) -> ProtocolState {
+        if self.promised.is_some_and(|promised| promised > ballot) {
+            return ProtocolState::InProcess;
+        }
+        self.promised = Some(ballot);
+        self.accepted = Some((ballot, v));
+        node.send_ready(node.msg(PX_ACCEPTED(ballot, v)));
+        if node.crashed {
+            return ProtocolState::InProcess;
+        }
+        self.learn(node, node.id, ballot, v)
+    }
+
+    /// Learner side of ACCEPTED
+    fn learn(
+        &mut self,
+        node: &mut NodeInternals,
+        from: NodeId,
+        ballot: Ballot,
+        v: Value,
+    ) -> ProtocolState {
+        let quorum = node.quorums(FaultModel::Crash).quorum();
+        let (_, acceptors) = self
+            .accepted_by
+            .entry(ballot)
+            .or_insert_with(|| (v, BTreeSet::new()));
+        acceptors.insert(from);
+        if acceptors.len() >= quorum {
+            node.deliver(v)
+        } else {
+            ProtocolState::InProcess
+        }
+    }
+}
+
+impl Protocol for Paxos {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        if self.input.is_none() {
+            self.input = Some(input);
+        }
+        self.elect(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<PaxosMessage>() {
+            Some(PX_PREPARE(ballot)) => {
+                let reply = match self.promise(*ballot) {
+                    Ok(accepted) => PX_PROMISE(*ballot, accepted),
+                    Err(promised) => PX_NACK(*ballot, promised),
+                };
+                node.send_to(from, node.msg(reply));
+                ProtocolState::InProcess
+            }
+            Some(PX_PROMISE(ballot, accepted)) => self.promised_to(node, from, *ballot, *accepted),
+            Some(PX_ACCEPT(ballot, v)) => {
+                if let Some(promised) = self.promised.filter(|promised| promised > ballot) {
+                    node.send_to(from, node.msg(PX_NACK(*ballot, promised)));
+                    return ProtocolState::InProcess;
+                }
+                self.accept(node, *ballot, *v)
+            }
+            Some(PX_ACCEPTED(ballot, v)) => self.learn(node, from, *ballot, *v),
+            // Preempted by a higher ballot, try again above it
+            Some(PX_NACK(ballot, promised)) => match &self.proposal {
+                Some(proposal) if proposal.ballot == *ballot => self.prepare(node, promised.0 + 1),
+                _ => ProtocolState::InProcess,
+            },
+            Some(PX_FORWARD(v)) => {
+                if self.input.is_some() {
+                    return ProtocolState::InProcess;
+                }
+                self.input = Some(*v);
+                self.elect(node)
+            }
+            Some(PX_SUSPECT(leader)) if from == node.id => {
+                if self.leader(node) != *leader {
+                    return ProtocolState::InProcess;
+                }
+                node.debug(&format!("suspects leader {}", leader));
+                self.suspected.insert(*leader);
+                self.timeout *= 2;
+                self.elect(node)
+            }
+            _ => ProtocolState::InProcess,
+        }
+    }
+
+    /// Faulty nodes of the crash model only stop
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        vec![("suspected", format!("{:?}", self.suspected))]
+    }
+}
+
+/// Messages, state and threshold of Paxos
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let quorum = Quorums::new(FaultModel::Crash, num_nodes).quorum();
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(PX_PREPARE((0, 0))),
+            MessageSchema::of(PX_PROMISE((0, 0), None)),
+            MessageSchema::of(PX_ACCEPT((0, 0), 0)),
+            MessageSchema::of(PX_ACCEPTED((0, 0), 0)),
+            MessageSchema::of(PX_NACK((0, 0), (0, 0))),
+            MessageSchema::of(PX_FORWARD(0)),
+            MessageSchema::of(PX_SUSPECT(0)),
+        ],
+        state: vec![
+            "input",
+            "proposal",
+            "promised",
+            "accepted",
+            "accepted_by",
+            "suspected",
+            "timeout",
+        ],
+        thresholds: vec![Threshold::new(
+            "crash_quorum",
+            quorum,
+            "majority of PROMISE to propose and of ACCEPTED to decide",
+        )],
+        ..ProtocolDescription::new(PAXOS)
+    }
+}
@@ -0,0 +1,566 @@
+use crate::cost::CryptoOp;
+use crate::crypto::threshold_sig::{SignatureConfig, SignatureShare, ThresholdSignature};
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::mix;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Parameters of PBFT
+#[derive(Clone, Debug, PartialEq)]
+pub struct PbftConfig {
+    // Primary of the first view, the primaries of the next views follow it
+    pub primary: NodeId,
+    // Time a node waits for a decision in the first view before it asks
+    // for the next one, doubled at every view
+    pub view_timeout: Duration,
+}
+
+impl Default for PbftConfig {
+    fn default() -> Self {
+        PbftConfig {
+            primary: 0,
+            view_timeout: Duration::from_millis(50),
+        }
+    }
+}
+
+pub(crate) type View = u32;
+
+/// Signatures of `n - f` nodes on the same message, checked by anyone
+pub(crate) type Certificate = StateMap<NodeId, SignatureShare>;
+
+/// Value a node prepared in a view, with the PREPAREs of a quorum
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Prepared {
+    view: View,
+    value: Value,
+    certificate: Certificate,
+}
+
+/// VIEW_CHANGEs a primary starts its view from: the last value every node
+/// prepared and its signature of the message
+pub(crate) type ViewChanges = StateMap<NodeId, (Option<Prepared>, SignatureShare)>;
+
+/// PBFT consensus of the partially synchronous model: the primary of the
+/// view orders a value, every node PREPAREs it then COMMITs once a quorum
+/// prepared it, and decides once a quorum committed it
+/// Nodes that wait too long for a decision move to the next view, its
+/// primary proposes the value prepared in the latest view, if any
+/// Messages forwarded by other nodes are signed with the signing keys of
+/// the nodes, only the links are authenticated
+#[derive(Clone, PartialEq)]
+pub(crate) enum PbftMessage {
+    PB_PRE_PREPARE(View, Value),
+    PB_PREPARE(View, Value, SignatureShare),
+    PB_COMMIT(View, Value, SignatureShare),
+    // Commit certificate of a node that decided, for the nodes left behind
+    PB_DECIDED(View, Value, Certificate),
+    PB_VIEW_CHANGE(View, Option<Prepared>, SignatureShare),
+    // View changes of a quorum, the proof of the value of the new view
+    PB_NEW_VIEW(View, Value, ViewChanges),
+    // Timer of a node waiting for a decision in this view
+    PB_TIMEOUT(View),
+}
+use PbftMessage::*;
+
+// Kinds of the signed messages
+const PREPARE: u64 = 1;
+const COMMIT: u64 = 2;
+const VIEW_CHANGE: u64 = 3;
+
+/// Message a node signs
+fn digest(kind: u64, view: View, v: Value) -> u64 {
+    mix(mix(kind << 32 | view as u64) ^ v as u64)
+}
+
+fn view_change_digest(view: View, prepared: &Option<Prepared>) -> u64 {
+    let claim = prepared
+        .as_ref()
+        .map_or(0, |prepared| digest(PREPARE, prepared.view, prepared.value));
+    mix(digest(VIEW_CHANGE, view, 0) ^ claim)
+}
+
+fn certificate_size(certificate: &Certificate) -> usize {
+    certificate.len() * (std::mem::size_of::<NodeId>() + std::mem::size_of::<SignatureShare>())
+}
+
+fn prepared_size(prepared: &Option<Prepared>) -> usize {
+    prepared.as_ref().map_or(0, |prepared| {
+        std::mem::size_of::<View>()
+            + std::mem::size_of::<Value>()
+            + certificate_size(&prepared.certificate)
+    })
+}
+
+impl ProtocolMessage for PbftMessage {
+    const PROTOCOL: ProtocolId = PBFT;
+
+    fn phase(&self) -> Phase {
+        match self {
+            PB_PRE_PREPARE(..) => Phase::Init,
+            PB_PREPARE(..) => Phase::Echo,
+            PB_COMMIT(..) | PB_DECIDED(..) => Phase::Ready,
+            PB_VIEW_CHANGE(..) | PB_NEW_VIEW(..) => Phase::Leader,
+            PB_TIMEOUT(_) => Phase::Control,
+        }
+    }
+
+    /// Kind, views, values, signatures and certificates
+    fn size(&self) -> usize {
+        let view = std::mem::size_of::<View>();
+        let value = std::mem::size_of::<Value>();
+        let share = std::mem::size_of::<SignatureShare>();
+        1 + match self {
+            PB_PRE_PREPARE(..) => view + value,
+            PB_PREPARE(..) | PB_COMMIT(..) => view + value + share,
+            PB_DECIDED(_, _, certificate) => view + value + certificate_size(certificate),
+            PB_VIEW_CHANGE(_, prepared, _) => view + prepared_size(prepared) + share,
+            PB_NEW_VIEW(_, _, proof) => {
+                let node = std::mem::size_of::<NodeId>();
+                view + value
+                    + proof
+                        .values()
+                        .map(|(prepared, _)| node + prepared_size(prepared) + share)
+                        .sum::<usize>()
+            }
+            PB_TIMEOUT(_) => view,
+        }
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            PB_PRE_PREPARE(view, _) => PB_PRE_PREPARE(*view, v),
+            PB_PREPARE(view, _, share) => PB_PREPARE(*view, v, *share),
+            PB_COMMIT(view, _, share) => PB_COMMIT(*view, v, *share),
+            PB_DECIDED(view, _, certificate) => PB_DECIDED(*view, v, certificate.clone()),
+            PB_VIEW_CHANGE(view, Some(prepared), share) => PB_VIEW_CHANGE(
+                *view,
+                Some(Prepared {
+                    value: v,
+                    ..prepared.clone()
+                }),
+                *share,
+            ),
+            PB_NEW_VIEW(view, _, proof) => PB_NEW_VIEW(*view, v, proof.clone()),
+            msg => msg.clone(),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            PB_PRE_PREPARE(view, v) => {
+                Some(Commitment::new("PRE_PREPARE", *view as u64, *v as u64))
+            }
+            PB_PREPARE(view, v, _) => Some(Commitment::new("PREPARE", *view as u64, *v as u64)),
+            PB_COMMIT(view, v, _) => Some(Commitment::new("COMMIT", *view as u64, *v as u64)),
+            PB_NEW_VIEW(view, v, _) => Some(Commitment::new("NEW_VIEW", *view as u64, *v as u64)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for PbftMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prepared = |prepared: &Option<Prepared>| match prepared {
+            Some(prepared) => format!("{} {}", prepared.view, prepared.value),
+            None => String::from("none"),
+        };
+        match self {
+            PB_PRE_PREPARE(view, v) => write!(f, "<PRE_PREPARE, {}, {}>", view, v),
+            PB_PREPARE(view, v, _) => write!(f, "<PREPARE, {}, {}>", view, v),
+            PB_COMMIT(view, v, _) => write!(f, "<COMMIT, {}, {}>", view, v),
+            PB_DECIDED(view, v, _) => write!(f, "<DECIDED, {}, {}>", view, v),
+            PB_VIEW_CHANGE(view, claim, _) => {
+                write!(f, "<VIEW_CHANGE, {}, {}>", view, prepared(claim))
+            }
+            PB_NEW_VIEW(view, v, proof) => {
+                write!(f, "<NEW_VIEW, {}, {}, {} proofs>", view, v, proof.len())
+            }
+            PB_TIMEOUT(view) => write!(f, "<TIMEOUT, {}>", view),
+        }
+    }
+}
+
+/// PBFT as run by a node, primary of some views and backup of the others
+/// Invalid signatures and certificates are counted in `invalid_signatures`,
+/// the views the node moved to in `view_changes`
+pub(crate) struct Pbft {
+    config: PbftConfig,
+    scheme: Arc<dyn ThresholdSignature>,
+    // Value the node proposes when it is the primary
+    input: Option<Value>,
+    view: View,
+    // Node moved to `view` and waits for its NEW_VIEW
+    changing: bool,
+    // Value the primary of the view ordered
+    pre_prepared: Option<(View, Value)>,
+    prepares: StateMap<(View, Value), Certificate>,
+    commits: StateMap<(View, Value), Certificate>,
+    // Views the node sent its COMMIT in
+    committed: StateSet<View>,
+    // Last value the node prepared
+    prepared: Option<Prepared>,
+    view_changes: StateMap<View, ViewChanges>,
+    // Views the node started as their primary
+    new_views: StateSet<View>,
+    decided: Option<Value>,
+}
+
+impl Pbft {
+    pub fn new(config: PbftConfig, signatures: SignatureConfig) -> Self {
+        Pbft {
+            config,
+            scheme: signatures.scheme,
+            input: None,
+            view: 0,
+            changing: false,
+            pre_prepared: None,
+            prepares: StateMap::new(),
+            commits: StateMap::new(),
+            committed: StateSet::new(),
+            prepared: None,
+            view_changes: StateMap::new(),
+            new_views: StateSet::new(),
+            decided: None,
+        }
+    }
+
+    fn primary(&self, node: &NodeInternals, view: View) -> NodeId {
+        (self.config.primary + view as usize) % node.num_nodes
+    }
+
+    /// Wait for a decision in `view`, longer at every view
+    fn start_timer(&self, node: &mut NodeInternals, view: View) {
+        let timeout = self.config.view_timeout * 2u32.pow(view.min(16));
+        node.schedule(timeout, node.msg(PB_TIMEOUT(view)));
+    }
+
+    fn sign(&self, node: &NodeInternals, message: u64) -> SignatureShare {
+        node.crypto(CryptoOp::Sign, 1);
+        self.scheme.sign_share(&node.signing_key, message)
+    }
+
+    fn verify(
+        &self,
+        node: &NodeInternals,
+        from: NodeId,
+        message: u64,
+        share: SignatureShare,
+    ) -> bool {
+        node.crypto(CryptoOp::Verify, 1);
+        if self.scheme.verify_share(from, message, share) {
+            return true;
+        }
+        node.count("invalid_signatures", 1);
+        false
+    }
+
+    /// Whether `certificate` holds valid signatures of a quorum on `message`
+    fn certified(&self, node: &NodeInternals, message: u64, certificate: &Certificate) -> bool {
+        certificate.len() >= node.min_honnest_nodes
+            && certificate
+                .iter()
+                .all(|(from, share)| self.verify(node, *from, message, *share))
+    }
+
+    /// Whether the VIEW_CHANGE of `from` to `view` is signed and its claim
+    /// proven by a certificate of an earlier view
+    fn valid_view_change(
+        &self,
+        node: &NodeInternals,
+        from: NodeId,
+        view: View,
+        (prepared, share): &(Option<Prepared>, SignatureShare),
+    ) -> bool {
+        let claim = |prepared: &Prepared| {
+            prepared.view < view
+                && self.certified(
+                    node,
+                    digest(PREPARE, prepared.view, prepared.value),
+                    &prepared.certificate,
+                )
+        };
+        self.verify(node, from, view_change_digest(view, prepared), *share)
+            && prepared.as_ref().is_none_or(claim)
+    }
+
+    /// Value of the latest view a node of `proof` prepared
+    fn latest_prepared(proof: &ViewChanges) -> Option<Value> {
+        proof
+            .values()
+            .filter_map(|(prepared, _)| prepared.as_ref())
+            .max_by_key(|prepared| prepared.view)
+            .map(|prepared| prepared.value)
+    }
+
+    /// Primary of the view ordered `v`
+    fn pre_prepare(&mut self, node: &mut NodeInternals, view: View, v: Value) -> ProtocolState {
+        if view != self.view || self.changing || self.pre_prepared.is_some_and(|(w, _)| w >= view) {
+            return ProtocolState::InProcess;
+        }
+        self.pre_prepared = Some((view, v));
+        let share = self.sign(node, digest(PREPARE, view, v));
+        node.send_to_all(node.msg(PB_PREPARE(view, v, share)));
+        let id = node.id;
+        self.prepare(node, id, view, v, share)
+    }
+
+    fn prepare(
+        &mut self,
+        node: &mut NodeInternals,
+        from: NodeId,
+        view: View,
+        v: Value,
+        share: SignatureShare,
+    ) -> ProtocolState {
+        if !self.verify(node, from, digest(PREPARE, view, v), share) {
+            return ProtocolState::InProcess;
+        }
+        self.prepares
+            .entry((view, v))
+            .or_default()
+            .insert(from, share);
+        self.try_commit(node, view, v)
+    }
+
+    /// COMMIT once a quorum prepared the value the primary ordered
+    fn try_commit(&mut self, node: &mut NodeInternals, view: View, v: Value) -> ProtocolState {
+        let certificate = match self.prepares.get(&(view, v)) {
+            Some(certificate) if certificate.len() >= node.min_honnest_nodes => certificate,
+            _ => return ProtocolState::InProcess,
+        };
+        if view != self.view
+            || self.changing
+            || self.pre_prepared != Some((view, v))
+            || !self.committed.insert(view)
+        {
+            return ProtocolState::InProcess;
+        }
+        self.prepared = Some(Prepared {
+            view,
+            value: v,
+            certificate: certificate.clone(),
+        });
+        let share = self.sign(node, digest(COMMIT, view, v));
+        node.send_to_all(node.msg(PB_COMMIT(view, v, share)));
+        let id = node.id;
+        self.commit(node, id, view, v, share)
+    }
+
+    fn commit(
+        &mut self,
+        node: &mut NodeInternals,
+        from: NodeId,
+        view: View,
+        v: Value,
+        share: SignatureShare,
+    ) -> ProtocolState {
+        if !self.verify(node, from, digest(COMMIT, view, v), share) {
+            return ProtocolState::InProcess;
+        }
+        let certificate = self.commits.entry((view, v)).or_default();
+        certificate.insert(from, share);
+        if certificate.len() < node.min_honnest_nodes || self.decided.is_some() {
+            return ProtocolState::InProcess;
+        }
+        // Nodes stop once they decide, those left behind decide from the
+        // certificate
+        let certificate = certificate.clone();
+        node.send_to_all(node.msg(PB_DECIDED(view, v, certificate)));
+        self.decided = Some(v);
+        node.deliver(v)
+    }
+
+    /// Move to `view` and ask its primary to start it
+    fn view_change(&mut self, node: &mut NodeInternals, view: View) -> ProtocolState {
+        if view <= self.view {
+            return ProtocolState::InProcess;
+        }
+        node.debug(&format!("moves to view {}", view));
+        node.count("view_changes", 1);
+        self.view = view;
+        self.changing = true;
+        self.start_timer(node, view);
+        let prepared = self.prepared.clone();
+        let share = self.sign(node, view_change_digest(view, &prepared));
+        node.send_to_all(node.msg(PB_VIEW_CHANGE(view, prepared.clone(), share)));
+        let id = node.id;
+        self.view_changed(node, id, view, prepared, share)
+    }
+
+    fn view_changed(
+        &mut self,
+        node: &mut NodeInternals,
+        from: NodeId,
+        view: View,
+        prepared: Option<Prepared>,
+        share: SignatureShare,
+    ) -> ProtocolState {
+        let view_change = (prepared, share);
+        if !self.valid_view_change(node, from, view, &view_change) {
+            return ProtocolState::InProcess;
+        }
+        let view_changes = self.view_changes.entry(view).or_default();
+        view_changes.insert(from, view_change);
+        let count = view_changes.len();
+
+        // Some honest node gave up on the views before, the node follows
+        if view > self.view && count > node.max_malicious_nodes {
+            return self.view_change(node, view);
+        }
+        if self.primary(node, view) != node.id
+            || view != self.view
+            || !self.changing
+            || count < node.min_honnest_nodes
+            || !self.new_views.insert(view)
+        {
+            return ProtocolState::InProcess;
+        }
+        let proof: ViewChanges = self.view_changes[&view]
+            .iter()
+            .take(node.min_honnest_nodes)
+            .map(|(from, view_change)| (*from, view_change.clone()))
+            .collect();
+        let v = Pbft::latest_prepared(&proof).unwrap_or(self.input.unwrap_or_default());
+        node.send_to_all(node.msg(PB_NEW_VIEW(view, v, proof)));
+        self.changing = false;
+        self.pre_prepare(node, view, v)
+    }
+
+    /// Primary of `view` starts it with `v`, checked against its proof
+    fn new_view(
+        &mut self,
+        node: &mut NodeInternals,
+        view: View,
+        v: Value,
+        proof: &ViewChanges,
+    ) -> ProtocolState {
+        if view < self.view || (view == self.view && !self.changing) {
+            return ProtocolState::InProcess;
+        }
+        let valid = proof.len() >= node.min_honnest_nodes
+            && proof
+                .iter()
+                .all(|(from, view_change)| self.valid_view_change(node, *from, view, view_change))
+            && Pbft::latest_prepared(proof).is_none_or(|prepared| prepared == v);
+        if !valid {
+            node.count("invalid_signatures", 1);
+            return ProtocolState::InProcess;
+        }
+        if view > self.view {
+            node.count("view_changes", 1);
+            self.view = view;
+            self.start_timer(node, view);
+        }
+        self.changing = false;
+        self.pre_prepare(node, view, v)
+    }
+}
+
+impl Protocol for Pbft {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        if self.input.is_some() {
+            return ProtocolState::InProcess;
+        }
+        self.input = Some(input);
+        if self.view > 0 {
+            return ProtocolState::InProcess;
+        }
+        self.start_timer(node, 0);
+        if self.primary(node, 0) != node.id {
+            return ProtocolState::InProcess;
+        }
+        node.send_to_all(node.msg(PB_PRE_PREPARE(0, input)));
+        self.pre_prepare(node, 0, input)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<PbftMessage>() {
+            // Primaries of the next views order their value in NEW_VIEW
+            Some(PB_PRE_PREPARE(0, v)) if from == self.primary(node, 0) => {
+                self.pre_prepare(node, 0, *v)
+            }
+            Some(PB_PREPARE(view, v, share)) => self.prepare(node, from, *view, *v, *share),
+            Some(PB_COMMIT(view, v, share)) => self.commit(node, from, *view, *v, *share),
+            Some(PB_DECIDED(view, v, certificate)) => {
+                if self.decided.is_some()
+                    || !self.certified(node, digest(COMMIT, *view, *v), certificate)
+                {
+                    return ProtocolState::InProcess;
+                }
+                self.decided = Some(*v);
+                node.deliver(*v)
+            }
+            Some(PB_VIEW_CHANGE(view, prepared, share)) => {
+                self.view_changed(node, from, *view, prepared.clone(), *share)
+            }
+            Some(PB_NEW_VIEW(view, v, proof)) if from == self.primary(node, *view) => {
+                self.new_view(node, *view, *v, proof)
+            }
+            Some(PB_TIMEOUT(view)) if from == node.id => {
+                if self.decided.is_some() || *view != self.view {
+                    return ProtocolState::InProcess;
+                }
+                self.view_change(node, view + 1)
+            }
+            _ => ProtocolState::InProcess,
+        }
+    }
+
+    /// Malicious node orders a value of its own, out of turn
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(PB_PRE_PREPARE(0, rng.next_u64() as Value)))
+    }
+}
+
+/// Messages, state and thresholds of PBFT
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(PB_PRE_PREPARE(0, 0)),
+            MessageSchema::of(PB_PREPARE(0, 0, 0)),
+            MessageSchema::of(PB_COMMIT(0, 0, 0)),
+            MessageSchema::of(PB_DECIDED(0, 0, Certificate::new())),
+            MessageSchema::of(PB_VIEW_CHANGE(0, None, 0)),
+            MessageSchema::of(PB_NEW_VIEW(0, 0, ViewChanges::new())),
+            MessageSchema::of(PB_TIMEOUT(0)),
+        ],
+        state: vec![
+            "input",
+            "view",
+            "changing",
+            "pre_prepared",
+            "prepares",
+            "commits",
+            "committed",
+            "prepared",
+            "view_changes",
+            "new_views",
+            "decided",
+        ],
+        thresholds: vec![
+            Threshold::new(
+                "certificate",
+                quorum,
+                "n - f signatures of PREPARE, COMMIT or VIEW_CHANGE certify them",
+            ),
+            Threshold::new(
+                "view_change_join",
+                f + 1,
+                "f + 1 VIEW_CHANGE hold an honest one, the node moves to the view",
+            ),
+        ],
+        ..ProtocolDescription::new(PBFT)
+    }
+}
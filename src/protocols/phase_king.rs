@@ -0,0 +1,259 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::Duration;
+
+/// Faulty nodes the phase king tolerates out of `num_nodes`, n > 4f
+pub fn phase_king_faults(num_nodes: usize) -> usize {
+    num_nodes.saturating_sub(1) / 4
+}
+
+/// Parameters of the phase king agreements
+#[derive(Clone, Debug, PartialEq)]
+pub struct PhaseKingConfig {
+    // Length of a round, the bound on the delays of the synchronous model
+    pub round: Duration,
+    // King of the first phase, the next node is king of the next one
+    pub first_king: NodeId,
+}
+
+impl Default for PhaseKingConfig {
+    fn default() -> Self {
+        PhaseKingConfig {
+            round: Duration::from_millis(20),
+            first_king: 0,
+        }
+    }
+}
+
+/// Phase king of Berman, Garay and Perry: deterministic agreement of the
+/// synchronous model in f + 1 phases of two rounds, for n > 4f
+/// In the first round nodes send their value to all and take the value
+/// most of them sent, in the second one the king of the phase sends the
+/// value it took, nodes that saw it sent by more than n / 2 + f nodes keep
+/// it and the others take the value of the king
+/// A phase with an honest king leaves every honest node with the same
+/// value, the next ones keep it: one of the f + 1 phases has one
+/// Rounds end with a timer of the node, in lockstep schedules timers fire
+/// once every message in flight is delivered, rounds are synchronous
+#[derive(Clone, PartialEq)]
+pub(crate) enum PhaseKingMessage {
+    PK_VALUE(u32, Value),
+    PK_KING(u32, Value),
+    // Timer of the node, ends the round
+    PK_ROUND(u32),
+}
+use PhaseKingMessage::*;
+
+impl ProtocolMessage for PhaseKingMessage {
+    const PROTOCOL: ProtocolId = PHASE_KING;
+
+    fn phase(&self) -> Phase {
+        match self {
+            PK_VALUE(..) => Phase::Vote,
+            PK_KING(..) => Phase::Leader,
+            PK_ROUND(_) => Phase::Control,
+        }
+    }
+
+    /// Kind, phase and value
+    fn size(&self) -> usize {
+        match self {
+            PK_ROUND(_) => 1 + std::mem::size_of::<u32>(),
+            _ => 1 + std::mem::size_of::<u32>() + std::mem::size_of::<Value>(),
+        }
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            PK_VALUE(phase, _) => PK_VALUE(*phase, v),
+            PK_KING(phase, _) => PK_KING(*phase, v),
+            PK_ROUND(round) => PK_ROUND(*round),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    fn value(&self) -> Option<Value> {
+        match self {
+            PK_VALUE(_, v) | PK_KING(_, v) => Some(*v),
+            PK_ROUND(_) => None,
+        }
+    }
+}
+
+impl fmt::Debug for PhaseKingMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PK_VALUE(phase, v) => write!(f, "<VALUE, {}, {}>", phase, v),
+            PK_KING(phase, v) => write!(f, "<KING, {}, {}>", phase, v),
+            PK_ROUND(round) => write!(f, "<ROUND, {}>", round),
+        }
+    }
+}
+
+/// Phase king as run by a node
+pub(crate) struct PhaseKing {
+    config: PhaseKingConfig,
+    value: Value,
+    // Round under way, phase `round / 2`
+    round: u32,
+    // First value of every node in each phase, the node itself included
+    values: BTreeMap<u32, BTreeMap<NodeId, Value>>,
+    // Value of the king of each phase
+    kings: BTreeMap<u32, Value>,
+    // Value most nodes sent in the phase under way, and how many sent it
+    majority: (Value, usize),
+    decided: bool,
+}
+
+impl PhaseKing {
+    pub fn new(config: PhaseKingConfig) -> Self {
+        PhaseKing {
+            config,
+            value: 0,
+            round: 0,
+            values: BTreeMap::new(),
+            kings: BTreeMap::new(),
+            majority: (0, 0),
+            decided: false,
+        }
+    }
+
+    fn king(&self, node: &NodeInternals, phase: u32) -> NodeId {
+        (self.config.first_king + phase as usize) % node.num_nodes
+    }
+
+    /// First round of `phase`: send the value to all
+    fn send_value(&mut self, node: &mut NodeInternals, phase: u32) {
+        self.values
+            .entry(phase)
+            .or_default()
+            .insert(node.id, self.value);
+        node.send_to_all(node.msg(PK_VALUE(phase, self.value)));
+        node.schedule(self.config.round, node.msg(PK_ROUND(self.round)));
+    }
+
+    /// End of the round under way
+    fn end_round(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let phase = self.round / 2;
+        if self.round.is_multiple_of(2) {
+            // Smallest of the values most nodes sent, missing ones count
+            // for none
+            let mut counts: BTreeMap<Value, usize> = BTreeMap::new();
+            for v in self
+                .values
+                .get(&phase)
+                .into_iter()
+                .flat_map(|values| values.values())
+            {
+                *counts.entry(*v).or_default() += 1;
+            }
+            self.majority = counts
+                .iter()
+                .max_by_key(|(v, count)| (**count, std::cmp::Reverse(**v)))
+                .map_or((self.value, 0), |(v, count)| (*v, *count));
+            if self.king(node, phase) == node.id {
+                self.kings.insert(phase, self.majority.0);
+                node.send_to_all(node.msg(PK_KING(phase, self.majority.0)));
+            }
+            self.round += 1;
+            node.schedule(self.config.round, node.msg(PK_ROUND(self.round)));
+            return ProtocolState::InProcess;
+        }
+
+        // A value sent by more than n / 2 + f nodes was sent by more than
+        // n / 2 honest nodes, every honest king took it
+        let (majority, count) = self.majority;
+        let strong = count > node.num_nodes / 2 + phase_king_faults(node.num_nodes);
+        self.value = match self.kings.get(&phase) {
+            Some(king) if !strong => *king,
+            _ => majority,
+        };
+        if !strong {
+            node.count("king_values", 1);
+        }
+        self.round += 1;
+        if phase as usize == phase_king_faults(node.num_nodes) {
+            self.decided = true;
+            return node.deliver(self.value);
+        }
+        self.send_value(node, phase + 1);
+        ProtocolState::InProcess
+    }
+}
+
+impl Protocol for PhaseKing {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        self.value = input;
+        self.send_value(node, 0);
+        ProtocolState::InProcess
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<PhaseKingMessage>() {
+            Some(PK_VALUE(phase, v)) => {
+                self.values
+                    .entry(*phase)
+                    .or_default()
+                    .entry(from)
+                    .or_insert(*v);
+            }
+            Some(PK_KING(phase, v)) if from == self.king(node, *phase) => {
+                self.kings.entry(*phase).or_insert(*v);
+            }
+            Some(PK_ROUND(round)) if from == node.id && *round == self.round && !self.decided => {
+                return self.end_round(node);
+            }
+            _ => {}
+        }
+        ProtocolState::InProcess
+    }
+
+    /// VALUE of the malicious value for the first phase
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(PK_VALUE(0, MALICIOUS_VALUE)))
+    }
+
+    /// Honest nodes decide the same value
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.decided {
+            true => vec![("decided", self.value.to_string())],
+            false => vec![],
+        }
+    }
+}
+
+/// Messages, state and thresholds of the phase king
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let f = phase_king_faults(num_nodes);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(PK_VALUE(0, 0)),
+            MessageSchema::of(PK_KING(0, 0)),
+            MessageSchema::of(PK_ROUND(0)),
+        ],
+        state: vec!["value", "round", "values", "kings", "majority", "decided"],
+        thresholds: vec![
+            Threshold::new("max_faulty", f, "(n - 1) / 4, the phase king needs n > 4f"),
+            Threshold::new(
+                "phases",
+                f + 1,
+                "f + 1 phases, one of them has an honest king",
+            ),
+            Threshold::new(
+                "strong_majority",
+                num_nodes / 2 + f + 1,
+                "more than n / 2 + f VALUE of the majority to keep it over the king",
+            ),
+        ],
+        ..ProtocolDescription::new(PHASE_KING)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::stats::Phase;
+use std::fmt;
+
+/// Point-to-point message between nodes that are not linked, passed on by
+/// the nodes on a shortest path from `origin` to `to`
+/// Relays are not instances of a protocol: nodes handle them before
+/// running any instance, `to` handles `envelope` as sent by `origin`
+#[derive(Clone, PartialEq)]
+pub(crate) struct Relay {
+    pub origin: NodeId,
+    pub to: NodeId,
+    pub envelope: Envelope,
+}
+
+impl ProtocolMessage for Relay {
+    const PROTOCOL: ProtocolId = ROUTING;
+
+    /// Relays count with the messages they carry
+    fn phase(&self) -> Phase {
+        self.envelope.payload.phase()
+    }
+
+    /// Origin, destination and the message carried
+    fn size(&self) -> usize {
+        2 * std::mem::size_of::<NodeId>() + self.envelope.size()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        Relay {
+            envelope: self.envelope.with_value(v),
+            ..self.clone()
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        Relay {
+            envelope: self.envelope.malicious(),
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Debug for Relay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "<RELAY, {} -> {}, {:?}>",
+            self.origin, self.to, self.envelope
+        )
+    }
+}
@@ -0,0 +1,142 @@
+use crate::protocols::*;
+use crate::quorum::{FaultModel, Threshold};
+use crate::stats::Phase;
+use std::fmt;
+
+/// Message type of a protocol, as read off a message of the type
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MessageSchema {
+    // Type carried by the envelopes, e.g. BroadcastMessage
+    pub payload: &'static str,
+    // Kind shown in the traces, e.g. ECHO
+    pub kind: String,
+    pub phase: Phase,
+    // Bytes of the message read, those of lists grow with them
+    pub size: usize,
+    // Kind of the commitment of the sender, None if honest nodes may send
+    // several versions of the message
+    pub commitment: Option<&'static str>,
+    // Carries a value of the application, checked by the inbound filters
+    pub valued: bool,
+}
+
+impl MessageSchema {
+    pub(crate) fn of<M: ProtocolMessage>(msg: M) -> Self {
+        let payload = std::any::type_name::<M>();
+        let debug = format!("{:?}", msg);
+        let kind = debug
+            .trim_start_matches('<')
+            .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .next()
+            .unwrap_or_default();
+        MessageSchema {
+            payload: payload.rsplit("::").next().unwrap_or(payload),
+            kind: kind.to_string(),
+            phase: ProtocolMessage::phase(&msg),
+            size: ProtocolMessage::size(&msg),
+            commitment: ProtocolMessage::commitment(&msg).map(|c| c.kind),
+            valued: ProtocolMessage::value(&msg).is_some(),
+        }
+    }
+}
+
+/// Structure of a protocol for the CLI and the diagrams, read off its
+/// definitions: the messages it sends, the state of an instance and the
+/// thresholds its nodes count to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProtocolDescription {
+    pub id: ProtocolId,
+    pub name: &'static str,
+    pub fault_model: FaultModel,
+    pub versions: &'static [&'static str],
+    pub messages: Vec<MessageSchema>,
+    // Fields of the state of an instance
+    pub state: Vec<&'static str>,
+    // For the number of nodes described
+    pub thresholds: Vec<Threshold>,
+    // Protocols of the sub-instances it spawns
+    pub children: Vec<ProtocolId>,
+}
+
+impl ProtocolDescription {
+    /// Description of protocol `id` without messages, state or thresholds
+    pub(crate) fn new(id: ProtocolId) -> Self {
+        ProtocolDescription {
+            id,
+            name: name(id).unwrap_or_default(),
+            fault_model: fault_model(id),
+            versions: versions(id),
+            messages: vec![],
+            state: vec![],
+            thresholds: vec![],
+            children: vec![],
+        }
+    }
+
+    /// Schema of the messages of kind `kind`
+    pub fn message(&self, kind: &str) -> Option<&MessageSchema> {
+        self.messages.iter().find(|schema| schema.kind == kind)
+    }
+
+    /// Kinds of the messages of `phase` to label it on a diagram, e.g.
+    /// `BVAL/AUX/TERM`
+    pub fn label(&self, phase: Phase) -> String {
+        let mut kinds: Vec<&str> = vec![];
+        for schema in self.messages.iter().filter(|schema| schema.phase == phase) {
+            if !kinds.contains(&schema.kind.as_str()) {
+                kinds.push(&schema.kind);
+            }
+        }
+        kinds.join("/")
+    }
+
+    pub fn threshold(&self, name: &str) -> Option<usize> {
+        self.thresholds
+            .iter()
+            .find(|threshold| threshold.name == name)
+            .map(|threshold| threshold.value)
+    }
+}
+
+impl fmt::Display for ProtocolDescription {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}), {} faults",
+            self.name, self.id, self.fault_model
+        )?;
+        if !self.versions.is_empty() {
+            write!(f, ", releases {}", self.versions.join(" "))?;
+        }
+        writeln!(f)?;
+        for schema in self.messages.iter() {
+            writeln!(
+                f,
+                "  {:<16} {:<20} {:<8} {:>4} bytes{}{}",
+                schema.kind,
+                schema.payload,
+                format!("{:?}", schema.phase),
+                schema.size,
+                schema
+                    .commitment
+                    .map_or(String::new(), |kind| format!(", commits to {}", kind)),
+                if schema.valued { ", valued" } else { "" }
+            )?;
+        }
+        if !self.state.is_empty() {
+            writeln!(f, "  state: {}", self.state.join(", "))?;
+        }
+        for threshold in self.thresholds.iter() {
+            writeln!(
+                f,
+                "  {} = {}: {}",
+                threshold.name, threshold.value, threshold.justification
+            )?;
+        }
+        if !self.children.is_empty() {
+            let children: Vec<&str> = self.children.iter().filter_map(|id| name(*id)).collect();
+            writeln!(f, "  spawns: {}", children.join(", "))?;
+        }
+        Ok(())
+    }
+}
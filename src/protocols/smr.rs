@@ -0,0 +1,813 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Guarantee of a read on the freshness of the state it returns
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ReadConsistency {
+    // State of the replica itself, however far behind it is
+    #[default]
+    Local,
+    // Most recent state among a quorum of replicas
+    Quorum,
+    // State once the replica has committed every command the leader had
+    // ordered when asked, nothing committed before the read is missed
+    Linearizable,
+}
+
+impl FromStr for ReadConsistency {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "local" => Ok(ReadConsistency::Local),
+            "quorum" => Ok(ReadConsistency::Quorum),
+            "linearizable" => Ok(ReadConsistency::Linearizable),
+            _ => Err(format!("Unknown read consistency: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for ReadConsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReadConsistency::Local => write!(f, "local"),
+            ReadConsistency::Quorum => write!(f, "quorum"),
+            ReadConsistency::Linearizable => write!(f, "linearizable"),
+        }
+    }
+}
+
+/// Behaviour of the clients submitting the commands
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClientConfig {
+    // Time a client waits for the reply before retrying another replica
+    pub timeout: Duration,
+    // Retries before the client gives up on its command
+    pub retries: usize,
+    // Time a client waits before submitting again a command the leader
+    // rejected, neither a retry nor a timeout
+    pub backoff: Duration,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            timeout: Duration::from_millis(20),
+            retries: 3,
+            backoff: Duration::from_millis(5),
+        }
+    }
+}
+
+/// How a load generator issues the commands of its client
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Arrivals {
+    // Poisson process of `rate` commands per second, whatever the replies
+    Open { rate: f64 },
+    // `outstanding` commands at a time, the next one `think` after a reply
+    Closed { outstanding: usize, think: Duration },
+}
+
+impl fmt::Display for Arrivals {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arrivals::Open { rate } => write!(f, "open {}/s", rate),
+            Arrivals::Closed { outstanding, think } => {
+                write!(f, "closed {} think {:?}", outstanding, think)
+            }
+        }
+    }
+}
+
+/// Commands generated by every client
+/// Command `k` of the node with input `v` is `v + (k << 32)`, inputs must
+/// stay below 2^32 for the commands to be distinct
+#[derive(Clone, Debug, PartialEq)]
+pub struct LoadConfig {
+    pub arrivals: Arrivals,
+    pub commands: usize,
+}
+
+impl Default for LoadConfig {
+    fn default() -> Self {
+        LoadConfig {
+            arrivals: Arrivals::Closed {
+                outstanding: 1,
+                think: Duration::ZERO,
+            },
+            commands: 1,
+        }
+    }
+}
+
+/// Parameters of state machine replication
+#[derive(Clone, Debug, PartialEq)]
+pub struct SmrConfig {
+    // Replicas execute commands in the order they hear of them, before the
+    // leader orders them, and roll back when the order turns out different
+    pub speculative: bool,
+    // Commands to commit before the replicas output their state
+    pub commands: usize,
+    // Reads of every node submitting a command, one per period from its
+    // start, a read still going on when the next one starts fails
+    // Replicas that output no longer answer reads
+    pub reads: usize,
+    pub read_period: Duration,
+    pub consistency: ReadConsistency,
+    // Nodes submit their command through a client that talks to one
+    // replica at a time and waits for its reply, instead of sending it to
+    // every replica
+    pub client: Option<ClientConfig>,
+    // Clients submit generated commands instead of their single input, with
+    // the default client unless `client` is set
+    pub load: Option<LoadConfig>,
+    // Commands the leader orders ahead of its commits, it rejects client
+    // submissions past it
+    pub mempool: Option<usize>,
+}
+
+impl Default for SmrConfig {
+    fn default() -> Self {
+        SmrConfig {
+            speculative: false,
+            commands: 0,
+            reads: 0,
+            read_period: Duration::from_millis(5),
+            consistency: ReadConsistency::Local,
+            client: None,
+            load: None,
+            mempool: None,
+        }
+    }
+}
+
+/// State machine replication: nodes send their command to every replica,
+/// the leader, node 0, orders the commands in slots, one Bracha instance
+/// per slot, and replicas execute the commands in slot order
+/// Commands are distinct, order matters to the state machine
+/// Reads are numbered by their reader, replicas answer with their number of
+/// committed commands and their state
+/// Clients submit to a single replica, that redirects them to the leader,
+/// or on a retry forwards the command to the leader itself, and replies
+/// once the command is committed
+/// A leader with a full mempool rejects the submission as busy, through
+/// the replica that forwarded it
+#[derive(Clone, PartialEq)]
+pub(crate) enum SmrMessage {
+    SMR_REQUEST(Value),
+    // Command of a client and whether it retries
+    SMR_SUBMIT(Value, bool),
+    SMR_REDIRECT(Value, NodeId),
+    SMR_REPLY(Value),
+    SMR_BUSY(Value),
+    // Timer of the request sent by a client, numbered by the client
+    SMR_CLIENT_TIMEOUT(Value, usize),
+    // Timer of the next command of a load generator
+    SMR_ARRIVAL(usize),
+    // Timer of the submission again of a rejected command, numbered as the
+    // requests of the client
+    SMR_BACKOFF(Value, usize),
+    SMR_READ(usize),
+    SMR_STATE(usize, usize, Value),
+    // Linearizable read: the leader answers with its next slot
+    SMR_READ_INDEX(usize),
+    SMR_INDEX(usize, usize),
+    // Timer of the next read
+    SMR_READ_TICK(usize),
+}
+use SmrMessage::*;
+
+impl ProtocolMessage for SmrMessage {
+    const PROTOCOL: ProtocolId = SMR;
+
+    fn phase(&self) -> Phase {
+        match self {
+            SMR_REQUEST(_) | SMR_SUBMIT(..) | SMR_REDIRECT(..) | SMR_BUSY(_) => Phase::Init,
+            SMR_READ(_) | SMR_STATE(..) | SMR_READ_INDEX(_) | SMR_INDEX(..) => Phase::Vote,
+            SMR_REPLY(_) => Phase::Vote,
+            SMR_READ_TICK(_) | SMR_CLIENT_TIMEOUT(..) | SMR_ARRIVAL(_) | SMR_BACKOFF(..) => {
+                Phase::Control
+            }
+        }
+    }
+
+    /// Kind, read number, version and value
+    fn size(&self) -> usize {
+        let number = std::mem::size_of::<usize>();
+        1 + match self {
+            SMR_REQUEST(_) | SMR_REPLY(_) | SMR_BUSY(_) => std::mem::size_of::<Value>(),
+            SMR_SUBMIT(..) => 1 + std::mem::size_of::<Value>(),
+            SMR_REDIRECT(..) | SMR_CLIENT_TIMEOUT(..) | SMR_BACKOFF(..) => {
+                number + std::mem::size_of::<Value>()
+            }
+            SMR_READ(_) | SMR_READ_INDEX(_) | SMR_READ_TICK(_) | SMR_ARRIVAL(_) => number,
+            SMR_STATE(..) => 2 * number + std::mem::size_of::<Value>(),
+            SMR_INDEX(..) => 2 * number,
+        }
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            SMR_REQUEST(_) => SMR_REQUEST(v),
+            SMR_SUBMIT(_, retry) => SMR_SUBMIT(v, *retry),
+            SMR_REPLY(_) => SMR_REPLY(v),
+            SMR_STATE(read, version, _) => SMR_STATE(*read, *version, v),
+            msg => msg.clone(),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        match self {
+            // Lie about being up to date
+            SMR_STATE(read, version, _) => SMR_STATE(*read, version + 1, MALICIOUS_VALUE),
+            msg => ProtocolMessage::with_value(msg, MALICIOUS_VALUE),
+        }
+    }
+}
+
+impl fmt::Debug for SmrMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SMR_REQUEST(v) => write!(f, "<REQUEST, {}>", v),
+            SMR_SUBMIT(v, false) => write!(f, "<SUBMIT, {}>", v),
+            SMR_SUBMIT(v, true) => write!(f, "<RETRY, {}>", v),
+            SMR_REDIRECT(v, leader) => write!(f, "<REDIRECT, {}, {}>", v, leader),
+            SMR_REPLY(v) => write!(f, "<REPLY, {}>", v),
+            SMR_BUSY(v) => write!(f, "<BUSY, {}>", v),
+            SMR_CLIENT_TIMEOUT(v, sent) => write!(f, "<CLIENT_TIMEOUT, {}, {}>", v, sent),
+            SMR_ARRIVAL(k) => write!(f, "<ARRIVAL, {}>", k),
+            SMR_BACKOFF(v, sent) => write!(f, "<BACKOFF, {}, {}>", v, sent),
+            SMR_READ(read) => write!(f, "<READ, {}>", read),
+            SMR_STATE(read, version, v) => write!(f, "<STATE, {}, {} {}>", read, version, v),
+            SMR_READ_INDEX(read) => write!(f, "<READ_INDEX, {}>", read),
+            SMR_INDEX(read, index) => write!(f, "<INDEX, {}, {}>", read, index),
+            SMR_READ_TICK(read) => write!(f, "<READ_TICK, {}>", read),
+        }
+    }
+}
+
+/// State after executing `command` on `state`
+fn apply(state: Value, command: Value) -> Value {
+    state.wrapping_mul(31).wrapping_add(command)
+}
+
+/// Read going on
+#[derive(Debug)]
+struct PendingRead {
+    number: usize,
+    // Most commands committed by a replica when the read started
+    latest: usize,
+    states: BTreeMap<NodeId, (usize, Value)>,
+    // Commands to commit before a linearizable read returns
+    index: Option<usize>,
+}
+
+/// Command of a client waiting for its reply
+#[derive(Debug)]
+struct PendingRequest {
+    submitted: Instant,
+    // Requests sent so far, the timer of the last one is the one that counts
+    sent: usize,
+    retries: usize,
+}
+
+/// Client run by a node next to its replica, fed by a load generator
+#[derive(Debug)]
+struct Client {
+    config: ClientConfig,
+    load: LoadConfig,
+    input: Value,
+    // Commands generated so far
+    issued: usize,
+    // Replica requests go to, the leader once a replica redirects there
+    contact: NodeId,
+    pending: BTreeMap<Value, PendingRequest>,
+}
+
+/// Replica of the state machine as run by a node
+/// Counts `speculated` commands executed ahead of their slot, `rollbacks`
+/// and `undone` executions while rolling back, `reads` and `failed_reads`,
+/// for clients `redirects`, `retries`, `abandoned` commands and
+/// `backpressured` submissions, and the submissions the leader `rejected`,
+/// see `NodeInternals::count`
+/// Samples the `staleness` of every read: commands committed by some
+/// replica when the read started that the state it returns misses, and the
+/// `client_latency_us` from submission to reply of every command, and the
+/// `mempool` of the leader, commands ordered but not committed, on every
+/// submission it admits
+pub(crate) struct Smr {
+    config: SmrConfig,
+    state: Value,
+    // Commands executed so far, the first `committed` ones in slot order,
+    // the others speculatively
+    log: Vec<Value>,
+    // State before every command of the log, to restore on rollback
+    snapshots: Vec<Value>,
+    committed: usize,
+    // Slots delivered ahead of the ones before them
+    ordered: BTreeMap<usize, Value>,
+    // Next slot the leader assigns
+    next_slot: usize,
+    read: Option<PendingRead>,
+    reads_over: bool,
+    // Commands handed to the ordering instances by the leader
+    sequenced: BTreeSet<Value>,
+    // Clients to reply to once their command is committed
+    waiting: BTreeMap<Value, NodeId>,
+    client: Option<Client>,
+}
+
+impl Smr {
+    pub fn new(config: SmrConfig) -> Self {
+        Smr {
+            config,
+            state: 0,
+            log: vec![],
+            snapshots: vec![],
+            committed: 0,
+            ordered: BTreeMap::new(),
+            next_slot: 0,
+            read: None,
+            reads_over: true,
+            sequenced: BTreeSet::new(),
+            waiting: BTreeMap::new(),
+            client: None,
+        }
+    }
+
+    fn execute(&mut self, command: Value) {
+        self.snapshots.push(self.state);
+        self.log.push(command);
+        self.state = apply(self.state, command);
+    }
+
+    fn request(&mut self, node: &mut NodeInternals, command: Value) {
+        if node.id == 0 && self.sequenced.insert(command) {
+            node.spawn(BRACHA_BROADCAST, self.next_slot as u32, command);
+            self.next_slot += 1;
+        }
+        if self.config.speculative && !self.log.contains(&command) {
+            self.execute(command);
+            node.count("speculated", 1);
+        }
+    }
+
+    /// Execute `command` in the next slot, undoing the speculative
+    /// executions that took its place
+    fn commit(&mut self, node: &mut NodeInternals, command: Value) {
+        let slot = self.committed;
+        self.committed += 1;
+        if self.log.get(slot) == Some(&command) {
+            return;
+        }
+        let undone = self.log.split_off(slot);
+        if !undone.is_empty() {
+            self.state = self.snapshots[slot];
+            self.snapshots.truncate(slot);
+            node.count("rollbacks", 1);
+            node.count("undone", undone.len());
+        }
+        self.execute(command);
+        // Commands heard of but not ordered yet are speculated again
+        for other in undone.into_iter().filter(|other| *other != command) {
+            self.execute(other);
+        }
+    }
+
+    /// State after the committed commands and their number, the version of
+    /// the state
+    fn committed_state(&self) -> (usize, Value) {
+        match self.snapshots.get(self.committed) {
+            Some(state) => (self.committed, *state),
+            None => (self.committed, self.state),
+        }
+    }
+
+    /// Start read `number`, failing the one going on
+    fn start_read(&mut self, node: &mut NodeInternals, number: usize) {
+        if self.read.take().is_some() {
+            node.count("failed_reads", 1);
+        }
+        if number >= self.config.reads {
+            self.reads_over = true;
+            return;
+        }
+        node.schedule(self.config.read_period, node.msg(SMR_READ_TICK(number + 1)));
+        let mut read = PendingRead {
+            number,
+            latest: node.highest_count("committed"),
+            states: BTreeMap::from([(node.id, self.committed_state())]),
+            index: None,
+        };
+        match self.config.consistency {
+            ReadConsistency::Local => {}
+            ReadConsistency::Quorum => node.send_to_all(node.msg(SMR_READ(number))),
+            ReadConsistency::Linearizable if node.id == 0 => read.index = Some(self.next_slot),
+            ReadConsistency::Linearizable => node.send_to(0, node.msg(SMR_READ_INDEX(number))),
+        }
+        self.read = Some(read);
+        self.complete_read(node);
+    }
+
+    /// Return the read going on if it has what it waits for
+    fn complete_read(&mut self, node: &mut NodeInternals) {
+        let read = match &self.read {
+            Some(read) => read,
+            None => return,
+        };
+        let version = match self.config.consistency {
+            ReadConsistency::Local => self.committed,
+            ReadConsistency::Quorum if read.states.len() >= node.min_honnest_nodes => read
+                .states
+                .values()
+                .map(|(version, _)| *version)
+                .max()
+                .unwrap_or(0),
+            ReadConsistency::Linearizable
+                if read.index.is_some_and(|index| self.committed >= index) =>
+            {
+                self.committed
+            }
+            _ => return,
+        };
+        node.count("reads", 1);
+        node.sample("staleness", read.latest.saturating_sub(version));
+        self.read = None;
+    }
+
+    /// Generate the next command of the client and submit it
+    fn issue(&mut self, node: &mut NodeInternals) {
+        let client = match self.client.as_mut() {
+            Some(client) if client.issued < client.load.commands => client,
+            _ => return,
+        };
+        let command = client.input.wrapping_add(client.issued << 32);
+        let request = PendingRequest {
+            submitted: Instant::now(),
+            sent: 0,
+            retries: 0,
+        };
+        client.pending.insert(command, request);
+        client.issued += 1;
+        if let Arrivals::Open { rate } = client.load.arrivals {
+            if client.issued < client.load.commands {
+                // Exponential time between arrivals
+                let gap = -(1.0 - node.rng.gen_f64()).ln() / rate;
+                let arrival = node.msg(SMR_ARRIVAL(client.issued));
+                node.schedule(Duration::from_secs_f64(gap), arrival);
+            }
+        }
+        self.send_request(node, command);
+    }
+
+    /// Closed loops issue the next command once one is over
+    fn request_over(&mut self, node: &mut NodeInternals) {
+        let client = match self.client.as_ref() {
+            Some(client) => client,
+            None => return,
+        };
+        match client.load.arrivals {
+            Arrivals::Closed { think, .. } if think.is_zero() => self.issue(node),
+            Arrivals::Closed { think, .. } => {
+                node.schedule(think, node.msg(SMR_ARRIVAL(client.issued)));
+            }
+            Arrivals::Open { .. } => {}
+        }
+    }
+
+    /// Send the request of the client for `command` to its contact
+    fn send_request(&mut self, node: &mut NodeInternals, command: Value) {
+        let client = match self.client.as_mut() {
+            Some(client) => client,
+            None => return,
+        };
+        let request = match client.pending.get_mut(&command) {
+            Some(request) => request,
+            None => return,
+        };
+        request.sent += 1;
+        let retry = request.retries > 0;
+        let timeout = node.msg(SMR_CLIENT_TIMEOUT(command, request.sent));
+        node.schedule(client.config.timeout, timeout);
+        let contact = client.contact;
+        if contact == node.id {
+            self.serve(node, node.id, command, retry);
+        } else {
+            node.send_to(contact, node.msg(SMR_SUBMIT(command, retry)));
+        }
+    }
+
+    /// Whether nodes submit through clients rather than to every replica
+    fn with_clients(&self) -> bool {
+        self.config.client.is_some() || self.config.load.is_some()
+    }
+
+    /// Whether the leader has room in its mempool for `command`, recorded
+    /// when it has
+    fn admits(&self, node: &mut NodeInternals, command: Value) -> bool {
+        if self.sequenced.contains(&command) {
+            return true;
+        }
+        let mempool = self.next_slot.saturating_sub(self.committed);
+        if self
+            .config
+            .mempool
+            .is_some_and(|capacity| mempool >= capacity)
+        {
+            node.count("rejected", 1);
+            return false;
+        }
+        node.sample("mempool", mempool);
+        true
+    }
+
+    /// Replica side of a submission by `client`
+    fn serve(&mut self, node: &mut NodeInternals, client: NodeId, command: Value, retry: bool) {
+        if self.log[..self.committed.min(self.log.len())].contains(&command) {
+            self.reply(node, client, command);
+        } else if node.id == 0 && !self.admits(node, command) {
+            self.reject(node, client, command);
+        } else if node.id == 0 {
+            self.waiting.insert(command, client);
+            self.request(node, command);
+        } else if retry {
+            // The leader may be out of reach of the client, not of us
+            self.waiting.insert(command, client);
+            node.send_to(0, node.msg(SMR_REQUEST(command)));
+        } else if client == node.id {
+            self.redirected(node, command, 0);
+        } else {
+            node.send_to(client, node.msg(SMR_REDIRECT(command, 0)));
+        }
+    }
+
+    fn reject(&mut self, node: &mut NodeInternals, client: NodeId, command: Value) {
+        if client == node.id {
+            self.backpressured(node, command);
+        } else {
+            node.send_to(client, node.msg(SMR_BUSY(command)));
+        }
+    }
+
+    /// Client side: submit `command` again once the leader may have room
+    fn backpressured(&mut self, node: &mut NodeInternals, command: Value) {
+        let client = match self.client.as_mut() {
+            Some(client) => client,
+            None => return,
+        };
+        if let Some(request) = client.pending.get_mut(&command) {
+            // The timeout of the rejected request no longer counts
+            request.sent += 1;
+            let backoff = node.msg(SMR_BACKOFF(command, request.sent));
+            node.schedule(client.config.backoff, backoff);
+            node.count("backpressured", 1);
+        }
+    }
+
+    fn reply(&mut self, node: &mut NodeInternals, client: NodeId, command: Value) {
+        if client == node.id {
+            self.replied(node, command);
+        } else {
+            node.send_to(client, node.msg(SMR_REPLY(command)));
+        }
+    }
+
+    /// Client side: `leader` orders the commands
+    fn redirected(&mut self, node: &mut NodeInternals, command: Value, leader: NodeId) {
+        if let Some(client) = self.client.as_mut() {
+            if client.pending.contains_key(&command) && client.contact != leader {
+                client.contact = leader;
+                node.count("redirects", 1);
+                self.send_request(node, command);
+            }
+        }
+    }
+
+    fn replied(&mut self, node: &mut NodeInternals, command: Value) {
+        let client = self.client.as_mut();
+        if let Some(request) = client.and_then(|client| client.pending.remove(&command)) {
+            let latency = request.submitted.elapsed().as_micros() as usize;
+            node.sample("client_latency_us", latency);
+            self.request_over(node);
+        }
+    }
+
+    /// Client side: no reply to request `sent` in time, try the next replica
+    fn timed_out(&mut self, node: &mut NodeInternals, command: Value, sent: usize) {
+        let client = match self.client.as_mut() {
+            Some(client) => client,
+            None => return,
+        };
+        let request = match client.pending.get_mut(&command) {
+            Some(request) if request.sent == sent => request,
+            _ => return,
+        };
+        if request.retries == client.config.retries {
+            client.pending.remove(&command);
+            node.count("abandoned", 1);
+            self.request_over(node);
+            return;
+        }
+        request.retries += 1;
+        client.contact = (client.contact + 1) % node.num_nodes;
+        node.count("retries", 1);
+        self.send_request(node, command);
+    }
+
+    /// Replicas output once every command is committed, their reads are
+    /// over and their client is done
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let client_done = self.client.as_ref().is_none_or(|client| {
+            client.issued == client.load.commands && client.pending.is_empty()
+        });
+        if self.committed < self.config.commands || !self.reads_over || !client_done {
+            return ProtocolState::InProcess;
+        }
+        node.deliver(self.state)
+    }
+}
+
+impl Protocol for Smr {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        let client = match self.with_clients() {
+            true => Some(self.config.client.clone().unwrap_or_default()),
+            false => None,
+        };
+        match client {
+            // Clients start with the replica after their node
+            Some(config) => {
+                let load = self.config.load.clone().unwrap_or_default();
+                let first = match load.arrivals {
+                    Arrivals::Open { .. } => 1,
+                    Arrivals::Closed { outstanding, .. } => outstanding,
+                };
+                self.client = Some(Client {
+                    config,
+                    load,
+                    input,
+                    issued: 0,
+                    contact: (node.id + 1) % node.num_nodes,
+                    pending: BTreeMap::new(),
+                });
+                for _ in 0..first {
+                    self.issue(node);
+                }
+            }
+            None => {
+                node.send_to_all(node.msg(SMR_REQUEST(input)));
+                self.request(node, input);
+            }
+        }
+        if self.config.reads > 0 {
+            self.reads_over = false;
+            node.schedule(self.config.read_period, node.msg(SMR_READ_TICK(0)));
+        }
+        ProtocolState::InProcess
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<SmrMessage>() {
+            // Forwarded by a replica for a client
+            Some(SMR_REQUEST(command)) if node.id == 0 && self.with_clients() => {
+                if self.admits(node, *command) {
+                    self.request(node, *command);
+                } else {
+                    node.send_to(from, node.msg(SMR_BUSY(*command)));
+                }
+            }
+            Some(SMR_REQUEST(command)) => self.request(node, *command),
+            Some(SMR_SUBMIT(command, retry)) => self.serve(node, from, *command, *retry),
+            Some(SMR_REDIRECT(command, leader)) => self.redirected(node, *command, *leader),
+            Some(SMR_REPLY(command)) => {
+                self.replied(node, *command);
+                return self.progress(node);
+            }
+            Some(SMR_BUSY(command)) if from == 0 && self.waiting.contains_key(command) => {
+                let client = self.waiting.remove(command).unwrap();
+                self.reject(node, client, *command);
+            }
+            Some(SMR_BUSY(command)) => self.backpressured(node, *command),
+            Some(SMR_ARRIVAL(_)) if from == node.id => self.issue(node),
+            Some(SMR_BACKOFF(command, sent)) if from == node.id => {
+                let current = self
+                    .client
+                    .as_ref()
+                    .and_then(|client| client.pending.get(command));
+                if current.is_some_and(|request| request.sent == *sent) {
+                    self.send_request(node, *command);
+                }
+            }
+            Some(SMR_CLIENT_TIMEOUT(command, sent)) if from == node.id => {
+                self.timed_out(node, *command, *sent);
+                return self.progress(node);
+            }
+            Some(SMR_READ(number)) => {
+                let (version, state) = self.committed_state();
+                node.send_to(from, node.msg(SMR_STATE(*number, version, state)));
+            }
+            Some(SMR_STATE(number, version, state)) => {
+                if let Some(read) = self.read.as_mut().filter(|read| read.number == *number) {
+                    read.states.insert(from, (*version, *state));
+                    self.complete_read(node);
+                }
+            }
+            Some(SMR_READ_INDEX(number)) if node.id == 0 => {
+                node.send_to(from, node.msg(SMR_INDEX(*number, self.next_slot)));
+            }
+            Some(SMR_INDEX(number, index)) if from == 0 => {
+                if let Some(read) = self.read.as_mut().filter(|read| read.number == *number) {
+                    read.index = Some(*index);
+                    self.complete_read(node);
+                }
+            }
+            Some(SMR_READ_TICK(number)) if from == node.id => {
+                self.start_read(node, *number);
+                return self.progress(node);
+            }
+            _ => {}
+        }
+        ProtocolState::InProcess
+    }
+
+    /// Slot `tag` is ordered
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        if let Some(command) = output.value() {
+            self.ordered.insert(child.tag() as usize, command);
+        }
+        while let Some(command) = self.ordered.remove(&self.committed) {
+            self.commit(node, command);
+            node.count("committed", 1);
+            if let Some(client) = self.waiting.remove(&command) {
+                self.reply(node, client, command);
+            }
+        }
+        node.debug(&self.log);
+        self.complete_read(node);
+        self.progress(node)
+    }
+
+    /// Random nodes disturb the ordering instances, a random command would
+    /// take the slot of a real one
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        let committed = &self.log[..self.committed.min(self.log.len())];
+        vec![("committed", format!("{:?}", committed))]
+    }
+}
+
+/// Messages, state and threshold of the replicated state machine
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (_, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(SMR_REQUEST(0)),
+            MessageSchema::of(SMR_SUBMIT(0, false)),
+            MessageSchema::of(SMR_REDIRECT(0, 0)),
+            MessageSchema::of(SMR_REPLY(0)),
+            MessageSchema::of(SMR_BUSY(0)),
+            MessageSchema::of(SMR_CLIENT_TIMEOUT(0, 0)),
+            MessageSchema::of(SMR_ARRIVAL(0)),
+            MessageSchema::of(SMR_BACKOFF(0, 0)),
+            MessageSchema::of(SMR_READ(0)),
+            MessageSchema::of(SMR_STATE(0, 0, 0)),
+            MessageSchema::of(SMR_READ_INDEX(0)),
+            MessageSchema::of(SMR_INDEX(0, 0)),
+            MessageSchema::of(SMR_READ_TICK(0)),
+        ],
+        state: vec![
+            "state",
+            "log",
+            "snapshots",
+            "committed",
+            "ordered",
+            "next_slot",
+            "read",
+            "sequenced",
+            "waiting",
+            "client",
+        ],
+        thresholds: vec![Threshold::new(
+            "read_quorum",
+            quorum,
+            "n - f STATE answer a quorum read, the most recent one is returned",
+        )],
+        children: vec![BRACHA_BROADCAST],
+        ..ProtocolDescription::new(SMR)
+    }
+}
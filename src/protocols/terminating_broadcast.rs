@@ -0,0 +1,166 @@
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::time::Duration;
+
+/// Parameters of the terminating reliable broadcasts
+#[derive(Clone, Debug, PartialEq)]
+pub struct TerminatingConfig {
+    // Designated sender, set by every run
+    pub sender: NodeId,
+    // Time nodes give the broadcast of the sender before voting it didn't
+    // deliver, the bound on the delays of the synchronous model
+    pub timeout: Duration,
+}
+
+impl Default for TerminatingConfig {
+    fn default() -> Self {
+        TerminatingConfig {
+            sender: 0,
+            timeout: Duration::from_millis(50),
+        }
+    }
+}
+
+/// Timer of the node, votes on the broadcast if it didn't deliver yet
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TrbTimeout;
+
+impl ProtocolMessage for TrbTimeout {
+    const PROTOCOL: ProtocolId = TERMINATING_BROADCAST;
+
+    fn phase(&self) -> Phase {
+        Phase::Control
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
+
+    fn with_value(&self, _v: Value) -> Self {
+        TrbTimeout
+    }
+
+    fn malicious(&self) -> Self {
+        TrbTimeout
+    }
+}
+
+/// Terminating reliable broadcast, the Byzantine generals with a designated
+/// sender: the sender reliably broadcasts its input, every node votes 1 in
+/// a binary agreement as soon as the broadcast delivers to it and 0 at its
+/// timeout if it didn't, and outputs the value of the sender if the
+/// agreement decides 1, `Output::Bottom` otherwise
+/// The agreement decides 1 only if an honest node delivered, the broadcast
+/// then delivers to every honest node: honest nodes terminate even when the
+/// sender is mute, on the same output
+/// An honest sender delivers everywhere before the timeout as long as the
+/// delays stay within it, every honest node votes 1 and outputs its value
+pub(crate) struct TerminatingBroadcast {
+    config: TerminatingConfig,
+    broadcast: Children,
+    agreement: Children,
+    voted: bool,
+    done: bool,
+}
+
+impl TerminatingBroadcast {
+    pub fn new(config: TerminatingConfig) -> Self {
+        TerminatingBroadcast {
+            config,
+            broadcast: Children::new(),
+            agreement: Children::new(),
+            voted: false,
+            done: false,
+        }
+    }
+
+    fn vote(&mut self, node: &mut NodeInternals, delivered: bool) {
+        if self.voted {
+            return;
+        }
+        self.voted = true;
+        if !delivered {
+            node.count("timeouts", 1);
+        }
+        node.spawn(BINARY_AGREEMENT, 0, delivered as Value);
+    }
+
+    /// Output once the agreement decided, and the broadcast delivered if it
+    /// decided 1
+    fn progress(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let sender = self.config.sender as u32;
+        let delivered = self.broadcast.output(sender).and_then(Output::value);
+        if delivered.is_some() {
+            self.vote(node, true);
+        }
+        if self.done {
+            return ProtocolState::InProcess;
+        }
+        let output = match self.agreement.output(0).and_then(Output::value) {
+            Some(0) => Output::Bottom,
+            Some(_) => match delivered {
+                Some(v) => Output::Value(v),
+                None => return ProtocolState::InProcess,
+            },
+            None => return ProtocolState::InProcess,
+        };
+        self.done = true;
+        node.deliver_output(output)
+    }
+}
+
+impl Protocol for TerminatingBroadcast {
+    /// Sender broadcasts its input, every node starts its timer
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.schedule(self.config.timeout, node.msg(TrbTimeout));
+        if node.id == self.config.sender {
+            node.spawn(BRACHA_BROADCAST, self.config.sender as u32, input);
+        }
+        self.progress(node)
+    }
+
+    // Other messages go to the broadcast and the agreement
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        if msg.open::<TrbTimeout>().is_some() && from == node.id {
+            self.vote(node, false);
+            return self.progress(node);
+        }
+        ProtocolState::InProcess
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        let recorded = match child.protocol() {
+            BRACHA_BROADCAST => {
+                child.tag() == self.config.sender as u32 && self.broadcast.record(child, output)
+            }
+            BINARY_AGREEMENT => self.agreement.record(child, output),
+            _ => false,
+        };
+        if !recorded {
+            return ProtocolState::InProcess;
+        }
+        self.progress(node)
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+/// Messages and instances of the terminating broadcast
+pub fn describe(_num_nodes: usize) -> ProtocolDescription {
+    ProtocolDescription {
+        messages: vec![MessageSchema::of(TrbTimeout)],
+        state: vec!["broadcast", "agreement", "voted", "done"],
+        children: vec![BRACHA_BROADCAST, BINARY_AGREEMENT],
+        ..ProtocolDescription::new(TERMINATING_BROADCAST)
+    }
+}
@@ -0,0 +1,347 @@
+#[cfg(feature = "crypto")]
+use crate::cost::CryptoOp;
+#[cfg(feature = "crypto")]
+use crate::crypto::threshold_sig::{SignatureConfig, SignatureShare, ThresholdSignature};
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::*;
+use crate::rng::Rng;
+use crate::stats::Phase;
+#[cfg(feature = "crypto")]
+use std::collections::BTreeMap;
+#[cfg(feature = "crypto")]
+use std::sync::Arc;
+use std::time::Duration;
+
+// Protocols exercising the node runtime in tests
+pub(crate) const PANICKING: ProtocolId = ProtocolId::MAX;
+pub(crate) const TICKING: ProtocolId = ProtocolId::MAX - 1;
+pub(crate) const EQUIVOCATING: ProtocolId = ProtocolId::MAX - 2;
+pub(crate) const FORGETFUL: ProtocolId = ProtocolId::MAX - 3;
+#[cfg(feature = "crypto")]
+pub(crate) const CERTIFYING: ProtocolId = ProtocolId::MAX - 4;
+pub(crate) const RELAY_PANICKING: ProtocolId = ProtocolId::MAX - 5;
+
+/// Panics as soon as it starts, to test how node panics are contained
+pub(crate) struct Panicking;
+
+impl Protocol for Panicking {
+    fn start(&mut self, _node: &mut NodeInternals, input: Value) -> ProtocolState {
+        panic!("Panicking protocol started with {}", input)
+    }
+
+    fn handle(&mut self, _node: &mut NodeInternals, _from: NodeId, _msg: &Envelope) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Relayed(pub Value);
+
+impl ProtocolMessage for Relayed {
+    const PROTOCOL: ProtocolId = RELAY_PANICKING;
+
+    fn phase(&self) -> Phase {
+        Phase::Echo
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Value>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        Relayed(v)
+    }
+
+    fn malicious(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// Outputs its input as it starts and sends it to all, node 0 panics on the
+/// first message it handles after, to test panics of nodes that delivered
+pub(crate) struct RelayPanicking;
+
+impl Protocol for RelayPanicking {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.send_to_all(node.msg(Relayed(input)));
+        node.deliver(input)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<Relayed>() {
+            Some(Relayed(v)) if node.id == 0 => {
+                panic!("Node 0 panicked relaying {} from {}", v, from)
+            }
+            _ => ProtocolState::InProcess,
+        }
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+pub(crate) const TICK_PERIOD: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Tick(pub Value);
+
+impl ProtocolMessage for Tick {
+    const PROTOCOL: ProtocolId = TICKING;
+
+    fn phase(&self) -> Phase {
+        Phase::Control
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Value>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        Tick(v)
+    }
+
+    fn malicious(&self) -> Self {
+        self.clone()
+    }
+}
+
+/// Counts ticks scheduled every `TICK_PERIOD` and outputs the count once it
+/// reaches its input
+pub(crate) struct Ticking {
+    target: Value,
+}
+
+impl Ticking {
+    pub fn new() -> Self {
+        Ticking { target: 0 }
+    }
+}
+
+impl Protocol for Ticking {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        self.target = input;
+        node.schedule(TICK_PERIOD, node.msg(Tick(1)));
+        ProtocolState::InProcess
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<Tick>() {
+            Some(Tick(count)) if from == node.id => {
+                if *count >= self.target {
+                    return node.deliver(*count);
+                }
+                node.schedule(TICK_PERIOD, node.msg(Tick(count + 1)));
+                ProtocolState::InProcess
+            }
+            _ => ProtocolState::InProcess,
+        }
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Vote(pub Value);
+
+impl ProtocolMessage for Vote {
+    const PROTOCOL: ProtocolId = EQUIVOCATING;
+
+    fn phase(&self) -> Phase {
+        Phase::Vote
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<Value>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        Vote(v)
+    }
+
+    fn malicious(&self) -> Self {
+        self.clone()
+    }
+
+    fn commitment(&self) -> Option<Commitment> {
+        Some(Commitment::new("VOTE", 0, self.0 as u64))
+    }
+}
+
+/// Buggy protocol whose every node votes twice, for different values, and
+/// outputs its own input, to test the monitors of the network
+pub(crate) struct Equivocating;
+
+impl Protocol for Equivocating {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        node.send_to_all(node.msg(Vote(input)));
+        node.send_to_all(node.msg(Vote(input + 1)));
+        node.deliver(input)
+    }
+
+    fn handle(&mut self, _node: &mut NodeInternals, _from: NodeId, _msg: &Envelope) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+}
+
+/// Buggy protocol that starts without keeping its input, to test the
+/// invariants checked by the nodes
+#[derive(Debug)]
+pub(crate) struct Forgetful {
+    started: bool,
+    input: Option<Value>,
+}
+
+impl Forgetful {
+    pub fn new() -> Self {
+        Forgetful {
+            started: false,
+            input: None,
+        }
+    }
+}
+
+impl Protocol for Forgetful {
+    fn start(&mut self, _node: &mut NodeInternals, _input: Value) -> ProtocolState {
+        self.started = true;
+        ProtocolState::InProcess
+    }
+
+    fn handle(&mut self, _node: &mut NodeInternals, _from: NodeId, _msg: &Envelope) -> ProtocolState {
+        ProtocolState::InProcess
+    }
+
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        None
+    }
+
+    fn check(&self, _node: &NodeInternals) -> Result<(), String> {
+        match self.started && self.input.is_none() {
+            true => Err(format!("started without an input: {:?}", self)),
+            false => Ok(()),
+        }
+    }
+}
+
+#[cfg(feature = "crypto")]
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct SigShare(pub SignatureShare);
+
+#[cfg(feature = "crypto")]
+impl ProtocolMessage for SigShare {
+    const PROTOCOL: ProtocolId = CERTIFYING;
+
+    fn phase(&self) -> Phase {
+        Phase::Vote
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<SignatureShare>()
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        SigShare(v as SignatureShare)
+    }
+
+    fn malicious(&self) -> Self {
+        SigShare(!self.0)
+    }
+}
+
+/// Certificate of the input of the nodes: every node hands out its share
+/// of the signature of its input and outputs the signature once it holds
+/// enough valid shares, to test the signing keys dealt to the nodes
+/// Shares received before the input are checked once it is known, invalid
+/// ones are counted in `invalid_shares`
+#[cfg(feature = "crypto")]
+pub(crate) struct Certifying {
+    scheme: Arc<dyn ThresholdSignature>,
+    input: Option<Value>,
+    shares: BTreeMap<NodeId, SignatureShare>,
+    early: BTreeMap<NodeId, SignatureShare>,
+    signed: bool,
+}
+
+#[cfg(feature = "crypto")]
+impl Certifying {
+    pub fn new(config: SignatureConfig) -> Self {
+        Certifying {
+            scheme: config.scheme,
+            input: None,
+            shares: BTreeMap::new(),
+            early: BTreeMap::new(),
+            signed: false,
+        }
+    }
+
+    fn add_share(
+        &mut self,
+        node: &mut NodeInternals,
+        from: NodeId,
+        share: SignatureShare,
+    ) -> ProtocolState {
+        let input = match self.input {
+            Some(_) if self.signed => return ProtocolState::InProcess,
+            Some(input) => input as u64,
+            None => {
+                self.early.entry(from).or_insert(share);
+                return ProtocolState::InProcess;
+            }
+        };
+        node.crypto(CryptoOp::Verify, 1);
+        if !self.scheme.verify_share(from, input, share) {
+            node.count("invalid_shares", 1);
+            return ProtocolState::InProcess;
+        }
+        self.shares.insert(from, share);
+        match self.scheme.combine(input, &self.shares) {
+            Some(signature) => {
+                node.crypto(CryptoOp::SignCombine, 1);
+                self.signed = true;
+                node.deliver(signature as Value)
+            }
+            None => ProtocolState::InProcess,
+        }
+    }
+}
+
+#[cfg(feature = "crypto")]
+impl Protocol for Certifying {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        self.input = Some(input);
+        let share = self.scheme.sign_share(&node.signing_key, input as u64);
+        node.crypto(CryptoOp::SignShare, 1);
+        node.send_to_all(node.msg(SigShare(share)));
+        let id = node.id;
+        let mut state = self.add_share(node, id, share);
+        for (from, share) in std::mem::take(&mut self.early) {
+            if self.signed {
+                break;
+            }
+            state = self.add_share(node, from, share);
+        }
+        state
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<SigShare>() {
+            Some(SigShare(share)) => self.add_share(node, from, *share),
+            None => ProtocolState::InProcess,
+        }
+    }
+
+    fn random_message(&self, rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(SigShare(rng.next_u64())))
+    }
+}
@@ -0,0 +1,688 @@
+use crate::cost::CryptoOp;
+use crate::crypto::threshold_sig::{SignatureConfig, SignatureShare, ThresholdSignature};
+use crate::network::*;
+use crate::node::*;
+use crate::protocols::pbft::{Certificate, View};
+use crate::protocols::*;
+use crate::quorum::Threshold;
+use crate::rng::mix;
+use crate::rng::Rng;
+use crate::stats::Phase;
+use std::fmt;
+use std::sync::Arc;
+
+/// Stages of the promotion of a proposal, the proofs of the first three
+/// are the key, the lock and the commit of the value
+pub const STAGES: u32 = 4;
+const KEY: u32 = 1;
+const LOCK: u32 = 2;
+const COMMIT: u32 = 3;
+
+/// Signatures of `n - f` nodes on stage `stage` of the promotion of
+/// `value` by `promoter` in `view`, checked by anyone
+/// A proof of any stage shows that `n - f` nodes took the value at the
+/// first stage, which honest nodes do for a single value per promoter
+/// and view
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Proof {
+    view: View,
+    promoter: NodeId,
+    stage: u32,
+    value: Value,
+    signatures: Certificate,
+}
+
+impl Proof {
+    /// Message signed by the nodes that take stage `stage`
+    fn message(view: View, promoter: NodeId, stage: u32, value: Value) -> u64 {
+        let header = (view as u64) << 32 | (stage as u64) << 16 | promoter as u64;
+        mix(mix(u64::from_be_bytes(*b"VB_PROOF") ^ header) ^ value as u64)
+    }
+
+    fn size(&self) -> usize {
+        std::mem::size_of::<View>()
+            + std::mem::size_of::<NodeId>()
+            + std::mem::size_of::<u32>()
+            + std::mem::size_of::<Value>()
+            + self.signatures.len()
+                * (std::mem::size_of::<NodeId>() + std::mem::size_of::<SignatureShare>())
+    }
+}
+
+fn proof_size(proof: &Option<Proof>) -> usize {
+    proof.as_ref().map_or(0, Proof::size)
+}
+
+/// What a node knows of the promotion of the elected leader of a view:
+/// the proofs of its stages the leader sent it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct ViewChange {
+    key: Option<Proof>,
+    lock: Option<Proof>,
+    commit: Option<Proof>,
+}
+
+impl ViewChange {
+    fn proofs(&self) -> impl Iterator<Item = &Proof> {
+        [&self.key, &self.lock, &self.commit].into_iter().flatten()
+    }
+}
+
+/// Validated asynchronous Byzantine agreement of Abraham, Malkhi and
+/// Spiegelman: nodes agree on a value that satisfies the external validity
+/// predicate, in a constant expected number of views
+/// In a view every node promotes its proposal in 4 stages of a provable
+/// broadcast: it sends the value with the proof of the last stage, nodes
+/// sign it back to it, and `n - f` signatures are the proof of the stage
+/// Nodes keep the proofs of the key, lock and commit stages of each
+/// promoter, and take the first stage of a value only if it is valid to
+/// them and carries a key of the elected leader of a view, of the value of
+/// their lock in the view of the lock or of any value in a later one
+/// A promoter done with its 4 stages sends DONE, on `n - f` DONE nodes send
+/// SKIP, f + 1 SKIP have the others send it too and on `n - f` SKIP nodes
+/// stop the promotions and elect the leader of the view with the coins
+/// Nodes then send the proofs they hold from the leader in VIEW_CHANGE, and
+/// on `n - f` of them decide the value of a commit, or adopt the lock and
+/// the latest key they got and propose the key in the next view
+/// A commit shows that `n - f` nodes hold the lock of the value, any `n -
+/// f` VIEW_CHANGE carry one: every honest node locks the value and proposes
+/// it, no other value gets a key in the later views
+/// A leader done before the election had `n - f` nodes hold its commit,
+/// the election picks one of the `n - f` done with probability 2/3
+/// Nodes that decide send the commit in DECIDE, so that the others decide
+/// without them
+/// Views are counted in `views`, invalid proofs and signatures in
+/// `invalid_signatures`
+#[derive(Clone, PartialEq)]
+pub(crate) enum VabaMessage {
+    // Stage of the proposal of the sender with the proof of the last
+    // stage, or with its key at the first stage
+    VB_SEND(View, u32, Value, Option<Proof>),
+    // Signature of the receiver of a SEND on its stage
+    VB_ACK(View, u32, SignatureShare),
+    // Sender is done with the promotion of its proposal
+    VB_DONE(View, Proof),
+    VB_SKIP(View),
+    VB_VIEW_CHANGE(View, ViewChange),
+    // Commit of the elected leader of a view, its value is decided
+    VB_DECIDE(Proof),
+}
+use VabaMessage::*;
+
+impl ProtocolMessage for VabaMessage {
+    const PROTOCOL: ProtocolId = VABA;
+
+    fn phase(&self) -> Phase {
+        match self {
+            VB_SEND(_, stage, ..) if *stage == KEY => Phase::Init,
+            VB_SEND(..) | VB_ACK(..) => Phase::Echo,
+            VB_DONE(..) | VB_SKIP(_) | VB_DECIDE(_) => Phase::Ready,
+            VB_VIEW_CHANGE(..) => Phase::Leader,
+        }
+    }
+
+    /// Kind, view, stage, value and proofs
+    fn size(&self) -> usize {
+        let view = std::mem::size_of::<View>();
+        let stage = std::mem::size_of::<u32>();
+        1 + match self {
+            VB_SEND(_, _, _, proof) => {
+                view + stage + std::mem::size_of::<Value>() + proof_size(proof)
+            }
+            VB_ACK(..) => view + stage + std::mem::size_of::<SignatureShare>(),
+            VB_DONE(_, proof) => view + proof.size(),
+            VB_SKIP(_) => view,
+            VB_VIEW_CHANGE(_, change) => view + change.proofs().map(Proof::size).sum::<usize>(),
+            VB_DECIDE(proof) => proof.size(),
+        }
+    }
+
+    fn with_value(&self, v: Value) -> Self {
+        match self {
+            VB_SEND(view, stage, _, proof) => VB_SEND(*view, *stage, v, proof.clone()),
+            VB_DECIDE(proof) => VB_DECIDE(Proof {
+                value: v,
+                ..proof.clone()
+            }),
+            msg => msg.clone(),
+        }
+    }
+
+    fn malicious(&self) -> Self {
+        ProtocolMessage::with_value(self, MALICIOUS_VALUE)
+    }
+
+    /// Promoters send a single value per view
+    fn commitment(&self) -> Option<Commitment> {
+        match self {
+            VB_SEND(view, _, v, _) => Some(Commitment::new("SEND", *view as u64, *v as u64)),
+            _ => None,
+        }
+    }
+
+    fn value(&self) -> Option<Value> {
+        match self {
+            VB_SEND(_, _, v, _) => Some(*v),
+            VB_DECIDE(proof) => Some(proof.value),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Debug for VabaMessage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VB_SEND(view, stage, v, _) => write!(f, "<SEND, {}, {}, {}>", view, stage, v),
+            VB_ACK(view, stage, _) => write!(f, "<ACK, {}, {}>", view, stage),
+            VB_DONE(view, _) => write!(f, "<DONE, {}>", view),
+            VB_SKIP(view) => write!(f, "<SKIP, {}>", view),
+            VB_VIEW_CHANGE(view, change) => {
+                let stages: Vec<String> = change.proofs().map(|p| p.stage.to_string()).collect();
+                write!(f, "<VIEW_CHANGE, {}, [{}]>", view, stages.join(" "))
+            }
+            VB_DECIDE(proof) => write!(f, "<DECIDE, {}, {}>", proof.view, proof.value),
+        }
+    }
+}
+
+/// VABA as run by a node
+pub(crate) struct Vaba {
+    scheme: Arc<dyn ThresholdSignature>,
+    input: Value,
+    view: View,
+    // Latest proof the node adopted at a view change, the proposal of the
+    // next views
+    key: Option<Proof>,
+    // View and value of the latest lock the node adopted, that of the
+    // leader of the view
+    lock: Option<(View, Value)>,
+    // Latest key of the proposals of the node itself, replaying nodes
+    // propose it again whoever led its view
+    promoted: Option<Proof>,
+    // Value and key promoted by the node in the view under way, the stage
+    // under way and the signatures collected for it
+    proposal: (Value, Option<Proof>),
+    stage: u32,
+    acks: Certificate,
+    // Stages of the promoters the node signed in the view under way
+    acked: StateSet<(NodeId, u32)>,
+    // Proofs the promoters sent in the view under way, by promoter and
+    // stage
+    proofs: StateMap<(NodeId, u32), Proof>,
+    dones: StateSet<NodeId>,
+    skips: StateSet<NodeId>,
+    skipped: bool,
+    // Node stopped the promotions of the view under way
+    abandoned: bool,
+    elections: Children,
+    view_changes: StateMap<NodeId, ViewChange>,
+    changed: bool,
+    // Messages of the views ahead of the node
+    ahead: Vec<(NodeId, VabaMessage)>,
+    // Commits of views whose leader the node doesn't know yet
+    decisions: Vec<Proof>,
+    decided: Option<Value>,
+}
+
+impl Vaba {
+    pub fn new(signatures: SignatureConfig) -> Self {
+        Vaba {
+            scheme: signatures.scheme,
+            input: 0,
+            view: 0,
+            key: None,
+            lock: None,
+            promoted: None,
+            proposal: (0, None),
+            stage: 0,
+            acks: Certificate::new(),
+            acked: StateSet::new(),
+            proofs: StateMap::new(),
+            dones: StateSet::new(),
+            skips: StateSet::new(),
+            skipped: false,
+            abandoned: false,
+            elections: Children::new(),
+            view_changes: StateMap::new(),
+            changed: false,
+            ahead: vec![],
+            decisions: vec![],
+            decided: None,
+        }
+    }
+
+    fn sign(&self, node: &NodeInternals, message: u64) -> SignatureShare {
+        node.crypto(CryptoOp::Sign, 1);
+        self.scheme.sign_share(&node.signing_key, message)
+    }
+
+    /// Whether `proof` holds valid signatures of a quorum, invalid ones are
+    /// counted
+    fn proven(&self, node: &NodeInternals, proof: &Proof) -> bool {
+        let message = Proof::message(proof.view, proof.promoter, proof.stage, proof.value);
+        node.crypto(CryptoOp::Verify, proof.signatures.len());
+        let valid = proof.signatures.len() >= node.min_honnest_nodes
+            && proof
+                .signatures
+                .iter()
+                .all(|(signer, share)| self.scheme.verify_share(*signer, message, *share));
+        if !valid {
+            node.count("invalid_signatures", 1);
+        }
+        valid
+    }
+
+    /// Leader elected for `view`, once the coins are tossed
+    fn leader(&self, view: View) -> Option<NodeId> {
+        self.elections.output(view).and_then(Output::value)
+    }
+
+    /// Start the view with the key of the node, or its input without one
+    fn start_view(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        node.count("views", 1);
+        let key = match node.replays() {
+            true => self.promoted.as_ref().or(self.key.as_ref()),
+            false => self.key.as_ref(),
+        };
+        self.proposal = match key {
+            Some(key) => (key.value, Some(key.clone())),
+            None => (self.input, None),
+        };
+        self.stage = 0;
+        let key = self.proposal.1.clone();
+        self.promote(node, key);
+        // Messages that arrived ahead of the view
+        let ahead = std::mem::take(&mut self.ahead);
+        for (from, msg) in ahead {
+            if let ProtocolState::Terminated(output) = self.receive(node, from, msg) {
+                return ProtocolState::Terminated(output);
+            }
+        }
+        ProtocolState::InProcess
+    }
+
+    /// Next stage of the proposal of the node, with the proof of the last
+    /// one
+    fn promote(&mut self, node: &mut NodeInternals, proof: Option<Proof>) {
+        self.stage += 1;
+        self.acks.clear();
+        let (view, stage, value) = (self.view, self.stage, self.proposal.0);
+        node.send_to_all(node.msg(VB_SEND(view, stage, value, proof.clone())));
+        if let Some(share) = self.take(node, node.id, stage, value, proof) {
+            self.acks.insert(node.id, share);
+        }
+    }
+
+    /// Signature of the node on stage `stage` of the proposal of
+    /// `promoter`, None if it doesn't take it
+    fn take(
+        &mut self,
+        node: &mut NodeInternals,
+        promoter: NodeId,
+        stage: u32,
+        value: Value,
+        proof: Option<Proof>,
+    ) -> Option<SignatureShare> {
+        if self.abandoned || !(KEY..=STAGES).contains(&stage) {
+            return None;
+        }
+        if !self.acked.insert((promoter, stage)) {
+            return None;
+        }
+        let view = self.view;
+        let taken = match (stage, proof) {
+            // Valid value with a key of the leader of an earlier view, of
+            // the locked value in the view of the lock or of a later view
+            (KEY, key) => {
+                node.is_valid(value)
+                    && match key {
+                        None => self.lock.is_none(),
+                        Some(key) => {
+                            key.value == value
+                                && key.view < view
+                                && self.leader(key.view) == Some(key.promoter)
+                                && self.lock.is_none_or(|(lock, locked)| {
+                                    key.view > lock || (key.view == lock && key.value == locked)
+                                })
+                                && self.proven(node, &key)
+                        }
+                    }
+            }
+            // Proof of the last stage of this promotion
+            (_, Some(proof)) => {
+                let valid = proof.view == view
+                    && proof.promoter == promoter
+                    && proof.stage == stage - 1
+                    && proof.value == value
+                    && self.proven(node, &proof);
+                if valid {
+                    self.proofs.insert((promoter, proof.stage), proof);
+                }
+                valid
+            }
+            (_, None) => false,
+        };
+        if !taken {
+            if promoter != node.id {
+                node.count("rejected", 1);
+            }
+            return None;
+        }
+        Some(self.sign(node, Proof::message(view, promoter, stage, value)))
+    }
+
+    /// Signature of `from` on the stage under way of the proposal
+    fn ack(&mut self, node: &mut NodeInternals, from: NodeId, share: SignatureShare) {
+        let message = Proof::message(self.view, node.id, self.stage, self.proposal.0);
+        node.crypto(CryptoOp::Verify, 1);
+        if !self.scheme.verify_share(from, message, share) {
+            node.count("invalid_signatures", 1);
+            return;
+        }
+        self.acks.insert(from, share);
+        if self.acks.len() < node.min_honnest_nodes {
+            return;
+        }
+        let proof = Proof {
+            view: self.view,
+            promoter: node.id,
+            stage: self.stage,
+            value: self.proposal.0,
+            signatures: std::mem::take(&mut self.acks),
+        };
+        if self.stage == KEY {
+            self.promoted = Some(proof.clone());
+        }
+        if self.stage < STAGES {
+            self.promote(node, Some(proof));
+        } else {
+            self.stage += 1;
+            node.send_to_all(node.msg(VB_DONE(self.view, proof)));
+            self.done(node, node.id);
+        }
+    }
+
+    fn done(&mut self, node: &mut NodeInternals, from: NodeId) {
+        self.dones.insert(from);
+        if self.dones.len() >= node.min_honnest_nodes {
+            self.skip(node);
+        }
+    }
+
+    fn skip(&mut self, node: &mut NodeInternals) {
+        if self.skipped {
+            return;
+        }
+        self.skipped = true;
+        node.send_to_all(node.msg(VB_SKIP(self.view)));
+        self.skipped_by(node, node.id);
+    }
+
+    /// SKIP of `from`: f + 1 of them hold an honest one, `n - f` end the
+    /// promotions of the view
+    fn skipped_by(&mut self, node: &mut NodeInternals, from: NodeId) {
+        self.skips.insert(from);
+        if self.skips.len() > node.max_malicious_nodes {
+            self.skip(node);
+        }
+        if self.skips.len() >= node.min_honnest_nodes && !self.abandoned {
+            self.abandoned = true;
+            node.spawn(LEADER_ELECTION, self.view, 0);
+        }
+    }
+
+    /// Proofs of `change` from the leader of the view, the others are
+    /// dropped
+    fn checked(&self, node: &NodeInternals, leader: NodeId, change: ViewChange) -> ViewChange {
+        let check = |proof: Option<Proof>, stage: u32| {
+            proof.filter(|proof| {
+                proof.view == self.view
+                    && proof.promoter == leader
+                    && proof.stage == stage
+                    && self.proven(node, proof)
+            })
+        };
+        ViewChange {
+            key: check(change.key, KEY),
+            lock: check(change.lock, LOCK),
+            commit: check(change.commit, COMMIT),
+        }
+    }
+
+    /// Once the leader of the view is known, send what the node holds of
+    /// its promotion, and on `n - f` VIEW_CHANGE decide or move to the next
+    /// view
+    fn change_view(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let leader = match self.leader(self.view) {
+            Some(leader) if self.abandoned => leader,
+            _ => return ProtocolState::InProcess,
+        };
+        if !self.changed {
+            self.changed = true;
+            let change = ViewChange {
+                key: self.proofs.get(&(leader, KEY)).cloned(),
+                lock: self.proofs.get(&(leader, LOCK)).cloned(),
+                commit: self.proofs.get(&(leader, COMMIT)).cloned(),
+            };
+            node.send_to_all(node.msg(VB_VIEW_CHANGE(self.view, change.clone())));
+            self.view_changes.insert(node.id, change);
+        }
+        if self.view_changes.len() < node.min_honnest_nodes {
+            return ProtocolState::InProcess;
+        }
+        let changes: Vec<ViewChange> = std::mem::take(&mut self.view_changes)
+            .into_iter()
+            .map(|(from, change)| match from == node.id {
+                true => change,
+                false => self.checked(node, leader, change),
+            })
+            .collect();
+        if let Some(commit) = changes.iter().find_map(|change| change.commit.clone()) {
+            return self.decide(node, commit);
+        }
+        if let Some(lock) = changes.iter().find_map(|change| change.lock.as_ref()) {
+            self.lock = Some((lock.view, lock.value));
+        }
+        for proof in changes.iter().flat_map(ViewChange::proofs) {
+            if self.key.as_ref().is_none_or(|key| proof.view > key.view) {
+                self.key = Some(proof.clone());
+            }
+        }
+
+        self.view += 1;
+        self.acked.clear();
+        self.proofs.clear();
+        self.dones.clear();
+        self.skips.clear();
+        self.skipped = false;
+        self.abandoned = false;
+        self.changed = false;
+        self.start_view(node)
+    }
+
+    /// Commit of a view whose leader the node knows, tell the others
+    fn decide(&mut self, node: &mut NodeInternals, commit: Proof) -> ProtocolState {
+        self.decided = Some(commit.value);
+        node.debug(&format!("view {} decided {}", commit.view, commit.value));
+        let value = commit.value;
+        node.send_to_all(node.msg(VB_DECIDE(commit)));
+        node.deliver(value)
+    }
+
+    /// Commits of the views whose leader is known, the first valid one is
+    /// decided
+    fn check_decisions(&mut self, node: &mut NodeInternals) -> ProtocolState {
+        let (known, unknown): (Vec<Proof>, Vec<Proof>) = std::mem::take(&mut self.decisions)
+            .into_iter()
+            .partition(|proof| self.leader(proof.view).is_some());
+        self.decisions = unknown;
+        for proof in known {
+            let valid = self.leader(proof.view) == Some(proof.promoter)
+                && proof.stage == COMMIT
+                && self.proven(node, &proof);
+            if valid {
+                return self.decide(node, proof);
+            }
+        }
+        ProtocolState::InProcess
+    }
+
+    fn receive(
+        &mut self,
+        node: &mut NodeInternals,
+        from: NodeId,
+        msg: VabaMessage,
+    ) -> ProtocolState {
+        if self.decided.is_some() {
+            return ProtocolState::InProcess;
+        }
+        let view = match &msg {
+            VB_DECIDE(proof) => {
+                self.decisions.push(proof.clone());
+                return self.check_decisions(node);
+            }
+            VB_SEND(view, ..)
+            | VB_ACK(view, ..)
+            | VB_DONE(view, _)
+            | VB_SKIP(view)
+            | VB_VIEW_CHANGE(view, _) => *view,
+        };
+        if view > self.view {
+            self.ahead.push((from, msg));
+            return ProtocolState::InProcess;
+        }
+        if view < self.view {
+            return ProtocolState::InProcess;
+        }
+        match msg {
+            VB_SEND(_, stage, v, proof) => {
+                if let Some(share) = self.take(node, from, stage, v, proof) {
+                    node.send_to(from, node.msg(VB_ACK(view, stage, share)));
+                }
+            }
+            VB_ACK(_, stage, share) if stage == self.stage && !self.abandoned => {
+                self.ack(node, from, share);
+            }
+            VB_DONE(_, proof) => {
+                let valid = proof.view == view
+                    && proof.promoter == from
+                    && proof.stage == STAGES
+                    && self.proven(node, &proof);
+                if valid {
+                    self.done(node, from);
+                }
+            }
+            VB_SKIP(_) => self.skipped_by(node, from),
+            VB_VIEW_CHANGE(_, change) => {
+                self.view_changes.entry(from).or_insert(change);
+                return self.change_view(node);
+            }
+            _ => {}
+        }
+        ProtocolState::InProcess
+    }
+}
+
+impl Protocol for Vaba {
+    fn start(&mut self, node: &mut NodeInternals, input: Value) -> ProtocolState {
+        self.input = input;
+        self.start_view(node)
+    }
+
+    fn handle(&mut self, node: &mut NodeInternals, from: NodeId, msg: &Envelope) -> ProtocolState {
+        match msg.open::<VabaMessage>() {
+            Some(msg) => self.receive(node, from, msg.clone()),
+            None => ProtocolState::InProcess,
+        }
+    }
+
+    fn child_output(
+        &mut self,
+        node: &mut NodeInternals,
+        child: &InstanceId,
+        output: &Output,
+    ) -> ProtocolState {
+        if child.protocol() != LEADER_ELECTION || !self.elections.record(child, output) {
+            return ProtocolState::InProcess;
+        }
+        if self.decided.is_some() {
+            return ProtocolState::InProcess;
+        }
+        if let ProtocolState::Terminated(output) = self.check_decisions(node) {
+            return ProtocolState::Terminated(output);
+        }
+        self.change_view(node)
+    }
+
+    /// First stage of the malicious value without a key
+    fn random_message(&self, _rng: &mut dyn Rng) -> Option<Box<dyn Payload>> {
+        Some(Box::new(VB_SEND(self.view, KEY, MALICIOUS_VALUE, None)))
+    }
+
+    /// Honest nodes decide the same value
+    fn audit(&self) -> Vec<(&'static str, String)> {
+        match self.decided {
+            Some(v) => vec![("decided", v.to_string())],
+            None => vec![],
+        }
+    }
+}
+
+/// Messages, state and thresholds of VABA
+pub fn describe(num_nodes: usize) -> ProtocolDescription {
+    let (f, quorum) = thresholds(num_nodes, FaultModel::Byzantine);
+    let proof = Proof {
+        view: 0,
+        promoter: 0,
+        stage: KEY,
+        value: 0,
+        signatures: Certificate::new(),
+    };
+    ProtocolDescription {
+        messages: vec![
+            MessageSchema::of(VB_SEND(0, LOCK, 0, Some(proof.clone()))),
+            MessageSchema::of(VB_ACK(0, KEY, 0)),
+            MessageSchema::of(VB_DONE(0, proof.clone())),
+            MessageSchema::of(VB_SKIP(0)),
+            MessageSchema::of(VB_VIEW_CHANGE(0, ViewChange::default())),
+            MessageSchema::of(VB_DECIDE(proof)),
+        ],
+        state: vec![
+            "view",
+            "key",
+            "lock",
+            "promoted",
+            "proposal",
+            "stage",
+            "acks",
+            "acked",
+            "proofs",
+            "dones",
+            "skips",
+            "abandoned",
+            "elections",
+            "view_changes",
+            "decided",
+        ],
+        thresholds: vec![
+            Threshold::new(
+                "stage_proof",
+                quorum,
+                "n - f signatures prove a stage, two such quorums share an honest node",
+            ),
+            Threshold::new("stages", STAGES as usize, "key, lock, commit and done"),
+            Threshold::new(
+                "skip_amplification",
+                f + 1,
+                "f + 1 SKIP hold an honest one, the node SKIPs too",
+            ),
+            Threshold::new(
+                "view_change",
+                quorum,
+                "n - f VIEW_CHANGE hold the lock of any commit of the leader",
+            ),
+        ],
+        children: vec![LEADER_ELECTION],
+        ..ProtocolDescription::new(VABA)
+    }
+}
@@ -0,0 +1,295 @@
+use crate::node::{thresholds, MaliciousKind};
+use std::fmt;
+use std::str::FromStr;
+
+/// Failures a protocol is designed for, they set how many faulty nodes it
+/// tolerates and how large its quorums are
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum FaultModel {
+    // Faulty nodes may do anything, less than a third of the nodes
+    #[default]
+    Byzantine,
+    // Faulty nodes only stop, possibly in the middle of a send, less than
+    // half of the nodes
+    Crash,
+    // Separate budgets of nodes that may do anything and of nodes that only
+    // stop, tolerated by more than 3 * byzantine + 2 * crash nodes
+    Hybrid {
+        byzantine: usize,
+        crash: usize,
+    },
+}
+
+impl FaultModel {
+    /// Largest number of faulty nodes among `num_nodes` nodes
+    /// Budgets of a hybrid model are all or nothing: none of them if there
+    /// are too few nodes
+    pub fn max_faulty(&self, num_nodes: usize) -> usize {
+        match self {
+            FaultModel::Byzantine => num_nodes.saturating_sub(1) / 3,
+            FaultModel::Crash => num_nodes.saturating_sub(1) / 2,
+            FaultModel::Hybrid { byzantine, crash } if num_nodes >= self.min_nodes(0) => {
+                byzantine + crash
+            }
+            FaultModel::Hybrid { .. } => 0,
+        }
+    }
+
+    /// Smallest number of nodes tolerating `faulty` faulty nodes, the
+    /// budgets of a hybrid model set the faulty nodes
+    pub fn min_nodes(&self, faulty: usize) -> usize {
+        match self {
+            FaultModel::Byzantine => 3 * faulty + 1,
+            FaultModel::Crash => 2 * faulty + 1,
+            FaultModel::Hybrid { byzantine, crash } => 3 * byzantine + 2 * crash + 1,
+        }
+    }
+
+    /// Whether malicious nodes of `kind` fail within the model
+    pub fn allows(&self, kind: &MaliciousKind) -> bool {
+        match self {
+            FaultModel::Byzantine => true,
+            FaultModel::Crash => matches!(kind, MaliciousKind::Silent | MaliciousKind::Crash(_)),
+            // Kind of the byzantine nodes, the others crash from the start
+            FaultModel::Hybrid { .. } => true,
+        }
+    }
+}
+
+impl FromStr for FaultModel {
+    type Err = String;
+
+    /// `byzantine`, `crash` or `hybrid:BYZANTINE,CRASH`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        if let Some(budgets) = s.strip_prefix("hybrid:") {
+            let invalid = || format!("Invalid hybrid budgets: {}", budgets);
+            let (byzantine, crash) = budgets.split_once(',').ok_or_else(invalid)?;
+            return Ok(FaultModel::Hybrid {
+                byzantine: byzantine.trim().parse().map_err(|_| invalid())?,
+                crash: crash.trim().parse().map_err(|_| invalid())?,
+            });
+        }
+        match s.as_str() {
+            "byzantine" => Ok(FaultModel::Byzantine),
+            "crash" => Ok(FaultModel::Crash),
+            _ => Err(format!("Unknown fault model: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for FaultModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FaultModel::Byzantine => write!(f, "byzantine"),
+            FaultModel::Crash => write!(f, "crash"),
+            FaultModel::Hybrid { byzantine, crash } => {
+                write!(f, "hybrid:{},{}", byzantine, crash)
+            }
+        }
+    }
+}
+
+/// Thresholds of a protocol run by `num_nodes` nodes under a fault model
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quorums {
+    pub model: FaultModel,
+    pub num_nodes: usize,
+    pub max_faulty: usize,
+}
+
+impl Quorums {
+    pub fn new(model: FaultModel, num_nodes: usize) -> Self {
+        Quorums {
+            model,
+            num_nodes,
+            max_faulty: model.max_faulty(num_nodes),
+        }
+    }
+
+    /// Number of nodes a node can wait for, faulty nodes may never answer
+    pub fn quorum(&self) -> usize {
+        self.num_nodes - self.max_faulty
+    }
+
+    /// Smallest number of nodes with at least one correct node among them
+    pub fn with_correct(&self) -> usize {
+        self.max_faulty + 1
+    }
+
+    /// Number of faulty nodes that may lie
+    pub fn max_byzantine(&self) -> usize {
+        match self.model {
+            FaultModel::Byzantine => self.max_faulty,
+            FaultModel::Crash => 0,
+            FaultModel::Hybrid { byzantine, .. } => byzantine.min(self.max_faulty),
+        }
+    }
+
+    /// Smallest number of nodes with at least one node that doesn't lie
+    /// among them, crashed nodes send nothing at all
+    pub fn with_honest(&self) -> usize {
+        self.max_byzantine() + 1
+    }
+
+    /// Whether any two quorums share a node that doesn't lie, so that they
+    /// can't vouch for different values
+    pub fn intersect(&self) -> bool {
+        let shared = (2 * self.quorum()).saturating_sub(self.num_nodes);
+        shared > self.max_byzantine()
+    }
+}
+
+/// Number the protocols compare counts of nodes to, and why it is that
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Threshold {
+    pub name: &'static str,
+    pub value: usize,
+    pub justification: String,
+}
+
+impl Threshold {
+    pub fn new(name: &'static str, value: usize, justification: &str) -> Self {
+        Threshold {
+            name,
+            value,
+            justification: justification.to_string(),
+        }
+    }
+}
+
+/// Thresholds derived from the number of nodes and the fault model, as the
+/// nodes count them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Parameters {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub model: FaultModel,
+    pub thresholds: Vec<Threshold>,
+}
+
+impl Parameters {
+    pub fn new(num_nodes: usize, num_malicious: usize, model: FaultModel) -> Self {
+        let n = num_nodes;
+        let quorums = Quorums::new(model, n);
+        let (f, quorum) = thresholds(n, model);
+        let mut thresholds = vec![];
+        let mut add = |name, value, justification: String| {
+            thresholds.push(Threshold {
+                name,
+                value,
+                justification,
+            })
+        };
+        add(
+            "max_faulty_nodes",
+            quorums.max_faulty,
+            format!(
+                "largest f with n >= {}, tolerating {} malicious nodes",
+                model.min_nodes(quorums.max_faulty),
+                num_malicious
+            ),
+        );
+        let counted = match model {
+            FaultModel::Hybrid { .. } => String::from("byzantine budget of the hybrid model"),
+            _ if n.is_multiple_of(3) && n > 0 => format!(
+                "n / 3 = {}, one more than the byzantine model tolerates when 3 divides n",
+                f
+            ),
+            _ => format!("n / 3 = {}", f),
+        };
+        add("max_malicious_nodes", f, counted);
+        add(
+            "min_honnest_nodes",
+            quorum,
+            format!(
+                "n - {} = {}: nodes a node can wait for, the others may never speak",
+                f, quorum
+            ),
+        );
+        add(
+            "echo_quorum",
+            quorum.saturating_sub(1),
+            String::from(
+                "min_honnest_nodes - 1 ECHOes from the other nodes before READY, nodes don't \
+                 send to themselves and count their own ECHO",
+            ),
+        );
+        add(
+            "ready_amplification",
+            f + 1,
+            String::from("max_malicious_nodes + 1 READYs include an honest one, a node joins them"),
+        );
+        add(
+            "ready_delivery",
+            quorum.saturating_sub(1),
+            String::from("min_honnest_nodes - 1 READYs from the other nodes, with its own"),
+        );
+        add(
+            "v2_echo_quorum",
+            ((n + f + 2) / 2).saturating_sub(1),
+            String::from("ceil((n + f + 1) / 2) - 1: any two such quorums share an honest node"),
+        );
+        add(
+            "quorum_overlap",
+            (2 * quorum).saturating_sub(n),
+            format!(
+                "2 * min_honnest_nodes - n nodes are in any two quorums, {} than the {} liars",
+                if 2 * quorum > n + f {
+                    "more"
+                } else {
+                    "not more"
+                },
+                f
+            ),
+        );
+        let crash = Quorums::new(FaultModel::Crash, n);
+        add(
+            "crash_quorum",
+            crash.quorum(),
+            format!(
+                "n - (n - 1) / 2 = {}: majority of Paxos and majority broadcast",
+                crash.quorum()
+            ),
+        );
+        add(
+            "key_threshold",
+            f + 1,
+            String::from(
+                "max_malicious_nodes + 1 shares decrypt or sign, faulty nodes alone can't",
+            ),
+        );
+        Parameters {
+            num_nodes,
+            num_malicious,
+            model,
+            thresholds,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<usize> {
+        self.thresholds
+            .iter()
+            .find(|threshold| threshold.name == name)
+            .map(|threshold| threshold.value)
+    }
+}
+
+/// One threshold per line with its justification
+impl fmt::Display for Parameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "n = {}, {} malicious, {} model",
+            self.num_nodes, self.num_malicious, self.model
+        )?;
+        for threshold in self.thresholds.iter() {
+            writeln!(
+                f,
+                "{} = {}: {}",
+                threshold.name, threshold.value, threshold.justification
+            )?;
+        }
+        Ok(())
+    }
+}
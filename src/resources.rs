@@ -0,0 +1,83 @@
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Resources of every network of the process
+static LIVE_THREADS: AtomicUsize = AtomicUsize::new(0);
+static LIVE_CHANNELS: AtomicUsize = AtomicUsize::new(0);
+
+/// Threads running and channels open
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ResourceCounts {
+    pub threads: usize,
+    // Channels whose receiving end is still alive
+    pub channels: usize,
+}
+
+impl fmt::Display for ResourceCounts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} threads, {} channels", self.threads, self.channels)
+    }
+}
+
+/// Resources of all the networks of the process, long campaigns should get
+/// back to none between networks
+pub fn live() -> ResourceCounts {
+    ResourceCounts {
+        threads: LIVE_THREADS.load(Ordering::SeqCst),
+        channels: LIVE_CHANNELS.load(Ordering::SeqCst),
+    }
+}
+
+/// Threads and channels of a network, each one counts until the token
+/// handed out for it is dropped
+#[derive(Clone, Debug, Default)]
+pub struct ResourceGuard {
+    threads: Arc<AtomicUsize>,
+    channels: Arc<AtomicUsize>,
+}
+
+impl ResourceGuard {
+    pub fn new() -> Self {
+        ResourceGuard::default()
+    }
+
+    /// Token moved into a thread when it is spawned
+    pub(crate) fn thread(&self) -> Token {
+        Token::new(self.threads.clone(), &LIVE_THREADS)
+    }
+
+    /// Token kept next to the receiving end of a channel
+    pub(crate) fn channel(&self) -> Token {
+        Token::new(self.channels.clone(), &LIVE_CHANNELS)
+    }
+
+    pub fn live(&self) -> ResourceCounts {
+        ResourceCounts {
+            threads: self.threads.load(Ordering::SeqCst),
+            channels: self.channels.load(Ordering::SeqCst),
+        }
+    }
+}
+
+/// Resource counted as live until the token is dropped
+#[derive(Debug)]
+pub(crate) struct Token {
+    count: Arc<AtomicUsize>,
+    global: &'static AtomicUsize,
+}
+
+impl Token {
+    fn new(count: Arc<AtomicUsize>, global: &'static AtomicUsize) -> Self {
+        count.fetch_add(1, Ordering::SeqCst);
+        global.fetch_add(1, Ordering::SeqCst);
+        Token { count, global }
+    }
+}
+
+impl Drop for Token {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+        self.global.fetch_sub(1, Ordering::SeqCst);
+    }
+}
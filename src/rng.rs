@@ -0,0 +1,168 @@
+use crate::node::NodeId;
+use log::warn;
+use rand::rngs::OsRng;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Source of randomness of the nodes and of the network
+pub trait Rng: Send {
+    fn next_u64(&mut self) -> u64;
+
+    /// Uniform draw in `0..n`
+    fn gen_range(&mut self, n: usize) -> usize {
+        assert!(n > 0, "Can't draw from an empty range");
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniform draw in `[0, 1)`
+    fn gen_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// True with probability `p`
+    fn gen_bool(&mut self, p: f64) -> bool {
+        self.gen_f64() < p
+    }
+}
+
+/// Reproducible randomness, each node draws from its own ChaCha stream
+pub struct SeededRng(ChaCha8Rng);
+
+impl SeededRng {
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        rng.set_stream(stream);
+        SeededRng(rng)
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_u64(&mut self) -> u64 {
+        self.0.next_u64()
+    }
+}
+
+/// Randomness of the operating system
+pub struct OsRandom;
+
+impl Rng for OsRandom {
+    fn next_u64(&mut self) -> u64 {
+        OsRng.next_u64()
+    }
+}
+
+/// SplitMix64 finalizer, the simulated schemes derive their keys and
+/// shares with it and the protocols hash their messages with it
+pub(crate) fn mix(z: u64) -> u64 {
+    let mut z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Draws made by every node (and the network) during a run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Randomness {
+    pub draws: BTreeMap<NodeId, Vec<u64>>,
+}
+
+/// Forward the draws of another source and keep a copy of them
+pub struct RecordingRng {
+    id: NodeId,
+    inner: Box<dyn Rng>,
+    record: Arc<Mutex<Randomness>>,
+}
+
+impl Rng for RecordingRng {
+    fn next_u64(&mut self) -> u64 {
+        let draw = self.inner.next_u64();
+        let mut record = self.record.lock().unwrap();
+        record.draws.entry(self.id).or_default().push(draw);
+        draw
+    }
+}
+
+/// Replay draws recorded by a `RecordingRng`
+/// Runs diverging from the recording go on with seeded draws
+pub struct ReplayRng {
+    id: NodeId,
+    draws: Vec<u64>,
+    next: usize,
+    fallback: SeededRng,
+}
+
+impl ReplayRng {
+    pub fn new(id: NodeId, draws: Vec<u64>) -> Self {
+        ReplayRng {
+            id,
+            draws,
+            next: 0,
+            fallback: SeededRng::new(0, id as u64),
+        }
+    }
+}
+
+impl Rng for ReplayRng {
+    fn next_u64(&mut self) -> u64 {
+        self.next += 1;
+        match self.draws.get(self.next - 1) {
+            Some(draw) => *draw,
+            None => {
+                if self.next == self.draws.len() + 1 {
+                    warn!("Node {} ran out of recorded randomness", self.id);
+                }
+                self.fallback.next_u64()
+            }
+        }
+    }
+}
+
+/// How the random generators of the nodes and of the network are built
+#[derive(Clone, Debug)]
+pub enum RngSource {
+    Seeded(u64),
+    Os,
+    // Record the draws of the inner source
+    Recording(Box<RngSource>),
+    // Replay draws recorded during a previous run
+    Replay(Randomness),
+}
+
+impl RngSource {
+    /// Seed reproducing the draws, if any
+    pub fn seed(&self) -> Option<u64> {
+        match self {
+            RngSource::Seeded(seed) => Some(*seed),
+            RngSource::Recording(inner) => inner.seed(),
+            RngSource::Os | RngSource::Replay(_) => None,
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            RngSource::Seeded(seed) => format!("seeded({})", seed),
+            RngSource::Os => String::from("os"),
+            RngSource::Recording(inner) => format!("recording({})", inner.name()),
+            RngSource::Replay(_) => String::from("replay"),
+        }
+    }
+
+    /// Build the generator of `id`, recorded draws are stored in `record`
+    pub(crate) fn build(&self, id: NodeId, record: &Arc<Mutex<Randomness>>) -> Box<dyn Rng> {
+        match self {
+            RngSource::Seeded(seed) => Box::new(SeededRng::new(*seed, id as u64)),
+            RngSource::Os => Box::new(OsRandom),
+            RngSource::Recording(inner) => Box::new(RecordingRng {
+                id,
+                inner: inner.build(id, record),
+                record: record.clone(),
+            }),
+            RngSource::Replay(randomness) => Box::new(ReplayRng::new(
+                id,
+                randomness.draws.get(&id).cloned().unwrap_or_default(),
+            )),
+        }
+    }
+}
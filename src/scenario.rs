@@ -0,0 +1,698 @@
+use crate::assertions::Assertion;
+use crate::manifest::Manifest;
+use crate::network::{Network, Output, Value};
+use crate::node::{CrashPoint, FaultSchedule, MaliciousKind, NodeId};
+use crate::protocols::bracha_broadcast::BroadcastConfig;
+use crate::quorum::FaultModel;
+use crate::rng::{Rng, RngSource, SeededRng};
+use crate::scheduler::{Schedule, ScriptedDelivery};
+use crate::stats::Phase;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+// Value broadcast by the leader of every scenario
+const VALUE: Value = 7;
+const SEED: u64 = 0;
+// Time honest nodes wait for progress when the scenario expects a stall
+const LIVENESS_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Ready-made attack on Bracha broadcast with the outcome it must have,
+/// runs are deterministic for a given number of nodes
+pub struct Scenario {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub expected: &'static str,
+    // Fault model of the faulty nodes and protocol under attack, as
+    // `fault:MODEL` and `protocol:NAME`, runs add their size class
+    pub tags: &'static [&'static str],
+    // Network of `n` nodes under attack and the choice of the leader of
+    // the broadcast
+    setup: fn(usize) -> (Network, LeaderPolicy),
+    // Primitive run by the `n` nodes, with its outputs
+    protocol: fn(&mut Network, usize, NodeId) -> HashMap<NodeId, Output>,
+    // Whether the outputs of the run are the expected ones, faulty nodes
+    // come last
+    check: fn(&ScenarioOutcome) -> bool,
+}
+
+/// Largest number of faulty nodes Bracha broadcast tolerates with `n`
+/// nodes
+fn max_faulty(n: usize) -> usize {
+    (n - 1) / 3
+}
+
+fn network(n: usize, f: usize, kind: MaliciousKind) -> Network {
+    let mut network = Network::with_rng(n, f, kind, RngSource::Seeded(SEED));
+    network.set_schedule(Schedule::Deterministic);
+    network
+}
+
+/// Choice of the leader of a broadcast among the nodes of a network
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LeaderPolicy {
+    // This node, honest or not
+    Fixed(NodeId),
+    // Honest node drawn uniformly with this seed
+    RandomHonest(u64),
+    // Node drawn uniformly with this seed, malicious ones included
+    RandomAny(u64),
+    // Malicious node with the highest id
+    Malicious,
+}
+
+impl LeaderPolicy {
+    /// Leader of the `run`-th broadcast on `network`, random policies draw
+    /// a new one for every run
+    pub fn leader(&self, network: &Network, run: u64) -> Result<NodeId, String> {
+        let good = network.good_nodes();
+        let malicious = network.malicious_nodes();
+        let num_nodes = good.len() + malicious.len();
+        let draw = |seed: u64, nodes: usize| SeededRng::new(seed, run).gen_range(nodes);
+        match self {
+            LeaderPolicy::Fixed(id) if *id < num_nodes => Ok(*id),
+            LeaderPolicy::Fixed(id) => Err(format!(
+                "Leader {} is not one of the {} nodes",
+                id, num_nodes
+            )),
+            LeaderPolicy::RandomHonest(_) if good.is_empty() => {
+                Err(String::from("Network has no honest node to lead"))
+            }
+            LeaderPolicy::RandomHonest(seed) => Ok(good[draw(*seed, good.len())]),
+            LeaderPolicy::RandomAny(seed) => Ok(draw(*seed, num_nodes)),
+            LeaderPolicy::Malicious => malicious
+                .last()
+                .copied()
+                .ok_or_else(|| String::from("Network has no malicious node to lead")),
+        }
+    }
+}
+
+impl fmt::Display for LeaderPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LeaderPolicy::Fixed(id) => write!(f, "fixed:{}", id),
+            LeaderPolicy::RandomHonest(seed) => write!(f, "honest:{}", seed),
+            LeaderPolicy::RandomAny(seed) => write!(f, "any:{}", seed),
+            LeaderPolicy::Malicious => write!(f, "malicious"),
+        }
+    }
+}
+
+impl FromStr for LeaderPolicy {
+    type Err = String;
+
+    /// `fixed:ID`, `honest:SEED`, `any:SEED` or `malicious`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid leader policy: {}", s);
+        match s.split_once(':') {
+            None if s == "malicious" => Ok(LeaderPolicy::Malicious),
+            Some(("fixed", id)) => id.parse().map(LeaderPolicy::Fixed).map_err(|_| invalid()),
+            Some(("honest", seed)) => seed
+                .parse()
+                .map(LeaderPolicy::RandomHonest)
+                .map_err(|_| invalid()),
+            Some(("any", seed)) => seed
+                .parse()
+                .map(LeaderPolicy::RandomAny)
+                .map_err(|_| invalid()),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+/// Bracha broadcast of `VALUE` by `leader`
+fn broadcast(network: &mut Network, _n: usize, leader: NodeId) -> HashMap<NodeId, Output> {
+    let (_, results) = network.bracha_broadcast(VALUE, leader);
+    results
+        .into_iter()
+        .map(|(id, v)| (id, Output::Value(v)))
+        .collect()
+}
+
+/// Common subset of the inputs of all the nodes, `VALUE + id` for node `id`
+fn subset(network: &mut Network, n: usize, _leader: NodeId) -> HashMap<NodeId, Output> {
+    let inputs = (0..n).map(|id| (id, VALUE + id)).collect();
+    let (_, results) = network.common_subset(&inputs);
+    results
+}
+
+/// Every honest node delivers the value of the leader
+fn all_deliver(outcome: &ScenarioOutcome) -> bool {
+    let n = outcome.nodes;
+    (0..n - max_faulty(n)).all(|id| outcome.outputs.get(&id) == Some(&Output::Value(VALUE)))
+}
+
+pub const SCENARIOS: [Scenario; 6] = [
+    Scenario {
+        name: "equivocating-leader",
+        description: "Faulty leader sends its value to half of the nodes and another \
+                      value to the others, at every step",
+        expected: "honest nodes never deliver different values",
+        tags: &["fault:byzantine", "protocol:bracha"],
+        setup: |n| {
+            let network = network(n, max_faulty(n), MaliciousKind::Equivocate);
+            (network, LeaderPolicy::Malicious)
+        },
+        protocol: broadcast,
+        check: |outcome| {
+            let honest: BTreeSet<&Output> = outcome
+                .outputs
+                .range(..outcome.nodes - max_faulty(outcome.nodes))
+                .map(|o| o.1)
+                .collect();
+            honest.len() <= 1
+        },
+    },
+    Scenario {
+        name: "split-echo",
+        description: "Faulty nodes ECHO and READY the value of the leader to half of \
+                      the nodes and another value to the others",
+        expected: "every honest node delivers the value of the leader",
+        tags: &["fault:byzantine", "protocol:bracha"],
+        setup: |n| {
+            let network = network(n, max_faulty(n), MaliciousKind::Equivocate);
+            (network, LeaderPolicy::Fixed(0))
+        },
+        protocol: broadcast,
+        check: all_deliver,
+    },
+    Scenario {
+        name: "ready-withholding",
+        description: "Faulty nodes ECHO the value of the leader but never send READY",
+        expected: "every honest node delivers the value of the leader",
+        tags: &["fault:crash", "protocol:bracha"],
+        setup: |n| {
+            let kind = MaliciousKind::Crash(CrashPoint::DuringReady(0));
+            (network(n, max_faulty(n), kind), LeaderPolicy::Fixed(0))
+        },
+        protocol: broadcast,
+        check: all_deliver,
+    },
+    Scenario {
+        name: "late-joiner",
+        description: "Honest last node joins after INIT and half of the ECHOs were \
+                      sent to it, they are lost",
+        expected: "the late node catches up with the READYs and delivers too",
+        tags: &["fault:omission", "protocol:bracha"],
+        setup: |n| {
+            let network = network(n, 0, MaliciousKind::Silent);
+            let late = n - 1;
+            network.controller().drop_next(1 + n / 2, move |msg| {
+                msg.to == late && matches!(msg.phase, Phase::Init | Phase::Echo)
+            });
+            (network, LeaderPolicy::Fixed(0))
+        },
+        protocol: broadcast,
+        check: |outcome| {
+            (0..outcome.nodes).all(|id| outcome.outputs.get(&id) == Some(&Output::Value(VALUE)))
+        },
+    },
+    Scenario {
+        name: "silent-leader",
+        description: "Faulty leader never sends anything, Bracha broadcast has no \
+                      liveness without a leader and honest nodes wait with a timeout",
+        expected: "every honest node gives up after its liveness timeout, none delivers",
+        tags: &["fault:crash", "protocol:bracha"],
+        setup: |n| {
+            let mut network = network(n, max_faulty(n), MaliciousKind::Silent);
+            network.set_broadcast_config(BroadcastConfig {
+                liveness_timeout: Some(LIVENESS_TIMEOUT),
+                ..BroadcastConfig::default()
+            });
+            (network, LeaderPolicy::Malicious)
+        },
+        protocol: broadcast,
+        check: |outcome| {
+            let honest: BTreeSet<NodeId> = (0..outcome.nodes - max_faulty(outcome.nodes)).collect();
+            outcome.outputs.is_empty() && outcome.stalled == honest
+        },
+    },
+    Scenario {
+        name: "silent-senders",
+        description: "Faulty nodes never send anything while every node broadcasts its \
+                      own value and the nodes agree on the broadcasts delivered",
+        expected: "every honest node outputs the values of all the honest nodes, no \
+                   leader to wait for",
+        tags: &["fault:crash", "protocol:common-subset"],
+        setup: |n| {
+            let network = network(n, max_faulty(n), MaliciousKind::Silent);
+            (network, LeaderPolicy::Fixed(0))
+        },
+        protocol: subset,
+        check: |outcome| {
+            let honest: BTreeMap<NodeId, Value> = (0..outcome.nodes - max_faulty(outcome.nodes))
+                .map(|id| (id, VALUE + id))
+                .collect();
+            let set = Output::Set(honest.clone());
+            honest
+                .keys()
+                .all(|id| outcome.outputs.get(id) == Some(&set))
+        },
+    },
+];
+
+/// Scenario named `name`
+pub fn find(name: &str) -> Option<&'static Scenario> {
+    SCENARIOS.iter().find(|scenario| scenario.name == name)
+}
+
+/// Outputs of a scenario run and whether they are the expected ones
+#[derive(Clone, Debug, PartialEq)]
+pub struct ScenarioOutcome {
+    pub scenario: &'static str,
+    pub nodes: usize,
+    pub outputs: BTreeMap<NodeId, Output>,
+    // Nodes that gave up after their liveness timeout
+    pub stalled: BTreeSet<NodeId>,
+    pub passed: bool,
+}
+
+impl Scenario {
+    /// Tags of a run of the scenario with `nodes` nodes
+    pub fn tags_at(&self, nodes: usize) -> Vec<&'static str> {
+        let mut tags = self.tags.to_vec();
+        tags.push(size_class(nodes));
+        tags
+    }
+
+    /// Panics if the leader policy of the scenario has no leader to pick
+    pub fn run(&self, nodes: usize) -> ScenarioOutcome {
+        let (mut network, policy) = (self.setup)(nodes);
+        let leader = policy
+            .leader(&network, 0)
+            .unwrap_or_else(|e| panic!("{}: {}", self.name, e));
+        let results = (self.protocol)(&mut network, nodes, leader);
+        let stalled = network.stalled().clone();
+        network.close();
+        let mut outcome = ScenarioOutcome {
+            scenario: self.name,
+            nodes,
+            outputs: results.into_iter().collect(),
+            stalled,
+            passed: false,
+        };
+        outcome.passed = (self.check)(&outcome);
+        outcome
+    }
+}
+
+impl fmt::Display for ScenarioOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scenario = find(self.scenario).unwrap();
+        writeln!(
+            f,
+            "{} with {} nodes: {}",
+            scenario.name, self.nodes, scenario.description
+        )?;
+        writeln!(f, "expected: {}", scenario.expected)?;
+        writeln!(f, "outputs: {:?}", self.outputs)?;
+        if !self.stalled.is_empty() {
+            writeln!(f, "stalled: {:?}", self.stalled)?;
+        }
+        write!(f, "{}", if self.passed { "PASSED" } else { "FAILED" })
+    }
+}
+
+/// Sizes of the test matrix, every scenario runs at each of them
+pub const SIZES: [(&str, usize); 3] = [("small", 4), ("medium", 10), ("large", 16)];
+
+/// Size class of a run of `nodes` nodes, as the `size:CLASS` tag
+pub fn size_class(nodes: usize) -> &'static str {
+    match nodes {
+        0..=4 => "size:small",
+        5..=10 => "size:medium",
+        _ => "size:large",
+    }
+}
+
+// Tokens of a tag expression, in order
+type Tokens = std::iter::Peekable<std::vec::IntoIter<String>>;
+
+/// Selection of runs by their tags: tags combined with `&`, `|` and `!`,
+/// in that order of precedence from the loosest, and parentheses
+/// `all` selects every run
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TagExpr {
+    All,
+    Tag(String),
+    Not(Box<TagExpr>),
+    And(Box<TagExpr>, Box<TagExpr>),
+    Or(Box<TagExpr>, Box<TagExpr>),
+}
+
+impl TagExpr {
+    pub fn matches(&self, tags: &[&str]) -> bool {
+        match self {
+            TagExpr::All => true,
+            TagExpr::Tag(tag) => tags.contains(&tag.as_str()),
+            TagExpr::Not(expr) => !expr.matches(tags),
+            TagExpr::And(a, b) => a.matches(tags) && b.matches(tags),
+            TagExpr::Or(a, b) => a.matches(tags) || b.matches(tags),
+        }
+    }
+
+    /// Disjunction of conjunctions of terms from `tokens`
+    fn parse_or(tokens: &mut Tokens) -> Result<Self, String> {
+        let mut expr = TagExpr::parse_and(tokens)?;
+        while tokens.next_if(|token| token == "|").is_some() {
+            expr = TagExpr::Or(Box::new(expr), Box::new(TagExpr::parse_and(tokens)?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_and(tokens: &mut Tokens) -> Result<Self, String> {
+        let mut expr = TagExpr::parse_term(tokens)?;
+        while tokens.next_if(|token| token == "&").is_some() {
+            expr = TagExpr::And(Box::new(expr), Box::new(TagExpr::parse_term(tokens)?));
+        }
+        Ok(expr)
+    }
+
+    fn parse_term(tokens: &mut Tokens) -> Result<Self, String> {
+        match tokens.next().as_deref() {
+            Some("!") => Ok(TagExpr::Not(Box::new(TagExpr::parse_term(tokens)?))),
+            Some("(") => {
+                let expr = TagExpr::parse_or(tokens)?;
+                match tokens.next().as_deref() {
+                    Some(")") => Ok(expr),
+                    _ => Err(String::from("Missing )")),
+                }
+            }
+            Some("all") => Ok(TagExpr::All),
+            Some(token) if !"&|)".contains(token) => Ok(TagExpr::Tag(token.to_string())),
+            Some(token) => Err(format!("Unexpected {}", token)),
+            None => Err(String::from("Missing tag")),
+        }
+    }
+}
+
+impl FromStr for TagExpr {
+    type Err = String;
+
+    /// `fault:byzantine & !size:large`, `(protocol:bracha | fault:crash)`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut tokens = vec![];
+        let mut tag = String::new();
+        for c in s.chars() {
+            if c.is_whitespace() || "&|!()".contains(c) {
+                if !tag.is_empty() {
+                    tokens.push(std::mem::take(&mut tag));
+                }
+                if !c.is_whitespace() {
+                    tokens.push(c.to_string());
+                }
+            } else {
+                tag.push(c);
+            }
+        }
+        if !tag.is_empty() {
+            tokens.push(tag);
+        }
+        let mut tokens = tokens.into_iter().peekable();
+        let expr =
+            TagExpr::parse_or(&mut tokens).map_err(|e| format!("Invalid tags {}: {}", s, e))?;
+        match tokens.next() {
+            Some(token) => Err(format!("Invalid tags {}: unexpected {}", s, token)),
+            None => Ok(expr),
+        }
+    }
+}
+
+impl fmt::Display for TagExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TagExpr::All => write!(f, "all"),
+            TagExpr::Tag(tag) => write!(f, "{}", tag),
+            TagExpr::Not(expr) => write!(f, "!{}", expr),
+            TagExpr::And(a, b) => write!(f, "({} & {})", a, b),
+            TagExpr::Or(a, b) => write!(f, "({} | {})", a, b),
+        }
+    }
+}
+
+/// Outcomes of the runs of the scenario library a tag expression selects,
+/// the protocol test matrix of the crate
+#[derive(Clone, Debug, PartialEq)]
+pub struct MatrixReport {
+    pub selection: TagExpr,
+    pub outcomes: Vec<ScenarioOutcome>,
+}
+
+/// Run every scenario at every size of `SIZES` that `selection` matches
+pub fn run_matrix(selection: &TagExpr) -> MatrixReport {
+    let outcomes = SCENARIOS
+        .iter()
+        .flat_map(|scenario| SIZES.iter().map(move |(_, nodes)| (scenario, *nodes)))
+        .filter(|(scenario, nodes)| selection.matches(&scenario.tags_at(*nodes)))
+        .map(|(scenario, nodes)| scenario.run(nodes))
+        .collect();
+    MatrixReport {
+        selection: selection.clone(),
+        outcomes,
+    }
+}
+
+impl MatrixReport {
+    pub fn passed(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.passed)
+            .count()
+    }
+
+    pub fn failed(&self) -> Vec<&ScenarioOutcome> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| !outcome.passed)
+            .collect()
+    }
+
+    /// Runs passed and run of every tag
+    pub fn by_tag(&self) -> BTreeMap<&'static str, (usize, usize)> {
+        let mut tags: BTreeMap<&'static str, (usize, usize)> = BTreeMap::new();
+        for outcome in self.outcomes.iter() {
+            let scenario = find(outcome.scenario).unwrap();
+            for tag in scenario.tags_at(outcome.nodes) {
+                let (passed, run) = tags.entry(tag).or_default();
+                *passed += outcome.passed as usize;
+                *run += 1;
+            }
+        }
+        tags
+    }
+}
+
+impl fmt::Display for MatrixReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Scenarios matching {}", self.selection)?;
+        for outcome in self.outcomes.iter() {
+            let verdict = if outcome.passed { "PASSED" } else { "FAILED" };
+            writeln!(
+                f,
+                "{} {:<20} {} nodes",
+                verdict, outcome.scenario, outcome.nodes
+            )?;
+        }
+        for (tag, (passed, run)) in self.by_tag() {
+            writeln!(f, "{:<24} {}/{}", tag, passed, run)?;
+        }
+        write!(
+            f,
+            "{} passed, {} failed",
+            self.passed(),
+            self.outcomes.len() - self.passed()
+        )
+    }
+}
+
+/// Execution found by hand, steering a lockstep run with the network
+/// controller and injecting faults, saved to be replayed as a regression
+/// test
+/// The file has the manifest of the run, then a `deliver FROM TO MESSAGE`
+/// line per delivery, an `output NODE OUTPUT` line per output and an
+/// `expect ASSERTION` line per assertion the run must satisfy
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordedScenario {
+    pub manifest: Manifest,
+    pub deliveries: Vec<ScriptedDelivery>,
+    // Outputs of the run, as they print
+    pub outputs: BTreeMap<NodeId, String>,
+    pub expectations: Vec<Assertion>,
+}
+
+impl RecordedScenario {
+    /// Last run of `network`, which output `outputs`
+    pub fn record(network: &Network, outputs: &HashMap<NodeId, Output>) -> Self {
+        let trace = network.trace();
+        let mut manifest = trace.manifest.clone();
+        // Kind as `MaliciousKind::from_str` reads it
+        manifest.set("kind", &trace.kind);
+        RecordedScenario {
+            manifest,
+            deliveries: trace
+                .events
+                .iter()
+                .map(|event| ScriptedDelivery {
+                    from: event.from,
+                    to: event.to,
+                    msg: format!("{:?}", event.msg),
+                })
+                .collect(),
+            outputs: outputs
+                .iter()
+                .map(|(id, output)| (*id, format!("{:?}", output)))
+                .collect(),
+            expectations: vec![],
+        }
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut scenario = RecordedScenario {
+            manifest: Manifest::parse(text, "#")?,
+            deliveries: vec![],
+            outputs: BTreeMap::new(),
+            expectations: vec![],
+        };
+        for line in text.lines().filter(|line| !line.starts_with('#')) {
+            let invalid = || format!("Invalid scenario line: {}", line);
+            match line.split_once(' ') {
+                Some(("deliver", delivery)) => scenario.deliveries.push(delivery.parse()?),
+                Some(("output", output)) => {
+                    let (id, output) = output.split_once(' ').ok_or_else(invalid)?;
+                    let id = id.parse().map_err(|_| invalid())?;
+                    scenario.outputs.insert(id, output.to_string());
+                }
+                Some(("expect", assertion)) => scenario.expectations.push(assertion.parse()?),
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(scenario)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        RecordedScenario::parse(&text)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        fs::write(path, self.to_string()).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Network set up like the recorded one, delivering the messages in
+    /// the recorded order
+    /// Settings the manifest doesn't record, like the protocol
+    /// configuration, are left to the caller
+    pub fn network(&self) -> Result<Network, String> {
+        let get = |key: &str| {
+            self.manifest
+                .get(key)
+                .ok_or_else(|| format!("Scenario has no {}", key))
+        };
+        let number = |key: &str| -> Result<usize, String> {
+            get(key)?
+                .parse()
+                .map_err(|_| format!("Invalid {}: {}", key, get(key).unwrap()))
+        };
+        let seed = self
+            .manifest
+            .seed
+            .ok_or_else(|| String::from("Only seeded runs can be replayed"))?;
+        let fault_model = match self.manifest.get("fault_model") {
+            Some(model) => model.parse()?,
+            None => FaultModel::Byzantine,
+        };
+        let mut network = Network::with_fault_model(
+            number("num_nodes")?,
+            number("num_malicious")?,
+            get("kind")?.parse()?,
+            RngSource::Seeded(seed),
+            fault_model,
+        );
+        if let Some(faults) = self.manifest.get("fault_schedules") {
+            for fault in faults.split(" | ") {
+                let invalid = || format!("Invalid fault schedule: {}", fault);
+                let (id, schedule) = fault.split_once(' ').ok_or_else(invalid)?;
+                let id = id.parse().map_err(|_| invalid())?;
+                network.set_fault_schedule(id, schedule.parse::<FaultSchedule>()?);
+            }
+        }
+        if let Some(topology) = self.manifest.get("topology") {
+            network.set_topology(topology.parse()?);
+        }
+        network.set_routing(self.manifest.get("routing") == Some("true"));
+        if let Some(after) = self.manifest.get("after_delivery") {
+            network.set_after_delivery(after.parse()?);
+        }
+        network.set_schedule(Schedule::Script(self.deliveries.clone()));
+        Ok(network)
+    }
+
+    /// Run `protocol` again on the recorded network, it must deliver the
+    /// same messages in the same order, output the same values and satisfy
+    /// the expectations
+    /// `protocol` is the same one with the same inputs as in the recording
+    pub fn replay<P>(&self, protocol: P) -> Result<(), String>
+    where
+        P: FnOnce(&mut Network) -> HashMap<NodeId, Output>,
+    {
+        let mut network = self.network()?;
+        let outputs = protocol(&mut network);
+        let replayed = RecordedScenario::record(&network, &outputs);
+        let failed: Vec<String> = self
+            .expectations
+            .iter()
+            .filter(|assertion| !assertion.holds(network.trace()))
+            .map(Assertion::to_string)
+            .collect();
+        network.close();
+
+        let diverged = self
+            .deliveries
+            .iter()
+            .zip(replayed.deliveries.iter())
+            .position(|(a, b)| a != b);
+        if let Some(i) = diverged {
+            return Err(format!(
+                "Delivery {} is {} instead of {}",
+                i, replayed.deliveries[i], self.deliveries[i]
+            ));
+        }
+        if replayed.deliveries.len() != self.deliveries.len() {
+            return Err(format!(
+                "Run delivered {} messages instead of {}",
+                replayed.deliveries.len(),
+                self.deliveries.len()
+            ));
+        }
+        if replayed.outputs != self.outputs {
+            return Err(format!(
+                "Nodes output {:?} instead of {:?}",
+                replayed.outputs, self.outputs
+            ));
+        }
+        if !failed.is_empty() {
+            return Err(format!("Run fails {}", failed.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RecordedScenario {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.manifest.header("#"))?;
+        for delivery in self.deliveries.iter() {
+            writeln!(f, "deliver {}", delivery)?;
+        }
+        for (id, output) in self.outputs.iter() {
+            writeln!(f, "output {} {}", id, output)?;
+        }
+        for assertion in self.expectations.iter() {
+            writeln!(f, "expect {}", assertion)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use crate::network::NetworkMessage;
+use crate::node::NodeId;
+use crate::rng::Rng;
+use crate::trace::Trace;
+use log::warn;
+use std::fmt;
+use std::str::FromStr;
+
+/// Delivery of a scenario file, messages are told apart by how they print
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptedDelivery {
+    pub from: NodeId,
+    pub to: NodeId,
+    pub msg: String,
+}
+
+impl ScriptedDelivery {
+    fn matches(&self, msg: &NetworkMessage) -> bool {
+        self.from == msg.from && self.to == msg.to && self.msg == format!("{:?}", msg.msg)
+    }
+}
+
+/// `FROM TO MESSAGE`
+impl fmt::Display for ScriptedDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.from, self.to, self.msg)
+    }
+}
+
+impl FromStr for ScriptedDelivery {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid delivery: {}", s);
+        let mut fields = s.splitn(3, ' ');
+        let mut node = || -> Result<NodeId, String> {
+            fields
+                .next()
+                .and_then(|id| id.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let (from, to) = (node()?, node()?);
+        let msg = fields.next().ok_or_else(invalid)?.to_string();
+        Ok(ScriptedDelivery { from, to, msg })
+    }
+}
+
+/// How the network orders the deliveries of messages
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    // Relay messages as soon as they arrive, node threads race each other
+    Concurrent,
+    // Deliver one message at a time to a node picked at random among the
+    // nodes with messages in flight, oldest message first: the run only
+    // depends on the randomness of the network and of the nodes
+    Deterministic,
+    // Deliver messages in the order of a recorded run
+    Replay(Trace),
+    // Deliver messages in the order of a scenario file
+    Script(Vec<ScriptedDelivery>),
+    // Replay a recorded run without one of its deliveries, once the run
+    // diverges from the recording the oldest message is delivered first
+    ReplayWithout {
+        trace: Trace,
+        omitted: usize,
+        dropped: bool,
+        diverged: bool,
+    },
+    // Deliver the messages of a snapshot in their recorded order, then
+    // continue like the deterministic schedule
+    Branch {
+        snapshot: Trace,
+    },
+}
+
+impl Schedule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Schedule::Concurrent => "concurrent",
+            Schedule::Deterministic => "deterministic",
+            Schedule::Replay(_) => "replay",
+            Schedule::Script(_) => "script",
+            Schedule::ReplayWithout { .. } => "replay-without",
+            Schedule::Branch { .. } => "branch",
+        }
+    }
+
+    /// Nodes process one message at a time and acknowledge it
+    pub(crate) fn is_lockstep(&self) -> bool {
+        !matches!(self, Schedule::Concurrent)
+    }
+
+    /// Pick the next message to deliver among the messages in flight
+    /// `delivered` is the number of messages delivered so far in the run,
+    /// messages the schedule leaves out go to `omitted`
+    pub(crate) fn next(
+        &mut self,
+        pending: &mut Vec<NetworkMessage>,
+        delivered: usize,
+        rng: &mut dyn Rng,
+        omitted_msgs: &mut Vec<NetworkMessage>,
+    ) -> Option<NetworkMessage> {
+        let index = match self {
+            Schedule::Concurrent => None,
+
+            Schedule::Branch { snapshot } if delivered < snapshot.len() => {
+                let event = &snapshot.events[delivered];
+                let index = pending.iter().position(|msg| event.matches(msg));
+                if index.is_none() {
+                    warn!("Restoring the snapshot diverged at delivery {}", delivered);
+                }
+                index
+            }
+
+            Schedule::Deterministic | Schedule::Branch { .. } => {
+                // Choosing the node rather than the message keeps nodes
+                // flooded by malicious ones from starving the others
+                let mut destinations = vec![];
+                for msg in pending.iter() {
+                    if !destinations.contains(&msg.to) {
+                        destinations.push(msg.to);
+                    }
+                }
+                if destinations.is_empty() {
+                    None
+                } else {
+                    let to = destinations[rng.gen_range(destinations.len())];
+                    pending.iter().position(|msg| msg.to == to)
+                }
+            }
+
+            Schedule::Replay(trace) => {
+                let event = trace.events.get(delivered)?;
+                let index = pending.iter().position(|msg| event.matches(msg));
+                if index.is_none() {
+                    warn!("Replay diverged from the trace at delivery {}", delivered);
+                }
+                index
+            }
+
+            Schedule::Script(deliveries) => {
+                let delivery = deliveries.get(delivered)?;
+                let index = pending.iter().position(|msg| delivery.matches(msg));
+                if index.is_none() {
+                    warn!("Script diverged at delivery {}: {}", delivered, delivery);
+                }
+                index
+            }
+
+            Schedule::ReplayWithout {
+                trace,
+                omitted,
+                dropped,
+                diverged,
+            } => {
+                if !*dropped && delivered == *omitted {
+                    // Lose the omitted message as soon as it's in flight
+                    if let Some(index) = trace
+                        .events
+                        .get(*omitted)
+                        .and_then(|event| pending.iter().position(|msg| event.matches(msg)))
+                    {
+                        omitted_msgs.push(pending.remove(index));
+                    }
+                    *dropped = true;
+                }
+
+                let mut index = None;
+                if !*diverged {
+                    let event = trace.events.get(delivered + *dropped as usize);
+                    index =
+                        event.and_then(|event| pending.iter().position(|msg| event.matches(msg)));
+                    *diverged = index.is_none();
+                }
+                if index.is_none() && !pending.is_empty() {
+                    index = Some(0);
+                }
+                index
+            }
+        };
+        index.map(|index| pending.remove(index))
+    }
+}
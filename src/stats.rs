@@ -0,0 +1,434 @@
+use crate::manifest::Manifest;
+use crate::network::Output;
+use crate::node::NodeId;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Protocol phase a message belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Phase {
+    Leader,
+    Init,
+    Echo,
+    Ready,
+    // Votes of binary agreement
+    Vote,
+    // Messages exchanged between the nodes and the network
+    Control,
+}
+
+/// Protocol points reached by every node, their time is recorded per node
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Milestone {
+    FirstEcho,
+    FirstReady,
+    Delivered,
+    // Node gave up the broadcast, nothing moved for its liveness timeout
+    Stalled,
+}
+
+impl Milestone {
+    /// Milestone reached by a node sending its first message of `phase`
+    fn sent(phase: Phase) -> Option<Self> {
+        match phase {
+            Phase::Echo => Some(Milestone::FirstEcho),
+            Phase::Ready => Some(Milestone::FirstReady),
+            _ => None,
+        }
+    }
+}
+
+/// Times at which the nodes reached a milestone, fastest node first
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LatencyDistribution {
+    pub samples: Vec<(NodeId, Duration)>,
+}
+
+impl LatencyDistribution {
+    fn new(times: &BTreeMap<NodeId, Duration>) -> Self {
+        let mut samples: Vec<(NodeId, Duration)> =
+            times.iter().map(|(id, at)| (*id, *at)).collect();
+        samples.sort_by_key(|(id, at)| (*at, *id));
+        LatencyDistribution { samples }
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn min(&self) -> Duration {
+        self.samples.first().map_or(Duration::ZERO, |(_, at)| *at)
+    }
+
+    pub fn max(&self) -> Duration {
+        self.samples.last().map_or(Duration::ZERO, |(_, at)| *at)
+    }
+
+    /// Time by which `p` percent of the nodes had reached the milestone
+    pub fn percentile(&self, p: f64) -> Duration {
+        if self.samples.is_empty() {
+            return Duration::ZERO;
+        }
+        let rank = (p / 100.0 * self.samples.len() as f64).ceil() as usize;
+        self.samples[rank.clamp(1, self.samples.len()) - 1].1
+    }
+
+    /// Last node to reach the milestone
+    pub fn slowest(&self) -> Option<NodeId> {
+        self.samples.last().map(|(id, _)| *id)
+    }
+
+    /// Number of nodes per interval, `buckets` intervals of the same width
+    /// between the fastest and the slowest node
+    pub fn histogram(&self, buckets: usize) -> Vec<(Duration, usize)> {
+        let buckets = buckets.max(1);
+        let width = (self.max() - self.min()) / buckets as u32;
+        let mut counts = vec![0; buckets];
+        for (_, at) in self.samples.iter() {
+            let bucket = match width.as_nanos() {
+                0 => 0,
+                width => ((*at - self.min()).as_nanos() / width) as usize,
+            };
+            counts[bucket.min(buckets - 1)] += 1;
+        }
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| (self.min() + width * i as u32, count))
+            .collect()
+    }
+}
+
+/// Percentiles then one bar per interval of the histogram
+impl fmt::Display for LatencyDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "nodes {} min {:?} p50 {:?} p90 {:?} p99 {:?} max {:?}",
+            self.len(),
+            self.min(),
+            self.percentile(50.0),
+            self.percentile(90.0),
+            self.percentile(99.0),
+            self.max()
+        )?;
+        for (from, count) in self.histogram(10) {
+            writeln!(f, "{:>12?} {}", from, "#".repeat(count))?;
+        }
+        Ok(())
+    }
+}
+
+/// Traffic of one phase
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PhaseStats {
+    pub messages: usize,
+    pub bytes: usize,
+    // First and last message of the phase, relative to the start of the run
+    pub first: Option<Duration>,
+    pub last: Option<Duration>,
+}
+
+impl PhaseStats {
+    /// Time between the first and the last message of the phase
+    pub fn duration(&self) -> Duration {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::ZERO,
+        }
+    }
+}
+
+/// Per-phase breakdown of the traffic of a run
+#[derive(Clone, Debug)]
+pub struct NetworkStats {
+    start: Instant,
+    phases: BTreeMap<Phase, PhaseStats>,
+    // Time at which each node reached each milestone
+    milestones: BTreeMap<Milestone, BTreeMap<NodeId, Duration>>,
+    // Time the messages of each node waited for its capped uplink
+    queued: BTreeMap<NodeId, Duration>,
+    // Time each node spent handling messages, with a CPU model
+    busy: BTreeMap<NodeId, Duration>,
+}
+
+impl Default for NetworkStats {
+    fn default() -> Self {
+        NetworkStats::new()
+    }
+}
+
+impl NetworkStats {
+    pub fn new() -> Self {
+        NetworkStats {
+            start: Instant::now(),
+            phases: BTreeMap::new(),
+            milestones: BTreeMap::new(),
+            queued: BTreeMap::new(),
+            busy: BTreeMap::new(),
+        }
+    }
+
+    /// Record a message of `bytes` bytes sent during `phase`
+    pub(crate) fn record(&mut self, phase: Phase, bytes: usize) {
+        let elapsed = self.start.elapsed();
+        let stats = self.phases.entry(phase).or_default();
+        stats.messages += 1;
+        stats.bytes += bytes;
+        stats.first.get_or_insert(elapsed);
+        stats.last = Some(elapsed);
+    }
+
+    /// Record that `node` sent a message of `phase`
+    pub(crate) fn record_sent(&mut self, node: NodeId, phase: Phase) {
+        if let Some(milestone) = Milestone::sent(phase) {
+            self.reach(node, milestone);
+        }
+    }
+
+    /// Record that a message of `node` waited `delay` for its uplink
+    pub(crate) fn record_queued(&mut self, node: NodeId, delay: Duration) {
+        *self.queued.entry(node).or_default() += delay;
+    }
+
+    /// Total time the messages of each node waited for its uplink, only
+    /// nodes with a capped uplink appear
+    pub fn queued(&self) -> &BTreeMap<NodeId, Duration> {
+        &self.queued
+    }
+
+    /// Record that `node` spent `cost` handling a message
+    pub(crate) fn record_busy(&mut self, node: NodeId, cost: Duration) {
+        *self.busy.entry(node).or_default() += cost;
+    }
+
+    /// Total time each node spent handling messages, only with a CPU model
+    pub fn busy(&self) -> &BTreeMap<NodeId, Duration> {
+        &self.busy
+    }
+
+    /// Record that `node` reached `milestone`, only the first time counts
+    pub(crate) fn reach(&mut self, node: NodeId, milestone: Milestone) {
+        let elapsed = self.start.elapsed();
+        self.milestones
+            .entry(milestone)
+            .or_default()
+            .entry(node)
+            .or_insert(elapsed);
+    }
+
+    /// Time at which each node reached `milestone`
+    pub fn milestone(&self, milestone: Milestone) -> Option<&BTreeMap<NodeId, Duration>> {
+        self.milestones.get(&milestone)
+    }
+
+    /// Distribution of the times at which nodes reached `milestone`
+    pub fn latency(&self, milestone: Milestone) -> Option<LatencyDistribution> {
+        self.milestones
+            .get(&milestone)
+            .map(LatencyDistribution::new)
+    }
+
+    /// Time at which each node reached each milestone as CSV, after the
+    /// manifest of the run
+    pub fn milestones_csv(&self, manifest: &Manifest) -> String {
+        let mut csv = manifest.header("#");
+        csv.push_str("milestone,node,time_us\n");
+        for (milestone, times) in self.milestones.iter() {
+            for (node, at) in times.iter() {
+                csv.push_str(&format!("{:?},{},{}\n", milestone, node, at.as_micros()));
+            }
+        }
+        csv
+    }
+
+    /// Time since the start of the run
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    pub fn phase(&self, phase: Phase) -> Option<&PhaseStats> {
+        self.phases.get(&phase)
+    }
+
+    pub fn phases(&self) -> impl Iterator<Item = (&Phase, &PhaseStats)> {
+        self.phases.iter()
+    }
+
+    pub fn total_messages(&self) -> usize {
+        self.phases.values().map(|stats| stats.messages).sum()
+    }
+
+    pub fn total_bytes(&self) -> usize {
+        self.phases.values().map(|stats| stats.bytes).sum()
+    }
+
+    /// Traffic per phase as CSV, after the manifest of the run
+    pub fn csv(&self, manifest: &Manifest) -> String {
+        let mut csv = manifest.header("#");
+        csv.push_str("phase,messages,bytes,duration_us\n");
+        for (phase, stats) in self.phases.iter() {
+            csv.push_str(&format!(
+                "{:?},{},{},{}\n",
+                phase,
+                stats.messages,
+                stats.bytes,
+                stats.duration().as_micros()
+            ));
+        }
+        csv
+    }
+}
+
+impl fmt::Display for NetworkStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<10} {:>10} {:>10} {:>12}",
+            "phase", "messages", "bytes", "duration"
+        )?;
+        for (phase, stats) in self.phases.iter() {
+            writeln!(
+                f,
+                "{:<10} {:>10} {:>10} {:>12?}",
+                format!("{:?}", phase),
+                stats.messages,
+                stats.bytes,
+                stats.duration()
+            )?;
+        }
+        write!(
+            f,
+            "{:<10} {:>10} {:>10}",
+            "total",
+            self.total_messages(),
+            self.total_bytes()
+        )
+    }
+}
+
+/// Traffic and outputs of the nodes of one behaviour class during a run
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClassStats {
+    pub nodes: usize,
+    // Messages sent to other nodes
+    pub sent: usize,
+    // Messages sent by nodes that had heard from a malicious node, directly
+    // or not
+    pub influenced: usize,
+    // Number of nodes of the class delivering each output
+    pub delivered: BTreeMap<Output, usize>,
+}
+
+/// Breakdown of a run by behaviour class, to quantify the impact of an
+/// attack
+/// In concurrent schedules a node counts as influenced as soon as a
+/// malicious message is delivered to its queue, before it processes it
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BehaviourStats {
+    pub honest: ClassStats,
+    pub malicious: ClassStats,
+}
+
+impl BehaviourStats {
+    pub(crate) fn new(honest: usize, malicious: usize) -> Self {
+        BehaviourStats {
+            honest: ClassStats {
+                nodes: honest,
+                ..ClassStats::default()
+            },
+            malicious: ClassStats {
+                nodes: malicious,
+                ..ClassStats::default()
+            },
+        }
+    }
+
+    pub(crate) fn class(&mut self, malicious: bool) -> &mut ClassStats {
+        if malicious {
+            &mut self.malicious
+        } else {
+            &mut self.honest
+        }
+    }
+
+    /// Share of the honest messages influenced by malicious nodes
+    pub fn influence(&self) -> f64 {
+        if self.honest.sent == 0 {
+            return 0.0;
+        }
+        self.honest.influenced as f64 / self.honest.sent as f64
+    }
+}
+
+impl fmt::Display for ClassStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} nodes, {} messages sent, {} influenced, delivered {:?}",
+            self.nodes, self.sent, self.influenced, self.delivered
+        )
+    }
+}
+
+impl fmt::Display for BehaviourStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "honest: {}", self.honest)?;
+        write!(f, "malicious: {}", self.malicious)
+    }
+}
+
+/// Cost of flooding the values of the nodes over a sparse topology
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FloodReport {
+    // Copies delivered, first ones and duplicates
+    pub copies: usize,
+    // Links crossed by the first copy from each origin to each node, and
+    // the length of the shortest path between them, by origin and node
+    pub first: BTreeMap<(NodeId, NodeId), (usize, usize)>,
+    // Pairs of distinct nodes able to reach each other
+    pub pairs: usize,
+}
+
+impl FloodReport {
+    /// Share of the pairs of nodes where the value went through
+    pub fn coverage(&self) -> f64 {
+        self.first.len() as f64 / self.pairs.max(1) as f64
+    }
+
+    /// Copies delivered per useful copy, 1 when no node receives a value
+    /// twice
+    pub fn redundancy(&self) -> f64 {
+        self.copies as f64 / self.first.len().max(1) as f64
+    }
+
+    /// Mean ratio between the links crossed by the first copy and the
+    /// shortest path, 1 when values always take a shortest path
+    pub fn stretch(&self) -> f64 {
+        let ratios: Vec<f64> = self
+            .first
+            .values()
+            .filter(|(_, distance)| *distance > 0)
+            .map(|(hops, distance)| *hops as f64 / *distance as f64)
+            .collect();
+        ratios.iter().sum::<f64>() / ratios.len().max(1) as f64
+    }
+}
+
+impl fmt::Display for FloodReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} copies, coverage {:.2}, redundancy {:.2}, stretch {:.2}",
+            self.copies,
+            self.coverage(),
+            self.redundancy(),
+            self.stretch()
+        )
+    }
+}
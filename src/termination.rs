@@ -0,0 +1,80 @@
+use crate::network::Output;
+use crate::node::NodeId;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+/// State of a run the termination policy looks at, after every event
+#[derive(Debug)]
+pub struct RunProgress<'a> {
+    // Outputs of the nodes so far, malicious ones included
+    pub results: &'a HashMap<NodeId, Output>,
+    pub good_nodes: &'a [NodeId],
+    // Good nodes that have neither output nor crashed
+    pub running: usize,
+    pub elapsed: Duration,
+    // Messages sent and not delivered yet, timers included
+    pub in_flight: usize,
+}
+
+impl RunProgress<'_> {
+    /// Number of good nodes that delivered a value, stalled ones left out
+    pub fn delivered(&self) -> usize {
+        self.good_nodes
+            .iter()
+            .filter_map(|id| self.results.get(id))
+            .filter(|output| **output != Output::Stalled)
+            .count()
+    }
+}
+
+/// When a run ends, it always ends once no good node is running and when
+/// its time limit is reached
+/// Nodes still running when the run ends are left idle until the next run
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Termination {
+    // Every good node has output or crashed
+    #[default]
+    AllGood,
+    // This many good nodes delivered
+    Quorum(usize),
+    // Nothing was sent for this long and no message is in transit, the
+    // nodes left are waiting for messages that will never come
+    // Only applies to the concurrent schedule, lockstep runs already end
+    // as soon as no message can be delivered
+    Idle(Duration),
+    // Predicate over the progress of the run
+    When(fn(&RunProgress) -> bool),
+}
+
+impl Termination {
+    /// Whether the run is over
+    /// Idleness is detected by the network, it doesn't show in `progress`
+    pub fn ended(&self, progress: &RunProgress) -> bool {
+        progress.running == 0
+            || match self {
+                Termination::AllGood | Termination::Idle(_) => false,
+                Termination::Quorum(quorum) => progress.delivered() >= *quorum,
+                Termination::When(predicate) => predicate(progress),
+            }
+    }
+
+    /// Time without activity after which the run ends
+    pub fn idle(&self) -> Option<Duration> {
+        match self {
+            Termination::Idle(idle) => Some(*idle),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Termination::AllGood => write!(f, "all-good"),
+            Termination::Quorum(quorum) => write!(f, "quorum:{}", quorum),
+            Termination::Idle(idle) => write!(f, "idle:{:?}", idle),
+            Termination::When(_) => write!(f, "predicate"),
+        }
+    }
+}
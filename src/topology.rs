@@ -0,0 +1,186 @@
+use crate::node::NodeId;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+
+/// Links between the nodes, nodes only send messages to the nodes they are
+/// linked to
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Topology {
+    // Every pair of nodes is linked
+    #[default]
+    Complete,
+    // Node `i` is linked to `i - 1` and `i + 1` modulo the number of nodes
+    Ring,
+    // Rows of `width` nodes, each node is linked to the nodes above, below,
+    // left and right of it
+    Grid(usize),
+}
+
+impl Topology {
+    /// Nodes linked to `id` in a network of `num_nodes` nodes, in
+    /// increasing order
+    pub fn neighbours(&self, id: NodeId, num_nodes: usize) -> Vec<NodeId> {
+        (0..num_nodes)
+            .filter(|other| self.linked(id, *other, num_nodes))
+            .collect()
+    }
+
+    pub fn linked(&self, a: NodeId, b: NodeId, num_nodes: usize) -> bool {
+        if a == b || a >= num_nodes || b >= num_nodes {
+            return false;
+        }
+        match self {
+            Topology::Complete => true,
+            Topology::Ring => (a + 1) % num_nodes == b || (b + 1) % num_nodes == a,
+            Topology::Grid(width) => {
+                let (row, col) = (a / width, a % width);
+                let (other_row, other_col) = (b / width, b % width);
+                (row == other_row && col.abs_diff(other_col) == 1)
+                    || (col == other_col && row.abs_diff(other_row) == 1)
+            }
+        }
+    }
+
+    /// Number of links on the shortest path from `from` to every node, None
+    /// for the nodes it can't reach
+    pub fn distances(&self, from: NodeId, num_nodes: usize) -> Vec<Option<usize>> {
+        let mut distances = vec![None; num_nodes];
+        let mut queue = VecDeque::new();
+        if from < num_nodes {
+            distances[from] = Some(0);
+            queue.push_back(from);
+        }
+        while let Some(id) = queue.pop_front() {
+            let next = distances[id].map(|d| d + 1);
+            for other in self.neighbours(id, num_nodes) {
+                if distances[other].is_none() {
+                    distances[other] = next;
+                    queue.push_back(other);
+                }
+            }
+        }
+        distances
+    }
+
+    /// Routing table of `from`: next node on a shortest path to every node,
+    /// None for `from` itself and the nodes it can't reach
+    /// Ties go to the lowest neighbour, so every run takes the same paths
+    pub fn routes(&self, from: NodeId, num_nodes: usize) -> Vec<Option<NodeId>> {
+        let mut routes = vec![None; num_nodes];
+        let mut queue = VecDeque::new();
+        for id in self.neighbours(from, num_nodes) {
+            routes[id] = Some(id);
+            queue.push_back(id);
+        }
+        while let Some(id) = queue.pop_front() {
+            for other in self.neighbours(id, num_nodes) {
+                if other != from && routes[other].is_none() {
+                    routes[other] = routes[id];
+                    queue.push_back(other);
+                }
+            }
+        }
+        routes
+    }
+
+    /// Least number of nodes whose removal disconnects the others, one
+    /// less than the number of nodes when every pair is linked
+    pub fn connectivity(&self, num_nodes: usize) -> usize {
+        let links: Vec<Vec<NodeId>> = (0..num_nodes)
+            .map(|id| self.neighbours(id, num_nodes))
+            .collect();
+        let mut connectivity = num_nodes.saturating_sub(1);
+        for a in 0..num_nodes {
+            for b in a + 1..num_nodes {
+                if !links[a].contains(&b) {
+                    connectivity = connectivity.min(disjoint_paths(&links, a, b));
+                }
+            }
+        }
+        connectivity
+    }
+}
+
+/// Number of paths from `from` to `to` that share no node but their ends,
+/// as the maximum flow once every node is split in an entry and an exit
+/// of capacity 1
+fn disjoint_paths(links: &[Vec<NodeId>], from: NodeId, to: NodeId) -> usize {
+    let n = links.len();
+    // Entry of node `i` is `2 * i`, its exit `2 * i + 1`
+    let mut capacity: HashMap<(usize, usize), i32> = HashMap::new();
+    for (id, neighbours) in links.iter().enumerate() {
+        let inner = if id == from || id == to { n as i32 } else { 1 };
+        capacity.insert((2 * id, 2 * id + 1), inner);
+        for other in neighbours {
+            capacity.insert((2 * id + 1, 2 * other), 1);
+        }
+    }
+    let (source, sink) = (2 * from + 1, 2 * to);
+    let mut paths = 0;
+    loop {
+        // Shortest augmenting path in the residual graph
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut queue = VecDeque::from([source]);
+        while let Some(v) = queue.pop_front() {
+            if v == sink {
+                break;
+            }
+            let id = v / 2;
+            let mut next = vec![v ^ 1];
+            for other in links[id].iter() {
+                // Out of the exit of `id`, or back into its entry
+                next.push(if v % 2 == 1 { 2 * other } else { 2 * other + 1 });
+            }
+            for w in next {
+                let residual = capacity.get(&(v, w)).cloned().unwrap_or(0);
+                if residual > 0 && w != source && !previous.contains_key(&w) {
+                    previous.insert(w, v);
+                    queue.push_back(w);
+                }
+            }
+        }
+        if !previous.contains_key(&sink) {
+            return paths;
+        }
+        let mut w = sink;
+        while w != source {
+            let v = previous[&w];
+            *capacity.entry((v, w)).or_default() -= 1;
+            *capacity.entry((w, v)).or_default() += 1;
+            w = v;
+        }
+        paths += 1;
+    }
+}
+
+impl FromStr for Topology {
+    type Err = String;
+
+    /// `complete`, `ring` or `grid:WIDTH`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, arg) = match s.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (s, None),
+        };
+        match (name.to_lowercase().as_str(), arg) {
+            ("complete", None) => Ok(Topology::Complete),
+            ("ring", None) => Ok(Topology::Ring),
+            ("grid", Some(width)) => match width.parse() {
+                Ok(width) if width > 0 => Ok(Topology::Grid(width)),
+                _ => Err(format!("Invalid grid width: {}", width)),
+            },
+            _ => Err(format!("Unknown topology: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Topology::Complete => write!(f, "complete"),
+            Topology::Ring => write!(f, "ring"),
+            Topology::Grid(width) => write!(f, "grid:{}", width),
+        }
+    }
+}
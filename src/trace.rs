@@ -0,0 +1,140 @@
+use crate::manifest::Manifest;
+use crate::network::*;
+use crate::node::{MaliciousKind, NodeId};
+use crate::rng::Randomness;
+use std::time::Duration;
+
+/// Message delivered to a node during a run
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct TraceEvent {
+    // Delivery time relative to the start of the run
+    pub at: Duration,
+    // Time the network got the message from its sender, and how long it
+    // then waited for the uplink of the sender
+    pub sent: Duration,
+    pub queued: Duration,
+    pub from: NodeId,
+    pub to: NodeId,
+    pub msg: Message,
+}
+
+impl TraceEvent {
+    /// Whether `msg` is the message delivered by this event
+    pub(crate) fn matches(&self, msg: &NetworkMessage) -> bool {
+        self.from == msg.from && self.to == msg.to && self.msg == msg.msg
+    }
+}
+
+/// Recording of a run: configuration of the network, order of the
+/// deliveries and random draws of the nodes and the scheduler
+#[derive(Clone, Debug)]
+pub struct Trace {
+    pub num_nodes: usize,
+    pub num_malicious: usize,
+    pub kind: MaliciousKind,
+    pub(crate) events: Vec<TraceEvent>,
+    // Outputs of the nodes with the number of deliveries before them
+    pub(crate) outputs: Vec<(usize, NodeId, Output)>,
+    pub randomness: Randomness,
+    // Build and configuration of the recorded run
+    pub manifest: Manifest,
+}
+
+impl Trace {
+    pub(crate) fn new(num_nodes: usize, num_malicious: usize, kind: MaliciousKind) -> Self {
+        Trace {
+            num_nodes,
+            num_malicious,
+            kind,
+            events: vec![],
+            outputs: vec![],
+            randomness: Randomness::default(),
+            manifest: Manifest::new(),
+        }
+    }
+
+    pub(crate) fn record(
+        &mut self,
+        at: Duration,
+        departure: (Duration, Duration),
+        msg: &NetworkMessage,
+    ) {
+        let (sent, queued) = departure;
+        self.events.push(TraceEvent {
+            at,
+            sent,
+            queued,
+            from: msg.from,
+            to: msg.to,
+            msg: msg.msg.clone(),
+        });
+    }
+
+    pub(crate) fn output(&mut self, node: NodeId, output: &Output) {
+        self.outputs.push((self.len(), node, output.clone()));
+    }
+
+    /// First `deliveries` deliveries of the run
+    pub(crate) fn prefix(&self, deliveries: usize) -> Trace {
+        Trace {
+            events: self.events[..deliveries.min(self.len())].to_vec(),
+            outputs: self
+                .outputs
+                .iter()
+                .filter(|(after, _, _)| *after <= deliveries)
+                .cloned()
+                .collect(),
+            ..self.clone()
+        }
+    }
+
+    /// Number of deliveries
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Fingerprint of the deliveries and the outputs of the run, in their
+    /// order, timings left out
+    /// Same across builds and platforms, runs of a deterministic schedule
+    /// only change digest when the behaviour of the protocols changes
+    pub fn digest(&self) -> u64 {
+        let mut digest = Fnv::new();
+        for event in self.events.iter() {
+            digest.write(&format!("{} {} {:?}\n", event.from, event.to, event.msg));
+        }
+        for (after, node, output) in self.outputs.iter() {
+            digest.write(&format!("{} {} {:?}\n", after, node, output));
+        }
+        digest.0
+    }
+
+    /// Whether both runs delivered the same messages in the same order
+    pub fn same_deliveries(&self, other: &Trace) -> bool {
+        self.len() == other.len()
+            && self
+                .events
+                .iter()
+                .zip(other.events.iter())
+                .all(|(a, b)| a.from == b.from && a.to == b.to && a.msg == b.msg)
+    }
+}
+
+/// 64 bit FNV-1a, unlike the hashers of the standard library its output is
+/// specified and never changes
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, s: &str) {
+        for byte in s.bytes() {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
@@ -0,0 +1,121 @@
+use crate::network::Value;
+use crate::node::NodeId;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// External validity of the values the nodes agree on, as every node sees
+/// it: honest nodes may disagree, such as when only some of them have the
+/// data to check a payload
+pub trait ValidityOracle: fmt::Display + Send + Sync {
+    /// Whether `node` accepts `v`
+    fn is_valid(&self, node: NodeId, v: Value) -> bool;
+
+    /// Whether every node accepts every value, no broadcast is then
+    /// rejected
+    fn accepts_all(&self) -> bool {
+        false
+    }
+}
+
+/// Every node accepts every value
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AnyValue;
+
+impl ValidityOracle for AnyValue {
+    fn is_valid(&self, _node: NodeId, _v: Value) -> bool {
+        true
+    }
+
+    fn accepts_all(&self) -> bool {
+        true
+    }
+}
+
+impl fmt::Display for AnyValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "any value")
+    }
+}
+
+/// Nodes reject the values whose data they miss and accept the others
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MissingData {
+    pub missing: BTreeMap<NodeId, BTreeSet<Value>>,
+}
+
+impl MissingData {
+    /// Data of `v` is missing at every node of `nodes`
+    pub fn withhold(mut self, v: Value, nodes: impl IntoIterator<Item = NodeId>) -> Self {
+        for node in nodes {
+            self.missing.entry(node).or_default().insert(v);
+        }
+        self
+    }
+}
+
+impl ValidityOracle for MissingData {
+    fn is_valid(&self, node: NodeId, v: Value) -> bool {
+        self.missing
+            .get(&node)
+            .is_none_or(|missing| !missing.contains(&v))
+    }
+}
+
+impl fmt::Display for MissingData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let views: Vec<String> = self
+            .missing
+            .iter()
+            .map(|(node, missing)| {
+                let values: Vec<String> = missing.iter().map(Value::to_string).collect();
+                format!("{}: {}", node, values.join(" "))
+            })
+            .collect();
+        write!(f, "missing data, {}", views.join(" | "))
+    }
+}
+
+/// Every node checks the values with the same predicate, as the external
+/// validity of agreements usually goes
+#[derive(Clone)]
+pub struct Predicate(pub Arc<dyn Fn(Value) -> bool + Send + Sync>);
+
+impl ValidityOracle for Predicate {
+    fn is_valid(&self, _node: NodeId, v: Value) -> bool {
+        (self.0)(v)
+    }
+}
+
+impl fmt::Display for Predicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "predicate")
+    }
+}
+
+/// Validity views of the nodes, every value is valid by default
+#[derive(Clone)]
+pub struct ValidityConfig {
+    pub oracle: Arc<dyn ValidityOracle>,
+}
+
+impl Default for ValidityConfig {
+    fn default() -> Self {
+        ValidityConfig {
+            oracle: Arc::new(AnyValue),
+        }
+    }
+}
+
+impl fmt::Debug for ValidityConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.oracle)
+    }
+}
+
+/// Oracles are told apart by their description
+impl PartialEq for ValidityConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.oracle.to_string() == other.oracle.to_string()
+    }
+}
@@ -0,0 +1,120 @@
+use crate::adversary::LinkAttack;
+use crate::config::NetworkConfig;
+use crate::network::{Network, Value};
+use crate::node::NodeId;
+use crate::trace::Trace;
+use std::collections::HashMap;
+use std::fmt;
+
+/// State of a deterministic run after its first deliveries
+/// Restoring it re-executes these deliveries, which leads to the same state
+/// since the run only depends on its schedule and random draws
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub(crate) trace: Trace,
+}
+
+impl Snapshot {
+    /// Snapshot of a recorded run after `deliveries` deliveries
+    pub fn take(trace: &Trace, deliveries: usize) -> Self {
+        Snapshot {
+            trace: trace.prefix(deliveries),
+        }
+    }
+
+    /// Number of deliveries before the snapshot
+    pub fn deliveries(&self) -> usize {
+        self.trace.len()
+    }
+}
+
+/// Continuation of a run from a snapshot
+#[derive(Clone, Debug)]
+pub struct Branch {
+    pub name: String,
+    // Seed of the scheduler choosing the deliveries after the snapshot
+    pub seed: u64,
+    // Link conditions after the snapshot
+    pub config: NetworkConfig,
+    // Links attacked after the snapshot
+    pub attacks: Vec<(NodeId, NodeId, LinkAttack)>,
+}
+
+impl Branch {
+    pub fn new(name: &str, seed: u64) -> Self {
+        Branch {
+            name: name.to_string(),
+            seed,
+            config: NetworkConfig::default(),
+            attacks: vec![],
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct BranchOutcome {
+    pub name: String,
+    pub success: bool,
+    pub results: HashMap<NodeId, Value>,
+    // Deliveries of the whole run, snapshot included
+    pub deliveries: usize,
+}
+
+/// Outcomes of all the branches forked from one snapshot
+#[derive(Clone, Debug)]
+pub struct WhatIfReport {
+    pub snapshot: usize,
+    pub branches: Vec<BranchOutcome>,
+}
+
+/// Fork every branch from `snapshot`
+/// `run` has to start the protocol of the recorded run with the same inputs
+pub fn fork<F>(snapshot: &Snapshot, branches: &[Branch], run: F) -> WhatIfReport
+where
+    F: Fn(&mut Network) -> (bool, HashMap<NodeId, Value>),
+{
+    let branches = branches
+        .iter()
+        .map(|branch| {
+            let mut network = Network::branch(snapshot, branch);
+            let (success, results) = run(&mut network);
+            let deliveries = network.trace().len();
+            network.close();
+            BranchOutcome {
+                name: branch.name.clone(),
+                success,
+                results,
+                deliveries,
+            }
+        })
+        .collect();
+    WhatIfReport {
+        snapshot: snapshot.deliveries(),
+        branches,
+    }
+}
+
+impl fmt::Display for WhatIfReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "Branches from the snapshot after {} deliveries",
+            self.snapshot
+        )?;
+        writeln!(
+            f,
+            "{:<20} {:>8} {:>11}  results",
+            "branch", "success", "deliveries"
+        )?;
+        for branch in self.branches.iter() {
+            let mut results: Vec<_> = branch.results.iter().collect();
+            results.sort();
+            writeln!(
+                f,
+                "{:<20} {:>8} {:>11}  {:?}",
+                branch.name, branch.success, branch.deliveries, results
+            )?;
+        }
+        Ok(())
+    }
+}